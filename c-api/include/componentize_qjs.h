@@ -0,0 +1,209 @@
+/*
+ * C bindings for componentize-qjs.
+ *
+ * Link against the `componentize_qjs_c` shared or static library built from
+ * the `c-api` crate.
+ */
+#ifndef COMPONENTIZE_QJS_H
+#define COMPONENTIZE_QJS_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* The call succeeded. */
+#define COMPONENTIZE_QJS_OK 0
+/* An argument was null, not UTF-8, or otherwise invalid. */
+#define COMPONENTIZE_QJS_INVALID_ARG 1
+/* Componentization failed. */
+#define COMPONENTIZE_QJS_FAILED 2
+
+/*
+ * Optional settings, mirroring the CLI flags.
+ *
+ * Zero-initialize the struct and set `size` to `sizeof(componentize_qjs_opts)`.
+ * Pointers may be null to leave a setting unset. New fields are only ever
+ * appended: the library leaves the fields past `size` at their defaults, so
+ * callers built against an older header keep working.
+ */
+typedef struct componentize_qjs_opts {
+  size_t size;
+  /* World name to use from the WIT. */
+  const char *world;
+  /* Path to the JavaScript entry file, used as the base for resolving imports. */
+  const char *js_path;
+  /* Root directory exposed during Wizer for resolving JavaScript imports. */
+  const char *module_root;
+  /* Stub all WASI imports with traps. */
+  bool stub_wasi;
+  /* Disable automatic garbage collection in the QuickJS runtime. */
+  bool disable_gc;
+  /* Use the built-in runtime optimized for smaller generated components. */
+  bool opt_size;
+  /* Use the built-in non-async runtime. */
+  bool sync;
+  /* Feature flags exposed to JavaScript through `__FEATURES__`. */
+  const char *const *features;
+  size_t features_len;
+  /* Record sync import calls and their results to this guest file at runtime. */
+  const char *record_imports;
+  /* Satisfy sync import calls from a capture recorded with `record_imports`. */
+  const char *replay_imports;
+  /* Custom QuickJS runtime Wasm bytes. */
+  const uint8_t *runtime;
+  size_t runtime_len;
+  /* Cache compiled components here so JavaScript-only rebuilds skip compilation. */
+  const char *cache_dir;
+  /* Reject lists and strings lowered from JavaScript longer than this (0 disables). */
+  uint32_t max_list_length;
+  /* Replace `Math.random`: "wasi" for wasi:random entropy or "seed:<N>" for a
+   * reproducible sequence. */
+  const char *math_random;
+  /* Exports to call once during initialization so the snapshot starts warm,
+   * each as "<export>=<JSON array of arguments>". */
+  const char *const *prime;
+  size_t prime_len;
+  /* Freeze the intrinsics, polyfills and WIT import objects once the
+   * JavaScript module is evaluated. */
+  bool harden;
+  /* Check values lowered from JavaScript against their WIT type, throwing a
+   * TypeError naming the mismatch. */
+  bool strict_types;
+  /* Pass top-level result values as { tag, val } objects instead of
+   * return/throw. */
+  bool tagged_results;
+  /* With tagged_results, still return the ok payload of imports and throw
+   * the err one. */
+  bool throwing_imports;
+  /* Wrap out-of-range integers lowered from JavaScript instead of throwing a
+   * RangeError. */
+  bool wrap_integers;
+  /* Reject NaN and infinite floats lowered from JavaScript with a
+   * RangeError. */
+  bool finite_floats;
+  /* Warn on stderr when a number lowered as an f32 loses precision. */
+  bool warn_f32_rounding;
+  /* Log every value lifted into or lowered out of JavaScript to stderr. */
+  bool trace_values;
+  /* Cap the QuickJS heap at this many bytes (0 disables). */
+  uint32_t memory_limit;
+  /* Print heap usage to stderr after every this many export calls (0
+   * disables). */
+  uint32_t memory_report;
+  /* Trap when a call into JavaScript runs more than about this many bytecode
+   * instructions (0 disables). */
+  uint64_t max_instructions;
+  /* Fail the build when Wizer initialization runs longer than this many
+   * milliseconds (0 disables). */
+  uint64_t init_timeout;
+  /* Drop the source text of JavaScript functions from the component. */
+  bool strip_source;
+  /* Run a full garbage collection cycle after every this many export calls
+   * (0 disables). */
+  uint32_t gc_interval;
+  /* Start an automatic garbage collection cycle once this many bytes were
+   * allocated since the last one (0 keeps the QuickJS default). */
+  uint32_t gc_threshold;
+  /* Evaluate the JavaScript module on the first export call instead of during
+   * Wizer. */
+  bool lazy_eval;
+  /* Which shortened names import a versioned WIT interface: "none",
+   * "strip-patch" or "strip-all" (NULL for "strip-all"). */
+  const char *version_aliasing;
+  /* Extra import specifiers for WIT interfaces, each as
+   * "<specifier>=<interface>". */
+  const char *const *import_aliases;
+  size_t import_aliases_len;
+  /* Names of WIT functions in JavaScript: "camel", "kebab" or "both" (NULL for
+   * "camel"). */
+  const char *naming;
+  /* JavaScript names for individual WIT functions, each as
+   * "<function>=<js-name>". */
+  const char *const *renames;
+  size_t renames_len;
+  /* Use the debug runtime, with assertions and function names, to diagnose a
+   * failing component. */
+  bool debug_runtime;
+  /* Run the snapshotted component's core modules through wasm-opt: "speed" or
+   * "size" (NULL disables). */
+  const char *optimize;
+  /* Write a single core module importing WASI directly instead of a
+   * component; needs a non-async runtime. */
+  bool core_module;
+  /* Name, version and description to record in the component's metadata
+   * (NULL for none). */
+  const char *metadata_name;
+  const char *metadata_version;
+  const char *metadata_description;
+  /* Leave the producers and metadata sections out of the component. */
+  bool no_metadata;
+  /* Build byte-identical components from identical inputs, pinning the clocks
+   * and randomness initialization sees. */
+  bool reproducible;
+  /* Fail on initialization warnings, such as exports whose parameter count
+   * differs from their WIT signature, which are otherwise dropped. */
+  bool deny_warnings;
+  /* Compile the entry module to QuickJS bytecode and evaluate that during
+   * initialization instead of its source. */
+  bool bytecode;
+  /* What to do with exports declaring more or fewer parameters than their WIT
+   * signature: "ignore", "warn" or "error" (NULL for "warn"). */
+  const char *arity_mismatch;
+  /* Globals installed on top of plain QuickJS (NULL for the default polyfills
+   * and every global generated from WASI imports). */
+  const struct componentize_qjs_env_shims *env_shims;
+} componentize_qjs_opts;
+
+/*
+ * Globals installed on top of plain QuickJS.
+ *
+ * Versioned like `componentize_qjs_opts`: zero-initialize the struct and set
+ * `size` to `sizeof(componentize_qjs_env_shims)`.
+ */
+typedef struct componentize_qjs_env_shims {
+  size_t size;
+  /* Bundled polyfills to install in addition to the defaults. */
+  const char *const *polyfills;
+  size_t polyfills_len;
+  /* Install only the polyfills listed in `polyfills`. */
+  bool no_default_polyfills;
+  /* Leave out the globals generated from the world's WASI imports, such as
+   * `fetch`, `env` and `fs`. */
+  bool no_wasi_globals;
+} componentize_qjs_env_shims;
+
+/* Component bytes owned by the library; release with componentize_qjs_buf_free. */
+typedef struct componentize_qjs_buf {
+  uint8_t *data;
+  size_t len;
+} componentize_qjs_buf;
+
+/*
+ * Convert JavaScript source code into a WebAssembly component.
+ *
+ * `opts` may be null for defaults. On success writes the component to `out`
+ * and returns COMPONENTIZE_QJS_OK; otherwise `out` is left untouched and
+ * componentize_qjs_last_error() describes the failure.
+ */
+int32_t componentize_qjs(const char *wit_path, const char *js_source,
+                         const componentize_qjs_opts *opts,
+                         componentize_qjs_buf *out);
+
+/* Release a buffer filled in by componentize_qjs and reset it to empty. */
+void componentize_qjs_buf_free(componentize_qjs_buf *buf);
+
+/*
+ * Message describing the last failure on the calling thread, or null. Valid
+ * until the next componentize_qjs call on the same thread.
+ */
+const char *componentize_qjs_last_error(void);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* COMPONENTIZE_QJS_H */
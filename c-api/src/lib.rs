@@ -0,0 +1,622 @@
+//! C ABI for driving componentize-qjs from non-Rust build tools.
+//!
+//! The declarations live in `include/componentize_qjs.h`. Every entry point
+//! catches panics, reports failures through a status code, and leaves a
+//! message retrievable with `componentize_qjs_last_error` on the calling
+//! thread.
+
+use std::cell::RefCell;
+use std::ffi::{CStr, CString, c_char};
+use std::mem::{MaybeUninit, offset_of};
+use std::panic::{AssertUnwindSafe, catch_unwind};
+use std::path::Path;
+use std::time::Duration;
+
+use anyhow::{Context, Result, anyhow, bail};
+use componentize_qjs::{
+    ArityMismatch, ComponentizeOpts, EnvShims, ImportAlias, ImportLog, MathRandom, Metadata,
+    Naming, OptLevel, PrimeCall, Rename, Runtime, VersionAliasing,
+};
+
+/// The call succeeded.
+pub const COMPONENTIZE_QJS_OK: i32 = 0;
+/// An argument was null, not UTF-8, or otherwise invalid.
+pub const COMPONENTIZE_QJS_INVALID_ARG: i32 = 1;
+/// Componentization failed.
+pub const COMPONENTIZE_QJS_FAILED: i32 = 2;
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// Optional settings, mirroring the CLI flags.
+///
+/// Pointers may be null to leave a setting unset. New fields are only ever
+/// appended, and `size` lets later versions tell which ones the caller knows
+/// about: fields past `size` are left at zero, their default.
+#[allow(non_camel_case_types)]
+#[repr(C)]
+pub struct componentize_qjs_opts {
+    /// `sizeof(componentize_qjs_opts)` as seen by the caller
+    pub size: usize,
+    /// World name to use from the WIT
+    pub world: *const c_char,
+    /// Path to the JavaScript entry file, used as the base for resolving imports
+    pub js_path: *const c_char,
+    /// Root directory exposed during Wizer for resolving JavaScript imports
+    pub module_root: *const c_char,
+    /// Stub all WASI imports with traps
+    pub stub_wasi: bool,
+    /// Disable automatic garbage collection in the QuickJS runtime
+    pub disable_gc: bool,
+    /// Use the built-in runtime optimized for smaller generated components
+    pub opt_size: bool,
+    /// Use the built-in non-async runtime
+    pub sync: bool,
+    /// Feature flags exposed to JavaScript through `__FEATURES__`
+    pub features: *const *const c_char,
+    /// Number of entries in `features`
+    pub features_len: usize,
+    /// Record sync import calls and their results to this guest file at runtime
+    pub record_imports: *const c_char,
+    /// Satisfy sync import calls from a capture recorded with `record_imports`
+    pub replay_imports: *const c_char,
+    /// Custom QuickJS runtime Wasm bytes
+    pub runtime: *const u8,
+    /// Number of bytes in `runtime`
+    pub runtime_len: usize,
+    /// Cache compiled components here so JavaScript-only rebuilds skip compilation
+    pub cache_dir: *const c_char,
+    /// Reject lists and strings lowered from JavaScript that are longer than
+    /// this (0 disables)
+    pub max_list_length: u32,
+    /// Replace `Math.random`: `wasi` for wasi:random entropy or `seed:<N>` for
+    /// a reproducible sequence
+    pub math_random: *const c_char,
+    /// Exports to call once during initialization so the snapshot starts
+    /// warm, each as `<export>=<JSON array of arguments>`
+    pub prime: *const *const c_char,
+    /// Number of entries in `prime`
+    pub prime_len: usize,
+    /// Freeze the intrinsics, polyfills and WIT import objects once the
+    /// JavaScript module is evaluated
+    pub harden: bool,
+    /// Check values lowered from JavaScript against their WIT type, throwing a
+    /// `TypeError` naming the mismatch
+    pub strict_types: bool,
+    /// Pass top-level `result` values as `{ tag, val }` objects instead of
+    /// return/throw
+    pub tagged_results: bool,
+    /// With `tagged_results`, still return the `ok` payload of imports and
+    /// throw the `err` one
+    pub throwing_imports: bool,
+    /// Wrap out-of-range integers lowered from JavaScript instead of throwing a
+    /// `RangeError`
+    pub wrap_integers: bool,
+    /// Reject NaN and infinite floats lowered from JavaScript with a
+    /// `RangeError`
+    pub finite_floats: bool,
+    /// Warn on stderr when a number lowered as an `f32` loses precision
+    pub warn_f32_rounding: bool,
+    /// Log every value lifted into or lowered out of JavaScript to stderr
+    pub trace_values: bool,
+    /// Cap the QuickJS heap at this many bytes (0 disables)
+    pub memory_limit: u32,
+    /// Print heap usage to stderr after every this many export calls (0
+    /// disables)
+    pub memory_report: u32,
+    /// Trap when a call into JavaScript runs more than about this many bytecode
+    /// instructions (0 disables)
+    pub max_instructions: u64,
+    /// Fail the build when Wizer initialization runs longer than this many
+    /// milliseconds (0 disables)
+    pub init_timeout: u64,
+    /// Drop the source text of JavaScript functions from the component
+    pub strip_source: bool,
+    /// Run a full garbage collection cycle after every this many export calls
+    /// (0 disables)
+    pub gc_interval: u32,
+    /// Start an automatic garbage collection cycle once this many bytes were
+    /// allocated since the last one (0 keeps the QuickJS default)
+    pub gc_threshold: u32,
+    /// Evaluate the JavaScript module on the first export call instead of
+    /// during Wizer
+    pub lazy_eval: bool,
+    /// Which shortened names import a versioned WIT interface: `none`,
+    /// `strip-patch` or `strip-all` (NULL for `strip-all`)
+    pub version_aliasing: *const c_char,
+    /// Extra import specifiers for WIT interfaces, each as
+    /// `<specifier>=<interface>`
+    pub import_aliases: *const *const c_char,
+    /// Number of entries in `import_aliases`
+    pub import_aliases_len: usize,
+    /// Names of WIT functions in JavaScript: `camel`, `kebab` or `both` (NULL
+    /// for `camel`)
+    pub naming: *const c_char,
+    /// JavaScript names for individual WIT functions, each as
+    /// `<function>=<js-name>`
+    pub renames: *const *const c_char,
+    /// Number of entries in `renames`
+    pub renames_len: usize,
+    /// Use the debug runtime, with assertions and function names, to diagnose
+    /// a failing component
+    pub debug_runtime: bool,
+    /// Run the snapshotted component's core modules through wasm-opt: `speed`
+    /// or `size` (NULL disables)
+    pub optimize: *const c_char,
+    /// Write a single core module importing WASI directly instead of a
+    /// component; needs a non-async runtime
+    pub core_module: bool,
+    /// Name to record in the component's metadata (NULL for none)
+    pub metadata_name: *const c_char,
+    /// Version to record in the component's metadata (NULL for none)
+    pub metadata_version: *const c_char,
+    /// Description to record in the component's metadata (NULL for none)
+    pub metadata_description: *const c_char,
+    /// Leave the producers and metadata sections out of the component
+    pub no_metadata: bool,
+    /// Build byte-identical components from identical inputs, pinning the
+    /// clocks and randomness initialization sees
+    pub reproducible: bool,
+    /// Fail on initialization warnings, such as exports whose parameter count
+    /// differs from their WIT signature, which are otherwise dropped
+    pub deny_warnings: bool,
+    /// Compile the entry module to QuickJS bytecode and evaluate that during
+    /// initialization instead of its source
+    pub bytecode: bool,
+    /// What to do with exports declaring more or fewer parameters than their
+    /// WIT signature: `ignore`, `warn` or `error` (NULL for `warn`)
+    pub arity_mismatch: *const c_char,
+    /// Globals installed on top of plain QuickJS (NULL for the default
+    /// polyfills and every global generated from WASI imports)
+    pub env_shims: *const componentize_qjs_env_shims,
+}
+
+/// Globals installed on top of plain QuickJS, versioned by `size` like
+/// `componentize_qjs_opts`.
+#[allow(non_camel_case_types)]
+#[repr(C)]
+pub struct componentize_qjs_env_shims {
+    /// `sizeof(componentize_qjs_env_shims)` as seen by the caller
+    pub size: usize,
+    /// Bundled polyfills to install in addition to the defaults
+    pub polyfills: *const *const c_char,
+    /// Number of entries in `polyfills`
+    pub polyfills_len: usize,
+    /// Install only the polyfills listed in `polyfills`
+    pub no_default_polyfills: bool,
+    /// Leave out the globals generated from the world's WASI imports, such as
+    /// `fetch`, `env` and `fs`
+    pub no_wasi_globals: bool,
+}
+
+/// Size of `componentize_qjs_opts` in the first version of the header, which
+/// ended with `runtime_len`.
+const MIN_OPTS_SIZE: usize = offset_of!(componentize_qjs_opts, runtime_len) + size_of::<usize>();
+
+/// Size of `componentize_qjs_env_shims` in the first version of the header,
+/// which ended with `no_wasi_globals`.
+const MIN_ENV_SHIMS_SIZE: usize =
+    offset_of!(componentize_qjs_env_shims, no_wasi_globals) + size_of::<bool>();
+
+/// Copy the fields of a size-versioned struct the caller knows about, leaving
+/// the ones a newer header appended zeroed, which is their default.
+///
+/// # Safety
+///
+/// `ptr` must be non-null and point to a `T` that starts with its `size` as a
+/// `usize` and whose first `size` bytes are initialized. Only those bytes are
+/// read, so an older caller's smaller struct is fine. Zero must be a valid
+/// value for every field of `T`.
+unsafe fn read_sized<T>(ptr: *const T, min_size: usize, name: &str) -> Result<T> {
+    let size = unsafe { *ptr.cast::<usize>() };
+    if size < min_size {
+        bail!("`{name}.size` is {size}, expected at least {min_size}");
+    }
+    let len = size.min(size_of::<T>());
+    let mut known = MaybeUninit::<T>::zeroed();
+    // SAFETY: the caller initialized `len` bytes, and zero is a valid value
+    // for every field.
+    unsafe {
+        std::ptr::copy_nonoverlapping(ptr.cast::<u8>(), known.as_mut_ptr().cast::<u8>(), len);
+        Ok(known.assume_init())
+    }
+}
+
+/// Owned output buffer, released with `componentize_qjs_buf_free`.
+#[allow(non_camel_case_types)]
+#[repr(C)]
+pub struct componentize_qjs_buf {
+    pub data: *mut u8,
+    pub len: usize,
+}
+
+/// Read an optional UTF-8 C string argument.
+///
+/// # Safety
+///
+/// `ptr` must be null or point to a nul-terminated string.
+unsafe fn opt_str<'a>(ptr: *const c_char, name: &str) -> Result<Option<&'a str>> {
+    if ptr.is_null() {
+        return Ok(None);
+    }
+    unsafe { CStr::from_ptr(ptr) }
+        .to_str()
+        .map(Some)
+        .with_context(|| format!("`{name}` is not valid UTF-8"))
+}
+
+/// Read a required UTF-8 C string argument.
+///
+/// # Safety
+///
+/// `ptr` must be null or point to a nul-terminated string.
+unsafe fn req_str<'a>(ptr: *const c_char, name: &str) -> Result<&'a str> {
+    unsafe { opt_str(ptr, name) }?.ok_or_else(|| anyhow!("`{name}` must not be null"))
+}
+
+/// Read an array of `len` UTF-8 C strings.
+///
+/// # Safety
+///
+/// `ptr` must be null or point to `len` nul-terminated strings.
+unsafe fn str_list(ptr: *const *const c_char, len: usize, name: &str) -> Result<Vec<String>> {
+    if len == 0 {
+        return Ok(Vec::new());
+    }
+    if ptr.is_null() {
+        bail!("`{name}` must not be null when its length is non-zero");
+    }
+    unsafe { std::slice::from_raw_parts(ptr, len) }
+        .iter()
+        .map(|&item| unsafe { req_str(item, &format!("{name}[]")) }.map(str::to_string))
+        .collect()
+}
+
+/// Settings decoded from `componentize_qjs_opts`.
+#[derive(Default)]
+struct Settings<'a> {
+    world: Option<&'a str>,
+    js_path: Option<&'a str>,
+    module_root: Option<&'a str>,
+    stub_wasi: bool,
+    disable_gc: bool,
+    strip_source: bool,
+    bytecode: bool,
+    lazy_eval: bool,
+    gc_interval: Option<u32>,
+    gc_threshold: Option<u32>,
+    max_list_length: Option<u32>,
+    memory_limit: Option<u32>,
+    memory_report: Option<u32>,
+    max_instructions: Option<u64>,
+    init_timeout: Option<Duration>,
+    strict_types: bool,
+    tagged_results: bool,
+    throwing_imports: bool,
+    wrap_integers: bool,
+    finite_floats: bool,
+    warn_f32_rounding: bool,
+    trace_values: bool,
+    features: Vec<String>,
+    polyfills: Vec<String>,
+    no_default_polyfills: bool,
+    no_wasi_globals: bool,
+    deny_warnings: bool,
+    arity_mismatch: ArityMismatch,
+    math_random: Option<MathRandom>,
+    prime: Vec<PrimeCall>,
+    harden: bool,
+    version_aliasing: VersionAliasing,
+    import_aliases: Vec<ImportAlias>,
+    naming: Naming,
+    renames: Vec<Rename>,
+    import_log: Option<ImportLog<'a>>,
+    optimize: Option<OptLevel>,
+    core_module: bool,
+    metadata: Metadata<'a>,
+    reproducible: bool,
+    cache_dir: Option<&'a str>,
+    runtime: Runtime<'a>,
+    debug_runtime: bool,
+}
+
+impl<'a> Settings<'a> {
+    /// # Safety
+    ///
+    /// `opts` must be null or point to a valid `componentize_qjs_opts` whose
+    /// first `size` bytes are initialized.
+    unsafe fn read(opts: *const componentize_qjs_opts) -> Result<Self> {
+        if opts.is_null() {
+            return Ok(Self::default());
+        }
+        let opts = &unsafe { read_sized(opts, MIN_OPTS_SIZE, "opts") }?;
+        let env_shims = if opts.env_shims.is_null() {
+            None
+        } else {
+            Some(unsafe { read_sized(opts.env_shims, MIN_ENV_SHIMS_SIZE, "opts.env_shims") }?)
+        };
+
+        let features = unsafe { str_list(opts.features, opts.features_len, "opts.features") }?;
+        let polyfills = match &env_shims {
+            Some(shims) => unsafe {
+                str_list(
+                    shims.polyfills,
+                    shims.polyfills_len,
+                    "opts.env_shims.polyfills",
+                )
+            }?,
+            None => Vec::new(),
+        };
+        let prime = unsafe { str_list(opts.prime, opts.prime_len, "opts.prime") }?
+            .iter()
+            .map(|call| call.parse())
+            .collect::<Result<_>>()?;
+        let import_aliases = unsafe {
+            str_list(
+                opts.import_aliases,
+                opts.import_aliases_len,
+                "opts.import_aliases",
+            )
+        }?
+        .iter()
+        .map(|alias| alias.parse())
+        .collect::<Result<_>>()?;
+        let renames = unsafe { str_list(opts.renames, opts.renames_len, "opts.renames") }?
+            .iter()
+            .map(|rename| rename.parse())
+            .collect::<Result<_>>()?;
+
+        let record = unsafe { opt_str(opts.record_imports, "opts.record_imports") }?;
+        let replay = unsafe { opt_str(opts.replay_imports, "opts.replay_imports") }?;
+        let import_log = match (record, replay) {
+            (Some(_), Some(_)) => bail!("`record_imports` and `replay_imports` are exclusive"),
+            (Some(path), None) => Some(ImportLog::Record(path)),
+            (None, Some(path)) => Some(ImportLog::Replay(path)),
+            (None, None) => None,
+        };
+
+        let custom = match (opts.runtime.is_null(), opts.runtime_len) {
+            (true, 0) => None,
+            (true, _) => bail!("`opts.runtime` must not be null when `runtime_len` is non-zero"),
+            (false, len) => Some(unsafe { std::slice::from_raw_parts(opts.runtime, len) }),
+        };
+        let runtime = match custom {
+            Some(_) if opts.opt_size || opts.sync => {
+                bail!("`opt_size` and `sync` cannot be combined with a custom runtime")
+            }
+            Some(wasm) => Runtime::Custom(wasm),
+            None => match (opts.sync, opts.opt_size) {
+                (true, true) => Runtime::OptSizeSync,
+                (true, false) => Runtime::DefaultSync,
+                (false, true) => Runtime::OptSize,
+                (false, false) => Runtime::default(),
+            },
+        };
+
+        Ok(Self {
+            world: unsafe { opt_str(opts.world, "opts.world") }?,
+            js_path: unsafe { opt_str(opts.js_path, "opts.js_path") }?,
+            module_root: unsafe { opt_str(opts.module_root, "opts.module_root") }?,
+            stub_wasi: opts.stub_wasi,
+            disable_gc: opts.disable_gc,
+            strip_source: opts.strip_source,
+            bytecode: opts.bytecode,
+            lazy_eval: opts.lazy_eval,
+            gc_interval: (opts.gc_interval != 0).then_some(opts.gc_interval),
+            gc_threshold: (opts.gc_threshold != 0).then_some(opts.gc_threshold),
+            max_list_length: (opts.max_list_length != 0).then_some(opts.max_list_length),
+            memory_limit: (opts.memory_limit != 0).then_some(opts.memory_limit),
+            memory_report: (opts.memory_report != 0).then_some(opts.memory_report),
+            max_instructions: (opts.max_instructions != 0).then_some(opts.max_instructions),
+            init_timeout: (opts.init_timeout != 0)
+                .then(|| Duration::from_millis(opts.init_timeout)),
+            strict_types: opts.strict_types,
+            tagged_results: opts.tagged_results,
+            throwing_imports: opts.throwing_imports,
+            wrap_integers: opts.wrap_integers,
+            finite_floats: opts.finite_floats,
+            warn_f32_rounding: opts.warn_f32_rounding,
+            trace_values: opts.trace_values,
+            features,
+            polyfills,
+            no_default_polyfills: env_shims
+                .as_ref()
+                .is_some_and(|shims| shims.no_default_polyfills),
+            no_wasi_globals: env_shims
+                .as_ref()
+                .is_some_and(|shims| shims.no_wasi_globals),
+            deny_warnings: opts.deny_warnings,
+            arity_mismatch: unsafe { opt_str(opts.arity_mismatch, "opts.arity_mismatch") }?
+                .map(str::parse)
+                .transpose()?
+                .unwrap_or_default(),
+            math_random: unsafe { opt_str(opts.math_random, "opts.math_random") }?
+                .map(str::parse)
+                .transpose()?,
+            prime,
+            harden: opts.harden,
+            version_aliasing: unsafe { opt_str(opts.version_aliasing, "opts.version_aliasing") }?
+                .map(str::parse)
+                .transpose()?
+                .unwrap_or_default(),
+            import_aliases,
+            naming: unsafe { opt_str(opts.naming, "opts.naming") }?
+                .map(str::parse)
+                .transpose()?
+                .unwrap_or_default(),
+            renames,
+            import_log,
+            optimize: unsafe { opt_str(opts.optimize, "opts.optimize") }?
+                .map(str::parse)
+                .transpose()?,
+            core_module: opts.core_module,
+            metadata: Metadata {
+                suppress: opts.no_metadata,
+                name: unsafe { opt_str(opts.metadata_name, "opts.metadata_name") }?,
+                version: unsafe { opt_str(opts.metadata_version, "opts.metadata_version") }?,
+                description: unsafe {
+                    opt_str(opts.metadata_description, "opts.metadata_description")
+                }?,
+            },
+            reproducible: opts.reproducible,
+            cache_dir: unsafe { opt_str(opts.cache_dir, "opts.cache_dir") }?,
+            runtime,
+            debug_runtime: opts.debug_runtime,
+        })
+    }
+}
+
+fn run(wit_path: &str, js_source: &str, settings: &Settings<'_>) -> Result<Vec<u8>> {
+    let opts = ComponentizeOpts {
+        wit_path: Path::new(wit_path),
+        wit_source: None,
+        wit_package: None,
+        js_source,
+        js_path: settings.js_path.map(Path::new),
+        module_root: settings.module_root.map(Path::new),
+        init_env: &[],
+        init_args: &[],
+        init_dirs: &[],
+        modules: &[],
+        entry_module: None,
+        preludes: &[],
+        world_name: settings.world,
+        stub_wasi: settings.stub_wasi,
+        disable_gc: settings.disable_gc,
+        strip_source: settings.strip_source,
+        bytecode: settings.bytecode,
+        lazy_eval: settings.lazy_eval,
+        gc_interval: settings.gc_interval,
+        gc_threshold: settings.gc_threshold,
+        max_list_length: settings.max_list_length,
+        memory_limit: settings.memory_limit,
+        memory_report: settings.memory_report,
+        max_instructions: settings.max_instructions,
+        init_timeout: settings.init_timeout,
+        strict_types: settings.strict_types,
+        tagged_results: settings.tagged_results,
+        throwing_imports: settings.throwing_imports,
+        wrap_integers: settings.wrap_integers,
+        finite_floats: settings.finite_floats,
+        warn_f32_rounding: settings.warn_f32_rounding,
+        trace_values: settings.trace_values,
+        features: &settings.features,
+        env_shims: EnvShims {
+            polyfills: &settings.polyfills,
+            no_default_polyfills: settings.no_default_polyfills,
+            no_wasi_globals: settings.no_wasi_globals,
+        },
+        deny_warnings: settings.deny_warnings,
+        arity_mismatch: settings.arity_mismatch,
+        math_random: settings.math_random,
+        prime: &settings.prime,
+        version_aliasing: settings.version_aliasing,
+        import_aliases: &settings.import_aliases,
+        naming: settings.naming,
+        renames: &settings.renames,
+        harden: settings.harden,
+        import_log: settings.import_log,
+        optimize: settings.optimize,
+        core_module: settings.core_module,
+        custom_sections: &[],
+        metadata: settings.metadata,
+        reproducible: settings.reproducible,
+        cache_dir: settings.cache_dir.map(Path::new),
+        runtime: settings.runtime,
+        debug_runtime: settings.debug_runtime,
+    };
+
+    tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?
+        .block_on(componentize_qjs::componentize(&opts))
+}
+
+fn set_last_error(err: impl std::fmt::Display) {
+    let msg = CString::new(err.to_string().replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(msg));
+}
+
+/// Convert JavaScript source code into a WebAssembly component.
+///
+/// On success writes the component to `out` and returns
+/// `COMPONENTIZE_QJS_OK`; `out` is left untouched otherwise.
+///
+/// # Safety
+///
+/// `wit_path` and `js_source` must be nul-terminated strings, `opts` must be
+/// null or valid (see `componentize_qjs_opts`), and `out` must be writable.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn componentize_qjs(
+    wit_path: *const c_char,
+    js_source: *const c_char,
+    opts: *const componentize_qjs_opts,
+    out: *mut componentize_qjs_buf,
+) -> i32 {
+    let args = || -> Result<_> {
+        if out.is_null() {
+            bail!("`out` must not be null");
+        }
+        let wit_path = unsafe { req_str(wit_path, "wit_path") }?;
+        let js_source = unsafe { req_str(js_source, "js_source") }?;
+        let settings = unsafe { Settings::read(opts) }?;
+        Ok((wit_path, js_source, settings))
+    };
+    let (wit_path, js_source, settings) = match args() {
+        Ok(args) => args,
+        Err(err) => {
+            set_last_error(format!("{err:#}"));
+            return COMPONENTIZE_QJS_INVALID_ARG;
+        }
+    };
+
+    let result = catch_unwind(AssertUnwindSafe(|| run(wit_path, js_source, &settings)))
+        .unwrap_or_else(|_| Err(anyhow!("componentize-qjs panicked")));
+
+    match result {
+        Ok(component) => {
+            let component = component.into_boxed_slice();
+            let len = component.len();
+            let data = Box::into_raw(component).cast::<u8>();
+            unsafe { out.write(componentize_qjs_buf { data, len }) };
+            COMPONENTIZE_QJS_OK
+        }
+        Err(err) => {
+            set_last_error(format!("{err:#}"));
+            COMPONENTIZE_QJS_FAILED
+        }
+    }
+}
+
+/// Release a buffer filled in by `componentize_qjs`.
+///
+/// # Safety
+///
+/// `buf` must be null or hold a buffer returned by this library that hasn't
+/// been freed yet. The buffer is reset to empty afterwards.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn componentize_qjs_buf_free(buf: *mut componentize_qjs_buf) {
+    let Some(buf) = (unsafe { buf.as_mut() }) else {
+        return;
+    };
+    if !buf.data.is_null() {
+        let slice = std::ptr::slice_from_raw_parts_mut(buf.data, buf.len);
+        drop(unsafe { Box::from_raw(slice) });
+    }
+    buf.data = std::ptr::null_mut();
+    buf.len = 0;
+}
+
+/// Message describing the last failure on the calling thread, or null.
+///
+/// The pointer stays valid until the next `componentize_qjs` call on the
+/// same thread.
+#[unsafe(no_mangle)]
+pub extern "C" fn componentize_qjs_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| {
+        last.borrow()
+            .as_ref()
+            .map_or(std::ptr::null(), |msg| msg.as_ptr())
+    })
+}
@@ -0,0 +1,126 @@
+//! Tests calling the C entry points as a C caller would.
+
+use std::ffi::{CStr, CString};
+use std::mem::offset_of;
+use std::ptr;
+
+use componentize_qjs_c::{
+    COMPONENTIZE_QJS_FAILED, COMPONENTIZE_QJS_INVALID_ARG, COMPONENTIZE_QJS_OK, componentize_qjs,
+    componentize_qjs_buf, componentize_qjs_buf_free, componentize_qjs_env_shims,
+    componentize_qjs_last_error, componentize_qjs_opts,
+};
+use tempfile::TempDir;
+
+const WIT: &str = r#"
+package test:ffi;
+world ffi {
+    export add: func(a: u32, b: u32) -> u32;
+}
+"#;
+
+const JS: &str = "export function add(a, b) { return a + b; }";
+
+/// Size of the options struct in the first version of the header.
+const FIRST_OPTS_SIZE: usize = offset_of!(componentize_qjs_opts, runtime_len) + size_of::<usize>();
+
+fn opts(size: usize) -> componentize_qjs_opts {
+    // SAFETY: zero is the default of every field, as the header documents.
+    let mut opts: componentize_qjs_opts = unsafe { std::mem::zeroed() };
+    opts.size = size;
+    opts
+}
+
+/// Run `componentize_qjs`, returning the status and the component or the last
+/// error.
+fn call(opts: Option<&componentize_qjs_opts>) -> (i32, Result<Vec<u8>, String>) {
+    let dir = TempDir::new().unwrap();
+    let wit_path = dir.path().join("ffi.wit");
+    std::fs::write(&wit_path, WIT).unwrap();
+    let wit_path = CString::new(wit_path.to_str().unwrap()).unwrap();
+    let js = CString::new(JS).unwrap();
+
+    let mut out = componentize_qjs_buf {
+        data: ptr::null_mut(),
+        len: 0,
+    };
+    let opts = opts.map_or(ptr::null(), ptr::from_ref);
+    let status = unsafe { componentize_qjs(wit_path.as_ptr(), js.as_ptr(), opts, &mut out) };
+    if status != COMPONENTIZE_QJS_OK {
+        let error = unsafe { CStr::from_ptr(componentize_qjs_last_error()) };
+        return (status, Err(error.to_string_lossy().into_owned()));
+    }
+
+    let component = unsafe { std::slice::from_raw_parts(out.data, out.len) }.to_vec();
+    unsafe { componentize_qjs_buf_free(&mut out) };
+    assert!(out.data.is_null() && out.len == 0);
+    (status, Ok(component))
+}
+
+fn assert_component(bytes: &[u8]) {
+    assert_eq!(&bytes[..8], b"\0asm\x0d\0\x01\0", "expected a component");
+}
+
+#[test]
+fn test_componentize_without_opts() {
+    let (status, component) = call(None);
+    assert_eq!(status, COMPONENTIZE_QJS_OK);
+    assert_component(&component.unwrap());
+}
+
+#[test]
+fn test_componentize_with_current_opts() {
+    let opts = opts(size_of::<componentize_qjs_opts>());
+    let (status, component) = call(Some(&opts));
+    assert_eq!(status, COMPONENTIZE_QJS_OK);
+    assert_component(&component.unwrap());
+}
+
+#[test]
+fn test_componentize_ignores_fields_past_size() {
+    // A caller built against the first header doesn't know about the later
+    // fields, so whatever follows its struct must not be read.
+    let mut opts = opts(FIRST_OPTS_SIZE);
+    let bogus = CString::new("bogus").unwrap();
+    opts.optimize = bogus.as_ptr();
+    opts.naming = bogus.as_ptr();
+    opts.memory_limit = 1;
+    let (status, component) = call(Some(&opts));
+    assert_eq!(status, COMPONENTIZE_QJS_OK);
+    assert_component(&component.unwrap());
+
+    opts.size = size_of::<componentize_qjs_opts>();
+    let (status, error) = call(Some(&opts));
+    assert_eq!(status, COMPONENTIZE_QJS_INVALID_ARG);
+    assert!(error.unwrap_err().contains("bogus"));
+}
+
+#[test]
+fn test_componentize_rejects_opts_older_than_the_first_header() {
+    let opts = opts(FIRST_OPTS_SIZE - 1);
+    let (status, error) = call(Some(&opts));
+    assert_eq!(status, COMPONENTIZE_QJS_INVALID_ARG);
+    assert!(error.unwrap_err().contains("`opts.size`"));
+}
+
+#[test]
+fn test_componentize_reads_env_shims() {
+    let bogus = CString::new("bogus").unwrap();
+    let polyfills = [bogus.as_ptr()];
+    // SAFETY: zero is the default of every field, as the header documents.
+    let mut env_shims: componentize_qjs_env_shims = unsafe { std::mem::zeroed() };
+    env_shims.size = size_of::<componentize_qjs_env_shims>();
+    env_shims.polyfills = polyfills.as_ptr();
+    env_shims.polyfills_len = polyfills.len();
+
+    let mut opts = opts(size_of::<componentize_qjs_opts>());
+    opts.env_shims = &env_shims;
+    let (status, error) = call(Some(&opts));
+    assert_eq!(status, COMPONENTIZE_QJS_FAILED);
+    assert!(error.unwrap_err().contains("unknown polyfill `bogus`"));
+
+    env_shims.size = 0;
+    opts.env_shims = &env_shims;
+    let (status, error) = call(Some(&opts));
+    assert_eq!(status, COMPONENTIZE_QJS_INVALID_ARG);
+    assert!(error.unwrap_err().contains("`opts.env_shims.size`"));
+}
@@ -12,33 +12,51 @@ const WASI_SKD_DL_URL: &str = "https://github.com/WebAssembly/wasi-sdk/releases/
 const BINARYEN_VERSION: &str = "130";
 const BINARYEN_DL_URL: &str = "https://github.com/WebAssembly/binaryen/releases/download";
 const RUNTIME_AUDITABLE_ENV: &str = "COMPONENTIZE_QJS_RUNTIME_AUDITABLE";
+const RUNTIME_PROFILE_ENV: &str = "COMPONENTIZE_QJS_RUNTIME_PROFILE";
 const MAX_ARCHIVE_BYTES: u64 = 1_000_000_000;
 
 #[derive(Clone, Copy)]
 struct RuntimeBuild {
     optimize_size: bool,
     async_support: bool,
+    debug: bool,
 }
 
 impl RuntimeBuild {
     const DEFAULT: Self = Self {
         optimize_size: false,
         async_support: true,
+        debug: false,
     };
     const OPT_SIZE: Self = Self {
         optimize_size: true,
         async_support: true,
+        debug: false,
     };
     const DEFAULT_SYNC: Self = Self {
         optimize_size: false,
         async_support: false,
+        debug: false,
     };
     const OPT_SIZE_SYNC: Self = Self {
         optimize_size: true,
         async_support: false,
+        debug: false,
     };
 
+    /// The debug runtime, with the async support of the default one.
+    fn debug(async_support: bool) -> Self {
+        Self {
+            optimize_size: false,
+            async_support,
+            debug: true,
+        }
+    }
+
     fn name(self) -> &'static str {
+        if self.debug {
+            return "debug";
+        }
         match (self.optimize_size, self.async_support) {
             (false, true) => "default",
             (true, true) => "opt-size",
@@ -48,6 +66,9 @@ impl RuntimeBuild {
     }
 
     fn filename(self) -> &'static str {
+        if self.debug {
+            return "runtime-debug.wasm";
+        }
         match (self.optimize_size, self.async_support) {
             (false, true) => "runtime.wasm",
             (true, true) => "runtime-opt-size.wasm",
@@ -77,6 +98,15 @@ impl CargoProfile {
         Self { name, release }
     }
 
+    /// The unoptimized dev profile, which keeps debug assertions, overflow
+    /// checks and the name section whatever the outer profile is.
+    fn debug() -> Self {
+        Self {
+            name: "debug".to_string(),
+            release: false,
+        }
+    }
+
     fn runtime_rustflags(&self, optimize_size: bool) -> String {
         let flags = "-Clink-arg=-shared -Clink-arg=-Wl,--no-entry -Clink-arg=-Wl,--allow-undefined";
         match (self.release, optimize_size) {
@@ -109,12 +139,14 @@ impl CargoProfile {
 /// when the `component-model-async` feature is disabled, in which case the
 /// generated `DEFAULT_RUNTIME_WASM` / `OPT_SIZE_RUNTIME_WASM` constants alias
 /// the non-async variants (preserving the historical non-async-by-default
-/// behavior).
+/// behavior). The debug variant is only present with the `debug-runtime`
+/// feature.
 struct RuntimePaths {
     default_sync: PathBuf,
     opt_size_sync: PathBuf,
     default_async: Option<PathBuf>,
     opt_size_async: Option<PathBuf>,
+    debug: Option<PathBuf>,
 }
 
 fn main() -> Result<()> {
@@ -131,17 +163,20 @@ fn main() -> Result<()> {
     println!("cargo:rerun-if-changed=prebuilt/runtime-opt-size.wasm");
     println!("cargo:rerun-if-changed=prebuilt/runtime-sync.wasm");
     println!("cargo:rerun-if-changed=prebuilt/runtime-opt-size-sync.wasm");
+    println!("cargo:rerun-if-changed=prebuilt/runtime-debug.wasm");
     println!("cargo:rerun-if-env-changed={RUNTIME_AUDITABLE_ENV}");
+    println!("cargo:rerun-if-env-changed={RUNTIME_PROFILE_ENV}");
 
     let out_dir = PathBuf::from(env::var("OUT_DIR").context("OUT_DIR not set")?);
     let async_on = component_model_async_enabled();
+    let debug_on = debug_runtime_enabled();
 
     // Check for pre-built runtimes (used when installing from crates.io)
     let prebuilt_dir = manifest_dir.join("prebuilt");
     let prebuilt_sync = prebuilt_dir.join("runtime-sync.wasm");
 
     if prebuilt_sync.exists() {
-        return emit_from_prebuilt(&prebuilt_dir, async_on, &out_dir);
+        return emit_from_prebuilt(&prebuilt_dir, async_on, debug_on, &out_dir);
     }
 
     // Check that runtime source is available (won't be when installed from crates.io
@@ -168,6 +203,12 @@ fn main() -> Result<()> {
     } else {
         (None, None)
     };
+    let debug = if debug_on {
+        let build = RuntimeBuild::debug(async_on);
+        Some(build_runtime(&out_dir, build, &CargoProfile::debug())?)
+    } else {
+        None
+    };
 
     emit_runtime_wasms(
         &RuntimePaths {
@@ -175,13 +216,19 @@ fn main() -> Result<()> {
             opt_size_sync,
             default_async,
             opt_size_async,
+            debug,
         },
         &out_dir,
     )
 }
 
 /// Emit runtime constants from the pre-built runtimes packaged with the crate.
-fn emit_from_prebuilt(prebuilt_dir: &Path, async_on: bool, out_dir: &Path) -> Result<()> {
+fn emit_from_prebuilt(
+    prebuilt_dir: &Path,
+    async_on: bool,
+    debug_on: bool,
+    out_dir: &Path,
+) -> Result<()> {
     let default_sync = prebuilt_dir.join("runtime-sync.wasm");
     let opt_size_sync = prebuilt_dir.join("runtime-opt-size-sync.wasm");
 
@@ -211,6 +258,21 @@ fn emit_from_prebuilt(prebuilt_dir: &Path, async_on: bool, out_dir: &Path) -> Re
         (None, None)
     };
 
+    // The debug runtime isn't packaged; it needs the runtime source.
+    let debug = if debug_on {
+        let debug = prebuilt_dir.join("runtime-debug.wasm");
+        if !debug.exists() {
+            bail!(
+                "The debug-runtime feature is enabled but there is no pre-built debug runtime \
+                 at {}. Build componentize-qjs from source to use the debug runtime.",
+                debug.display(),
+            );
+        }
+        Some(debug)
+    } else {
+        None
+    };
+
     eprintln!("Using prebuilt runtimes from: {}", prebuilt_dir.display());
 
     emit_runtime_wasms(
@@ -219,6 +281,7 @@ fn emit_from_prebuilt(prebuilt_dir: &Path, async_on: bool, out_dir: &Path) -> Re
             opt_size_sync,
             default_async,
             opt_size_async,
+            debug,
         },
         out_dir,
     )
@@ -246,6 +309,13 @@ fn emit_runtime_wasms(paths: &RuntimePaths, out_dir: &Path) -> Result<()> {
         }
     }
 
+    match &paths.debug {
+        Some(path) => output.push_str(&format!(
+            "const DEBUG_RUNTIME_WASM: Option<&[u8]> = Some(include_bytes!({path:?}));\n"
+        )),
+        None => output.push_str("const DEBUG_RUNTIME_WASM: Option<&[u8]> = None;\n"),
+    }
+
     fs::write(out_dir.join("output.rs"), output).context("Failed to write output.rs")?;
 
     Ok(())
@@ -285,6 +355,8 @@ fn build_runtime(out_dir: &Path, build: RuntimeBuild, profile: &CargoProfile) ->
         .arg(target)
         .arg("--package=componentize-qjs-runtime")
         .arg("--no-default-features")
+        .arg("--features")
+        .arg("js-env")
         .env("CARGO_TARGET_DIR", &target_dir)
         .env(format!("CARGO_TARGET_{upcase}_RUSTFLAGS"), rustflags)
         .env(format!("CARGO_TARGET_{upcase}_LINKER"), &clang)
@@ -304,6 +376,10 @@ fn build_runtime(out_dir: &Path, build: RuntimeBuild, profile: &CargoProfile) ->
         cargo.arg("--features").arg("component-model-async");
     }
 
+    if profile_enabled() {
+        cargo.arg("--features").arg("profile");
+    }
+
     eprintln!("Building {} runtime: {cargo:?}", build.name());
     let status = cargo.status().context("Failed to run cargo build")?;
     if !status.success() {
@@ -360,6 +436,14 @@ fn component_model_async_enabled() -> bool {
     env::var_os("CARGO_FEATURE_COMPONENT_MODEL_ASYNC").is_some()
 }
 
+fn debug_runtime_enabled() -> bool {
+    env::var_os("CARGO_FEATURE_DEBUG_RUNTIME").is_some()
+}
+
+fn profile_enabled() -> bool {
+    env::var_os("CARGO_FEATURE_PROFILE").is_some() || env::var_os(RUNTIME_PROFILE_ENV).is_some()
+}
+
 fn get_wasi_sdk(out_dir: &Path) -> Result<PathBuf> {
     // Check environment first
     if let Ok(path) = env::var("WASI_SDK_PATH") {
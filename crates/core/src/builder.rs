@@ -0,0 +1,197 @@
+//! A fluent alternative to filling in every field of [`ComponentizeOpts`].
+
+use std::path::Path;
+use std::time::Duration;
+
+use anyhow::Result;
+
+use crate::{
+    ArityMismatch, BuildPlan, ComponentizeOpts, EnvShims, ImportAlias, ImportLog, InitDir,
+    MathRandom, Metadata, Naming, OptLevel, PrimeCall, Rename, Runtime, VersionAliasing,
+};
+
+/// Builds [`ComponentizeOpts`] from the WIT and JavaScript entry source, with
+/// every other option left at its default until set.
+///
+/// ```no_run
+/// # async fn build() -> anyhow::Result<Vec<u8>> {
+/// use std::path::Path;
+///
+/// use componentize_qjs::ComponentizeBuilder;
+///
+/// ComponentizeBuilder::new(Path::new("app.wit"), "export const run = () => {};")
+///     .world_name("app")
+///     .stub_wasi(true)
+///     .memory_limit(16 << 20)
+///     .componentize()
+///     .await
+/// # }
+/// ```
+pub struct ComponentizeBuilder<'a> {
+    opts: ComponentizeOpts<'a>,
+}
+
+/// Setters storing their argument as is.
+macro_rules! setters {
+    ($($field:ident: $ty:ty),* $(,)?) => {
+        $(
+            #[doc = concat!("Set [`ComponentizeOpts::", stringify!($field), "`].")]
+            pub fn $field(mut self, $field: $ty) -> Self {
+                self.opts.$field = $field;
+                self
+            }
+        )*
+    };
+}
+
+/// Setters for optional fields, storing their argument as `Some`.
+macro_rules! optional_setters {
+    ($($field:ident: $ty:ty),* $(,)?) => {
+        $(
+            #[doc = concat!("Set [`ComponentizeOpts::", stringify!($field), "`].")]
+            pub fn $field(mut self, $field: $ty) -> Self {
+                self.opts.$field = Some($field);
+                self
+            }
+        )*
+    };
+}
+
+impl<'a> ComponentizeBuilder<'a> {
+    /// Start from the WIT at `wit_path` and the entry module `js_source`, which
+    /// is empty when [`entry_module`](Self::entry_module) names the entry.
+    pub fn new(wit_path: &'a Path, js_source: &'a str) -> Self {
+        Self {
+            opts: ComponentizeOpts {
+                wit_path,
+                wit_source: None,
+                wit_package: None,
+                js_source,
+                js_path: None,
+                module_root: None,
+                init_env: &[],
+                init_args: &[],
+                init_dirs: &[],
+                modules: &[],
+                entry_module: None,
+                preludes: &[],
+                world_name: None,
+                stub_wasi: false,
+                disable_gc: false,
+                strip_source: false,
+                bytecode: false,
+                lazy_eval: false,
+                gc_interval: None,
+                gc_threshold: None,
+                max_list_length: None,
+                memory_limit: None,
+                memory_report: None,
+                max_instructions: None,
+                init_timeout: None,
+                strict_types: false,
+                tagged_results: false,
+                throwing_imports: false,
+                wrap_integers: false,
+                finite_floats: false,
+                warn_f32_rounding: false,
+                trace_values: false,
+                features: &[],
+                env_shims: EnvShims::default(),
+                deny_warnings: false,
+                arity_mismatch: ArityMismatch::default(),
+                math_random: None,
+                prime: &[],
+                harden: false,
+                version_aliasing: VersionAliasing::default(),
+                import_aliases: &[],
+                naming: Naming::default(),
+                renames: &[],
+                import_log: None,
+                optimize: None,
+                core_module: false,
+                custom_sections: &[],
+                metadata: Metadata::default(),
+                reproducible: false,
+                cache_dir: None,
+                runtime: Runtime::default(),
+                debug_runtime: false,
+            },
+        }
+    }
+
+    setters! {
+        init_env: &'a [(String, String)],
+        init_args: &'a [String],
+        init_dirs: &'a [InitDir],
+        modules: &'a [(String, String)],
+        preludes: &'a [(String, String)],
+        stub_wasi: bool,
+        disable_gc: bool,
+        strip_source: bool,
+        bytecode: bool,
+        lazy_eval: bool,
+        strict_types: bool,
+        tagged_results: bool,
+        throwing_imports: bool,
+        wrap_integers: bool,
+        finite_floats: bool,
+        warn_f32_rounding: bool,
+        trace_values: bool,
+        features: &'a [String],
+        env_shims: EnvShims<'a>,
+        deny_warnings: bool,
+        arity_mismatch: ArityMismatch,
+        prime: &'a [PrimeCall],
+        harden: bool,
+        version_aliasing: VersionAliasing,
+        import_aliases: &'a [ImportAlias],
+        naming: Naming,
+        renames: &'a [Rename],
+        core_module: bool,
+        custom_sections: &'a [(String, Vec<u8>)],
+        metadata: Metadata<'a>,
+        reproducible: bool,
+        runtime: Runtime<'a>,
+        debug_runtime: bool,
+    }
+
+    optional_setters! {
+        wit_source: &'a str,
+        wit_package: &'a [u8],
+        js_path: &'a Path,
+        module_root: &'a Path,
+        world_name: &'a str,
+        entry_module: &'a str,
+        gc_interval: u32,
+        gc_threshold: u32,
+        max_list_length: u32,
+        memory_limit: u32,
+        memory_report: u32,
+        max_instructions: u64,
+        init_timeout: Duration,
+        math_random: MathRandom,
+        import_log: ImportLog<'a>,
+        optimize: OptLevel,
+        cache_dir: &'a Path,
+    }
+
+    /// The options as configured so far.
+    pub fn opts(&self) -> &ComponentizeOpts<'a> {
+        &self.opts
+    }
+
+    /// Finish, returning the options.
+    pub fn build(self) -> ComponentizeOpts<'a> {
+        self.opts
+    }
+
+    /// Convert the JavaScript into a component, as [`componentize`](crate::componentize).
+    pub async fn componentize(&self) -> Result<Vec<u8>> {
+        crate::componentize(&self.opts).await
+    }
+
+    /// Plan the build without running it, as [`plan`](crate::plan).
+    pub fn plan(&self) -> Result<BuildPlan> {
+        crate::plan(&self.opts)
+    }
+}
@@ -0,0 +1,130 @@
+//! On-disk cache of compiled, Wizer-instrumented components.
+//!
+//! Compiling the instrumented component is the bulk of a build, and its input
+//! only depends on the runtime and the WIT world, not on the JavaScript. When
+//! only the JavaScript changes, a cached compilation is reused and just the
+//! Wizer initialization runs again.
+//!
+//! Entries are named by the SHA-256 digest of their input and start with that
+//! digest and the digest of the compiled artifact, which are both checked
+//! before the artifact is loaded. That catches corrupted, truncated and
+//! misplaced entries, but not ones crafted on purpose: loading an artifact
+//! runs its code, so the cache directory must be trusted.
+
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use anyhow::{Context, Result};
+use sha2::{Digest, Sha256};
+use wasmtime::Engine;
+use wasmtime::component::Component;
+
+/// Length of a SHA-256 digest in bytes.
+const DIGEST_LEN: usize = 32;
+
+/// Compile `instrumented`, reusing a previous compilation from `cache_dir`.
+///
+/// Unreadable, corrupted or incompatible cache entries are recompiled and
+/// replaced; failing to write the cache is not an error.
+pub(crate) fn compile(
+    engine: &Engine,
+    instrumented: &[u8],
+    cache_dir: Option<&Path>,
+) -> Result<Component> {
+    let Some(cache_dir) = cache_dir else {
+        return Component::new(engine, instrumented);
+    };
+    let key = input_digest(engine, instrumented);
+    let path = entry_path_for(cache_dir, &key);
+
+    if let Some(component) = load(engine, &path, &key) {
+        return Ok(component);
+    }
+
+    let precompiled = engine
+        .precompile_component(instrumented)
+        .context("failed to compile instrumented component")?;
+    let mut entry = Vec::with_capacity(2 * DIGEST_LEN + precompiled.len());
+    entry.extend_from_slice(&key);
+    entry.extend_from_slice(&Sha256::digest(&precompiled));
+    entry.extend_from_slice(&precompiled);
+    let _ = store(cache_dir, &path, &entry);
+
+    // SAFETY: produced by `precompile_component` on this engine just above.
+    unsafe { Component::deserialize(engine, &precompiled) }
+}
+
+/// Path of the cache entry holding the compilation of `instrumented`.
+pub(crate) fn entry_path(engine: &Engine, instrumented: &[u8], cache_dir: &Path) -> PathBuf {
+    entry_path_for(cache_dir, &input_digest(engine, instrumented))
+}
+
+fn entry_path_for(cache_dir: &Path, key: &[u8; DIGEST_LEN]) -> PathBuf {
+    let name: String = key.iter().map(|byte| format!("{byte:02x}")).collect();
+    cache_dir.join(format!("{name}.cwasm"))
+}
+
+/// Digest of everything a compilation depends on: this crate's version, the
+/// engine's compilation settings and the instrumented component.
+fn input_digest(engine: &Engine, instrumented: &[u8]) -> [u8; DIGEST_LEN] {
+    let mut hasher = DigestHasher(Sha256::new());
+    hasher.0.update(env!("CARGO_PKG_VERSION"));
+    engine.precompile_compatibility_hash().hash(&mut hasher);
+    hasher.0.update(instrumented);
+    hasher.0.finalize().into()
+}
+
+/// Load the entry at `path` if it holds the compilation for `key` and its
+/// artifact is intact.
+fn load(engine: &Engine, path: &Path, key: &[u8; DIGEST_LEN]) -> Option<Component> {
+    let entry = std::fs::read(path).ok()?;
+    let (input, rest) = entry.split_first_chunk::<DIGEST_LEN>()?;
+    let (digest, artifact) = rest.split_first_chunk::<DIGEST_LEN>()?;
+    if input != key || digest[..] != Sha256::digest(artifact)[..] {
+        return None;
+    }
+    // SAFETY: the entry was written by `compile` for this input, unless the
+    // cache directory was tampered with, which callers must rule out.
+    // wasmtime also rejects artifacts from incompatible engines.
+    unsafe { Component::deserialize(engine, artifact) }.ok()
+}
+
+/// SHA-256 digest of what `value` feeds its `Hash` implementation, which
+/// length-prefixes slices, so tuples of byte strings can't collide.
+pub(crate) fn digest(value: impl Hash) -> [u8; DIGEST_LEN] {
+    let mut hasher = DigestHasher(Sha256::new());
+    value.hash(&mut hasher);
+    hasher.0.finalize().into()
+}
+
+/// Feeds what a `Hash` implementation writes into a SHA-256 digest.
+struct DigestHasher(Sha256);
+
+impl Hasher for DigestHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        self.0.update(bytes);
+    }
+
+    fn finish(&self) -> u64 {
+        unreachable!("only the SHA-256 digest is used")
+    }
+}
+
+/// Write an entry atomically so concurrent builds never see a partial file.
+///
+/// The temporary file is named after the process and a per-call counter, so
+/// builds in other processes and other threads of this one never share it.
+fn store(cache_dir: &Path, path: &Path, bytes: &[u8]) -> std::io::Result<()> {
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+    std::fs::create_dir_all(cache_dir)?;
+    let tmp = path.with_extension(format!(
+        "{}-{}.tmp",
+        std::process::id(),
+        NEXT.fetch_add(1, Ordering::Relaxed)
+    ));
+    std::fs::write(&tmp, bytes)?;
+    std::fs::rename(&tmp, path).inspect_err(|_| {
+        let _ = std::fs::remove_file(&tmp);
+    })
+}
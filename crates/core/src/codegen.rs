@@ -1,33 +1,87 @@
 //! Code generation for the JS shim that bridges WIT types to the quickjs runtime.
 
-use indexmap::IndexSet;
+use heck::{ToLowerCamelCase, ToUpperCamelCase};
+use indexmap::{IndexMap, IndexSet};
 use std::collections::{HashMap, HashSet};
-use wit_parser::{Resolve, Type, TypeDefKind, TypeId, TypeOwner, WorldId, WorldItem};
+use wit_parser::{
+    FunctionKind, Handle, InterfaceId, Resolve, Type, TypeDefKind, TypeId, TypeOwner, WorldId,
+    WorldItem,
+};
 
-/// Generate a JS shim from WIT metadata that sets up stream/future factories.
-pub fn generate_shim(resolve: &Resolve, world_id: WorldId) -> String {
-    let mut ctx = EmitContext::new(resolve, world_id);
+/// Generate a JS shim from WIT metadata that sets up stream/future factories
+/// and, with `wasi_globals` and for worlds importing the matching WASI
+/// interfaces, `fetch()`, `env`, `config`, `fs`, `net`, `keyvalue`, `console`
+/// logging, timers and a `Date` backed by the wall clock.
+pub fn generate_shim(resolve: &Resolve, world_id: WorldId, wasi_globals: bool) -> String {
+    let mut ctx = EmitContext::new(resolve, world_id, wasi_globals);
     ctx.emit();
     ctx.output()
 }
 
+/// The globals and adapters [`generate_shim`] emits for `world_id` that call
+/// imports returning a top-level `result`.
+///
+/// They expect those imports to return the `ok` payload and throw the `err`
+/// one, so they don't work in builds passing import results as `{ tag, val }`
+/// objects.
+pub(crate) fn result_consumers(
+    resolve: &Resolve,
+    world_id: WorldId,
+    wasi_globals: bool,
+) -> Vec<&'static str> {
+    let ctx = EmitContext::new(resolve, world_id, wasi_globals);
+    let mut consumers = Vec::new();
+    if wasi_globals {
+        if ctx.fetch_imports().is_some() {
+            consumers.push("fetch");
+        }
+        if ctx
+            .wasi_import("config", &["store", "runtime-config"])
+            .is_some()
+        {
+            consumers.push("config");
+        }
+        if ctx.wasi_import("filesystem", &["preopens"]).is_some() {
+            consumers.push("fs");
+        }
+        if ctx.tcp_imports().is_some() {
+            consumers.push("net");
+        }
+        if ctx.wasi_import("keyvalue", &["store"]).is_some() {
+            consumers.push("keyvalue");
+        }
+    }
+    if ctx.incoming_handler_export().is_some() {
+        consumers.push("wasi:http/incoming-handler adapter");
+    }
+    consumers
+}
+
 struct EmitContext<'a> {
     resolve: &'a Resolve,
     world_id: WorldId,
+    /// Whether to emit the environment globals backed by WASI imports
+    wasi_globals: bool,
+    /// Whether `UTF8_JS` was emitted already
+    utf8: bool,
     lines: Vec<String>,
     streams: IndexSet<Option<Type>>,
     futures: IndexSet<Option<Type>>,
+    constants: IndexSet<TypeId>,
     visited_types: HashSet<TypeId>,
 }
 
 impl<'a> EmitContext<'a> {
-    fn new(resolve: &'a Resolve, world_id: WorldId) -> Self {
+    fn new(resolve: &'a Resolve, world_id: WorldId, wasi_globals: bool) -> Self {
         Self {
             resolve,
             world_id,
+            wasi_globals,
+            utf8: false,
             lines: Vec::new(),
             streams: IndexSet::new(),
             futures: IndexSet::new(),
+            constants: IndexSet::new(),
             visited_types: HashSet::new(),
         }
     }
@@ -61,17 +115,407 @@ impl<'a> EmitContext<'a> {
             }
         }
 
+        for item in world.imports.values().chain(world.exports.values()) {
+            match item {
+                WorldItem::Interface { id, .. } => {
+                    for ty in self.resolve.interfaces[*id].types.values() {
+                        self.collect_constant(*ty);
+                    }
+                }
+                WorldItem::Type { id, .. } => self.collect_constant(*id),
+                WorldItem::Function(_) => {}
+            }
+        }
+
+        let fetch = self.fetch_imports().filter(|_| self.wasi_globals);
+        let serve = self.incoming_handler_export();
+        if let Some((handler, _)) = &fetch {
+            self.line(&format!("import outgoingHandler from \"{handler}\";"));
+        }
+        let http_types = fetch.as_ref().or(serve.as_ref()).map(|(_, types)| types);
+        if let Some(types) = http_types {
+            self.line(&format!("import http from \"{types}\";"));
+        }
+
         self.line("const wit = globalThis.wit = {};");
 
         let streams: Vec<_> = self.streams.iter().copied().collect();
         if !streams.is_empty() {
             self.emit_constructor("Stream", "__cqjs.makeStream", &streams);
+            self.emit_stream_from();
         }
 
         let futures: Vec<_> = self.futures.iter().copied().collect();
         if !futures.is_empty() {
             self.emit_constructor("Future", "__cqjs.makeFuture", &futures);
         }
+
+        self.emit_constants();
+        self.emit_datetime();
+        if self.wasi_globals {
+            self.emit_wasi_globals();
+        }
+        self.emit_json();
+        self.emit_world_info();
+
+        if http_types.is_some() {
+            self.emit_utf8();
+            self.lines.extend(HTTP_JS.lines().map(str::to_string));
+        }
+        if fetch.is_some() {
+            self.lines.extend(FETCH_JS.lines().map(str::to_string));
+        }
+        if let Some((handler, _)) = &serve {
+            self.lines.extend(SERVE_JS.lines().map(str::to_string));
+            self.line(&format!(
+                "export const adapters = {{ \"{handler}\": serveHttp }};"
+            ));
+        }
+    }
+
+    /// The globals backed by the world's WASI imports: `Date` and timers on
+    /// the clocks, `env`, `config`, `fs`, `net`, `keyvalue` and `console` on
+    /// logging. `fetch()` is emitted by `emit` itself.
+    fn emit_wasi_globals(&mut self) {
+        let wall_clock = self.wasi_import("clocks", &["wall-clock", "system-clock"]);
+        if let Some(module) = wall_clock.and_then(|id| self.resolve.id_of(id)) {
+            self.emit_wall_clock(&module);
+        }
+
+        let monotonic_clock = self
+            .wasi_import("clocks", &["monotonic-clock"])
+            .filter(|id| {
+                let functions = &self.resolve.interfaces[*id].functions;
+                functions.contains_key("subscribe-instant")
+            });
+        if let Some(module) = monotonic_clock.and_then(|id| self.resolve.id_of(id)) {
+            self.emit_timers(&module);
+        }
+
+        let environment = self.wasi_import("cli", &["environment"]);
+        if let Some(module) = environment.and_then(|id| self.resolve.id_of(id)) {
+            self.emit_env(&module);
+        }
+
+        let config = self.wasi_import("config", &["store", "runtime-config"]);
+        if let Some(module) = config.and_then(|id| self.resolve.id_of(id)) {
+            self.emit_config(&module);
+        }
+
+        let preopens = self.wasi_import("filesystem", &["preopens"]);
+        if let Some(module) = preopens.and_then(|id| self.resolve.id_of(id)) {
+            self.emit_fs(&module);
+        }
+
+        if let Some(modules) = self.tcp_imports() {
+            self.emit_net(&modules);
+        }
+
+        let store = self.wasi_import("keyvalue", &["store"]);
+        if let Some(module) = store.and_then(|id| self.resolve.id_of(id)) {
+            self.emit_keyvalue(&module);
+        }
+
+        let logging = self.wasi_import("logging", &["logging"]);
+        if let Some(module) = logging.and_then(|id| self.resolve.id_of(id)) {
+            self.emit_logging(&module);
+        }
+    }
+
+    /// `wit.world`: a description of the world's functions with their
+    /// signatures, of its interfaces with their versions, and of its enum,
+    /// flags and variant types, for glue code that adapts at runtime.
+    ///
+    /// Freezes `wit` afterwards, so the whole object is read-only.
+    fn emit_world_info(&mut self) {
+        let resolve = self.resolve;
+        let world = &resolve.worlds[self.world_id];
+        let mut functions = [Vec::new(), Vec::new()];
+        let mut interfaces = Vec::new();
+        let mut world_types = Vec::new();
+        let items = [("import", &world.imports), ("export", &world.exports)];
+        for (funcs, (direction, items)) in functions.iter_mut().zip(items) {
+            for (key, item) in items {
+                match item {
+                    WorldItem::Function(func) => funcs.push(function_info(resolve, None, func)),
+                    WorldItem::Interface { id, .. } => {
+                        let name = resolve.name_world_key(key);
+                        let iface = &resolve.interfaces[*id];
+                        funcs.extend(
+                            iface
+                                .functions
+                                .values()
+                                .map(|func| function_info(resolve, Some(name.as_str()), func)),
+                        );
+                        let info = interface_info(resolve, *id, direction);
+                        interfaces.push(format!("\"{name}\": {info}"));
+                    }
+                    WorldItem::Type { id, .. } => world_types.push(*id),
+                }
+            }
+        }
+        let [imports, exports] = functions;
+
+        self.line("{");
+        self.line("  const freeze = (value) => {");
+        self.line("    if (value !== null && typeof value === \"object\") {");
+        self.line("      Object.values(value).forEach(freeze);");
+        self.line("      Object.freeze(value);");
+        self.line("    }");
+        self.line("    return value;");
+        self.line("  };");
+        self.line("  wit.world = freeze({");
+        self.line(&format!("    name: \"{}\",", world.name));
+        self.line(&format!("    imports: [{}],", imports.join(", ")));
+        self.line(&format!("    exports: [{}],", exports.join(", ")));
+        self.line(&format!("    interfaces: {{ {} }},", interfaces.join(", ")));
+        self.line(&format!(
+            "    types: {},",
+            types_info(resolve, world_types.into_iter())
+        ));
+        self.line("  });");
+        self.line("  Object.freeze(wit);");
+        self.line("}");
+    }
+
+    /// `wit.toJSON(value, type)` and `wit.fromJSON(text, type)`, converting by
+    /// a table of the named types the world refers to.
+    fn emit_json(&mut self) {
+        let resolve = self.resolve;
+        let world = &resolve.worlds[self.world_id];
+        let mut named = IndexSet::new();
+        let collect_function = |func: &wit_parser::Function, named: &mut IndexSet<TypeId>| {
+            for ty in func.params.iter().map(|p| &p.ty).chain(&func.result) {
+                collect_named(resolve, ty, named);
+            }
+        };
+        for item in world.imports.values().chain(world.exports.values()) {
+            match item {
+                WorldItem::Function(func) => collect_function(func, &mut named),
+                WorldItem::Interface { id, .. } => {
+                    let iface = &resolve.interfaces[*id];
+                    for id in iface.types.values() {
+                        collect_named(resolve, &Type::Id(*id), &mut named);
+                    }
+                    for func in iface.functions.values() {
+                        collect_function(func, &mut named);
+                    }
+                }
+                WorldItem::Type { id, .. } => collect_named(resolve, &Type::Id(*id), &mut named),
+            }
+        }
+
+        self.line("{");
+        self.line("  const TYPES = {");
+        for id in named {
+            let name = qualified_type_name(resolve, &Type::Id(id));
+            let schema = json_schema(resolve, id);
+            self.line(&format!("    \"{name}\": {schema},"));
+        }
+        self.line("  };");
+        self.lines.extend(JSON_JS.lines().map(str::to_string));
+        self.line("}");
+    }
+
+    /// Module specifiers of `wasi:http/outgoing-handler` and its sibling
+    /// `types` interface, if the world imports the 0.2 outgoing handler.
+    fn fetch_imports(&self) -> Option<(String, String)> {
+        let handler = self.wasi_import("http", &["outgoing-handler"])?;
+        let package = &self.resolve.packages[self.resolve.interfaces[handler].package?];
+        let types = *package.interfaces.get("types")?;
+        Some((self.resolve.id_of(handler)?, self.resolve.id_of(types)?))
+    }
+
+    /// Qualified names of the exported `wasi:http/incoming-handler` and its
+    /// sibling `types` interface, if the world exports the 0.2 incoming
+    /// handler.
+    fn incoming_handler_export(&self) -> Option<(String, String)> {
+        let world = &self.resolve.worlds[self.world_id];
+        let handler = self.wasi_interface(world.exports.values(), "http", &["incoming-handler"])?;
+        let package = &self.resolve.packages[self.resolve.interfaces[handler].package?];
+        let types = *package.interfaces.get("types")?;
+        Some((self.resolve.id_of(handler)?, self.resolve.id_of(types)?))
+    }
+
+    /// Module specifiers of `wasi:sockets/instance-network`, `ip-name-lookup`
+    /// and `tcp-create-socket`, if the world imports all three.
+    fn tcp_imports(&self) -> Option<[String; 3]> {
+        let names = ["instance-network", "ip-name-lookup", "tcp-create-socket"];
+        let [network, lookup, tcp] = names.map(|name| {
+            self.wasi_import("sockets", &[name])
+                .and_then(|id| self.resolve.id_of(id))
+        });
+        Some([network?, lookup?, tcp?])
+    }
+
+    /// The world's import of the `wasi:<package>` interface with one of
+    /// `names`, at whatever version.
+    fn wasi_import(&self, package: &str, names: &[&str]) -> Option<InterfaceId> {
+        let world = &self.resolve.worlds[self.world_id];
+        self.wasi_interface(world.imports.values(), package, names)
+    }
+
+    /// The interface among `items` that is `wasi:<package>` with one of
+    /// `names`.
+    fn wasi_interface<'b>(
+        &self,
+        mut items: impl Iterator<Item = &'b WorldItem>,
+        package: &str,
+        names: &[&str],
+    ) -> Option<InterfaceId> {
+        items.find_map(|item| {
+            let WorldItem::Interface { id, .. } = item else {
+                return None;
+            };
+            let iface = &self.resolve.interfaces[*id];
+            let package_name = &self.resolve.packages[iface.package?].name;
+            let matches = package_name.namespace == "wasi"
+                && package_name.name == package
+                && names.contains(&iface.name.as_deref()?);
+            matches.then_some(*id)
+        })
+    }
+
+    /// `wit.toDate(datetime)` and `wit.fromDate(date?)`, converting between
+    /// `Date`s and the `{ seconds, nanoseconds }` records that WASI clocks and
+    /// filesystem timestamps use. Dates only keep milliseconds.
+    fn emit_datetime(&mut self) {
+        self.line("wit.toDate = ({ seconds, nanoseconds }) =>");
+        self.line("  new Date(Number(seconds) * 1000 + Math.floor(nanoseconds / 1e6));");
+        self.line("wit.fromDate = (date = new Date()) => {");
+        self.line("  const ms = Number(date);");
+        self.line(
+            "  if (!Number.isFinite(ms)) throw new RangeError(\"wit.fromDate: invalid date\");",
+        );
+        self.line("  const seconds = Math.floor(ms / 1000);");
+        self.line("  return { seconds, nanoseconds: (ms - seconds * 1000) * 1e6 };");
+        self.line("};");
+    }
+
+    /// Route `Date.now()` and `new Date()` through the world's wall clock
+    /// (`wall-clock` in 0.2, `system-clock` in 0.3) rather than libc's time
+    /// source, so they follow whatever clock the host provides for the world.
+    fn emit_wall_clock(&mut self, module: &str) {
+        self.line(&format!("import wallClock from \"{module}\";"));
+        self.line("{");
+        self.line("  const NativeDate = Date;");
+        self.line("  const now = () => {");
+        self.line("    const { seconds, nanoseconds } = wallClock.now();");
+        self.line("    return Number(seconds) * 1000 + Math.floor(nanoseconds / 1e6);");
+        self.line("  };");
+        self.line("  NativeDate.now = now;");
+        self.line("  globalThis.Date = new Proxy(NativeDate, {");
+        self.line("    construct(target, args, newTarget) {");
+        self.line(
+            "      return Reflect.construct(target, args.length ? args : [now()], newTarget);",
+        );
+        self.line("    },");
+        self.line("    apply() { return new NativeDate(now()).toString(); },");
+        self.line("  });");
+        self.line("}");
+    }
+
+    /// `setTimeout`, `setInterval` and their `clear` functions over the 0.2
+    /// monotonic clock, with the `runTimer` hook the runtime pumps.
+    fn emit_timers(&mut self, module: &str) {
+        self.line(&format!("import monotonicClock from \"{module}\";"));
+        self.lines.extend(TIMERS_JS.lines().map(str::to_string));
+    }
+
+    /// A read-only `env` global mapping variable names to values.
+    ///
+    /// Every access calls `get-environment` again, so reads at run time see the
+    /// host's environment rather than the one present during Wizer.
+    fn emit_env(&mut self, module: &str) {
+        self.line(&format!("import environment from \"{module}\";"));
+        self.line("{");
+        self.line("  const vars = () => new Map(environment.getEnvironment());");
+        self.line("  const readOnly = () => { throw new TypeError(\"env is read-only\"); };");
+        self.line("  globalThis.env = new Proxy({}, {");
+        self.line("    get(_, name) { return typeof name === \"string\" ? vars().get(name) : undefined; },");
+        self.line("    has(_, name) { return vars().has(name); },");
+        self.line("    ownKeys() { return [...vars().keys()]; },");
+        self.line("    getOwnPropertyDescriptor(_, name) {");
+        self.line("      const value = vars().get(name);");
+        self.line("      if (value === undefined) return undefined;");
+        self.line("      return { value, writable: false, enumerable: true, configurable: true };");
+        self.line("    },");
+        self.line("    set: readOnly,");
+        self.line("    defineProperty: readOnly,");
+        self.line("    deleteProperty: readOnly,");
+        self.line("  });");
+        self.line("}");
+    }
+
+    /// A `config` global whose `get(key)` and `getAll()` return the values of
+    /// `wasi:config` (`store`, or `runtime-config` in earlier drafts) as plain
+    /// strings, throwing the config error as an `Error`.
+    fn emit_config(&mut self, module: &str) {
+        self.line(&format!("import configStore from \"{module}\";"));
+        self.line("{");
+        self.line("  const call = (f, message) => {");
+        self.line("    try {");
+        self.line("      return f();");
+        self.line("    } catch (e) {");
+        self.line("      const error = e?.payload;");
+        self.line("      if (error === undefined) throw e;");
+        self.line("      throw Object.assign(new Error(`${message}: ${error.val ?? error.tag}`), { code: error.tag, cause: e });");
+        self.line("    }");
+        self.line("  };");
+        self.line("  globalThis.config = Object.freeze({");
+        self.line("    get(key) {");
+        self.line("      return call(() => configStore.get(String(key)), `cannot read config '${key}'`) ?? undefined;");
+        self.line("    },");
+        self.line("    getAll() {");
+        self.line("      return Object.fromEntries(call(() => configStore.getAll(), \"cannot read config\"));");
+        self.line("    },");
+        self.line("  });");
+        self.line("}");
+    }
+
+    /// An `fs` global with `readFileSync`, `writeFileSync`, `readdirSync` and
+    /// `readdir` over the preopened directories.
+    fn emit_fs(&mut self, module: &str) {
+        self.line(&format!("import preopens from \"{module}\";"));
+        self.emit_utf8();
+        self.lines.extend(FS_JS.lines().map(str::to_string));
+    }
+
+    /// A `net` global whose `connect(host, port)` opens a TCP connection.
+    fn emit_net(&mut self, [network, lookup, tcp]: &[String; 3]) {
+        self.line(&format!("import instanceNetwork from \"{network}\";"));
+        self.line(&format!("import ipNameLookup from \"{lookup}\";"));
+        self.line(&format!("import tcpCreateSocket from \"{tcp}\";"));
+        self.emit_utf8();
+        self.lines.extend(NET_JS.lines().map(str::to_string));
+    }
+
+    /// A `keyvalue` global whose `openBucket(name)` wraps a bucket in a
+    /// Map-like object.
+    fn emit_keyvalue(&mut self, module: &str) {
+        self.line(&format!("import store from \"{module}\";"));
+        self.emit_utf8();
+        self.lines.extend(KEYVALUE_JS.lines().map(str::to_string));
+    }
+
+    /// The UTF-8 helpers, unless an earlier global already emitted them.
+    fn emit_utf8(&mut self) {
+        if !self.utf8 {
+            self.utf8 = true;
+            self.lines.extend(UTF8_JS.lines().map(str::to_string));
+        }
+    }
+
+    /// `console.debug`, `info`, `warn` and `error` (plus `log` as `info` and
+    /// `trace` as `trace`) send their formatted message to `wasi:logging` at
+    /// the matching level, so the host's log pipeline sees it.
+    ///
+    /// Arguments are formatted like a console does: `%s`, `%d`, `%i`, `%f`,
+    /// `%o`, `%O`, `%j` and `%%` in a leading string are substituted, and the
+    /// rest are joined with spaces.
+    fn emit_logging(&mut self, module: &str) {
+        self.line(&format!("import logging from \"{module}\";"));
+        self.lines.extend(LOGGING_JS.lines().map(str::to_string));
     }
 
     fn emit_constructor(&mut self, name: &str, native_fn: &str, types: &[Option<Type>]) {
@@ -99,6 +543,82 @@ impl<'a> EmitContext<'a> {
         }
     }
 
+    /// Constants objects for every enum and flags type in the world, holding
+    /// the JS value of each enum case and the property name of each flag.
+    ///
+    /// World-level types live directly on `wit` (`wit.Color.Red`); interface
+    /// types live on an object keyed by the interface name
+    /// (`wit["my:pkg/types@1.0.0"].Color.Red`). The unversioned key aliases it
+    /// unless several versions of the interface are present, in which case
+    /// reading it throws.
+    fn emit_constants(&mut self) {
+        let mut world_types = Vec::new();
+        let mut interfaces: IndexMap<InterfaceId, Vec<TypeId>> = IndexMap::new();
+        for &id in &self.constants {
+            match self.resolve.types[id].owner {
+                TypeOwner::Interface(iface) => interfaces.entry(iface).or_default().push(id),
+                _ => world_types.push(id),
+            }
+        }
+
+        for id in world_types {
+            let (name, value) = constants_entry(self.resolve, id);
+            self.line(&format!("wit.{name} = {value};"));
+        }
+
+        let mut unversioned: IndexMap<String, Vec<String>> = IndexMap::new();
+        for (iface, types) in &interfaces {
+            let Some(iface_name) = self.resolve.id_of(*iface) else {
+                continue;
+            };
+            self.line(&format!("wit[\"{iface_name}\"] = Object.freeze({{"));
+            for &id in types {
+                let (name, value) = constants_entry(self.resolve, id);
+                self.line(&format!("  {name}: {value},"));
+            }
+            self.line("});");
+
+            if let Some((base, _)) = iface_name.split_once('@') {
+                unversioned
+                    .entry(base.to_string())
+                    .or_default()
+                    .push(iface_name.clone());
+            }
+        }
+
+        for (base, versions) in unversioned {
+            if let [iface_name] = versions.as_slice() {
+                self.line(&format!("wit[\"{base}\"] = wit[\"{iface_name}\"];"));
+            } else {
+                self.line(&format!(
+                    "Object.defineProperty(wit, \"{base}\", {{ get() {{ throw new Error(\"`{base}` is ambiguous between {}; use a versioned name\"); }} }});",
+                    versions.join(", ")
+                ));
+            }
+        }
+    }
+
+    /// `wit.Stream.from(iterable, type?)`: pump a (possibly async) iterable
+    /// into a new stream and return its readable end.
+    ///
+    /// Typed arrays are written as chunks; any other value is a single element.
+    fn emit_stream_from(&mut self) {
+        self.line("wit.Stream.from = function(iterable, type) {");
+        self.line("  const { readable, writable } = wit.Stream(type);");
+        self.line("  (async () => {");
+        self.line("    try {");
+        self.line("      for await (const value of iterable) {");
+        self.line("        if (writable.closed) break;");
+        self.line("        await writable.writeAll(ArrayBuffer.isView(value) ? value : [value]);");
+        self.line("      }");
+        self.line("    } finally {");
+        self.line("      writable.drop();");
+        self.line("    }");
+        self.line("  })();");
+        self.line("  return readable;");
+        self.line("};");
+    }
+
     fn collect_from_world_item(&mut self, item: &WorldItem) {
         match item {
             WorldItem::Function(f) => {
@@ -115,6 +635,19 @@ impl<'a> EmitContext<'a> {
         }
     }
 
+    /// Record an enum or flags type, looking through `use` aliases.
+    fn collect_constant(&mut self, mut id: TypeId) {
+        while let TypeDefKind::Type(Type::Id(inner)) = self.resolve.types[id].kind {
+            id = inner;
+        }
+        if matches!(
+            self.resolve.types[id].kind,
+            TypeDefKind::Enum(_) | TypeDefKind::Flags(_)
+        ) {
+            self.constants.insert(id);
+        }
+    }
+
     fn collect_from_function(&mut self, func: &wit_parser::Function) {
         for param in &func.params {
             self.collect_from_type(&param.ty);
@@ -194,6 +727,1005 @@ impl<'a> EmitContext<'a> {
     }
 }
 
+/// Timers on top of the 0.2 `wasi:clocks/monotonic-clock`, appended to the
+/// shim after the import of `monotonicClock`.
+///
+/// Nothing runs them from JavaScript: the runtime calls the exported
+/// `runTimer(block)` while an export's promise is pending, which fires the
+/// earliest due timer, first blocking on a pollable for it if `block` is set,
+/// and returns whether one fired. Exceptions thrown by a callback propagate to
+/// the runtime.
+const TIMERS_JS: &str = r#"
+const timers = new Map();
+let nextTimerId = 1;
+const schedule = (callback, delay, args, repeat) => {
+  if (typeof callback !== "function") throw new TypeError("timer callback must be a function");
+  const ms = Math.max(0, Number(delay) || 0);
+  const id = nextTimerId++;
+  timers.set(id, { callback, args, ms, repeat, due: monotonicClock.now() + Math.ceil(ms * 1e6) });
+  return id;
+};
+const clear = (id) => {
+  timers.delete(id);
+};
+globalThis.setTimeout = (callback, delay, ...args) => schedule(callback, delay, args, false);
+globalThis.setInterval = (callback, delay, ...args) => schedule(callback, delay, args, true);
+globalThis.clearTimeout = clear;
+globalThis.clearInterval = clear;
+
+export const runTimer = (block) => {
+  let next;
+  for (const [id, timer] of timers) {
+    if (!next || timer.due < next[1].due) next = [id, timer];
+  }
+  if (!next) return false;
+  const [id, timer] = next;
+  if (timer.due > monotonicClock.now()) {
+    if (!block) return false;
+    const pollable = monotonicClock.subscribeInstant(timer.due);
+    pollable.block();
+    pollable.drop();
+  }
+  if (timer.repeat) {
+    timer.due = monotonicClock.now() + Math.ceil(Math.max(timer.ms, 1) * 1e6);
+  } else {
+    timers.delete(id);
+  }
+  timer.callback(...timer.args);
+  return true;
+};
+"#;
+
+/// `utf8Encode` and `utf8Decode`, shared by the `fs`, `net`, `keyvalue` and
+/// HTTP globals and emitted once before the first of them.
+///
+/// Both come from the runtime's `__cqjs` namespace, which also backs the
+/// `writeAll` of `wasi:io` output streams, so every shim encodes strings the
+/// same lenient way.
+const UTF8_JS: &str = r#"
+const { utf8Encode, utf8Decode } = __cqjs;
+"#;
+
+/// The `fs` global on top of `wasi:filesystem`, appended to the shim after the
+/// import of `preopens`.
+///
+/// Paths are looked up in the preopen with the longest matching prefix.
+/// Relative paths start at `/`, and a preopen named `.` counts as `/`. Each
+/// call fetches the preopens again and drops every descriptor it opened.
+const FS_JS: &str = r#"
+{
+  const components = (path) => path.split("/").filter((part) => part && part !== ".");
+  const fsError = (e, syscall, path) => {
+    if (e?.payload === undefined) return e;
+    const code = e.payload;
+    return Object.assign(new Error(`${code}: ${syscall} '${path}'`), { code, syscall, path });
+  };
+  const withDir = (path, syscall, f) => {
+    path = String(path);
+    const dirs = preopens.getDirectories();
+    try {
+      const parts = components(path);
+      let best;
+      for (const [dir, name] of dirs) {
+        const prefix = components(name);
+        if (prefix.length > parts.length || best?.prefix.length > prefix.length) continue;
+        if (prefix.every((part, i) => part === parts[i])) best = { dir, prefix };
+      }
+      if (!best) {
+        throw Object.assign(new Error(`no-entry: ${syscall} '${path}' (not under a preopened directory)`), {
+          code: "no-entry",
+          syscall,
+          path,
+        });
+      }
+      return f(best.dir, parts.slice(best.prefix.length).join("/") || ".");
+    } catch (e) {
+      throw fsError(e, syscall, path);
+    } finally {
+      for (const [dir] of dirs) dir.drop();
+    }
+  };
+  const openAt = (path, syscall, openFlags, descriptorFlags, f) =>
+    withDir(path, syscall, (dir, rest) => {
+      const descriptor = dir.openAt({ symlinkFollow: true }, rest, openFlags, descriptorFlags);
+      try {
+        return f(descriptor);
+      } finally {
+        descriptor.drop();
+      }
+    });
+  const CHUNK = 65536;
+
+  const readFileSync = (path, options) => {
+    const encoding = typeof options === "string" ? options : options?.encoding;
+    if (encoding != null && !/^utf-?8$/i.test(encoding)) {
+      throw new TypeError(`fs: unsupported encoding '${encoding}'`);
+    }
+    const chunks = [];
+    let length = 0;
+    openAt(path, "open", {}, { read: true }, (file) => {
+      for (;;) {
+        const [chunk, end] = file.read(CHUNK, length);
+        chunks.push(chunk);
+        length += chunk.length;
+        if (end || !chunk.length) return;
+      }
+    });
+    const bytes = new Uint8Array(length);
+    let offset = 0;
+    for (const chunk of chunks) {
+      bytes.set(chunk, offset);
+      offset += chunk.length;
+    }
+    return encoding == null ? bytes : utf8Decode(bytes);
+  };
+
+  const writeFileSync = (path, data) => {
+    const bytes = typeof data === "string" ? utf8Encode(data)
+      : data instanceof ArrayBuffer ? new Uint8Array(data)
+      : ArrayBuffer.isView(data) ? new Uint8Array(data.buffer, data.byteOffset, data.byteLength)
+      : utf8Encode(String(data));
+    openAt(path, "open", { create: true, truncate: true }, { write: true }, (file) => {
+      let offset = 0;
+      while (offset < bytes.length) {
+        offset += Number(file.write(bytes.subarray(offset, offset + CHUNK), offset));
+      }
+    });
+  };
+
+  const readdirSync = (path) =>
+    openAt(path, "scandir", { directory: true }, { read: true }, (dir) => {
+      const names = [];
+      const entries = dir.readDirectory();
+      try {
+        for (let entry; (entry = entries.readDirectoryEntry()); ) names.push(entry.name);
+      } finally {
+        entries.drop();
+      }
+      return names.sort();
+    });
+  // `fs.promises.readdir`'s form; the listing itself still blocks.
+  const readdir = async (path) => readdirSync(path);
+
+  globalThis.fs = Object.freeze({ readFileSync, writeFileSync, readdirSync, readdir });
+}
+"#;
+
+/// The `net` global on top of `wasi:sockets`, appended to the shim after the
+/// imports of `instanceNetwork`, `ipNameLookup` and `tcpCreateSocket`.
+///
+/// `connect` tries each address the host name resolves to in turn. Like
+/// `fetch()`, it blocks the guest while waiting on the host.
+const NET_JS: &str = r#"
+{
+  const socketError = (e, message) => {
+    const code = e?.payload;
+    if (code === undefined) return e;
+    const detail = typeof code === "string" ? code : code.tag;
+    return Object.assign(new Error(`${detail}: ${message}`), { code: detail, cause: e });
+  };
+  // Retry a non-blocking operation until it stops failing with `would-block`.
+  const untilReady = (pollable, f) => {
+    for (;;) {
+      try {
+        return f();
+      } catch (e) {
+        if (e?.payload !== "would-block") throw e;
+        pollable.block();
+      }
+    }
+  };
+  const toBytes = (data) => {
+    if (typeof data === "string") return utf8Encode(data);
+    if (data instanceof ArrayBuffer) return new Uint8Array(data);
+    if (ArrayBuffer.isView(data)) return new Uint8Array(data.buffer, data.byteOffset, data.byteLength);
+    throw new TypeError("net: data must be a string or a buffer");
+  };
+  const formatAddress = (address) =>
+    address.tag === "ipv4" ? address.val.join(".") : address.val.map((n) => n.toString(16)).join(":");
+
+  const resolve = (network, host) => {
+    const stream = ipNameLookup.resolveAddresses(network, host);
+    const pollable = stream.subscribe();
+    const addresses = [];
+    try {
+      for (let address; (address = untilReady(pollable, () => stream.resolveNextAddress())); ) {
+        addresses.push(address);
+      }
+    } finally {
+      pollable.drop();
+      stream.drop();
+    }
+    return addresses;
+  };
+
+  class Socket {
+    #socket;
+    #input;
+    #output;
+    constructor(socket, input, output, address, port) {
+      this.#socket = socket;
+      this.#input = input;
+      this.#output = output;
+      this.remoteAddress = formatAddress(address);
+      this.remotePort = port;
+    }
+    // Resolves with the next chunk of at most `max` bytes, or `null` once the
+    // peer closed its end.
+    async read(max = 65536) {
+      if (!this.#input) return null;
+      for (;;) {
+        let chunk;
+        try {
+          chunk = this.#input.blockingRead(max);
+        } catch (e) {
+          if (e?.payload?.tag === "closed") return null;
+          throw socketError(e, "read failed");
+        }
+        if (chunk.length) return chunk;
+      }
+    }
+    async write(data) {
+      if (!this.#output) throw new Error("net: the socket is not writable");
+      const bytes = toBytes(data);
+      try {
+        for (let i = 0; i < bytes.length; i += 4096) {
+          this.#output.blockingWriteAndFlush(bytes.subarray(i, i + 4096));
+        }
+      } catch (e) {
+        throw socketError(e, "write failed");
+      }
+    }
+    // Close the sending half, so the peer reads the end of the stream.
+    end() {
+      if (!this.#output) return;
+      this.#output.drop();
+      this.#output = null;
+      try {
+        this.#socket.shutdown("send");
+      } catch {}
+    }
+    close() {
+      if (!this.#socket) return;
+      this.#input?.drop();
+      this.#output?.drop();
+      this.#input = this.#output = null;
+      this.#socket.drop();
+      this.#socket = null;
+    }
+    async *[Symbol.asyncIterator]() {
+      for (let chunk; (chunk = await this.read()) !== null; ) yield chunk;
+    }
+  }
+
+  const connectTo = (network, address, port) => {
+    const socket = tcpCreateSocket.createTcpSocket(address.tag);
+    const pollable = socket.subscribe();
+    try {
+      socket.startConnect(
+        network,
+        address.tag === "ipv4"
+          ? { tag: "ipv4", val: { port, address: address.val } }
+          : { tag: "ipv6", val: { port, flowInfo: 0, address: address.val, scopeId: 0 } },
+      );
+      const [input, output] = untilReady(pollable, () => socket.finishConnect());
+      pollable.drop();
+      return new Socket(socket, input, output, address, port);
+    } catch (e) {
+      pollable.drop();
+      socket.drop();
+      throw e;
+    }
+  };
+
+  const connect = async (host, port) => {
+    const network = instanceNetwork.instanceNetwork();
+    try {
+      let addresses;
+      try {
+        addresses = resolve(network, String(host));
+      } catch (e) {
+        throw socketError(e, `cannot resolve ${host}`);
+      }
+      let error = Object.assign(new Error(`name-unresolvable: cannot resolve ${host}`), {
+        code: "name-unresolvable",
+      });
+      for (const address of addresses) {
+        try {
+          return connectTo(network, address, port);
+        } catch (e) {
+          error = socketError(e, `cannot connect to ${host}:${port}`);
+        }
+      }
+      throw error;
+    } finally {
+      network.drop();
+    }
+  };
+
+  globalThis.net = Object.freeze({ connect });
+}
+"#;
+
+/// The `keyvalue` global on top of `wasi:keyvalue/store`, appended to the shim
+/// after the import of `store`.
+///
+/// Values are stored as bytes: strings are UTF-8 encoded, and `get` decodes
+/// them again when asked for the `utf8` encoding. `listKeys` follows the
+/// cursor, whether it is a number (`0.2.0-draft`) or a string (later drafts).
+const KEYVALUE_JS: &str = r#"
+{
+  const keyvalueError = (e, message) => {
+    const error = e?.payload;
+    if (error === undefined) return e;
+    const detail = error.tag === "other" ? error.val : error.tag;
+    return Object.assign(new Error(`${message}: ${detail}`), { code: error.tag, cause: e });
+  };
+  const toBytes = (value) => {
+    if (typeof value === "string") return utf8Encode(value);
+    if (value instanceof ArrayBuffer) return new Uint8Array(value);
+    if (ArrayBuffer.isView(value)) {
+      return new Uint8Array(value.buffer, value.byteOffset, value.byteLength);
+    }
+    throw new TypeError("keyvalue: values must be strings or buffers");
+  };
+
+  class Bucket {
+    #bucket;
+    constructor(bucket, name) {
+      this.#bucket = bucket;
+      this.name = name;
+    }
+    #call(message, f) {
+      if (!this.#bucket) throw new Error(`keyvalue: bucket '${this.name}' is closed`);
+      try {
+        return f(this.#bucket);
+      } catch (e) {
+        throw keyvalueError(e, `${message} in bucket '${this.name}'`);
+      }
+    }
+    // The value of `key` as a `Uint8Array`, or as a string for the `utf8`
+    // encoding; `undefined` if there is none.
+    get(key, options) {
+      const encoding = typeof options === "string" ? options : options?.encoding;
+      if (encoding != null && !/^utf-?8$/i.test(encoding)) {
+        throw new TypeError(`keyvalue: unsupported encoding '${encoding}'`);
+      }
+      const value = this.#call(`cannot get '${key}'`, (bucket) => bucket.get(String(key)));
+      if (value == null) return undefined;
+      return encoding == null ? value : utf8Decode(value);
+    }
+    set(key, value) {
+      const bytes = toBytes(value);
+      this.#call(`cannot set '${key}'`, (bucket) => bucket.set(String(key), bytes));
+      return this;
+    }
+    has(key) {
+      return this.#call(`cannot check '${key}'`, (bucket) => bucket.exists(String(key)));
+    }
+    // Whether `key` had a value before it was deleted.
+    delete(key) {
+      return this.#call(`cannot delete '${key}'`, (bucket) => {
+        const existed = bucket.exists(String(key));
+        if (existed) bucket.delete(String(key));
+        return existed;
+      });
+    }
+    listKeys() {
+      return this.#call("cannot list keys", (bucket) => {
+        const keys = [];
+        let cursor = null;
+        do {
+          const page = bucket.listKeys(cursor);
+          keys.push(...page.keys);
+          cursor = page.cursor;
+        } while (cursor != null);
+        return keys;
+      });
+    }
+    close() {
+      this.#bucket?.drop();
+      this.#bucket = null;
+    }
+  }
+
+  const openBucket = (name) => {
+    try {
+      return new Bucket(store.open(String(name)), String(name));
+    } catch (e) {
+      throw keyvalueError(e, `cannot open bucket '${name}'`);
+    }
+  };
+
+  globalThis.keyvalue = Object.freeze({ openBucket });
+}
+"#;
+
+/// The `console` level methods on top of `wasi:logging/logging`, appended to
+/// the shim after the import of `logging`.
+const LOGGING_JS: &str = r#"
+{
+  const inspect = (value) => {
+    if (typeof value === "string") return value;
+    if (value instanceof Error) return value.stack ? `${value}\n${value.stack}` : `${value}`;
+    if (typeof value !== "object" || value === null) return String(value);
+    try {
+      return JSON.stringify(value, (_, v) => typeof v === "bigint" ? `${v}n` : v) ?? String(value);
+    } catch {
+      return String(value);
+    }
+  };
+  const format = (args) => {
+    let rest = args;
+    const parts = [];
+    if (typeof args[0] === "string") {
+      let i = 1;
+      parts.push(args[0].replace(/%([sdifoOj%])/g, (spec, kind) => {
+        if (kind === "%") return "%";
+        if (i >= args.length) return spec;
+        const value = args[i++];
+        switch (kind) {
+          case "d": case "i": return typeof value === "bigint" ? `${value}n` : `${Math.trunc(Number(value))}`;
+          case "f": return `${Number(value)}`;
+          default: return inspect(value);
+        }
+      }));
+      rest = args.slice(i);
+    }
+    return parts.concat(rest.map(inspect)).join(" ");
+  };
+  const levels = { trace: "trace", debug: "debug", log: "info", info: "info", warn: "warn", error: "error" };
+  const console = globalThis.console ??= {};
+  for (const [method, level] of Object.entries(levels)) {
+    console[method] = (...args) => logging.log(level, "", format(args));
+  }
+}
+"#;
+
+/// `Headers`, `Request` and `Response` along with the body helpers shared by
+/// `fetch()` and the `fetch` handler, appended to the shim after the import of
+/// `http` (the `wasi:http/types` module).
+///
+/// Bodies are streamed: request bodies may be strings, buffers or (async)
+/// iterables of chunks, and `Response.body` is an async iterable of
+/// `Uint8Array` chunks read from the incoming stream on demand. Header values
+/// and `text()` decode invalid UTF-8 to U+FFFD, like a non-fatal
+/// `TextDecoder`, rather than throwing.
+const HTTP_JS: &str = r#"
+const toBytes = (chunk) => {
+  if (typeof chunk === "string") return utf8Encode(chunk);
+  if (chunk instanceof ArrayBuffer) return new Uint8Array(chunk);
+  if (ArrayBuffer.isView(chunk)) {
+    return new Uint8Array(chunk.buffer, chunk.byteOffset, chunk.byteLength);
+  }
+  throw new TypeError("fetch: body chunks must be strings or buffers");
+};
+const bodySource = (body) => {
+  if (body == null) return null;
+  if (typeof body === "string" || body instanceof ArrayBuffer || ArrayBuffer.isView(body)) {
+    const bytes = toBytes(body);
+    return (async function* () { yield bytes; })();
+  }
+  if (body instanceof Body) return body.body;
+  if (Symbol.asyncIterator in body || Symbol.iterator in body) {
+    return (async function* () { for await (const chunk of body) yield toBytes(chunk); })();
+  }
+  throw new TypeError("fetch: unsupported body type");
+};
+
+const headerList = Symbol("headerList");
+
+class Headers {
+  #map = new Map();
+  constructor(init) {
+    if (init == null) return;
+    const entries = init instanceof Headers || Symbol.iterator in Object(init)
+      ? init
+      : Object.entries(init);
+    for (const [name, value] of entries) this.append(name, value);
+  }
+  append(name, value) {
+    const key = String(name).toLowerCase();
+    const values = this.#map.get(key);
+    if (values) values.push(String(value));
+    else this.#map.set(key, [String(value)]);
+  }
+  set(name, value) { this.#map.set(String(name).toLowerCase(), [String(value)]); }
+  get(name) { return this.#map.get(String(name).toLowerCase())?.join(", ") ?? null; }
+  has(name) { return this.#map.has(String(name).toLowerCase()); }
+  delete(name) { this.#map.delete(String(name).toLowerCase()); }
+  *entries() {
+    const names = [...this.#map.keys()].sort();
+    for (const name of names) yield [name, this.#map.get(name).join(", ")];
+  }
+  *keys() { for (const [name] of this.entries()) yield name; }
+  *values() { for (const [, value] of this.entries()) yield value; }
+  forEach(callback, thisArg) {
+    for (const [name, value] of this.entries()) callback.call(thisArg, value, name, this);
+  }
+  [Symbol.iterator]() { return this.entries(); }
+  // Every value separately, as sent on the wire.
+  [headerList]() {
+    return [...this.#map].flatMap(([name, values]) => values.map((v) => [name, v]));
+  }
+}
+
+class Body {
+  #source;
+  #used = false;
+  constructor(body) { this.#source = bodySource(body); }
+  get body() { return this.#source; }
+  get bodyUsed() { return this.#used; }
+  async bytes() {
+    if (this.#used) throw new TypeError("Body has already been consumed");
+    this.#used = true;
+    const chunks = [];
+    let length = 0;
+    if (this.#source) {
+      for await (const chunk of this.#source) {
+        chunks.push(chunk);
+        length += chunk.length;
+      }
+    }
+    const bytes = new Uint8Array(length);
+    let offset = 0;
+    for (const chunk of chunks) {
+      bytes.set(chunk, offset);
+      offset += chunk.length;
+    }
+    return bytes;
+  }
+  async arrayBuffer() { return (await this.bytes()).buffer; }
+  async text() { return utf8Decode(await this.bytes()); }
+  async json() { return JSON.parse(await this.text()); }
+}
+
+class Request extends Body {
+  constructor(input, init = {}) {
+    const base = input instanceof Request ? input : null;
+    super(init.body ?? base);
+    this.url = base ? base.url : new URL(input).href;
+    this.method = String(init.method ?? base?.method ?? "GET").toUpperCase();
+    this.headers = new Headers(init.headers ?? base?.headers);
+    this.signal = init.signal ?? base?.signal ?? null;
+  }
+}
+
+class Response extends Body {
+  constructor(body = null, init = {}) {
+    super(body);
+    this.status = init.status ?? 200;
+    this.statusText = init.statusText ?? "";
+    this.headers = new Headers(init.headers);
+    this.url = "";
+  }
+  get ok() { return this.status >= 200 && this.status < 300; }
+}
+
+const networkError = (code) =>
+  Object.assign(new TypeError(`fetch failed: ${code?.tag ?? code}`), { cause: code });
+
+const writeBody = async (outgoingBody, source, signal) => {
+  const stream = outgoingBody.write();
+  try {
+    if (source) {
+      for await (const chunk of source) {
+        signal?.throwIfAborted();
+        for (let i = 0; i < chunk.length; i += 4096) {
+          stream.blockingWriteAndFlush(chunk.slice(i, i + 4096));
+        }
+      }
+    }
+  } catch (e) {
+    stream.drop();
+    outgoingBody.drop();
+    throw e;
+  }
+  stream.drop();
+  http.OutgoingBody.finish(outgoingBody, null);
+};
+
+const readBody = async function* (incoming, signal) {
+  const body = incoming.consume();
+  const stream = body.stream();
+  try {
+    for (;;) {
+      signal?.throwIfAborted();
+      let chunk;
+      try {
+        chunk = stream.blockingRead(65536);
+      } catch (e) {
+        if (e?.payload?.tag === "closed") return;
+        throw networkError(e?.payload?.val ?? e);
+      }
+      if (chunk.length) yield chunk;
+    }
+  } finally {
+    stream.drop();
+    body.drop();
+    incoming.drop();
+  }
+};
+
+globalThis.Headers = Headers;
+globalThis.Request = Request;
+globalThis.Response = Response;"#;
+
+/// `fetch()` on top of `wasi:http/outgoing-handler`, appended after `HTTP_JS`
+/// and the import of `outgoingHandler`.
+///
+/// The request's `signal` is checked before the request is sent, before every
+/// body chunk is written or read and once the response arrives. An aborted fetch
+/// drops its pending response or body stream, which cancels it on the host,
+/// and rejects with the signal's reason.
+///
+/// Waiting on the host blocks the guest, so timers don't fire meanwhile: an
+/// abort scheduled by a timer is only seen at the next check, after the host
+/// answered.
+const FETCH_JS: &str = r#"
+const METHODS = ["GET", "HEAD", "POST", "PUT", "DELETE", "CONNECT", "OPTIONS", "TRACE", "PATCH"];
+
+globalThis.fetch = async function fetch(input, init) {
+  const request = new Request(input, init);
+  const signal = request.signal;
+  signal?.throwIfAborted();
+  const url = new URL(request.url);
+  const scheme = url.protocol.slice(0, -1);
+
+  const fields = http.Fields.fromList(
+    request.headers[headerList]().map(([name, value]) => [name, utf8Encode(value)]),
+  );
+  const outgoing = new http.OutgoingRequest(fields);
+  outgoing.setMethod(
+    METHODS.includes(request.method)
+      ? { tag: request.method.toLowerCase() }
+      : { tag: "other", val: request.method },
+  );
+  outgoing.setScheme(
+    scheme === "http" ? { tag: "HTTP" }
+      : scheme === "https" ? { tag: "HTTPS" }
+      : { tag: "other", val: scheme },
+  );
+  outgoing.setAuthority(url.host);
+  outgoing.setPathWithQuery(url.pathname + url.search);
+
+  const outgoingBody = outgoing.body();
+  let future;
+  try {
+    future = outgoingHandler.handle(outgoing, null);
+  } catch (e) {
+    outgoingBody.drop();
+    throw networkError(e?.payload ?? e);
+  }
+  try {
+    await writeBody(outgoingBody, request.body, signal);
+    signal?.throwIfAborted();
+  } catch (e) {
+    future.drop();
+    throw e;
+  }
+
+  const pollable = future.subscribe();
+  pollable.block();
+  pollable.drop();
+  const result = future.get();
+  future.drop();
+  if (result.val?.tag !== "ok") throw networkError(result.val?.val);
+  const incoming = result.val.val;
+  if (signal?.aborted) {
+    incoming.drop();
+    throw signal.reason;
+  }
+
+  const headers = new Headers();
+  const responseFields = incoming.headers();
+  for (const [name, value] of responseFields.entries()) headers.append(name, utf8Decode(value));
+  responseFields.drop();
+
+  const response = new Response(readBody(incoming, signal), { status: incoming.status(), headers });
+  response.url = request.url;
+  return response;
+};"#;
+
+/// The `wasi:http/incoming-handler` adapter over a module's default export,
+/// appended after `HTTP_JS`.
+///
+/// Each incoming request becomes a `Request` passed to the export's
+/// `fetch(request)`; the returned `Response`, or the one its promise resolves
+/// to, is sent through the response outparam with its body streamed out. A
+/// handler that throws sends an `internal-error` instead, and logs the error
+/// with `console.error` if there is a console.
+const SERVE_JS: &str = r#"
+const serveHttp = (module) => {
+  const handler = module.default;
+  if (typeof handler?.fetch !== "function") return undefined;
+  const schemeName = (scheme) =>
+    !scheme ? "http" : scheme.tag === "other" ? scheme.val : scheme.tag.toLowerCase();
+  return {
+    async handle(incoming, responseOut) {
+      // The request body may still be read while the response streams out.
+      let consumed = false;
+      const body = (async function* () {
+        consumed = true;
+        yield* readBody(incoming);
+      })();
+      // Closing a started body runs `readBody`'s cleanup, which drops the
+      // request; an unread one leaves that to us.
+      const release = async () => {
+        await body.return();
+        if (!consumed) incoming.drop();
+      };
+      let response;
+      try {
+        const headers = new Headers();
+        const fields = incoming.headers();
+        for (const [name, value] of fields.entries()) headers.append(name, utf8Decode(value));
+        fields.drop();
+        const method = incoming.method();
+        const url = `${schemeName(incoming.scheme())}://${incoming.authority() ?? "localhost"}` +
+          (incoming.pathWithQuery() ?? "/");
+        const request = new Request(url, {
+          method: method.tag === "other" ? method.val : method.tag,
+          headers,
+          body,
+        });
+        response = await handler.fetch(request);
+        if (!(response instanceof Response)) {
+          throw new TypeError("fetch handler must return a Response");
+        }
+      } catch (e) {
+        // Throwing would trap the export after the response was set, so the
+        // error is only logged.
+        globalThis.console?.error?.(e?.stack ? `${e}\n${e.stack}` : String(e));
+        await release();
+        http.ResponseOutparam.set(responseOut, {
+          tag: "err",
+          val: { tag: "internal-error", val: String(e) },
+        });
+        return;
+      }
+
+      const fields = http.Fields.fromList(
+        response.headers[headerList]().map(([name, value]) => [name, utf8Encode(value)]),
+      );
+      const outgoing = new http.OutgoingResponse(fields);
+      outgoing.setStatusCode(response.status);
+      const outgoingBody = outgoing.body();
+      http.ResponseOutparam.set(responseOut, { tag: "ok", val: outgoing });
+      try {
+        await writeBody(outgoingBody, response.body);
+      } finally {
+        await release();
+      }
+    },
+  };
+};
+"#;
+
+/// `wit.toJSON()` and `wit.fromJSON()`, appended inside a block after the
+/// `TYPES` table of the world's named types.
+///
+/// Types are WIT type strings as in `wit.world`, such as `list<point>`, with
+/// interface types optionally prefixed by their interface to tell apart types
+/// of the same name. The JSON mirrors the JavaScript values, made canonical:
+/// records list every field in declaration order, flags list every flag, and
+/// 64-bit integers beyond the safe range and non-finite floats are strings.
+const JSON_JS: &str = r#"
+  const RANGES = {
+    u8: [0n, 0xffn], s8: [-0x80n, 0x7fn], u16: [0n, 0xffffn], s16: [-0x8000n, 0x7fffn],
+    u32: [0n, 0xffffffffn], s32: [-0x80000000n, 0x7fffffffn],
+    u64: [0n, 0xffffffffffffffffn], s64: [-0x8000000000000000n, 0x7fffffffffffffffn],
+  };
+  const UNSUPPORTED = ["own", "borrow", "stream", "future", "error-context"];
+
+  const parse = (text) => {
+    let pos = 0;
+    const fail = () => { throw new TypeError(`wit: invalid type \`${text}\``); };
+    const skip = () => { while (text[pos] === " ") pos++; };
+    const node = () => {
+      skip();
+      const name = /^[^<>,\s]+/.exec(text.slice(pos))?.[0] ?? fail();
+      pos += name.length;
+      skip();
+      const args = [];
+      if (text[pos] === "<") {
+        do {
+          pos++;
+          args.push(node());
+          skip();
+        } while (text[pos] === ",");
+        if (text[pos++] !== ">") fail();
+      }
+      return { name, args };
+    };
+    const root = node();
+    skip();
+    if (pos !== text.length) fail();
+    return root;
+  };
+
+  const render = ({ name, args }) =>
+    args.length ? `${name}<${args.map(render).join(", ")}>` : name;
+
+  // Follows `use` aliases, so an interface's use of a type is the same type.
+  const target = (key) => {
+    while (TYPES[key].kind === "alias" && Object.hasOwn(TYPES, TYPES[key].type)) key = TYPES[key].type;
+    return key;
+  };
+  const lookup = (name) => {
+    if (Object.hasOwn(TYPES, name)) return TYPES[name];
+    const matches = [
+      ...new Set(Object.keys(TYPES).filter((key) => key.slice(key.lastIndexOf("/") + 1) === name).map(target)),
+    ];
+    if (matches.length === 1) return TYPES[matches[0]];
+    if (matches.length > 1) {
+      throw new TypeError(`wit: type \`${name}\` is ambiguous; use one of: ${matches.join(", ")}`);
+    }
+    throw new TypeError(`wit: unknown type \`${name}\``);
+  };
+
+  const build = (node) => {
+    const { name, args } = node;
+    const text = render(node);
+    const arity = (...counts) => {
+      if (!counts.includes(args.length)) throw new TypeError(`wit: invalid type \`${text}\``);
+    };
+    const optional = (arg) => (arg && arg.name !== "_" ? build(arg) : null);
+    if (name in RANGES || ["bool", "f32", "f64", "char", "string"].includes(name)) {
+      arity(0);
+      return { kind: name, text };
+    }
+    switch (name) {
+      case "list":
+      case "option":
+        arity(1);
+        return { kind: name, of: build(args[0]), text };
+      case "tuple":
+        return { kind: name, types: args.map(build), text };
+      case "result":
+        arity(0, 1, 2);
+        return { kind: name, ok: optional(args[0]), err: optional(args[1]), text };
+    }
+    if (UNSUPPORTED.includes(name)) return { kind: "unsupported", text };
+    arity(0);
+    const schema = lookup(name);
+    switch (schema.kind) {
+      case "alias":
+        return build(parse(schema.type));
+      case "record":
+        return { kind: "record", fields: schema.fields.map(([field, type]) => [field, build(parse(type))]), text };
+      case "variant":
+        return { kind: "variant", cases: new Map(schema.cases.map(([tag, type]) => [tag, type && build(parse(type))])), text };
+      case "enum":
+        return { kind: "enum", cases: schema.cases, text };
+      case "flags":
+        return { kind: "flags", flags: schema.flags, text };
+      default:
+        return { kind: "unsupported", text };
+    }
+  };
+
+  const types = new Map();
+  const resolveType = (text) => {
+    if (typeof text !== "string") throw new TypeError("wit: the type must be a string");
+    let type = types.get(text);
+    if (!type) {
+      type = build(parse(text));
+      types.set(text, type);
+    }
+    return type;
+  };
+
+  const describe = (value) => (value === null ? "null" : Array.isArray(value) ? "array" : typeof value);
+
+  // Both directions walk the type the same way; `convert` carries what differs.
+  const walk = (convert, value, type, path) => {
+    const fail = () => {
+      throw new TypeError(`wit.${convert.name}: expected ${type.text} at ${path}, got ${describe(value)}`);
+    };
+    const isObject = value !== null && typeof value === "object" && !Array.isArray(value);
+    const tagged = (payloadOf) => {
+      if (!isObject || typeof value.tag !== "string") fail();
+      const payload = payloadOf(value.tag);
+      if (payload === undefined) fail();
+      if (payload === null) return { tag: value.tag };
+      return { tag: value.tag, val: walk(convert, value.val, payload, `${path}.val`) };
+    };
+    switch (type.kind) {
+      case "bool":
+        if (typeof value !== "boolean") fail();
+        return value;
+      case "char":
+        if (typeof value !== "string" || [...value].length !== 1) fail();
+        return value;
+      case "string":
+        if (typeof value !== "string") fail();
+        return value;
+      case "f32":
+      case "f64":
+        return convert.float(value) ?? fail();
+      case "list": {
+        const items = convert.list(value) ?? fail();
+        const list = Array.from(items, (item, i) => walk(convert, item, type.of, `${path}[${i}]`));
+        return type.of.kind === "u8" ? convert.bytes(list) : list;
+      }
+      case "tuple": {
+        const items = convert.list(value);
+        if (!items || items.length !== type.types.length) fail();
+        return type.types.map((item, i) => walk(convert, items[i], item, `${path}[${i}]`));
+      }
+      case "option":
+        if (type.of.kind !== "option") {
+          return value == null ? null : walk(convert, value, type.of, path);
+        }
+        // Nested options wrap the outer one as `{ tag: "some", val }`.
+        if (value == null || value.tag === "none") return convert.none();
+        return tagged((tag) => (tag === "some" ? type.of : undefined));
+      case "result":
+        return tagged((tag) => (tag === "ok" ? type.ok : tag === "err" ? type.err : undefined));
+      case "variant":
+        return tagged((tag) => type.cases.get(tag));
+      case "enum":
+        if (!type.cases.includes(value)) fail();
+        return value;
+      case "record": {
+        if (!isObject) fail();
+        const record = {};
+        for (const [field, fieldType] of type.fields) {
+          record[field] = walk(convert, value[field], fieldType, `${path}.${field}`);
+        }
+        return record;
+      }
+      case "flags": {
+        if (!isObject) fail();
+        const flags = {};
+        for (const flag of type.flags) {
+          if (value[flag] !== undefined && typeof value[flag] !== "boolean") fail();
+          flags[flag] = value[flag] === true;
+        }
+        return flags;
+      }
+      case "unsupported":
+        throw new TypeError(`wit.${convert.name}: ${type.text} at ${path} has no JSON form`);
+      default: {
+        const [min, max] = RANGES[type.kind];
+        const int = convert.integer(value);
+        if (int === null || int < min || int > max) fail();
+        return Number.isSafeInteger(Number(int)) ? Number(int) : convert.big(int);
+      }
+    }
+  };
+
+  const integer = (value) =>
+    typeof value === "bigint" ? value
+      : Number.isInteger(value) ? BigInt(value)
+      : null;
+  const NON_FINITE = ["NaN", "Infinity", "-Infinity"];
+
+  const toJSON = {
+    name: "toJSON",
+    integer,
+    big: String,
+    float: (value) => (typeof value !== "number" ? null : Number.isFinite(value) ? value : String(value)),
+    list: (value) =>
+      value !== null && typeof value === "object" && typeof value.length === "number" ? value : null,
+    bytes: (list) => list,
+    none: () => null,
+  };
+  const fromJSON = {
+    name: "fromJSON",
+    integer: (value) => (typeof value === "string" && /^-?\d+$/.test(value) ? BigInt(value) : integer(value)),
+    big: (int) => int,
+    float: (value) =>
+      typeof value === "number" ? value : NON_FINITE.includes(value) ? Number(value) : null,
+    list: (value) => (Array.isArray(value) ? value : null),
+    bytes: (list) => Uint8Array.from(list),
+    none: () => ({ tag: "none" }),
+  };
+
+  wit.toJSON = (value, type) => JSON.stringify(walk(toJSON, value, resolveType(type), "$"));
+  wit.fromJSON = (text, type) => walk(fromJSON, JSON.parse(text), resolveType(type), "$");"#;
+
 #[derive(Clone, Copy)]
 enum ConstNameStyle {
     Local,
@@ -289,6 +1821,279 @@ fn typedef_const_name(resolve: &Resolve, id: TypeId, style: ConstNameStyle) -> S
     }
 }
 
+/// A JS string literal for a WIT name or version, or `null`.
+fn js_string(s: Option<&str>) -> String {
+    s.map_or_else(|| "null".to_string(), |s| format!("\"{s}\""))
+}
+
+/// `{ name, package, version, direction, types }` for an imported or exported
+/// interface.
+fn interface_info(resolve: &Resolve, id: InterfaceId, direction: &str) -> String {
+    let iface = &resolve.interfaces[id];
+    let package = iface.package.map(|id| &resolve.packages[id].name);
+    let package_name = package.map(|p| format!("{}:{}", p.namespace, p.name));
+    let version = package
+        .and_then(|p| p.version.as_ref())
+        .map(|v| v.to_string());
+    format!(
+        "{{ name: {}, package: {}, version: {}, direction: \"{direction}\", types: {} }}",
+        js_string(iface.name.as_deref()),
+        js_string(package_name.as_deref()),
+        js_string(version.as_deref()),
+        types_info(resolve, iface.types.values().copied()),
+    )
+}
+
+/// `{ name, interface, params: [{ name, type }], result, async }` for a
+/// function, with types written in WIT syntax.
+fn function_info(
+    resolve: &Resolve,
+    interface: Option<&str>,
+    func: &wit_parser::Function,
+) -> String {
+    let params: Vec<_> = func
+        .params
+        .iter()
+        .map(|param| {
+            format!(
+                "{{ name: \"{}\", type: \"{}\" }}",
+                param.name,
+                wit_type_name(resolve, &param.ty)
+            )
+        })
+        .collect();
+    let result = func.result.map(|ty| wit_type_name(resolve, &ty));
+    let is_async = matches!(
+        func.kind,
+        FunctionKind::AsyncFreestanding
+            | FunctionKind::AsyncMethod(_)
+            | FunctionKind::AsyncStatic(_)
+    );
+    format!(
+        "{{ name: \"{}\", interface: {}, params: [{}], result: {}, async: {is_async} }}",
+        func.name,
+        js_string(interface),
+        params.join(", "),
+        js_string(result.as_deref()),
+    )
+}
+
+/// `{ name: { kind, ... } }` for the enum, flags and variant types among
+/// `types`: the cases of an enum, the flags of a flags type and the cases of a
+/// variant, each with its payload type or `null`.
+fn types_info(resolve: &Resolve, types: impl Iterator<Item = TypeId>) -> String {
+    let entries: Vec<_> = types
+        .filter_map(|id| {
+            let typedef = &resolve.types[id];
+            let name = typedef.name.as_deref()?;
+            let info = match &typedef.kind {
+                TypeDefKind::Enum(e) => {
+                    let cases: Vec<_> = e.cases.iter().map(|c| format!("\"{}\"", c.name)).collect();
+                    format!("{{ kind: \"enum\", cases: [{}] }}", cases.join(", "))
+                }
+                TypeDefKind::Flags(f) => {
+                    let flags: Vec<_> = f.flags.iter().map(|f| format!("\"{}\"", f.name)).collect();
+                    format!("{{ kind: \"flags\", flags: [{}] }}", flags.join(", "))
+                }
+                TypeDefKind::Variant(v) => {
+                    let cases: Vec<_> = v
+                        .cases
+                        .iter()
+                        .map(|c| {
+                            let ty = c.ty.map(|ty| wit_type_name(resolve, &ty));
+                            format!(
+                                "{{ name: \"{}\", type: {} }}",
+                                c.name,
+                                js_string(ty.as_deref())
+                            )
+                        })
+                        .collect();
+                    format!("{{ kind: \"variant\", cases: [{}] }}", cases.join(", "))
+                }
+                _ => return None,
+            };
+            Some(format!("\"{name}\": {info}"))
+        })
+        .collect();
+    format!("{{ {} }}", entries.join(", "))
+}
+
+/// A type as written in WIT, such as `list<option<u32>>` or `borrow<file>`.
+fn wit_type_name(resolve: &Resolve, ty: &Type) -> String {
+    type_name(resolve, ty, false)
+}
+
+/// Like [`wit_type_name`], but with interface types prefixed by their
+/// interface, such as `wasi:io/streams@0.2.12/input-stream`, so the name is
+/// unambiguous.
+fn qualified_type_name(resolve: &Resolve, ty: &Type) -> String {
+    type_name(resolve, ty, true)
+}
+
+fn type_name(resolve: &Resolve, ty: &Type, qualified: bool) -> String {
+    let id = match ty {
+        Type::Bool => return "bool".to_string(),
+        Type::U8 => return "u8".to_string(),
+        Type::S8 => return "s8".to_string(),
+        Type::U16 => return "u16".to_string(),
+        Type::S16 => return "s16".to_string(),
+        Type::U32 => return "u32".to_string(),
+        Type::S32 => return "s32".to_string(),
+        Type::U64 => return "u64".to_string(),
+        Type::S64 => return "s64".to_string(),
+        Type::F32 => return "f32".to_string(),
+        Type::F64 => return "f64".to_string(),
+        Type::Char => return "char".to_string(),
+        Type::String => return "string".to_string(),
+        Type::ErrorContext => return "error-context".to_string(),
+        Type::Id(id) => *id,
+    };
+
+    let typedef = &resolve.types[id];
+    match (&typedef.name, typedef.owner) {
+        (Some(name), TypeOwner::Interface(iface)) if qualified => match resolve.id_of(iface) {
+            Some(iface) => format!("{iface}/{name}"),
+            None => name.clone(),
+        },
+        (Some(name), _) => name.clone(),
+        (None, _) => structure_name(resolve, id, qualified),
+    }
+}
+
+/// A type as written in WIT, ignoring its own name, so `type id = u64` gives
+/// `u64`.
+fn structure_name(resolve: &Resolve, id: TypeId, qualified: bool) -> String {
+    let name = |ty: &Type| type_name(resolve, ty, qualified);
+    let optional = |ty: &Option<Type>| ty.as_ref().map_or("_".to_string(), name);
+    match &resolve.types[id].kind {
+        TypeDefKind::Option(inner) => format!("option<{}>", name(inner)),
+        TypeDefKind::List(inner) => format!("list<{}>", name(inner)),
+        TypeDefKind::Tuple(t) => {
+            let types: Vec<_> = t.types.iter().map(name).collect();
+            format!("tuple<{}>", types.join(", "))
+        }
+        TypeDefKind::Result(r) => match (&r.ok, &r.err) {
+            (None, None) => "result".to_string(),
+            (Some(ok), None) => format!("result<{}>", name(ok)),
+            (ok, Some(err)) => format!("result<{}, {}>", optional(ok), name(err)),
+        },
+        TypeDefKind::Future(inner) => match inner {
+            Some(inner) => format!("future<{}>", name(inner)),
+            None => "future".to_string(),
+        },
+        TypeDefKind::Stream(inner) => match inner {
+            Some(inner) => format!("stream<{}>", name(inner)),
+            None => "stream".to_string(),
+        },
+        TypeDefKind::Handle(Handle::Own(resource)) => name(&Type::Id(*resource)),
+        TypeDefKind::Handle(Handle::Borrow(resource)) => {
+            format!("borrow<{}>", name(&Type::Id(*resource)))
+        }
+        TypeDefKind::Type(inner) => name(inner),
+        _ => "unknown".to_string(),
+    }
+}
+
+/// Add the named types that `ty` is or refers to, directly or through other
+/// types, to `out`.
+fn collect_named(resolve: &Resolve, ty: &Type, out: &mut IndexSet<TypeId>) {
+    let Type::Id(id) = ty else {
+        return;
+    };
+    let typedef = &resolve.types[*id];
+    if typedef.name.is_some() && !out.insert(*id) {
+        return;
+    }
+    let mut walk = |ty: &Type| collect_named(resolve, ty, out);
+    match &typedef.kind {
+        TypeDefKind::Record(r) => r.fields.iter().for_each(|f| walk(&f.ty)),
+        TypeDefKind::Variant(v) => v.cases.iter().filter_map(|c| c.ty.as_ref()).for_each(walk),
+        TypeDefKind::Tuple(t) => t.types.iter().for_each(walk),
+        TypeDefKind::Result(r) => r.ok.iter().chain(&r.err).for_each(walk),
+        TypeDefKind::Option(inner) | TypeDefKind::List(inner) | TypeDefKind::Type(inner) => {
+            walk(inner)
+        }
+        TypeDefKind::Handle(Handle::Own(resource) | Handle::Borrow(resource)) => {
+            walk(&Type::Id(*resource))
+        }
+        _ => {}
+    }
+}
+
+/// How `wit.toJSON()` and `wit.fromJSON()` convert a named type: the fields of
+/// a record, the cases of a variant or enum, the flags of a flags type, or the
+/// type an alias stands for. Field and payload types are qualified.
+fn json_schema(resolve: &Resolve, id: TypeId) -> String {
+    let name = |ty: &Type| format!("\"{}\"", qualified_type_name(resolve, ty));
+    match &resolve.types[id].kind {
+        TypeDefKind::Record(r) => {
+            let fields: Vec<_> = r
+                .fields
+                .iter()
+                .map(|f| format!("[\"{}\", {}]", f.name.to_lower_camel_case(), name(&f.ty)))
+                .collect();
+            format!("{{ kind: \"record\", fields: [{}] }}", fields.join(", "))
+        }
+        TypeDefKind::Variant(v) => {
+            let cases: Vec<_> = v
+                .cases
+                .iter()
+                .map(|c| {
+                    let ty = c.ty.as_ref().map_or("null".to_string(), name);
+                    format!("[\"{}\", {ty}]", c.name)
+                })
+                .collect();
+            format!("{{ kind: \"variant\", cases: [{}] }}", cases.join(", "))
+        }
+        TypeDefKind::Enum(e) => {
+            let cases: Vec<_> = e.cases.iter().map(|c| format!("\"{}\"", c.name)).collect();
+            format!("{{ kind: \"enum\", cases: [{}] }}", cases.join(", "))
+        }
+        TypeDefKind::Flags(f) => {
+            let flags: Vec<_> = f
+                .flags
+                .iter()
+                .map(|f| format!("\"{}\"", f.name.to_lower_camel_case()))
+                .collect();
+            format!("{{ kind: \"flags\", flags: [{}] }}", flags.join(", "))
+        }
+        TypeDefKind::Resource => "{ kind: \"resource\" }".to_string(),
+        _ => format!(
+            "{{ kind: \"alias\", type: \"{}\" }}",
+            structure_name(resolve, id, true)
+        ),
+    }
+}
+
+/// `Name: Object.freeze({ Case: "value", ... })` for an enum or flags type.
+fn constants_entry(resolve: &Resolve, id: TypeId) -> (String, String) {
+    let typedef = &resolve.types[id];
+    let entries: Vec<_> = match &typedef.kind {
+        TypeDefKind::Enum(e) => e
+            .cases
+            .iter()
+            .map(|c| format!("{}: \"{}\"", c.name.to_upper_camel_case(), c.name))
+            .collect(),
+        TypeDefKind::Flags(f) => f
+            .flags
+            .iter()
+            .map(|f| {
+                format!(
+                    "{}: \"{}\"",
+                    f.name.to_upper_camel_case(),
+                    f.name.to_lower_camel_case()
+                )
+            })
+            .collect(),
+        _ => unreachable!("only enums and flags have constants"),
+    };
+    let name = typedef.name.as_deref().unwrap_or_default();
+    (
+        name.to_upper_camel_case(),
+        format!("Object.freeze({{ {} }})", entries.join(", ")),
+    )
+}
+
 fn unique_const_names(resolve: &Resolve, types: &[Option<Type>]) -> Vec<String> {
     let base_names: Vec<_> = types
         .iter()
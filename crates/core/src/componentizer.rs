@@ -0,0 +1,384 @@
+//! Builds that share their setup through a [`Componentizer`].
+//!
+//! Most of a build only depends on the runtime and the WIT world: linking the
+//! runtime with the WIT bindings, instrumenting the result for Wizer and,
+//! above all, compiling it. A `Componentizer` keeps that work in memory keyed
+//! by a SHA-256 digest of its input, so a repeated build for the same world
+//! only evaluates the JavaScript and snapshots it again.
+
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result, anyhow};
+use bytes::Bytes;
+use cap_rand::SeedableRng;
+use cap_rand::rngs::StdRng;
+use indexmap::IndexMap;
+use wasmtime::component::{Component, Linker, ResourceTable};
+use wasmtime::{Engine, Store, UpdateDeadline};
+use wasmtime_wasi::p2::pipe::{MemoryInputPipe, MemoryOutputPipe};
+use wasmtime_wasi::{DirPerms, FilePerms, HostMonotonicClock, HostWallClock, WasiCtxBuilder};
+use wasmtime_wizer::{WasmtimeWizerComponent, Wizer};
+
+use crate::flatten::flatten;
+use crate::modules::Modules;
+use crate::optimize::{optimize, optimize_module};
+use crate::resolver::Resolver;
+use crate::stubwasi::{stub_internal_imports, stub_wasi_imports};
+use crate::{
+    BuildReport, ComponentizeOpts, Ctx, Init, InitOptions, Runtime, Script, append_custom_sections,
+    cache, check_opts, codegen, init_options, load_world, module_resolution,
+    register_module_loader, runtime_wasm, selected_runtime, wit_dylib_module, wizer_engine,
+};
+
+/// Converts JavaScript into components, reusing the wasmtime engine, the
+/// linked runtime and the compiled, Wizer-instrumented component across
+/// builds.
+///
+/// Worth keeping around wherever several components are built in one process,
+/// such as tests, watch modes or build matrices; [`componentize`] is a fresh
+/// `Componentizer` per call. Builds may run concurrently.
+///
+/// The most recently used [`CACHED_WORLDS`](Self::CACHED_WORLDS) linked
+/// runtimes and compiled components are kept, so memory stays bounded however
+/// many worlds one `Componentizer` builds.
+///
+/// [`componentize`]: crate::componentize
+pub struct Componentizer {
+    engine: Engine,
+    /// Runtimes linked with WIT bindings, by a digest of both.
+    linked: Mutex<Lru<Arc<[u8]>>>,
+    /// Compiled instrumented components, by a digest of their bytes.
+    prepared: Mutex<Lru<Prepared>>,
+}
+
+/// A compiled instrumented component and a linker providing its imports, less
+/// the module loader each build registers itself.
+#[derive(Clone)]
+struct Prepared {
+    component: Component,
+    linker: Linker<Ctx>,
+}
+
+impl Componentizer {
+    /// How many linked runtimes and compiled components are kept.
+    pub const CACHED_WORLDS: usize = 8;
+
+    /// Create a `Componentizer` with an empty cache.
+    pub fn new() -> Result<Self> {
+        Ok(Self {
+            engine: wizer_engine()?,
+            linked: Mutex::default(),
+            prepared: Mutex::default(),
+        })
+    }
+
+    /// Convert JavaScript source code into a WebAssembly component, as
+    /// [`componentize`](crate::componentize).
+    pub async fn componentize(&self, opts: &ComponentizeOpts<'_>) -> Result<Vec<u8>> {
+        let (component, _) = self.componentize_with_report(opts).await?;
+        Ok(component)
+    }
+
+    /// Convert JavaScript source code into a WebAssembly component and report
+    /// on the build, as [`componentize_with_report`](crate::componentize_with_report).
+    pub async fn componentize_with_report(
+        &self,
+        opts: &ComponentizeOpts<'_>,
+    ) -> Result<(Vec<u8>, BuildReport)> {
+        check_opts(opts)?;
+        let (resolve, world_id, _) = load_world(opts)?;
+
+        let shim = codegen::generate_shim(&resolve, world_id, !opts.env_shims.no_wasi_globals);
+        let resolver = module_resolution(opts)?;
+        let modules = Modules::new(opts.modules)?;
+        let wit_dylib = wit_dylib_module(&resolve, world_id)?;
+        let pre_wizer_component = self.link(selected_runtime(opts), &wit_dylib)?;
+        let init_options = init_options(opts)?;
+
+        let (mut component, mut report) = self
+            .wizer_init(
+                &pre_wizer_component,
+                &shim,
+                opts,
+                resolver,
+                modules,
+                &init_options,
+            )
+            .await?;
+        if opts.deny_warnings && !report.warnings.is_empty() {
+            return Err(anyhow!(
+                "initialization produced warnings:\n{}",
+                report.warnings.join("\n")
+            ));
+        }
+
+        if opts.core_module {
+            let stub_wasi = opts.stub_wasi;
+            let mut module = flatten(&component, |name| {
+                name == "local:init/module-loader"
+                    || (stub_wasi
+                        && (name == "wasi_snapshot_preview1" || name.starts_with("wasi:")))
+            })
+            .context("failed to flatten the component into a core module")?;
+            if let Some(level) = opts.optimize {
+                module =
+                    optimize_module(&module, level).context("failed to optimize core module")?;
+            }
+            module = opts
+                .metadata
+                .write(&module, &report.quickjs_version)
+                .context("failed to write module metadata")?;
+            append_custom_sections(&mut module, opts.custom_sections);
+            report.inspect(&module)?;
+            return Ok((module, report));
+        }
+
+        if let Some(level) = opts.optimize {
+            component = optimize(&component, level).context("failed to optimize component")?;
+        }
+
+        component = stub_internal_imports(&component)
+            .context("failed to stub internal module-loader import")?;
+
+        if opts.stub_wasi {
+            component = stub_wasi_imports(&component).context("failed to stub WASI imports")?;
+        }
+        component = opts
+            .metadata
+            .write(&component, &report.quickjs_version)
+            .context("failed to write component metadata")?;
+        append_custom_sections(&mut component, opts.custom_sections);
+
+        report.inspect(&component)?;
+        Ok((component, report))
+    }
+
+    fn link(&self, runtime: Runtime<'_>, wit_dylib: &[u8]) -> Result<Arc<[u8]>> {
+        let key = cache::digest((runtime_wasm(runtime), wit_dylib));
+        if let Some(linked) = self.linked.lock().unwrap().get(&key) {
+            return Ok(linked.clone());
+        }
+        let linked: Arc<[u8]> = crate::link(runtime, wit_dylib)?.into();
+        self.linked.lock().unwrap().insert(key, linked.clone());
+        Ok(linked)
+    }
+
+    fn prepare(&self, instrumented: &[u8], cache_dir: Option<&Path>) -> Result<Prepared> {
+        let key = cache::digest(instrumented);
+        if let Some(prepared) = self.prepared.lock().unwrap().get(&key) {
+            return Ok(prepared.clone());
+        }
+
+        let component = cache::compile(&self.engine, instrumented, cache_dir)?;
+        let mut linker = Linker::new(&self.engine);
+        linker.allow_shadowing(true);
+        linker.define_unknown_imports_as_traps(&component)?;
+        wasmtime_wasi::p2::add_to_linker_async(&mut linker)?;
+        wasmtime_wasi::p3::add_to_linker(&mut linker)?;
+
+        let prepared = Prepared { component, linker };
+        self.prepared.lock().unwrap().insert(key, prepared.clone());
+        Ok(prepared)
+    }
+
+    async fn wizer_init(
+        &self,
+        component: &[u8],
+        shim: &str,
+        opts: &ComponentizeOpts<'_>,
+        resolver: Option<Resolver>,
+        modules: Modules,
+        options: &InitOptions,
+    ) -> Result<(Vec<u8>, BuildReport)> {
+        let stdout = MemoryOutputPipe::new(10000);
+        let stderr = MemoryOutputPipe::new(10000);
+
+        let mut wasi = WasiCtxBuilder::new();
+        wasi.stdin(MemoryInputPipe::new(Bytes::new()))
+            .stdout(stdout.clone())
+            .stderr(stderr.clone())
+            .envs(opts.init_env)
+            .args(opts.init_args);
+        if opts.reproducible {
+            // QuickJS seeds `Math.random` from the wall clock, so pinning the
+            // clocks pins the seed too.
+            wasi.secure_random(StdRng::seed_from_u64(0))
+                .insecure_random(StdRng::seed_from_u64(1))
+                .insecure_random_seed(0)
+                .wall_clock(SteppingClock::default())
+                .monotonic_clock(SteppingClock::default());
+        }
+        for dir in opts.init_dirs {
+            wasi.preopened_dir(&dir.host, &dir.guest, DirPerms::READ, FilePerms::READ)
+                .with_context(|| {
+                    format!("failed to preopen init directory {}", dir.host.display())
+                })?;
+        }
+        let wasi = wasi.build();
+
+        let table = ResourceTable::new();
+        let mut store = Store::new(&self.engine, Ctx { wasi, table });
+
+        // The engine's epoch only advances while a build with a timeout runs,
+        // which may be another one sharing the engine, so every store checks
+        // the time itself.
+        let deadline = opts
+            .init_timeout
+            .map(|timeout| (timeout, Instant::now() + timeout));
+        store.set_epoch_deadline(1);
+        store.epoch_deadline_callback(move |_| match deadline {
+            Some((timeout, deadline)) if Instant::now() >= deadline => Err(anyhow!(
+                "Wizer initialization exceeded its time budget of {} ms",
+                timeout.as_millis()
+            )),
+            _ => Ok(UpdateDeadline::Continue(1)),
+        });
+        let _ticker = deadline.map(|_| EpochTicker::start(&self.engine));
+
+        // Instrumenting is cheap next to compiling, and its context borrows
+        // the component, so only the compilation is cached.
+        let wizer = Wizer::new();
+        let (cx, instrumented) = wizer.instrument_component(component)?;
+        let Prepared {
+            component: comp,
+            mut linker,
+        } = self.prepare(&instrumented, opts.cache_dir)?;
+
+        let entry = opts
+            .entry_module
+            .map(|name| modules.entry(name))
+            .transpose()?;
+        let (script, entry_path) = match &entry {
+            Some((path, source)) => (source.as_str(), Some(path.as_str())),
+            None => (opts.js_source, resolver.as_ref().map(Resolver::entry_path)),
+        };
+
+        register_module_loader(&mut linker, resolver.clone(), modules)?;
+
+        let instance = linker.instantiate_async(&mut store, &comp).await?;
+        let init = Init::new(&mut store, &instance)?;
+        let script = if opts.bytecode {
+            let bytecode = init
+                .call_compile(&mut store, script, entry_path, opts.strip_source)
+                .await?
+                .map_err(|e| anyhow!("{e}"))
+                .context("failed to compile JavaScript to QuickJS bytecode")?;
+            Script::Bytecode(bytecode)
+        } else {
+            Script::Source(script.to_string())
+        };
+        let result = init
+            .call_init(&mut store, shim, &script, entry_path, options)
+            .await?;
+        let init_stdout = String::from_utf8_lossy(&stdout.contents()).into_owned();
+        let init_stderr = String::from_utf8_lossy(&stderr.contents()).into_owned();
+        let warnings = result
+            .map_err(|e| anyhow!("{e}"))
+            .with_context(|| format!("{init_stdout}{init_stderr}"))?;
+        let quickjs_version = init.call_quickjs_version(&mut store).await?;
+
+        let component = wizer
+            .snapshot_component(
+                cx,
+                &mut WasmtimeWizerComponent {
+                    store: &mut store,
+                    instance,
+                },
+            )
+            .await?;
+
+        let report = BuildReport {
+            warnings,
+            init_stdout,
+            init_stderr,
+            quickjs_version,
+            ..BuildReport::default()
+        };
+        Ok((component, report))
+    }
+}
+
+/// A clock starting at zero that advances by a microsecond every time it is
+/// read, so initialization sees time pass but the same times on every build.
+#[derive(Default)]
+struct SteppingClock(AtomicU64);
+
+impl SteppingClock {
+    const STEP: u64 = 1_000;
+
+    fn tick(&self) -> u64 {
+        self.0.fetch_add(Self::STEP, Ordering::Relaxed)
+    }
+}
+
+impl HostWallClock for SteppingClock {
+    fn resolution(&self) -> Duration {
+        Duration::from_nanos(Self::STEP)
+    }
+
+    fn now(&self) -> Duration {
+        Duration::from_nanos(self.tick())
+    }
+}
+
+impl HostMonotonicClock for SteppingClock {
+    fn resolution(&self) -> u64 {
+        Self::STEP
+    }
+
+    fn now(&self) -> u64 {
+        self.tick()
+    }
+}
+
+/// Advances the engine's epoch every few milliseconds until dropped.
+struct EpochTicker(Arc<AtomicBool>);
+
+impl EpochTicker {
+    fn start(engine: &Engine) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+        let engine = engine.clone();
+        let stopped = stop.clone();
+        std::thread::spawn(move || {
+            while !stopped.load(Ordering::Relaxed) {
+                std::thread::sleep(Duration::from_millis(10));
+                engine.increment_epoch();
+            }
+        });
+        Self(stop)
+    }
+}
+
+impl Drop for EpochTicker {
+    fn drop(&mut self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Entries by the digest of their input, dropping the least recently used
+/// one beyond `Componentizer::CACHED_WORLDS`.
+struct Lru<T>(IndexMap<[u8; 32], T>);
+
+impl<T> Default for Lru<T> {
+    fn default() -> Self {
+        Self(IndexMap::new())
+    }
+}
+
+impl<T> Lru<T> {
+    fn get(&mut self, key: &[u8; 32]) -> Option<&T> {
+        let index = self.0.get_index_of(key)?;
+        self.0.move_index(index, self.0.len() - 1);
+        self.0.last().map(|(_, value)| value)
+    }
+
+    fn insert(&mut self, key: [u8; 32], value: T) {
+        self.0.insert(key, value);
+        if self.0.len() > Componentizer::CACHED_WORLDS {
+            self.0.shift_remove_index(0);
+        }
+    }
+}
@@ -0,0 +1,542 @@
+//! Flattening a snapshotted component into a single core module.
+//!
+//! The runtime is a shared library: the linker instantiates it, the WIT
+//! bindings, libc and the WASI adapter as separate core modules that reach
+//! each other's functions, memory and table through the component's core
+//! instances. Flattening replays those instantiations to find, for every
+//! import of every module, what it is bound to:
+//!
+//! - an export of another instance, which becomes an import `wasm-merge`
+//!   resolves when it merges the modules;
+//! - a function the component defines with `canon`, such as a lowered WIT
+//!   import, which stays an import under the module's own name for it;
+//! - a WASI preview 1 function, which stays an import instead of going
+//!   through the adapter, so the module runs on preview 1 hosts.
+//!
+//! Imports routed through the linker's indirect-call shim are followed to the
+//! function the fixup module puts in the shim's table. Only instances reached
+//! from the component's exports are merged, which leaves out the shim, the
+//! fixups and the adapter.
+
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::fs;
+use std::path::Path;
+
+use anyhow::{Context, Result, anyhow, bail};
+use wasm_encoder::{
+    CodeSection, Encode, ExportKind, ExportSection, Function, FunctionSection, Instruction, Module,
+    RawSection,
+};
+use wasmparser::{
+    BinaryReader, CanonicalFunction, CanonicalOption, ComponentAlias, ComponentType, ExternalKind,
+    Instance, Parser, Payload, TypeRef,
+};
+
+use crate::optimize::{FEATURE_ARGS, binaryen, temp_path};
+
+/// Module section ids.
+const TYPE_SECTION: u8 = 1;
+const IMPORT_SECTION: u8 = 2;
+const EXPORT_SECTION: u8 = 7;
+
+/// Export kind of a table.
+const TABLE_KIND: u8 = 1;
+
+/// Import module name of WASI preview 1 functions.
+const PREVIEW1: &str = "wasi_snapshot_preview1";
+
+/// Export the linker's shim module puts its table of indirect functions under.
+const SHIM_TABLE: &str = "$imports";
+
+/// Flatten `component` into one core module importing WASI directly.
+///
+/// Imports for which `stub` returns true, by import module name, are bound to
+/// functions that trap. `wasm-merge` is taken from the `WASM_MERGE`
+/// environment variable, or else looked up on `PATH`.
+pub(crate) fn flatten(component: &[u8], stub: impl Fn(&str) -> bool) -> Result<Vec<u8>> {
+    let graph = Graph::parse(component)?;
+
+    let mut exports = BTreeMap::new();
+    for (instance, name) in graph.roots()? {
+        if let Some((other, _)) = exports.insert(name.clone(), (instance, name.clone()))
+            && other != instance
+        {
+            bail!("two core instances export `{name}`");
+        }
+    }
+
+    let mut pending: Vec<u32> = exports.values().map(|(instance, _)| *instance).collect();
+    let mut merged = BTreeMap::new();
+    let mut stubs = Vec::new();
+    while let Some(instance) = pending.pop() {
+        if merged.contains_key(&instance) {
+            continue;
+        }
+        let (module, stubbed) = graph.rebind(instance, &stub, &mut pending)?;
+        if !stubbed.is_empty() {
+            stubs.push((instance, stub_module(graph.module(instance)?, &stubbed)?));
+        }
+        merged.insert(instance, module);
+    }
+
+    let inputs = stubs
+        .into_iter()
+        .map(|(instance, module)| (stub_name(instance), module))
+        .chain(
+            merged
+                .into_iter()
+                .map(|(instance, module)| (instance_name(instance), module)),
+        )
+        .collect::<Vec<_>>();
+    let module = wasm_merge(&inputs)?;
+
+    let exports = exports
+        .into_iter()
+        .map(|(name, (instance, export))| (export_name(instance, &export), name))
+        .collect();
+    rewrite(&module, |id, data| match id {
+        EXPORT_SECTION => keep_exports(data, &exports).map(Some),
+        _ => Ok(None),
+    })
+}
+
+/// Module name a merged instance is given.
+fn instance_name(instance: u32) -> String {
+    format!("core-instance-{instance}")
+}
+
+/// Module name of the traps standing in for an instance's stubbed imports.
+fn stub_name(instance: u32) -> String {
+    format!("core-instance-{instance}-stubs")
+}
+
+/// Export `name` of `instance`, renamed so no two merged modules collide.
+fn export_name(instance: u32, name: &str) -> String {
+    format!("{instance}:{name}")
+}
+
+/// What a core item of the component is.
+#[derive(Clone, Debug)]
+enum Item {
+    /// An export of a core instance
+    Export { instance: u32, name: String },
+    /// A function defined with `canon`
+    Canon,
+}
+
+/// What an import of a module is bound to once the instances are replayed.
+enum Target {
+    /// An export of a module that is merged too
+    Export { instance: u32, name: String },
+    /// Something only the host can provide
+    Host,
+}
+
+/// The top-level core definitions of a component.
+struct Graph<'a> {
+    modules: Vec<&'a [u8]>,
+    instances: Vec<Instance<'a>>,
+    /// Core items by sort, indexed by [`sort`]
+    items: [Vec<Item>; 5],
+    /// Core functions lifted or referenced by resource types
+    roots: Vec<(ExternalKind, u32)>,
+}
+
+/// Index of the core sort `kind` belongs to.
+fn sort(kind: ExternalKind) -> usize {
+    match kind {
+        ExternalKind::Table => 1,
+        ExternalKind::Memory => 2,
+        ExternalKind::Global => 3,
+        ExternalKind::Tag => 4,
+        _ => 0,
+    }
+}
+
+impl<'a> Graph<'a> {
+    fn parse(component: &'a [u8]) -> Result<Self> {
+        let mut graph = Self {
+            modules: Vec::new(),
+            instances: Vec::new(),
+            items: Default::default(),
+            roots: Vec::new(),
+        };
+        // Nested modules and components, whose definitions are their own.
+        let mut depth = 0usize;
+
+        for payload in Parser::new(0).parse_all(component) {
+            let payload = payload?;
+            match payload {
+                Payload::Version { .. } => depth += 1,
+                Payload::End(_) => depth -= 1,
+                _ if depth != 1 => {}
+                Payload::ModuleSection {
+                    unchecked_range, ..
+                } => graph.modules.push(&component[unchecked_range]),
+                Payload::InstanceSection(reader) => {
+                    for instance in reader {
+                        graph.instances.push(instance?);
+                    }
+                }
+                Payload::ComponentAliasSection(reader) => {
+                    for alias in reader {
+                        if let ComponentAlias::CoreInstanceExport {
+                            kind,
+                            instance_index,
+                            name,
+                        } = alias?
+                        {
+                            graph.items[sort(kind)].push(Item::Export {
+                                instance: instance_index,
+                                name: name.to_string(),
+                            });
+                        }
+                    }
+                }
+                Payload::ComponentCanonicalSection(reader) => {
+                    for function in reader {
+                        match function? {
+                            CanonicalFunction::Lift {
+                                core_func_index,
+                                options,
+                                ..
+                            } => {
+                                graph.roots.push((ExternalKind::Func, core_func_index));
+                                for option in options.iter() {
+                                    match option {
+                                        CanonicalOption::Realloc(index)
+                                        | CanonicalOption::PostReturn(index)
+                                        | CanonicalOption::Callback(index) => {
+                                            graph.roots.push((ExternalKind::Func, *index));
+                                        }
+                                        _ => {}
+                                    }
+                                }
+                            }
+                            _ => graph.items[0].push(Item::Canon),
+                        }
+                    }
+                }
+                Payload::ComponentTypeSection(reader) => {
+                    for ty in reader {
+                        if let ComponentType::Resource {
+                            dtor: Some(dtor), ..
+                        } = ty?
+                        {
+                            graph.roots.push((ExternalKind::Func, dtor));
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        Ok(graph)
+    }
+
+    /// The exports of merged instances the flattened module exports: what
+    /// the component lifts, and the memory.
+    fn roots(&self) -> Result<Vec<(u32, String)>> {
+        let memories = (0..self.items[sort(ExternalKind::Memory)].len() as u32)
+            .map(|index| (ExternalKind::Memory, index));
+        let mut roots = Vec::new();
+        for (kind, index) in self.roots.iter().copied().chain(memories) {
+            match self.resolve_item(kind, index)? {
+                Target::Export { instance, name } => roots.push((instance, name)),
+                Target::Host => bail!("the component exports a function it imports"),
+            }
+        }
+        Ok(roots)
+    }
+
+    fn module(&self, instance: u32) -> Result<&'a [u8]> {
+        match self.instance(instance)? {
+            Instance::Instantiate { module_index, .. } => self
+                .modules
+                .get(*module_index as usize)
+                .copied()
+                .ok_or_else(|| anyhow!("unknown core module {module_index}")),
+            Instance::FromExports(_) => bail!("core instance {instance} is not instantiated"),
+        }
+    }
+
+    fn instance(&self, instance: u32) -> Result<&Instance<'a>> {
+        self.instances
+            .get(instance as usize)
+            .ok_or_else(|| anyhow!("unknown core instance {instance}"))
+    }
+
+    fn resolve_item(&self, kind: ExternalKind, index: u32) -> Result<Target> {
+        match self.items[sort(kind)].get(index as usize) {
+            Some(Item::Export { instance, name }) => self.resolve_export(*instance, name),
+            Some(Item::Canon) => Ok(Target::Host),
+            None => bail!("unknown core {kind:?} {index}"),
+        }
+    }
+
+    /// What export `name` of `instance` is bound to.
+    fn resolve_export(&self, instance: u32, name: &str) -> Result<Target> {
+        match self.instance(instance)? {
+            Instance::Instantiate { .. } if self.is_shim(instance)? => {
+                self.resolve_shim(instance, name)
+            }
+            Instance::Instantiate { .. } => Ok(Target::Export {
+                instance,
+                name: name.to_string(),
+            }),
+            Instance::FromExports(exports) => {
+                let export = exports
+                    .iter()
+                    .find(|export| export.name == name)
+                    .ok_or_else(|| anyhow!("core instance {instance} has no export `{name}`"))?;
+                self.resolve_item(export.kind, export.index)
+            }
+        }
+    }
+
+    fn is_shim(&self, instance: u32) -> Result<bool> {
+        for (id, data) in sections(self.module(instance)?)? {
+            if id == EXPORT_SECTION {
+                return Ok(exports(data)?
+                    .iter()
+                    .any(|(name, kind, _)| *name == SHIM_TABLE && *kind == TABLE_KIND));
+            }
+        }
+        Ok(false)
+    }
+
+    /// What the fixup module puts into the slot of `shim` that its export
+    /// `name` calls through.
+    fn resolve_shim(&self, shim: u32, name: &str) -> Result<Target> {
+        for instance in &self.instances {
+            let Instance::Instantiate { args, .. } = instance else {
+                continue;
+            };
+            let Some(arg) = args.iter().find(|arg| arg.name.is_empty()) else {
+                continue;
+            };
+            let Instance::FromExports(exports) = self.instance(arg.index)? else {
+                continue;
+            };
+            let fills_shim = exports.iter().any(|export| {
+                export.name == SHIM_TABLE
+                    && matches!(
+                        self.items[sort(export.kind)].get(export.index as usize),
+                        Some(Item::Export { instance, name })
+                            if *instance == shim && name == SHIM_TABLE
+                    )
+            });
+            if !fills_shim {
+                continue;
+            }
+            let export = exports
+                .iter()
+                .find(|export| export.name == name)
+                .ok_or_else(|| anyhow!("the fixups leave shim slot `{name}` empty"))?;
+            return self.resolve_item(export.kind, export.index);
+        }
+        bail!("no fixups fill the shim of core instance {shim}")
+    }
+
+    /// The module of `instance` with its imports renamed for `wasm-merge` and
+    /// its exports renamed apart, pushing the instances it imports from onto
+    /// `pending`. Also returns the types of the imports bound to traps.
+    fn rebind(
+        &self,
+        instance: u32,
+        stub: &impl Fn(&str) -> bool,
+        pending: &mut Vec<u32>,
+    ) -> Result<(Vec<u8>, Vec<u32>)> {
+        let Instance::Instantiate { args, .. } = self.instance(instance)? else {
+            bail!("core instance {instance} is not instantiated");
+        };
+        let mut stubbed = Vec::new();
+
+        let module = rewrite(self.module(instance)?, |id, data| match id {
+            IMPORT_SECTION => {
+                let mut section = Vec::new();
+                let imports = imports(data)?;
+                (imports.len() as u32).encode(&mut section);
+                for (module, name, ty) in imports {
+                    let target = if module == PREVIEW1 {
+                        Target::Host
+                    } else {
+                        let arg = args.iter().find(|arg| arg.name == module).ok_or_else(|| {
+                            anyhow!("core instance {instance} is missing import `{module}`")
+                        })?;
+                        self.resolve_export(arg.index, name)?
+                    };
+                    let (module, name) = match target {
+                        Target::Export { instance, name } => {
+                            pending.push(instance);
+                            (instance_name(instance), export_name(instance, &name))
+                        }
+                        Target::Host if stub(module) => {
+                            let TypeRef::Func(ty) = BinaryReader::new(ty, 0).read()? else {
+                                bail!("cannot stub `{module}` import `{name}`: not a function");
+                            };
+                            stubbed.push(ty);
+                            (stub_name(instance), (stubbed.len() - 1).to_string())
+                        }
+                        Target::Host => (module.to_string(), name.to_string()),
+                    };
+                    module.encode(&mut section);
+                    name.encode(&mut section);
+                    section.extend_from_slice(ty);
+                }
+                Ok(Some(section))
+            }
+            EXPORT_SECTION => {
+                let renamed = exports(data)?
+                    .into_iter()
+                    .map(|(name, kind, index)| (export_name(instance, name), kind, index))
+                    .collect::<Vec<_>>();
+                Ok(Some(export_section(renamed.iter().map(
+                    |(name, kind, index)| (name.as_str(), *kind, *index),
+                ))))
+            }
+            _ => Ok(None),
+        })?;
+
+        Ok((module, stubbed))
+    }
+}
+
+/// The sections of `module`, as (id, contents).
+fn sections(module: &[u8]) -> Result<Vec<(u8, &[u8])>> {
+    let mut reader = BinaryReader::new(module, 0);
+    if reader.read_bytes(8)? != b"\0asm\x01\0\0\0" {
+        bail!("expected a core module");
+    }
+
+    let mut sections = Vec::new();
+    while !reader.eof() {
+        let id = reader.read_u8()?;
+        let size = reader.read_var_u32()? as usize;
+        sections.push((id, reader.read_bytes(size)?));
+    }
+    Ok(sections)
+}
+
+/// Copy `module`, replacing the contents of each section for which `f`
+/// returns new ones.
+fn rewrite(
+    module: &[u8],
+    mut f: impl FnMut(u8, &[u8]) -> Result<Option<Vec<u8>>>,
+) -> Result<Vec<u8>> {
+    let mut output = Module::new();
+    for (id, data) in sections(module)? {
+        match f(id, data)? {
+            Some(data) => output.section(&RawSection { id, data: &data }),
+            None => output.section(&RawSection { id, data }),
+        };
+    }
+    Ok(output.finish())
+}
+
+/// The entries of an import section, as (module, name, encoded type).
+fn imports(section: &[u8]) -> Result<Vec<(&str, &str, &[u8])>> {
+    let mut reader = BinaryReader::new(section, 0);
+    let count = reader.read_var_u32()?;
+    let mut imports = Vec::with_capacity(count as usize);
+    for _ in 0..count {
+        let module = reader.read_string()?;
+        let name = reader.read_string()?;
+        let start = reader.original_position();
+        reader.read::<TypeRef>()?;
+        imports.push((module, name, &section[start..reader.original_position()]));
+    }
+    Ok(imports)
+}
+
+/// The entries of an export section, as (name, kind, index).
+fn exports(section: &[u8]) -> Result<Vec<(&str, u8, u32)>> {
+    let mut reader = BinaryReader::new(section, 0);
+    let count = reader.read_var_u32()?;
+    let mut exports = Vec::with_capacity(count as usize);
+    for _ in 0..count {
+        exports.push((
+            reader.read_string()?,
+            reader.read_u8()?,
+            reader.read_var_u32()?,
+        ));
+    }
+    Ok(exports)
+}
+
+/// Encode `exports` as the contents of an export section.
+fn export_section<'a>(exports: impl ExactSizeIterator<Item = (&'a str, u8, u32)>) -> Vec<u8> {
+    let mut section = Vec::new();
+    (exports.len() as u32).encode(&mut section);
+    for (name, kind, index) in exports {
+        name.encode(&mut section);
+        section.push(kind);
+        index.encode(&mut section);
+    }
+    section
+}
+
+/// Keep only the exports of the merged module named in `names`, under the
+/// names they map to.
+fn keep_exports(section: &[u8], names: &BTreeMap<String, String>) -> Result<Vec<u8>> {
+    let kept = exports(section)?
+        .into_iter()
+        .filter_map(|(name, kind, index)| Some((names.get(name)?.as_str(), kind, index)))
+        .collect::<Vec<_>>();
+    if kept.len() != names.len() {
+        bail!("wasm-merge dropped exports of the merged modules");
+    }
+    Ok(export_section(kept.into_iter()))
+}
+
+/// A module exporting, under their position in `types`, functions of those
+/// types from the type section of `module` that trap.
+fn stub_module(module: &[u8], types: &[u32]) -> Result<Vec<u8>> {
+    let mut output = Module::new();
+    if let Some((id, data)) = sections(module)?
+        .into_iter()
+        .find(|(id, _)| *id == TYPE_SECTION)
+    {
+        output.section(&RawSection { id, data });
+    }
+
+    let mut funcs = FunctionSection::new();
+    let mut exports = ExportSection::new();
+    let mut code = CodeSection::new();
+    for (index, ty) in types.iter().enumerate() {
+        funcs.function(*ty);
+        exports.export(&index.to_string(), ExportKind::Func, index as u32);
+        let mut body = Function::new([]);
+        body.instruction(&Instruction::Unreachable);
+        body.instruction(&Instruction::End);
+        code.function(&body);
+    }
+    output.section(&funcs);
+    output.section(&exports);
+    output.section(&code);
+    Ok(output.finish())
+}
+
+/// Merge `modules`, given as (name, module), with `wasm-merge`.
+fn wasm_merge(modules: &[(String, Vec<u8>)]) -> Result<Vec<u8>> {
+    let dir = temp_path("d");
+    fs::create_dir(&dir).with_context(|| format!("failed to create {}", dir.display()))?;
+    let merged = merge_in(&dir, modules);
+    let _ = fs::remove_dir_all(&dir);
+    merged
+}
+
+fn merge_in(dir: &Path, modules: &[(String, Vec<u8>)]) -> Result<Vec<u8>> {
+    let mut args = Vec::new();
+    for (index, (name, module)) in modules.iter().enumerate() {
+        let path = dir.join(format!("{index}.wasm"));
+        fs::write(&path, module).with_context(|| format!("failed to write {}", path.display()))?;
+        args.extend([OsString::from(path), OsString::from(name)]);
+    }
+    let output = dir.join("merged.wasm");
+    args.extend(FEATURE_ARGS.iter().map(OsString::from));
+    args.extend([OsString::from("-o"), OsString::from(&output)]);
+    binaryen("wasm-merge", "WASM_MERGE", &args)?;
+    fs::read(&output).with_context(|| format!("failed to read {}", output.display()))
+}
@@ -0,0 +1,101 @@
+//! Run generated components under wasmtime.
+//!
+//! A thin convenience layer for tools and tests that want to call into a
+//! component produced by [`componentize`](crate::componentize) without wiring
+//! up an engine, linker and WASI context themselves. Exports are invoked
+//! dynamically with [`Val`]s.
+
+use anyhow::{Context, Result, anyhow};
+use wasmtime::component::{Component, Func, Instance, Linker, ResourceTable, Val};
+use wasmtime::{Config, Engine, Store};
+use wasmtime_wasi::WasiCtxBuilder;
+
+use crate::Ctx;
+
+/// An instantiated component with its own store.
+pub struct ComponentInstance {
+    store: Store<Ctx>,
+    instance: Instance,
+}
+
+impl ComponentInstance {
+    /// Instantiate `wasm` with a default WASI context that inherits the host's
+    /// stdio.
+    pub async fn new(wasm: &[u8]) -> Result<Self> {
+        let mut wasi = WasiCtxBuilder::new();
+        wasi.inherit_stdio();
+        Self::with_wasi(wasm, wasi).await
+    }
+
+    /// Instantiate `wasm` with a caller-configured WASI context, e.g. to set
+    /// environment variables, capture stdout or preopen directories.
+    pub async fn with_wasi(wasm: &[u8], mut wasi: WasiCtxBuilder) -> Result<Self> {
+        let mut config = Config::new();
+        config.wasm_component_model(true);
+        config.wasm_component_model_async(true);
+        config.wasm_component_model_error_context(true);
+
+        let engine = Engine::new(&config)?;
+        let component = Component::new(&engine, wasm).context("invalid component")?;
+
+        let mut linker = Linker::new(&engine);
+        wasmtime_wasi::p2::add_to_linker_async(&mut linker)?;
+        wasmtime_wasi::p3::add_to_linker(&mut linker)?;
+
+        let ctx = Ctx {
+            wasi: wasi.build(),
+            table: ResourceTable::new(),
+        };
+        let mut store = Store::new(&engine, ctx);
+        let instance = linker
+            .instantiate_async(&mut store, &component)
+            .await
+            .context("failed to instantiate component")?;
+
+        Ok(Self { store, instance })
+    }
+
+    /// Call an export and return its results.
+    ///
+    /// Freestanding exports are named as in WIT (`greet`); interface exports
+    /// are qualified with the interface (`my:pkg/api#greet`).
+    pub async fn call(&mut self, name: &str, params: &[Val]) -> Result<Vec<Val>> {
+        let func = self.func(name)?;
+        let result_count = func.ty(&self.store).results().len();
+
+        let mut results = vec![Val::Bool(false); result_count];
+        func.call_async(&mut self.store, params, &mut results)
+            .await
+            .with_context(|| format!("calling `{name}` failed"))?;
+        Ok(results)
+    }
+
+    /// Call an export returning a single value.
+    pub async fn call1(&mut self, name: &str, params: &[Val]) -> Result<Val> {
+        self.call(name, params)
+            .await?
+            .into_iter()
+            .next()
+            .ok_or_else(|| anyhow!("export `{name}` returns no value"))
+    }
+
+    fn func(&mut self, name: &str) -> Result<Func> {
+        let not_found = || anyhow!("export `{name}` not found");
+        let index = match name.split_once('#') {
+            Some((iface, func)) => {
+                let iface = self
+                    .instance
+                    .get_export_index(&mut self.store, None, iface)
+                    .ok_or_else(not_found)?;
+                self.instance
+                    .get_export_index(&mut self.store, Some(&iface), func)
+            }
+            None => self.instance.get_export_index(&mut self.store, None, name),
+        }
+        .ok_or_else(not_found)?;
+
+        self.instance
+            .get_func(&mut self.store, index)
+            .ok_or_else(|| anyhow!("export `{name}` is not a function"))
+    }
+}
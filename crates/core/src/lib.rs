@@ -1,20 +1,41 @@
+mod builder;
+mod cache;
 pub mod codegen;
+mod componentizer;
+mod flatten;
+#[cfg(feature = "host")]
+pub mod host;
+mod metadata;
+mod modules;
+mod names;
+mod optimize;
+mod plan;
+mod report;
 mod resolver;
 pub mod stubwasi;
+mod support;
 
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
 
 use anyhow::{Context, Result, anyhow};
-use bytes::Bytes;
+use modules::Modules;
 use resolver::Resolver;
-use stubwasi::{stub_internal_imports, stub_wasi_imports};
 use wasi_preview1_component_adapter_provider::WASI_SNAPSHOT_PREVIEW1_REACTOR_ADAPTER;
-use wasmtime::component::{Component as WasmtimeComponent, Linker, ResourceTable};
-use wasmtime::{Config, Engine, Store};
-use wasmtime_wasi::p2::pipe::{MemoryInputPipe, MemoryOutputPipe};
-use wasmtime_wasi::{WasiCtx, WasiCtxBuilder, WasiCtxView, WasiView};
-use wasmtime_wizer::{WasmtimeWizerComponent, Wizer};
-use wit_parser::Resolve;
+use wasm_encoder::{ComponentSectionId, CustomSection, Encode};
+use wasmtime::component::{Linker, ResourceTable};
+use wasmtime::{Config, Engine};
+use wasmtime_wasi::{WasiCtx, WasiCtxView, WasiView};
+use wit_parser::decoding::{DecodedWasm, decode};
+use wit_parser::{PackageId, Resolve, WorldId};
+
+pub use builder::ComponentizeBuilder;
+pub use componentizer::Componentizer;
+pub use metadata::Metadata;
+pub use optimize::OptLevel;
+pub use plan::{BuildPlan, ImportAction, PlannedImport, PlannedLibrary, plan};
+pub use report::{BuildReport, ModuleReport};
 
 include!(concat!(env!("OUT_DIR"), "/output.rs"));
 
@@ -42,20 +63,507 @@ impl WasiView for Ctx {
 pub struct ComponentizeOpts<'a> {
     /// Path to the WIT file or directory
     pub wit_path: &'a Path,
-    /// JavaScript source code
+    /// WIT source parsed instead of reading `wit_path`, which then only names
+    /// the source in error messages. Must be a single package.
+    pub wit_source: Option<&'a str>,
+    /// Binary WIT package, as encoded by `wasm-tools component wit --wasm`,
+    /// decoded instead of reading `wit_path`
+    pub wit_package: Option<&'a [u8]>,
+    /// JavaScript source code of the entry module; empty when `entry_module`
+    /// names it instead
     pub js_source: &'a str,
     /// Path to the JavaScript entry file, used as the base for resolving imports
     pub js_path: Option<&'a Path>,
     /// Host directory exposed read-only during Wizer for resolving imported modules
     pub module_root: Option<&'a Path>,
+    /// Environment variables visible to the JavaScript during Wizer
+    /// initialization, as (name, value) pairs
+    pub init_env: &'a [(String, String)],
+    /// Command-line arguments visible to the JavaScript during Wizer
+    /// initialization
+    pub init_args: &'a [String],
+    /// Host directories preopened read-only during Wizer initialization
+    pub init_dirs: &'a [InitDir],
+    /// Additional JavaScript modules as (name, source) pairs, importable by
+    /// the entry module and each other without touching the filesystem
+    pub modules: &'a [(String, String)],
+    /// Name of the module in `modules` to evaluate as the entry module instead
+    /// of `js_source`, so a build can come entirely from memory
+    pub entry_module: Option<&'a str>,
+    /// Scripts as (name, source) pairs, evaluated in order in the global scope
+    /// after the polyfills and before the entry module
+    pub preludes: &'a [(String, String)],
     /// World name to use from the WIT (None = default world)
     pub world_name: Option<&'a str>,
     /// Stub all WASI imports with traps
     pub stub_wasi: bool,
     /// Disable automatic garbage collection in the QuickJS runtime
     pub disable_gc: bool,
+    /// Drop the source text QuickJS keeps for `Function.prototype.toString`
+    /// from the snapshot
+    pub strip_source: bool,
+    /// Compile the entry module to QuickJS bytecode before Wizer and evaluate
+    /// that, so initialization never sees its source
+    pub bytecode: bool,
+    /// Compile the JavaScript module during Wizer but evaluate it on the first
+    /// export call, so its top-level code sees the runtime environment
+    pub lazy_eval: bool,
+    /// Run a full garbage collection cycle after every this many export calls
+    pub gc_interval: Option<u32>,
+    /// Start an automatic garbage collection cycle once this many bytes were
+    /// allocated since the last one
+    pub gc_threshold: Option<u32>,
+    /// Reject lists (in elements) and strings (in UTF-8 bytes) longer than this
+    /// when lowering values from JavaScript, with a catchable `RangeError`
+    pub max_list_length: Option<u32>,
+    /// Cap the QuickJS heap at this many bytes, so allocations beyond it throw
+    /// an out-of-memory error instead of growing linear memory
+    pub memory_limit: Option<u32>,
+    /// Print heap usage to stderr after every this many export calls; needs WASI
+    pub memory_report: Option<u32>,
+    /// Trap when a call into JavaScript runs more than about this many bytecode
+    /// instructions
+    pub max_instructions: Option<u64>,
+    /// Fail the build when Wizer initialization, including top-level code and
+    /// priming, runs longer than this
+    pub init_timeout: Option<Duration>,
+    /// Check values lowered from JavaScript against their WIT type and throw a
+    /// `TypeError` naming the mismatch, instead of coercing or trapping
+    pub strict_types: bool,
+    /// Pass top-level `result` values as `{ tag, val }` objects instead of returning
+    /// the `ok` payload and throwing the `err` one. Worlds that get the `fetch`,
+    /// `fs`, `net`, `keyvalue` or `config` globals or the incoming-handler
+    /// adapter need `throwing_imports` too
+    pub tagged_results: bool,
+    /// With `tagged_results`, still return the `ok` payload of imports and throw
+    /// an `Error` carrying the `err` one
+    pub throwing_imports: bool,
+    /// Wrap out-of-range integers lowered from JavaScript, as `as` casts do,
+    /// instead of throwing a `RangeError`
+    pub wrap_integers: bool,
+    /// Reject NaN and infinite floats lowered from JavaScript with a `RangeError`
+    pub finite_floats: bool,
+    /// Warn on stderr when a number lowered as an `f32` loses precision; needs WASI
+    pub warn_f32_rounding: bool,
+    /// Log every value lifted into or lowered out of JavaScript to stderr, with
+    /// its WIT type and function; needs WASI
+    pub trace_values: bool,
+    /// Feature flags exposed to JavaScript through the read-only `__FEATURES__` set
+    pub features: &'a [String],
+    /// Globals installed on top of plain QuickJS: polyfills and the ones
+    /// generated from the world's WASI imports
+    pub env_shims: EnvShims<'a>,
+    /// Fail the build on initialization warnings, such as exports whose
+    /// parameter count differs from their WIT signature, instead of only
+    /// listing them in the [`BuildReport`]
+    pub deny_warnings: bool,
+    /// What to do with exports declaring more or fewer parameters than their
+    /// WIT signature
+    pub arity_mismatch: ArityMismatch,
+    /// Replace QuickJS's `Math.random` (None = keep the built-in generator)
+    pub math_random: Option<MathRandom>,
+    /// Exports to call once during Wizer initialization so the snapshot starts
+    /// warm
+    pub prime: &'a [PrimeCall],
+    /// Freeze the intrinsics, polyfills and WIT import objects once the
+    /// JavaScript module is evaluated
+    pub harden: bool,
+    /// Which shortened names import a versioned WIT interface
+    pub version_aliasing: VersionAliasing,
+    /// Extra import specifiers, each naming an imported WIT interface
+    pub import_aliases: &'a [ImportAlias],
+    /// Names WIT functions have in JavaScript
+    pub naming: Naming,
+    /// JavaScript names for individual WIT functions, overriding `naming`
+    pub renames: &'a [Rename],
+    /// Record or replay synchronous import calls through a capture file
+    pub import_log: Option<ImportLog<'a>>,
+    /// Run the core modules of the snapshotted component through `wasm-opt`,
+    /// dropping the code only initialization needed
+    pub optimize: Option<OptLevel>,
+    /// Emit one core module instead of a component, for engines without
+    /// component-model support: the snapshotted modules are merged, and import
+    /// WASI directly instead of through the preview 1 adapter. Needs a
+    /// non-async runtime and Binaryen's `wasm-merge`
+    pub core_module: bool,
+    /// Custom sections to append to the component, as (name, contents), such
+    /// as source maps or build provenance
+    pub custom_sections: &'a [(String, Vec<u8>)],
+    /// Name, version and description to record in the component, or whether
+    /// to leave out its `producers` and metadata sections
+    pub metadata: Metadata<'a>,
+    /// Make the output depend on nothing but the inputs: initialization sees
+    /// stepping clocks that start at the Unix epoch and seeded randomness, so
+    /// identical inputs build byte-identical components
+    pub reproducible: bool,
+    /// Directory caching compiled components between builds, so rebuilding
+    /// after a JavaScript-only change only re-runs Wizer initialization.
+    ///
+    /// Cached compilations are native code that builds load and run, so the
+    /// directory must only be writable by users trusted to run code here
+    pub cache_dir: Option<&'a Path>,
     /// Runtime to embed before Wizer initialization
     pub runtime: Runtime<'a>,
+    /// Embed the debug runtime in place of the built-in one: unoptimized, with
+    /// assertions and overflow checks on and function names kept, for
+    /// diagnosing a failing component. Needs the `debug-runtime` feature
+    pub debug_runtime: bool,
+}
+
+/// Polyfills bundled with the runtime, in the order they are installed.
+pub const POLYFILLS: &[&str] = &[
+    "url",
+    "encoding",
+    "structured-clone",
+    "events",
+    "abort",
+    "streams",
+];
+
+/// Polyfills installed unless [`EnvShims::no_default_polyfills`] is set.
+pub const DEFAULT_POLYFILLS: &[&str] = &["url", "encoding", "structured-clone", "events", "abort"];
+
+/// Globals a component gets on top of plain QuickJS.
+///
+/// The default installs [`DEFAULT_POLYFILLS`] and every global generated from
+/// the world's WASI imports.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct EnvShims<'a> {
+    /// Polyfills from [`POLYFILLS`] to install in addition to the defaults
+    pub polyfills: &'a [String],
+    /// Install only the polyfills listed in `polyfills`, not [`DEFAULT_POLYFILLS`]
+    pub no_default_polyfills: bool,
+    /// Leave out the globals generated from the world's WASI imports, such as
+    /// `fetch`, `env`, `fs` and the wall-clock `Date`, keeping plain QuickJS
+    /// ones; the imports themselves stay importable
+    pub no_wasi_globals: bool,
+}
+
+/// Resolve the requested polyfills against the defaults, in install order.
+fn selected_polyfills(shims: &EnvShims<'_>) -> Result<Vec<String>> {
+    if let Some(unknown) = shims
+        .polyfills
+        .iter()
+        .find(|name| !POLYFILLS.contains(&name.as_str()))
+    {
+        return Err(anyhow!(
+            "unknown polyfill `{unknown}`; expected one of: {}",
+            POLYFILLS.join(", ")
+        ));
+    }
+
+    Ok(POLYFILLS
+        .iter()
+        .filter(|name| {
+            (!shims.no_default_polyfills && DEFAULT_POLYFILLS.contains(name))
+                || shims.polyfills.iter().any(|p| p == *name)
+        })
+        .map(|name| name.to_string())
+        .collect())
+}
+
+/// Import call capture mode.
+///
+/// Paths are guest paths, resolved through WASI when the component runs, so
+/// they must live in a directory the host preopens. Worlds with a synchronous
+/// import passing a resource, stream, future or error context are rejected.
+#[derive(Clone, Copy, Debug)]
+pub enum ImportLog<'a> {
+    /// Append every synchronous import call and its result to the file.
+    Record(&'a str),
+    /// Satisfy synchronous import calls from a previously recorded file.
+    Replay(&'a str),
+}
+
+impl From<ImportLog<'_>> for ImportLogMode {
+    fn from(log: ImportLog<'_>) -> Self {
+        match log {
+            ImportLog::Record(path) => ImportLogMode::Record(path.to_string()),
+            ImportLog::Replay(path) => ImportLogMode::Replay(path.to_string()),
+        }
+    }
+}
+
+/// Source of `Math.random()` values.
+///
+/// QuickJS seeds its built-in generator when the runtime is created, before
+/// the Wizer snapshot, so every instance of a component otherwise replays the
+/// same sequence.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MathRandom {
+    /// Draw fresh entropy from `wasi:random` on every call.
+    Wasi,
+    /// Generate a reproducible sequence from this seed, restarting in every
+    /// new instance.
+    Seeded(u64),
+}
+
+impl FromStr for MathRandom {
+    type Err = anyhow::Error;
+
+    /// Parse `wasi` or `seed:<N>`.
+    fn from_str(s: &str) -> Result<Self> {
+        if s == "wasi" {
+            return Ok(Self::Wasi);
+        }
+        s.strip_prefix("seed:")
+            .and_then(|seed| seed.parse().ok())
+            .map(Self::Seeded)
+            .ok_or_else(|| {
+                anyhow!("invalid Math.random source `{s}`; expected `wasi` or `seed:<N>`")
+            })
+    }
+}
+
+impl From<MathRandom> for RandomSource {
+    fn from(source: MathRandom) -> Self {
+        match source {
+            MathRandom::Wasi => RandomSource::Wasi,
+            MathRandom::Seeded(seed) => RandomSource::Seed(seed),
+        }
+    }
+}
+
+/// An export called once during Wizer initialization.
+///
+/// Lazily compiled functions and caches filled by the call are captured in the
+/// snapshot, so the first call in production starts warm. Heap changes made by
+/// the call are kept too, and it runs against the build-time WASI context, so
+/// only prime exports whose side effects are safe to bake in.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PrimeCall {
+    /// Export name: a world function, or `<interface>#<function>`
+    pub name: String,
+    /// Arguments as a JSON array, passed to the JavaScript function as is
+    pub args: String,
+}
+
+impl FromStr for PrimeCall {
+    type Err = anyhow::Error;
+
+    /// Parse `<export>` or `<export>=<JSON array>`.
+    fn from_str(s: &str) -> Result<Self> {
+        let (name, args) = s.split_once('=').unwrap_or((s, "[]"));
+        if name.is_empty() {
+            return Err(anyhow!(
+                "invalid prime call `{s}`; expected `<export>=<JSON array>`"
+            ));
+        }
+        Ok(Self {
+            name: name.to_string(),
+            args: args.to_string(),
+        })
+    }
+}
+
+/// Which shortened import specifiers resolve to a versioned WIT interface,
+/// such as `wasi:io/streams@0.2.6`.
+///
+/// A shortened name matching several imported versions is always an error.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum VersionAliasing {
+    /// Only the full name
+    None,
+    /// The full name, or the name without the patch version
+    /// (`wasi:io/streams@0.2`)
+    StripPatch,
+    /// Any of the above, or the name without a version (`wasi:io/streams`)
+    #[default]
+    StripAll,
+}
+
+impl FromStr for VersionAliasing {
+    type Err = anyhow::Error;
+
+    /// Parse `none`, `strip-patch` or `strip-all`.
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "none" => Ok(Self::None),
+            "strip-patch" => Ok(Self::StripPatch),
+            "strip-all" => Ok(Self::StripAll),
+            _ => Err(anyhow!(
+                "invalid version aliasing `{s}`; expected `none`, `strip-patch` or `strip-all`"
+            )),
+        }
+    }
+}
+
+impl From<VersionAliasing> for VersionMatch {
+    fn from(aliasing: VersionAliasing) -> Self {
+        match aliasing {
+            VersionAliasing::None => VersionMatch::Exact,
+            VersionAliasing::StripPatch => VersionMatch::StripPatch,
+            VersionAliasing::StripAll => VersionMatch::StripAll,
+        }
+    }
+}
+
+/// What to do with an export whose JavaScript function declares more or fewer
+/// parameters (`Function.length`) than its WIT signature.
+///
+/// Exports are always called with exactly the WIT arity: extra parameters are
+/// `undefined`, and arguments past the declared ones only reach parameters
+/// with defaults, a rest parameter or `arguments`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ArityMismatch {
+    /// Accept it silently
+    Ignore,
+    /// List it in the [`BuildReport`] warnings
+    #[default]
+    Warn,
+    /// Fail the build
+    Error,
+}
+
+impl FromStr for ArityMismatch {
+    type Err = anyhow::Error;
+
+    /// Parse `ignore`, `warn` or `error`.
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "ignore" => Ok(Self::Ignore),
+            "warn" => Ok(Self::Warn),
+            "error" => Ok(Self::Error),
+            _ => Err(anyhow!(
+                "invalid arity mismatch mode `{s}`; expected `ignore`, `warn` or `error`"
+            )),
+        }
+    }
+}
+
+impl From<ArityMismatch> for ArityPolicy {
+    fn from(mode: ArityMismatch) -> Self {
+        match mode {
+            ArityMismatch::Ignore => ArityPolicy::Ignore,
+            ArityMismatch::Warn => ArityPolicy::Warn,
+            ArityMismatch::Error => ArityPolicy::Deny,
+        }
+    }
+}
+
+/// Names WIT functions and resource methods have in JavaScript, both for
+/// imports and for the exports the module implements.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Naming {
+    /// camelCase names, such as `getRandomU64`
+    #[default]
+    CamelCase,
+    /// The WIT names as they are, such as `get-random-u64`
+    KebabCase,
+    /// Both: imports carry both names, and an export may use either
+    Both,
+}
+
+impl FromStr for Naming {
+    type Err = anyhow::Error;
+
+    /// Parse `camel`, `kebab` or `both`.
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "camel" => Ok(Self::CamelCase),
+            "kebab" => Ok(Self::KebabCase),
+            "both" => Ok(Self::Both),
+            _ => Err(anyhow!(
+                "invalid naming `{s}`; expected `camel`, `kebab` or `both`"
+            )),
+        }
+    }
+}
+
+impl From<Naming> for NamingPolicy {
+    fn from(naming: Naming) -> Self {
+        match naming {
+            Naming::CamelCase => NamingPolicy::CamelCase,
+            Naming::KebabCase => NamingPolicy::KebabCase,
+            Naming::Both => NamingPolicy::Both,
+        }
+    }
+}
+
+/// A JavaScript name for one WIT function, so an existing module can be
+/// componentized without renaming its functions.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Rename {
+    /// WIT function name, such as `handle`, `[method]counter.increment` or
+    /// `local:app/api#handle` to pick the one from an interface
+    pub function: String,
+    /// Name of the function in JavaScript
+    pub js_name: String,
+}
+
+impl FromStr for Rename {
+    type Err = anyhow::Error;
+
+    /// Parse `<function>=<js-name>`.
+    fn from_str(s: &str) -> Result<Self> {
+        match s.split_once('=') {
+            Some((function, js_name)) if !function.is_empty() && !js_name.is_empty() => Ok(Self {
+                function: function.to_string(),
+                js_name: js_name.to_string(),
+            }),
+            _ => Err(anyhow!(
+                "invalid rename `{s}`; expected `<function>=<js-name>`"
+            )),
+        }
+    }
+}
+
+/// A host directory preopened read-only during Wizer initialization, so
+/// top-level JavaScript can read data files into the snapshot.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InitDir {
+    /// Directory on the host
+    pub host: PathBuf,
+    /// Path the directory is preopened under in the guest
+    pub guest: String,
+}
+
+impl FromStr for InitDir {
+    type Err = anyhow::Error;
+
+    /// Parse `<host>[::<guest>]`, preopening the directory under its host path
+    /// if no guest path is given.
+    fn from_str(s: &str) -> Result<Self> {
+        let (host, guest) = s.split_once("::").unwrap_or((s, s));
+        if host.is_empty() || guest.is_empty() {
+            return Err(anyhow!(
+                "invalid init directory `{s}`; expected `<host>[::<guest>]`"
+            ));
+        }
+        Ok(Self {
+            host: PathBuf::from(host),
+            guest: guest.to_string(),
+        })
+    }
+}
+
+/// An extra import specifier for an imported WIT interface, for example to
+/// keep `import streams from "streams"` working across version bumps.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ImportAlias {
+    /// Specifier used in JavaScript imports
+    pub specifier: String,
+    /// Full name of the imported interface, with its version
+    pub interface: String,
+}
+
+impl FromStr for ImportAlias {
+    type Err = anyhow::Error;
+
+    /// Parse `<specifier>=<interface>`.
+    fn from_str(s: &str) -> Result<Self> {
+        match s.split_once('=') {
+            Some((specifier, interface)) if !specifier.is_empty() && !interface.is_empty() => {
+                Ok(Self {
+                    specifier: specifier.to_string(),
+                    interface: interface.to_string(),
+                })
+            }
+            _ => Err(anyhow!(
+                "invalid import alias `{s}`; expected `<specifier>=<interface>`"
+            )),
+        }
+    }
 }
 
 /// QuickJS runtime variant to embed in the generated component.
@@ -103,54 +611,312 @@ pub fn default_builtin_runtime() -> Runtime<'static> {
 }
 
 /// Convert JavaScript source code into a WebAssembly component.
+///
+/// Initialization warnings are dropped; get them from
+/// [`componentize_with_report`], or fail on them with
+/// [`ComponentizeOpts::deny_warnings`].
 pub async fn componentize(opts: &ComponentizeOpts<'_>) -> Result<Vec<u8>> {
+    Componentizer::new()?.componentize(opts).await
+}
+
+/// Convert JavaScript source code into a WebAssembly component, returning a
+/// [`BuildReport`] alongside it.
+///
+/// Initialization warnings go into the report; nothing is printed.
+pub async fn componentize_with_report(
+    opts: &ComponentizeOpts<'_>,
+) -> Result<(Vec<u8>, BuildReport)> {
+    Componentizer::new()?.componentize_with_report(opts).await
+}
+
+fn check_opts(opts: &ComponentizeOpts<'_>) -> Result<()> {
+    if opts.wit_source.is_some() && opts.wit_package.is_some() {
+        return Err(anyhow!("wit_source and wit_package cannot be combined"));
+    }
+    if opts.stub_wasi && opts.import_log.is_some() {
+        return Err(anyhow!(
+            "import_log needs WASI filesystem access and cannot be combined with stub_wasi"
+        ));
+    }
+    if opts.stub_wasi && opts.math_random == Some(MathRandom::Wasi) {
+        return Err(anyhow!(
+            "a wasi:random-backed Math.random cannot be combined with stub_wasi"
+        ));
+    }
+    if opts.disable_gc && (opts.gc_interval.is_some() || opts.gc_threshold.is_some()) {
+        return Err(anyhow!(
+            "gc_interval and gc_threshold cannot be combined with disable_gc"
+        ));
+    }
+    if opts.gc_interval == Some(0) {
+        return Err(anyhow!("gc_interval must be greater than zero"));
+    }
+    if opts.max_instructions == Some(0) {
+        return Err(anyhow!("max_instructions must be greater than zero"));
+    }
+    if opts.init_timeout == Some(Duration::ZERO) {
+        return Err(anyhow!("init_timeout must be greater than zero"));
+    }
+    if opts.stub_wasi && opts.warn_f32_rounding {
+        return Err(anyhow!(
+            "warn_f32_rounding writes to stderr and cannot be combined with stub_wasi"
+        ));
+    }
+    if opts.memory_report == Some(0) {
+        return Err(anyhow!("memory_report must be greater than zero"));
+    }
+    if opts.stub_wasi && opts.memory_report.is_some() {
+        return Err(anyhow!(
+            "memory_report writes to stderr and cannot be combined with stub_wasi"
+        ));
+    }
+    if opts.stub_wasi && opts.trace_values {
+        return Err(anyhow!(
+            "trace_values writes to stderr and cannot be combined with stub_wasi"
+        ));
+    }
+    if opts.throwing_imports && !opts.tagged_results {
+        return Err(anyhow!(
+            "throwing_imports only changes tagged results and needs tagged_results"
+        ));
+    }
+    if opts.lazy_eval && !opts.prime.is_empty() {
+        return Err(anyhow!(
+            "prime calls exports during Wizer and cannot be combined with lazy_eval"
+        ));
+    }
+    if opts.entry_module.is_some() && !opts.js_source.is_empty() {
+        return Err(anyhow!(
+            "entry_module names the entry module, so js_source must be empty"
+        ));
+    }
+    if opts.entry_module.is_some() && opts.js_path.is_some() {
+        return Err(anyhow!("entry_module cannot be combined with js_path"));
+    }
+    if opts.lazy_eval && !opts.modules.is_empty() {
+        return Err(anyhow!(
+            "modules are only loadable during Wizer and cannot be combined with lazy_eval"
+        ));
+    }
+    if opts.core_module
+        && cfg!(feature = "component-model-async")
+        && (opts.debug_runtime || matches!(opts.runtime, Runtime::Default | Runtime::OptSize))
+    {
+        return Err(anyhow!(
+            "core_module cannot use the component-model async ABI and needs a non-async runtime"
+        ));
+    }
+    opts.metadata.check()?;
+    if opts.lazy_eval && opts.arity_mismatch == ArityMismatch::Error {
+        return Err(anyhow!(
+            "arity_mismatch = error checks exports during Wizer and cannot be combined with \
+             lazy_eval"
+        ));
+    }
+    for (name, _) in opts.custom_sections {
+        if matches!(name.as_str(), "name" | "producers") || name.starts_with("component-type") {
+            return Err(anyhow!(
+                "custom section `{name}` is reserved for WebAssembly tooling"
+            ));
+        }
+    }
+    if opts.debug_runtime {
+        if DEBUG_RUNTIME_WASM.is_none() {
+            return Err(anyhow!(
+                "debug_runtime needs componentize-qjs built with the `debug-runtime` feature"
+            ));
+        }
+        // The debug runtime is built like the default one, so it stands in for
+        // any runtime that shares its async support.
+        let sync = matches!(opts.runtime, Runtime::DefaultSync | Runtime::OptSizeSync);
+        if matches!(opts.runtime, Runtime::Custom(_))
+            || (sync && cfg!(feature = "component-model-async"))
+        {
+            return Err(anyhow!(
+                "debug_runtime replaces the default runtime and cannot be combined with a \
+                 custom or non-async runtime"
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Parse the WIT and select the world to build, returning the world as
+/// selected and the world actually built, which differ for import-only worlds.
+fn load_world(opts: &ComponentizeOpts<'_>) -> Result<(Resolve, WorldId, WorldId)> {
     let mut resolve = Resolve::default();
-    let (pkg_id, _) = resolve.push_path(opts.wit_path)?;
-    let world_id = resolve.select_world(&[pkg_id], opts.world_name)?;
+    let pkg_id = match (opts.wit_source, opts.wit_package) {
+        (Some(source), _) => resolve.push_str(opts.wit_path, source)?,
+        (None, Some(package)) => push_wit_package(&mut resolve, package)?,
+        (None, None) if opts.wit_path.is_file() => {
+            let contents = std::fs::read(opts.wit_path)
+                .with_context(|| format!("failed to read {}", opts.wit_path.display()))?;
+            if contents.starts_with(b"\0asm") {
+                push_wit_package(&mut resolve, &contents)
+                    .with_context(|| format!("failed to decode {}", opts.wit_path.display()))?
+            } else {
+                resolve.push_path(opts.wit_path)?.0
+            }
+        }
+        (None, None) => resolve.push_path(opts.wit_path)?.0,
+    };
+    let selected = resolve.select_world(&[pkg_id], opts.world_name)?;
+    names::check_collisions(&resolve, selected)?;
+    support::check_supported(&resolve, selected)?;
+    check_result_convention(opts, &resolve, selected)?;
+    if opts.import_log.is_some() {
+        support::check_import_log(&resolve, selected)?;
+    }
+    let world_id = with_run_export(&mut resolve, selected)?;
+    Ok((resolve, world_id, selected))
+}
 
-    let shim = codegen::generate_shim(&resolve, world_id);
-    let resolver = module_resolution(opts)?;
-    let mut wit_dylib = wit_dylib::create(&resolve, world_id, None);
+/// Fail if tagged results would reach the generated globals, which expect
+/// imports to throw their `err` payload.
+fn check_result_convention(
+    opts: &ComponentizeOpts<'_>,
+    resolve: &Resolve,
+    world_id: WorldId,
+) -> Result<()> {
+    if !opts.tagged_results || opts.throwing_imports {
+        return Ok(());
+    }
+    let consumers = codegen::result_consumers(resolve, world_id, !opts.env_shims.no_wasi_globals);
+    if consumers.is_empty() {
+        return Ok(());
+    }
+    Err(anyhow!(
+        "tagged_results makes imports return `{{ tag, val }}` objects, but the generated {} \
+         expect them to throw; set throwing_imports as well",
+        consumers.join(", ")
+    ))
+}
 
+/// Merge a binary WIT package into `resolve`, returning its main package.
+fn push_wit_package(resolve: &mut Resolve, bytes: &[u8]) -> Result<PackageId> {
+    match decode(bytes)? {
+        DecodedWasm::WitPackage(decoded, pkg) => {
+            let remap = resolve.merge(decoded)?;
+            Ok(remap.packages[pkg.index()])
+        }
+        DecodedWasm::Component(..) => {
+            Err(anyhow!("expected a binary WIT package, got a component"))
+        }
+    }
+}
+
+/// Generate the core module binding the runtime to `world_id`.
+fn wit_dylib_module(resolve: &Resolve, world_id: WorldId) -> Result<Vec<u8>> {
+    let mut wit_dylib = wit_dylib::create(resolve, world_id, None);
     wit_component::embed_component_metadata(
         &mut wit_dylib,
-        &resolve,
+        resolve,
         world_id,
         wit_component::StringEncoding::UTF8,
     )?;
+    Ok(wit_dylib)
+}
 
-    let pre_wizer_component = wit_component::Linker::default()
+/// Link the runtime, the WIT bindings and the WASI adapter into the component
+/// Wizer initializes.
+fn link(runtime: Runtime<'_>, wit_dylib: &[u8]) -> Result<Vec<u8>> {
+    wit_component::Linker::default()
         .validate(true)
         .library(
             "componentize_qjs_runtime.wasm",
-            runtime_wasm(opts.runtime),
+            runtime_wasm(runtime),
             false,
         )?
-        .library("wit-dylib.wasm", &wit_dylib, false)?
+        .library("wit-dylib.wasm", wit_dylib, false)?
         .adapter(
             "wasi_snapshot_preview1",
             WASI_SNAPSHOT_PREVIEW1_REACTOR_ADAPTER,
         )?
         .encode()
-        .context("failed to link and encode component")?;
-
-    let mut component = wizer_init(
-        &pre_wizer_component,
-        &shim,
-        opts.js_source,
-        resolver,
-        opts.disable_gc,
-    )
-    .await?;
+        .context("failed to link and encode component")
+}
 
-    component = stub_internal_imports(&component)
-        .context("failed to stub internal module-loader import")?;
+fn init_options(opts: &ComponentizeOpts<'_>) -> Result<InitOptions> {
+    Ok(InitOptions {
+        disable_gc: opts.disable_gc,
+        strip_source: opts.strip_source,
+        lazy_eval: opts.lazy_eval,
+        gc_interval: opts.gc_interval,
+        gc_threshold: opts.gc_threshold,
+        max_list_length: opts.max_list_length,
+        memory_limit: opts.memory_limit,
+        memory_report: opts.memory_report,
+        max_instructions: opts.max_instructions,
+        strict_types: opts.strict_types,
+        tagged_results: opts.tagged_results,
+        throwing_imports: opts.throwing_imports,
+        wrap_integers: opts.wrap_integers,
+        finite_floats: opts.finite_floats,
+        warn_f32_rounding: opts.warn_f32_rounding,
+        trace_values: opts.trace_values,
+        features: opts.features.to_vec(),
+        polyfills: selected_polyfills(&opts.env_shims)?,
+        preludes: opts.preludes.to_vec(),
+        import_log: opts.import_log.map(Into::into),
+        math_random: opts.math_random.map(Into::into),
+        prime: opts
+            .prime
+            .iter()
+            .map(|call| ExportCall {
+                name: call.name.clone(),
+                args: call.args.clone(),
+            })
+            .collect(),
+        harden: opts.harden,
+        version_match: opts.version_aliasing.into(),
+        arity_mismatch: opts.arity_mismatch.into(),
+        import_aliases: opts
+            .import_aliases
+            .iter()
+            .map(|alias| (alias.specifier.clone(), alias.interface.clone()))
+            .collect(),
+        naming: opts.naming.into(),
+        renames: opts
+            .renames
+            .iter()
+            .map(|rename| (rename.function.clone(), rename.js_name.clone()))
+            .collect(),
+    })
+}
 
-    if opts.stub_wasi {
-        component = stub_wasi_imports(&component).context("failed to stub WASI imports")?;
+/// Give import-only worlds a `run` entry point.
+///
+/// A world without exports can't be called, so it is wrapped in a world that
+/// includes it and adds `export run: func() -> result`, backed by the module's
+/// `run` export. Worlds that already export something are returned unchanged.
+fn with_run_export(resolve: &mut Resolve, world_id: WorldId) -> Result<WorldId> {
+    let world = &resolve.worlds[world_id];
+    if !world.exports.is_empty() {
+        return Ok(world_id);
     }
 
-    Ok(component)
+    let package = world
+        .package
+        .map(|id| &resolve.packages[id].name)
+        .context("selected world does not belong to a package")?;
+    let version = package
+        .version
+        .as_ref()
+        .map(|v| format!("@{v}"))
+        .unwrap_or_default();
+    let source = format!(
+        "package componentize-qjs:entry;\n\
+         world run-entry {{\n\
+         include {}:{}/{}{version};\n\
+         export run: func() -> result;\n\
+         }}\n",
+        package.namespace, package.name, world.name,
+    );
+
+    let pkg_id = resolve
+        .push_str("componentize-qjs-entry.wit", &source)
+        .context("failed to add `run` export to import-only world")?;
+    resolve.select_world(&[pkg_id], Some("run-entry"))
 }
 
 fn module_resolution(opts: &ComponentizeOpts<'_>) -> Result<Option<Resolver>> {
@@ -164,6 +930,18 @@ fn module_resolution(opts: &ComponentizeOpts<'_>) -> Result<Option<Resolver>> {
     Resolver::new(js_path, opts.module_root).map(Some)
 }
 
+/// Append `sections` to `component` as custom sections, in order.
+fn append_custom_sections(component: &mut Vec<u8>, sections: &[(String, Vec<u8>)]) {
+    for (name, data) in sections {
+        component.push(ComponentSectionId::CoreCustom as u8);
+        CustomSection {
+            name: name.as_str().into(),
+            data: data.as_slice().into(),
+        }
+        .encode(component);
+    }
+}
+
 /// Return the built-in default runtime Wasm bytes.
 pub fn default_runtime_wasm() -> &'static [u8] {
     DEFAULT_RUNTIME_WASM
@@ -184,6 +962,20 @@ pub fn opt_size_sync_runtime_wasm() -> &'static [u8] {
     OPT_SIZE_SYNC_RUNTIME_WASM
 }
 
+/// Return the debug runtime Wasm bytes, if built with the `debug-runtime`
+/// feature.
+pub fn debug_runtime_wasm() -> Option<&'static [u8]> {
+    DEBUG_RUNTIME_WASM
+}
+
+/// The runtime `opts` embeds, which is the debug runtime if selected.
+fn selected_runtime<'a>(opts: &ComponentizeOpts<'a>) -> Runtime<'a> {
+    match DEBUG_RUNTIME_WASM {
+        Some(wasm) if opts.debug_runtime => Runtime::Custom(wasm),
+        _ => opts.runtime,
+    }
+}
+
 fn runtime_wasm(runtime: Runtime<'_>) -> &[u8] {
     match runtime {
         Runtime::Default => DEFAULT_RUNTIME_WASM,
@@ -194,83 +986,32 @@ fn runtime_wasm(runtime: Runtime<'_>) -> &[u8] {
     }
 }
 
-async fn wizer_init(
-    component: &[u8],
-    shim: &str,
-    js: &str,
-    resolver: Option<Resolver>,
-    disable_gc: bool,
-) -> Result<Vec<u8>> {
-    let stdout = MemoryOutputPipe::new(10000);
-    let stderr = MemoryOutputPipe::new(10000);
-
-    let wasi = WasiCtxBuilder::new()
-        .stdin(MemoryInputPipe::new(Bytes::new()))
-        .stdout(stdout.clone())
-        .stderr(stderr.clone())
-        .build();
-
-    let table = ResourceTable::new();
+fn wizer_engine() -> Result<Engine> {
     let mut config = Config::new();
     config.wasm_component_model(true);
     config.wasm_component_model_async(true);
+    config.wasm_component_model_error_context(true);
+    config.epoch_interruption(true);
+    Ok(Engine::new(&config)?)
+}
 
-    let engine = Engine::new(&config)?;
-    let mut store = Store::new(&engine, Ctx { wasi, table });
-
-    let wizer = Wizer::new();
-    let (cx, instrumented) = wizer.instrument_component(component)?;
-    let comp = WasmtimeComponent::new(&engine, &instrumented)?;
-
-    let mut linker = Linker::new(&engine);
-    linker.allow_shadowing(true);
-    linker.define_unknown_imports_as_traps(&comp)?;
-    wasmtime_wasi::p2::add_to_linker_async(&mut linker)?;
-    wasmtime_wasi::p3::add_to_linker(&mut linker)?;
-
-    register_module_loader(&mut linker, resolver.clone())?;
-
-    let instance = linker.instantiate_async(&mut store, &comp).await?;
-    let init = Init::new(&mut store, &instance)?;
-    init.call_init(
-        &mut store,
-        shim,
-        js,
-        resolver.as_ref().map(Resolver::entry_path),
-        disable_gc,
-    )
-    .await?
-    .map_err(|e| anyhow!("{e}"))
-    .with_context(move || {
-        format!(
-            "{}{}",
-            String::from_utf8_lossy(&stdout.contents()),
-            String::from_utf8_lossy(&stderr.contents())
-        )
-    })?;
-
-    let component = wizer
-        .snapshot_component(
-            cx,
-            &mut WasmtimeWizerComponent {
-                store: &mut store,
-                instance,
-            },
-        )
-        .await?;
-
-    Ok(component)
-}
-
-fn register_module_loader(linker: &mut Linker<Ctx>, resolver: Option<Resolver>) -> Result<()> {
-    let resolve = resolver.clone();
-    let load = resolver;
+fn register_module_loader(
+    linker: &mut Linker<Ctx>,
+    resolver: Option<Resolver>,
+    modules: Modules,
+) -> Result<()> {
+    let resolve = (resolver.clone(), modules.clone());
+    let load = (resolver, modules);
 
     let mut instance = linker.instance("local:init/module-loader")?;
     instance.func_wrap(
         "resolve",
         move |_, (referrer, specifier): (String, String)| -> wasmtime::Result<_> {
-            let result = resolve.as_ref().map_or_else(
+            let (resolver, modules) = &resolve;
+            if let Some(path) = modules.resolve(&referrer, &specifier) {
+                return Ok((Ok(path),));
+            }
+            let result = resolver.as_ref().map_or_else(
                 || {
                     Err(
                         "filesystem module not found: module resolution requires js_path"
@@ -289,7 +1030,11 @@ fn register_module_loader(linker: &mut Linker<Ctx>, resolver: Option<Resolver>)
     instance.func_wrap(
         "load",
         move |_, (path,): (String,)| -> wasmtime::Result<_> {
-            let result = load.as_ref().map_or_else(
+            let (resolver, modules) = &load;
+            if let Some(source) = modules.load(&path) {
+                return Ok((Ok(source),));
+            }
+            let result = resolver.as_ref().map_or_else(
                 || Err("filesystem module not found: module loading requires js_path".to_string()),
                 |resolver| resolver.load(&path).map_err(|err| err.to_string()),
             );
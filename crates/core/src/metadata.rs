@@ -0,0 +1,187 @@
+//! The `producers` section and metadata written into finished components.
+//!
+//! Every component records the componentize-qjs and QuickJS versions that
+//! built it in its top-level `producers` section, merged with whatever the
+//! linking tools put there. The name, version and description a caller gives
+//! are written as the `component-name`, `version` and `description` sections
+//! other WebAssembly tooling reads. Core modules get the same sections, except
+//! that the name goes into the module subsection of the `name` section.
+
+use anyhow::{Result, anyhow, bail};
+use indexmap::IndexMap;
+use wasm_encoder::{ComponentNameSection, CustomSection, Encode, ProducersField, ProducersSection};
+use wasmparser::{BinaryReader, CustomSectionReader, KnownCustom};
+
+/// Component section id of a custom section.
+const CUSTOM_SECTION: u8 = 0;
+/// Name subsection id of the module name.
+const MODULE_NAME: u8 = 0;
+
+/// What a component records about itself beyond its code.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Metadata<'a> {
+    /// Write no `producers` or metadata sections at all, leaving the ones the
+    /// linking tools wrote
+    pub suppress: bool,
+    /// Name of the component
+    pub name: Option<&'a str>,
+    /// Version of the component
+    pub version: Option<&'a str>,
+    /// Human-readable description of the component
+    pub description: Option<&'a str>,
+}
+
+impl Metadata<'_> {
+    pub(crate) fn check(&self) -> Result<()> {
+        if self.suppress
+            && (self.name.is_some() || self.version.is_some() || self.description.is_some())
+        {
+            return Err(anyhow!(
+                "metadata name, version and description cannot be combined with suppressing \
+                 metadata"
+            ));
+        }
+        Ok(())
+    }
+
+    /// Rewrite the top-level metadata sections of `component`, or of a core
+    /// module, recording that it embeds QuickJS `quickjs_version`.
+    pub(crate) fn write(&self, component: &[u8], quickjs_version: &str) -> Result<Vec<u8>> {
+        if self.suppress {
+            return Ok(component.to_vec());
+        }
+
+        let mut reader = BinaryReader::new(component, 0);
+        let header = reader.read_bytes(8)?;
+        let module = match header {
+            b"\0asm\x0d\0\x01\0" => false,
+            b"\0asm\x01\0\0\0" => true,
+            _ => bail!("expected a component or core module"),
+        };
+
+        let mut output = header.to_vec();
+        // Subsections of a core module's `name` section the module name joins.
+        let mut module_names: &[u8] = &[];
+        // Producers fields and their values, as name to version.
+        let mut producers: IndexMap<String, IndexMap<String, String>> = IndexMap::new();
+        while !reader.eof() {
+            let id = reader.read_u8()?;
+            let size = reader.read_var_u32()? as usize;
+            let data = reader.read_bytes(size)?;
+            if id == CUSTOM_SECTION {
+                let custom = CustomSectionReader::new(BinaryReader::new(data, 0))?;
+                if let KnownCustom::Producers(fields) = custom.as_known() {
+                    for field in fields {
+                        let field = field?;
+                        let values = producers.entry(field.name.to_string()).or_default();
+                        for value in field.values {
+                            let value = value?;
+                            values.insert(value.name.to_string(), value.version.to_string());
+                        }
+                    }
+                    continue;
+                }
+                if module && self.name.is_some() && custom.name() == "name" {
+                    module_names = custom.data();
+                    continue;
+                }
+                if self.replaces(custom.name()) {
+                    continue;
+                }
+            }
+            output.push(id);
+            data.encode(&mut output);
+        }
+
+        let fields = [
+            ("language", "JavaScript", ""),
+            (
+                "processed-by",
+                "componentize-qjs",
+                env!("CARGO_PKG_VERSION"),
+            ),
+            ("sdk", "QuickJS", quickjs_version),
+        ];
+        for (field, name, version) in fields {
+            producers
+                .entry(field.to_string())
+                .or_default()
+                .insert(name.to_string(), version.to_string());
+        }
+        let mut section = ProducersSection::new();
+        for (name, values) in &producers {
+            let mut field = ProducersField::new();
+            for (name, version) in values {
+                field.value(name, version);
+            }
+            section.field(name, &field);
+        }
+        push_custom(&mut output, &section);
+
+        if let Some(name) = self.name {
+            if module {
+                push_custom(
+                    &mut output,
+                    &CustomSection {
+                        name: "name".into(),
+                        data: with_module_name(name, module_names)?.into(),
+                    },
+                );
+            } else {
+                let mut names = ComponentNameSection::new();
+                names.component(name);
+                push_custom(&mut output, &names);
+            }
+        }
+        for (name, value) in [("version", self.version), ("description", self.description)] {
+            if let Some(value) = value {
+                push_custom(
+                    &mut output,
+                    &CustomSection {
+                        name: name.into(),
+                        data: value.as_bytes().into(),
+                    },
+                );
+            }
+        }
+
+        Ok(output)
+    }
+
+    /// Whether a top-level custom section called `name` is replaced by one
+    /// this writes.
+    fn replaces(&self, name: &str) -> bool {
+        match name {
+            "component-name" => self.name.is_some(),
+            "version" => self.version.is_some(),
+            "description" => self.description.is_some(),
+            _ => false,
+        }
+    }
+}
+
+/// The contents of a `name` section naming the module `name`, keeping the
+/// other subsections of `names`.
+fn with_module_name(name: &str, names: &[u8]) -> Result<Vec<u8>> {
+    let mut subsection = Vec::new();
+    name.encode(&mut subsection);
+    let mut data = vec![MODULE_NAME];
+    subsection.encode(&mut data);
+
+    let mut reader = BinaryReader::new(names, 0);
+    while !reader.eof() {
+        let id = reader.read_u8()?;
+        let size = reader.read_var_u32()? as usize;
+        let bytes = reader.read_bytes(size)?;
+        if id != MODULE_NAME {
+            data.push(id);
+            bytes.encode(&mut data);
+        }
+    }
+    Ok(data)
+}
+
+fn push_custom(output: &mut Vec<u8>, section: &impl Encode) {
+    output.push(CUSTOM_SECTION);
+    section.encode(output);
+}
@@ -0,0 +1,91 @@
+//! JavaScript modules passed in memory with the build options.
+//!
+//! They live in the same guest namespace as modules read through
+//! [`Resolver`](crate::resolver::Resolver), rooted at `/`, and are looked up
+//! first, so a build can consist of several files without a bundler or a
+//! filesystem.
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use anyhow::{Result, anyhow};
+
+#[derive(Clone, Default)]
+pub(crate) struct Modules {
+    /// Source text by absolute guest path
+    sources: Arc<BTreeMap<String, String>>,
+}
+
+impl Modules {
+    pub(crate) fn new(modules: &[(String, String)]) -> Result<Self> {
+        let mut sources = BTreeMap::new();
+        for (name, source) in modules {
+            let path = normalize("", name)
+                .filter(|path| path != "/")
+                .ok_or_else(|| anyhow!("invalid JavaScript module name {name:?}"))?;
+            if sources.insert(path, source.clone()).is_some() {
+                return Err(anyhow!("JavaScript module {name:?} is given twice"));
+            }
+        }
+        Ok(Self {
+            sources: Arc::new(sources),
+        })
+    }
+
+    /// Resolve `specifier` imported from `referrer` to one of the modules.
+    ///
+    /// Relative specifiers are resolved from the referrer's directory, or
+    /// from `/` for an entry module without a path; bare specifiers match
+    /// module names. As for files, `.js` and `.mjs` may be left out.
+    pub(crate) fn resolve(&self, referrer: &str, specifier: &str) -> Option<String> {
+        if self.sources.is_empty() {
+            return None;
+        }
+        let relative = ["./", "../", "/"]
+            .iter()
+            .any(|prefix| specifier.starts_with(prefix));
+        let base = match referrer.rsplit_once('/') {
+            Some((dir, _)) if relative && referrer.starts_with('/') => dir,
+            _ => "",
+        };
+        let path = normalize(base, specifier)?;
+        ["", ".js", ".mjs"]
+            .iter()
+            .map(|ext| format!("{path}{ext}"))
+            .find(|path| self.sources.contains_key(path))
+    }
+
+    /// The path and source of the module `name` names, for evaluating it as
+    /// the entry module. `name` is looked up as a bare specifier.
+    pub(crate) fn entry(&self, name: &str) -> Result<(String, String)> {
+        let path = self
+            .resolve("", name)
+            .ok_or_else(|| anyhow!("entry module {name:?} is not one of the modules"))?;
+        let source = self.sources[&path].clone();
+        Ok((path, source))
+    }
+
+    pub(crate) fn load(&self, path: &str) -> Option<String> {
+        self.sources.get(path).cloned()
+    }
+}
+
+/// Join `path` onto the directory `base`, resolving `.` and `..`, as an
+/// absolute guest path. `None` if it would leave the root.
+fn normalize(base: &str, path: &str) -> Option<String> {
+    let mut parts: Vec<&str> = if path.starts_with('/') {
+        Vec::new()
+    } else {
+        base.split('/').filter(|part| !part.is_empty()).collect()
+    };
+    for part in path.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop()?;
+            }
+            part => parts.push(part),
+        }
+    }
+    Some(format!("/{}", parts.join("/")))
+}
@@ -0,0 +1,188 @@
+//! Detection of WIT names that collide once converted to JavaScript.
+//!
+//! JavaScript sees kebab-case WIT names in camelCase, so distinct names such as
+//! `get-id` and `get-ID` become the same property and silently shadow each
+//! other. Collisions are reported before Wizer runs instead of surfacing as the
+//! wrong binding at run time.
+
+use anyhow::{Result, bail};
+use heck::{ToLowerCamelCase, ToUpperCamelCase};
+use indexmap::{IndexMap, IndexSet};
+use wit_parser::{Function, FunctionKind, Resolve, Type, TypeDefKind, TypeId, WorldId, WorldItem};
+
+/// Fail if two WIT names in the same JavaScript scope of `world_id` convert to
+/// the same JavaScript name.
+///
+/// Covers import and export functions, resource classes and their methods,
+/// the `wit` type constants, and record fields and flags.
+pub(crate) fn check_collisions(resolve: &Resolve, world_id: WorldId) -> Result<()> {
+    let world = &resolve.worlds[world_id];
+    let mut collisions = Collisions {
+        resolve,
+        found: Vec::new(),
+    };
+    let mut world_types = Vec::new();
+    let mut types = IndexSet::new();
+
+    for (direction, items) in [("import", &world.imports), ("export", &world.exports)] {
+        let mut funcs = Vec::new();
+        for (key, item) in items {
+            match item {
+                WorldItem::Function(func) => funcs.push(func),
+                WorldItem::Interface { id, .. } => {
+                    let iface = &resolve.interfaces[*id];
+                    let scope =
+                        format!("{direction}ed interface `{}`", resolve.name_world_key(key));
+                    collisions.functions(&scope, iface.functions.values());
+                    collisions.constants(&scope, iface.types.values().copied());
+                    types.extend(iface.types.values().map(|&id| resolve_alias(resolve, id)));
+                }
+                WorldItem::Type { id, .. } => {
+                    world_types.push(*id);
+                    types.insert(resolve_alias(resolve, *id));
+                }
+            }
+        }
+        collisions.functions(&format!("world {direction}s"), funcs);
+    }
+    collisions.constants("world types", world_types);
+
+    for id in types {
+        collisions.members(id);
+    }
+
+    collisions.into_result()
+}
+
+/// Follow `type a = b` aliases (including `use`) to the defining type.
+fn resolve_alias(resolve: &Resolve, mut id: TypeId) -> TypeId {
+    while let TypeDefKind::Type(Type::Id(next)) = resolve.types[id].kind {
+        id = next;
+    }
+    id
+}
+
+struct Collisions<'a> {
+    resolve: &'a Resolve,
+    found: Vec<String>,
+}
+
+impl<'a> Collisions<'a> {
+    /// Record every JavaScript name in `scope` produced by more than one WIT name.
+    fn check(&mut self, scope: &str, names: impl IntoIterator<Item = (&'a str, String)>) {
+        let mut by_js: IndexMap<String, Vec<&str>> = IndexMap::new();
+        for (wit, js) in names {
+            let wits = by_js.entry(js).or_default();
+            if !wits.contains(&wit) {
+                wits.push(wit);
+            }
+        }
+
+        for (js, wits) in by_js {
+            if wits.len() > 1 {
+                let wits: Vec<_> = wits.iter().map(|name| format!("`{name}`")).collect();
+                self.found
+                    .push(format!("{scope}: {} all map to `{js}`", wits.join(", ")));
+            }
+        }
+    }
+
+    /// Functions become camelCase members of one object, except resource
+    /// functions, which become an UpperCamelCase class with camelCase methods
+    /// and statics.
+    fn functions(&mut self, scope: &str, funcs: impl IntoIterator<Item = &'a Function>) {
+        let mut members = Vec::new();
+        let mut resources: IndexMap<TypeId, (Vec<_>, Vec<_>)> = IndexMap::new();
+        for func in funcs {
+            let item = func.item_name();
+            match func.kind {
+                FunctionKind::Freestanding | FunctionKind::AsyncFreestanding => {
+                    members.push((func.name.as_str(), func.name.to_lower_camel_case()));
+                }
+                FunctionKind::Method(id) | FunctionKind::AsyncMethod(id) => {
+                    let methods = &mut resources.entry(id).or_default().0;
+                    methods.push((item, item.to_lower_camel_case()));
+                }
+                FunctionKind::Static(id) | FunctionKind::AsyncStatic(id) => {
+                    let statics = &mut resources.entry(id).or_default().1;
+                    statics.push((item, item.to_lower_camel_case()));
+                }
+                FunctionKind::Constructor(id) => {
+                    resources.entry(id).or_default();
+                }
+            }
+        }
+
+        let resolve = self.resolve;
+        for (id, (methods, statics)) in resources {
+            let name = resolve.types[id].name.as_deref().unwrap_or_default();
+            members.push((name, name.to_upper_camel_case()));
+            self.check(&format!("methods of resource `{name}` in {scope}"), methods);
+            self.check(
+                &format!("static functions of resource `{name}` in {scope}"),
+                statics,
+            );
+        }
+        self.check(scope, members);
+    }
+
+    /// Enums and flags get a `wit` constant named after the type in
+    /// UpperCamelCase.
+    fn constants(&mut self, scope: &str, types: impl IntoIterator<Item = TypeId>) {
+        let resolve = self.resolve;
+        let names = types.into_iter().filter_map(|id| {
+            let typedef = &resolve.types[resolve_alias(resolve, id)];
+            let name = typedef.name.as_deref()?;
+            matches!(typedef.kind, TypeDefKind::Enum(_) | TypeDefKind::Flags(_))
+                .then(|| (name, name.to_upper_camel_case()))
+        });
+        self.check(&format!("type constants of {scope}"), names);
+    }
+
+    /// Record fields and flags are camelCase properties; enum cases are
+    /// UpperCamelCase keys of the type's constant.
+    fn members(&mut self, id: TypeId) {
+        let resolve = self.resolve;
+        let typedef = &resolve.types[id];
+        let name = typedef.name.as_deref().unwrap_or_default();
+        match &typedef.kind {
+            TypeDefKind::Record(record) => {
+                let fields = record
+                    .fields
+                    .iter()
+                    .map(|f| (f.name.as_str(), f.name.to_lower_camel_case()));
+                self.check(&format!("fields of record `{name}`"), fields);
+            }
+            TypeDefKind::Flags(flags) => {
+                let props = flags
+                    .flags
+                    .iter()
+                    .map(|f| (f.name.as_str(), f.name.to_lower_camel_case()));
+                self.check(&format!("flags of `{name}`"), props);
+                let keys = flags
+                    .flags
+                    .iter()
+                    .map(|f| (f.name.as_str(), f.name.to_upper_camel_case()));
+                self.check(&format!("constants of flags `{name}`"), keys);
+            }
+            TypeDefKind::Enum(e) => {
+                let keys = e
+                    .cases
+                    .iter()
+                    .map(|c| (c.name.as_str(), c.name.to_upper_camel_case()));
+                self.check(&format!("constants of enum `{name}`"), keys);
+            }
+            _ => {}
+        }
+    }
+
+    fn into_result(self) -> Result<()> {
+        if self.found.is_empty() {
+            return Ok(());
+        }
+        bail!(
+            "WIT names collide once converted to JavaScript:\n  {}",
+            self.found.join("\n  ")
+        )
+    }
+}
@@ -0,0 +1,139 @@
+//! An optional `wasm-opt` pass over the core modules of a snapshotted
+//! component.
+//!
+//! Wizer leaves the runtime's initialization code in place and snapshots the
+//! heap as data segments, so the modules carry code that never runs again.
+//! Binaryen's optimizer works on core modules only, so each top-level module
+//! of the component is run through an external `wasm-opt` and written back in
+//! place; every other section is copied as is.
+
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use anyhow::{Context, Result, anyhow, bail};
+use wasm_encoder::{Component, RawSection};
+use wasmparser::BinaryReader;
+
+/// Component section id of a core module.
+const CORE_MODULE_SECTION: u8 = 1;
+
+/// What the optimization pass over a snapshotted component optimizes for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OptLevel {
+    /// Optimize for execution speed, as `wasm-opt -O3`
+    Speed,
+    /// Optimize for size, as `wasm-opt -Oz`
+    Size,
+}
+
+impl OptLevel {
+    fn flag(self) -> &'static str {
+        match self {
+            Self::Speed => "-O3",
+            Self::Size => "-Oz",
+        }
+    }
+}
+
+impl FromStr for OptLevel {
+    type Err = anyhow::Error;
+
+    /// Parse `speed` or `size`.
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "speed" => Ok(Self::Speed),
+            "size" => Ok(Self::Size),
+            _ => Err(anyhow!(
+                "invalid optimization level `{s}`; expected `speed` or `size`"
+            )),
+        }
+    }
+}
+
+/// Run every top-level core module of `component` through `wasm-opt`.
+///
+/// `wasm-opt` is taken from the `WASM_OPT` environment variable, or else
+/// looked up on `PATH`.
+pub(crate) fn optimize(component: &[u8], level: OptLevel) -> Result<Vec<u8>> {
+    let mut reader = BinaryReader::new(component, 0);
+    if reader.read_bytes(8)? != b"\0asm\x0d\0\x01\0" {
+        bail!("expected a component");
+    }
+
+    let mut output = Component::new();
+    while !reader.eof() {
+        let id = reader.read_u8()?;
+        let size = reader.read_var_u32()? as usize;
+        let data = reader.read_bytes(size)?;
+        if id == CORE_MODULE_SECTION {
+            let module = optimize_module(data, level)?;
+            output.section(&RawSection { id, data: &module });
+        } else {
+            output.section(&RawSection { id, data });
+        }
+    }
+
+    Ok(output.finish())
+}
+
+/// Run a single core module through `wasm-opt`.
+pub(crate) fn optimize_module(module: &[u8], level: OptLevel) -> Result<Vec<u8>> {
+    let path = temp_path("wasm");
+    fs::write(&path, module).with_context(|| format!("failed to write {}", path.display()))?;
+    let optimized = wasm_opt(&path, level).and_then(|()| {
+        fs::read(&path).with_context(|| format!("failed to read {}", path.display()))
+    });
+    let _ = fs::remove_file(&path);
+    optimized
+}
+
+fn wasm_opt(path: &Path, level: OptLevel) -> Result<()> {
+    let mut args = vec![OsString::from(level.flag())];
+    args.extend(FEATURE_ARGS.iter().map(OsString::from));
+    args.extend([path.into(), "-o".into(), path.into()]);
+    binaryen("wasm-opt", "WASM_OPT", &args)
+}
+
+/// Feature flags passed to every Binaryen tool.
+pub(crate) const FEATURE_ARGS: &[&str] = &[
+    "--all-features",
+    "--disable-gc",
+    "--disable-reference-types",
+];
+
+/// A fresh path in the temporary directory with the given extension, unique
+/// within this process.
+pub(crate) fn temp_path(extension: &str) -> PathBuf {
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+    env::temp_dir().join(format!(
+        "componentize-qjs-{}-{}.{extension}",
+        std::process::id(),
+        NEXT.fetch_add(1, Ordering::Relaxed)
+    ))
+}
+
+/// Run the Binaryen tool `name` with `args`.
+///
+/// The tool is taken from the environment variable `var`, or else looked up
+/// on `PATH`.
+pub(crate) fn binaryen(name: &str, var: &str, args: &[OsString]) -> Result<()> {
+    let tool = env::var_os(var).unwrap_or_else(|| name.into());
+    let output = Command::new(&tool).args(args).output().with_context(|| {
+        format!(
+            "failed to run {}; install binaryen or point {var} at {name}",
+            tool.to_string_lossy()
+        )
+    })?;
+    if !output.status.success() {
+        bail!(
+            "{name} failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(())
+}
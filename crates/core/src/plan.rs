@@ -0,0 +1,262 @@
+//! Dry-run build plans.
+//!
+//! A plan resolves the WIT world, links the component Wizer would initialize
+//! and inspects it, but skips compilation, Wizer and import stubbing. It
+//! reports what a build would do, for policy checks and tooling.
+
+use std::fmt::Write;
+use std::path::PathBuf;
+
+use anyhow::{Context, Result, bail};
+use wasi_preview1_component_adapter_provider::WASI_SNAPSHOT_PREVIEW1_REACTOR_ADAPTER;
+use wasmtime_wizer::Wizer;
+use wit_parser::decoding::{DecodedWasm, decode};
+
+use crate::{
+    ComponentizeOpts, Modules, Runtime, cache, check_opts, init_options, link, load_world,
+    module_resolution, runtime_wasm, selected_runtime, wit_dylib_module, wizer_engine,
+};
+
+/// What [`componentize`](crate::componentize) would do with the same options.
+#[derive(Debug, Clone)]
+pub struct BuildPlan {
+    /// Name of the selected world
+    pub world: String,
+    /// Whether a `run` export is added because the world only imports
+    pub run_export_added: bool,
+    /// Built-in runtime variant, or `custom`
+    pub runtime: &'static str,
+    /// Modules linked into the component, in link order
+    pub libraries: Vec<PlannedLibrary>,
+    /// Imports of the linked component and how the build treats them
+    pub imports: Vec<PlannedImport>,
+    /// Exports of the world, as seen by the host
+    pub exports: Vec<String>,
+    /// Feature flags exposed through `__FEATURES__`
+    pub features: Vec<String>,
+    /// Polyfills installed during initialization, in install order
+    pub polyfills: Vec<String>,
+    /// Cache entry the compilation would be read from or written to
+    pub cache_entry: Option<PathBuf>,
+    /// Whether `cache_entry` already exists, so compilation would be skipped
+    pub cache_hit: bool,
+    /// Rough output size: the linked component plus the JavaScript sources,
+    /// preludes included, which stand in for the heap Wizer snapshots
+    pub estimated_size: usize,
+}
+
+/// A module linked into the component.
+#[derive(Debug, Clone)]
+pub struct PlannedLibrary {
+    /// Module name
+    pub name: &'static str,
+    /// Module size in bytes
+    pub size: usize,
+}
+
+/// An import of the linked component.
+#[derive(Debug, Clone)]
+pub struct PlannedImport {
+    /// Import name, e.g. `wasi:cli/stdout@0.2.12`
+    pub name: String,
+    /// What the build does with it
+    pub action: ImportAction,
+}
+
+/// How a build treats an import.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportAction {
+    /// Left for the host to provide
+    Host,
+    /// Replaced with an implementation that traps
+    Stub,
+}
+
+impl ImportAction {
+    fn as_str(self) -> &'static str {
+        match self {
+            ImportAction::Host => "host",
+            ImportAction::Stub => "stub",
+        }
+    }
+}
+
+/// Compute the plan for building `opts` without running the expensive phases.
+///
+/// Fails on the same invalid options and WIT errors as
+/// [`componentize`](crate::componentize). Errors only Wizer would hit, such as
+/// JavaScript syntax errors and unresolvable module imports, are not detected.
+pub fn plan(opts: &ComponentizeOpts<'_>) -> Result<BuildPlan> {
+    check_opts(opts)?;
+    let (resolve, world_id, selected) = load_world(opts)?;
+    module_resolution(opts)?;
+    let modules = Modules::new(opts.modules)?;
+    if let Some(name) = opts.entry_module {
+        modules.entry(name)?;
+    }
+    let init_options = init_options(opts)?;
+
+    let wit_dylib = wit_dylib_module(&resolve, world_id)?;
+    let linked = link(selected_runtime(opts), &wit_dylib)?;
+
+    let DecodedWasm::Component(linked_resolve, linked_world) =
+        decode(&linked).context("failed to decode linked component")?
+    else {
+        bail!("expected a component, got a WIT package");
+    };
+    let imports = linked_resolve.worlds[linked_world]
+        .imports
+        .keys()
+        .map(|key| {
+            let name = linked_resolve.name_world_key(key);
+            let stubbed =
+                name == "local:init/module-loader" || (opts.stub_wasi && name.starts_with("wasi:"));
+            let action = if stubbed {
+                ImportAction::Stub
+            } else {
+                ImportAction::Host
+            };
+            PlannedImport { name, action }
+        })
+        .collect();
+    let exports = resolve.worlds[world_id]
+        .exports
+        .keys()
+        .map(|key| resolve.name_world_key(key))
+        .collect();
+
+    let cache_entry = match opts.cache_dir {
+        Some(dir) => {
+            let (_, instrumented) = Wizer::new().instrument_component(&linked)?;
+            Some(cache::entry_path(&wizer_engine()?, &instrumented, dir))
+        }
+        None => None,
+    };
+
+    Ok(BuildPlan {
+        world: resolve.worlds[selected].name.clone(),
+        run_export_added: world_id != selected,
+        runtime: if opts.debug_runtime {
+            "debug"
+        } else {
+            runtime_name(opts.runtime)
+        },
+        libraries: vec![
+            PlannedLibrary {
+                name: "componentize_qjs_runtime.wasm",
+                size: runtime_wasm(selected_runtime(opts)).len(),
+            },
+            PlannedLibrary {
+                name: "wit-dylib.wasm",
+                size: wit_dylib.len(),
+            },
+            PlannedLibrary {
+                name: "wasi_snapshot_preview1",
+                size: WASI_SNAPSHOT_PREVIEW1_REACTOR_ADAPTER.len(),
+            },
+        ],
+        imports,
+        exports,
+        features: init_options.features,
+        polyfills: init_options.polyfills,
+        cache_hit: cache_entry.as_ref().is_some_and(|path| path.exists()),
+        cache_entry,
+        estimated_size: linked.len()
+            + opts.js_source.len()
+            + opts
+                .modules
+                .iter()
+                .chain(opts.preludes)
+                .map(|(_, source)| source.len())
+                .sum::<usize>(),
+    })
+}
+
+fn runtime_name(runtime: Runtime<'_>) -> &'static str {
+    match runtime {
+        Runtime::Default => "default",
+        Runtime::OptSize => "opt-size",
+        Runtime::DefaultSync => "default-sync",
+        Runtime::OptSizeSync => "opt-size-sync",
+        Runtime::Custom(_) => "custom",
+    }
+}
+
+impl BuildPlan {
+    /// Render the plan as a JSON object.
+    pub fn to_json(&self) -> String {
+        let strings = |items: &[String]| {
+            let items: Vec<_> = items.iter().map(|s| json_string(s)).collect();
+            format!("[{}]", items.join(", "))
+        };
+        let libraries: Vec<_> = self
+            .libraries
+            .iter()
+            .map(|lib| {
+                format!(
+                    "{{\"name\": {}, \"size\": {}}}",
+                    json_string(lib.name),
+                    lib.size
+                )
+            })
+            .collect();
+        let imports: Vec<_> = self
+            .imports
+            .iter()
+            .map(|import| {
+                format!(
+                    "{{\"name\": {}, \"action\": {}}}",
+                    json_string(&import.name),
+                    json_string(import.action.as_str())
+                )
+            })
+            .collect();
+        let cache_entry = self
+            .cache_entry
+            .as_ref()
+            .map_or("null".to_string(), |path| {
+                json_string(&path.display().to_string())
+            });
+
+        let mut out = String::from("{\n");
+        let fields = [
+            ("world", json_string(&self.world)),
+            ("run_export_added", self.run_export_added.to_string()),
+            ("runtime", json_string(self.runtime)),
+            ("libraries", format!("[{}]", libraries.join(", "))),
+            ("imports", format!("[{}]", imports.join(", "))),
+            ("exports", strings(&self.exports)),
+            ("features", strings(&self.features)),
+            ("polyfills", strings(&self.polyfills)),
+            ("cache_entry", cache_entry),
+            ("cache_hit", self.cache_hit.to_string()),
+            ("estimated_size", self.estimated_size.to_string()),
+        ];
+        for (i, (key, value)) in fields.iter().enumerate() {
+            let comma = if i + 1 < fields.len() { "," } else { "" };
+            let _ = writeln!(out, "  \"{key}\": {value}{comma}");
+        }
+        out.push('}');
+        out
+    }
+}
+
+fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
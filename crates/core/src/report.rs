@@ -0,0 +1,87 @@
+//! Build reports returned by [`componentize_with_report`](crate::componentize_with_report).
+
+use anyhow::{Context, Result};
+use wasmparser::{Encoding, KnownCustom, Name, Parser, Payload};
+
+/// What a build produced, for tooling to show alongside the component.
+#[derive(Debug, Clone, Default)]
+pub struct BuildReport {
+    /// Size of the component in bytes
+    pub component_size: usize,
+    /// Core modules embedded in the component, in order, as written after
+    /// Wizer initialization and import stubbing
+    pub modules: Vec<ModuleReport>,
+    /// Warnings from initialization, such as exports the JavaScript doesn't
+    /// define or arity mismatches
+    pub warnings: Vec<String>,
+    /// Output of the JavaScript to stdout during initialization
+    pub init_stdout: String,
+    /// Output of the JavaScript to stderr during initialization
+    pub init_stderr: String,
+    /// Version of the QuickJS engine in the runtime
+    pub quickjs_version: String,
+}
+
+/// A core module embedded in the component.
+#[derive(Debug, Clone, Default)]
+pub struct ModuleReport {
+    /// Name from the module's `name` section, if it has one
+    pub name: Option<String>,
+    /// Module size in bytes
+    pub size: usize,
+    /// Custom sections as (name, size in bytes); they carry no code, so they
+    /// are what a stripping pass could remove
+    pub custom_sections: Vec<(String, usize)>,
+}
+
+impl BuildReport {
+    /// Fill in the size and modules of the finished `component`.
+    pub(crate) fn inspect(&mut self, component: &[u8]) -> Result<()> {
+        self.component_size = component.len();
+        self.modules = modules(component).context("failed to inspect the built component")?;
+        Ok(())
+    }
+}
+
+fn modules(component: &[u8]) -> Result<Vec<ModuleReport>> {
+    let mut modules: Vec<ModuleReport> = Vec::new();
+    // The module each nesting level belongs to, or `None` for components.
+    let mut stack: Vec<Option<usize>> = Vec::new();
+
+    for payload in Parser::new(0).parse_all(component) {
+        match payload? {
+            Payload::ModuleSection {
+                unchecked_range, ..
+            } => modules.push(ModuleReport {
+                size: unchecked_range.len(),
+                ..ModuleReport::default()
+            }),
+            Payload::Version { encoding, .. } => stack.push(match encoding {
+                Encoding::Module => modules.len().checked_sub(1),
+                Encoding::Component => None,
+            }),
+            Payload::End(_) => {
+                stack.pop();
+            }
+            Payload::CustomSection(reader) => {
+                let Some(Some(index)) = stack.last() else {
+                    continue;
+                };
+                let module = &mut modules[*index];
+                if let KnownCustom::Name(names) = reader.as_known() {
+                    for name in names.into_iter().flatten() {
+                        if let Name::Module { name, .. } = name {
+                            module.name = Some(name.to_string());
+                        }
+                    }
+                }
+                module
+                    .custom_sections
+                    .push((reader.name().to_string(), reader.data().len()));
+            }
+            _ => {}
+        }
+    }
+
+    Ok(modules)
+}
@@ -0,0 +1,209 @@
+//! Detection of WIT constructs the runtime can't bind yet.
+//!
+//! The runtime lifts and lowers a fixed set of WIT types and only dispatches
+//! async exports to freestanding functions. A world using anything else still
+//! links, but the component traps on the first call that reaches the
+//! unsupported part. Such worlds are rejected before Wizer runs instead, with
+//! every offending item listed.
+
+use anyhow::{Result, bail};
+use indexmap::IndexSet;
+use wit_parser::{Function, FunctionKind, Resolve, Type, TypeDefKind, TypeId, WorldId, WorldItem};
+
+/// Fail if a function of `world_id` uses a type the runtime can't lift or
+/// lower, or is an async export the runtime can't dispatch.
+pub(crate) fn check_supported(resolve: &Resolve, world_id: WorldId) -> Result<()> {
+    let world = &resolve.worlds[world_id];
+    let mut support = Support {
+        resolve,
+        seen: IndexSet::new(),
+        found: Vec::new(),
+    };
+
+    for (direction, items) in [("import", &world.imports), ("export", &world.exports)] {
+        for (key, item) in items {
+            match item {
+                WorldItem::Function(func) => {
+                    support.function(direction, &format!("`{}`", func.name), func);
+                }
+                WorldItem::Interface { id, .. } => {
+                    let iface = resolve.name_world_key(key);
+                    for func in resolve.interfaces[*id].functions.values() {
+                        let name = format!("`{iface}#{}`", func.name);
+                        support.function(direction, &name, func);
+                    }
+                }
+                WorldItem::Type { .. } => {}
+            }
+        }
+    }
+
+    support.into_result()
+}
+
+/// Fail if a sync import of `world_id` passes a resource handle, stream,
+/// future or error context, none of which the import log can capture: a
+/// replayed handle would name nothing in the replaying instance.
+pub(crate) fn check_import_log(resolve: &Resolve, world_id: WorldId) -> Result<()> {
+    let world = &resolve.worlds[world_id];
+    let mut found = Vec::new();
+    let mut check = |name: String, func: &Function| {
+        let is_async = matches!(
+            func.kind,
+            FunctionKind::AsyncFreestanding
+                | FunctionKind::AsyncMethod(_)
+                | FunctionKind::AsyncStatic(_)
+        );
+        let captures_handle = func
+            .params
+            .iter()
+            .map(|p| &p.ty)
+            .chain(&func.result)
+            .any(|ty| has_handle(resolve, ty));
+        if !is_async && captures_handle {
+            found.push(name);
+        }
+    };
+
+    for (key, item) in &world.imports {
+        match item {
+            WorldItem::Function(func) => check(format!("`{}`", func.name), func),
+            WorldItem::Interface { id, .. } => {
+                let iface = resolve.name_world_key(key);
+                for func in resolve.interfaces[*id].functions.values() {
+                    check(format!("`{iface}#{}`", func.name), func);
+                }
+            }
+            WorldItem::Type { .. } => {}
+        }
+    }
+
+    if found.is_empty() {
+        return Ok(());
+    }
+    bail!(
+        "import_log can't record or replay imports passing resources, streams, futures or \
+         error contexts:\n  {}",
+        found.join("\n  ")
+    )
+}
+
+/// Whether a value of `ty` can hold a handle.
+fn has_handle(resolve: &Resolve, ty: &Type) -> bool {
+    let Type::Id(id) = *ty else {
+        return matches!(ty, Type::ErrorContext);
+    };
+    match &resolve.types[id].kind {
+        TypeDefKind::Record(record) => record.fields.iter().any(|f| has_handle(resolve, &f.ty)),
+        TypeDefKind::Tuple(tuple) => tuple.types.iter().any(|ty| has_handle(resolve, ty)),
+        TypeDefKind::Variant(variant) => variant
+            .cases
+            .iter()
+            .filter_map(|case| case.ty.as_ref())
+            .any(|ty| has_handle(resolve, ty)),
+        TypeDefKind::Result(result) => result
+            .ok
+            .iter()
+            .chain(&result.err)
+            .any(|ty| has_handle(resolve, ty)),
+        TypeDefKind::Option(ty) | TypeDefKind::List(ty) | TypeDefKind::Type(ty) => {
+            has_handle(resolve, ty)
+        }
+        TypeDefKind::Resource
+        | TypeDefKind::Handle(_)
+        | TypeDefKind::Future(_)
+        | TypeDefKind::Stream(_) => true,
+        _ => false,
+    }
+}
+
+struct Support<'a> {
+    resolve: &'a Resolve,
+    /// Types already checked, so each unsupported one is reported once.
+    seen: IndexSet<TypeId>,
+    found: Vec<String>,
+}
+
+impl Support<'_> {
+    fn function(&mut self, direction: &str, name: &str, func: &Function) {
+        // Async exports are looked up as freestanding functions of the module.
+        if direction == "export"
+            && matches!(
+                func.kind,
+                FunctionKind::AsyncMethod(_) | FunctionKind::AsyncStatic(_)
+            )
+        {
+            self.found.push(format!(
+                "exported {name}: async resource methods and static functions are not supported"
+            ));
+        }
+        for ty in func.params.iter().map(|p| &p.ty).chain(&func.result) {
+            self.ty(direction, name, ty);
+        }
+    }
+
+    fn ty(&mut self, direction: &str, name: &str, ty: &Type) {
+        let Type::Id(id) = *ty else {
+            return;
+        };
+        if !self.seen.insert(id) {
+            return;
+        }
+        let resolve = self.resolve;
+        match &resolve.types[id].kind {
+            TypeDefKind::Record(record) => {
+                for field in &record.fields {
+                    self.ty(direction, name, &field.ty);
+                }
+            }
+            TypeDefKind::Tuple(tuple) => {
+                for ty in &tuple.types {
+                    self.ty(direction, name, ty);
+                }
+            }
+            TypeDefKind::Variant(variant) => {
+                for case in &variant.cases {
+                    if let Some(ty) = &case.ty {
+                        self.ty(direction, name, ty);
+                    }
+                }
+            }
+            TypeDefKind::Result(result) => {
+                for ty in result.ok.iter().chain(&result.err) {
+                    self.ty(direction, name, ty);
+                }
+            }
+            TypeDefKind::Option(ty) | TypeDefKind::List(ty) | TypeDefKind::Type(ty) => {
+                self.ty(direction, name, ty);
+            }
+            TypeDefKind::Future(ty) | TypeDefKind::Stream(ty) => {
+                if let Some(ty) = ty {
+                    self.ty(direction, name, ty);
+                }
+            }
+            TypeDefKind::Resource
+            | TypeDefKind::Handle(_)
+            | TypeDefKind::Flags(_)
+            | TypeDefKind::Enum(_) => {}
+            kind => {
+                let what = match &resolve.types[id].name {
+                    Some(type_name) => format!("`{type_name}`, a {}", kind.as_str()),
+                    None => format!("a {}", kind.as_str()),
+                };
+                self.found.push(format!(
+                    "{direction}ed {name}: uses {what}, which is not supported"
+                ));
+            }
+        }
+    }
+
+    fn into_result(self) -> Result<()> {
+        if self.found.is_empty() {
+            return Ok(());
+        }
+        bail!(
+            "the world uses WIT features the runtime does not support:\n  {}",
+            self.found.join("\n  ")
+        )
+    }
+}
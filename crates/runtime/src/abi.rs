@@ -199,6 +199,11 @@ impl CopyEnd {
         Ok((h, self.type_index))
     }
 
+    /// Whether the end was dropped locally or the other end has gone away.
+    pub(crate) fn closed(&self) -> bool {
+        self.handle.is_none() || self.state == CopyState::Done
+    }
+
     /// Transition to AsyncCopying when the operation blocks.
     pub(crate) fn mark_blocked(&mut self) {
         debug_assert_eq!(self.state, CopyState::Idle);
@@ -300,6 +305,15 @@ mod async_builtins {
         #[link_name = "[thread-yield]"]
         #[allow(dead_code)]
         pub(crate) fn thread_yield() -> u32;
+
+        #[link_name = "[error-context-new-utf8]"]
+        pub(crate) fn error_context_new_utf8(ptr: *const u8, len: usize) -> u32;
+
+        #[link_name = "[error-context-debug-message-utf8]"]
+        pub(crate) fn error_context_debug_message_utf8(handle: u32, ret: *mut usize);
+
+        #[link_name = "[error-context-drop]"]
+        pub(crate) fn error_context_drop(handle: u32);
     }
 
     #[link(wasm_import_module = "[export]$root")]
@@ -368,10 +382,37 @@ mod async_builtins {
     pub(crate) unsafe fn backpressure_set(_enabled: u32) {
         async_disabled()
     }
+
+    pub(crate) unsafe fn error_context_new_utf8(_ptr: *const u8, _len: usize) -> u32 {
+        async_disabled()
+    }
+
+    pub(crate) unsafe fn error_context_debug_message_utf8(_handle: u32, _ret: *mut usize) {
+        async_disabled()
+    }
+
+    pub(crate) unsafe fn error_context_drop(_handle: u32) {
+        async_disabled()
+    }
 }
 
 pub(crate) use async_builtins::*;
 
+/// Create an error-context with `message` as its debug message.
+pub(crate) fn error_context_new(message: &str) -> u32 {
+    unsafe { error_context_new_utf8(message.as_ptr(), message.len()) }
+}
+
+/// Read the debug message of the error-context `handle`.
+pub(crate) fn error_context_debug_message(handle: u32) -> String {
+    let mut ret = [0usize; 2];
+    unsafe {
+        error_context_debug_message_utf8(handle, ret.as_mut_ptr());
+        // Allocated with `cabi_realloc`, so owned by us from here on.
+        String::from_raw_parts(ret[0] as *mut u8, ret[1], ret[1])
+    }
+}
+
 // WASI adapter state reset that is used during Wizer pre-initialization
 #[link(wasm_import_module = "wasi_snapshot_preview1")]
 unsafe extern "C" {
@@ -381,4 +422,7 @@ unsafe extern "C" {
 
 unsafe extern "C" {
     pub(crate) fn __wasilibc_reset_preopens();
+
+    /// Fill `buf` with `len` (at most 256) random bytes from `wasi:random`.
+    pub(crate) fn getentropy(buf: *mut u8, len: usize) -> i32;
 }
@@ -0,0 +1,84 @@
+//! Call-scoped storage for the values a call lifts and lowers.
+//!
+//! Values wait here between the steps of the `Call` trait, each of which runs
+//! in its own `with_ctx`. They are stored with their lifetime erased and handed
+//! back branded with the lifetime of the context they are read in. Unlike
+//! `Persistent`, nothing is checked or converted on the way in or out, which
+//! relies on two invariants:
+//!
+//! - the runtime has a single context, which outlives every call context, so
+//!   any `Ctx` a value is read in is the one it was created in;
+//! - values are only released inside `with_ctx`: an arena is either emptied by
+//!   the steps of its call or cleared with [`ValueArena::clear`] before it
+//!   drops, as `QjsCallContext` does.
+
+use rquickjs::{Ctx, Value};
+
+/// A stack of JS values owned by one call context.
+#[derive(Default)]
+pub(crate) struct ValueArena {
+    values: Vec<Value<'static>>,
+}
+
+impl ValueArena {
+    pub(crate) fn with_capacity(capacity: usize) -> Self {
+        Self {
+            values: Vec::with_capacity(capacity),
+        }
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Release every value; `ctx` shows the context is entered.
+    pub(crate) fn clear(&mut self, _ctx: &Ctx<'_>) {
+        self.values.clear();
+    }
+
+    pub(crate) fn push(&mut self, val: Value<'_>) {
+        // SAFETY: the value keeps its context alive, is only read back through
+        // `brand` and is only released inside `with_ctx`, as the module
+        // documentation requires.
+        let val = unsafe { std::mem::transmute::<Value<'_>, Value<'static>>(val) };
+        self.values.push(val);
+    }
+
+    pub(crate) fn pop<'js>(&mut self, ctx: &Ctx<'js>) -> Option<Value<'js>> {
+        self.values.pop().map(|val| brand(ctx, val))
+    }
+
+    /// Drop the top value.
+    pub(crate) fn discard(&mut self) {
+        self.values.pop().expect("stack underflow");
+    }
+
+    /// A new reference to the top value, which stays on the stack.
+    pub(crate) fn last<'js>(&self, ctx: &Ctx<'js>) -> Option<Value<'js>> {
+        self.values.last().map(|val| brand(ctx, val.clone()))
+    }
+
+    /// Remove the top `count` values, bottom first.
+    pub(crate) fn drain_last<'a, 'js>(
+        &'a mut self,
+        ctx: &'a Ctx<'js>,
+        count: usize,
+    ) -> impl Iterator<Item = Value<'js>> + 'a {
+        let start = self
+            .values
+            .len()
+            .checked_sub(count)
+            .expect("stack underflow");
+        self.values.drain(start..).map(|val| brand(ctx, val))
+    }
+}
+
+fn brand<'js>(_ctx: &Ctx<'js>, val: Value<'static>) -> Value<'js> {
+    // SAFETY: see the module documentation; `ctx` is the runtime's only
+    // context.
+    unsafe { std::mem::transmute::<Value<'static>, Value<'js>>(val) }
+}
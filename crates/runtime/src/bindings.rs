@@ -1,33 +1,41 @@
 //! WIT to/from JS binding registration.
-use heck::{ToLowerCamelCase, ToUpperCamelCase};
+use heck::ToUpperCamelCase;
 use rquickjs::Persistent;
 use rquickjs::function;
 use rquickjs::function::{Constructor, Rest, This};
+use rquickjs::object::Property;
 use rquickjs::{Ctx, Function, Object, Value};
 use smallvec::SmallVec;
-use wit_dylib_ffi::{Resource, Wit};
+use wit_dylib_ffi::{Resource, Type, Wit};
 
 use crate::CtxExt;
 use crate::futures::{make_future, register_future_classes};
-use crate::result::ResultBoundary;
+use crate::options::Naming;
+use crate::resources::handle_finalizer;
+use crate::result::{ResultBoundary, resolve_alias, unwrap_import_result};
 use crate::streams::{make_stream, register_stream_classes};
 use crate::task::Pending;
-use crate::trivia::iface_lookup;
+use crate::trivia::{fn_lookup, get_export_fn, iface_object, member_name};
 use crate::wit_imports::{FuncKind, WitInterface, classify, find_resource, root_bindings};
-use crate::{DetHashSet, DetIndexMap, QjsCallContext, coerce_fn};
+use crate::{DetHashSet, DetIndexMap, QjsCallContext, coerce_fn, symbol_dispose};
 
 /// Register all wit bindings on the js global scope.
 pub(crate) fn register(ctx: &rquickjs::Ctx<'_>, wit_def: Wit) -> rquickjs::Result<()> {
+    let utf8: Object = ctx.eval(UTF8_JS)?;
     register_stream_classes(ctx)?;
     register_future_classes(ctx)?;
-    register_resource_classes(ctx, wit_def)?;
+    register_resource_classes(ctx, wit_def, &utf8)?;
     register_root_imports(ctx, wit_def)?;
-    register_cqjs_namespace(ctx, wit_def)?;
+    register_cqjs_namespace(ctx, wit_def, &utf8)?;
     Ok(())
 }
 
 /// Build a JS "class" (constructor + prototype) for every imported resource.
-fn register_resource_classes<'js>(ctx: &Ctx<'js>, wit: Wit) -> rquickjs::Result<()> {
+fn register_resource_classes<'js>(
+    ctx: &Ctx<'js>,
+    wit: Wit,
+    utf8: &Object<'js>,
+) -> rquickjs::Result<()> {
     struct Group {
         resource: Resource,
         ctor: Option<usize>,
@@ -75,10 +83,18 @@ fn register_resource_classes<'js>(ctx: &Ctx<'js>, wit: Wit) -> rquickjs::Result<
         usize,
         Persistent<Value<'static>>,
         Persistent<Value<'static>>,
+        Option<Persistent<Object<'static>>>,
     )> = Vec::new();
 
     for (index, group) in groups {
         let prototype = Object::new(ctx.clone())?;
+
+        // Installed before the WIT methods so a resource method that is itself
+        // named `drop` takes precedence.
+        let drop_fn = resource_drop_fn(ctx, group.resource)?;
+        prototype.set("drop", drop_fn.clone())?;
+        prototype.set(symbol_dispose(ctx)?, drop_fn)?;
+
         for (method, func_index) in group.methods {
             let js_func = Function::new(
                 ctx.clone(),
@@ -90,7 +106,16 @@ fn register_resource_classes<'js>(ctx: &Ctx<'js>, wit: Wit) -> rquickjs::Result<
                     call_import(ctx, func_index, call_args)
                 },
             )?;
-            prototype.set(method.to_lower_camel_case(), js_func)?;
+            prototype.set(fn_lookup(ctx, method), js_func)?;
+        }
+
+        if let Some(input) = wasi_io_stream(group.resource) {
+            let helpers: Function = ctx.eval(STREAM_HELPERS_JS)?;
+            let utf8_encode: Function = utf8.get("utf8Encode")?;
+            let unwrap = Function::new(ctx.clone(), |ctx: Ctx<'js>, value: Value<'js>| {
+                unwrap_import_result(&ctx, value)
+            })?;
+            helpers.call::<_, ()>((prototype.clone(), input, utf8_encode, unwrap))?;
         }
 
         let class: Constructor = match group.ctor {
@@ -121,24 +146,53 @@ fn register_resource_classes<'js>(ctx: &Ctx<'js>, wit: Wit) -> rquickjs::Result<
                 Function::new(ctx.clone(), move |ctx: Ctx<'js>, args: Rest<Value<'js>>| {
                     call_import(ctx, func_index, SmallVec::from_vec(args.0))
                 })?;
-            class.set(method.to_lower_camel_case(), js_func)?;
+            class.set(fn_lookup(ctx, method), js_func)?;
         }
 
+        let finalizer = handle_finalizer(ctx, group.resource)?;
         built.push((
             index,
             Persistent::save(ctx, class.into_value()),
             Persistent::save(ctx, prototype.into_value()),
+            finalizer.map(|f| Persistent::save(ctx, f)),
         ));
     }
 
     let registry = ctx.resource_classes();
-    for (index, class, prototype) in built {
-        registry.insert(index, class, prototype);
+    for (index, class, prototype, finalizer) in built {
+        registry.insert(index, class, prototype, finalizer);
     }
 
     Ok(())
 }
 
+/// Build the `drop()` / `[Symbol.dispose]()` method for an imported resource.
+///
+/// Drops the owned handle and removes `__cqjs_handle` so that a second drop is
+/// a no-op, and cancels the handle's finalizer. Borrowed handles are released
+/// by the call that lent them, so dropping one from JS throws.
+fn resource_drop_fn<'js>(ctx: &Ctx<'js>, resource: Resource) -> rquickjs::Result<Function<'js>> {
+    let drop_handle = resource.drop();
+    let index = resource.index();
+    Function::new(
+        ctx.clone(),
+        move |this: This<Object<'js>>, ctx: Ctx<'js>| -> rquickjs::Result<()> {
+            if this.0.get::<_, Option<bool>>("__cqjs_borrow")? == Some(true) {
+                return Err(rquickjs::Exception::throw_type(
+                    &ctx,
+                    "cannot drop a borrowed resource handle",
+                ));
+            }
+            if let Some(handle) = this.0.get::<_, Option<u32>>("__cqjs_handle")? {
+                this.0.remove("__cqjs_handle")?;
+                ctx.resource_classes().untrack_owned(&ctx, index, &this.0)?;
+                unsafe { drop_handle(handle) };
+            }
+            Ok(())
+        },
+    )
+}
+
 /// Create a js object containing all functions, flags, enums, and variants
 /// for a single wit interface.
 pub(crate) fn interface_to_js<'js>(
@@ -151,7 +205,7 @@ pub(crate) fn interface_to_js<'js>(
     for func in &iface.funcs {
         match classify(func.name()) {
             FuncKind::Freestanding => {
-                let func_name = func.name().to_lower_camel_case();
+                let func_name = fn_lookup(ctx, func.name());
                 let func_index = func.index();
                 let js_func = rquickjs::Function::new(
                     ctx.clone(),
@@ -193,18 +247,151 @@ fn register_root_imports(ctx: &rquickjs::Ctx<'_>, wit_def: Wit) -> rquickjs::Res
     Ok(())
 }
 
+/// Also expose the functions of `obj` named after the WIT functions `funcs`
+/// under their kebab-case names, per the naming policy, and under the names
+/// given by renames.
+///
+/// In kebab-case mode the camelCase names stay as non-enumerable aliases, since
+/// the generated shim calls imports by them.
+pub(crate) fn apply_naming<'js>(
+    ctx: &Ctx<'js>,
+    obj: &Object<'js>,
+    funcs: impl IntoIterator<Item = (Option<&'static str>, &'static str)>,
+) -> rquickjs::Result<()> {
+    let naming = ctx.options().naming;
+    for (iface, name) in funcs {
+        let member = member_name(name);
+        let camel = fn_lookup(ctx, member);
+        let value: Value = obj.get(camel)?;
+        if value.is_undefined() {
+            continue;
+        }
+        if naming != Naming::CamelCase && camel != member {
+            obj.set(member, value.clone())?;
+            if naming == Naming::KebabCase {
+                obj.prop(
+                    camel,
+                    Property::from(value.clone()).writable().configurable(),
+                )?;
+            }
+        }
+        let js_name = ctx.options().rename(iface, name);
+        if let Some(js_name) = js_name {
+            obj.set(js_name, value)?;
+        }
+    }
+    Ok(())
+}
+
+/// Apply the naming policy and renames to the root imports on the global
+/// object and to the methods and statics of the imported resource classes.
+pub(crate) fn apply_import_naming(ctx: &Ctx<'_>) -> rquickjs::Result<()> {
+    let wit = ctx.wit();
+    let root = root_bindings(wit);
+    let freestanding = root
+        .funcs
+        .iter()
+        .filter(|func| matches!(classify(func.name()), FuncKind::Freestanding))
+        .map(|func| (None, func.name()));
+    apply_naming(ctx, &ctx.globals(), freestanding)?;
+
+    let registry = ctx.resource_classes();
+    for func in wit.iter_import_funcs() {
+        let (resource, is_static) = match classify(func.name()) {
+            FuncKind::Method { resource, .. } => (resource, false),
+            FuncKind::Static { resource, .. } => (resource, true),
+            _ => continue,
+        };
+        let Some(resource) = find_resource(wit, func.interface(), resource) else {
+            continue;
+        };
+        let target = if is_static {
+            registry.class(resource.index())
+        } else {
+            registry.prototype(resource.index())
+        };
+        let Some(target) = target.and_then(|target| target.restore(ctx).ok()?.into_object()) else {
+            continue;
+        };
+        apply_naming(ctx, &target, [(func.interface(), func.name())])?;
+    }
+    Ok(())
+}
+
 fn call_import<'js>(
     ctx: rquickjs::Ctx<'js>,
     func_index: usize,
-    args: SmallVec<[Value<'js>; 8]>,
+    mut args: SmallVec<[Value<'js>; 8]>,
 ) -> rquickjs::Result<Value<'js>> {
     let wit_def = ctx.wit();
     let func = wit_def.import_func(func_index);
 
-    let boundary = ResultBoundary::new(func.result());
-    let mut call = QjsCallContext::default();
+    #[cfg(feature = "profile")]
+    let _profile = crate::profile::enter_import(&func);
+    let _panic = crate::panic::enter_import(func.interface(), func.name());
+
+    let qualified_name = || match func.interface() {
+        Some(iface) => format!("{iface}#{}", func.name()),
+        None => func.name().to_string(),
+    };
+
+    // Trailing `option` parameters may be left out, like optional arguments
+    // of a JS API, and are passed as `none`. Leaving out any other parameter
+    // is an arity error rather than a failure deep in lowering.
+    let is_option = |ty: &Type| matches!(resolve_alias(*ty), Type::Option(_));
+    let params: SmallVec<[Type; 8]> = func.params().collect();
+    let omitted = params.get(args.len()..).unwrap_or_default();
+    if !omitted.iter().all(is_option) {
+        // A method's receiver is `this`, not an argument the caller counts.
+        let receiver = usize::from(matches!(classify(func.name()), FuncKind::Method { .. }));
+        let total = params.len() - receiver;
+        let optional = params.iter().rev().take_while(|ty| is_option(ty)).count();
+        let expected = match total - optional {
+            required if required == total => format!("{total}"),
+            required => format!("{required} to {total}"),
+        };
+        let plural = if total == 1 { "" } else { "s" };
+        return Err(rquickjs::Exception::throw_type(
+            &ctx,
+            &format!(
+                "`{}` expects {expected} argument{plural}, got {}",
+                qualified_name(),
+                args.len().saturating_sub(receiver)
+            ),
+        ));
+    }
+    let missing = omitted.len();
+    args.extend((0..missing).map(|_| Value::new_undefined(ctx.clone())));
+
+    let boundary = ResultBoundary::import(&ctx, func.result());
+
+    // Async imports are not captured: their results arrive through the event
+    // loop and can interleave arbitrarily with other calls.
+    let logged_name = (!func.is_async() && ctx.import_log().is_active()).then(qualified_name);
+    if let Some(name) = &logged_name {
+        let replayed = ctx.import_log().replay(&ctx, name)?;
+        if let Some(ret) = replayed {
+            let ret = func.result().map(|_| ret);
+            return boundary.lift(&ctx, ret)?.into_result(&ctx);
+        }
+    }
+    crate::validate::check(
+        &ctx,
+        |index| format!("`{}` argument {index}", qualified_name()),
+        args.iter().zip(func.params()),
+    )?;
+    crate::limits::check(
+        &ctx,
+        || format!("argument to `{}`", qualified_name()),
+        args.iter().zip(func.params()),
+    )?;
+    let logged_args = logged_name
+        .filter(|_| ctx.import_log().is_recording())
+        .map(|name| (name, args.clone()));
+
+    let mut call = QjsCallContext::default().traced(func.interface(), func.name());
     for arg in args.into_iter().rev() {
-        call.push_value(&ctx, arg);
+        call.push_value(arg);
     }
 
     if func.is_async() {
@@ -226,7 +413,7 @@ fn call_import<'js>(
             ctx.task().register(handle, pending);
         } else {
             boundary
-                .lift(&ctx, call.maybe_pop_value(&ctx)?)?
+                .lift(&ctx, call.maybe_pop_value(&ctx))?
                 .settle(&resolve, &reject)
                 .expect("Failed to settle async import");
         }
@@ -234,9 +421,11 @@ fn call_import<'js>(
         Ok(promise.into_value())
     } else {
         func.call_import_sync(&mut call);
-        boundary
-            .lift(&ctx, call.maybe_pop_value(&ctx)?)?
-            .into_result(&ctx)
+        let ret = call.maybe_pop_value(&ctx);
+        if let Some((name, args)) = logged_args {
+            ctx.import_log().record(&ctx, &name, &args, ret.as_ref())?;
+        }
+        boundary.lift(&ctx, ret)?.into_result(&ctx)
     }
 }
 
@@ -254,24 +443,25 @@ fn build_async_exports<'js>(
     let mut iface_objs: DetIndexMap<String, rquickjs::Object<'_>> = DetIndexMap::default();
 
     for (func_index, func) in wit_def.iter_export_funcs().enumerate() {
-        let func_name = func.name().to_lower_camel_case();
-        let iface_name = func
-            .interface()
-            .map(|interface| iface_lookup(ctx, interface).to_string());
+        let func_name = fn_lookup(ctx, func.name());
+        let iface_name = func.interface();
 
-        let fn_name = func_name.clone();
-        let iface = iface_name.clone();
+        let wit_name = func.name();
 
         let wrapper = Function::new(
             ctx.clone(),
             coerce_fn(move |ctx: Ctx<'_>, args: Rest<Value<'_>>| {
                 let exports = ctx.user_module().exports(&ctx)?;
 
-                let user_fn: Function = if let Some(ref iface) = iface {
-                    let iface_obj: rquickjs::Object = exports.get(iface.as_str())?;
-                    iface_obj.get(fn_name.as_str())?
+                let user_fn: Function = if let Some(iface) = iface_name {
+                    get_export_fn(
+                        &ctx,
+                        &iface_object(&ctx, &exports, iface)?,
+                        iface_name,
+                        wit_name,
+                    )?
                 } else {
-                    exports.get(fn_name.as_str())?
+                    get_export_fn(&ctx, &exports, None, wit_name)?
                 };
 
                 let mut js_args = function::Args::new(ctx.clone(), args.0.len());
@@ -296,15 +486,16 @@ fn build_async_exports<'js>(
                             .unwrap_or_else(|| Value::new_undefined(ctx.clone()));
 
                         let func = ctx.wit().export_func(func_index);
-                        let boundary = ResultBoundary::new(func.result());
-                        let mut call = QjsCallContext::default();
+                        let boundary = ResultBoundary::new(&ctx, func.result());
+                        let mut call =
+                            QjsCallContext::default().traced(func.interface(), func.name());
 
                         let value = boundary.lower_value(&ctx, value).unwrap_or_else(|e| {
                             panic!("Call failed '{}': {:?}", "async export", e)
                         });
 
                         if let Some(value) = value {
-                            call.push_value(&ctx, value);
+                            call.push_value(value);
                         }
                         func.call_task_return(&mut call);
                         Ok(Value::new_undefined(ctx))
@@ -320,14 +511,15 @@ fn build_async_exports<'js>(
                             .next()
                             .unwrap_or_else(|| Value::new_undefined(ctx.clone()));
                         let func = ctx.wit().export_func(func_index);
-                        let boundary = ResultBoundary::new(func.result());
-                        let mut call = QjsCallContext::default();
+                        let boundary = ResultBoundary::new(&ctx, func.result());
+                        let mut call =
+                            QjsCallContext::default().traced(func.interface(), func.name());
                         let value = boundary.lower_throw(&ctx, reason).unwrap_or_else(|e| {
                             panic!("Call failed '{}': {:?}", "async export", e)
                         });
 
                         if let Some(value) = value {
-                            call.push_value(&ctx, value);
+                            call.push_value(value);
                         }
 
                         func.call_task_return(&mut call);
@@ -343,13 +535,13 @@ fn build_async_exports<'js>(
             }),
         )?;
 
-        let target = match &iface_name {
+        let target = match iface_name {
             Some(iface) => iface_objs
-                .entry(iface.clone())
+                .entry(iface.to_string())
                 .or_insert_with(|| rquickjs::Object::new(ctx.clone()).unwrap()),
             None => &exports,
         };
-        target.set(func_name.as_str(), wrapper)?;
+        target.set(func_name, wrapper)?;
     }
 
     for (name, obj) in iface_objs {
@@ -359,15 +551,178 @@ fn build_async_exports<'js>(
     Ok(exports)
 }
 
+/// Whether `resource` is the `input-stream` (`Some(true)`) or `output-stream`
+/// (`Some(false)`) of `wasi:io/streams`.
+fn wasi_io_stream(resource: Resource) -> Option<bool> {
+    let iface = resource.interface()?;
+    let unversioned = iface.split_once('@').map_or(iface, |(name, _)| name);
+    if unversioned != "wasi:io/streams" {
+        return None;
+    }
+    match resource.name() {
+        "input-stream" => Some(true),
+        "output-stream" => Some(false),
+        _ => None,
+    }
+}
+
+/// Conveniences added to the `wasi:io/streams` prototypes, each unless a WIT
+/// method already has the name.
+///
+/// An input stream is iterable, synchronously or with `for await`, over the
+/// chunks `blockingRead` returns until the stream is closed, and `readAll()`
+/// collects them into one `Uint8Array`. An output stream's `writeAll(data)`
+/// writes a string or buffer in chunks the host accepts, flushing each;
+/// strings are encoded with the lenient `utf8Encode` of [`UTF8_JS`].
+///
+/// The helpers call the stream methods through `unwrap`, which turns a tagged
+/// `result` back into a return or a throw, so they work the same whether or
+/// not the build passes import results as `{ tag, val }` objects.
+const STREAM_HELPERS_JS: &str = r#"(prototype, input, utf8Encode, unwrap) => {
+  const define = (key, value) => {
+    if (key in prototype) return;
+    Object.defineProperty(prototype, key, { value, writable: true, configurable: true });
+  };
+  if (input) {
+    const chunks = function* (stream, size) {
+      for (;;) {
+        let chunk;
+        try {
+          chunk = unwrap(stream.blockingRead(size));
+        } catch (e) {
+          if (e?.payload?.tag === "closed") return;
+          throw e;
+        }
+        if (chunk.length) yield chunk;
+      }
+    };
+    define(Symbol.iterator, function (size = 65536) {
+      return chunks(this, size);
+    });
+    define(Symbol.asyncIterator, async function* (size = 65536) {
+      yield* chunks(this, size);
+    });
+    define("readAll", function () {
+      const all = [...chunks(this, 65536)];
+      const bytes = new Uint8Array(all.reduce((length, chunk) => length + chunk.length, 0));
+      let offset = 0;
+      for (const chunk of all) {
+        bytes.set(chunk, offset);
+        offset += chunk.length;
+      }
+      return bytes;
+    });
+  } else {
+    const toBytes = (data) => {
+      if (typeof data === "string") return utf8Encode(data);
+      if (data instanceof ArrayBuffer) return new Uint8Array(data);
+      if (ArrayBuffer.isView(data)) {
+        return new Uint8Array(data.buffer, data.byteOffset, data.byteLength);
+      }
+      throw new TypeError("writeAll: data must be a string or a buffer");
+    };
+    define("writeAll", function (data) {
+      const bytes = toBytes(data);
+      for (let i = 0; i < bytes.length; i += 4096) {
+        unwrap(this.blockingWriteAndFlush(bytes.subarray(i, i + 4096)));
+      }
+    });
+  }
+}"#;
+
+/// `utf8Encode` and `utf8Decode`, installed as `__cqjs.utf8Encode` and
+/// `__cqjs.utf8Decode` for the generated shims and used by `writeAll`.
+///
+/// They follow `TextEncoder` and a non-fatal `TextDecoder` without needing the
+/// `encoding` polyfill: lone surrogates are encoded, and invalid UTF-8 decoded,
+/// as U+FFFD rather than throwing.
+const UTF8_JS: &str = r#"(() => {
+  const utf8Encode = (s) => {
+    const bytes = [];
+    for (const ch of s) {
+      let c = ch.codePointAt(0);
+      if (c >= 0xd800 && c <= 0xdfff) c = 0xfffd;
+      if (c < 0x80) {
+        bytes.push(c);
+      } else if (c < 0x800) {
+        bytes.push(0xc0 | (c >> 6), 0x80 | (c & 0x3f));
+      } else if (c < 0x10000) {
+        bytes.push(0xe0 | (c >> 12), 0x80 | ((c >> 6) & 0x3f), 0x80 | (c & 0x3f));
+      } else {
+        bytes.push(0xf0 | (c >> 18), 0x80 | ((c >> 12) & 0x3f), 0x80 | ((c >> 6) & 0x3f), 0x80 | (c & 0x3f));
+      }
+    }
+    return Uint8Array.from(bytes);
+  };
+  const utf8Decode = (bytes) => {
+    let out = "";
+    const units = [];
+    const emit = (c) => {
+      if (c > 0xffff) units.push(0xd800 + ((c - 0x10000) >> 10), 0xdc00 + ((c - 0x10000) & 0x3ff));
+      else units.push(c);
+      if (units.length >= 8192) out += String.fromCharCode.apply(null, units.splice(0));
+    };
+    let needed = 0, seen = 0, codePoint = 0, lower = 0x80, upper = 0xbf;
+    for (let i = 0; i < bytes.length; i++) {
+      const b = bytes[i];
+      if (needed === 0) {
+        if (b <= 0x7f) {
+          emit(b);
+        } else if (b >= 0xc2 && b <= 0xdf) {
+          needed = 1;
+          codePoint = b & 0x1f;
+        } else if (b >= 0xe0 && b <= 0xef) {
+          if (b === 0xe0) lower = 0xa0;
+          if (b === 0xed) upper = 0x9f;
+          needed = 2;
+          codePoint = b & 0xf;
+        } else if (b >= 0xf0 && b <= 0xf4) {
+          if (b === 0xf0) lower = 0x90;
+          if (b === 0xf4) upper = 0x8f;
+          needed = 3;
+          codePoint = b & 0x7;
+        } else {
+          emit(0xfffd);
+        }
+        continue;
+      }
+      if (b < lower || b > upper) {
+        // The byte ends the broken sequence and is read again on its own.
+        needed = seen = codePoint = 0;
+        lower = 0x80;
+        upper = 0xbf;
+        emit(0xfffd);
+        i--;
+        continue;
+      }
+      lower = 0x80;
+      upper = 0xbf;
+      codePoint = (codePoint << 6) | (b & 0x3f);
+      if (++seen === needed) {
+        emit(codePoint);
+        needed = seen = codePoint = 0;
+      }
+    }
+    if (needed !== 0) emit(0xfffd);
+    return out + String.fromCharCode.apply(null, units);
+  };
+  return { utf8Encode, utf8Decode };
+})()"#;
+
 /// Register the `__cqjs` namespace object on globalThis.
 ///
 /// Consolidates all internal bridge globals into a single frozen object:
 /// - `makeStream(typeIndex)` — create a stream pair
 /// - `makeFuture(typeIndex)` — create a future pair
-/// - `getMemoryUsage()` — return QuickJS memory statistics
+/// - `getMemoryUsage()` — return QuickJS memory statistics and the sampled peak
 /// - `runGc()` — trigger QuickJS garbage collection
+/// - `utf8Encode(string)` and `utf8Decode(bytes)` — lenient UTF-8 conversion
 /// - `asyncExports` — object containing async export wrappers
-fn register_cqjs_namespace(ctx: &rquickjs::Ctx<'_>, wit_def: Wit) -> rquickjs::Result<()> {
+fn register_cqjs_namespace<'js>(
+    ctx: &rquickjs::Ctx<'js>,
+    wit_def: Wit,
+    utf8: &Object<'js>,
+) -> rquickjs::Result<()> {
     let ns = rquickjs::Object::new(ctx.clone())?;
 
     // Stream/future factories
@@ -394,12 +749,7 @@ fn register_cqjs_namespace(ctx: &rquickjs::Ctx<'_>, wit_def: Wit) -> rquickjs::R
             ctx.clone(),
             coerce_fn(
                 move |ctx: Ctx<'_>, _args: Rest<Value<'_>>| -> rquickjs::Result<Value<'_>> {
-                    let usage = unsafe {
-                        let rt = rquickjs::qjs::JS_GetRuntime(ctx.as_raw().as_ptr());
-                        let mut usage = std::mem::MaybeUninit::uninit();
-                        rquickjs::qjs::JS_ComputeMemoryUsage(rt, usage.as_mut_ptr());
-                        usage.assume_init()
-                    };
+                    let usage = crate::memory::usage(&ctx);
                     let obj = rquickjs::Object::new(ctx.clone())?;
                     obj.set("mallocSize", usage.malloc_size)?;
                     obj.set("mallocCount", usage.malloc_count)?;
@@ -411,6 +761,7 @@ fn register_cqjs_namespace(ctx: &rquickjs::Ctx<'_>, wit_def: Wit) -> rquickjs::R
                     obj.set("propCount", usage.prop_count)?;
                     obj.set("shapeCount", usage.shape_count)?;
                     obj.set("arrayCount", usage.array_count)?;
+                    obj.set("peakMallocSize", crate::memory::peak())?;
                     Ok(obj.into_value())
                 },
             ),
@@ -433,6 +784,37 @@ fn register_cqjs_namespace(ctx: &rquickjs::Ctx<'_>, wit_def: Wit) -> rquickjs::R
         )?,
     )?;
 
+    #[cfg(feature = "profile")]
+    ns.set(
+        "getProfile",
+        Function::new(
+            ctx.clone(),
+            coerce_fn(
+                move |ctx: Ctx<'_>, _args: Rest<Value<'_>>| -> rquickjs::Result<Value<'_>> {
+                    Ok(crate::profile::snapshot(&ctx)?.into_value())
+                },
+            ),
+        )?,
+    )?;
+
+    #[cfg(feature = "profile")]
+    ns.set(
+        "dumpProfile",
+        Function::new(
+            ctx.clone(),
+            coerce_fn(
+                move |ctx: Ctx<'_>, _args: Rest<Value<'_>>| -> rquickjs::Result<Value<'_>> {
+                    crate::profile::dump();
+                    Ok(Value::new_undefined(ctx))
+                },
+            ),
+        )?,
+    )?;
+
+    // UTF-8 helpers for the generated shims
+    ns.set("utf8Encode", utf8.get::<_, Function>("utf8Encode")?)?;
+    ns.set("utf8Decode", utf8.get::<_, Function>("utf8Decode")?)?;
+
     // Async export wrappers
     let async_exports = build_async_exports(ctx, wit_def)?;
     ns.set("asyncExports", async_exports)?;
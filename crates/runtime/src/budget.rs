@@ -0,0 +1,48 @@
+//! Execution budget for runaway JavaScript.
+//!
+//! QuickJS calls an interrupt handler about every 10,000 bytecode operations.
+//! With a budget configured, every entry into JavaScript from the host gets a
+//! fixed number of those checks, and running out traps the component instead
+//! of letting an accidental `while (true)` hang the host.
+
+use std::cell::Cell;
+
+/// Bytecode operations between two interrupt checks, QuickJS'
+/// `JS_INTERRUPT_COUNTER_INIT`.
+const OPS_PER_CHECK: u64 = 10_000;
+
+thread_local! {
+    /// Interrupt checks each entry from the host gets, if limited
+    static CHECKS: Cell<Option<u64>> = const { Cell::new(None) };
+    /// Interrupt checks left for the current entry
+    static REMAINING: Cell<u64> = const { Cell::new(0) };
+}
+
+/// Limit every entry from the host to about `max_instructions` bytecode
+/// operations.
+pub(crate) fn enable(max_instructions: u64) {
+    CHECKS.set(Some(max_instructions.div_ceil(OPS_PER_CHECK)));
+}
+
+/// Start a fresh budget for an entry from the host.
+pub(crate) fn reset() {
+    if let Some(checks) = CHECKS.get() {
+        REMAINING.set(checks);
+    }
+}
+
+/// Count an interrupt check against the current entry, from the runtime's
+/// interrupt handler.
+pub(crate) fn tick() -> bool {
+    if CHECKS.get().is_none() {
+        return false;
+    }
+    let remaining = REMAINING.get();
+    if remaining == 0 {
+        // Trap rather than throw: an interrupted call could otherwise be
+        // mapped to the `err` case of a `result` export.
+        panic!("execution budget exceeded");
+    }
+    REMAINING.set(remaining - 1);
+    false
+}
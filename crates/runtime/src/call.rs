@@ -1,13 +1,17 @@
 //! `Call` trait implementation for quickjs to/from wit type conversions.
 use crate::CtxExt;
+use crate::arena::ValueArena;
+use crate::error_context;
 use crate::futures::{FutureReadable, FutureWritable};
 use crate::resources::{exported_resource_to_handle, imported_resource_to_handle};
 use crate::streams::{StreamReadable, StreamWritable};
+use crate::trace;
 use crate::trivia::fn_lookup;
-use crate::{BorrowedResource, QjsCallContext, with_ctx};
+use crate::validate::{self, describe};
+use crate::{BorrowedResource, ListBatch, QjsCallContext, with_ctx};
 
 use rquickjs::class::Class;
-use rquickjs::{Coerced, IntoJs, Persistent, Value};
+use rquickjs::{Array, Coerced, Ctx, Function, IntoJs, Object, Value};
 use smallvec::SmallVec;
 use wit_dylib_ffi::{
     Call, Enum, Flags, Future, List, Record, Resource, Stream, Tuple, Type, Variant, WitOption,
@@ -17,7 +21,7 @@ use wit_dylib_ffi::{
 use std::alloc::Layout;
 
 /// Whether an option's payload is itself an option (after resolving aliases).
-fn option_is_nested(ty: WitOption) -> bool {
+pub(crate) fn option_is_nested(ty: WitOption) -> bool {
     let mut inner = ty.ty();
     loop {
         match inner {
@@ -28,20 +32,135 @@ fn option_is_nested(ty: WitOption) -> bool {
     }
 }
 
-/// Pop a value from the stack, restore it in the current JS context, and transform it.
+/// The length of a value lowered as a `list`: an `Array`, or an array-like
+/// object with a whole, non-negative `length`.
+///
+/// Elements are read one at a time while lowering, so an array-like whose
+/// indexed properties are getters (or a `Proxy`) can produce a huge list
+/// without it ever existing as a JavaScript array.
+pub(crate) fn list_length(v: &Value<'_>) -> Option<usize> {
+    if let Some(array) = v.as_array() {
+        return Some(array.len());
+    }
+    if v.is_function() {
+        return None;
+    }
+    let length: Value = v.as_object()?.get("length").ok()?;
+    length
+        .as_number()
+        .filter(|n| n.fract() == 0.0 && (0.0..=f64::from(u32::MAX)).contains(n))
+        .map(|n| n as usize)
+}
+
+/// The values of a `Set` lowered as a `list`, copied into an array in
+/// insertion order, or `None` if `v` isn't a `Set`.
+///
+/// Only worth checking once [`list_length`] has turned `v` down.
+pub(crate) fn set_values<'js>(
+    ctx: &Ctx<'js>,
+    v: &Value<'js>,
+) -> rquickjs::Result<Option<Array<'js>>> {
+    let Some(obj) = v.as_object() else {
+        return Ok(None);
+    };
+    let set: Value = ctx.globals().get("Set")?;
+    if !obj.is_instance_of(&set) {
+        return Ok(None);
+    }
+    let from: Function = ctx.globals().get::<_, Object>("Array")?.get("from")?;
+    from.call((obj.clone(),)).map(Some)
+}
+
+/// The `char` that `v` lowers to: a string of exactly one code point, or an
+/// integer that is a Unicode scalar value.
+///
+/// Converting to UTF-8 joins a surrogate pair into one code point, so an
+/// astral character like "😀" counts as one even though its `length` is 2.
+pub(crate) fn char_value(v: &Value<'_>) -> Option<char> {
+    if let Some(s) = v.as_string() {
+        let s = s.to_string().ok()?;
+        let mut chars = s.chars();
+        return match (chars.next(), chars.next()) {
+            (Some(c), None) => Some(c),
+            _ => None,
+        };
+    }
+    v.as_number()
+        .filter(|n| n.fract() == 0.0 && (0.0..=f64::from(u32::MAX)).contains(n))
+        .and_then(|n| char::from_u32(n as u32))
+}
+
+/// The value of `v` modulo 2^128 if it's a `BigInt`, for casting to an integer
+/// type with `as`.
+///
+/// [`check_range`] rejects out-of-range values before they get here unless the
+/// build wraps integers, in which case the cast wraps them like any other
+/// number.
+pub(crate) fn big_int(v: &Value<'_>) -> Option<i128> {
+    if !v.is_big_int() {
+        return None;
+    }
+    let digits = v.get::<Coerced<String>>().ok()?.0;
+    let (negative, digits) = match digits.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, digits.as_str()),
+    };
+    let n = digits.bytes().fold(0u128, |n, digit| {
+        n.wrapping_mul(10).wrapping_add(u128::from(digit - b'0'))
+    });
+    let n = if negative { n.wrapping_neg() } else { n };
+    Some(n as i128)
+}
+
+/// Trap on an integer outside the range of `ty`, unless the build wraps
+/// integers.
+///
+/// Lowering can't throw back into JavaScript, so `validate::check` raises a
+/// catchable `RangeError` before it starts, but only for top-level values;
+/// checking an integer nested in a larger value there would walk the value
+/// twice. Those are checked here instead, as they are lowered.
+fn check_range(v: &Value<'_>, ty: Type) {
+    if v.ctx().options().wrap_integers {
+        return;
+    }
+    if let Some(problem) = validate::out_of_range(v, ty) {
+        panic!("{problem}");
+    }
+}
+
+/// Pop a value from the stack in the current JS context and transform it.
 fn pop_with<R: 'static>(cx: &mut QjsCallContext, f: impl FnOnce(Value<'_>) -> R) -> R {
-    let persistent = cx.pop_persistent();
-    with_ctx(|ctx| {
-        let v = persistent.restore(ctx).unwrap();
-        f(v)
-    })
+    with_ctx(|ctx| f(cx.pop_value(ctx)))
+}
+
+impl QjsCallContext {
+    /// Trace the value on top of the stack, about to be lowered as a `ty`.
+    fn trace_lower(&self, ty: &str) {
+        if trace::enabled() {
+            with_ctx(|ctx| {
+                if let Some(value) = self.stack.last(ctx) {
+                    trace::lower(self.traced.as_deref(), ty, &value);
+                }
+            });
+        }
+    }
+
+    /// Trace the value on top of the stack, just lifted from a `ty`.
+    fn trace_lift(&self, ty: &str) {
+        if trace::enabled() {
+            with_ctx(|ctx| {
+                if let Some(value) = self.stack.last(ctx) {
+                    trace::lift(self.traced.as_deref(), ty, &value);
+                }
+            });
+        }
+    }
 }
 
 /// Create a JS value in the current context and push it onto the stack.
 fn push_with(cx: &mut QjsCallContext, f: impl for<'js> FnOnce(&rquickjs::Ctx<'js>) -> Value<'js>) {
     with_ctx(|ctx| {
-        let v = f(ctx);
-        cx.push_value(ctx, v);
+        cx.push_value(f(ctx));
     });
 }
 
@@ -86,72 +205,133 @@ macro_rules! try_typed_array_copy {
     };
 }
 
+/// Replace every NaN in a float list copied by `try_typed_array_copy!` with
+/// the canonical NaN, so NaN payloads don't leak out of the component.
+macro_rules! canonicalize_nans {
+    ($copy:expr, $t:ty) => {
+        $copy.inspect(|&(ptr, count, _)| {
+            if count > 0 {
+                let slice = unsafe { std::slice::from_raw_parts_mut(ptr as *mut $t, count) };
+                for n in slice.iter_mut().filter(|n| n.is_nan()) {
+                    *n = <$t>::NAN;
+                }
+            }
+        })
+    };
+}
+
 impl Call for QjsCallContext {
     unsafe fn defer_deallocate(&mut self, ptr: *mut u8, layout: Layout) {
         self.deferred_deallocs.push((ptr, layout));
     }
 
     fn pop_bool(&mut self) -> bool {
+        self.trace_lower("bool");
         pop_with(self, |v| v.as_bool().expect("expected bool"))
     }
 
     fn pop_u8(&mut self) -> u8 {
-        pop_with(self, |v| v.get::<i32>().expect("expected number") as u8)
+        self.trace_lower("u8");
+        pop_with(self, |v| {
+            check_range(&v, Type::U8);
+            v.get::<i32>().expect("expected number") as u8
+        })
     }
 
     fn pop_s8(&mut self) -> i8 {
-        pop_with(self, |v| v.get::<i32>().expect("expected number") as i8)
+        self.trace_lower("s8");
+        pop_with(self, |v| {
+            check_range(&v, Type::S8);
+            v.get::<i32>().expect("expected number") as i8
+        })
     }
 
     fn pop_u16(&mut self) -> u16 {
-        pop_with(self, |v| v.get::<i32>().expect("expected number") as u16)
+        self.trace_lower("u16");
+        pop_with(self, |v| {
+            check_range(&v, Type::U16);
+            v.get::<i32>().expect("expected number") as u16
+        })
     }
 
     fn pop_s16(&mut self) -> i16 {
-        pop_with(self, |v| v.get::<i32>().expect("expected number") as i16)
+        self.trace_lower("s16");
+        pop_with(self, |v| {
+            check_range(&v, Type::S16);
+            v.get::<i32>().expect("expected number") as i16
+        })
     }
 
     fn pop_u32(&mut self) -> u32 {
-        pop_with(self, |v| v.get::<i32>().expect("expected number") as u32)
+        self.trace_lower("u32");
+        pop_with(self, |v| {
+            check_range(&v, Type::U32);
+            big_int(&v).map_or_else(
+                || v.get::<i32>().expect("expected number") as u32,
+                |n| n as u32,
+            )
+        })
     }
 
     fn pop_s32(&mut self) -> i32 {
-        pop_with(self, |v| v.get().expect("expected number"))
+        self.trace_lower("s32");
+        pop_with(self, |v| {
+            check_range(&v, Type::S32);
+            v.get().expect("expected number")
+        })
     }
 
     fn pop_u64(&mut self) -> u64 {
-        pop_with(self, |v| v.get().expect("expected number"))
+        self.trace_lower("u64");
+        pop_with(self, |v| {
+            check_range(&v, Type::U64);
+            big_int(&v).map_or_else(|| v.get().expect("expected number"), |n| n as u64)
+        })
     }
 
     fn pop_s64(&mut self) -> i64 {
-        pop_with(self, |v| v.get().expect("expected number"))
+        self.trace_lower("s64");
+        pop_with(self, |v| {
+            check_range(&v, Type::S64);
+            big_int(&v).map_or_else(|| v.get().expect("expected number"), |n| n as i64)
+        })
     }
 
     fn pop_f32(&mut self) -> f32 {
-        pop_with(self, |v| v.get::<f64>().expect("expected number") as f32)
+        self.trace_lower("f32");
+        pop_with(self, |v| {
+            let n = v.get::<f64>().expect("expected number") as f32;
+            if n.is_nan() { f32::NAN } else { n }
+        })
     }
 
     fn pop_f64(&mut self) -> f64 {
-        pop_with(self, |v| v.get().expect("expected number"))
+        self.trace_lower("f64");
+        pop_with(self, |v| {
+            let n: f64 = v.get().expect("expected number");
+            if n.is_nan() { f64::NAN } else { n }
+        })
     }
 
     fn pop_char(&mut self) -> char {
+        self.trace_lower("char");
         pop_with(self, |v| {
-            let s = v.get::<String>().expect("expected string");
-            let mut chars = s.chars();
-            let c = chars.next().expect("expected non-empty string for char");
-            if chars.next().is_some() {
-                panic!("expected single char, got {} chars", s.chars().count());
-            }
-            c
+            char_value(&v).unwrap_or_else(|| {
+                panic!(
+                    "expected a string of one code point or a Unicode scalar value for char, \
+                     got {}",
+                    describe(&v)
+                )
+            })
         })
     }
 
     fn pop_string(&mut self) -> &str {
-        let persistent = self.stack.pop().expect("stack underflow");
+        self.trace_lower("string");
         let s = with_ctx(|ctx| {
-            let val = persistent.restore(ctx).unwrap();
-            val.get::<String>().expect("expected string")
+            self.pop_value(ctx)
+                .get::<String>()
+                .expect("expected string")
         });
 
         self.temp_strings.push(s);
@@ -159,10 +339,8 @@ impl Call for QjsCallContext {
     }
 
     unsafe fn maybe_pop_list(&mut self, ty: List) -> Option<(*const u8, usize)> {
-        let persistent = self.stack.last()?.clone();
-
         let result = with_ctx(|ctx| {
-            let val = persistent.restore(ctx).unwrap();
+            let val = self.stack.last(ctx)?;
             match ty.ty() {
                 Type::U8 => try_typed_array_copy!(val, u8),
                 Type::S8 => try_typed_array_copy!(val, i8),
@@ -172,8 +350,8 @@ impl Call for QjsCallContext {
                 Type::S32 => try_typed_array_copy!(val, i32),
                 Type::U64 => try_typed_array_copy!(val, u64),
                 Type::S64 => try_typed_array_copy!(val, i64),
-                Type::F32 => try_typed_array_copy!(val, f32),
-                Type::F64 => try_typed_array_copy!(val, f64),
+                Type::F32 => canonicalize_nans!(try_typed_array_copy!(val, f32), f32),
+                Type::F64 => canonicalize_nans!(try_typed_array_copy!(val, f64), f64),
                 _ => None,
             }
         });
@@ -182,31 +360,37 @@ impl Call for QjsCallContext {
             if layout.size() > 0 {
                 self.deferred_deallocs.push((ptr as *mut u8, layout));
             }
-            self.stack.pop();
+            self.trace_lower("list");
+            self.stack.discard();
             (ptr, count)
         })
     }
 
     fn pop_list(&mut self, _ty: List) -> usize {
+        self.trace_lower("list");
         self.iter_stack.push(0);
-        let persistent = self.stack.last().expect("stack underflow").clone();
-
         with_ctx(|ctx| {
-            let val = persistent.restore(ctx).unwrap();
-            let arr = val.as_array().expect("expected array");
-            arr.len()
+            let val = self.stack.last(ctx).expect("stack underflow");
+            if let Some(length) = list_length(&val) {
+                return length;
+            }
+            let values = set_values(ctx, &val)
+                .ok()
+                .flatten()
+                .expect("expected array");
+            self.stack.discard();
+            self.stack.push(values.clone().into_value());
+            values.len()
         })
     }
 
     fn pop_iter_next(&mut self, _ty: List) {
         let index = *self.iter_stack.last().expect("iter_stack underflow");
-        let arr_persistent = self.stack.last().expect("stack underflow").clone();
-
         with_ctx(|ctx| {
-            let arr_val = arr_persistent.restore(ctx).unwrap();
-            let arr = arr_val.as_array().expect("expected array");
-            let elem: Value = arr.get(index).unwrap();
-            self.stack.push(Persistent::save(ctx, elem));
+            let arr_val = self.stack.last(ctx).expect("stack underflow");
+            let arr = arr_val.as_object().expect("expected array");
+            let elem: Value = arr.get(index as u32).unwrap();
+            self.stack.push(elem);
         });
 
         *self.iter_stack.last_mut().unwrap() = index + 1;
@@ -214,39 +398,48 @@ impl Call for QjsCallContext {
 
     fn pop_iter(&mut self, _ty: List) {
         self.iter_stack.pop().expect("iter_stack underflow");
-        self.stack.pop().expect("stack underflow");
+        self.stack.discard();
     }
 
     fn pop_option(&mut self, ty: WitOption) -> u32 {
-        let persistent = self.stack.pop().expect("stack underflow");
+        self.trace_lower("option");
+        if !option_is_nested(ty) {
+            // Bare option, the common `option<string>` / `option<u32>` shape:
+            // `some(v)` is `v` itself, so leave it on the stack for the payload
+            // pop instead of popping and pushing it again.
+            return with_ctx(|ctx| {
+                let val = self.stack.last(ctx).expect("stack underflow");
+                if val.is_null() || val.is_undefined() {
+                    self.stack.discard();
+                    return 0;
+                }
+                1
+            });
+        }
 
         with_ctx(|ctx| {
-            let val = persistent.restore(ctx).unwrap();
-
-            if option_is_nested(ty) {
-                // Nested option: { tag: "some", val } | { tag: "none" }.
-                let obj = val.as_object().expect("expected nested option object");
-                let tag: String = obj.get("tag").expect("expected tag");
-                if tag == "some" {
-                    let inner: Value = obj.get("val").unwrap_or(Value::new_undefined(ctx.clone()));
-                    self.stack.push(Persistent::save(ctx, inner));
-                    1
-                } else {
-                    0
-                }
-            } else if val.is_null() || val.is_undefined() {
-                0
-            } else {
-                self.stack.push(Persistent::save(ctx, val));
+            // Nested option: { tag: "some", val } | { tag: "none" }. A bare
+            // `null` is accepted for the outer `none`, as for plain options.
+            let val = self.pop_value(ctx);
+            if val.is_null() || val.is_undefined() {
+                return 0;
+            }
+            let obj = val.as_object().expect("expected nested option object");
+            let tag: String = obj.get("tag").expect("expected tag");
+            if tag == "some" {
+                let inner: Value = obj.get("val").unwrap_or(Value::new_undefined(ctx.clone()));
+                self.stack.push(inner);
                 1
+            } else {
+                0
             }
         })
     }
 
     fn pop_result(&mut self, ty: WitResult) -> u32 {
-        let persistent = self.stack.pop().expect("stack underflow");
+        self.trace_lower("result");
         with_ctx(|ctx| {
-            let val = persistent.restore(ctx).unwrap();
+            let val = self.pop_value(ctx);
             let obj = val.as_object().expect("expected object");
             let tag: String = obj.get("tag").expect("expected tag");
 
@@ -260,7 +453,7 @@ impl Call for QjsCallContext {
 
             if has_payload {
                 let inner: Value = obj.get("val").unwrap_or(Value::new_undefined(ctx.clone()));
-                self.stack.push(Persistent::save(ctx, inner));
+                self.stack.push(inner);
             }
 
             discriminant
@@ -268,9 +461,9 @@ impl Call for QjsCallContext {
     }
 
     fn pop_variant(&mut self, ty: Variant) -> u32 {
-        let persistent = self.stack.pop().expect("stack underflow");
+        self.trace_lower("variant");
         with_ctx(|ctx| {
-            let val = persistent.restore(ctx).unwrap();
+            let val = self.pop_value(ctx);
             let obj = val.as_object().expect("expected object");
             let tag: String = obj.get("tag").expect("expected tag");
 
@@ -288,20 +481,16 @@ impl Call for QjsCallContext {
 
             if has_payload {
                 let inner: Value = obj.get("val").unwrap_or(Value::new_undefined(ctx.clone()));
-                self.stack.push(Persistent::save(ctx, inner));
+                self.stack.push(inner);
             }
             index
         })
     }
 
     fn pop_enum(&mut self, ty: Enum) -> u32 {
-        let persistent = self.pop_persistent();
+        self.trace_lower("enum");
         with_ctx(|ctx| {
-            let name: String = persistent
-                .restore(ctx)
-                .unwrap()
-                .get()
-                .expect("expected enum string");
+            let name: String = self.pop_value(ctx).get().expect("expected enum string");
             ty.names()
                 .position(|n| name == n)
                 .unwrap_or_else(|| panic!("unknown enum case: {name}")) as u32
@@ -309,9 +498,9 @@ impl Call for QjsCallContext {
     }
 
     fn pop_flags(&mut self, ty: Flags) -> u32 {
-        let persistent = self.pop_persistent();
+        self.trace_lower("flags");
         with_ctx(|ctx| {
-            let val = persistent.restore(ctx).unwrap();
+            let val = self.pop_value(ctx);
             let obj = val.as_object().expect("expected flags object");
             let mut bits = 0u32;
             for (i, name) in ty.names().enumerate() {
@@ -328,9 +517,9 @@ impl Call for QjsCallContext {
     }
 
     fn pop_borrow(&mut self, ty: Resource) -> u32 {
-        let persistent = self.pop_persistent();
+        self.trace_lower("borrow");
         with_ctx(|ctx| {
-            let val = persistent.restore(ctx).unwrap();
+            let val = self.pop_value(ctx);
             if ty.new().is_some() {
                 exported_resource_to_handle(ctx, ty, &val)
             } else {
@@ -340,42 +529,49 @@ impl Call for QjsCallContext {
     }
 
     fn pop_own(&mut self, ty: Resource) -> u32 {
-        let persistent = self.pop_persistent();
+        self.trace_lower("own");
         with_ctx(|ctx| {
-            let val = persistent.restore(ctx).unwrap();
+            let val = self.pop_value(ctx);
             if ty.new().is_some() {
                 exported_resource_to_handle(ctx, ty, &val)
             } else {
+                // Ownership moves to the callee, which drops the handle itself.
+                if let Some(obj) = val.as_object() {
+                    ctx.resource_classes()
+                        .untrack_owned(ctx, ty.index(), obj)
+                        .unwrap();
+                }
                 imported_resource_to_handle(&val)
             }
         })
     }
 
     fn pop_tuple(&mut self, ty: Tuple) {
-        let persistent = self.stack.pop().expect("stack underflow");
+        self.trace_lower("tuple");
         with_ctx(|ctx| {
-            let val = persistent.restore(ctx).unwrap();
+            let val = self.pop_value(ctx);
             let arr = val.as_array().expect("expected array");
             for i in (0..ty.types().len()).rev() {
                 let elem: Value = arr.get(i).unwrap();
-                self.stack.push(Persistent::save(ctx, elem));
+                self.stack.push(elem);
             }
         });
     }
 
     fn pop_record(&mut self, ty: Record) {
-        let persistent = self.stack.pop().expect("stack underflow");
+        self.trace_lower("record");
         with_ctx(|ctx| {
-            let val = persistent.restore(ctx).unwrap();
+            let val = self.pop_value(ctx);
             let obj = val.as_object().expect("expected object");
             for (name, _) in ty.fields().rev() {
                 let field: Value = obj.get(fn_lookup(ctx, name)).unwrap();
-                self.stack.push(Persistent::save(ctx, field));
+                self.stack.push(field);
             }
         });
     }
 
     fn pop_future(&mut self, _ty: Future) -> u32 {
+        self.trace_lower("future");
         pop_with(self, |v| {
             if let Ok(class) = Class::<FutureReadable>::from_value(&v) {
                 return class.borrow().end.handle.expect("future already dropped");
@@ -388,6 +584,7 @@ impl Call for QjsCallContext {
     }
 
     fn pop_stream(&mut self, _ty: Stream) -> u32 {
+        self.trace_lower("stream");
         pop_with(self, |v| {
             if let Ok(class) = Class::<StreamReadable>::from_value(&v) {
                 return class.borrow().end.handle.expect("stream already dropped");
@@ -399,57 +596,80 @@ impl Call for QjsCallContext {
         })
     }
 
+    fn pop_error_context(&mut self) -> u32 {
+        self.trace_lower("error-context");
+        with_ctx(|ctx| {
+            let val = self.pop_value(ctx);
+            let handle = error_context::lower(&val).expect("failed to lower error-context");
+            self.error_contexts.push(handle);
+            handle
+        })
+    }
+
     // Push operations
     fn push_bool(&mut self, val: bool) {
         push_with(self, |ctx| val.into_js(ctx).unwrap());
+        self.trace_lift("bool");
     }
 
     fn push_u8(&mut self, val: u8) {
         push_with(self, |ctx| val.into_js(ctx).unwrap());
+        self.trace_lift("u8");
     }
 
     fn push_s8(&mut self, val: i8) {
         push_with(self, |ctx| val.into_js(ctx).unwrap());
+        self.trace_lift("s8");
     }
 
     fn push_u16(&mut self, val: u16) {
         push_with(self, |ctx| val.into_js(ctx).unwrap());
+        self.trace_lift("u16");
     }
 
     fn push_s16(&mut self, val: i16) {
         push_with(self, |ctx| val.into_js(ctx).unwrap());
+        self.trace_lift("s16");
     }
 
     fn push_u32(&mut self, val: u32) {
         push_with(self, |ctx| val.into_js(ctx).unwrap());
+        self.trace_lift("u32");
     }
 
     fn push_s32(&mut self, val: i32) {
         push_with(self, |ctx| val.into_js(ctx).unwrap());
+        self.trace_lift("s32");
     }
 
     fn push_u64(&mut self, val: u64) {
         push_with(self, |ctx| val.into_js(ctx).unwrap());
+        self.trace_lift("u64");
     }
 
     fn push_s64(&mut self, val: i64) {
         push_with(self, |ctx| val.into_js(ctx).unwrap());
+        self.trace_lift("s64");
     }
 
     fn push_f32(&mut self, val: f32) {
         push_with(self, |ctx| val.into_js(ctx).unwrap());
+        self.trace_lift("f32");
     }
 
     fn push_f64(&mut self, val: f64) {
         push_with(self, |ctx| val.into_js(ctx).unwrap());
+        self.trace_lift("f64");
     }
 
     fn push_char(&mut self, val: char) {
         push_with(self, |ctx| val.into_js(ctx).unwrap());
+        self.trace_lift("char");
     }
 
     fn push_string(&mut self, val: String) {
         push_with(self, |ctx| val.into_js(ctx).unwrap());
+        self.trace_lift("string");
     }
 
     unsafe fn push_raw_list(&mut self, ty: List, ptr: *mut u8, len: usize) -> bool {
@@ -458,59 +678,89 @@ impl Call for QjsCallContext {
                 let vec = unsafe { Vec::from_raw_parts(ptr, len, len) };
                 with_ctx(|ctx| {
                     let ta = rquickjs::TypedArray::<u8>::new(ctx.clone(), vec).unwrap();
-                    self.stack.push(Persistent::save(ctx, ta.into_value()));
+                    self.stack.push(ta.into_value());
                 });
+                self.trace_lift("list");
                 true
             }
             Type::S8 => {
                 let vec = unsafe { Vec::from_raw_parts(ptr.cast::<i8>(), len, len) };
                 with_ctx(|ctx| {
                     let ta = rquickjs::TypedArray::<i8>::new(ctx.clone(), vec).unwrap();
-                    self.stack.push(Persistent::save(ctx, ta.into_value()));
+                    self.stack.push(ta.into_value());
                 });
+                self.trace_lift("list");
                 true
             }
             _ => false,
         }
     }
 
-    fn push_list(&mut self, _ty: List, _capacity: usize) {
+    fn push_list(&mut self, _ty: List, capacity: usize) {
         push_with(self, |ctx| {
             rquickjs::Array::new(ctx.clone()).unwrap().into_value()
         });
+        // `capacity` is the exact element count, so buffer the elements and
+        // fill the array in one context entry once the last one arrives.
+        if capacity > 0 {
+            self.list_batches.push(ListBatch {
+                depth: self.stack.len(),
+                remaining: capacity,
+                elems: ValueArena::with_capacity(capacity),
+            });
+        }
+        self.trace_lift("list");
     }
 
     fn list_append(&mut self, _ty: List) {
-        let elem = self.stack.pop().expect("stack underflow");
-        let arr_persistent = self.stack.last().expect("stack underflow").clone();
-
         with_ctx(|ctx| {
-            let arr_val = arr_persistent.restore(ctx).unwrap();
+            let elem = self.pop_value(ctx);
+            let arr_val = self.stack.last(ctx).expect("stack underflow");
             let arr = arr_val.as_array().expect("expected array");
-            let val = elem.restore(ctx).unwrap();
-            let len = arr.len();
-            arr.set(len, val).unwrap();
+
+            let batch = self
+                .list_batches
+                .last_mut()
+                .filter(|batch| batch.depth == self.stack.len());
+            let Some(batch) = batch else {
+                // More elements than announced: append directly.
+                arr.set(arr.len(), elem).unwrap();
+                return;
+            };
+
+            batch.elems.push(elem);
+            batch.remaining -= 1;
+            if batch.remaining > 0 {
+                return;
+            }
+            let mut batch = self.list_batches.pop().unwrap();
+            let offset = arr.len();
+            let count = batch.elems.len();
+            for (index, elem) in batch.elems.drain_last(ctx, count).enumerate() {
+                arr.set(offset + index, elem).unwrap();
+            }
         });
     }
 
     fn push_option(&mut self, ty: WitOption, is_some: bool) {
         if option_is_nested(ty) {
             // Nested option: wrap so `none` and `some(none)` stay distinct.
-            let inner = if is_some { self.stack.pop() } else { None };
             with_ctx(|ctx| {
+                let inner = if is_some { self.stack.pop(ctx) } else { None };
                 let obj = rquickjs::Object::new(ctx.clone()).unwrap();
                 if let Some(inner) = inner {
                     obj.set("tag", "some").unwrap();
-                    obj.set("val", inner.restore(ctx).unwrap()).unwrap();
+                    obj.set("val", inner).unwrap();
                 } else {
                     obj.set("tag", "none").unwrap();
                 }
-                self.stack.push(Persistent::save(ctx, obj.into_value()));
+                self.stack.push(obj.into_value());
             });
         } else if !is_some {
             // Bare option: `none` is null; `some(v)` leaves `v` on the stack.
             push_with(self, |ctx| Value::new_null(ctx.clone()));
         }
+        self.trace_lift("option");
     }
 
     fn push_result(&mut self, ty: WitResult, is_err: bool) {
@@ -520,16 +770,20 @@ impl Call for QjsCallContext {
             ty.ok().is_some()
         };
 
-        let inner = if has_payload { self.stack.pop() } else { None };
-
         with_ctx(|ctx| {
+            let inner = if has_payload {
+                self.stack.pop(ctx)
+            } else {
+                None
+            };
             let obj = rquickjs::Object::new(ctx.clone()).unwrap();
             obj.set("tag", if is_err { "err" } else { "ok" }).unwrap();
             if let Some(val) = inner {
-                obj.set("val", val.restore(ctx).unwrap()).unwrap();
+                obj.set("val", val).unwrap();
             }
-            self.stack.push(Persistent::save(ctx, obj.into_value()));
+            self.stack.push(obj.into_value());
         });
+        self.trace_lift("result");
     }
 
     fn push_variant(&mut self, ty: Variant, tag: u32) {
@@ -539,16 +793,20 @@ impl Call for QjsCallContext {
             .expect("variant tag out of range");
         let has_payload = payload_ty.is_some();
 
-        let inner = if has_payload { self.stack.pop() } else { None };
-
         with_ctx(|ctx| {
+            let inner = if has_payload {
+                self.stack.pop(ctx)
+            } else {
+                None
+            };
             let obj = rquickjs::Object::new(ctx.clone()).unwrap();
             obj.set("tag", name).unwrap();
             if let Some(val) = inner {
-                obj.set("val", val.restore(ctx).unwrap()).unwrap();
+                obj.set("val", val).unwrap();
             }
-            self.stack.push(Persistent::save(ctx, obj.into_value()));
+            self.stack.push(obj.into_value());
         });
+        self.trace_lift("variant");
     }
 
     fn push_enum(&mut self, ty: Enum, val: u32) {
@@ -557,6 +815,7 @@ impl Call for QjsCallContext {
             .nth(val as usize)
             .expect("enum index out of range");
         push_with(self, move |ctx| name.into_js(ctx).unwrap());
+        self.trace_lift("enum");
     }
 
     fn push_flags(&mut self, ty: Flags, val: u32) {
@@ -566,8 +825,9 @@ impl Call for QjsCallContext {
                 obj.set(fn_lookup(ctx, name), (val & (1 << i)) != 0)
                     .unwrap();
             }
-            self.stack.push(Persistent::save(ctx, obj.into_value()));
+            self.stack.push(obj.into_value());
         });
+        self.trace_lift("flags");
     }
 
     fn push_borrow(&mut self, ty: Resource, handle: u32) {
@@ -583,11 +843,13 @@ impl Call for QjsCallContext {
 
                 let obj = rquickjs::Object::new(ctx.clone()).unwrap();
                 obj.set("__cqjs_handle", handle).unwrap();
+                obj.set("__cqjs_borrow", true).unwrap();
                 set_imported_prototype(ctx, &obj, ty);
                 obj.into_value()
             };
-            self.push_value(ctx, val);
+            self.push_value(val);
         });
+        self.trace_lift("borrow");
     }
 
     fn push_own(&mut self, ty: Resource, handle: u32) {
@@ -603,41 +865,38 @@ impl Call for QjsCallContext {
                 let obj = rquickjs::Object::new(ctx.clone()).unwrap();
                 obj.set("__cqjs_handle", handle).unwrap();
                 set_imported_prototype(ctx, &obj, ty);
+                ctx.resource_classes()
+                    .track_owned(ctx, ty.index(), &obj, handle)
+                    .unwrap();
                 obj.into_value()
             };
-            self.push_value(ctx, val);
+            self.push_value(val);
         });
+        self.trace_lift("own");
     }
 
     fn push_tuple(&mut self, ty: Tuple) {
         let len = ty.types().len();
-        let elems: SmallVec<[_; 8]> = (0..len)
-            .map(|_| self.stack.pop().expect("stack underflow"))
-            .collect();
-
         with_ctx(|ctx| {
             let arr = rquickjs::Array::new(ctx.clone()).unwrap();
-            for (i, elem) in elems.into_iter().rev().enumerate() {
-                arr.set(i, elem.restore(ctx).unwrap()).unwrap();
+            for (i, elem) in self.stack.drain_last(ctx, len).enumerate() {
+                arr.set(i, elem).unwrap();
             }
-            self.stack.push(Persistent::save(ctx, arr.into_value()));
+            self.stack.push(arr.into_value());
         });
+        self.trace_lift("tuple");
     }
 
     fn push_record(&mut self, ty: Record) {
         let fields: SmallVec<[_; 16]> = ty.fields().collect();
-        let vals: SmallVec<[_; 16]> = (0..fields.len())
-            .map(|_| self.stack.pop().expect("stack underflow"))
-            .collect();
-
         with_ctx(|ctx| {
             let obj = rquickjs::Object::new(ctx.clone()).unwrap();
-            for ((name, _), val) in fields.iter().zip(vals.into_iter().rev()) {
-                obj.set(fn_lookup(ctx, name), val.restore(ctx).unwrap())
-                    .unwrap();
+            for ((name, _), val) in fields.iter().zip(self.stack.drain_last(ctx, fields.len())) {
+                obj.set(fn_lookup(ctx, name), val).unwrap();
             }
-            self.stack.push(Persistent::save(ctx, obj.into_value()));
+            self.stack.push(obj.into_value());
         });
+        self.trace_lift("record");
     }
 
     fn push_future(&mut self, ty: Future, handle: u32) {
@@ -649,8 +908,9 @@ impl Call for QjsCallContext {
                     .expect("matching future type must exist in WIT") as u32;
 
             let obj = crate::futures::make_future_readable(ctx, type_index, handle).unwrap();
-            self.stack.push(Persistent::save(ctx, obj.into_value()));
+            self.stack.push(obj.into_value());
         });
+        self.trace_lift("future");
     }
 
     fn push_stream(&mut self, ty: Stream, handle: u32) {
@@ -662,7 +922,16 @@ impl Call for QjsCallContext {
                     .expect("matching stream type must exist in WIT") as u32;
 
             let obj = crate::streams::make_stream_readable(ctx, type_index, handle).unwrap();
-            self.stack.push(Persistent::save(ctx, obj.into_value()));
+            self.stack.push(obj.into_value());
+        });
+        self.trace_lift("stream");
+    }
+
+    fn push_error_context(&mut self, handle: u32) {
+        with_ctx(|ctx| {
+            let error = error_context::lift(ctx, handle).expect("failed to lift error-context");
+            self.stack.push(error);
         });
+        self.trace_lift("error-context");
     }
 }
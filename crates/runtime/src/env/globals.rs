@@ -0,0 +1,70 @@
+//! Globals configured at componentize time: `__FEATURES__` and `Math.random`.
+
+use std::cell::Cell;
+
+use rquickjs::object::Property;
+use rquickjs::{Ctx, Exception, Function, Object, Value};
+
+use crate::abi;
+use crate::init::RandomSource;
+
+/// Build a frozen `Set` whose mutating methods throw.
+const FEATURES_JS: &str = r#"(names) => {
+  const features = new Set(names);
+  const readOnly = () => { throw new TypeError("__FEATURES__ is read-only"); };
+  for (const method of ["add", "delete", "clear"]) {
+    Object.defineProperty(features, method, { value: readOnly });
+  }
+  return Object.freeze(features);
+}"#;
+
+/// Define the read-only `globalThis.__FEATURES__` set of enabled feature flags.
+pub(crate) fn install_features(ctx: &Ctx<'_>, features: &[String]) -> rquickjs::Result<()> {
+    let make: Function = ctx.eval(FEATURES_JS)?;
+    let set: Value = make.call((features.to_vec(),))?;
+    ctx.globals().prop("__FEATURES__", Property::from(set))
+}
+
+/// Replace `Math.random` with the configured source.
+///
+/// A seeded generator keeps its state in linear memory, so the snapshot
+/// captures it and every instance starts the sequence over from the seed.
+pub(crate) fn install_math_random(
+    ctx: &Ctx<'_>,
+    source: Option<&RandomSource>,
+) -> rquickjs::Result<()> {
+    let random = match source {
+        None => return Ok(()),
+        Some(RandomSource::Wasi) => Function::new(ctx.clone(), |ctx: Ctx<'_>| {
+            let mut bytes = [0u8; 8];
+            if unsafe { abi::getentropy(bytes.as_mut_ptr(), bytes.len()) } != 0 {
+                return Err(Exception::throw_message(
+                    &ctx,
+                    "failed to read entropy from wasi:random",
+                ));
+            }
+            Ok(unit_interval(u64::from_le_bytes(bytes)))
+        })?,
+        Some(RandomSource::Seed(seed)) => {
+            let state = Cell::new(*seed);
+            Function::new(ctx.clone(), move || unit_interval(splitmix64(&state)))?
+        }
+    };
+    let math: Object = ctx.globals().get("Math")?;
+    math.set("random", random.with_name("random")?)
+}
+
+/// Map 64 random bits to a double in `[0, 1)`.
+fn unit_interval(bits: u64) -> f64 {
+    (bits >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// Advance a SplitMix64 generator.
+fn splitmix64(state: &Cell<u64>) -> u64 {
+    let next = state.get().wrapping_add(0x9e37_79b9_7f4a_7c15);
+    state.set(next);
+    let mut z = next;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
@@ -0,0 +1,170 @@
+//! Opt-in lockdown of the JavaScript environment.
+//!
+//! Once the user module is evaluated, every global that existed before it ran
+//! (the ECMAScript intrinsics, polyfills and generated bindings) is frozen
+//! transitively, together with the WIT import objects, and those global
+//! bindings become read-only. Globals defined by the user module are left
+//! alone.
+
+use std::cell::{Cell, RefCell};
+
+use rquickjs::{Ctx, Function, JsLifetime, Object, Persistent};
+
+/// Freeze everything reachable from the named globals and `imports`.
+///
+/// Prototype properties that code commonly assigns on derived objects, like
+/// `toString` or an error's `name`, are turned into accessors first. Otherwise
+/// `this.name = "MyError"` would throw once `Error.prototype` is frozen, since
+/// assignment refuses to shadow a read-only inherited property.
+const LOCKDOWN_JS: &str = r#"(names, imports) => {
+  const OVERRIDABLE = [
+    "constructor", "name", "message", "toString", "toLocaleString", "valueOf",
+    "toJSON", "hasOwnProperty", "apply", "bind", "call",
+  ];
+  const { defineProperty, getOwnPropertyDescriptor, getPrototypeOf, hasOwn } = Object;
+  const isObject = (value) =>
+    (typeof value === "object" && value !== null) || typeof value === "function";
+
+  // `env` is a proxy that already rejects writes, and freezing its target
+  // would break the variables it reports.
+  const skip = new Set([globalThis]);
+  if (names.includes("env")) skip.add(globalThis.env);
+
+  const roots = [
+    ...names.map((name) => globalThis[name]),
+    ...imports,
+    getPrototypeOf(function* () {}),
+    getPrototypeOf(async function () {}),
+    getPrototypeOf(async function* () {}),
+    getPrototypeOf([][Symbol.iterator]()),
+    getPrototypeOf(new Map()[Symbol.iterator]()),
+    getPrototypeOf(new Set()[Symbol.iterator]()),
+    getPrototypeOf(""[Symbol.iterator]()),
+    getPrototypeOf(/./[Symbol.matchAll]("")),
+  ];
+  const reached = new Set();
+  while (roots.length) {
+    const value = roots.pop();
+    if (!isObject(value) || skip.has(value) || reached.has(value)) continue;
+    reached.add(value);
+    roots.push(getPrototypeOf(value));
+    for (const key of Reflect.ownKeys(value)) {
+      const desc = getOwnPropertyDescriptor(value, key);
+      if (hasOwn(desc, "value")) roots.push(desc.value);
+      else roots.push(desc.get, desc.set);
+    }
+  }
+
+  const tame = (proto, key) => {
+    const desc = getOwnPropertyDescriptor(proto, key);
+    if (!desc || !hasOwn(desc, "value") || !desc.configurable) return;
+    const value = desc.value;
+    const get = function () { return value; };
+    const set = function (v) {
+      if (this === proto) throw new TypeError(`Cannot assign to read only property '${key}'`);
+      if (hasOwn(this, key)) this[key] = v;
+      else defineProperty(this, key, { value: v, writable: true, enumerable: true, configurable: true });
+    };
+    defineProperty(proto, key, { get, set, enumerable: desc.enumerable, configurable: false });
+    reached.add(get).add(set);
+  };
+  for (const value of [...reached]) {
+    if (typeof value !== "function") continue;
+    const desc = getOwnPropertyDescriptor(value, "prototype");
+    if (desc && isObject(desc.value) && reached.has(desc.value)) {
+      for (const key of OVERRIDABLE) tame(desc.value, key);
+    }
+  }
+
+  for (const value of reached) Object.freeze(value);
+
+  for (const name of names) {
+    const desc = getOwnPropertyDescriptor(globalThis, name);
+    if (!desc) continue;
+    defineProperty(
+      globalThis,
+      name,
+      hasOwn(desc, "value") ? { writable: false, configurable: false } : { configurable: false },
+    );
+  }
+}"#;
+
+/// Hardening state stored as rquickjs userdata, present only when enabled.
+#[derive(Default)]
+pub(crate) struct Hardening {
+    /// Globals defined before the user module was evaluated.
+    globals: RefCell<Vec<String>>,
+    /// Import objects evaluated before the lockdown.
+    imports: RefCell<Vec<Persistent<Object<'static>>>>,
+    locked: Cell<bool>,
+}
+
+// SAFETY: `Hardening` stores only `Persistent<Object<'static>>` values, which
+// are tied to the owning QuickJS runtime and restored only for that same
+// runtime.
+unsafe impl<'js> JsLifetime<'js> for Hardening {
+    type Changed<'to> = Hardening;
+}
+
+/// Turn hardening on for this context.
+pub(crate) fn enable(ctx: &Ctx<'_>) -> Result<(), String> {
+    ctx.store_userdata(Hardening::default())
+        .map(|_| ())
+        .map_err(|_| "Failed to store hardening state".to_string())
+}
+
+/// Remember the globals defined so far, which the lockdown will freeze.
+pub(crate) fn record_globals(ctx: &Ctx<'_>) -> rquickjs::Result<()> {
+    let Some(hardening) = ctx.userdata::<Hardening>() else {
+        return Ok(());
+    };
+    let names: Vec<String> = ctx.eval("Object.getOwnPropertyNames(globalThis)")?;
+    hardening.globals.replace(names);
+    Ok(())
+}
+
+/// Register an evaluated WIT import object.
+///
+/// Objects evaluated before the lockdown are frozen with it; later ones, from
+/// dynamic imports, are frozen right away.
+pub(crate) fn register_import<'js>(ctx: &Ctx<'js>, obj: &Object<'js>) -> rquickjs::Result<()> {
+    let Some(hardening) = ctx.userdata::<Hardening>() else {
+        return Ok(());
+    };
+    if hardening.locked.get() {
+        return freeze(ctx, Vec::new(), vec![obj.clone()]);
+    }
+    hardening
+        .imports
+        .borrow_mut()
+        .push(Persistent::save(ctx, obj.clone()));
+    Ok(())
+}
+
+/// Freeze the recorded globals and import objects, if hardening is enabled.
+pub(crate) fn lockdown(ctx: &Ctx<'_>) -> Result<(), String> {
+    let Some(hardening) = ctx.userdata::<Hardening>() else {
+        return Ok(());
+    };
+    let globals = hardening.globals.take();
+    let imports = hardening
+        .imports
+        .take()
+        .into_iter()
+        .map(|obj| obj.restore(ctx))
+        .collect::<rquickjs::Result<Vec<_>>>()
+        .map_err(|e| format!("Failed to restore import objects: {e}"))?;
+    hardening.locked.set(true);
+    drop(hardening);
+
+    freeze(ctx, globals, imports).map_err(|e| format!("Failed to harden the environment: {e}"))
+}
+
+fn freeze<'js>(
+    ctx: &Ctx<'js>,
+    names: Vec<String>,
+    imports: Vec<Object<'js>>,
+) -> rquickjs::Result<()> {
+    let lockdown: Function = ctx.eval(LOCKDOWN_JS)?;
+    lockdown.call((names, imports))
+}
@@ -0,0 +1,78 @@
+//! The JavaScript environment layer.
+//!
+//! Everything here only shapes what user code sees in the global scope —
+//! polyfills, `__FEATURES__`, the `Math.random` override and hardening — and
+//! none of it affects how values cross the component boundary, which is the
+//! WIT conversion layer's job (`call`, `bindings`, `resources` and friends).
+//! The layer is compiled in with the `js-env` feature. A runtime built without
+//! it is smaller and leaves the QuickJS globals untouched, and rejects init
+//! options that need the layer.
+
+#[cfg(feature = "js-env")]
+mod globals;
+#[cfg(feature = "js-env")]
+mod harden;
+#[cfg(feature = "js-env")]
+mod polyfills;
+
+use rquickjs::{Ctx, Object};
+
+use crate::init::InitOptions;
+
+/// Install the globals selected by `options`, before any module is evaluated.
+#[cfg(feature = "js-env")]
+pub(crate) fn install(ctx: &Ctx<'_>, options: &InitOptions) -> Result<(), String> {
+    if options.harden {
+        harden::enable(ctx)?;
+    }
+    globals::install_features(ctx, &options.features)
+        .map_err(|e| format!("Failed to install feature flags: {e}"))?;
+    globals::install_math_random(ctx, options.math_random.as_ref())
+        .map_err(|e| format!("Failed to install Math.random: {e}"))?;
+    polyfills::install(ctx, &options.polyfills)
+}
+
+/// Reject options that need the environment layer.
+#[cfg(not(feature = "js-env"))]
+pub(crate) fn install(_ctx: &Ctx<'_>, options: &InitOptions) -> Result<(), String> {
+    let requested = [
+        (!options.features.is_empty(), "feature flags"),
+        (!options.polyfills.is_empty(), "polyfills"),
+        (options.math_random.is_some(), "a Math.random source"),
+        (options.harden, "hardening"),
+    ];
+    match requested.iter().find(|(on, _)| *on) {
+        Some((_, what)) => Err(format!(
+            "{what} requested, but the runtime was built without the `js-env` feature"
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Remember the globals defined before the user module, for hardening.
+pub(crate) fn record_globals(ctx: &Ctx<'_>) -> Result<(), String> {
+    #[cfg(feature = "js-env")]
+    harden::record_globals(ctx)
+        .map_err(|e| format!("Failed to record globals for hardening: {e}"))?;
+    #[cfg(not(feature = "js-env"))]
+    let _ = ctx;
+    Ok(())
+}
+
+/// Register an evaluated WIT import object, for hardening.
+pub(crate) fn register_import<'js>(ctx: &Ctx<'js>, obj: &Object<'js>) -> rquickjs::Result<()> {
+    #[cfg(feature = "js-env")]
+    harden::register_import(ctx, obj)?;
+    #[cfg(not(feature = "js-env"))]
+    let _ = (ctx, obj);
+    Ok(())
+}
+
+/// Harden the environment once the user module is evaluated, if requested.
+pub(crate) fn lockdown(ctx: &Ctx<'_>) -> Result<(), String> {
+    #[cfg(feature = "js-env")]
+    harden::lockdown(ctx)?;
+    #[cfg(not(feature = "js-env"))]
+    let _ = ctx;
+    Ok(())
+}
@@ -0,0 +1,136 @@
+//! The `AbortController` and `AbortSignal` globals.
+//!
+//! Signals follow the DOM Standard: `aborted`, `reason`, `throwIfAborted()`,
+//! `onabort` and `addEventListener("abort", ...)` with the `once` and `signal`
+//! options, plus the static `abort()`, `any()` and `timeout()` helpers. With
+//! the `events` polyfill installed first, `AbortSignal` extends `EventTarget`
+//! and dispatches an `Event`; otherwise listeners receive a plain
+//! `{ type, target }` object. `timeout()` needs a `setTimeout` global when it
+//! is called.
+
+pub(super) const SOURCE: &str = r#"(() => {
+const domError = (message, name) =>
+  typeof DOMException === "function"
+    ? new DOMException(message, name)
+    : Object.assign(new Error(message), { name });
+
+const INTERNAL = Symbol("AbortSignal.internal");
+
+// Without the `events` polyfill, signals carry just enough of `EventTarget`
+// for "abort" listeners.
+class AbortListeners {
+  #listeners = [];
+
+  addEventListener(type, listener, options) {
+    if (type !== "abort" || listener == null) return;
+    const once = typeof options === "object" && options !== null && !!options.once;
+    const signal = typeof options === "object" && options !== null ? options.signal : undefined;
+    if (signal?.aborted) return;
+    if (this.#listeners.some((entry) => entry.listener === listener)) return;
+    this.#listeners.push({ listener, once });
+    signal?.addEventListener("abort", () => this.removeEventListener(type, listener), { once: true });
+  }
+
+  removeEventListener(type, listener) {
+    if (type !== "abort") return;
+    this.#listeners = this.#listeners.filter((entry) => entry.listener !== listener);
+  }
+
+  dispatchEvent(event) {
+    if (event?.type !== "abort") return true;
+    for (const entry of this.#listeners.slice()) {
+      if (entry.once) this.removeEventListener("abort", entry.listener);
+      report(() => {
+        if (typeof entry.listener === "function") entry.listener.call(this, event);
+        else entry.listener.handleEvent(event);
+      });
+    }
+    return true;
+  }
+}
+
+// Report an exception without skipping the remaining listeners.
+const report = (f) => {
+  try {
+    f();
+  } catch (e) {
+    Promise.reject(e);
+  }
+};
+
+class AbortSignal extends (typeof EventTarget === "function" ? EventTarget : AbortListeners) {
+  #aborted = false;
+  #reason = undefined;
+  #onabort = null;
+
+  constructor(key) {
+    if (key !== INTERNAL) throw new TypeError("Illegal constructor");
+    super();
+  }
+
+  get aborted() { return this.#aborted; }
+  get reason() { return this.#reason; }
+
+  get onabort() { return this.#onabort; }
+  set onabort(handler) { this.#onabort = typeof handler === "function" ? handler : null; }
+
+  throwIfAborted() {
+    if (this.#aborted) throw this.#reason;
+  }
+
+  static abort(reason) {
+    const signal = new AbortSignal(INTERNAL);
+    signal[INTERNAL](reason);
+    return signal;
+  }
+
+  static timeout(ms) {
+    if (typeof setTimeout !== "function") {
+      throw new TypeError("AbortSignal.timeout requires a setTimeout global");
+    }
+    const signal = new AbortSignal(INTERNAL);
+    setTimeout(() => signal[INTERNAL](domError("The operation timed out.", "TimeoutError")), ms);
+    return signal;
+  }
+
+  static any(signals) {
+    const signal = new AbortSignal(INTERNAL);
+    const sources = Array.from(signals);
+    const done = sources.find((source) => source.aborted);
+    if (done) {
+      signal[INTERNAL](done.reason);
+      return signal;
+    }
+    for (const source of sources) {
+      source.addEventListener("abort", () => signal[INTERNAL](source.reason), { once: true, signal });
+    }
+    return signal;
+  }
+
+  [INTERNAL](reason) {
+    if (this.#aborted) return;
+    this.#aborted = true;
+    this.#reason = reason === undefined ? domError("This operation was aborted", "AbortError") : reason;
+    const event = typeof Event === "function" ? new Event("abort") : { type: "abort", target: this };
+    if (this.#onabort) report(() => this.#onabort.call(this, event));
+    this.dispatchEvent(event);
+  }
+
+  get [Symbol.toStringTag]() { return "AbortSignal"; }
+}
+
+class AbortController {
+  #signal = new AbortSignal(INTERNAL);
+
+  get signal() { return this.#signal; }
+
+  abort(reason) {
+    this.#signal[INTERNAL](reason);
+  }
+
+  get [Symbol.toStringTag]() { return "AbortController"; }
+}
+
+globalThis.AbortSignal = AbortSignal;
+globalThis.AbortController = AbortController;
+})();"#;
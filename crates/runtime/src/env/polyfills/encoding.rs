@@ -0,0 +1,163 @@
+//! WHATWG `TextEncoder` and `TextDecoder` globals for UTF-8.
+//!
+//! The decoder follows the Encoding Standard's UTF-8 decoder, including
+//! streaming, `fatal` and BOM handling. Other encodings are rejected with a
+//! `RangeError`.
+
+pub(super) const SOURCE: &str = r#"(() => {
+const UTF8_LABELS = ["unicode-1-1-utf-8", "unicode11utf8", "unicode20utf8", "utf-8", "utf8", "x-unicode20utf8"];
+
+const bytesOf = (input) => {
+  if (input === undefined) return new Uint8Array(0);
+  if (input instanceof ArrayBuffer) return new Uint8Array(input);
+  if (ArrayBuffer.isView(input)) return new Uint8Array(input.buffer, input.byteOffset, input.byteLength);
+  throw new TypeError("The input must be an ArrayBuffer or ArrayBufferView");
+};
+
+const fromCodeUnits = (units) => {
+  let out = "";
+  for (let i = 0; i < units.length; i += 8192) {
+    out += String.fromCharCode.apply(null, units.slice(i, i + 8192));
+  }
+  return out;
+};
+
+class TextEncoder {
+  get encoding() { return "utf-8"; }
+
+  encode(input = "") {
+    const bytes = [];
+    this.encodeInto(String(input), { length: Infinity, set: (b) => bytes.push(b) });
+    return Uint8Array.from(bytes);
+  }
+
+  encodeInto(source, destination) {
+    source = String(source);
+    const put = destination instanceof Uint8Array
+      ? (b, at) => { destination[at] = b; }
+      : (b) => destination.set(b);
+    let read = 0, written = 0;
+    for (const ch of source) {
+      let c = ch.codePointAt(0);
+      if (c >= 0xd800 && c <= 0xdfff) c = 0xfffd;
+      const size = c < 0x80 ? 1 : c < 0x800 ? 2 : c < 0x10000 ? 3 : 4;
+      if (written + size > destination.length) break;
+      if (size === 1) {
+        put(c, written);
+      } else {
+        put((0xf0 << (4 - size)) & 0xff | (c >> (6 * (size - 1))), written);
+        for (let i = 1; i < size; i++) put(0x80 | ((c >> (6 * (size - 1 - i))) & 0x3f), written + i);
+      }
+      read += ch.length;
+      written += size;
+    }
+    return { read, written };
+  }
+
+  get [Symbol.toStringTag]() { return "TextEncoder"; }
+}
+
+class TextDecoder {
+  #fatal;
+  #ignoreBOM;
+  #needed = 0;
+  #seen = 0;
+  #codePoint = 0;
+  #lower = 0x80;
+  #upper = 0xbf;
+  #bomSeen = false;
+
+  constructor(label = "utf-8", options = {}) {
+    if (!UTF8_LABELS.includes(String(label).trim().toLowerCase())) {
+      throw new RangeError(`The "${label}" encoding is not supported`);
+    }
+    this.#fatal = Boolean(options.fatal);
+    this.#ignoreBOM = Boolean(options.ignoreBOM);
+  }
+
+  get encoding() { return "utf-8"; }
+  get fatal() { return this.#fatal; }
+  get ignoreBOM() { return this.#ignoreBOM; }
+
+  #reset() {
+    this.#needed = this.#seen = this.#codePoint = 0;
+    this.#lower = 0x80;
+    this.#upper = 0xbf;
+  }
+
+  #error(units) {
+    if (this.#fatal) {
+      this.#reset();
+      this.#bomSeen = false;
+      throw new TypeError("The encoded data was not valid UTF-8");
+    }
+    this.#bomSeen = true;
+    units.push(0xfffd);
+  }
+
+  decode(input, options = {}) {
+    const bytes = bytesOf(input);
+    const units = [];
+    const emit = (c) => {
+      if (!this.#bomSeen) {
+        this.#bomSeen = true;
+        if (c === 0xfeff && !this.#ignoreBOM) return;
+      }
+      if (c > 0xffff) units.push(0xd800 + ((c - 0x10000) >> 10), 0xdc00 + ((c - 0x10000) & 0x3ff));
+      else units.push(c);
+    };
+
+    for (let i = 0; i < bytes.length; i++) {
+      const b = bytes[i];
+      if (this.#needed === 0) {
+        if (b <= 0x7f) {
+          emit(b);
+        } else if (b >= 0xc2 && b <= 0xdf) {
+          this.#needed = 1;
+          this.#codePoint = b & 0x1f;
+        } else if (b >= 0xe0 && b <= 0xef) {
+          if (b === 0xe0) this.#lower = 0xa0;
+          if (b === 0xed) this.#upper = 0x9f;
+          this.#needed = 2;
+          this.#codePoint = b & 0xf;
+        } else if (b >= 0xf0 && b <= 0xf4) {
+          if (b === 0xf0) this.#lower = 0x90;
+          if (b === 0xf4) this.#upper = 0x8f;
+          this.#needed = 3;
+          this.#codePoint = b & 0x7;
+        } else {
+          this.#error(units);
+        }
+        continue;
+      }
+      if (b < this.#lower || b > this.#upper) {
+        this.#reset();
+        this.#error(units);
+        i--;
+        continue;
+      }
+      this.#lower = 0x80;
+      this.#upper = 0xbf;
+      this.#codePoint = (this.#codePoint << 6) | (b & 0x3f);
+      if (++this.#seen === this.#needed) {
+        emit(this.#codePoint);
+        this.#reset();
+      }
+    }
+
+    if (!options.stream) {
+      if (this.#needed !== 0) {
+        this.#reset();
+        this.#error(units);
+      }
+      this.#bomSeen = false;
+    }
+    return fromCodeUnits(units);
+  }
+
+  get [Symbol.toStringTag]() { return "TextDecoder"; }
+}
+
+globalThis.TextEncoder = TextEncoder;
+globalThis.TextDecoder = TextDecoder;
+})();"#;
@@ -0,0 +1,165 @@
+//! The `Event`, `CustomEvent` and `EventTarget` globals.
+//!
+//! A target has no parent, so dispatch only runs the listeners at the target
+//! itself: `bubbles` and capture are recorded but never propagate anywhere.
+//! Listeners may be functions or objects with `handleEvent`, and take the
+//! `capture`, `once`, `passive` and `signal` options. An exception thrown by a
+//! listener doesn't stop the others; it surfaces as an unhandled rejection.
+
+pub(super) const SOURCE: &str = r#"(() => {
+const domError = (message, name) =>
+  typeof DOMException === "function"
+    ? new DOMException(message, name)
+    : Object.assign(new Error(message), { name });
+
+const NONE = 0;
+const AT_TARGET = 2;
+
+// Dispatch-time state that only the dispatching target may change.
+const states = new WeakMap();
+const state = (event) => {
+  const s = states.get(event);
+  if (!s) throw new TypeError("Illegal invocation");
+  return s;
+};
+
+class Event {
+  constructor(type, init = {}) {
+    if (arguments.length === 0) throw new TypeError("Event requires a type");
+    states.set(this, {
+      type: String(type),
+      bubbles: !!init?.bubbles,
+      cancelable: !!init?.cancelable,
+      composed: !!init?.composed,
+      timeStamp: Date.now(),
+      target: null,
+      currentTarget: null,
+      phase: NONE,
+      canceled: false,
+      passive: false,
+      stop: false,
+      stopImmediate: false,
+      dispatching: false,
+    });
+  }
+
+  get type() { return state(this).type; }
+  get bubbles() { return state(this).bubbles; }
+  get cancelable() { return state(this).cancelable; }
+  get composed() { return state(this).composed; }
+  get timeStamp() { return state(this).timeStamp; }
+  get target() { return state(this).target; }
+  get srcElement() { return state(this).target; }
+  get currentTarget() { return state(this).currentTarget; }
+  get eventPhase() { return state(this).phase; }
+  get defaultPrevented() { return state(this).canceled; }
+  get isTrusted() { return false; }
+  get returnValue() { return !state(this).canceled; }
+  set returnValue(value) { if (!value) this.preventDefault(); }
+  get cancelBubble() { return state(this).stop; }
+  set cancelBubble(value) { if (value) state(this).stop = true; }
+
+  composedPath() {
+    const { currentTarget } = state(this);
+    return currentTarget ? [currentTarget] : [];
+  }
+
+  preventDefault() {
+    const s = state(this);
+    if (s.cancelable && !s.passive) s.canceled = true;
+  }
+
+  stopPropagation() { state(this).stop = true; }
+
+  stopImmediatePropagation() {
+    const s = state(this);
+    s.stop = true;
+    s.stopImmediate = true;
+  }
+
+  get [Symbol.toStringTag]() { return "Event"; }
+}
+
+for (const [name, value] of Object.entries({ NONE, CAPTURING_PHASE: 1, AT_TARGET, BUBBLING_PHASE: 3 })) {
+  Object.defineProperty(Event, name, { value, enumerable: true });
+  Object.defineProperty(Event.prototype, name, { value, enumerable: true });
+}
+
+class CustomEvent extends Event {
+  #detail;
+
+  constructor(type, init = {}) {
+    super(type, init);
+    this.#detail = init?.detail ?? null;
+  }
+
+  get detail() { return this.#detail; }
+
+  get [Symbol.toStringTag]() { return "CustomEvent"; }
+}
+
+const flatten = (options) =>
+  typeof options === "boolean" ? { capture: options } : Object(options ?? {});
+
+class EventTarget {
+  #listeners = new Map();
+
+  addEventListener(type, callback, options) {
+    if (callback == null) return;
+    const { capture = false, once = false, passive = false, signal } = flatten(options);
+    if (signal?.aborted) return;
+    type = String(type);
+    let list = this.#listeners.get(type);
+    if (!list) this.#listeners.set(type, (list = []));
+    if (list.some((l) => l.callback === callback && l.capture === !!capture)) return;
+    list.push({ callback, capture: !!capture, once: !!once, passive: !!passive, removed: false });
+    signal?.addEventListener("abort", () => this.removeEventListener(type, callback, { capture }), {
+      once: true,
+    });
+  }
+
+  removeEventListener(type, callback, options) {
+    const capture = !!flatten(options).capture;
+    const list = this.#listeners.get(String(type));
+    const index = list?.findIndex((l) => l.callback === callback && l.capture === capture) ?? -1;
+    if (index < 0) return;
+    list[index].removed = true;
+    list.splice(index, 1);
+  }
+
+  dispatchEvent(event) {
+    if (!(event instanceof Event)) throw new TypeError("dispatchEvent requires an Event");
+    const s = state(event);
+    if (s.dispatching) throw domError("The event is already being dispatched", "InvalidStateError");
+    s.dispatching = true;
+    s.target = this;
+    s.currentTarget = this;
+    s.phase = AT_TARGET;
+    for (const listener of (this.#listeners.get(s.type) ?? []).slice()) {
+      if (listener.removed) continue;
+      if (listener.once) this.removeEventListener(s.type, listener.callback, listener);
+      s.passive = listener.passive;
+      try {
+        if (typeof listener.callback === "function") listener.callback.call(this, event);
+        else listener.callback.handleEvent(event);
+      } catch (e) {
+        Promise.reject(e);
+      }
+      s.passive = false;
+      if (s.stopImmediate) break;
+    }
+    s.dispatching = false;
+    s.currentTarget = null;
+    s.phase = NONE;
+    s.stop = false;
+    s.stopImmediate = false;
+    return !s.canceled;
+  }
+
+  get [Symbol.toStringTag]() { return "EventTarget"; }
+}
+
+globalThis.Event = Event;
+globalThis.CustomEvent = CustomEvent;
+globalThis.EventTarget = EventTarget;
+})();"#;
@@ -0,0 +1,37 @@
+//! Web platform globals that QuickJS lacks.
+//!
+//! Each polyfill is a script evaluated during Wizer initialization, so only the
+//! ones selected at componentize time end up in the snapshot. Names must match
+//! `POLYFILLS` in the core crate, which validates the selection.
+
+mod abort;
+mod encoding;
+mod events;
+mod streams;
+mod structured_clone;
+mod url;
+
+use rquickjs::{CaughtError, Ctx};
+
+/// Polyfill scripts by name.
+const REGISTRY: &[(&str, &str)] = &[
+    ("url", url::SOURCE),
+    ("encoding", encoding::SOURCE),
+    ("structured-clone", structured_clone::SOURCE),
+    ("events", events::SOURCE),
+    ("abort", abort::SOURCE),
+    ("streams", streams::SOURCE),
+];
+
+/// Evaluate the named polyfills in order, defining their globals.
+pub(crate) fn install(ctx: &Ctx<'_>, names: &[String]) -> Result<(), String> {
+    for name in names {
+        let (_, source) = REGISTRY
+            .iter()
+            .find(|(known, _)| known == name)
+            .ok_or_else(|| format!("unknown polyfill `{name}`"))?;
+        CaughtError::catch(ctx, ctx.eval::<(), _>(*source))
+            .map_err(|e| format!("Failed to install the `{name}` polyfill: {e}"))?;
+    }
+    Ok(())
+}
@@ -0,0 +1,598 @@
+//! WHATWG `ReadableStream`, `WritableStream` and `TransformStream` globals.
+//!
+//! A compact implementation of the Streams Standard's default (non-byte)
+//! streams: underlying sources and sinks, queuing strategies, readers and
+//! writers, async iteration, `tee`, `pipeTo` and `pipeThrough`. BYOB readers
+//! and abort signals are not supported, and a `TransformStream` does not apply
+//! backpressure from its readable side to its writable side.
+
+pub(super) const SOURCE: &str = r#"(() => {
+const deferred = () => {
+  let resolve, reject;
+  const promise = new Promise((res, rej) => { resolve = res; reject = rej; });
+  promise.catch(() => {});
+  return { promise, resolve, reject };
+};
+
+const sizeOf = (strategy, chunk) => {
+  const size = strategy?.size ? Number(strategy.size(chunk)) : 1;
+  if (!(size >= 0) || size === Infinity) throw new RangeError("The chunk size must be a finite, non-negative number");
+  return size;
+};
+
+const highWaterMarkOf = (strategy, fallback) => {
+  const hwm = strategy?.highWaterMark === undefined ? fallback : Number(strategy.highWaterMark);
+  if (Number.isNaN(hwm) || hwm < 0) throw new RangeError("Invalid highWaterMark");
+  return hwm;
+};
+
+class CountQueuingStrategy {
+  constructor({ highWaterMark }) { this.highWaterMark = highWaterMark; }
+  get size() { return () => 1; }
+}
+
+class ByteLengthQueuingStrategy {
+  constructor({ highWaterMark }) { this.highWaterMark = highWaterMark; }
+  get size() { return (chunk) => chunk.byteLength; }
+}
+
+// Internal state of each ReadableStream.
+const readables = new WeakMap();
+
+const readableState = (stream) => {
+  const state = readables.get(stream);
+  if (!state) throw new TypeError("Illegal invocation");
+  return state;
+};
+
+class ReadableStreamDefaultController {
+  #state;
+  constructor(state) { this.#state = state; }
+  get desiredSize() {
+    const state = this.#state;
+    if (state.status === "errored") return null;
+    if (state.status === "closed") return 0;
+    return state.highWaterMark - state.queueSize;
+  }
+  enqueue(chunk) {
+    const state = this.#state;
+    if (state.closeRequested || state.status !== "readable") {
+      throw new TypeError("Cannot enqueue into a closed stream");
+    }
+    if (state.readRequests.length) {
+      state.readRequests.shift().resolve({ value: chunk, done: false });
+    } else {
+      let size;
+      try {
+        size = sizeOf(state.strategy, chunk);
+      } catch (e) {
+        state.error(e);
+        throw e;
+      }
+      state.queue.push([chunk, size]);
+      state.queueSize += size;
+    }
+    state.pullIfNeeded();
+  }
+  close() {
+    const state = this.#state;
+    if (state.closeRequested || state.status !== "readable") {
+      throw new TypeError("The stream is already closing");
+    }
+    state.closeRequested = true;
+    if (!state.queue.length) state.finishClose();
+  }
+  error(reason) { this.#state.error(reason); }
+}
+
+class ReadableStream {
+  constructor(source = {}, strategy = {}) {
+    if (source.type === "bytes") throw new RangeError("Byte streams are not supported");
+    const state = {
+      status: "readable",
+      queue: [],
+      queueSize: 0,
+      readRequests: [],
+      closeRequested: false,
+      started: false,
+      pulling: false,
+      pullAgain: false,
+      storedError: undefined,
+      reader: null,
+      strategy,
+      highWaterMark: highWaterMarkOf(strategy, 1),
+      pullIfNeeded() {
+        if (!state.started || state.status !== "readable" || state.closeRequested) return;
+        if (!state.readRequests.length && state.highWaterMark - state.queueSize <= 0) return;
+        if (state.pulling) {
+          state.pullAgain = true;
+          return;
+        }
+        state.pulling = true;
+        Promise.resolve()
+          .then(() => source.pull?.(controller))
+          .then(() => {
+            state.pulling = false;
+            if (state.pullAgain) {
+              state.pullAgain = false;
+              state.pullIfNeeded();
+            }
+          }, state.error);
+      },
+      finishClose() {
+        if (state.status !== "readable") return;
+        state.status = "closed";
+        state.queue = [];
+        state.queueSize = 0;
+        for (const request of state.readRequests.splice(0)) request.resolve({ value: undefined, done: true });
+        state.reader?.closed.resolve();
+      },
+      error(reason) {
+        if (state.status !== "readable") return;
+        state.status = "errored";
+        state.storedError = reason;
+        state.queue = [];
+        state.queueSize = 0;
+        for (const request of state.readRequests.splice(0)) request.reject(reason);
+        state.reader?.closed.reject(reason);
+      },
+      read() {
+        if (state.queue.length) {
+          const [value, size] = state.queue.shift();
+          state.queueSize -= size;
+          if (state.closeRequested && !state.queue.length) state.finishClose();
+          else state.pullIfNeeded();
+          return Promise.resolve({ value, done: false });
+        }
+        if (state.status === "closed") return Promise.resolve({ value: undefined, done: true });
+        if (state.status === "errored") return Promise.reject(state.storedError);
+        const request = deferred();
+        state.readRequests.push(request);
+        state.pullIfNeeded();
+        return request.promise;
+      },
+      cancel(reason) {
+        if (state.status === "closed") return Promise.resolve();
+        if (state.status === "errored") return Promise.reject(state.storedError);
+        state.finishClose();
+        return Promise.resolve().then(() => source.cancel?.(reason)).then(() => {});
+      },
+    };
+    const controller = new ReadableStreamDefaultController(state);
+    readables.set(this, state);
+    Promise.resolve(source.start?.(controller)).then(() => {
+      state.started = true;
+      state.pullIfNeeded();
+    }, state.error);
+  }
+
+  static from(iterable) {
+    const iterator = iterable[Symbol.asyncIterator]?.() ?? iterable[Symbol.iterator]();
+    return new ReadableStream({
+      async pull(controller) {
+        const { value, done } = await iterator.next();
+        if (done) controller.close();
+        else controller.enqueue(await value);
+      },
+      async cancel(reason) {
+        await iterator.return?.(reason);
+      },
+    }, { highWaterMark: 0 });
+  }
+
+  get locked() { return readableState(this).reader !== null; }
+
+  cancel(reason) {
+    if (this.locked) return Promise.reject(new TypeError("Cannot cancel a locked stream"));
+    return readableState(this).cancel(reason);
+  }
+
+  getReader(options = {}) {
+    if (options.mode !== undefined) throw new RangeError("BYOB readers are not supported");
+    return new ReadableStreamDefaultReader(this);
+  }
+
+  tee() {
+    const reader = this.getReader();
+    const controllers = [];
+    const canceled = [false, false];
+    const reasons = [];
+    const done = deferred();
+    let reading = false;
+    const pull = () => {
+      if (reading) return;
+      reading = true;
+      reader.read().then(({ value, done: finished }) => {
+        reading = false;
+        controllers.forEach((controller, i) => {
+          if (canceled[i]) return;
+          if (finished) controller.close();
+          else controller.enqueue(value);
+        });
+        if (finished) done.resolve();
+      }, (reason) => {
+        controllers.forEach((controller) => controller.error(reason));
+        done.resolve();
+      });
+    };
+    const branch = (i) => new ReadableStream({
+      start(controller) { controllers[i] = controller; },
+      pull,
+      cancel(reason) {
+        canceled[i] = true;
+        reasons[i] = reason;
+        if (canceled[0] && canceled[1]) {
+          done.resolve();
+          return reader.cancel(reasons);
+        }
+        return done.promise;
+      },
+    });
+    return [branch(0), branch(1)];
+  }
+
+  async pipeTo(destination, options = {}) {
+    const reader = this.getReader();
+    const writer = destination.getWriter();
+    try {
+      for (;;) {
+        let result;
+        try {
+          result = await reader.read();
+        } catch (e) {
+          if (!options.preventAbort) await writer.abort(e);
+          throw e;
+        }
+        if (result.done) break;
+        try {
+          await writer.ready;
+          await writer.write(result.value);
+        } catch (e) {
+          if (!options.preventCancel) await reader.cancel(e);
+          throw e;
+        }
+      }
+      if (!options.preventClose) await writer.close();
+    } finally {
+      reader.releaseLock();
+      writer.releaseLock();
+    }
+  }
+
+  pipeThrough({ writable, readable }, options = {}) {
+    this.pipeTo(writable, options).catch(() => {});
+    return readable;
+  }
+
+  async *values(options = {}) {
+    const reader = this.getReader();
+    let finished = false;
+    try {
+      for (;;) {
+        const { value, done } = await reader.read();
+        if (done) {
+          finished = true;
+          return;
+        }
+        yield value;
+      }
+    } catch (e) {
+      finished = true;
+      throw e;
+    } finally {
+      if (!finished && !options.preventCancel) await reader.cancel();
+      reader.releaseLock();
+    }
+  }
+
+  [Symbol.asyncIterator](options) { return this.values(options); }
+
+  get [Symbol.toStringTag]() { return "ReadableStream"; }
+}
+
+class ReadableStreamDefaultReader {
+  #state;
+  #closed = deferred();
+
+  constructor(stream) {
+    const state = readableState(stream);
+    if (state.reader) throw new TypeError("The stream is already locked to a reader");
+    this.#state = state;
+    state.reader = { closed: this.#closed };
+    if (state.status === "closed") this.#closed.resolve();
+    if (state.status === "errored") this.#closed.reject(state.storedError);
+  }
+
+  get closed() { return this.#closed.promise; }
+
+  read() {
+    if (!this.#state) return Promise.reject(new TypeError("The reader has been released"));
+    return this.#state.read();
+  }
+
+  cancel(reason) {
+    if (!this.#state) return Promise.reject(new TypeError("The reader has been released"));
+    return this.#state.cancel(reason);
+  }
+
+  releaseLock() {
+    const state = this.#state;
+    if (!state) return;
+    const released = new TypeError("The reader has been released");
+    for (const request of state.readRequests.splice(0)) request.reject(released);
+    this.#closed = deferred();
+    this.#closed.reject(released);
+    state.reader = null;
+    this.#state = null;
+  }
+
+  get [Symbol.toStringTag]() { return "ReadableStreamDefaultReader"; }
+}
+
+// Internal state of each WritableStream.
+const writables = new WeakMap();
+
+const writableState = (stream) => {
+  const state = writables.get(stream);
+  if (!state) throw new TypeError("Illegal invocation");
+  return state;
+};
+
+class WritableStreamDefaultController {
+  #state;
+  constructor(state) { this.#state = state; }
+  error(reason) { this.#state.error(reason); }
+}
+
+class WritableStream {
+  constructor(sink = {}, strategy = {}) {
+    const state = {
+      status: "writable",
+      queue: [],
+      queueSize: 0,
+      writing: false,
+      started: false,
+      closeRequest: null,
+      storedError: undefined,
+      writer: null,
+      readyWaiters: [],
+      highWaterMark: highWaterMarkOf(strategy, 1),
+      get desiredSize() {
+        if (state.status === "errored") return null;
+        if (state.status === "closed") return 0;
+        return state.highWaterMark - state.queueSize;
+      },
+      advance() {
+        if (!state.started || state.writing || state.status !== "writable") return;
+        if (state.queue.length) {
+          const { chunk, size, request } = state.queue[0];
+          state.writing = true;
+          Promise.resolve()
+            .then(() => sink.write?.(chunk, controller))
+            .then(() => {
+              state.queue.shift();
+              state.queueSize -= size;
+              state.writing = false;
+              request.resolve();
+              if (state.desiredSize > 0) {
+                for (const waiter of state.readyWaiters.splice(0)) waiter.resolve();
+              }
+              state.advance();
+            }, (reason) => {
+              state.writing = false;
+              state.error(reason);
+            });
+        } else if (state.closeRequest) {
+          state.writing = true;
+          Promise.resolve()
+            .then(() => sink.close?.())
+            .then(() => {
+              state.writing = false;
+              state.status = "closed";
+              state.closeRequest.resolve();
+              state.writer?.closed.resolve();
+            }, (reason) => {
+              state.writing = false;
+              state.error(reason);
+            });
+        }
+      },
+      error(reason) {
+        if (state.status !== "writable") return;
+        state.status = "errored";
+        state.storedError = reason;
+        for (const { request } of state.queue.splice(0)) request.reject(reason);
+        state.queueSize = 0;
+        state.closeRequest?.reject(reason);
+        for (const waiter of state.readyWaiters.splice(0)) waiter.reject(reason);
+        state.writer?.closed.reject(reason);
+      },
+      write(chunk) {
+        if (state.status === "errored") return Promise.reject(state.storedError);
+        if (state.status === "closed" || state.closeRequest) {
+          return Promise.reject(new TypeError("Cannot write to a closing stream"));
+        }
+        let size;
+        try {
+          size = sizeOf(strategy, chunk);
+        } catch (e) {
+          state.error(e);
+          return Promise.reject(e);
+        }
+        const request = deferred();
+        state.queue.push({ chunk, size, request });
+        state.queueSize += size;
+        state.advance();
+        return request.promise;
+      },
+      ready() {
+        if (state.status === "errored") return Promise.reject(state.storedError);
+        if (state.desiredSize > 0 || state.status === "closed") return Promise.resolve();
+        const waiter = deferred();
+        state.readyWaiters.push(waiter);
+        return waiter.promise;
+      },
+      close() {
+        if (state.status === "errored") return Promise.reject(state.storedError);
+        if (state.status === "closed" || state.closeRequest) {
+          return Promise.reject(new TypeError("The stream is already closing"));
+        }
+        state.closeRequest = deferred();
+        state.advance();
+        return state.closeRequest.promise;
+      },
+      abort(reason) {
+        if (state.status !== "writable") return Promise.resolve();
+        state.error(reason);
+        return Promise.resolve().then(() => sink.abort?.(reason)).then(() => {});
+      },
+    };
+    const controller = new WritableStreamDefaultController(state);
+    writables.set(this, state);
+    Promise.resolve(sink.start?.(controller)).then(() => {
+      state.started = true;
+      state.advance();
+    }, state.error);
+  }
+
+  get locked() { return writableState(this).writer !== null; }
+
+  abort(reason) {
+    if (this.locked) return Promise.reject(new TypeError("Cannot abort a locked stream"));
+    return writableState(this).abort(reason);
+  }
+
+  close() {
+    if (this.locked) return Promise.reject(new TypeError("Cannot close a locked stream"));
+    return writableState(this).close();
+  }
+
+  getWriter() { return new WritableStreamDefaultWriter(this); }
+
+  get [Symbol.toStringTag]() { return "WritableStream"; }
+}
+
+class WritableStreamDefaultWriter {
+  #state;
+  #closed = deferred();
+
+  constructor(stream) {
+    const state = writableState(stream);
+    if (state.writer) throw new TypeError("The stream is already locked to a writer");
+    this.#state = state;
+    state.writer = { closed: this.#closed };
+    if (state.status === "closed") this.#closed.resolve();
+    if (state.status === "errored") this.#closed.reject(state.storedError);
+  }
+
+  #locked() {
+    if (!this.#state) throw new TypeError("The writer has been released");
+    return this.#state;
+  }
+
+  get closed() { return this.#closed.promise; }
+  get desiredSize() { return this.#locked().desiredSize; }
+  get ready() {
+    try {
+      return this.#locked().ready();
+    } catch (e) {
+      return Promise.reject(e);
+    }
+  }
+
+  write(chunk) {
+    try {
+      return this.#locked().write(chunk);
+    } catch (e) {
+      return Promise.reject(e);
+    }
+  }
+
+  close() {
+    try {
+      return this.#locked().close();
+    } catch (e) {
+      return Promise.reject(e);
+    }
+  }
+
+  abort(reason) {
+    try {
+      return this.#locked().abort(reason);
+    } catch (e) {
+      return Promise.reject(e);
+    }
+  }
+
+  releaseLock() {
+    const state = this.#state;
+    if (!state) return;
+    const released = new TypeError("The writer has been released");
+    this.#closed = deferred();
+    this.#closed.reject(released);
+    state.writer = null;
+    this.#state = null;
+  }
+
+  get [Symbol.toStringTag]() { return "WritableStreamDefaultWriter"; }
+}
+
+class TransformStream {
+  constructor(transformer = {}, writableStrategy = {}, readableStrategy = { highWaterMark: 0 }) {
+    let readableController;
+    this.readable = new ReadableStream({
+      start(c) { readableController = c; },
+      cancel(reason) { writableController.error(reason); },
+    }, readableStrategy);
+
+    const controller = {
+      get desiredSize() { return readableController.desiredSize; },
+      enqueue(chunk) { readableController.enqueue(chunk); },
+      error(reason) {
+        readableController.error(reason);
+        writableController.error(reason);
+      },
+      terminate() {
+        try {
+          readableController.close();
+        } catch {}
+        writableController.error(new TypeError("The transform stream has been terminated"));
+      },
+    };
+
+    let writableController;
+    this.writable = new WritableStream({
+      start(c) {
+        writableController = c;
+        return transformer.start?.(controller);
+      },
+      write(chunk) {
+        return transformer.transform
+          ? transformer.transform(chunk, controller)
+          : controller.enqueue(chunk);
+      },
+      async close() {
+        await transformer.flush?.(controller);
+        try {
+          readableController.close();
+        } catch {}
+      },
+      abort(reason) { readableController.error(reason); },
+    }, writableStrategy);
+  }
+
+  get [Symbol.toStringTag]() { return "TransformStream"; }
+}
+
+Object.assign(globalThis, {
+  ReadableStream,
+  ReadableStreamDefaultController,
+  ReadableStreamDefaultReader,
+  WritableStream,
+  WritableStreamDefaultController,
+  WritableStreamDefaultWriter,
+  TransformStream,
+  CountQueuingStrategy,
+  ByteLengthQueuingStrategy,
+});
+})();"#;
@@ -0,0 +1,95 @@
+//! The `structuredClone` global.
+//!
+//! Deep-copies the types the HTML structured clone algorithm supports in a
+//! single realm: primitives and their wrappers, plain objects and arrays,
+//! `Date`, `RegExp`, `Map`, `Set`, `Error`s, `ArrayBuffer`s and their views.
+//! Shared and cyclic references are preserved. Anything else, such as
+//! functions or symbols, throws a `DataCloneError`.
+
+pub(super) const SOURCE: &str = r#"(() => {
+const ERRORS = { Error, EvalError, RangeError, ReferenceError, SyntaxError, TypeError, URIError };
+
+const dataCloneError = (message) =>
+  typeof DOMException === "function"
+    ? new DOMException(message, "DataCloneError")
+    : Object.assign(new Error(message), { name: "DataCloneError" });
+
+const tag = (value) => Object.prototype.toString.call(value).slice(8, -1);
+
+globalThis.structuredClone = function structuredClone(value, options = {}) {
+  if (arguments.length === 0) throw new TypeError("structuredClone requires a value");
+  const transfer = Array.from(options?.transfer ?? []);
+  for (const buffer of transfer) {
+    if (!(buffer instanceof ArrayBuffer)) throw dataCloneError("Only ArrayBuffers can be transferred");
+  }
+  const memory = new Map();
+
+  const clone = (value) => {
+    if (typeof value === "symbol") throw dataCloneError(`${String(value)} could not be cloned`);
+    if (typeof value === "function") throw dataCloneError(`${value.name || "function"} could not be cloned`);
+    if (value === null || typeof value !== "object") return value;
+    if (memory.has(value)) return memory.get(value);
+
+    const remember = (copy) => {
+      memory.set(value, copy);
+      return copy;
+    };
+
+    switch (tag(value)) {
+      case "Boolean":
+      case "Number":
+      case "String":
+      case "BigInt":
+        return remember(Object(value.valueOf()));
+      case "Date":
+        return remember(new Date(value.getTime()));
+      case "RegExp":
+        return remember(new RegExp(value.source, value.flags));
+      case "ArrayBuffer":
+        return remember(value.slice(0));
+      case "DataView":
+        return remember(new DataView(clone(value.buffer), value.byteOffset, value.byteLength));
+      case "Map": {
+        const copy = remember(new Map());
+        for (const [k, v] of value) copy.set(clone(k), clone(v));
+        return copy;
+      }
+      case "Set": {
+        const copy = remember(new Set());
+        for (const v of value) copy.add(clone(v));
+        return copy;
+      }
+      case "Error": {
+        const name = ERRORS[value.name] ? value.name : "Error";
+        const copy = remember(new ERRORS[name](clone(value.message)));
+        if ("cause" in value) copy.cause = clone(value.cause);
+        if (typeof value.stack === "string") copy.stack = value.stack;
+        return copy;
+      }
+      case "Array": {
+        const copy = remember(new Array(value.length));
+        for (const key of Object.keys(value)) copy[key] = clone(value[key]);
+        return copy;
+      }
+      case "Object": {
+        const copy = remember({});
+        for (const key of Object.keys(value)) copy[key] = clone(value[key]);
+        return copy;
+      }
+    }
+    if (ArrayBuffer.isView(value)) {
+      return remember(new value.constructor(clone(value.buffer), value.byteOffset, value.length));
+    }
+    throw dataCloneError(`${tag(value)} object could not be cloned`);
+  };
+
+  for (const buffer of transfer) {
+    memory.set(buffer, buffer.slice(0));
+  }
+  const result = clone(value);
+  for (const buffer of transfer) {
+    if (typeof buffer.transfer === "function") buffer.transfer();
+  }
+  return result;
+};
+})();"#;
@@ -0,0 +1,648 @@
+//! WHATWG `URL` and `URLSearchParams` globals.
+//!
+//! QuickJS ships neither, so both are implemented in JavaScript following the
+//! URL Standard's parser: special schemes, relative resolution, dot-segment
+//! removal, IPv4/IPv6 hosts and the standard percent-encode sets. Internationalized
+//! domain names are lowercased, NFC-normalized and Punycode-encoded, without
+//! the full UTS #46 mapping table.
+
+pub(super) const SOURCE: &str = r##"(() => {
+const SPECIAL = new Map([["ftp", 21], ["file", null], ["http", 80], ["https", 443], ["ws", 80], ["wss", 443]]);
+
+const FRAGMENT = ' "<>`';
+const QUERY = ' "#<>';
+const SPECIAL_QUERY = QUERY + "'";
+const PATH = QUERY + "?^`{}";
+const USERINFO = PATH + "/:;=@[\\]|";
+const COMPONENT = USERINFO + "$%&+,";
+const FORM = COMPONENT + "!'()~";
+
+const utf8Encode = (s) => {
+  const bytes = [];
+  for (const ch of s) {
+    let c = ch.codePointAt(0);
+    if (c >= 0xd800 && c <= 0xdfff) c = 0xfffd;
+    if (c < 0x80) bytes.push(c);
+    else if (c < 0x800) bytes.push(0xc0 | (c >> 6), 0x80 | (c & 63));
+    else if (c < 0x10000) bytes.push(0xe0 | (c >> 12), 0x80 | ((c >> 6) & 63), 0x80 | (c & 63));
+    else bytes.push(0xf0 | (c >> 18), 0x80 | ((c >> 12) & 63), 0x80 | ((c >> 6) & 63), 0x80 | (c & 63));
+  }
+  return bytes;
+};
+
+const utf8Decode = (bytes) => {
+  let out = "";
+  for (let i = 0; i < bytes.length;) {
+    const b = bytes[i];
+    const n = b < 0x80 ? 0 : b >= 0xc2 && b < 0xe0 ? 1 : b >= 0xe0 && b < 0xf0 ? 2 : b >= 0xf0 && b < 0xf5 ? 3 : -1;
+    if (n < 0) { out += "\uFFFD"; i++; continue; }
+    let c = n ? b & (0x3f >> n) : b;
+    let j = 1;
+    for (; j <= n && (bytes[i + j] & 0xc0) === 0x80; j++) c = (c << 6) | (bytes[i + j] & 0x3f);
+    if (j <= n) { out += "\uFFFD"; i += j; continue; }
+    const invalid = (n === 2 && (c < 0x800 || (c >= 0xd800 && c < 0xe000))) || (n === 3 && (c < 0x10000 || c > 0x10ffff));
+    out += invalid ? "\uFFFD" : String.fromCodePoint(c);
+    i += n + 1;
+  }
+  return out;
+};
+
+const hex = (b) => "%" + (b < 16 ? "0" : "") + b.toString(16).toUpperCase();
+
+const encode = (s, set, spaceAsPlus = false) => {
+  let out = "";
+  for (const ch of s) {
+    const c = ch.codePointAt(0);
+    if (spaceAsPlus && ch === " ") out += "+";
+    else if (c < 0x20 || c > 0x7e || set.includes(ch)) out += utf8Encode(ch).map(hex).join("");
+    else out += ch;
+  }
+  return out;
+};
+
+const percentDecode = (s) => {
+  const bytes = utf8Encode(s);
+  const out = [];
+  for (let i = 0; i < bytes.length; i++) {
+    const pair = String.fromCharCode(bytes[i + 1], bytes[i + 2]);
+    if (bytes[i] === 0x25 && i + 2 < bytes.length && /^[0-9a-fA-F]{2}$/.test(pair)) {
+      out.push(parseInt(pair, 16));
+      i += 2;
+    } else {
+      out.push(bytes[i]);
+    }
+  }
+  return out;
+};
+
+const punycode = (label) => {
+  const points = Array.from(label, (ch) => ch.codePointAt(0));
+  let out = points.filter((c) => c < 0x80).map((c) => String.fromCharCode(c)).join("");
+  const basic = out.length;
+  if (basic) out += "-";
+  const digit = (d) => String.fromCharCode(d + 22 + 75 * (d < 26));
+  const adapt = (delta, count, first) => {
+    delta = first ? Math.floor(delta / 700) : delta >> 1;
+    delta += Math.floor(delta / count);
+    let k = 0;
+    for (; delta > 455; k += 36) delta = Math.floor(delta / 35);
+    return k + Math.floor((36 * delta) / (delta + 38));
+  };
+  let n = 128, delta = 0, bias = 72;
+  for (let h = basic; h < points.length; n++, delta++) {
+    const m = Math.min(...points.filter((c) => c >= n));
+    delta += (m - n) * (h + 1);
+    n = m;
+    for (const c of points) {
+      if (c < n) delta++;
+      if (c !== n) continue;
+      let q = delta;
+      for (let k = 36; ; k += 36) {
+        const t = k <= bias ? 1 : k >= bias + 26 ? 26 : k - bias;
+        if (q < t) break;
+        out += digit(t + ((q - t) % (36 - t)));
+        q = Math.floor((q - t) / (36 - t));
+      }
+      out += digit(q);
+      bias = adapt(delta, h + 1, h === basic);
+      delta = 0;
+      h++;
+    }
+  }
+  return "xn--" + out;
+};
+
+const parseIPv4Number = (s) => {
+  if (s === "") return NaN;
+  let radix = 10;
+  if (/^0[xX]/.test(s)) { s = s.slice(2); radix = 16; }
+  else if (s.length > 1 && s[0] === "0") { s = s.slice(1); radix = 8; }
+  if (s === "") return 0;
+  const digits = radix === 16 ? /^[0-9a-fA-F]+$/ : radix === 8 ? /^[0-7]+$/ : /^[0-9]+$/;
+  return digits.test(s) ? parseInt(s, radix) : NaN;
+};
+
+const ipv4Parts = (host) => {
+  const parts = host.split(".");
+  if (parts.length > 1 && parts[parts.length - 1] === "") parts.pop();
+  return parts;
+};
+
+const endsInNumber = (host) => {
+  const last = ipv4Parts(host).pop();
+  return /^[0-9]+$/.test(last) || !Number.isNaN(parseIPv4Number(last));
+};
+
+const parseIPv4 = (host) => {
+  const numbers = ipv4Parts(host).map(parseIPv4Number);
+  if (numbers.length > 4 || numbers.some(Number.isNaN)) return null;
+  const last = numbers.pop();
+  if (numbers.some((n) => n > 255) || last >= 256 ** (4 - numbers.length)) return null;
+  const address = numbers.reduce((sum, n, i) => sum + n * 256 ** (3 - i), last);
+  return [24, 16, 8, 0].map((shift) => Math.floor(address / 2 ** shift) % 256).join(".");
+};
+
+const parseIPv6 = (input) => {
+  const pieces = [0, 0, 0, 0, 0, 0, 0, 0];
+  const isHex = (ch) => ch !== undefined && /[0-9a-fA-F]/.test(ch);
+  const isDigit = (ch) => ch !== undefined && /[0-9]/.test(ch);
+  let index = 0, compress = null, i = 0;
+  if (input[0] === ":") {
+    if (input[1] !== ":") return null;
+    i = 2;
+    compress = ++index;
+  }
+  while (i < input.length) {
+    if (index === 8) return null;
+    if (input[i] === ":") {
+      if (compress !== null) return null;
+      i++;
+      compress = ++index;
+      continue;
+    }
+    let value = 0, length = 0;
+    for (; length < 4 && isHex(input[i]); length++, i++) value = value * 16 + parseInt(input[i], 16);
+    if (input[i] === ".") {
+      if (length === 0 || index > 6) return null;
+      i -= length;
+      let seen = 0;
+      while (i < input.length) {
+        if (seen > 0) {
+          if (input[i] !== "." || seen >= 4) return null;
+          i++;
+        }
+        if (!isDigit(input[i])) return null;
+        let octet = null;
+        for (; isDigit(input[i]); i++) {
+          if (octet === 0) return null;
+          octet = (octet ?? 0) * 10 + Number(input[i]);
+          if (octet > 255) return null;
+        }
+        pieces[index] = pieces[index] * 256 + octet;
+        if (++seen === 2 || seen === 4) index++;
+      }
+      if (seen !== 4) return null;
+      break;
+    }
+    if (input[i] === ":") {
+      if (++i >= input.length) return null;
+    } else if (i < input.length) {
+      return null;
+    }
+    pieces[index++] = value;
+  }
+  if (compress !== null) {
+    for (let swaps = index - compress, last = 7; last !== 0 && swaps > 0; last--, swaps--) {
+      [pieces[last], pieces[compress + swaps - 1]] = [pieces[compress + swaps - 1], pieces[last]];
+    }
+  } else if (index !== 8) {
+    return null;
+  }
+  return pieces;
+};
+
+const serializeIPv6 = (pieces) => {
+  let start = -1, length = 1;
+  for (let i = 0; i < 8;) {
+    let j = i;
+    while (j < 8 && pieces[j] === 0) j++;
+    if (j - i > length) { start = i; length = j - i; }
+    i = j === i ? i + 1 : j;
+  }
+  let out = "";
+  for (let i = 0; i < 8; i++) {
+    if (i === start) {
+      out += i === 0 ? "::" : ":";
+      i += length - 1;
+    } else {
+      out += pieces[i].toString(16) + (i < 7 ? ":" : "");
+    }
+  }
+  return `[${out}]`;
+};
+
+const parseHost = (input, special) => {
+  if (input.startsWith("[")) {
+    const pieces = input.endsWith("]") ? parseIPv6(input.slice(1, -1)) : null;
+    return pieces && serializeIPv6(pieces);
+  }
+  if (!special) {
+    return /[\0\t\n\r #/:<>?@[\\\]^|]/.test(input) ? null : encode(input, "");
+  }
+  const domain = utf8Decode(percentDecode(input)).normalize("NFC").toLowerCase();
+  if (domain === "" || /[\0-\x20#%/:<>?@[\\\]^|\x7f]/.test(domain)) return null;
+  const ascii = domain.split(".").map((label) => (/[^\0-\x7f]/.test(label) ? punycode(label) : label)).join(".");
+  return endsInNumber(ascii) ? parseIPv4(ascii) : ascii;
+};
+
+const parsePort = (url, input) => {
+  if (!/^[0-9]+$/.test(input) || Number(input) > 65535) return false;
+  const port = Number(input);
+  url.port = port === SPECIAL.get(url.scheme) ? null : port;
+  return true;
+};
+
+const parseHostPort = (url, input) => {
+  const match = /^(\[[^\]]*\]|[^:]*)(?::(.*))?$/.exec(input);
+  if (!match) return false;
+  const host = parseHost(match[1], SPECIAL.has(url.scheme));
+  if (host === null || (host === "" && SPECIAL.has(url.scheme))) return false;
+  url.host = host;
+  url.port = null;
+  return match[2] === undefined || match[2] === "" || parsePort(url, match[2]);
+};
+
+const parseAuthority = (url, input) => {
+  const at = input.lastIndexOf("@");
+  if (at >= 0) {
+    const userinfo = input.slice(0, at);
+    const colon = userinfo.indexOf(":");
+    url.username = encode(colon < 0 ? userinfo : userinfo.slice(0, colon), USERINFO);
+    url.password = colon < 0 ? "" : encode(userinfo.slice(colon + 1), USERINFO);
+    input = input.slice(at + 1);
+    if (input === "") return false;
+  }
+  return parseHostPort(url, input);
+};
+
+const parsePath = (input, segments, special) => {
+  const path = [...segments];
+  const parts = input.split(special ? /[/\\]/ : "/");
+  if (parts.length > 1 && parts[0] === "") parts.shift();
+  parts.forEach((part, i) => {
+    const last = i === parts.length - 1;
+    const lower = part.toLowerCase();
+    if (lower === ".." || lower === ".%2e" || lower === "%2e." || lower === "%2e%2e") {
+      path.pop();
+      if (last) path.push("");
+    } else if (lower === "." || lower === "%2e") {
+      if (last) path.push("");
+    } else {
+      path.push(encode(part, PATH));
+    }
+  });
+  return path;
+};
+
+const splitTail = (input) => {
+  const hash = input.indexOf("#");
+  const fragment = hash < 0 ? null : input.slice(hash + 1);
+  if (hash >= 0) input = input.slice(0, hash);
+  const question = input.indexOf("?");
+  const query = question < 0 ? null : input.slice(question + 1);
+  if (question >= 0) input = input.slice(0, question);
+  return [input, query, fragment];
+};
+
+const setQuery = (url, query) => {
+  url.query = query === null ? null : encode(query, SPECIAL.has(url.scheme) ? SPECIAL_QUERY : QUERY);
+};
+
+const setFragment = (url, fragment) => {
+  url.fragment = fragment === null ? null : encode(fragment, FRAGMENT);
+};
+
+const parseAbsolute = (scheme, rest) => {
+  const url = { scheme, username: "", password: "", host: null, port: null, path: [], query: null, fragment: null };
+  const special = SPECIAL.has(scheme);
+  const [body, query, fragment] = splitTail(rest);
+  setQuery(url, query);
+  setFragment(url, fragment);
+  const slashes = special ? /^[/\\]*/ : /^\/\//;
+  const authorityEnd = (s) => s.search(special ? /[/\\]/ : /\//);
+
+  if (scheme === "file") {
+    let path = body;
+    url.host = "";
+    if (/^[/\\]{2}/.test(body)) {
+      const after = body.slice(2);
+      const end = authorityEnd(after);
+      const authority = end < 0 ? after : after.slice(0, end);
+      path = end < 0 ? "" : after.slice(end);
+      if (authority !== "") {
+        const host = parseHost(authority, true);
+        if (host === null) return null;
+        url.host = host === "localhost" ? "" : host;
+      }
+    }
+    url.path = parsePath(path, [], true);
+    return url;
+  }
+
+  if (special || slashes.test(body)) {
+    const after = body.replace(slashes, "");
+    const end = authorityEnd(after);
+    if (!parseAuthority(url, end < 0 ? after : after.slice(0, end))) return null;
+    const path = end < 0 ? "" : after.slice(end);
+    url.path = path === "" && !special ? [] : parsePath(path, [], special);
+  } else if (body.startsWith("/")) {
+    url.path = parsePath(body, [], false);
+  } else {
+    url.path = encode(body, "");
+  }
+  return url;
+};
+
+const parseRelative = (input, base) => {
+  const special = SPECIAL.has(base.scheme);
+  if ((special ? /^[/\\]{2}/ : /^\/\//).test(input)) return parseAbsolute(base.scheme, input);
+  const url = { ...base, path: [...base.path], fragment: null };
+  const [body, query, fragment] = splitTail(input);
+  setFragment(url, fragment);
+  if (body === "") {
+    if (query !== null) setQuery(url, query);
+    return url;
+  }
+  setQuery(url, query);
+  const absolute = special ? /^[/\\]/.test(body) : body.startsWith("/");
+  url.path = parsePath(body, absolute ? [] : base.path.slice(0, -1), special);
+  return url;
+};
+
+const parse = (input, base) => {
+  input = String(input).replace(/^[\0-\x20]+|[\0-\x20]+$/g, "").replace(/[\t\n\r]/g, "");
+  const match = /^([a-zA-Z][a-zA-Z0-9+.-]*):/.exec(input);
+  if (match) {
+    const scheme = match[1].toLowerCase();
+    const rest = input.slice(match[0].length);
+    const sameSpecial = base && base.scheme === scheme && SPECIAL.has(scheme);
+    if (sameSpecial && !/^[/\\]/.test(rest)) return parseRelative(rest, base);
+    return parseAbsolute(scheme, rest);
+  }
+  if (!base) return null;
+  if (typeof base.path === "string") {
+    if (!input.startsWith("#")) return null;
+    const url = { ...base };
+    setFragment(url, input.slice(1));
+    return url;
+  }
+  return parseRelative(input, base);
+};
+
+const pathname = (url) => (typeof url.path === "string" ? url.path : url.path.map((s) => "/" + s).join(""));
+
+const serialize = (url) => {
+  let out = url.scheme + ":";
+  if (url.host !== null) {
+    out += "//";
+    if (url.username || url.password) {
+      out += url.username + (url.password ? ":" + url.password : "") + "@";
+    }
+    out += url.host + (url.port === null ? "" : ":" + url.port);
+  } else if (typeof url.path !== "string" && url.path.length > 1 && url.path[0] === "") {
+    out += "/.";
+  }
+  out += pathname(url);
+  if (url.query !== null) out += "?" + url.query;
+  if (url.fragment !== null) out += "#" + url.fragment;
+  return out;
+};
+
+const origin = (url) => {
+  if (url.scheme === "blob") {
+    const inner = parse(pathname(url), null);
+    return inner && (inner.scheme === "http" || inner.scheme === "https") ? origin(inner) : "null";
+  }
+  if (!SPECIAL.has(url.scheme) || url.scheme === "file") return "null";
+  return `${url.scheme}://${url.host}${url.port === null ? "" : ":" + url.port}`;
+};
+
+const formDecode = (s) => utf8Decode(percentDecode(s.replace(/\+/g, " ")));
+
+const parseForm = (input) => input.split("&").filter(Boolean).map((pair) => {
+  const eq = pair.indexOf("=");
+  return eq < 0 ? [formDecode(pair), ""] : [formDecode(pair.slice(0, eq)), formDecode(pair.slice(eq + 1))];
+});
+
+const serializeForm = (list) =>
+  list.map(([name, value]) => encode(name, FORM, true) + "=" + encode(value, FORM, true)).join("&");
+
+// URLSearchParams state, shared with the URL that owns it (if any).
+const params = new WeakMap();
+
+const paramsOf = (self) => {
+  const state = params.get(self);
+  if (!state) throw new TypeError("Illegal invocation");
+  return state;
+};
+
+const updateUrl = (state) => {
+  if (!state.url) return;
+  const query = serializeForm(state.list);
+  state.url.query = query === "" ? null : query;
+};
+
+class URLSearchParams {
+  constructor(init = "") {
+    let list;
+    if ((typeof init === "object" && init !== null) || typeof init === "function") {
+      list = typeof init[Symbol.iterator] === "function"
+        ? Array.from(init, (pair) => {
+            const entry = Array.from(pair, String);
+            if (entry.length !== 2) throw new TypeError("URLSearchParams: each pair must have exactly two items");
+            return entry;
+          })
+        : Object.keys(init).map((name) => [name, String(init[name])]);
+    } else {
+      const query = String(init);
+      list = parseForm(query.startsWith("?") ? query.slice(1) : query);
+    }
+    params.set(this, { list, url: null });
+  }
+  get size() { return paramsOf(this).list.length; }
+  append(name, value) {
+    const state = paramsOf(this);
+    state.list.push([String(name), String(value)]);
+    updateUrl(state);
+  }
+  delete(name, value) {
+    const state = paramsOf(this);
+    name = String(name);
+    state.list = state.list.filter(([n, v]) => n !== name || (value !== undefined && v !== String(value)));
+    updateUrl(state);
+  }
+  get(name) {
+    name = String(name);
+    return paramsOf(this).list.find(([n]) => n === name)?.[1] ?? null;
+  }
+  getAll(name) {
+    name = String(name);
+    return paramsOf(this).list.filter(([n]) => n === name).map(([, v]) => v);
+  }
+  has(name, value) {
+    name = String(name);
+    return paramsOf(this).list.some(([n, v]) => n === name && (value === undefined || v === String(value)));
+  }
+  set(name, value) {
+    const state = paramsOf(this);
+    name = String(name);
+    const index = state.list.findIndex(([n]) => n === name);
+    if (index < 0) {
+      state.list.push([name, String(value)]);
+    } else {
+      state.list[index][1] = String(value);
+      state.list = state.list.filter(([n], i) => n !== name || i <= index);
+    }
+    updateUrl(state);
+  }
+  sort() {
+    const state = paramsOf(this);
+    state.list.sort(([a], [b]) => (a < b ? -1 : a > b ? 1 : 0));
+    updateUrl(state);
+  }
+  forEach(callback, thisArg) {
+    for (const [name, value] of this) callback.call(thisArg, value, name, this);
+  }
+  *entries() {
+    const state = paramsOf(this);
+    for (let i = 0; i < state.list.length; i++) yield [...state.list[i]];
+  }
+  *keys() { for (const [name] of this.entries()) yield name; }
+  *values() { for (const [, value] of this.entries()) yield value; }
+  [Symbol.iterator]() { return this.entries(); }
+  toString() { return serializeForm(paramsOf(this).list); }
+  get [Symbol.toStringTag]() { return "URLSearchParams"; }
+}
+
+class URL {
+  #url;
+  #searchParams;
+
+  constructor(url, base) {
+    let parsedBase = null;
+    if (base !== undefined) {
+      parsedBase = parse(base, null);
+      if (!parsedBase) throw new TypeError(`Invalid base URL: ${base}`);
+    }
+    const parsed = parse(url, parsedBase);
+    if (!parsed) throw new TypeError(`Invalid URL: ${url}`);
+    this.#url = parsed;
+    this.#searchParams = new URLSearchParams(parsed.query ?? "");
+    params.get(this.#searchParams).url = parsed;
+  }
+
+  static canParse(url, base) {
+    try {
+      new URL(url, base);
+      return true;
+    } catch {
+      return false;
+    }
+  }
+
+  static parse(url, base) {
+    try {
+      return new URL(url, base);
+    } catch {
+      return null;
+    }
+  }
+
+  #resetParams() {
+    params.get(this.#searchParams).list = parseForm(this.#url.query ?? "");
+  }
+
+  get href() { return serialize(this.#url); }
+  set href(value) {
+    const parsed = parse(value, null);
+    if (!parsed) throw new TypeError(`Invalid URL: ${value}`);
+    Object.assign(this.#url, parsed);
+    this.#resetParams();
+  }
+
+  get origin() { return origin(this.#url); }
+
+  get protocol() { return this.#url.scheme + ":"; }
+  set protocol(value) {
+    const url = this.#url;
+    const scheme = String(value).split(":")[0].toLowerCase();
+    if (!/^[a-z][a-z0-9+.-]*$/.test(scheme) || SPECIAL.has(scheme) !== SPECIAL.has(url.scheme)) return;
+    if (scheme === "file" && (url.username || url.password || url.port !== null)) return;
+    if (url.scheme === "file" && url.host === "") return;
+    url.scheme = scheme;
+    if (url.port === SPECIAL.get(scheme)) url.port = null;
+  }
+
+  #hasCredentials() {
+    const url = this.#url;
+    return url.host !== null && url.host !== "" && url.scheme !== "file";
+  }
+
+  get username() { return this.#url.username; }
+  set username(value) {
+    if (this.#hasCredentials()) this.#url.username = encode(String(value), USERINFO);
+  }
+
+  get password() { return this.#url.password; }
+  set password(value) {
+    if (this.#hasCredentials()) this.#url.password = encode(String(value), USERINFO);
+  }
+
+  #authorityInput(value) {
+    const special = SPECIAL.has(this.#url.scheme);
+    return String(value).split(special ? /[/\\?#]/ : /[/?#]/)[0];
+  }
+
+  get host() {
+    const { host, port } = this.#url;
+    return host === null ? "" : host + (port === null ? "" : ":" + port);
+  }
+  set host(value) {
+    if (typeof this.#url.path === "string") return;
+    const copy = { ...this.#url };
+    const input = this.#authorityInput(value);
+    if (!parseHostPort(copy, input)) return;
+    this.#url.host = this.#url.scheme === "file" && copy.host === "localhost" ? "" : copy.host;
+    if (/^(\[[^\]]*\]|[^:]*):./.test(input)) this.#url.port = copy.port;
+  }
+
+  get hostname() { return this.#url.host ?? ""; }
+  set hostname(value) {
+    if (typeof this.#url.path === "string") return;
+    const input = this.#authorityInput(value);
+    if (/^(\[[^\]]*\]|[^:]*):/.test(input)) return;
+    const copy = { ...this.#url };
+    if (!parseHostPort(copy, input)) return;
+    this.#url.host = this.#url.scheme === "file" && copy.host === "localhost" ? "" : copy.host;
+  }
+
+  get port() { return this.#url.port === null ? "" : String(this.#url.port); }
+  set port(value) {
+    if (!this.#hasCredentials()) return;
+    const digits = /^[0-9]*/.exec(this.#authorityInput(value))[0];
+    if (String(value) === "") this.#url.port = null;
+    else if (digits !== "") parsePort(this.#url, digits);
+  }
+
+  get pathname() { return pathname(this.#url); }
+  set pathname(value) {
+    const url = this.#url;
+    if (typeof url.path === "string") return;
+    const special = SPECIAL.has(url.scheme);
+    const input = String(value);
+    url.path = input === "" && !special ? [] : parsePath(input.replace(special ? /^[/\\]?/ : /^\/?/, "/"), [], special);
+  }
+
+  get search() { return this.#url.query ? "?" + this.#url.query : ""; }
+  set search(value) {
+    const input = String(value).replace(/^\?/, "");
+    setQuery(this.#url, String(value) === "" ? null : input);
+    this.#resetParams();
+  }
+
+  get searchParams() { return this.#searchParams; }
+
+  get hash() { return this.#url.fragment ? "#" + this.#url.fragment : ""; }
+  set hash(value) {
+    const input = String(value).replace(/^#/, "");
+    setFragment(this.#url, String(value) === "" ? null : input);
+  }
+
+  toString() { return this.href; }
+  toJSON() { return this.href; }
+  get [Symbol.toStringTag]() { return "URL"; }
+}
+
+globalThis.URL = URL;
+globalThis.URLSearchParams = URLSearchParams;
+})();"##;
@@ -0,0 +1,26 @@
+//! `error-context` values, which the async ABI uses to describe failures.
+//!
+//! An error-context is lifted as an `Error` whose message is its debug
+//! message. Any value lowered as one becomes a new error-context with the
+//! value's `message` as the debug message if it is an `Error`, or the value
+//! itself as a string otherwise.
+
+use rquickjs::{Coerced, Ctx, Exception, Result, Value};
+
+use crate::abi;
+
+/// Lift the error-context `handle` as an `Error`, dropping the handle.
+pub(crate) fn lift<'js>(ctx: &Ctx<'js>, handle: u32) -> Result<Value<'js>> {
+    let message = abi::error_context_debug_message(handle);
+    unsafe { abi::error_context_drop(handle) };
+    Ok(Exception::from_message(ctx.clone(), &message)?.into_value())
+}
+
+/// Lower `value` as a new error-context, owned by the caller.
+pub(crate) fn lower(value: &Value<'_>) -> Result<u32> {
+    let message = match value.as_object().filter(|_| value.is_error()) {
+        Some(error) => error.get::<_, Coerced<String>>("message")?.0,
+        None => value.get::<Coerced<String>>()?.0,
+    };
+    Ok(abi::error_context_new(&message))
+}
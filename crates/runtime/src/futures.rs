@@ -7,6 +7,7 @@ use rquickjs::{JsLifetime, function};
 use crate::CtxExt;
 use crate::abi::{CopyEnd, CopyResult, is_blocked_raw, unpack_copy_result};
 use crate::buffer::BufferGuard;
+use crate::streams::with_signal;
 use crate::task::Pending;
 use crate::{QjsCallContext, resolve_promise, symbol_dispose, with_ctx};
 
@@ -30,7 +31,8 @@ impl<'js> JsClass<'js> for FutureReadable {
 
     fn prototype(ctx: &Ctx<'js>) -> rquickjs::Result<Option<Object<'js>>> {
         let proto = Object::new(ctx.clone())?;
-        proto.set("read", Function::new(ctx.clone(), future_read)?)?;
+        let read = Function::new(ctx.clone(), future_read)?;
+        proto.set("read", with_signal(ctx, read, "cancelRead", 0)?)?;
         proto.set(
             "cancelRead",
             Function::new(ctx.clone(), future_cancel_read)?,
@@ -70,7 +72,8 @@ impl<'js> JsClass<'js> for FutureWritable {
 
     fn prototype(ctx: &Ctx<'js>) -> rquickjs::Result<Option<Object<'js>>> {
         let proto = Object::new(ctx.clone())?;
-        proto.set("write", Function::new(ctx.clone(), future_write)?)?;
+        let write = Function::new(ctx.clone(), future_write)?;
+        proto.set("write", with_signal(ctx, write, "cancelWrite", 1)?)?;
         proto.set(
             "cancelWrite",
             Function::new(ctx.clone(), future_cancel_write)?,
@@ -194,6 +197,8 @@ fn future_cancel_read<'js>(
             Ok(Value::new_undefined(ctx))
         }
         Some((_progress, result)) => {
+            // The copy settled without an event, so stop waiting for one.
+            drop(ctx.task().take(handle));
             this.0.borrow_mut().end.mark_completed(result);
             Ok(Value::new_number(ctx, result as u32 as f64))
         }
@@ -225,7 +230,7 @@ fn future_write<'js>(
     let buffer = BufferGuard::new_zeroed(ty.abi_payload_size(), ty.abi_payload_align());
 
     let mut call = QjsCallContext::default();
-    call.push_value(&ctx, value);
+    call.push_value(value);
     unsafe { ty.lower(&mut call, buffer.ptr()) };
 
     let code = unsafe { ty.write()(handle, buffer.ptr().cast()) };
@@ -268,6 +273,8 @@ fn future_cancel_write<'js>(
             Ok(Value::new_undefined(ctx))
         }
         Some((_progress, result)) => {
+            // The copy settled without an event, so stop waiting for one.
+            drop(ctx.task().take(handle));
             this.0.borrow_mut().end.mark_completed(result);
             Ok(Value::new_number(ctx, result as u32 as f64))
         }
@@ -341,7 +348,7 @@ pub(crate) fn handle_read_event(handle: u32, result: u32) {
                 let ty = ctx.wit().future(type_index as usize);
                 unsafe { ty.lift(&mut call, buffer.ptr()) };
 
-                Some(call.pop_persistent())
+                Some(call.pop_persistent(ctx))
             });
 
             drop(buffer);
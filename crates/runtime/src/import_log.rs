@@ -0,0 +1,202 @@
+//! Record and replay of synchronous import calls.
+//!
+//! In record mode every sync import call appends one JSON line holding the
+//! import's name, its arguments and its result to a capture file. In replay
+//! mode imports are not called at all: results are read back from the capture
+//! in call order. The capture is accessed through WASI at run time, so its
+//! path must live in a directory preopened by the host.
+
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::io::Write;
+
+use rquickjs::{Ctx, Exception, Function, JsLifetime, Object, Persistent, Value};
+
+use crate::init::ImportLogMode;
+
+/// Encode and decode capture entries.
+///
+/// `list<u8>`/`list<s8>` lift to typed arrays and BigInts don't survive plain
+/// JSON, so both are tagged on encode and restored on decode.
+const CODEC_JS: &str = r#"({
+  encode: (entry) => JSON.stringify(entry, (_, v) =>
+    typeof v === "bigint" ? { $bigint: v.toString() }
+    : ArrayBuffer.isView(v) ? { $typed: v.constructor.name, values: Array.from(v) }
+    : v),
+  decode: (line) => JSON.parse(line, (_, v) =>
+    v === null || typeof v !== "object" ? v
+    : "$bigint" in v ? BigInt(v.$bigint)
+    : "$typed" in v ? new globalThis[v.$typed](v.values)
+    : v),
+})"#;
+
+enum Mode {
+    Off,
+    Record(String),
+    Replay {
+        path: String,
+        /// Loaded on first use, since the capture only exists at run time.
+        entries: Option<VecDeque<String>>,
+    },
+}
+
+/// Capture mode saved while the log is suspended.
+pub(crate) struct ImportLogSuspended(Mode);
+
+/// The `encode` and `decode` functions of `CODEC_JS`.
+#[derive(Clone)]
+struct Codec {
+    encode: Persistent<Function<'static>>,
+    decode: Persistent<Function<'static>>,
+}
+
+/// Import call capture state stored as rquickjs userdata.
+///
+/// Neither cell is borrowed while JavaScript runs: encoding an entry may call
+/// a `toJSON` that calls an import. Such calls are not logged, as replay never
+/// encodes and so never makes them.
+#[derive(JsLifetime)]
+pub(crate) struct ImportLog {
+    mode: RefCell<Mode>,
+    /// Evaluated on first use.
+    codec: RefCell<Option<Codec>>,
+}
+
+impl Default for ImportLog {
+    fn default() -> Self {
+        Self::from(None)
+    }
+}
+
+impl From<Option<&ImportLogMode>> for ImportLog {
+    fn from(mode: Option<&ImportLogMode>) -> Self {
+        let mode = match mode {
+            None => Mode::Off,
+            Some(ImportLogMode::Record(path)) => Mode::Record(path.clone()),
+            Some(ImportLogMode::Replay(path)) => Mode::Replay {
+                path: path.clone(),
+                entries: None,
+            },
+        };
+        Self {
+            mode: RefCell::new(mode),
+            codec: RefCell::new(None),
+        }
+    }
+}
+
+impl ImportLog {
+    /// Whether sync import calls are recorded or replayed.
+    pub(crate) fn is_active(&self) -> bool {
+        !matches!(*self.mode.borrow(), Mode::Off)
+    }
+
+    pub(crate) fn is_recording(&self) -> bool {
+        matches!(*self.mode.borrow(), Mode::Record(_))
+    }
+
+    /// Turn recording and replay off until [`ImportLog::resume`].
+    pub(crate) fn suspend(&self) -> ImportLogSuspended {
+        ImportLogSuspended(self.mode.replace(Mode::Off))
+    }
+
+    /// Restore the mode saved by [`ImportLog::suspend`].
+    pub(crate) fn resume(&self, suspended: ImportLogSuspended) {
+        *self.mode.borrow_mut() = suspended.0;
+    }
+
+    fn codec(&self, ctx: &Ctx<'_>) -> rquickjs::Result<Codec> {
+        if let Some(codec) = &*self.codec.borrow() {
+            return Ok(codec.clone());
+        }
+        let functions: Object = ctx.eval(CODEC_JS)?;
+        let codec = Codec {
+            encode: Persistent::save(ctx, functions.get::<_, Function>("encode")?),
+            decode: Persistent::save(ctx, functions.get::<_, Function>("decode")?),
+        };
+        self.codec.replace(Some(codec.clone()));
+        Ok(codec)
+    }
+
+    /// Append a call of import `name` to the capture file in record mode.
+    pub(crate) fn record<'js>(
+        &self,
+        ctx: &Ctx<'js>,
+        name: &str,
+        args: &[Value<'js>],
+        ret: Option<&Value<'js>>,
+    ) -> rquickjs::Result<()> {
+        let path = match &*self.mode.borrow() {
+            Mode::Record(path) => path.clone(),
+            _ => return Ok(()),
+        };
+
+        let entry = Object::new(ctx.clone())?;
+        entry.set("import", name)?;
+        entry.set("args", args.to_vec())?;
+        if let Some(ret) = ret {
+            entry.set("ret", ret.clone())?;
+        }
+        let encode = self.codec(ctx)?.encode.restore(ctx)?;
+        let suspended = self.suspend();
+        let line = encode.call::<_, String>((entry,));
+        self.resume(suspended);
+        let line = line?;
+
+        std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .and_then(|mut file| writeln!(file, "{line}"))
+            .map_err(|e| {
+                Exception::throw_message(
+                    ctx,
+                    &format!("failed to record import `{name}` to `{path}`: {e}"),
+                )
+            })
+    }
+
+    /// Take the next captured result in replay mode.
+    ///
+    /// Returns `None` outside replay mode, in which case the import should be
+    /// called as usual. Throws if the capture is exhausted or the next entry
+    /// belongs to a different import.
+    pub(crate) fn replay<'js>(
+        &self,
+        ctx: &Ctx<'js>,
+        name: &str,
+    ) -> rquickjs::Result<Option<Value<'js>>> {
+        let mut mode = self.mode.borrow_mut();
+        let Mode::Replay { path, entries } = &mut *mode else {
+            return Ok(None);
+        };
+        let path = path.clone();
+
+        if entries.is_none() {
+            let text = std::fs::read_to_string(&*path).map_err(|e| {
+                Exception::throw_message(
+                    ctx,
+                    &format!("failed to read import capture `{path}`: {e}"),
+                )
+            })?;
+            *entries = Some(text.lines().map(str::to_string).collect());
+        }
+
+        let Some(line) = entries.as_mut().and_then(VecDeque::pop_front) else {
+            return Err(Exception::throw_message(
+                ctx,
+                &format!("import capture `{path}` has no entry left for `{name}`"),
+            ));
+        };
+        drop(mode);
+        let entry: Object = self.codec(ctx)?.decode.restore(ctx)?.call((line,))?;
+        let recorded: String = entry.get("import")?;
+        if recorded != name {
+            return Err(Exception::throw_message(
+                ctx,
+                &format!("import capture `{path}` expected a call to `{recorded}`, got `{name}`"),
+            ));
+        }
+        entry.get("ret").map(Some)
+    }
+}
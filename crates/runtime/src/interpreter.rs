@@ -2,22 +2,202 @@
 use crate::CtxExt;
 use crate::abi::{CallbackCode, Event};
 use crate::bindings::register;
-use crate::resources::ResourceTable;
+use crate::resources::{ResourceTable, collect_owned_handles};
 use crate::result::ResultBoundary;
 use crate::task::TaskState;
-use crate::trivia::{fn_lookup, iface_lookup};
-use crate::{QjsCallContext, with_ctx};
-use crate::{abi, futures, streams};
+use crate::trivia::{
+    check_iface_collisions, class_lookup, export_fn_name, fn_lookup, get_export_fn, iface_object,
+    precompute_names,
+};
+use crate::{DetHashMap, QjsCallContext, run_pending_jobs, with_ctx};
+use crate::{abi, futures, memory, streams};
 
-use heck::ToUpperCamelCase;
 use rquickjs::function::Constructor;
-use rquickjs::{JsLifetime, Value};
+use rquickjs::{Function, JsLifetime, Object, Persistent, Value};
 use wit_dylib_ffi::{ExportFunction, Interpreter, Resource, Wit};
 
+use std::cell::RefCell;
+
 /// Newtype wrapper for `Wit` so it can be stored as rquickjs userdata.
 #[derive(JsLifetime, Clone, Copy)]
 pub(crate) struct WitData(pub(crate) Wit);
 
+/// JS functions implementing freestanding exports, keyed by interface and
+/// name, so each is looked up on its first call only.
+#[derive(Default)]
+pub(crate) struct ExportFunctions(
+    RefCell<DetHashMap<(Option<&'static str>, &'static str), Persistent<Function<'static>>>>,
+);
+
+// SAFETY: `ExportFunctions` stores only `Persistent<Function<'static>>`s, which
+// are restored only for the runtime that saved them.
+unsafe impl<'js> JsLifetime<'js> for ExportFunctions {
+    type Changed<'to> = ExportFunctions;
+}
+
+/// Resolve the JS function implementing the freestanding export `func`,
+/// caching it for later calls.
+fn export_function<'js>(ctx: &rquickjs::Ctx<'js>, func: ExportFunction) -> Function<'js> {
+    let key = (func.interface(), func.name());
+    let cached = ctx.export_functions().0.borrow().get(&key).cloned();
+    if let Some(cached) = cached {
+        return cached
+            .restore(ctx)
+            .expect("Failed to restore export function");
+    }
+
+    let exports = ctx
+        .user_module()
+        .exports(ctx)
+        .expect("user module exports not found");
+
+    let func_name = export_fn_name(ctx, func.interface(), func.name());
+    let js_func: Function = if let Some(iface) = func.interface() {
+        let iface_obj: rquickjs::Object = iface_object(ctx, &exports, iface)
+            .unwrap_or_else(|e| panic!("interface '{}' not found: {:?}", iface, e));
+        get_export_fn(ctx, &iface_obj, func.interface(), func.name())
+            .unwrap_or_else(|e| panic!("function '{}' not found: {:?}", func_name, e))
+    } else {
+        get_export_fn(ctx, &exports, None, func.name())
+            .unwrap_or_else(|e| panic!("Failed to get function '{}': {:?}", func_name, e))
+    };
+
+    ctx.export_functions()
+        .0
+        .borrow_mut()
+        .insert(key, Persistent::save(ctx, js_func.clone()));
+    js_func
+}
+
+/// Check that the evaluated module implements every WIT export.
+///
+/// Freestanding exports must be functions, and exported resources with a
+/// constructor or static functions must be classes providing them. Methods are
+/// not checked, since a class can define them per instance. All missing exports
+/// are reported at once, with a hint when a same-named global exists (a classic
+/// script that forgot `export`). Fails first if exported interfaces sharing a
+/// short name would be bound to the same object.
+pub(crate) fn check_exports(ctx: &rquickjs::Ctx<'_>) -> Result<(), String> {
+    let exports = ctx
+        .user_module()
+        .exports(ctx)
+        .map_err(|e| format!("user module exports not found: {e}"))?;
+    check_iface_collisions(ctx, &exports)?;
+
+    let mut missing = Vec::new();
+    for func in ctx.wit().iter_export_funcs() {
+        let name = func.name();
+        let target = match func.interface() {
+            Some(iface) => iface_object(ctx, &exports, iface).ok(),
+            None => Some(exports.clone()),
+        };
+        let qualified = match func.interface() {
+            Some(iface) => format!("{iface}#{name}"),
+            None => name.to_string(),
+        };
+
+        let (js_name, found) = if let Some(resource) = name.strip_prefix("[constructor]") {
+            let class_name = class_lookup(ctx, resource);
+            let class = target.and_then(|obj| obj.get::<_, Option<Constructor>>(class_name).ok());
+            (class_name, class.flatten().is_some())
+        } else if let Some(rest) = name.strip_prefix("[static]") {
+            let Some((resource, _)) = rest.split_once('.') else {
+                continue;
+            };
+            let class_name = class_lookup(ctx, resource);
+            let method = target
+                .and_then(|obj| obj.get::<_, Option<Object>>(class_name).ok())
+                .flatten()
+                .and_then(|class| {
+                    get_export_fn::<Option<Function>>(ctx, &class, func.interface(), name).ok()
+                });
+            (class_name, method.flatten().is_some())
+        } else if name.starts_with('[') {
+            continue;
+        } else {
+            let js_name = export_fn_name(ctx, func.interface(), name);
+            let js_func = target.and_then(|obj| {
+                get_export_fn::<Option<Function>>(ctx, &obj, func.interface(), name).ok()
+            });
+            (js_name, js_func.flatten().is_some())
+        };
+        if found {
+            continue;
+        }
+
+        let global = ctx.globals().get::<_, Function>(js_name).is_ok();
+        let hint = if global && func.interface().is_none() {
+            format!(" (found a global `{js_name}`, export it from the module instead)")
+        } else {
+            String::new()
+        };
+        missing.push(format!("\n  `{qualified}`{hint}"));
+    }
+
+    if missing.is_empty() {
+        Ok(())
+    } else {
+        Err(format!(
+            "the JavaScript module does not implement these WIT exports:{}",
+            missing.concat()
+        ))
+    }
+}
+
+/// Report freestanding JS exports whose declared parameter count differs from
+/// their WIT signature.
+///
+/// Exports are always called with exactly the WIT arity, so extra parameters
+/// are `undefined`. `Function.length` stops at the first parameter with a
+/// default or a rest parameter, so exports using those can be reported as
+/// declaring too few.
+pub(crate) fn export_warnings(ctx: &rquickjs::Ctx<'_>) -> Vec<String> {
+    let Ok(exports) = ctx.user_module().exports(ctx) else {
+        return Vec::new();
+    };
+
+    let mut warnings = Vec::new();
+    for func in ctx.wit().iter_export_funcs() {
+        let name = func.name();
+        // Resource constructors, methods, and statics live on classes.
+        if name.starts_with('[') {
+            continue;
+        }
+
+        let (target, qualified) = match func.interface() {
+            Some(iface) => (
+                iface_object(ctx, &exports, iface).ok(),
+                format!("{iface}#{name}"),
+            ),
+            None => (Some(exports.clone()), name.to_string()),
+        };
+        let Some(js_func) = target
+            .and_then(|obj| {
+                get_export_fn::<Option<Function>>(ctx, &obj, func.interface(), name).ok()
+            })
+            .flatten()
+        else {
+            continue;
+        };
+
+        let declared = js_func.get::<_, usize>("length").unwrap_or(0);
+        let expected = func.params().count();
+        let consequence = match declared.cmp(&expected) {
+            std::cmp::Ordering::Greater => "the extra parameters will be `undefined`",
+            std::cmp::Ordering::Less => {
+                "the other arguments are only reachable through parameters with defaults, a \
+                 rest parameter or `arguments`"
+            }
+            std::cmp::Ordering::Equal => continue,
+        };
+        warnings.push(format!(
+            "export `{qualified}` declares {declared} parameters but its WIT signature has \
+             {expected}; {consequence}"
+        ));
+    }
+    warnings
+}
+
 /// quickjs interpreter implementation of the `Interpreter` trait.
 pub struct QjsInterpreter;
 
@@ -34,36 +214,42 @@ impl Interpreter for QjsInterpreter {
                 .expect("Failed to store ResourceClasses userdata");
             ctx.store_userdata(TaskState::new())
                 .expect("Failed to store TaskState userdata");
+            ctx.store_userdata(ExportFunctions::default())
+                .expect("Failed to store ExportFunctions userdata");
             register(ctx, wit).expect("Failed to register WIT bindings");
+            precompute_names(ctx, wit);
         });
     }
 
-    fn export_start<'a>(_wit: Wit, _func: ExportFunction) -> Box<Self::CallCx<'a>> {
-        Box::new(QjsCallContext::default())
+    fn export_start<'a>(_wit: Wit, func: ExportFunction) -> Box<Self::CallCx<'a>> {
+        crate::panic::enter_export(func.interface(), func.name());
+        Box::new(QjsCallContext::scoped().traced(func.interface(), func.name()))
     }
 
     fn export_call(_wit: Wit, func: ExportFunction, cx: &mut Self::CallCx<'_>) {
+        #[cfg(feature = "profile")]
+        let _profile = crate::profile::enter(&func);
+
         let name = func.name();
 
         if let Some(resource_name) = name.strip_prefix("[constructor]") {
             // Resource constructor: call `new ClassName(args...)` and store in table
-            let class_name = resource_name.to_upper_camel_case();
             with_ctx(|ctx| {
+                let class_name = class_lookup(ctx, resource_name);
                 let exports = ctx
                     .user_module()
                     .exports(ctx)
                     .expect("user module exports not found");
 
                 let ctor: Constructor = if let Some(iface) = func.interface() {
-                    let iface_obj: rquickjs::Object = exports
-                        .get(iface_lookup(ctx, iface))
+                    let iface_obj: rquickjs::Object = iface_object(ctx, &exports, iface)
                         .unwrap_or_else(|e| panic!("interface '{}' not found: {:?}", iface, e));
                     iface_obj
-                        .get(class_name.as_str())
+                        .get(class_name)
                         .unwrap_or_else(|e| panic!("class '{}' not found: {:?}", class_name, e))
                 } else {
                     exports
-                        .get(class_name.as_str())
+                        .get(class_name)
                         .unwrap_or_else(|e| panic!("class '{}' not found: {:?}", class_name, e))
                 };
 
@@ -71,114 +257,100 @@ impl Interpreter for QjsInterpreter {
                 let instance: Value = ctor
                     .construct_args(args)
                     .unwrap_or_else(|e| panic!("Failed to construct '{}': {:?}", class_name, e));
+                run_pending_jobs(ctx);
 
-                cx.push_value(ctx, instance);
+                cx.push_value(instance);
             });
         } else if let Some(rest) = name.strip_prefix("[method]") {
             // Resource method: first arg is `self` (resource handle), call method on it
-            let (_resource, method_name) = rest
-                .split_once('.')
-                .unwrap_or_else(|| panic!("invalid method name: {name}"));
+            assert!(rest.contains('.'), "invalid method name: {name}");
 
             with_ctx(|ctx| {
-                let method_name = fn_lookup(ctx, method_name);
+                let method_name = export_fn_name(ctx, func.interface(), name);
                 // First param is the resource (self)
                 let self_val = cx.pop_value(ctx);
                 let self_obj = self_val
                     .as_object()
                     .unwrap_or_else(|| panic!("method receiver is not an object"));
 
-                let method: rquickjs::Function = self_obj
-                    .get(method_name)
-                    .unwrap_or_else(|e| panic!("method '{}' not found: {:?}", method_name, e));
+                let method: rquickjs::Function =
+                    get_export_fn(ctx, self_obj, func.interface(), name)
+                        .unwrap_or_else(|e| panic!("method '{}' not found: {:?}", method_name, e));
 
                 let mut args = cx.stack_into_args(ctx);
                 args.this(self_val).expect("failed to set this");
 
-                let boundary = ResultBoundary::new(func.result());
+                let boundary = ResultBoundary::new(ctx, func.result());
                 let value = boundary
                     .lower_call(ctx, method.call_arg::<Value>(args))
                     .unwrap_or_else(|err| panic!("Failed to call '{}': {:?}", method_name, err));
 
                 if let Some(value) = value {
-                    cx.push_value(ctx, value);
+                    cx.push_value(value);
                 }
             });
         } else if let Some(rest) = name.strip_prefix("[static]") {
             // Static resource method: look up Class on the interface object,
             // then call the static method on the class.
-            let (resource, method_name) = rest
+            let (resource, _) = rest
                 .split_once('.')
                 .unwrap_or_else(|| panic!("invalid static method name: {name}"));
 
             with_ctx(|ctx| {
-                let method_name = fn_lookup(ctx, method_name);
-                let class_name = resource.to_upper_camel_case();
+                let method_name = export_fn_name(ctx, func.interface(), name);
+                let class_name = class_lookup(ctx, resource);
                 let exports = ctx
                     .user_module()
                     .exports(ctx)
                     .expect("user module exports not found");
 
                 let class_obj: rquickjs::Object = if let Some(iface) = func.interface() {
-                    let iface_obj: rquickjs::Object = exports
-                        .get(iface_lookup(ctx, iface))
+                    let iface_obj: rquickjs::Object = iface_object(ctx, &exports, iface)
                         .unwrap_or_else(|e| panic!("interface '{}' not found: {:?}", iface, e));
                     iface_obj
-                        .get(class_name.as_str())
+                        .get(class_name)
                         .unwrap_or_else(|e| panic!("class '{}' not found: {:?}", class_name, e))
                 } else {
                     exports
-                        .get(class_name.as_str())
+                        .get(class_name)
                         .unwrap_or_else(|e| panic!("class '{}' not found: {:?}", class_name, e))
                 };
 
-                let js_func: rquickjs::Function = class_obj.get(method_name).unwrap_or_else(|e| {
-                    panic!("static method '{}' not found: {:?}", method_name, e)
-                });
+                let js_func: rquickjs::Function =
+                    get_export_fn(ctx, &class_obj, func.interface(), name).unwrap_or_else(|e| {
+                        panic!("static method '{}' not found: {:?}", method_name, e)
+                    });
 
                 let args = cx.stack_into_args(ctx);
-                let boundary = ResultBoundary::new(func.result());
+                let boundary = ResultBoundary::new(ctx, func.result());
                 let value = boundary
                     .lower_call(ctx, js_func.call_arg::<Value>(args))
                     .unwrap_or_else(|err| panic!("Failed to call '{}': {:?}", method_name, err));
 
                 if let Some(value) = value {
-                    cx.push_value(ctx, value);
+                    cx.push_value(value);
                 }
             });
         } else {
             // Regular function
             with_ctx(|ctx| {
-                let exports = ctx
-                    .user_module()
-                    .exports(ctx)
-                    .expect("user module exports not found");
-
-                let func_name = fn_lookup(ctx, name);
-                let js_func: rquickjs::Function = if let Some(iface) = func.interface() {
-                    let iface_obj: rquickjs::Object = exports
-                        .get(iface_lookup(ctx, iface))
-                        .unwrap_or_else(|e| panic!("interface '{}' not found: {:?}", iface, e));
-                    iface_obj
-                        .get(func_name)
-                        .unwrap_or_else(|e| panic!("function '{}' not found: {:?}", func_name, e))
-                } else {
-                    exports.get(func_name).unwrap_or_else(|e| {
-                        panic!("Failed to get function '{}': {:?}", func_name, e)
-                    })
-                };
-
+                let js_func = export_function(ctx, func);
                 let args = cx.stack_into_args(ctx);
-                let boundary = ResultBoundary::new(func.result());
+                let boundary = ResultBoundary::new(ctx, func.result());
                 let value = boundary
                     .lower_call(ctx, js_func.call_arg::<Value>(args))
                     .unwrap_or_else(|err| panic!("Failed to call '{}': {:?}", func.name(), err));
 
                 if let Some(value) = value {
-                    cx.push_value(ctx, value);
+                    cx.push_value(value);
                 }
             });
         }
+
+        with_ctx(|ctx| {
+            collect_owned_handles(ctx);
+            memory::tick(ctx);
+        });
     }
 
     fn export_async_start(
@@ -186,6 +358,9 @@ impl Interpreter for QjsInterpreter {
         func: ExportFunction,
         mut cx: Box<Self::CallCx<'static>>,
     ) -> u32 {
+        #[cfg(feature = "profile")]
+        let _profile = crate::profile::enter(&func);
+
         with_ctx(|ctx| {
             ctx.task().init();
 
@@ -198,7 +373,7 @@ impl Interpreter for QjsInterpreter {
                 .expect("__cqjs.asyncExports not found");
 
             let wrapper_obj = if let Some(interface) = func.interface() {
-                async_exports.get(iface_lookup(ctx, interface)).unwrap()
+                async_exports.get(interface).unwrap()
             } else {
                 async_exports
             };
@@ -213,6 +388,8 @@ impl Interpreter for QjsInterpreter {
             let _result = js_func
                 .call_arg::<Value>(args)
                 .unwrap_or_else(|e| panic!("Failed to call async '{}': {:?}", func.name(), e));
+            collect_owned_handles(ctx);
+            memory::tick(ctx);
         });
 
         with_ctx(|ctx| ctx.task().poll())
@@ -1,15 +1,29 @@
 mod abi;
+mod arena;
 mod bindings;
+mod budget;
 mod buffer;
 mod call;
+mod env;
+mod error_context;
 mod futures;
+mod import_log;
 mod interpreter;
+mod limits;
+mod memory;
 mod module;
+mod options;
+mod panic;
+mod prime;
+#[cfg(feature = "profile")]
+mod profile;
 mod resources;
 mod result;
 mod streams;
 mod task;
+mod trace;
 mod trivia;
+mod validate;
 mod wit_imports;
 
 use std::cell::{Cell, OnceCell, RefCell};
@@ -22,7 +36,10 @@ use smallvec::SmallVec;
 use task::TaskState;
 use wit_dylib_ffi::Wit;
 
-use crate::interpreter::WitData;
+use crate::arena::ValueArena;
+use crate::import_log::ImportLog;
+use crate::interpreter::{ExportFunctions, WitData};
+use crate::options::RuntimeOptions;
 use crate::resources::BorrowedResource;
 use crate::resources::ResourceClasses;
 use crate::resources::ResourceTable;
@@ -63,7 +80,8 @@ struct JsState {
     context: Context,
     /// Ensures the JavaScript source is only evaluated once during initialization.
     evaluated: AtomicBool,
-    /// Cached active context pointer for re-entrant `with_ctx` calls.
+    /// Context of the `Context::with()` frame or `CallScope` in progress, for
+    /// re-entrant `with_ctx` calls.
     ctx_ptr: Cell<Option<*const ()>>,
 }
 
@@ -88,8 +106,17 @@ pub(crate) trait CtxExt<'js> {
     /// Retrieve the evaluated user ES module state.
     fn user_module(&self) -> UserDataGuard<'_, module::UserModule>;
 
+    /// Retrieve the cache of resolved export functions.
+    fn export_functions(&self) -> UserDataGuard<'_, ExportFunctions>;
+
     /// Retrieve transient WIT import module declaration state.
     fn wit_import_declarations(&self) -> UserDataGuard<'_, module::WitImportDeclarations>;
+
+    /// Retrieve the runtime options configured at componentize time.
+    fn options(&self) -> UserDataGuard<'_, RuntimeOptions>;
+
+    /// Retrieve the import call record/replay state.
+    fn import_log(&self) -> UserDataGuard<'_, ImportLog>;
 }
 
 impl<'js> CtxExt<'js> for rquickjs::Ctx<'js> {
@@ -117,9 +144,21 @@ impl<'js> CtxExt<'js> for rquickjs::Ctx<'js> {
         self.userdata().expect("UserModule not stored")
     }
 
+    fn export_functions(&self) -> UserDataGuard<'_, ExportFunctions> {
+        self.userdata().expect("ExportFunctions not initialized")
+    }
+
     fn wit_import_declarations(&self) -> UserDataGuard<'_, module::WitImportDeclarations> {
         self.userdata().expect("WitImportDeclarations not stored")
     }
+
+    fn options(&self) -> UserDataGuard<'_, RuntimeOptions> {
+        self.userdata().expect("RuntimeOptions not stored")
+    }
+
+    fn import_log(&self) -> UserDataGuard<'_, ImportLog> {
+        self.userdata().expect("ImportLog not stored")
+    }
 }
 
 impl JsState {
@@ -127,11 +166,22 @@ impl JsState {
         JS_STATE.0.get_or_init(|| {
             let runtime = Runtime::new().expect("Failed to create quikcjs runtime");
             module::install_loader(&runtime);
+            // QuickJS has a single interrupt handler, shared by the profiler
+            // and the execution budget.
+            runtime.set_interrupt_handler(Some(Box::new(|| {
+                #[cfg(feature = "profile")]
+                profile::tick();
+                budget::tick()
+            })));
             let context = Context::full(&runtime).expect("Failed to create quickjs context");
 
             context.with(|ctx| {
                 ctx.store_userdata(FnNameCache::default())
                     .expect("Failed to store function name cache");
+                ctx.store_userdata(RuntimeOptions::default())
+                    .expect("Failed to store runtime options");
+                ctx.store_userdata(ImportLog::default())
+                    .expect("Failed to store import log");
                 module::init_state(&ctx);
             });
 
@@ -143,6 +193,39 @@ impl JsState {
         })
     }
 
+    /// Enter the context until the returned scope drops, so every `with_ctx`
+    /// in between reuses it. Returns `None` if a context is already active.
+    ///
+    /// wit-dylib lifts the arguments, calls the export and lowers its result
+    /// through separate FFI calls, so no single `Context::with()` frame can
+    /// span them. The scope stands in for one instead, which holds because:
+    ///
+    /// - the scope owns a `Ctx`, a counted reference to the context, which
+    ///   itself lives in `JS_STATE` for the rest of the program;
+    /// - the runtime lock only guards against other threads and re-entrant
+    ///   `Context::with()` calls. Wasm runs on a single thread, and while
+    ///   `ctx_ptr` is set every use of the runtime goes through `with_ctx` or
+    ///   `with_active_ctx`, which reuse the scope's `Ctx` instead of locking;
+    /// - the scope belongs to the call context of a synchronous export, which
+    ///   wit-dylib drops before the next export call starts, and that call
+    ///   context releases its values before the scope closes.
+    fn enter_scope(&self) -> Option<CallScope> {
+        if self.ctx_ptr.get().is_some() {
+            return None;
+        }
+        // Entering once also records the stack top QuickJS measures stack
+        // overflows from, as a `Context::with()` per call would.
+        let ctx = self.context.with(|ctx| {
+            // SAFETY: the clone holds its own reference to the context, which
+            // lives in `JS_STATE` for the rest of the program.
+            unsafe { std::mem::transmute::<rquickjs::Ctx<'_>, rquickjs::Ctx<'static>>(ctx.clone()) }
+        });
+        let ctx = Box::new(ctx);
+        self.ctx_ptr.set(Some(std::ptr::from_ref(&*ctx).cast()));
+        budget::reset();
+        Some(CallScope { _ctx: ctx })
+    }
+
     /// Re-uses the active context if already inside `Context::with()` to avoid deadlock.
     ///
     /// This is needed for re-entrant flows such as export → host import callback → JS conversions.
@@ -152,12 +235,16 @@ impl JsState {
         R: 'static,
     {
         if let Some(ptr) = self.ctx_ptr.get() {
-            // SAFETY: ptr is only set while a `Context::with()` frame is active.
+            // SAFETY: `ctx_ptr` points at a live `Ctx`, owned either by the
+            // `Context::with()` frame below, which restores it before
+            // returning, or by the open `CallScope`, which clears it when
+            // dropped. See `enter_scope` for why no lock is needed.
             let ctx = unsafe { &*(ptr as *const rquickjs::Ctx<'_>) };
             return f(ctx);
         }
 
         self.context.with(|ctx| {
+            budget::reset();
             let prev = self.ctx_ptr.replace(Some(std::ptr::from_ref(&ctx).cast()));
             let result = f(&ctx);
             self.ctx_ptr.set(prev);
@@ -166,17 +253,43 @@ impl JsState {
     }
 }
 
+/// A context entered by `JsState::enter_scope` for the length of one call.
+pub(crate) struct CallScope {
+    _ctx: Box<rquickjs::Ctx<'static>>,
+}
+
+impl Drop for CallScope {
+    fn drop(&mut self) {
+        JsState::get_or_init().ctx_ptr.set(None);
+    }
+}
+
 // Implements the init interface for wit-bindgen
 struct InitImpl;
 
 impl init::Guest for InitImpl {
     fn init(
         shim: String,
-        js: String,
+        script: init::Script,
         entry_path: Option<String>,
-        disable_gc: bool,
-    ) -> Result<(), String> {
-        init_js(&shim, &js, entry_path.as_deref(), disable_gc)
+        options: init::InitOptions,
+    ) -> Result<Vec<String>, String> {
+        init_js(&shim, script, entry_path.as_deref(), &options)
+    }
+
+    fn compile(
+        script: String,
+        entry_path: Option<String>,
+        strip_source: bool,
+    ) -> Result<Vec<u8>, String> {
+        module::compile(&script, entry_path.as_deref(), strip_source)
+    }
+
+    fn quickjs_version() -> String {
+        // SAFETY: `JS_GetVersion` returns a pointer to a static, NUL-terminated
+        // string owned by QuickJS that lives for the whole program.
+        let version = unsafe { std::ffi::CStr::from_ptr(rquickjs::qjs::JS_GetVersion()) };
+        version.to_string_lossy().into_owned()
     }
 }
 
@@ -184,49 +297,73 @@ impl init::Guest for InitImpl {
 #[derive(Default)]
 pub struct QjsCallContext {
     /// Value stack for WIT to JS: arguments in, result out
-    stack: Vec<Persistent<Value<'static>>>,
+    stack: ValueArena,
     /// Tracks current index per nested list iteration
     iter_stack: SmallVec<[usize; 4]>,
+    /// Elements of lists being lifted, appended in one go once complete
+    list_batches: SmallVec<[ListBatch; 2]>,
     /// Keeps borrowed `&str` returns alive across FFI boundaries
     temp_strings: SmallVec<[String; 4]>,
     /// Raw allocations to free when this context is dropped
     deferred_deallocs: SmallVec<[(*mut u8, std::alloc::Layout); 4]>,
     /// Imported resource borrows to drop when this context is dropped
     borrows: SmallVec<[BorrowedResource; 4]>,
+    /// Error-contexts created while lowering, dropped with this context
+    error_contexts: SmallVec<[u32; 2]>,
+    /// The function values are traced under, when tracing is on
+    traced: Option<Box<str>>,
+    /// Keeps the JS context entered while this call context lives; dropped
+    /// last, after `drop` released the values
+    scope: Option<CallScope>,
+}
+
+/// Elements buffered for a lifted list until all of them have arrived.
+struct ListBatch {
+    /// Stack height with the list's array on top
+    depth: usize,
+    /// Number of elements still to come
+    remaining: usize,
+    elems: ValueArena,
 }
 
 impl QjsCallContext {
-    pub(crate) fn push_value<'js>(&mut self, ctx: &rquickjs::Ctx<'js>, val: Value<'js>) {
-        self.stack.push(Persistent::save(ctx, val));
+    /// A call context for a synchronous export, which enters the JS context
+    /// once for the whole call instead of once per lifted or lowered value.
+    pub(crate) fn scoped() -> Self {
+        Self {
+            scope: JsState::get_or_init().enter_scope(),
+            ..Self::default()
+        }
     }
 
-    pub(crate) fn pop_value<'js>(&mut self, ctx: &rquickjs::Ctx<'js>) -> Value<'js> {
-        self.pop_persistent().restore(ctx).expect("stack underflow")
+    /// Trace the values of this call under the function `name` of `iface`.
+    pub(crate) fn traced(mut self, iface: Option<&str>, name: &str) -> Self {
+        self.traced = trace::function(iface, name);
+        self
     }
 
-    pub(crate) fn pop_persistent(&mut self) -> Persistent<Value<'static>> {
-        self.stack.pop().expect("stack underflow")
+    pub(crate) fn push_value(&mut self, val: Value<'_>) {
+        self.stack.push(val);
     }
 
-    pub(crate) fn maybe_pop_persistent(&mut self) -> Option<Persistent<Value<'static>>> {
-        self.stack.pop()
+    pub(crate) fn pop_value<'js>(&mut self, ctx: &rquickjs::Ctx<'js>) -> Value<'js> {
+        self.stack.pop(ctx).expect("stack underflow")
+    }
+
+    /// Pop a value to keep beyond this call context.
+    pub(crate) fn pop_persistent(&mut self, ctx: &rquickjs::Ctx<'_>) -> Persistent<Value<'static>> {
+        Persistent::save(ctx, self.pop_value(ctx))
     }
 
-    pub(crate) fn maybe_pop_value<'js>(
-        &mut self,
-        ctx: &rquickjs::Ctx<'js>,
-    ) -> rquickjs::Result<Option<Value<'js>>> {
-        self.maybe_pop_persistent()
-            .map(|persistent| persistent.restore(ctx))
-            .transpose()
+    pub(crate) fn maybe_pop_value<'js>(&mut self, ctx: &rquickjs::Ctx<'js>) -> Option<Value<'js>> {
+        self.stack.pop(ctx)
     }
 
     pub(crate) fn stack_into_args<'js>(&mut self, ctx: &rquickjs::Ctx<'js>) -> function::Args<'js> {
-        let mut args = function::Args::new(ctx.clone(), self.stack.len());
-        for p in self.stack.drain(..) {
-            p.restore(ctx)
-                .and_then(|val| args.push_arg(val))
-                .expect("Failed to restore arg");
+        let len = self.stack.len();
+        let mut args = function::Args::new(ctx.clone(), len);
+        for val in self.stack.drain_last(ctx, len) {
+            args.push_arg(val).expect("Failed to push arg");
         }
         args
     }
@@ -234,6 +371,16 @@ impl QjsCallContext {
 
 impl Drop for QjsCallContext {
     fn drop(&mut self) {
+        // Call contexts of imports and async calls can outlive the
+        // `with_ctx` that filled them, so values are released in a new one.
+        if !self.stack.is_empty() || !self.list_batches.is_empty() {
+            with_ctx(|ctx| {
+                self.stack.clear(ctx);
+                for mut batch in self.list_batches.drain(..) {
+                    batch.elems.clear(ctx);
+                }
+            });
+        }
         for (ptr, layout) in self.deferred_deallocs.drain(..) {
             unsafe {
                 std::alloc::dealloc(ptr, layout);
@@ -244,46 +391,145 @@ impl Drop for QjsCallContext {
                 (borrow.drop_fn)(borrow.handle);
             }
         }
+        for handle in self.error_contexts.drain(..) {
+            unsafe { abi::error_context_drop(handle) };
+        }
     }
 }
 
-/// Cache for converting WIT function/interface names to camelCase, stored as
-/// rquickjs userdata so it is tied to the JS runtime lifetime.
+/// Cache for converting WIT names to camelCase, stored as rquickjs userdata so
+/// it is tied to the JS runtime lifetime.
+///
+/// Filled for the whole world at initialization, so it is part of the Wizer
+/// snapshot and calls only look names up.
 #[derive(Default, JsLifetime)]
-pub(crate) struct FnNameCache(RefCell<DetHashMap<&'static str, &'static str>>);
+pub(crate) struct FnNameCache {
+    /// Function, interface, field and flag names in lower camel case
+    names: RefCell<DetHashMap<&'static str, &'static str>>,
+    /// Resource names in upper camel case, as their classes are named
+    classes: RefCell<DetHashMap<&'static str, &'static str>>,
+}
 
-/// Initialize the quickjs runtime with JavaScript source code.
+/// Initialize the quickjs runtime with the user module, as JavaScript source
+/// or bytecode.
 /// This is called by Wizer during pre-initialization.
+///
+/// Returns non-fatal warnings about the evaluated module.
 fn init_js(
     shim: &str,
-    js_source: &str,
+    script: init::Script,
     entry_path: Option<&str>,
-    disable_gc: bool,
-) -> Result<(), String> {
+    options: &init::InitOptions,
+) -> Result<Vec<String>, String> {
     let state = JsState::get_or_init();
 
     if state.evaluated.swap(true, Ordering::SeqCst) {
         return Err("JavaScript already evaluated".to_string());
     }
+    panic::install();
+
+    state.with_ctx(|ctx| {
+        if options.trace_values {
+            trace::enable();
+        }
+        ctx.store_userdata(RuntimeOptions::from(options))
+            .map(|_| ())
+            .map_err(|_| "Failed to store runtime options".to_string())?;
+        ctx.store_userdata(ImportLog::from(options.import_log.as_ref()))
+            .map(|_| ())
+            .map_err(|_| "Failed to store import log".to_string())?;
+        trivia::check_renames(ctx)?;
+        bindings::apply_import_naming(ctx)
+            .map_err(|e| format!("Failed to apply the naming policy: {e}"))?;
+        env::install(ctx, options)
+    })?;
+
+    if options.strip_source {
+        state.with_ctx(|ctx| unsafe {
+            let rt = rquickjs::qjs::JS_GetRuntime(ctx.as_raw().as_ptr());
+            rquickjs::qjs::JS_SetStripInfo(rt, rquickjs::qjs::JS_STRIP_SOURCE as _);
+        });
+    }
 
-    if disable_gc {
+    let gc_threshold = if options.disable_gc {
+        Some(usize::MAX)
+    } else {
+        options.gc_threshold.map(|n| n as usize)
+    };
+    if let Some(threshold) = gc_threshold {
         state.with_ctx(|ctx| unsafe {
             let rt = rquickjs::qjs::JS_GetRuntime(ctx.as_raw().as_ptr());
-            rquickjs::qjs::JS_SetGCThreshold(rt, usize::MAX as _);
+            rquickjs::qjs::JS_SetGCThreshold(rt, threshold as _);
         });
     }
 
     state.with_ctx(|ctx| {
         module::evaluate_shim(ctx, shim)?;
-        module::evaluate_user(ctx, js_source, entry_path)
+        module::evaluate_preludes(ctx, &options.preludes)?;
+        env::record_globals(ctx)?;
+        if options.lazy_eval {
+            // Evaluated and locked down on the first export call instead.
+            return module::defer_user(ctx, script, entry_path);
+        }
+        module::evaluate_user(ctx, script, entry_path)?;
+        prime::run_init_hook(ctx)?;
+        env::lockdown(ctx)
     })?;
 
+    let mut warnings = Vec::new();
+    if !options.lazy_eval {
+        state.with_ctx(interpreter::check_exports)?;
+        let mismatches = state.with_ctx(interpreter::export_warnings);
+        match options.arity_mismatch {
+            init::ArityPolicy::Ignore => {}
+            init::ArityPolicy::Warn => warnings = mismatches,
+            init::ArityPolicy::Deny if mismatches.is_empty() => {}
+            init::ArityPolicy::Deny => return Err(mismatches.join("\n")),
+        }
+    }
+    warnings.extend(state.with_ctx(|ctx| prime::prime_exports(ctx, &options.prime))?);
+
+    // Applied last, so only the calls after the snapshot are limited.
+    if let Some(limit) = options.memory_limit {
+        state.with_ctx(|ctx| {
+            let usage = memory::usage(ctx);
+            if usage.malloc_size > i64::from(limit) {
+                return Err(format!(
+                    "the memory limit of {limit} bytes is below the {} bytes the heap \
+                     already uses after initialization",
+                    usage.malloc_size
+                ));
+            }
+            unsafe {
+                let rt = rquickjs::qjs::JS_GetRuntime(ctx.as_raw().as_ptr());
+                rquickjs::qjs::JS_SetMemoryLimit(rt, limit as _);
+            }
+            Ok(())
+        })?;
+    }
+    if let Some(max_instructions) = options.max_instructions {
+        budget::enable(max_instructions);
+    }
+
     unsafe {
         abi::reset_adapter_state();
         abi::__wasilibc_reset_preopens();
     }
 
-    Ok(())
+    Ok(warnings)
+}
+
+/// Run `f` in the context of the `with_ctx` call in progress, without entering
+/// the context if there is none.
+pub(crate) fn with_active_ctx<F, R>(f: F) -> Option<R>
+where
+    F: FnOnce(&rquickjs::Ctx<'_>) -> R,
+{
+    let ptr = JS_STATE.0.get()?.ctx_ptr.get()?;
+    // SAFETY: as in `JsState::with_ctx`, `ctx_ptr` points at the live `Ctx` of
+    // a `Context::with()` frame or `CallScope` in progress.
+    let ctx = unsafe { &*(ptr as *const rquickjs::Ctx<'_>) };
+    Some(f(ctx))
 }
 
 /// Delegates to `JsState::with_ctx`.
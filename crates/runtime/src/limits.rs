@@ -0,0 +1,178 @@
+//! Optional length limits on lists and strings lowered from JavaScript.
+//!
+//! Lowering copies a JS value into guest memory in one go, so a runaway list
+//! can exhaust linear memory before anything notices. With a limit set, values
+//! are checked against their WIT type before lowering and rejected with a
+//! `RangeError` that JavaScript can catch.
+
+use rquickjs::{Ctx, Exception, Result, Value};
+use wit_dylib_ffi::Type;
+
+use crate::CtxExt;
+use crate::call::{option_is_nested, set_values};
+use crate::trivia::fn_lookup;
+
+/// Check `values` about to be lowered as the paired WIT types, throwing a
+/// `RangeError` about `what()` if a list or string exceeds the configured
+/// limit.
+pub(crate) fn check<'a, 'js: 'a>(
+    ctx: &Ctx<'js>,
+    what: impl FnOnce() -> String,
+    values: impl IntoIterator<Item = (&'a Value<'js>, Type)>,
+) -> Result<()> {
+    let Some(max) = ctx.options().max_list_length else {
+        return Ok(());
+    };
+
+    for (value, ty) in values {
+        if let Some(len) = oversized(ctx, value, ty, max)? {
+            return Err(Exception::throw_range(
+                ctx,
+                &format!(
+                    "{}: length {len} exceeds the maximum list length of {max}",
+                    what()
+                ),
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// The length of the first list (in elements) or string (in UTF-8 bytes)
+/// within `value` that is longer than `max`.
+///
+/// Values of the wrong shape are skipped; lowering reports those.
+fn oversized<'js>(
+    ctx: &Ctx<'js>,
+    value: &Value<'js>,
+    ty: Type,
+    max: usize,
+) -> Result<Option<usize>> {
+    match ty {
+        Type::String => {
+            let len = match value.as_string() {
+                Some(s) => s.to_string()?.len(),
+                None => 0,
+            };
+            Ok((len > max).then_some(len))
+        }
+        Type::List(list) => {
+            let Some(obj) = value.as_object() else {
+                return Ok(None);
+            };
+            if !value.is_array()
+                && let Some(values) = set_values(ctx, value)?
+            {
+                return oversized(ctx, &values.into_value(), ty, max);
+            }
+            let len: usize = obj.get("length").unwrap_or(0);
+            if len > max {
+                return Ok(Some(len));
+            }
+            if is_scalar(list.ty()) {
+                return Ok(None);
+            }
+            for index in 0..len {
+                let elem: Value = obj.get(index as u32)?;
+                if let Some(len) = oversized(ctx, &elem, list.ty(), max)? {
+                    return Ok(Some(len));
+                }
+            }
+            Ok(None)
+        }
+        Type::Record(record) => {
+            let Some(obj) = value.as_object() else {
+                return Ok(None);
+            };
+            for (name, field_ty) in record.fields() {
+                let field: Value = obj.get(fn_lookup(ctx, name))?;
+                if let Some(len) = oversized(ctx, &field, field_ty, max)? {
+                    return Ok(Some(len));
+                }
+            }
+            Ok(None)
+        }
+        Type::Tuple(tuple) => {
+            let Some(obj) = value.as_object() else {
+                return Ok(None);
+            };
+            for (index, elem_ty) in tuple.types().enumerate() {
+                let elem: Value = obj.get(index as u32)?;
+                if let Some(len) = oversized(ctx, &elem, elem_ty, max)? {
+                    return Ok(Some(len));
+                }
+            }
+            Ok(None)
+        }
+        Type::Option(option) => {
+            if value.is_null() || value.is_undefined() {
+                return Ok(None);
+            }
+            if !option_is_nested(option) {
+                return oversized(ctx, value, option.ty(), max);
+            }
+            match tagged(value, |tag| (tag == "some").then(|| option.ty()))? {
+                Some((payload, ty)) => oversized(ctx, &payload, ty, max),
+                None => Ok(None),
+            }
+        }
+        Type::Result(result) => {
+            let payload = tagged(value, |tag| match tag {
+                "ok" => result.ok(),
+                "err" => result.err(),
+                _ => None,
+            })?;
+            match payload {
+                Some((payload, ty)) => oversized(ctx, &payload, ty, max),
+                None => Ok(None),
+            }
+        }
+        Type::Variant(variant) => {
+            let payload = tagged(value, |tag| {
+                variant
+                    .cases()
+                    .find(|(name, _)| *name == tag)
+                    .and_then(|(_, ty)| ty)
+            })?;
+            match payload {
+                Some((payload, ty)) => oversized(ctx, &payload, ty, max),
+                None => Ok(None),
+            }
+        }
+        Type::Alias(alias) => oversized(ctx, value, alias.ty(), max),
+        _ => Ok(None),
+    }
+}
+
+/// The `val` of a `{ tag, val }` object and its type, if `case_ty` maps the
+/// tag to a payload type.
+fn tagged<'js>(
+    value: &Value<'js>,
+    case_ty: impl FnOnce(&str) -> Option<Type>,
+) -> Result<Option<(Value<'js>, Type)>> {
+    let Some(obj) = value.as_object() else {
+        return Ok(None);
+    };
+    let Ok(tag) = obj.get::<_, String>("tag") else {
+        return Ok(None);
+    };
+    match case_ty(&tag) {
+        Some(ty) => Ok(Some((obj.get("val")?, ty))),
+        None => Ok(None),
+    }
+}
+
+/// Whether values of `ty` can never contain a list or string.
+fn is_scalar(ty: Type) -> bool {
+    !matches!(
+        ty,
+        Type::String
+            | Type::List(_)
+            | Type::Record(_)
+            | Type::Tuple(_)
+            | Type::Option(_)
+            | Type::Result(_)
+            | Type::Variant(_)
+            | Type::Alias(_)
+    )
+}
@@ -0,0 +1,59 @@
+//! QuickJS heap statistics, read through `__cqjs.getMemoryUsage()` or printed
+//! to stderr after every `memory_report` export calls.
+//!
+//! QuickJS doesn't track its peak heap size, and computing the statistics walks
+//! the whole heap, so the peak is the largest `malloc_size` seen by a sample:
+//! a report, a `getMemoryUsage()` call or the `memory_limit` check at the end
+//! of initialization.
+
+use std::cell::Cell;
+
+use rquickjs::Ctx;
+use rquickjs::qjs::JSMemoryUsage;
+
+use crate::CtxExt;
+
+thread_local! {
+    static PEAK: Cell<i64> = const { Cell::new(0) };
+    static CALLS: Cell<u64> = const { Cell::new(0) };
+}
+
+/// Compute the current heap statistics, recording them towards the peak.
+pub(crate) fn usage(ctx: &Ctx<'_>) -> JSMemoryUsage {
+    let usage = unsafe {
+        let rt = rquickjs::qjs::JS_GetRuntime(ctx.as_raw().as_ptr());
+        let mut usage = std::mem::MaybeUninit::uninit();
+        rquickjs::qjs::JS_ComputeMemoryUsage(rt, usage.as_mut_ptr());
+        usage.assume_init()
+    };
+    PEAK.set(PEAK.get().max(usage.malloc_size));
+    usage
+}
+
+/// The largest heap size sampled so far, in bytes.
+pub(crate) fn peak() -> i64 {
+    PEAK.get()
+}
+
+/// Count an export call and print a report every `memory_report` calls.
+pub(crate) fn tick(ctx: &Ctx<'_>) {
+    let Some(interval) = ctx.options().memory_report else {
+        return;
+    };
+    let calls = CALLS.get() + 1;
+    CALLS.set(calls);
+    if calls % u64::from(interval) != 0 {
+        return;
+    }
+    let usage = usage(ctx);
+    eprintln!(
+        "[memory] after {calls} calls: heap {} bytes in {} allocations ({} used), \
+         {} objects, {} strings, peak {} bytes",
+        usage.malloc_size,
+        usage.malloc_count,
+        usage.memory_used_size,
+        usage.obj_count,
+        usage.str_count,
+        peak()
+    );
+}
@@ -0,0 +1,109 @@
+//! Compiling the user module to QuickJS bytecode, and loading it back.
+//!
+//! Bytecode is not linked when it is read, so the user module is not loaded
+//! directly: a small entry module imports it under [`SPECIFIER`], which makes
+//! QuickJS load it through [`BytecodeLoader`] and resolve its imports like
+//! those of any module it declares.
+
+use rquickjs::loader::{ImportAttributes, Loader, Resolver};
+use rquickjs::module::{Declared, WriteOptions};
+use rquickjs::{CaughtError, Context, Ctx, Error, Module, Runtime};
+
+use crate::CtxExt;
+
+/// Specifier the entry module imports the user module's bytecode under.
+const SPECIFIER: &str = "componentize-qjs:bytecode";
+
+/// Source of the module that imports the user module's bytecode and exports
+/// its namespace as `default`.
+pub(super) const ENTRY: &str =
+    "import * as user from \"componentize-qjs:bytecode\"; export default user;";
+
+/// Compile `source` to bytecode, as the module `name`.
+///
+/// A throwaway runtime compiles it, resolving every import to an empty
+/// module: the bytecode only names its imports, which are resolved again when
+/// it is loaded.
+pub(super) fn compile(name: &str, source: &str, strip_source: bool) -> Result<Vec<u8>, String> {
+    let runtime = Runtime::new().map_err(|e| format!("Failed to create a QuickJS runtime: {e}"))?;
+    runtime.set_loader(Unlinked, Unlinked);
+    let context =
+        Context::full(&runtime).map_err(|e| format!("Failed to create a QuickJS context: {e}"))?;
+
+    context.with(|ctx| {
+        if strip_source {
+            unsafe {
+                let rt = rquickjs::qjs::JS_GetRuntime(ctx.as_raw().as_ptr());
+                rquickjs::qjs::JS_SetStripInfo(rt, rquickjs::qjs::JS_STRIP_SOURCE as _);
+            }
+        }
+        let module = CaughtError::catch(&ctx, Module::declare(ctx.clone(), name, source))
+            .map_err(|e| format!("Failed to compile user JavaScript module: {e}"))?;
+        CaughtError::catch(&ctx, module.write(WriteOptions::default()))
+            .map_err(|e| format!("Failed to write QuickJS bytecode: {e}"))
+    })
+}
+
+/// Resolves every import to its specifier and loads it as an empty module.
+struct Unlinked;
+
+impl Resolver for Unlinked {
+    fn resolve<'js>(
+        &mut self,
+        _ctx: &Ctx<'js>,
+        _base: &str,
+        name: &str,
+        _attributes: Option<ImportAttributes<'js>>,
+    ) -> rquickjs::Result<String> {
+        Ok(name.to_string())
+    }
+}
+
+impl Loader for Unlinked {
+    fn load<'js>(
+        &mut self,
+        ctx: &Ctx<'js>,
+        name: &str,
+        _attributes: Option<ImportAttributes<'js>>,
+    ) -> rquickjs::Result<Module<'js, Declared>> {
+        Module::declare(ctx.clone(), name, "")
+    }
+}
+
+pub(super) struct BytecodeResolver;
+
+impl Resolver for BytecodeResolver {
+    fn resolve<'js>(
+        &mut self,
+        ctx: &Ctx<'js>,
+        base: &str,
+        name: &str,
+        _attributes: Option<ImportAttributes<'js>>,
+    ) -> rquickjs::Result<String> {
+        if name == SPECIFIER
+            && let Some(name) = ctx.user_module().bytecode_name()
+        {
+            return Ok(name);
+        }
+        Err(Error::new_resolving(base, name))
+    }
+}
+
+pub(super) struct BytecodeLoader;
+
+impl Loader for BytecodeLoader {
+    fn load<'js>(
+        &mut self,
+        ctx: &Ctx<'js>,
+        name: &str,
+        _attributes: Option<ImportAttributes<'js>>,
+    ) -> rquickjs::Result<Module<'js, Declared>> {
+        let bytecode = ctx
+            .user_module()
+            .take_bytecode(name)
+            .ok_or_else(|| Error::new_loading(name))?;
+        // SAFETY: the bytecode was written by `compile` of this same runtime
+        // build, so it matches the QuickJS version and layout reading it.
+        unsafe { Module::load(ctx.clone(), &bytecode) }
+    }
+}
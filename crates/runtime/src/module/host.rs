@@ -2,6 +2,7 @@ use rquickjs::loader::{ImportAttributes, Loader, Resolver};
 use rquickjs::module::Declared;
 use rquickjs::{Ctx, Error, Module};
 
+use crate::CtxExt;
 use crate::init::local::init::module_loader;
 
 pub(super) struct HostModuleResolver;
@@ -9,11 +10,20 @@ pub(super) struct HostModuleResolver;
 impl Resolver for HostModuleResolver {
     fn resolve<'js>(
         &mut self,
-        _ctx: &Ctx<'js>,
+        ctx: &Ctx<'js>,
         base: &str,
         name: &str,
         _attributes: Option<ImportAttributes<'js>>,
     ) -> rquickjs::Result<String> {
+        if ctx.user_module().is_lazy() {
+            return Err(Error::new_resolving_message(
+                base,
+                name,
+                "JavaScript modules can only be imported from the filesystem during Wizer, \
+                 which lazy evaluation skips"
+                    .to_string(),
+            ));
+        }
         module_loader::resolve(base, name)
             .map_err(|err| Error::new_resolving_message(base, name, err))
     }
@@ -1,19 +1,30 @@
 //! ES module loading and evaluated user module state.
+mod bytecode;
 mod host;
 mod wit;
 
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 
-use rquickjs::{CaughtError, JsLifetime, Module, Persistent, Runtime};
+use rquickjs::module::Declared;
+use rquickjs::{CaughtError, FromJs, Function, JsLifetime, Module, Persistent, Runtime};
 
-use crate::CtxExt;
+use crate::init::Script;
+use crate::{CtxExt, env};
 
 pub(crate) use wit::WitImportDeclarations;
 
 pub(crate) fn install_loader(runtime: &Runtime) {
     runtime.set_loader(
-        (wit::WitModuleResolver, host::HostModuleResolver),
-        (wit::WitModuleLoader, host::HostModuleLoader),
+        (
+            bytecode::BytecodeResolver,
+            wit::WitModuleResolver,
+            host::HostModuleResolver,
+        ),
+        (
+            bytecode::BytecodeLoader,
+            wit::WitModuleLoader,
+            host::HostModuleLoader,
+        ),
     );
 }
 
@@ -26,24 +37,143 @@ pub(crate) fn init_state(ctx: &rquickjs::Ctx<'_>) {
 
 /// Stores the evaluated user ES module namespace as internal runtime state.
 #[derive(Default)]
-pub(crate) struct UserModule(RefCell<Option<Persistent<rquickjs::Object<'static>>>>);
+pub(crate) struct UserModule {
+    namespace: RefCell<Option<Persistent<rquickjs::Object<'static>>>>,
+    /// The declared user module, until it is evaluated on first use
+    deferred: RefCell<Option<Module<'static, Declared>>>,
+    lazy: Cell<bool>,
+    /// The user module's name and bytecode, until the module loader reads it
+    bytecode: RefCell<Option<(String, Vec<u8>)>>,
+    /// Whether the user module was loaded from bytecode, through an entry
+    /// module exporting its namespace as `default`
+    from_bytecode: Cell<bool>,
+    /// The generated shim's namespace, for the hooks it exports: `adapters`,
+    /// default implementations of exported interfaces built from the user
+    /// module's namespace, and `runTimer`, which fires the next timer
+    shim: RefCell<Option<Persistent<rquickjs::Object<'static>>>>,
+}
 
-// SAFETY: `UserModule` stores only a `Persistent<Object<'static>>`, which is
-// tied to the owning QuickJS runtime and restored only for that same runtime.
+// SAFETY: `UserModule` stores only a `Persistent<Object<'static>>` and a
+// declared module, which are tied to the owning QuickJS runtime and restored
+// only for that same runtime.
 unsafe impl<'js> JsLifetime<'js> for UserModule {
     type Changed<'to> = UserModule;
 }
 
 impl UserModule {
     fn store<'js>(&self, ctx: &rquickjs::Ctx<'js>, namespace: rquickjs::Object<'js>) {
-        self.0.replace(Some(Persistent::save(ctx, namespace)));
+        self.namespace
+            .replace(Some(Persistent::save(ctx, namespace)));
+    }
+
+    /// Name of the user module while its bytecode waits to be loaded.
+    fn bytecode_name(&self) -> Option<String> {
+        self.bytecode
+            .borrow()
+            .as_ref()
+            .map(|(name, _)| name.clone())
+    }
+
+    /// The user module's bytecode, if `name` is the user module.
+    fn take_bytecode(&self, name: &str) -> Option<Vec<u8>> {
+        let mut bytecode = self.bytecode.borrow_mut();
+        match bytecode.take() {
+            Some((user, bytes)) if user == name => Some(bytes),
+            other => {
+                *bytecode = other;
+                None
+            }
+        }
+    }
+
+    /// Evaluate the declared user module and return the user module's
+    /// namespace.
+    fn finish<'js>(
+        &self,
+        ctx: &rquickjs::Ctx<'js>,
+        module: Module<'js, Declared>,
+    ) -> Result<rquickjs::Object<'js>, String> {
+        let namespace = finish(ctx, module)?;
+        if !self.from_bytecode.get() {
+            return Ok(namespace);
+        }
+        CaughtError::catch(ctx, namespace.get("default"))
+            .map_err(|e| format!("Failed to read JavaScript module namespace: {e}"))
+    }
+
+    fn defer(&self, module: Module<'_, Declared>) {
+        // SAFETY: the module is owned by the runtime's only context, which
+        // outlives it, and is only used again inside that context.
+        let module = unsafe {
+            std::mem::transmute::<Module<'_, Declared>, Module<'static, Declared>>(module)
+        };
+        self.deferred.replace(Some(module));
+        self.lazy.set(true);
+    }
+
+    /// The object implementing the exported interface `full_name` that the
+    /// shim adapts from the user module's `exports`, if any.
+    pub(crate) fn adapter<'js>(
+        &self,
+        ctx: &rquickjs::Ctx<'js>,
+        exports: &rquickjs::Object<'js>,
+        full_name: &str,
+    ) -> rquickjs::Result<Option<rquickjs::Object<'js>>> {
+        let Some(adapters) = self.shim_export::<rquickjs::Object>(ctx, "adapters")? else {
+            return Ok(None);
+        };
+        match adapters.get::<_, Option<Function>>(full_name)? {
+            Some(adapt) => adapt.call((exports.clone(),)),
+            None => Ok(None),
+        }
     }
 
+    /// The shim's export `name`, if the shim was evaluated and exports it.
+    pub(crate) fn shim_export<'js, V: FromJs<'js>>(
+        &self,
+        ctx: &rquickjs::Ctx<'js>,
+        name: &str,
+    ) -> rquickjs::Result<Option<V>> {
+        let shim = self.shim.borrow().clone();
+        match shim {
+            Some(shim) => shim.restore(ctx)?.get(name),
+            None => Ok(None),
+        }
+    }
+
+    /// Whether evaluation of the user module was deferred past Wizer, when the
+    /// host module loader is no longer available.
+    pub(crate) fn is_lazy(&self) -> bool {
+        self.lazy.get()
+    }
+
+    /// The user module's namespace, evaluating the module first if it was
+    /// deferred.
     pub(crate) fn exports<'js>(
         &self,
         ctx: &rquickjs::Ctx<'js>,
     ) -> rquickjs::Result<rquickjs::Object<'js>> {
-        let namespace = self.0.borrow().as_ref().cloned().ok_or_else(|| {
+        let deferred = self.deferred.take();
+        if let Some(module) = deferred {
+            // SAFETY: see `defer`.
+            let module = unsafe {
+                std::mem::transmute::<Module<'static, Declared>, Module<'js, Declared>>(module)
+            };
+            self.finish(ctx, module)
+                .and_then(|namespace| {
+                    self.store(ctx, namespace);
+                    env::lockdown(ctx)
+                })
+                .map_err(|e| {
+                    rquickjs::Error::new_from_js_message(
+                        "undefined",
+                        "module namespace",
+                        format!("Failed to evaluate user JavaScript module: {e}"),
+                    )
+                })?;
+        }
+
+        let namespace = self.namespace.borrow().as_ref().cloned().ok_or_else(|| {
             rquickjs::Error::new_from_js_message(
                 "undefined",
                 "module namespace",
@@ -56,35 +186,112 @@ impl UserModule {
 }
 
 pub(crate) fn evaluate_shim(ctx: &rquickjs::Ctx<'_>, shim: &str) -> Result<(), String> {
-    evaluate(ctx, "componentize-qjs:shim.js", shim)
-        .map(|_| ())
-        .map_err(|e| format!("Failed to evaluate generated shim module: {e}"))
+    let namespace = evaluate(ctx, "componentize-qjs:shim.js", shim)
+        .map_err(|e| format!("Failed to evaluate generated shim module: {e}"))?;
+    ctx.user_module()
+        .shim
+        .replace(Some(Persistent::save(ctx, namespace)));
+    Ok(())
+}
+
+/// Evaluate the prelude scripts in order. They run after the shim, so they
+/// can already use `wit` and the globals it defines.
+pub(crate) fn evaluate_preludes(
+    ctx: &rquickjs::Ctx<'_>,
+    preludes: &[(String, String)],
+) -> Result<(), String> {
+    for (name, source) in preludes {
+        CaughtError::catch(ctx, ctx.eval::<(), _>(source.as_str()))
+            .map_err(|e| format!("Failed to evaluate prelude `{name}`: {e}"))?;
+    }
+    Ok(())
 }
 
 pub(crate) fn evaluate_user(
     ctx: &rquickjs::Ctx<'_>,
-    js_source: &str,
+    script: Script,
     entry_path: Option<&str>,
 ) -> Result<(), String> {
-    let namespace = evaluate(
-        ctx,
-        entry_path.unwrap_or("componentize-qjs:user.js"),
-        js_source,
-    )
-    .map_err(|e| format!("Failed to evaluate user JavaScript module: {e}"))?;
+    let module = declare_user(ctx, script, entry_path)
+        .and_then(|module| ctx.user_module().finish(ctx, module))
+        .map_err(|e| format!("Failed to evaluate user JavaScript module: {e}"))?;
 
-    ctx.user_module().store(ctx, namespace);
+    ctx.user_module().store(ctx, module);
 
     Ok(())
 }
 
+/// Compile the user module, leaving its evaluation to the first export call.
+pub(crate) fn defer_user(
+    ctx: &rquickjs::Ctx<'_>,
+    script: Script,
+    entry_path: Option<&str>,
+) -> Result<(), String> {
+    let module = declare_user(ctx, script, entry_path)
+        .map_err(|e| format!("Failed to compile user JavaScript module: {e}"))?;
+
+    ctx.user_module().defer(module);
+
+    Ok(())
+}
+
+/// Compile the user module to QuickJS bytecode for [`Script::Bytecode`].
+pub(crate) fn compile(
+    js_source: &str,
+    entry_path: Option<&str>,
+    strip_source: bool,
+) -> Result<Vec<u8>, String> {
+    bytecode::compile(user_module_name(entry_path), js_source, strip_source)
+}
+
+/// Declare the user module, or for bytecode an entry module importing it,
+/// which loads the bytecode and resolves its imports.
+fn declare_user<'js>(
+    ctx: &rquickjs::Ctx<'js>,
+    script: Script,
+    entry_path: Option<&str>,
+) -> Result<Module<'js, Declared>, String> {
+    let name = user_module_name(entry_path);
+    match script {
+        Script::Source(source) => declare(ctx, name, &source),
+        Script::Bytecode(bytes) => {
+            let user_module = ctx.user_module();
+            user_module
+                .bytecode
+                .replace(Some((name.to_string(), bytes)));
+            user_module.from_bytecode.set(true);
+            drop(user_module);
+            declare(ctx, "componentize-qjs:entry.js", bytecode::ENTRY)
+        }
+    }
+}
+
+fn user_module_name(entry_path: Option<&str>) -> &str {
+    entry_path.unwrap_or("componentize-qjs:user.js")
+}
+
 fn evaluate<'js>(
     ctx: &rquickjs::Ctx<'js>,
     name: &str,
     source: &str,
 ) -> Result<rquickjs::Object<'js>, String> {
-    let module = CaughtError::catch(ctx, Module::declare(ctx.clone(), name, source))
-        .map_err(|e| format!("Failed to declare JavaScript module: {e}"))?;
+    finish(ctx, declare(ctx, name, source)?)
+}
+
+fn declare<'js>(
+    ctx: &rquickjs::Ctx<'js>,
+    name: &str,
+    source: &str,
+) -> Result<Module<'js, Declared>, String> {
+    CaughtError::catch(ctx, Module::declare(ctx.clone(), name, source))
+        .map_err(|e| format!("Failed to declare JavaScript module: {e}"))
+}
+
+/// Evaluate a declared module and return its namespace.
+fn finish<'js>(
+    ctx: &rquickjs::Ctx<'js>,
+    module: Module<'js, Declared>,
+) -> Result<rquickjs::Object<'js>, String> {
     let (module, promise) = CaughtError::catch(ctx, module.eval())
         .map_err(|e| format!("Failed to evaluate JavaScript module: {e}"))?;
 
@@ -8,8 +8,12 @@ use rquickjs::module::{Declarations, Exports, ModuleDef};
 use rquickjs::{Ctx, Error, Module};
 use wit_dylib_ffi::Wit;
 
-use crate::wit_imports::{WitInterface, interface_member_names, partition_imports};
-use crate::{CtxExt, bindings, with_ctx};
+use crate::options::{Naming, VersionAliases};
+use crate::trivia::fn_lookup;
+use crate::wit_imports::{
+    FuncKind, WitInterface, classify, interface_member_names, partition_imports,
+};
+use crate::{CtxExt, bindings, env, with_ctx};
 
 /// Transient state used while declaring native WIT import modules.
 #[derive(Default, rquickjs::JsLifetime)]
@@ -42,10 +46,10 @@ impl Resolver for WitModuleResolver {
         name: &str,
         _attributes: Option<ImportAttributes<'js>>,
     ) -> rquickjs::Result<String> {
-        if has_import_module(ctx.wit(), name) {
-            Ok(name.to_string())
-        } else {
-            Err(Error::new_resolving(base, name))
+        match find_import_interface(ctx, name) {
+            Ok(Some((name, _))) => Ok(name.to_string()),
+            Ok(None) => Err(Error::new_resolving(base, name)),
+            Err(msg) => Err(Error::new_resolving_message(base, name, msg)),
         }
     }
 }
@@ -59,7 +63,8 @@ impl Loader for WitModuleLoader {
         name: &str,
         _attributes: Option<ImportAttributes<'js>>,
     ) -> rquickjs::Result<Module<'js, Declared>> {
-        let iface = find_import_interface(ctx.wit(), name)
+        let (_, iface) = find_import_interface(ctx, name)
+            .map_err(|msg| Error::new_loading_message(name, msg))?
             .ok_or_else(|| Error::new_loading_message(name, "WIT import not found"))?;
 
         declare_import_module(ctx, name, &iface)
@@ -84,14 +89,22 @@ impl ModuleDef for WitImportModule {
 
     fn evaluate<'js>(ctx: &Ctx<'js>, exports: &Exports<'js>) -> rquickjs::Result<()> {
         let module_name: String = exports.module().name()?;
-        let iface = find_import_interface(ctx.wit(), &module_name)
+        let (_, iface) = find_import_interface(ctx, &module_name)
+            .map_err(|msg| Error::new_loading_message(&module_name, msg))?
             .ok_or_else(|| Error::new_loading_message(module_name, "WIT import not found"))?;
 
         let obj = bindings::interface_to_js(ctx, &iface)?;
+        let freestanding = iface
+            .funcs
+            .iter()
+            .filter(|func| matches!(classify(func.name()), FuncKind::Freestanding))
+            .map(|func| (func.interface(), func.name()));
+        bindings::apply_naming(ctx, &obj, freestanding)?;
         freeze(ctx, obj.clone())?;
+        env::register_import(ctx, &obj)?;
 
         exports.export("default", obj.clone())?;
-        for name in export_names(&iface) {
+        for name in export_names(ctx, &iface) {
             let value: rquickjs::Value = obj.get(name.as_str())?;
             exports.export(name, value)?;
         }
@@ -110,24 +123,70 @@ impl Drop for DeclaredExportsGuard<'_> {
     }
 }
 
-fn find_import_interface(wit_def: Wit, specifier: &str) -> Option<WitInterface> {
-    for (name, iface) in partition_imports(wit_def) {
-        let Some(name) = name else {
-            continue;
-        };
-
-        let name_no_version = name.split('@').next().unwrap_or(name);
+/// Find the imported interface named by `specifier`, returning its full name.
+///
+/// Besides its full name, an interface is found under the configured import
+/// aliases, and under its name without the patch version or without a version
+/// as far as the version aliasing allows. A shortened specifier matching
+/// several imported versions of the same interface is ambiguous and reported
+/// as an error rather than picking one.
+fn find_import_interface(
+    ctx: &Ctx<'_>,
+    specifier: &str,
+) -> Result<Option<(&'static str, WitInterface)>, String> {
+    let options = ctx.options();
+    let mut imports = partition_imports(ctx.wit())
+        .into_iter()
+        .filter_map(|(name, iface)| Some((name?, iface)));
+
+    if let Some((_, target)) = options
+        .import_aliases
+        .iter()
+        .find(|(alias, _)| alias == specifier)
+    {
+        return imports
+            .find(|(name, _)| name == target)
+            .map(Some)
+            .ok_or_else(|| {
+                format!(
+                    "import alias `{specifier}` names `{target}`, which the world doesn't import"
+                )
+            });
+    }
 
-        if specifier == name || specifier == name_no_version {
-            return Some(iface);
+    let mut matches = Vec::new();
+    for (name, iface) in imports {
+        if specifier == name {
+            return Ok(Some((name, iface)));
         }
+        if version_aliases(name, options.version_aliases).any(|alias| alias == specifier) {
+            matches.push((name, iface));
+        }
+    }
+
+    if matches.len() > 1 {
+        let mut names: Vec<_> = matches.iter().map(|(name, _)| *name).collect();
+        names.sort_unstable();
+        return Err(format!(
+            "`{specifier}` is ambiguous between {}; import a versioned name or add an \
+             import alias",
+            names.join(", ")
+        ));
     }
 
-    None
+    Ok(matches.pop())
 }
 
-fn has_import_module(wit_def: Wit, specifier: &str) -> bool {
-    find_import_interface(wit_def, specifier).is_some()
+/// The shortened names `name` can be imported under.
+fn version_aliases(name: &str, mode: VersionAliases) -> impl Iterator<Item = &str> {
+    let (base, version) = name.split_once('@').unwrap_or((name, ""));
+    let minor = version
+        .match_indices('.')
+        .nth(1)
+        .map(|(end, _)| &name[..base.len() + 1 + end]);
+    let strip_patch = minor.filter(|_| mode >= VersionAliases::StripPatch);
+    let strip_all = (!version.is_empty() && mode >= VersionAliases::StripAll).then_some(base);
+    strip_patch.into_iter().chain(strip_all)
 }
 
 fn declare_import_module<'js>(
@@ -135,14 +194,39 @@ fn declare_import_module<'js>(
     name: &str,
     iface: &WitInterface,
 ) -> rquickjs::Result<Module<'js, Declared>> {
-    ctx.wit_import_declarations().push(export_names(iface));
+    ctx.wit_import_declarations().push(export_names(ctx, iface));
 
     let _guard = DeclaredExportsGuard { ctx: ctx.clone() };
     Module::declare_def::<WitImportModule, _>(ctx.clone(), name)
 }
 
-fn export_names(iface: &WitInterface) -> Vec<String> {
-    interface_member_names(iface)
+/// Named exports of a WIT import module: its functions, under the names the
+/// naming policy and renames give them, and its resource classes.
+fn export_names(ctx: &Ctx<'_>, iface: &WitInterface) -> Vec<String> {
+    let mut names = interface_member_names(iface);
+    let naming = ctx.options().naming;
+    for func in &iface.funcs {
+        let name = func.name();
+        if !matches!(classify(name), FuncKind::Freestanding) {
+            continue;
+        }
+        let camel = fn_lookup(ctx, name);
+        if camel != name {
+            match naming {
+                Naming::CamelCase => {}
+                Naming::KebabCase => {
+                    names.retain(|n| n != camel);
+                    names.push(name.to_string());
+                }
+                Naming::Both => names.push(name.to_string()),
+            }
+        }
+        let js_name = ctx.options().rename(func.interface(), name);
+        if let Some(js_name) = js_name.filter(|js_name| !names.iter().any(|n| n == js_name)) {
+            names.push(js_name.to_string());
+        }
+    }
+    names
 }
 
 fn freeze<'js>(ctx: &Ctx<'js>, obj: rquickjs::Object<'js>) -> rquickjs::Result<()> {
@@ -0,0 +1,126 @@
+//! Runtime behavior configured at componentize time.
+//!
+//! Options arrive through the `init` export during Wizer pre-initialization and
+//! are stored as context userdata, so they are baked into the snapshot.
+
+use rquickjs::JsLifetime;
+
+use crate::DetHashMap;
+use crate::init::{InitOptions, NamingPolicy, VersionMatch};
+
+/// Runtime options stored as rquickjs userdata.
+#[derive(Default, JsLifetime)]
+pub(crate) struct RuntimeOptions {
+    /// Reject lists and strings longer than this when lowering JS values.
+    pub(crate) max_list_length: Option<usize>,
+    /// Check values against their WIT type before lowering them.
+    pub(crate) strict_types: bool,
+    /// Pass top-level `result` values as `{ tag, val }` objects instead of
+    /// returning the `ok` payload and throwing the `err` one.
+    pub(crate) tagged_results: bool,
+    /// Keep returning the `ok` payload and throwing the `err` one from imports
+    /// when `tagged_results` is set.
+    pub(crate) throwing_imports: bool,
+    /// Let integers lowered from JavaScript wrap into range instead of throwing
+    /// a `RangeError`.
+    pub(crate) wrap_integers: bool,
+    /// Reject NaN and infinite floats lowered from JavaScript with a `RangeError`.
+    pub(crate) finite_floats: bool,
+    /// Warn on stderr when a number lowered as an `f32` loses precision.
+    pub(crate) warn_f32_rounding: bool,
+    /// Never run the garbage collector, including for handle finalization.
+    pub(crate) disable_gc: bool,
+    /// Run a full GC cycle after every this many export calls.
+    pub(crate) gc_interval: Option<u32>,
+    /// Print heap usage to stderr after every this many export calls.
+    pub(crate) memory_report: Option<u32>,
+    /// Shortened names that import a versioned WIT interface.
+    pub(crate) version_aliases: VersionAliases,
+    /// Extra import specifiers, each with the interface it names.
+    pub(crate) import_aliases: Vec<(String, String)>,
+    /// Names WIT functions have in JavaScript.
+    pub(crate) naming: Naming,
+    /// JavaScript names for individual WIT functions, keyed by the function
+    /// name, qualified as `<interface>#<function>` or not.
+    pub(crate) renames: DetHashMap<String, &'static str>,
+}
+
+impl RuntimeOptions {
+    /// The JavaScript name given to the WIT function `name` of `iface`, if any.
+    pub(crate) fn rename(&self, iface: Option<&str>, name: &str) -> Option<&'static str> {
+        if self.renames.is_empty() {
+            return None;
+        }
+        iface
+            .and_then(|iface| self.renames.get(&format!("{iface}#{name}")))
+            .or_else(|| self.renames.get(name))
+            .copied()
+    }
+}
+
+/// Names WIT functions and resource methods have in JavaScript.
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) enum Naming {
+    #[default]
+    CamelCase,
+    KebabCase,
+    Both,
+}
+
+impl From<NamingPolicy> for Naming {
+    fn from(policy: NamingPolicy) -> Self {
+        match policy {
+            NamingPolicy::CamelCase => Self::CamelCase,
+            NamingPolicy::KebabCase => Self::KebabCase,
+            NamingPolicy::Both => Self::Both,
+        }
+    }
+}
+
+/// Which shortened names import a versioned WIT interface, in increasing
+/// order of leniency.
+#[derive(Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum VersionAliases {
+    Exact,
+    StripPatch,
+    #[default]
+    StripAll,
+}
+
+impl From<VersionMatch> for VersionAliases {
+    fn from(mode: VersionMatch) -> Self {
+        match mode {
+            VersionMatch::Exact => Self::Exact,
+            VersionMatch::StripPatch => Self::StripPatch,
+            VersionMatch::StripAll => Self::StripAll,
+        }
+    }
+}
+
+impl From<&InitOptions> for RuntimeOptions {
+    fn from(opts: &InitOptions) -> Self {
+        Self {
+            max_list_length: opts.max_list_length.map(|n| n as usize),
+            strict_types: opts.strict_types,
+            tagged_results: opts.tagged_results,
+            throwing_imports: opts.throwing_imports,
+            wrap_integers: opts.wrap_integers,
+            finite_floats: opts.finite_floats,
+            warn_f32_rounding: opts.warn_f32_rounding,
+            disable_gc: opts.disable_gc,
+            gc_interval: opts.gc_interval,
+            memory_report: opts.memory_report,
+            version_aliases: opts.version_match.into(),
+            import_aliases: opts.import_aliases.clone(),
+            naming: opts.naming.into(),
+            renames: opts
+                .renames
+                .iter()
+                .map(|(function, js_name)| {
+                    let js_name: &'static str = Box::leak(js_name.clone().into_boxed_str());
+                    (function.clone(), js_name)
+                })
+                .collect(),
+        }
+    }
+}
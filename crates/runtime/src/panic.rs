@@ -0,0 +1,76 @@
+//! A panic hook that adds the WIT call in progress and any pending JavaScript
+//! exception to the panic message.
+//!
+//! Lifting and lowering trust a value to have the shape of its WIT type and
+//! panic deep inside the canonical ABI glue when it doesn't, so the message
+//! alone rarely says which call failed. After the default hook has printed the
+//! message, this one names the last export called and the import in progress,
+//! and prints the exception QuickJS has pending, all to stderr before the
+//! component traps.
+
+use std::cell::Cell;
+
+use rquickjs::CaughtError;
+
+use crate::with_active_ctx;
+
+/// A WIT function, as its interface and name.
+type Function = (Option<&'static str>, &'static str);
+
+thread_local! {
+    static EXPORT: Cell<Option<Function>> = const { Cell::new(None) };
+    static IMPORT: Cell<Option<Function>> = const { Cell::new(None) };
+}
+
+/// Install the hook; called during initialization, so it is in the snapshot.
+pub(crate) fn install() {
+    let default = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        default(info);
+        report();
+    }));
+}
+
+/// Record the export `name` of `iface` as the last one called.
+pub(crate) fn enter_export(iface: Option<&'static str>, name: &'static str) {
+    EXPORT.set(Some((iface, name)));
+}
+
+/// Restores the previous import in progress when dropped.
+pub(crate) struct ImportGuard(Option<Function>);
+
+impl Drop for ImportGuard {
+    fn drop(&mut self) {
+        IMPORT.set(self.0);
+    }
+}
+
+/// Record the import `name` of `iface` as in progress until the guard drops.
+pub(crate) fn enter_import(iface: Option<&'static str>, name: &'static str) -> ImportGuard {
+    ImportGuard(IMPORT.replace(Some((iface, name))))
+}
+
+fn report() {
+    if let Some(func) = EXPORT.get() {
+        eprintln!("  last export called: `{}`", qualified(func));
+    }
+    if let Some(func) = IMPORT.get() {
+        eprintln!("  import in progress: `{}`", qualified(func));
+    }
+    // Only look at the context if the panic happened inside it; entering it
+    // afresh from a panic could fail in turn.
+    let exception = with_active_ctx(|ctx| {
+        let pending = unsafe { rquickjs::qjs::JS_HasException(ctx.as_raw().as_ptr()) };
+        pending.then(|| CaughtError::from_error(ctx, rquickjs::Error::Exception).to_string())
+    });
+    if let Some(Some(exception)) = exception {
+        eprintln!("  pending JavaScript exception: {exception}");
+    }
+}
+
+fn qualified((iface, name): Function) -> String {
+    match iface {
+        Some(iface) => format!("{iface}#{name}"),
+        None => name.to_string(),
+    }
+}
@@ -0,0 +1,123 @@
+//! Cold-start priming of exports during Wizer initialization.
+//!
+//! QuickJS compiles functions lazily and user code often fills caches on first
+//! use. Calling selected exports once before the snapshot is taken bakes that
+//! work into the component, so the first production call starts warm.
+
+use rquickjs::function::Rest;
+use rquickjs::{Array, CatchResultExt, CaughtError, Ctx, Function, Value};
+
+use crate::CtxExt;
+use crate::init::ExportCall;
+use crate::trivia::{get_export_fn, iface_object, run_pending_jobs};
+
+/// The global function called once the module is evaluated during Wizer.
+const INIT_HOOK: &str = "__componentize_init";
+
+/// Call `globalThis.__componentize_init` if the module defined it, removing it
+/// first so it neither runs again nor stays in the snapshot.
+///
+/// The import log is suspended as for priming, but unlike a priming call an
+/// exception thrown by the hook, or a promise it returns that rejects or
+/// doesn't settle, fails initialization.
+pub(crate) fn run_init_hook(ctx: &Ctx<'_>) -> Result<(), String> {
+    let globals = ctx.globals();
+    let hook: Value = globals.get(INIT_HOOK).map_err(|e| e.to_string())?;
+    if hook.is_undefined() {
+        return Ok(());
+    }
+    let hook = hook
+        .into_function()
+        .ok_or_else(|| format!("`{INIT_HOOK}` must be a function"))?;
+    globals.remove(INIT_HOOK).map_err(|e| e.to_string())?;
+
+    let suspended = ctx.import_log().suspend();
+    let result = hook.call::<_, Value>(()).catch(ctx);
+    run_pending_jobs(ctx);
+    ctx.import_log().resume(suspended);
+
+    let error = match result {
+        Err(e) => e.to_string(),
+        Ok(value) => match value.as_promise().map(|p| p.result::<Value>()) {
+            None | Some(Some(Ok(_))) => return Ok(()),
+            Some(Some(Err(e))) => CaughtError::from_error(ctx, e).to_string(),
+            Some(None) => "its promise did not settle".to_string(),
+        },
+    };
+    Err(format!("`{INIT_HOOK}` failed: {error}"))
+}
+
+/// Call each export in `calls` once with its JSON-encoded arguments.
+///
+/// The import log is suspended meanwhile, so priming neither records nor
+/// consumes captured calls. Unknown exports and malformed arguments fail
+/// initialization; exceptions thrown by the export only produce a warning,
+/// since the snapshot is still usable.
+pub(crate) fn prime_exports(ctx: &Ctx<'_>, calls: &[ExportCall]) -> Result<Vec<String>, String> {
+    if calls.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let suspended = ctx.import_log().suspend();
+    let result = calls
+        .iter()
+        .map(|call| prime_export(ctx, call))
+        .collect::<Result<Vec<_>, _>>();
+    ctx.import_log().resume(suspended);
+
+    Ok(result?.into_iter().flatten().collect())
+}
+
+fn prime_export(ctx: &Ctx<'_>, call: &ExportCall) -> Result<Option<String>, String> {
+    let name = &call.name;
+    let func = export_function(ctx, name)?;
+    let args: Array = ctx
+        .json_parse(call.args.as_str())
+        .ok()
+        .and_then(|args: Value| args.into_array())
+        .ok_or_else(|| format!("arguments for priming `{name}` must be a JSON array"))?;
+    let args = args
+        .iter::<Value>()
+        .collect::<rquickjs::Result<Vec<_>>>()
+        .map_err(|e| e.to_string())?;
+
+    let result = func.call::<_, Value>((Rest(args),)).catch(ctx);
+    run_pending_jobs(ctx);
+
+    let error = match result {
+        Err(e) => e.to_string(),
+        Ok(value) => match value.as_promise().map(|p| p.result::<Value>()) {
+            None | Some(Some(Ok(_))) => return Ok(None),
+            Some(Some(Err(e))) => CaughtError::from_error(ctx, e).to_string(),
+            Some(None) => "its promise did not settle".to_string(),
+        },
+    };
+    Ok(Some(format!("priming export `{name}` failed: {error}")))
+}
+
+/// Find the JS function backing the freestanding export `name`, given as the
+/// WIT function name or `<interface>#<function>`.
+fn export_function<'js>(ctx: &Ctx<'js>, name: &str) -> Result<Function<'js>, String> {
+    let exports = ctx
+        .user_module()
+        .exports(ctx)
+        .map_err(|e| format!("user module exports not found: {e}"))?;
+
+    let func = ctx
+        .wit()
+        .iter_export_funcs()
+        .filter(|func| !func.name().starts_with('['))
+        .find(|func| match func.interface() {
+            Some(iface) => name.split_once('#') == Some((iface, func.name())),
+            None => name == func.name(),
+        })
+        .ok_or_else(|| format!("cannot prime `{name}`: no such exported function"))?;
+
+    let target = match func.interface() {
+        Some(iface) => iface_object(ctx, &exports, iface),
+        None => Ok(exports),
+    };
+    target
+        .and_then(|obj| get_export_fn(ctx, &obj, func.interface(), func.name()))
+        .map_err(|_| format!("cannot prime `{name}`: the JavaScript module doesn't implement it"))
+}
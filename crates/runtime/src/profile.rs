@@ -0,0 +1,152 @@
+//! Per-function call counts, timing and interrupt-tick sampling, enabled with
+//! the `profile` feature.
+//!
+//! Exports and imports count their calls and the wall time spent in them,
+//! which for an import includes the host's share. Async calls are only timed
+//! until they first return to the caller.
+//!
+//! This is not an opcode count: QuickJS has no per-opcode hook. Instead, the
+//! interpreter polls its interrupt handler after a fixed budget of calls and
+//! backward jumps, and each poll is sampled as a tick against the export
+//! currently on the call stack, a coarse measure of how much bytecode it ran.
+//!
+//! The counters are read back through `__cqjs.getProfile()` or printed to
+//! stderr by `__cqjs.dumpProfile()`.
+
+use std::cell::{Cell, RefCell};
+use std::time::{Duration, Instant};
+
+use rquickjs::{Ctx, Object};
+use wit_dylib_ffi::{ExportFunction, ImportFunction};
+
+use crate::DetIndexMap;
+
+#[derive(Default)]
+struct FuncProfile {
+    calls: u64,
+    interrupt_ticks: u64,
+    time: Duration,
+}
+
+type Profile = DetIndexMap<String, FuncProfile>;
+
+thread_local! {
+    static EXPORTS: RefCell<Profile> = RefCell::default();
+    static IMPORTS: RefCell<Profile> = RefCell::default();
+    static CURRENT: Cell<Option<usize>> = const { Cell::new(None) };
+}
+
+/// Sample an interrupt tick against the active export.
+pub(crate) fn tick() {
+    if let Some(index) = CURRENT.get() {
+        EXPORTS.with_borrow_mut(|profile| profile[index].interrupt_ticks += 1);
+    }
+}
+
+/// Adds the elapsed time to an export and restores the previously active
+/// export when dropped.
+pub(crate) struct ExportGuard {
+    index: usize,
+    previous: Option<usize>,
+    start: Instant,
+}
+
+impl Drop for ExportGuard {
+    fn drop(&mut self) {
+        let elapsed = self.start.elapsed();
+        EXPORTS.with_borrow_mut(|profile| profile[self.index].time += elapsed);
+        CURRENT.set(self.previous);
+    }
+}
+
+/// Adds the elapsed time to an import when dropped.
+pub(crate) struct ImportGuard {
+    index: usize,
+    start: Instant,
+}
+
+impl Drop for ImportGuard {
+    fn drop(&mut self) {
+        let elapsed = self.start.elapsed();
+        IMPORTS.with_borrow_mut(|profile| profile[self.index].time += elapsed);
+    }
+}
+
+/// Record a call to `func` and attribute ticks and time to it until the
+/// guard drops.
+pub(crate) fn enter(func: &ExportFunction) -> ExportGuard {
+    let index = EXPORTS.with_borrow_mut(|profile| count(profile, func.interface(), func.name()));
+    ExportGuard {
+        index,
+        previous: CURRENT.replace(Some(index)),
+        start: Instant::now(),
+    }
+}
+
+/// Record a call to the import `func` and attribute time to it until the
+/// guard drops.
+pub(crate) fn enter_import(func: &ImportFunction) -> ImportGuard {
+    let index = IMPORTS.with_borrow_mut(|profile| count(profile, func.interface(), func.name()));
+    ImportGuard {
+        index,
+        start: Instant::now(),
+    }
+}
+
+fn count(profile: &mut Profile, iface: Option<&str>, name: &str) -> usize {
+    let name = match iface {
+        Some(iface) => format!("{iface}#{name}"),
+        None => name.to_string(),
+    };
+    let entry = profile.entry(name);
+    let index = entry.index();
+    entry.or_default().calls += 1;
+    index
+}
+
+/// Build `{ exports, imports }` for `__cqjs.getProfile()`, each mapping a
+/// function to its `{ calls, interruptTicks, time }`, with `time` in
+/// milliseconds.
+pub(crate) fn snapshot<'js>(ctx: &Ctx<'js>) -> rquickjs::Result<Object<'js>> {
+    let exports = EXPORTS.with_borrow(|profile| to_object(ctx, profile))?;
+    let imports = IMPORTS.with_borrow(|profile| to_object(ctx, profile))?;
+    let obj = Object::new(ctx.clone())?;
+    obj.set("exports", exports)?;
+    obj.set("imports", imports)?;
+    Ok(obj)
+}
+
+fn to_object<'js>(ctx: &Ctx<'js>, profile: &Profile) -> rquickjs::Result<Object<'js>> {
+    let obj = Object::new(ctx.clone())?;
+    for (name, entry) in profile {
+        let stats = Object::new(ctx.clone())?;
+        stats.set("calls", entry.calls as f64)?;
+        stats.set("interruptTicks", entry.interrupt_ticks as f64)?;
+        stats.set("time", entry.time.as_secs_f64() * 1000.0)?;
+        obj.set(name.as_str(), stats)?;
+    }
+    Ok(obj)
+}
+
+/// Print the profile to stderr for `__cqjs.dumpProfile()`, exports and then
+/// imports, each with the functions that took the longest first.
+pub(crate) fn dump() {
+    eprintln!(
+        "{:<8} {:>10} {:>10} {:>12}  function",
+        "kind", "calls", "int. ticks", "time (ms)"
+    );
+    for (kind, profile) in [("export", &EXPORTS), ("import", &IMPORTS)] {
+        profile.with_borrow(|profile| {
+            let mut entries: Vec<_> = profile.iter().collect();
+            entries.sort_by(|(_, a), (_, b)| b.time.cmp(&a.time));
+            for (name, entry) in entries {
+                eprintln!(
+                    "{kind:<8} {:>10} {:>10} {:>12.3}  {name}",
+                    entry.calls,
+                    entry.interrupt_ticks,
+                    entry.time.as_secs_f64() * 1000.0
+                );
+            }
+        });
+    }
+}
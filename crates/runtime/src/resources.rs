@@ -6,11 +6,16 @@
 
 use std::cell::RefCell;
 
-use rquickjs::{JsLifetime, Persistent, Value};
+use rquickjs::{Ctx, Function, JsLifetime, Object, Persistent, Value};
 use wit_dylib_ffi::Resource;
 
 use crate::CtxExt;
 use crate::DetHashMap;
+use crate::run_pending_jobs;
+
+/// Export calls between forced GC cycles while owned imported handles are
+/// alive, so wrappers kept unreachable only by reference cycles are finalized.
+const GC_INTERVAL: u32 = 64;
 
 /// A borrowed imported resource handle that must be dropped when the call ends.
 pub(crate) struct BorrowedResource {
@@ -69,11 +74,18 @@ pub(crate) struct ResourceClasses {
 #[derive(Default)]
 struct ClassInner {
     map: DetHashMap<usize, ResourceClass>,
+    /// Owned handles currently registered with a finalizer.
+    live_owned: usize,
+    /// Export calls since the last forced GC cycle.
+    calls_since_gc: u32,
 }
 
 struct ResourceClass {
     class: Persistent<Value<'static>>,
     prototype: Persistent<Value<'static>>,
+    /// `FinalizationRegistry` dropping owned handles whose wrapper became
+    /// unreachable; `None` if the engine lacks one.
+    finalizer: Option<Persistent<Object<'static>>>,
 }
 
 impl ResourceClasses {
@@ -83,11 +95,16 @@ impl ResourceClasses {
         index: usize,
         class: Persistent<Value<'static>>,
         prototype: Persistent<Value<'static>>,
+        finalizer: Option<Persistent<Object<'static>>>,
     ) {
-        self.inner
-            .borrow_mut()
-            .map
-            .insert(index, ResourceClass { class, prototype });
+        self.inner.borrow_mut().map.insert(
+            index,
+            ResourceClass {
+                class,
+                prototype,
+                finalizer,
+            },
+        );
     }
 
     /// Get the class (constructor) for a resource, if any.
@@ -103,6 +120,122 @@ impl ResourceClasses {
             .get(&index)
             .map(|c| c.prototype.clone())
     }
+
+    fn finalizer<'js>(
+        &self,
+        ctx: &Ctx<'js>,
+        index: usize,
+    ) -> rquickjs::Result<Option<Object<'js>>> {
+        let finalizer = self
+            .inner
+            .borrow()
+            .map
+            .get(&index)
+            .and_then(|c| c.finalizer.clone());
+        finalizer.map(|f| f.restore(ctx)).transpose()
+    }
+
+    /// Drop `handle` once `wrapper`, which owns it, becomes unreachable.
+    pub(crate) fn track_owned<'js>(
+        &self,
+        ctx: &Ctx<'js>,
+        index: usize,
+        wrapper: &Object<'js>,
+        handle: u32,
+    ) -> rquickjs::Result<()> {
+        if let Some(finalizer) = self.finalizer(ctx, index)? {
+            let register: Function = finalizer.get("register")?;
+            register.call::<_, ()>((
+                rquickjs::function::This(finalizer),
+                wrapper.clone(),
+                handle,
+                wrapper.clone(),
+            ))?;
+            self.inner.borrow_mut().live_owned += 1;
+        }
+        Ok(())
+    }
+
+    /// Stop tracking `wrapper` because its handle was dropped explicitly or
+    /// its ownership moved out of JS.
+    pub(crate) fn untrack_owned<'js>(
+        &self,
+        ctx: &Ctx<'js>,
+        index: usize,
+        wrapper: &Object<'js>,
+    ) -> rquickjs::Result<()> {
+        if let Some(finalizer) = self.finalizer(ctx, index)? {
+            let unregister: Function = finalizer.get("unregister")?;
+            if unregister.call((rquickjs::function::This(finalizer), wrapper.clone()))? {
+                self.inner.borrow_mut().live_owned -= 1;
+            }
+        }
+        Ok(())
+    }
+
+    /// Record that a finalizer dropped an unreachable wrapper's handle.
+    pub(crate) fn finalized(&self) {
+        let mut inner = self.inner.borrow_mut();
+        inner.live_owned = inner.live_owned.saturating_sub(1);
+    }
+
+    /// Count an export call, returning whether a GC cycle is due: every
+    /// `interval` calls if set, otherwise every [`GC_INTERVAL`] calls while
+    /// owned handles are alive.
+    fn tick(&self, interval: Option<u32>) -> bool {
+        let mut inner = self.inner.borrow_mut();
+        if interval.is_none() && inner.live_owned == 0 {
+            inner.calls_since_gc = 0;
+            return false;
+        }
+        inner.calls_since_gc += 1;
+        if inner.calls_since_gc < interval.unwrap_or(GC_INTERVAL) {
+            return false;
+        }
+        inner.calls_since_gc = 0;
+        true
+    }
+}
+
+/// Build the `FinalizationRegistry` that drops an imported resource's owned
+/// handles, or `None` if the engine doesn't provide one.
+pub(crate) fn handle_finalizer<'js>(
+    ctx: &Ctx<'js>,
+    resource: Resource,
+) -> rquickjs::Result<Option<Object<'js>>> {
+    let Some(registry) = ctx
+        .globals()
+        .get::<_, Option<rquickjs::function::Constructor>>("FinalizationRegistry")?
+    else {
+        return Ok(None);
+    };
+    let drop_handle = resource.drop();
+    let cleanup = Function::new(ctx.clone(), move |ctx: Ctx<'_>, handle: u32| {
+        ctx.resource_classes().finalized();
+        unsafe { drop_handle(handle) };
+    })?;
+    registry.construct((cleanup,)).map(Some)
+}
+
+/// Finalize unreachable owned handles after an export call.
+///
+/// Wrappers freed by reference counting are finalized by the next job run on
+/// their own; a full GC cycle every [`GC_INTERVAL`] calls, or every
+/// `gc_interval` calls if configured, also catches those only kept alive by
+/// cycles. Skipped when GC is disabled.
+pub(crate) fn collect_owned_handles(ctx: &Ctx<'_>) {
+    let (disable_gc, interval) = {
+        let options = ctx.options();
+        (options.disable_gc, options.gc_interval)
+    };
+    if disable_gc || !ctx.resource_classes().tick(interval) {
+        return;
+    }
+    unsafe {
+        let rt = rquickjs::qjs::JS_GetRuntime(ctx.as_raw().as_ptr());
+        rquickjs::qjs::JS_RunGC(rt);
+    }
+    run_pending_jobs(ctx);
 }
 
 /// Extract the canonical handle from an imported resource wrapper object.
@@ -2,7 +2,9 @@
 //!
 //! Nested WIT `result` values use the normal tagged-object representation.
 //! Function returns, however, follow the JCO/ComponentizeJS convention:
-//! `ok` is returned/resolved and `err` is thrown/rejected.
+//! `ok` is returned/resolved and `err` is thrown/rejected. Builds with tagged
+//! results keep the tagged-object form at the top level too, except for
+//! imports when throwing imports are configured as well.
 
 use rquickjs::function::Args;
 use rquickjs::object::Property;
@@ -12,12 +14,13 @@ use rquickjs::{
 };
 use wit_dylib_ffi::{Type, WitResult};
 
-use crate::{reject_promise, resolve_promise};
+use crate::trivia::run_timer;
+use crate::{CtxExt, limits, reject_promise, resolve_promise, run_pending_jobs, validate};
 
 #[derive(Clone, Copy)]
 enum ReturnShape {
     None,
-    Plain,
+    Plain(Type),
     Result(WitResult),
 }
 
@@ -77,10 +80,32 @@ pub(crate) struct ResultBoundary {
 
 impl ResultBoundary {
     /// Create a boundary for a function return type.
-    pub(crate) fn new(result: Option<Type>) -> Self {
+    ///
+    /// With tagged results configured, a top-level `result` crosses the
+    /// boundary as a plain `{ tag, val }` object, like a nested one.
+    pub(crate) fn new(ctx: &Ctx<'_>, result: Option<Type>) -> Self {
+        Self::with_tags(ctx.options().tagged_results, result)
+    }
+
+    /// Create a boundary for the return type of an import.
+    ///
+    /// Unlike [`ResultBoundary::new`], throwing imports keep returning the
+    /// `ok` payload and throwing the `err` one under tagged results.
+    pub(crate) fn import(ctx: &Ctx<'_>, result: Option<Type>) -> Self {
+        Self::with_tags(Self::tagged_imports(ctx), result)
+    }
+
+    /// Whether imports return a top-level `result` as a `{ tag, val }`
+    /// object in this build.
+    pub(crate) fn tagged_imports(ctx: &Ctx<'_>) -> bool {
+        let options = ctx.options();
+        options.tagged_results && !options.throwing_imports
+    }
+
+    fn with_tags(tagged: bool, result: Option<Type>) -> Self {
         let shape = match result.map(resolve_alias) {
-            Some(Type::Result(result)) => ReturnShape::Result(result),
-            Some(_) => ReturnShape::Plain,
+            Some(Type::Result(result)) if !tagged => ReturnShape::Result(result),
+            Some(ty) => ReturnShape::Plain(ty),
             None => ReturnShape::None,
         };
 
@@ -131,7 +156,12 @@ impl ResultBoundary {
         ctx: &Ctx<'js>,
         result: Result<Value<'js>>,
     ) -> CaughtResult<'js, Option<Value<'js>>> {
-        self.lower_caught(ctx, result.catch(ctx))
+        let result = result.catch(ctx);
+        // The JS stack is empty again, so let the export's promise jobs run
+        // before returning to the host; nothing else would ever drive them.
+        run_pending_jobs(ctx);
+        let result = result.and_then(|value| settle_sync(ctx, value));
+        self.lower_caught(ctx, result)
     }
 
     /// Lower a rejected async JS export into a canonical return value.
@@ -149,11 +179,7 @@ impl ResultBoundary {
         ctx: &Ctx<'js>,
         value: Value<'js>,
     ) -> CaughtResult<'js, Option<Value<'js>>> {
-        match self.shape {
-            ReturnShape::None => Ok(None),
-            ReturnShape::Plain => Ok(Some(value)),
-            ReturnShape::Result(result_ty) => Ok(Some(tagged_ok(ctx, result_ty, value)?)),
-        }
+        self.lower_caught(ctx, Ok(value))
     }
 
     fn lower_caught<'js>(
@@ -161,10 +187,14 @@ impl ResultBoundary {
         ctx: &Ctx<'js>,
         result: CaughtResult<'js, Value<'js>>,
     ) -> CaughtResult<'js, Option<Value<'js>>> {
+        // A mistyped or oversized return value is treated as if the export had
+        // thrown.
+        let result = result.and_then(|value| self.check_value(ctx, value));
+
         let ReturnShape::Result(result_ty) = self.shape else {
             return match (self.shape, result) {
                 (ReturnShape::None, Ok(_)) => Ok(None),
-                (ReturnShape::Plain, Ok(value)) => Ok(Some(value)),
+                (ReturnShape::Plain(_), Ok(value)) => Ok(Some(value)),
                 (ReturnShape::Result(_), _) => unreachable!(),
                 (_, Err(err)) => Err(err),
             };
@@ -175,9 +205,74 @@ impl ResultBoundary {
             Err(err) => Ok(Some(tagged_err(ctx, result_ty, err)?)),
         }
     }
+
+    /// Apply strict type checking and the list length limit to a returned
+    /// value.
+    fn check_value<'js>(&self, ctx: &Ctx<'js>, value: Value<'js>) -> CaughtResult<'js, Value<'js>> {
+        let ty = match self.shape {
+            ReturnShape::None => None,
+            ReturnShape::Plain(ty) => Some(ty),
+            ReturnShape::Result(result_ty) => result_ty.ok(),
+        };
+        if let Some(ty) = ty {
+            validate::check(ctx, |_| "return value".to_string(), [(&value, ty)])
+                .and_then(|()| limits::check(ctx, || "return value".to_string(), [(&value, ty)]))
+                .map_err(|err| CaughtError::from_error(ctx, err))?;
+        }
+        Ok(value)
+    }
+}
+
+/// Return the `ok` payload of `value`, the return value of an import with a
+/// top-level `result`, or throw its `err` one, whichever convention imports
+/// follow in this build.
+///
+/// Runtime helpers that call imports, like the `wasi:io/streams`
+/// conveniences, pass results through it, so tagged results never reach them.
+pub(crate) fn unwrap_import_result<'js>(ctx: &Ctx<'js>, value: Value<'js>) -> Result<Value<'js>> {
+    if !ResultBoundary::tagged_imports(ctx) {
+        return Ok(value);
+    }
+    let obj = value
+        .as_object()
+        .ok_or_else(|| rquickjs::Error::new_from_js(value.type_of().as_str(), "result"))?;
+    let tag: String = obj.get("tag")?;
+    let payload: Value = obj.get("val")?;
+    if tag == "ok" {
+        Ok(payload)
+    } else {
+        Err(ctx.throw(component_error_value(ctx, payload)?))
+    }
+}
+
+/// Settle a promise returned from a synchronous export.
+///
+/// Runs after the job queue has been drained. Sync exports can't suspend, so
+/// a promise that is still pending (e.g. one waiting on an async import) is an
+/// error.
+fn settle_sync<'js>(ctx: &Ctx<'js>, value: Value<'js>) -> CaughtResult<'js, Value<'js>> {
+    let Some(promise) = value.as_promise() else {
+        return Ok(value);
+    };
+
+    // Timers may still settle it; each one fired can schedule more jobs.
+    while promise.result::<Value>().is_none() && run_timer(ctx, true).catch(ctx)? {
+        run_pending_jobs(ctx);
+    }
+
+    match promise.result::<Value>() {
+        Some(result) => result.catch(ctx),
+        None => Err(CaughtError::Error(rquickjs::Error::new_from_js_message(
+            "promise",
+            "value",
+            "synchronous export returned a promise that did not settle; \
+             declare the export `async` in WIT",
+        ))),
+    }
 }
 
-fn resolve_alias(mut ty: Type) -> Type {
+/// Follow `ty` through type aliases to the type it names.
+pub(crate) fn resolve_alias(mut ty: Type) -> Type {
     while let Type::Alias(alias) = ty {
         ty = alias.ty();
     }
@@ -13,7 +13,8 @@ use crate::{QjsCallContext, resolve_promise, symbol_dispose, with_ctx};
 use rquickjs::JsLifetime;
 use rquickjs::class::{Class, JsClass, Trace};
 use rquickjs::function::{self, Rest, This};
-use rquickjs::{Ctx, Function, Object, Persistent, Value};
+use rquickjs::object::Accessor;
+use rquickjs::{Ctx, Function, Object, Persistent, Symbol, Value};
 
 use std::cell::Cell;
 
@@ -45,6 +46,61 @@ macro_rules! copy_typed_array_as {
     }};
 }
 
+/// `StreamReadable.prototype[Symbol.asyncIterator]`.
+///
+/// `stream<u8>` yields `Uint8Array` chunks, other streams yield individual
+/// values. The handle is dropped once iteration ends or is abandoned.
+const STREAM_ITERATOR_JS: &str = r#"(async function* () {
+  try {
+    while (!this.closed) {
+      const chunk = await this.read(1024);
+      if (chunk instanceof Uint8Array) {
+        if (chunk.length > 0) yield chunk;
+      } else {
+        yield* chunk;
+      }
+    }
+  } finally {
+    this.drop();
+  }
+})"#;
+
+/// Wraps a `read` or `write` method so the options object at `index` may carry
+/// an `AbortSignal`.
+///
+/// Aborting cancels the pending copy through the `cancel` method and rejects
+/// with the signal's reason; an already aborted signal rejects without
+/// starting one.
+const WITH_SIGNAL_JS: &str = r#"((method, cancel, index) => function (...args) {
+  const signal = args[index]?.signal;
+  if (signal == null) return method.apply(this, args);
+  if (signal.aborted) return Promise.reject(signal.reason);
+  return new Promise((resolve, reject) => {
+    const onAbort = () => {
+      try {
+        this[cancel]();
+      } catch {}
+      reject(signal.reason);
+    };
+    signal.addEventListener("abort", onAbort, { once: true });
+    method
+      .apply(this, args.slice(0, index))
+      .then(resolve, reject)
+      .finally(() => signal.removeEventListener("abort", onAbort));
+  });
+})"#;
+
+/// Wrap `method` as described in [`WITH_SIGNAL_JS`].
+pub(crate) fn with_signal<'js>(
+    ctx: &Ctx<'js>,
+    method: Function<'js>,
+    cancel: &str,
+    index: usize,
+) -> rquickjs::Result<Function<'js>> {
+    let wrap: Function = ctx.eval(WITH_SIGNAL_JS)?;
+    wrap.call((method, cancel, index))
+}
+
 /// Rust side state for the readable end of a component-model stream.
 #[derive(Trace, JsLifetime)]
 pub(crate) struct StreamReadable {
@@ -66,17 +122,23 @@ impl<'js> JsClass<'js> for StreamReadable {
 
     fn prototype(ctx: &Ctx<'js>) -> rquickjs::Result<Option<Object<'js>>> {
         let proto = Object::new(ctx.clone())?;
-        proto.set("read", Function::new(ctx.clone(), stream_read)?)?;
+        let read = Function::new(ctx.clone(), stream_read)?;
+        proto.set("read", with_signal(ctx, read, "cancelRead", 1)?)?;
         proto.set(
             "cancelRead",
             Function::new(ctx.clone(), stream_cancel_read)?,
         )?;
 
+        proto.prop("closed", Accessor::new_get(stream_readable_closed))?;
+
         let drop_fn = Function::new(ctx.clone(), stream_drop_readable)?;
         proto.set("drop", drop_fn.clone())?;
 
         let dispose_sym = symbol_dispose(ctx)?;
         proto.set(dispose_sym, drop_fn)?;
+
+        let iterator: Function = ctx.eval(STREAM_ITERATOR_JS)?;
+        proto.set(Symbol::async_iterator(ctx.clone()), iterator)?;
         Ok(Some(proto))
     }
 
@@ -106,13 +168,16 @@ impl<'js> JsClass<'js> for StreamWritable {
 
     fn prototype(ctx: &Ctx<'js>) -> rquickjs::Result<Option<Object<'js>>> {
         let proto = Object::new(ctx.clone())?;
-        proto.set("write", Function::new(ctx.clone(), stream_write)?)?;
+        let write = Function::new(ctx.clone(), stream_write)?;
+        proto.set("write", with_signal(ctx, write, "cancelWrite", 1)?)?;
         proto.set("writeAll", Function::new(ctx.clone(), stream_write_all)?)?;
         proto.set(
             "cancelWrite",
             Function::new(ctx.clone(), stream_cancel_write)?,
         )?;
 
+        proto.prop("closed", Accessor::new_get(stream_writable_closed))?;
+
         let drop_fn = Function::new(ctx.clone(), stream_drop_writable)?;
         proto.set("drop", drop_fn.clone())?;
 
@@ -269,6 +334,8 @@ fn stream_cancel_read<'js>(
             Ok(Value::new_undefined(ctx))
         }
         Some((progress, result)) => {
+            // The copy settled without an event, so stop waiting for one.
+            drop(ctx.task().take(handle));
             this.0.borrow_mut().end.mark_completed(result);
             let obj = Object::new(ctx.clone())?;
             obj.set("progress", progress)?;
@@ -278,6 +345,10 @@ fn stream_cancel_read<'js>(
     }
 }
 
+fn stream_readable_closed<'js>(this: This<Class<'js, StreamReadable>>) -> bool {
+    this.0.borrow().end.closed()
+}
+
 fn stream_drop_readable<'js>(
     this: This<Class<'js, StreamReadable>>,
     ctx: Ctx<'js>,
@@ -316,13 +387,13 @@ fn stream_write<'js>(
 
         for i in 0..count {
             let elem: Value = arr.get(i)?;
-            call.push_value(&ctx, elem);
+            call.push_value(elem);
             unsafe { ty.lower(&mut call, buf.ptr().add(ty.abi_payload_size() * i)) };
         }
         (buf, count)
     } else {
         let buf = BufferGuard::new_zeroed(ty.abi_payload_size(), ty.abi_payload_align());
-        call.push_value(&ctx, data);
+        call.push_value(data);
         unsafe { ty.lower(&mut call, buf.ptr()) };
         (buf, 1)
     };
@@ -450,6 +521,8 @@ fn stream_cancel_write<'js>(
             Ok(Value::new_undefined(ctx))
         }
         Some((progress, result)) => {
+            // The copy settled without an event, so stop waiting for one.
+            drop(ctx.task().take(handle));
             this.0.borrow_mut().end.mark_completed(result);
             let obj = Object::new(ctx.clone())?;
             obj.set("progress", progress)?;
@@ -459,6 +532,10 @@ fn stream_cancel_write<'js>(
     }
 }
 
+fn stream_writable_closed<'js>(this: This<Class<'js, StreamWritable>>) -> bool {
+    this.0.borrow().end.closed()
+}
+
 fn stream_drop_writable<'js>(
     this: This<Class<'js, StreamWritable>>,
     ctx: Ctx<'js>,
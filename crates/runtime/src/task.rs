@@ -10,7 +10,8 @@ use crate::DetHashMap;
 use crate::abi::*;
 use crate::buffer::BufferGuard;
 use crate::result::ResultBoundary;
-use crate::{QjsCallContext, resolve_promise, with_ctx};
+use crate::trivia::run_timer;
+use crate::{QjsCallContext, resolve_promise, run_pending_jobs, with_ctx};
 
 /// A pending async operation awaiting a callback event.
 #[allow(dead_code)]
@@ -129,8 +130,22 @@ impl TaskState {
 
     /// Drive the quickjs job queue until drained, then decide whether to
     /// exit or wait for more events. Returns the encoded callback code.
+    ///
+    /// Due timers fire in between, and once nothing else is pending the task
+    /// waits for the remaining ones before exiting. Timers can't join the
+    /// waitable set, so while host calls are pending they only fire when an
+    /// event arrives.
     pub(crate) fn poll(&self) -> u32 {
-        with_ctx(|ctx| while ctx.execute_pending_job() {});
+        with_ctx(|ctx| {
+            run_pending_jobs(ctx);
+            loop {
+                let idle = self.with(|inner| inner.pending.is_empty());
+                if !run_timer(ctx, idle).expect("timer callback threw") {
+                    break;
+                }
+                run_pending_jobs(ctx);
+            }
+        });
 
         let mut inner = self.0.borrow_mut().take().expect("no active task state");
 
@@ -170,11 +185,12 @@ pub(crate) fn handle_subtask(handle: u32, state: SubtaskState) {
             };
 
             let func = with_ctx(|ctx| ctx.wit()).import_func(func_index);
+            let _panic = crate::panic::enter_import(func.interface(), func.name());
             unsafe { func.lift_import_async_result(&mut call, buffer) };
 
             with_ctx(|ctx| {
-                ResultBoundary::new(func.result())
-                    .lift(ctx, call.maybe_pop_value(ctx).unwrap())
+                ResultBoundary::import(ctx, func.result())
+                    .lift(ctx, call.maybe_pop_value(ctx))
                     .unwrap()
                     .settle_persistent(ctx, resolve, reject);
             });
@@ -0,0 +1,56 @@
+//! Tracing of the values crossing the component boundary, enabled with the
+//! `trace_values` init option.
+//!
+//! Every value lifted into JavaScript or lowered out of it logs a line to
+//! stderr naming the function being called, the WIT type and a short preview
+//! of the value, e.g. `[trace] local:app/api#add: lower u32 <- string "1"`.
+//! Compound values log their own line before or after their parts.
+
+use std::cell::Cell;
+
+use rquickjs::Value;
+
+use crate::validate::describe;
+
+thread_local! {
+    static ENABLED: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Turn tracing on; called during initialization, so it is in the snapshot.
+pub(crate) fn enable() {
+    ENABLED.set(true);
+}
+
+pub(crate) fn enabled() -> bool {
+    ENABLED.get()
+}
+
+/// The name values of the function `name` of `iface` are traced under, or
+/// `None` when tracing is off.
+pub(crate) fn function(iface: Option<&str>, name: &str) -> Option<Box<str>> {
+    if !enabled() {
+        return None;
+    }
+    Some(match iface {
+        Some(iface) => format!("{iface}#{name}").into(),
+        None => name.into(),
+    })
+}
+
+/// Log `value` being lifted into JavaScript as a `ty` by `function`.
+pub(crate) fn lift(function: Option<&str>, ty: &str, value: &Value<'_>) {
+    log(function, "lift", ty, "->", value);
+}
+
+/// Log `value` being lowered out of JavaScript as a `ty` by `function`.
+pub(crate) fn lower(function: Option<&str>, ty: &str, value: &Value<'_>) {
+    log(function, "lower", ty, "<-", value);
+}
+
+fn log(function: Option<&str>, op: &str, ty: &str, arrow: &str, value: &Value<'_>) {
+    let value = describe(value);
+    match function {
+        Some(function) => eprintln!("[trace] {function}: {op} {ty} {arrow} {value}"),
+        None => eprintln!("[trace] {op} {ty} {arrow} {value}"),
+    }
+}
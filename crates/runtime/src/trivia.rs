@@ -1,9 +1,10 @@
-use crate::CtxExt;
-use crate::with_ctx;
+use crate::options::Naming;
+use crate::{CtxExt, DetIndexMap, with_ctx};
 
-use heck::ToLowerCamelCase;
-use rquickjs::{Atom, Function, Persistent, Symbol};
+use heck::{ToLowerCamelCase, ToUpperCamelCase};
+use rquickjs::{Atom, FromJs, Function, Object, Persistent, Symbol};
 use rquickjs::{Result, Value, function::Rest};
+use wit_dylib_ffi::{Type, Wit};
 
 /// Coerce closure lifetimes so the returned `Value<'js>` gets the same
 /// lifetime as the `Ctx<'js>` argument.
@@ -49,6 +50,26 @@ pub(crate) fn reject_promise(
     });
 }
 
+/// Run queued promise jobs (`then` callbacks, microtasks, async function
+/// continuations) until the queue is empty.
+pub(crate) fn run_pending_jobs(ctx: &rquickjs::Ctx<'_>) {
+    while ctx.execute_pending_job() {}
+}
+
+/// Fire the next due timer set with `setTimeout` or `setInterval`, first
+/// waiting for the earliest one if `block` is set. Returns whether a timer
+/// fired; always `false` in worlds without timers.
+///
+/// Timers exist when the world imports the 0.2 monotonic clock, whose
+/// pollables the shim blocks on.
+pub(crate) fn run_timer(ctx: &rquickjs::Ctx<'_>, block: bool) -> Result<bool> {
+    let run = ctx.user_module().shim_export::<Function>(ctx, "runTimer")?;
+    match run {
+        Some(run) => run.call((block,)),
+        None => Ok(false),
+    }
+}
+
 /// Get `Symbol.for("dispose")` via the rquickjs API.
 pub(crate) fn symbol_dispose<'js>(ctx: &rquickjs::Ctx<'js>) -> Result<Atom<'js>> {
     Ok(Symbol::new_global(ctx.clone(), "dispose")?.as_atom())
@@ -57,7 +78,7 @@ pub(crate) fn symbol_dispose<'js>(ctx: &rquickjs::Ctx<'js>) -> Result<Atom<'js>>
 /// Convert a WIT function name to lower camel case, caching the result.
 pub(crate) fn fn_lookup(ctx: &rquickjs::Ctx<'_>, name: &'static str) -> &'static str {
     let cache = ctx.fns();
-    let mut map = cache.0.borrow_mut();
+    let mut map = cache.names.borrow_mut();
 
     map.entry(name)
         .or_insert_with(|| Box::leak(name.to_lower_camel_case().into_boxed_str()))
@@ -71,8 +92,279 @@ pub(crate) fn iface_lookup(ctx: &rquickjs::Ctx<'_>, full_name: &'static str) ->
 
     let short = short.split('@').next().unwrap_or(short);
     let cache = ctx.fns();
-    let mut map = cache.0.borrow_mut();
+    let mut map = cache.names.borrow_mut();
 
     map.entry(full_name)
         .or_insert_with(|| Box::leak(short.to_lower_camel_case().into_boxed_str()))
 }
+
+/// Convert a WIT resource name to the upper camel case class name, caching
+/// the result.
+pub(crate) fn class_lookup(ctx: &rquickjs::Ctx<'_>, resource: &'static str) -> &'static str {
+    let cache = ctx.fns();
+    let mut map = cache.classes.borrow_mut();
+
+    map.entry(resource)
+        .or_insert_with(|| Box::leak(resource.to_upper_camel_case().into_boxed_str()))
+}
+
+/// The name of a WIT function as a member of its object: `name` for
+/// `[method]res.name` and `[static]res.name`, the function name otherwise.
+pub(crate) fn member_name(name: &'static str) -> &'static str {
+    match name.strip_prefix('[').and_then(|rest| rest.split_once('.')) {
+        Some((_, method)) => method,
+        None => name,
+    }
+}
+
+/// The names the JS implementation of the exported WIT function or method
+/// `name` of `iface` is looked up under: the preferred one, and one to fall
+/// back to.
+fn export_fn_names(
+    ctx: &rquickjs::Ctx<'_>,
+    iface: Option<&str>,
+    name: &'static str,
+) -> (&'static str, Option<&'static str>) {
+    if let Some(js_name) = ctx.options().rename(iface, name) {
+        return (js_name, None);
+    }
+    let name = member_name(name);
+    let camel = fn_lookup(ctx, name);
+    match ctx.options().naming {
+        Naming::CamelCase => (camel, None),
+        Naming::KebabCase => (name, None),
+        Naming::Both => (camel, Some(name).filter(|name| *name != camel)),
+    }
+}
+
+/// Get the JS implementation of the exported WIT function or method `name`
+/// of `iface` from `obj`, under the names the naming policy and renames allow.
+pub(crate) fn get_export_fn<'js, V: FromJs<'js>>(
+    ctx: &rquickjs::Ctx<'js>,
+    obj: &Object<'js>,
+    iface: Option<&str>,
+    name: &'static str,
+) -> Result<V> {
+    let (preferred, fallback) = export_fn_names(ctx, iface, name);
+    let value: Value = obj.get(preferred)?;
+    match fallback {
+        Some(fallback) if value.is_undefined() => obj.get(fallback),
+        _ => V::from_js(ctx, value),
+    }
+}
+
+/// The name an exported WIT function or method is reported under.
+pub(crate) fn export_fn_name(
+    ctx: &rquickjs::Ctx<'_>,
+    iface: Option<&str>,
+    name: &'static str,
+) -> &'static str {
+    export_fn_names(ctx, iface, name).0
+}
+
+/// Check that every rename names a function of the world.
+pub(crate) fn check_renames(ctx: &rquickjs::Ctx<'_>) -> std::result::Result<(), String> {
+    let wit = ctx.wit();
+    let funcs: Vec<(Option<&str>, &str)> = wit
+        .iter_export_funcs()
+        .map(|func| (func.interface(), func.name()))
+        .chain(
+            wit.iter_import_funcs()
+                .map(|func| (func.interface(), func.name())),
+        )
+        .collect();
+    let options = ctx.options();
+    let mut unknown: Vec<&str> = options
+        .renames
+        .keys()
+        .map(String::as_str)
+        .filter(|key| {
+            !funcs.iter().any(|(iface, name)| {
+                key == name
+                    || iface.is_some_and(|iface| {
+                        key.strip_prefix(iface)
+                            .and_then(|rest| rest.strip_prefix('#'))
+                            == Some(*name)
+                    })
+            })
+        })
+        .collect();
+    unknown.sort_unstable();
+    match unknown.first() {
+        Some(key) => Err(format!(
+            "cannot rename `{key}`: the world has no such function"
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Fill the name cache with every name the world's functions use.
+///
+/// Runs at initialization, so the converted names are part of the Wizer
+/// snapshot and calls never run `heck` on the hot path.
+pub(crate) fn precompute_names(ctx: &rquickjs::Ctx<'_>, wit: Wit) {
+    let exports = wit.iter_export_funcs().map(|func| {
+        let types: Vec<_> = func.params().chain(func.result()).collect();
+        (func.name(), func.interface(), types)
+    });
+    let imports = wit.iter_import_funcs().map(|func| {
+        let types: Vec<_> = func.params().chain(func.result()).collect();
+        (func.name(), func.interface(), types)
+    });
+
+    for (name, iface, types) in exports.chain(imports) {
+        match name.strip_prefix('[').and_then(|name| name.split_once(']')) {
+            // `[constructor]res`, `[method]res.name` or `[static]res.name`
+            Some((_, rest)) => match rest.split_once('.') {
+                Some((resource, method)) => {
+                    class_lookup(ctx, resource);
+                    fn_lookup(ctx, method);
+                }
+                None => {
+                    class_lookup(ctx, rest);
+                }
+            },
+            None => {
+                fn_lookup(ctx, name);
+            }
+        }
+        if let Some(iface) = iface {
+            iface_lookup(ctx, iface);
+        }
+        for ty in types {
+            precompute_type_names(ctx, ty);
+        }
+    }
+}
+
+fn precompute_type_names(ctx: &rquickjs::Ctx<'_>, ty: Type) {
+    match ty {
+        Type::Record(record) => {
+            for (name, ty) in record.fields() {
+                fn_lookup(ctx, name);
+                precompute_type_names(ctx, ty);
+            }
+        }
+        Type::Flags(flags) => {
+            for name in flags.names() {
+                fn_lookup(ctx, name);
+            }
+        }
+        Type::List(list) => precompute_type_names(ctx, list.ty()),
+        Type::Tuple(tuple) => tuple.types().for_each(|ty| precompute_type_names(ctx, ty)),
+        Type::Option(option) => precompute_type_names(ctx, option.ty()),
+        Type::Result(result) => result
+            .ok()
+            .into_iter()
+            .chain(result.err())
+            .for_each(|ty| precompute_type_names(ctx, ty)),
+        Type::Variant(variant) => variant
+            .cases()
+            .filter_map(|(_, ty)| ty)
+            .for_each(|ty| precompute_type_names(ctx, ty)),
+        Type::Alias(alias) => precompute_type_names(ctx, alias.ty()),
+        _ => {}
+    }
+}
+
+/// Find the JS object implementing the exported interface `full_name`.
+///
+/// An object registered under the qualified interface name, with or without
+/// its version, wins: either a module export (`export { api as "my:pkg/api" }`)
+/// or a global (`globalThis["my:pkg/api"] = { ... }`). Otherwise falls back to
+/// the module export named after the interface in lower camel case, which
+/// collides when two exported interfaces share a short name, and finally to the
+/// shim's adapter for the interface, such as the `wasi:http/incoming-handler`
+/// built from a default export with a `fetch` method.
+pub(crate) fn iface_object<'js>(
+    ctx: &rquickjs::Ctx<'js>,
+    exports: &Object<'js>,
+    full_name: &'static str,
+) -> Result<Object<'js>> {
+    if let Some(obj) = qualified_iface_object(ctx, exports, full_name)? {
+        return Ok(obj);
+    }
+
+    let name = iface_lookup(ctx, full_name);
+    if let Some(obj) = exports.get::<_, Option<Object>>(name)? {
+        return Ok(obj);
+    }
+    let adapter = ctx.user_module().adapter(ctx, exports, full_name)?;
+    match adapter {
+        Some(obj) => Ok(obj),
+        None => exports.get(name),
+    }
+}
+
+/// The object registered under the qualified name of the exported interface
+/// `full_name`, with or without its version, if any.
+fn qualified_iface_object<'js>(
+    ctx: &rquickjs::Ctx<'js>,
+    exports: &Object<'js>,
+    full_name: &'static str,
+) -> Result<Option<Object<'js>>> {
+    let unversioned = full_name.split_once('@').map(|(name, _)| name);
+    let globals = ctx.globals();
+
+    for name in std::iter::once(full_name).chain(unversioned) {
+        for scope in [exports, &globals] {
+            if let Some(obj) = scope.get::<_, Option<Object>>(name)? {
+                return Ok(Some(obj));
+            }
+        }
+    }
+    Ok(None)
+}
+
+/// Check that no two exported interfaces fall back to the same module export.
+///
+/// Interfaces sharing a short name, such as `a:b/api` and `c:d/api`, would
+/// both be implemented by `export const api`, so all but one of them must be
+/// registered under their qualified name.
+pub(crate) fn check_iface_collisions(
+    ctx: &rquickjs::Ctx<'_>,
+    exports: &Object<'_>,
+) -> std::result::Result<(), String> {
+    let mut by_name: DetIndexMap<&'static str, Vec<&'static str>> = DetIndexMap::default();
+    for iface in ctx
+        .wit()
+        .iter_export_funcs()
+        .filter_map(|func| func.interface())
+    {
+        let ifaces = by_name.entry(iface_lookup(ctx, iface)).or_default();
+        if !ifaces.contains(&iface) {
+            ifaces.push(iface);
+        }
+    }
+
+    let mut collisions = Vec::new();
+    for (name, ifaces) in by_name {
+        if ifaces.len() < 2 || !exports.contains_key(name).unwrap_or(false) {
+            continue;
+        }
+        let mut unqualified = Vec::new();
+        for iface in ifaces {
+            let qualified = qualified_iface_object(ctx, exports, iface)
+                .map_err(|e| format!("Failed to look up exported interface `{iface}`: {e}"))?;
+            if qualified.is_none() {
+                unqualified.push(format!("`{iface}`"));
+            }
+        }
+        if unqualified.len() > 1 {
+            collisions.push(format!(
+                "\n  {} all fall back to the module export `{name}`",
+                unqualified.join(", ")
+            ));
+        }
+    }
+
+    if collisions.is_empty() {
+        Ok(())
+    } else {
+        Err(format!(
+            "exported interfaces share a JavaScript name; export each one under its \
+             qualified name, as in `export {{ impl as \"my:pkg/api\" }}`:{}",
+            collisions.concat()
+        ))
+    }
+}
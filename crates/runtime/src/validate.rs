@@ -0,0 +1,563 @@
+//! Validation of values lowered from JavaScript.
+//!
+//! Lowering trusts a value to have the shape of its WIT type: integers are
+//! cast with `as`, wrapping out-of-range numbers, and a wrong shape panics
+//! deep inside the call with a message like "expected number". Values are
+//! therefore checked against their WIT type first, and a problem throws an
+//! error that JavaScript can catch, naming where in the value it is:
+//! `return value.points[1].x: expected f64, got string "1"`.
+//!
+//! By default only top-level integers and chars are checked, with a
+//! `RangeError`, so that the common case doesn't walk a value twice. An
+//! integer nested in a larger value is range checked as it is lowered, where
+//! it can only trap (see `call::check_range`), and a nested char traps unless
+//! something else in the value is checked. Strict types check the whole shape
+//! with a `TypeError`, builds that wrap integers skip the range check, and the
+//! float policy can reject non-finite floats and warn about `f32` values that
+//! lose precision.
+
+use std::cell::RefCell;
+use std::collections::BTreeSet;
+use std::fmt::Write;
+
+use rquickjs::{Coerced, Ctx, Exception, Result, Value};
+use wit_dylib_ffi::Type;
+
+use crate::CtxExt;
+use crate::call::{char_value, list_length, option_is_nested, set_values};
+use crate::options::RuntimeOptions;
+use crate::result::resolve_alias;
+use crate::trivia::fn_lookup;
+
+thread_local! {
+    /// Values already warned about, with list indices erased.
+    static WARNED: RefCell<BTreeSet<String>> = const { RefCell::new(BTreeSet::new()) };
+}
+
+/// What the runtime options ask to check.
+#[derive(Clone, Copy)]
+struct Checks {
+    /// The whole value against its WIT type.
+    strict: bool,
+    /// Integers against the range of their WIT type.
+    ranges: bool,
+    /// Floats are neither NaN nor infinite.
+    finite: bool,
+    /// Warn when a number loses precision as an `f32`.
+    f32_rounding: bool,
+}
+
+impl Checks {
+    fn new(options: &RuntimeOptions) -> Self {
+        Self {
+            strict: options.strict_types,
+            ranges: options.strict_types || !options.wrap_integers,
+            finite: options.finite_floats,
+            f32_rounding: options.warn_f32_rounding,
+        }
+    }
+
+    /// Whether some part of a value of type `ty` needs checking.
+    ///
+    /// Integers and chars count when they are the whole value: walking every
+    /// value that merely contains one, such as a list of records with a `u32`
+    /// field, would cost a full extra pass before lowering reads it again.
+    fn apply_to(self, ty: Type) -> bool {
+        self.strict
+            || match resolve_alias(ty) {
+                Type::Char => true,
+                Type::U8
+                | Type::S8
+                | Type::U16
+                | Type::S16
+                | Type::U32
+                | Type::S32
+                | Type::U64
+                | Type::S64 => self.ranges,
+                _ => false,
+            }
+            || contains(ty, &|leaf| match leaf {
+                Type::F32 => self.finite || self.f32_rounding,
+                Type::F64 => self.finite,
+                _ => false,
+            })
+    }
+}
+
+/// Check `values` about to be lowered as the paired WIT types, throwing at
+/// the first mismatch. `label(i)` names the `i`-th value in the message.
+pub(crate) fn check<'a, 'js: 'a>(
+    ctx: &Ctx<'js>,
+    label: impl Fn(usize) -> String,
+    values: impl IntoIterator<Item = (&'a Value<'js>, Type)>,
+) -> Result<()> {
+    let checks = Checks::new(&ctx.options());
+
+    for (index, (value, ty)) in values.into_iter().enumerate() {
+        if !checks.apply_to(ty) {
+            continue;
+        }
+        let mut path = label(index);
+        if let Some(problem) = mismatch(ctx, value, ty, &mut path, checks)? {
+            let message = format!("{path}: {problem}");
+            return Err(if checks.strict {
+                Exception::throw_type(ctx, &message)
+            } else {
+                Exception::throw_range(ctx, &message)
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Why `value` isn't a `ty`, if it isn't. On a mismatch `path` is left
+/// pointing at the offending part of the value.
+fn mismatch<'js>(
+    ctx: &Ctx<'js>,
+    value: &Value<'js>,
+    ty: Type,
+    path: &mut String,
+    checks: Checks,
+) -> Result<Option<String>> {
+    let strict = checks.strict;
+    let expected =
+        |what: &str| Ok(strict.then(|| format!("expected {what}, got {}", describe(value))));
+
+    match ty {
+        Type::Bool if value.is_bool() => Ok(None),
+        Type::Bool => expected("bool"),
+        Type::U8
+        | Type::S8
+        | Type::U16
+        | Type::S16
+        | Type::U32
+        | Type::S32
+        | Type::U64
+        | Type::S64
+            if !checks.ranges =>
+        {
+            Ok(None)
+        }
+        Type::U8
+        | Type::S8
+        | Type::U16
+        | Type::S16
+        | Type::U32
+        | Type::S32
+        | Type::U64
+        | Type::S64 => range(value, ty, strict),
+        Type::F32 | Type::F64 => match value.as_number() {
+            Some(n) => Ok(float(n, ty, path, checks)),
+            None if matches!(ty, Type::F32) => expected("f32"),
+            None => expected("f64"),
+        },
+        Type::Char if char_value(value).is_some() => Ok(None),
+        Type::Char => Ok(Some(match value.as_string() {
+            Some(s) => format!(
+                "expected a single code point for char, got {} ({} code points)",
+                describe(value),
+                s.to_string()?.chars().count()
+            ),
+            None if value.is_number() => format!(
+                "expected a Unicode scalar value for char, got {}",
+                describe(value)
+            ),
+            None => format!(
+                "expected char (a string of one code point or a code point number), got {}",
+                describe(value)
+            ),
+        })),
+        Type::String if value.is_string() => Ok(None),
+        Type::String => expected("string"),
+        Type::Enum(enum_ty) => {
+            let name = value.as_string().and_then(|s| s.to_string().ok());
+            if name.is_some_and(|name| enum_ty.names().any(|n| n == name)) {
+                return Ok(None);
+            }
+            expected(&one_of(enum_ty.names()))
+        }
+        Type::Flags(_) if value.is_object() => Ok(None),
+        Type::Flags(_) => expected("flags object"),
+        Type::Own(_) | Type::Borrow(_) if value.is_object() => Ok(None),
+        Type::Own(resource) | Type::Borrow(resource) => {
+            expected(&format!("{} resource", resource.name()))
+        }
+        Type::List(list) => {
+            if !checks.apply_to(list.ty()) {
+                return Ok(None);
+            }
+            if is_typed_array_of(value, list.ty()) {
+                if checks.finite
+                    && let Some((index, n)) = non_finite_element(value, list.ty())
+                {
+                    let _ = write!(path, "[{index}]");
+                    return Ok(Some(format!(
+                        "expected a finite float, got {}",
+                        js_number(n)
+                    )));
+                }
+                return Ok(None);
+            }
+            let values = match list_length(value) {
+                Some(_) => None,
+                None => set_values(ctx, value)?.map(|values| values.into_value()),
+            };
+            let value = values.as_ref().unwrap_or(value);
+            let (Some(obj), Some(length)) = (value.as_object(), list_length(value)) else {
+                return expected("list");
+            };
+            for index in 0..length {
+                let elem: Value = obj.get(index as u32)?;
+                let len = path.len();
+                let _ = write!(path, "[{index}]");
+                if let Some(problem) = mismatch(ctx, &elem, list.ty(), path, checks)? {
+                    return Ok(Some(problem));
+                }
+                path.truncate(len);
+            }
+            Ok(None)
+        }
+        Type::Tuple(tuple) => {
+            let Some(array) = value.as_array() else {
+                return expected(&format!("tuple of {}", tuple.types().len()));
+            };
+            if array.len() != tuple.types().len() {
+                return Ok(strict.then(|| {
+                    format!(
+                        "expected tuple of {}, got array of {}",
+                        tuple.types().len(),
+                        array.len()
+                    )
+                }));
+            }
+            for (index, elem_ty) in tuple.types().enumerate() {
+                let elem: Value = array.get(index)?;
+                let len = path.len();
+                let _ = write!(path, "[{index}]");
+                if let Some(problem) = mismatch(ctx, &elem, elem_ty, path, checks)? {
+                    return Ok(Some(problem));
+                }
+                path.truncate(len);
+            }
+            Ok(None)
+        }
+        Type::Record(record) => {
+            let Some(obj) = value.as_object().filter(|_| !value.is_array()) else {
+                return expected("record object");
+            };
+            if strict {
+                let fields: Vec<_> = record
+                    .fields()
+                    .map(|(name, _)| fn_lookup(ctx, name))
+                    .collect();
+                for key in obj.keys::<String>() {
+                    let key = key?;
+                    if !fields.contains(&key.as_str()) {
+                        return Ok(Some(format!(
+                            "unexpected field {key:?}, expected {}",
+                            one_of(fields.iter().copied())
+                        )));
+                    }
+                }
+            }
+            for (name, field_ty) in record.fields() {
+                let js_name = fn_lookup(ctx, name);
+                let field: Value = obj.get(js_name)?;
+                let len = path.len();
+                let _ = write!(path, ".{js_name}");
+                if let Some(problem) = mismatch(ctx, &field, field_ty, path, checks)? {
+                    return Ok(Some(problem));
+                }
+                path.truncate(len);
+            }
+            Ok(None)
+        }
+        Type::Option(option) => {
+            if value.is_null() || value.is_undefined() {
+                return Ok(None);
+            }
+            if !option_is_nested(option) {
+                return mismatch(ctx, value, option.ty(), path, checks);
+            }
+            tagged(ctx, value, path, checks, &["some", "none"], |tag| {
+                (tag == "some").then(|| option.ty())
+            })
+        }
+        Type::Result(result) => tagged(ctx, value, path, checks, &["ok", "err"], |tag| match tag {
+            "ok" => result.ok(),
+            _ => result.err(),
+        }),
+        Type::Variant(variant) => {
+            let cases: Vec<_> = variant.cases().map(|(name, _)| name).collect();
+            tagged(ctx, value, path, checks, &cases, |tag| {
+                variant
+                    .cases()
+                    .find(|(name, _)| *name == tag)
+                    .and_then(|(_, ty)| ty)
+            })
+        }
+        Type::Alias(alias) => mismatch(ctx, value, alias.ty(), path, checks),
+        // Futures, streams and error contexts are checked when lowered.
+        _ => Ok(None),
+    }
+}
+
+/// Largest integer a JavaScript number holds exactly.
+const MAX_SAFE_INTEGER: i128 = (1 << 53) - 1;
+
+/// Why `value` is out of range for the integer type `ty`, if it is, for
+/// lowering to check an integer `check` didn't look at.
+pub(crate) fn out_of_range(value: &Value<'_>, ty: Type) -> Option<String> {
+    range(value, ty, false).ok().flatten()
+}
+
+/// Check a number or `BigInt` against the range of the integer type `ty`.
+fn range(value: &Value<'_>, ty: Type, strict: bool) -> Result<Option<String>> {
+    // Strict mode only accepts 64-bit numbers that are exact; larger values
+    // must be a `BigInt`.
+    let safe = |bound: i128| {
+        if strict {
+            bound.clamp(-MAX_SAFE_INTEGER, MAX_SAFE_INTEGER)
+        } else {
+            bound
+        }
+    };
+
+    match ty {
+        Type::U32 | Type::U64 | Type::S64 if value.is_big_int() => big_int(value, ty),
+        Type::U8 => integer(value, u8::MIN.into(), u8::MAX.into(), "u8", strict),
+        Type::S8 => integer(value, i8::MIN.into(), i8::MAX.into(), "s8", strict),
+        Type::U16 => integer(value, u16::MIN.into(), u16::MAX.into(), "u16", strict),
+        Type::S16 => integer(value, i16::MIN.into(), i16::MAX.into(), "s16", strict),
+        Type::U32 => integer(value, u32::MIN.into(), u32::MAX.into(), "u32", strict),
+        Type::S32 => integer(value, i32::MIN.into(), i32::MAX.into(), "s32", strict),
+        Type::U64 => integer(value, 0, safe(u64::MAX.into()), "u64", strict),
+        Type::S64 => integer(
+            value,
+            safe(i64::MIN.into()),
+            safe(i64::MAX.into()),
+            "s64",
+            strict,
+        ),
+        _ => Ok(None),
+    }
+}
+
+/// Check a number against the integer range `min..=max`. Fractions are only
+/// rejected in strict mode.
+fn integer(
+    value: &Value<'_>,
+    min: i128,
+    max: i128,
+    name: &str,
+    strict: bool,
+) -> Result<Option<String>> {
+    let Some(n) = value.as_number().filter(|n| !strict || n.fract() == 0.0) else {
+        return Ok(strict.then(|| format!("expected {name}, got {}", describe(value))));
+    };
+    if (min as f64..=max as f64).contains(&n.trunc()) {
+        return Ok(None);
+    }
+    Ok(Some(format!(
+        "expected {name}, got {n}, which is out of range ({min} to {max})"
+    )))
+}
+
+/// Check a `BigInt` against the range of the 64-bit or `u32` type `ty`.
+fn big_int(value: &Value<'_>, ty: Type) -> Result<Option<String>> {
+    let (min, max, name): (i128, i128, _) = match ty {
+        Type::U32 => (0, u32::MAX.into(), "u32"),
+        Type::U64 => (0, u64::MAX.into(), "u64"),
+        _ => (i64::MIN.into(), i64::MAX.into(), "s64"),
+    };
+    let digits = value.get::<Coerced<String>>()?.0;
+    if digits
+        .parse::<i128>()
+        .is_ok_and(|n| (min..=max).contains(&n))
+    {
+        return Ok(None);
+    }
+    Ok(Some(format!(
+        "expected {name}, got BigInt {digits}, which is out of range ({min} to {max})"
+    )))
+}
+
+/// Check a number lowered as the float type `ty` against the float policy,
+/// warning on stderr the first time a value at `path` loses precision as an
+/// `f32`.
+fn float(n: f64, ty: Type, path: &str, checks: Checks) -> Option<String> {
+    if checks.finite && !n.is_finite() {
+        return Some(format!("expected a finite float, got {}", js_number(n)));
+    }
+    if checks.f32_rounding && matches!(ty, Type::F32) && n.is_finite() && f64::from(n as f32) != n {
+        // Warn once per value rather than once per list element.
+        let mut key = String::with_capacity(path.len());
+        let mut in_index = false;
+        for c in path.chars() {
+            match c {
+                '[' => in_index = true,
+                ']' => in_index = false,
+                _ if in_index => continue,
+                _ => {}
+            }
+            key.push(c);
+        }
+        if WARNED.with_borrow_mut(|warned| warned.insert(key)) {
+            eprintln!(
+                "warning: {path}: {} is not exact as an f32 and becomes {}",
+                js_number(n),
+                n as f32
+            );
+        }
+    }
+    None
+}
+
+/// The first non-finite element of a float typed array lowered as a list of
+/// `elem`.
+fn non_finite_element(value: &Value<'_>, elem: Type) -> Option<(usize, f64)> {
+    let obj = value.as_object()?;
+    match elem {
+        Type::F32 => {
+            let array = obj.as_typed_array::<f32>()?;
+            let slice: &[f32] = array.as_ref();
+            let index = slice.iter().position(|n| !n.is_finite())?;
+            Some((index, slice[index].into()))
+        }
+        Type::F64 => {
+            let array = obj.as_typed_array::<f64>()?;
+            let slice: &[f64] = array.as_ref();
+            let index = slice.iter().position(|n| !n.is_finite())?;
+            Some((index, slice[index]))
+        }
+        _ => None,
+    }
+}
+
+/// Format a number the way JavaScript prints it, for non-finite values.
+fn js_number(n: f64) -> String {
+    match n {
+        n if n.is_nan() => "NaN".to_string(),
+        f64::INFINITY => "Infinity".to_string(),
+        f64::NEG_INFINITY => "-Infinity".to_string(),
+        n => n.to_string(),
+    }
+}
+
+/// Whether `ty` contains a type matching `leaf`.
+fn contains(ty: Type, leaf: &impl Fn(Type) -> bool) -> bool {
+    match ty {
+        Type::List(list) => contains(list.ty(), leaf),
+        Type::Tuple(tuple) => tuple.types().any(|ty| contains(ty, leaf)),
+        Type::Record(record) => record.fields().any(|(_, ty)| contains(ty, leaf)),
+        Type::Option(option) => contains(option.ty(), leaf),
+        Type::Result(result) => result
+            .ok()
+            .into_iter()
+            .chain(result.err())
+            .any(|ty| contains(ty, leaf)),
+        Type::Variant(variant) => variant
+            .cases()
+            .filter_map(|(_, ty)| ty)
+            .any(|ty| contains(ty, leaf)),
+        Type::Alias(alias) => contains(alias.ty(), leaf),
+        ty => leaf(ty),
+    }
+}
+
+/// Check a `{ tag, val }` object whose tag is one of `tags`, and its payload
+/// against the type `case_ty` gives for the tag.
+fn tagged<'js>(
+    ctx: &Ctx<'js>,
+    value: &Value<'js>,
+    path: &mut String,
+    checks: Checks,
+    tags: &[&str],
+    case_ty: impl FnOnce(&str) -> Option<Type>,
+) -> Result<Option<String>> {
+    let tag = value
+        .as_object()
+        .map(|obj| obj.get::<_, Value>("tag"))
+        .transpose()?;
+    let Some(tag) = tag
+        .as_ref()
+        .and_then(|tag| tag.as_string())
+        .and_then(|tag| tag.to_string().ok())
+        .filter(|tag| tags.contains(&tag.as_str()))
+    else {
+        if !checks.strict {
+            return Ok(None);
+        }
+        return Ok(Some(match tag {
+            Some(tag) => format!(
+                "expected tag {}, got {}",
+                one_of(tags.iter().copied()),
+                describe(&tag)
+            ),
+            None => format!("expected {{ tag, val }} object, got {}", describe(value)),
+        }));
+    };
+
+    let Some(ty) = case_ty(&tag) else {
+        return Ok(None);
+    };
+    let payload: Value = value.as_object().expect("checked above").get("val")?;
+    let len = path.len();
+    path.push_str(".val");
+    let problem = mismatch(ctx, &payload, ty, path, checks)?;
+    if problem.is_none() {
+        path.truncate(len);
+    }
+    Ok(problem)
+}
+
+/// Whether `value` is a typed array that lowers directly as a list of `elem`.
+fn is_typed_array_of(value: &Value<'_>, elem: Type) -> bool {
+    macro_rules! typed_array {
+        ($t:ty) => {
+            value
+                .as_object()
+                .is_some_and(|obj| obj.as_typed_array::<$t>().is_some())
+        };
+    }
+
+    match elem {
+        Type::U8 => typed_array!(u8),
+        Type::S8 => typed_array!(i8),
+        Type::U16 => typed_array!(u16),
+        Type::S16 => typed_array!(i16),
+        Type::U32 => typed_array!(u32),
+        Type::S32 => typed_array!(i32),
+        Type::U64 => typed_array!(u64),
+        Type::S64 => typed_array!(i64),
+        Type::F32 => typed_array!(f32),
+        Type::F64 => typed_array!(f64),
+        _ => false,
+    }
+}
+
+fn one_of<'a>(names: impl Iterator<Item = &'a str>) -> String {
+    let names: Vec<_> = names.map(|name| format!("{name:?}")).collect();
+    format!("one of {}", names.join(", "))
+}
+
+/// Short description of a value for diagnostics, e.g. `string "1"`.
+pub(crate) fn describe(value: &Value<'_>) -> String {
+    if let Some(s) = value.as_string() {
+        let s = s.to_string().unwrap_or_default();
+        return match s.char_indices().nth(32) {
+            Some((end, _)) => format!("string {:?}...", &s[..end]),
+            None => format!("string {s:?}"),
+        };
+    }
+    if let Some(n) = value.as_number() {
+        return format!("number {n}");
+    }
+    if let Some(b) = value.as_bool() {
+        return format!("boolean {b}");
+    }
+    if value.is_array() {
+        return "array".to_string();
+    }
+    value.type_name().to_string()
+}
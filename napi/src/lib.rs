@@ -1,4 +1,5 @@
-use std::path::PathBuf;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 
 use clap::error::ErrorKind;
 use napi::bindgen_prelude::*;
@@ -8,19 +9,144 @@ use napi_derive::napi;
 #[napi(object)]
 pub struct ComponentizeOpts {
     /// Path to the WIT file or directory
-    pub wit_path: String,
-    /// JavaScript source code
-    pub js_source: String,
+    pub wit_path: Option<String>,
+    /// WIT source, parsed instead of reading `wit_path`
+    pub wit_source: Option<String>,
+    /// Binary WIT package, decoded instead of reading `wit_path`
+    pub wit_package: Option<Buffer>,
+    /// JavaScript source code of the entry module
+    pub js_source: Option<String>,
     /// Path to the JavaScript entry file, used as the base for resolving imports
     pub js_path: Option<String>,
     /// Root directory exposed during Wizer for resolving JavaScript imports
     pub module_root: Option<String>,
+    /// Environment variables visible during Wizer initialization
+    pub init_env: Option<HashMap<String, String>>,
+    /// Command-line arguments visible during Wizer initialization
+    pub init_args: Option<Vec<String>>,
+    /// Host directories preopened read-only during Wizer initialization, by
+    /// guest path
+    pub init_dirs: Option<HashMap<String, String>>,
+    /// Additional JavaScript modules by name, importable by the entry module
+    /// and each other
+    pub modules: Option<HashMap<String, String>>,
+    /// Name of the module in `modules` to evaluate as the entry module, in
+    /// place of `jsSource`
+    pub entry_module: Option<String>,
+    /// Scripts evaluated in order in the global scope before the entry module
+    pub preludes: Option<Vec<String>>,
     /// World name to use from the WIT (omit for default world)
     pub world: Option<String>,
     /// Stub all WASI imports with traps (default: false)
     pub stub_wasi: Option<bool>,
     /// Disable automatic garbage collection (default: false)
     pub disable_gc: Option<bool>,
+    /// Drop the source text of JavaScript functions from the component
+    /// (default: false)
+    pub strip_source: Option<bool>,
+    /// Compile the entry module to QuickJS bytecode and evaluate that during
+    /// initialization instead of its source (default: false)
+    pub bytecode: Option<bool>,
+    /// Evaluate the JavaScript module on the first export call instead of
+    /// during Wizer (default: false)
+    pub lazy_eval: Option<bool>,
+    /// Run a full garbage collection cycle after every this many export calls
+    pub gc_interval: Option<u32>,
+    /// Start an automatic garbage collection cycle once this many bytes were
+    /// allocated since the last one
+    pub gc_threshold: Option<u32>,
+    /// Reject lists (in elements) and strings (in UTF-8 bytes) longer than this
+    /// when lowering values from JavaScript
+    pub max_list_length: Option<u32>,
+    /// Cap the QuickJS heap at this many bytes
+    pub memory_limit: Option<u32>,
+    /// Print heap usage to stderr after every this many export calls
+    pub memory_report: Option<u32>,
+    /// Trap when a call into JavaScript runs more than about this many bytecode
+    /// instructions
+    pub max_instructions: Option<i64>,
+    /// Fail the build when Wizer initialization runs longer than this many
+    /// milliseconds
+    pub init_timeout: Option<u32>,
+    /// Check values lowered from JavaScript against their WIT type, throwing a
+    /// `TypeError` naming the mismatch (default: false)
+    pub strict_types: Option<bool>,
+    /// Pass top-level `result` values as `{ tag, val }` objects instead of
+    /// return/throw (default: false)
+    pub tagged_results: Option<bool>,
+    /// With `tagged_results`, still return the `ok` payload of imports and
+    /// throw the `err` one (default: false)
+    pub throwing_imports: Option<bool>,
+    /// Wrap out-of-range integers lowered from JavaScript instead of throwing a
+    /// `RangeError` (default: false)
+    pub wrap_integers: Option<bool>,
+    /// Reject NaN and infinite floats lowered from JavaScript with a
+    /// `RangeError` (default: false)
+    pub finite_floats: Option<bool>,
+    /// Warn on stderr when a number lowered as an `f32` loses precision
+    /// (default: false)
+    pub warn_f32_rounding: Option<bool>,
+    /// Log every value lifted into or lowered out of JavaScript to stderr
+    /// (default: false)
+    pub trace_values: Option<bool>,
+    /// Feature flags exposed to JavaScript through the read-only `__FEATURES__` set
+    pub features: Option<Vec<String>>,
+    /// Globals installed on top of plain QuickJS (default: the default
+    /// polyfills and every global generated from WASI imports)
+    pub env_shims: Option<EnvShims>,
+    /// Fail the build on initialization warnings, such as exports whose
+    /// parameter count differs from their WIT signature (default: false)
+    pub deny_warnings: Option<bool>,
+    /// What to do with exports declaring more or fewer parameters than their
+    /// WIT signature: `"ignore"`, `"warn"` or `"error"` (default: `"warn"`)
+    pub arity_mismatch: Option<String>,
+    /// Replace `Math.random`: `"wasi"` for wasi:random entropy or `"seed:<N>"`
+    /// for a reproducible sequence
+    pub math_random: Option<String>,
+    /// Exports to call once during initialization so the snapshot starts
+    /// warm, each as `"<export>=<JSON array of arguments>"`
+    pub prime: Option<Vec<String>>,
+    /// Which shortened names import a versioned WIT interface: `"none"`,
+    /// `"strip-patch"` or `"strip-all"` (default: `"strip-all"`)
+    pub version_aliasing: Option<String>,
+    /// Extra import specifiers for WIT interfaces, each as
+    /// `"<specifier>=<interface>"`
+    pub import_aliases: Option<Vec<String>>,
+    /// Names of WIT functions in JavaScript: `"camel"`, `"kebab"` or `"both"`
+    /// (default: `"camel"`)
+    pub naming: Option<String>,
+    /// JavaScript names for individual WIT functions, each as
+    /// `"<function>=<js-name>"`
+    pub renames: Option<Vec<String>>,
+    /// Freeze the intrinsics, polyfills and WIT import objects once the
+    /// JavaScript module is evaluated (default: false)
+    pub harden: Option<bool>,
+    /// Record sync import calls and their results to this guest file at runtime
+    pub record_imports: Option<String>,
+    /// Satisfy sync import calls from a capture recorded with `recordImports`
+    pub replay_imports: Option<String>,
+    /// Run the snapshotted component's core modules through wasm-opt, for
+    /// `speed` or `size`
+    pub optimize: Option<String>,
+    /// Return a single core module importing WASI directly instead of a
+    /// component; needs `sync`
+    pub core_module: Option<bool>,
+    /// Custom sections to append to the component, by name
+    pub custom_sections: Option<HashMap<String, Buffer>>,
+    /// Name to record in the component's metadata
+    pub metadata_name: Option<String>,
+    /// Version to record in the component's metadata
+    pub metadata_version: Option<String>,
+    /// Description to record in the component's metadata
+    pub metadata_description: Option<String>,
+    /// Leave the producers and metadata sections out of the component
+    /// (default: false)
+    pub no_metadata: Option<bool>,
+    /// Build byte-identical components from identical inputs, pinning the
+    /// clocks and randomness initialization sees (default: false)
+    pub reproducible: Option<bool>,
+    /// Cache compiled components here so JavaScript-only rebuilds skip compilation
+    pub cache_dir: Option<String>,
     /// Use the built-in runtime optimized for smaller generated components
     pub opt_size: Option<bool>,
     /// Use the built-in non-async runtime, producing components that do not use
@@ -30,13 +156,58 @@ pub struct ComponentizeOpts {
     pub runtime: Option<String>,
     /// Custom QuickJS runtime Wasm bytes
     pub runtime_bytes: Option<Buffer>,
+    /// Use the debug runtime, with assertions and function names, to diagnose
+    /// a failing component
+    pub debug_runtime: Option<bool>,
 }
 
 /// Result of componentizing a JavaScript source.
+/// Globals installed on top of plain QuickJS.
+#[napi(object)]
+#[derive(Default)]
+pub struct EnvShims {
+    /// Bundled polyfills to install in addition to the defaults
+    pub polyfills: Option<Vec<String>>,
+    /// Install only the polyfills listed in `polyfills` (default: false)
+    pub no_default_polyfills: Option<bool>,
+    /// Leave out the globals generated from the world's WASI imports, such as
+    /// `fetch`, `env` and `fs` (default: false)
+    pub no_wasi_globals: Option<bool>,
+}
+
 #[napi(object)]
 pub struct ComponentizeResult {
     /// The WebAssembly component bytes
     pub component: Buffer,
+    /// Warnings from initialization, such as exports the JavaScript doesn't
+    /// define
+    pub warnings: Vec<String>,
+    /// Output of the JavaScript to stdout during initialization
+    pub init_stdout: String,
+    /// Output of the JavaScript to stderr during initialization
+    pub init_stderr: String,
+    /// Core modules embedded in the component, in order
+    pub modules: Vec<ModuleReport>,
+}
+
+/// A core module embedded in the component.
+#[napi(object)]
+pub struct ModuleReport {
+    /// Name from the module's `name` section, if it has one
+    pub name: Option<String>,
+    /// Module size in bytes
+    pub size: u32,
+    /// Custom sections of the module, in order
+    pub custom_sections: Vec<CustomSection>,
+}
+
+/// A custom section of a core module.
+#[napi(object)]
+pub struct CustomSection {
+    /// Section name
+    pub name: String,
+    /// Section size in bytes
+    pub size: u32,
 }
 
 /// Convert JavaScript source code into a WebAssembly component.
@@ -45,12 +216,42 @@ pub struct ComponentizeResult {
 /// WebAssembly component using the QuickJS runtime.
 #[napi]
 pub async fn componentize(opts: ComponentizeOpts) -> Result<ComponentizeResult> {
-    let wit_path = PathBuf::from(&opts.wit_path);
+    let given = [
+        opts.wit_path.is_some(),
+        opts.wit_source.is_some(),
+        opts.wit_package.is_some(),
+    ];
+    if given.iter().filter(|given| **given).count() > 1 {
+        return Err(Error::new(
+            Status::InvalidArg,
+            "only one of `witPath`, `witSource` or `witPackage` may be given",
+        ));
+    }
+    let wit_path = match &opts.wit_path {
+        Some(path) => {
+            let wit_path = PathBuf::from(path);
+            if !wit_path.exists() {
+                return Err(Error::new(
+                    Status::InvalidArg,
+                    format!("WIT file/directory not found: {path}"),
+                ));
+            }
+            wit_path
+        }
+        None if opts.wit_source.is_some() => PathBuf::from("<witSource>.wit"),
+        None if opts.wit_package.is_some() => PathBuf::from("<witPackage>.wasm"),
+        None => {
+            return Err(Error::new(
+                Status::InvalidArg,
+                "one of `witPath`, `witSource` or `witPackage` is required",
+            ));
+        }
+    };
 
-    if !wit_path.exists() {
+    if opts.js_source.is_some() == opts.entry_module.is_some() {
         return Err(Error::new(
             Status::InvalidArg,
-            format!("WIT file/directory not found: {}", opts.wit_path),
+            "exactly one of `jsSource` or `entryModule` must be given",
         ));
     }
 
@@ -117,23 +318,208 @@ pub async fn componentize(opts: ComponentizeOpts) -> Result<ComponentizeResult>
         },
     };
 
+    let features = opts.features.unwrap_or_default();
+    let env_shims = opts.env_shims.unwrap_or_default();
+    let polyfills = env_shims.polyfills.unwrap_or_default();
+    let import_log = match (&opts.record_imports, &opts.replay_imports) {
+        (Some(_), Some(_)) => {
+            return Err(Error::new(
+                Status::InvalidArg,
+                "recordImports and replayImports are mutually exclusive".to_string(),
+            ));
+        }
+        (Some(path), None) => Some(componentize_qjs::ImportLog::Record(path)),
+        (None, Some(path)) => Some(componentize_qjs::ImportLog::Replay(path)),
+        (None, None) => None,
+    };
+    let math_random = opts
+        .math_random
+        .as_deref()
+        .map(str::parse)
+        .transpose()
+        .map_err(|e| Error::new(Status::InvalidArg, format!("{e}")))?;
+    let prime = opts
+        .prime
+        .unwrap_or_default()
+        .iter()
+        .map(|call| call.parse())
+        .collect::<anyhow::Result<Vec<_>>>()
+        .map_err(|e| Error::new(Status::InvalidArg, format!("{e}")))?;
+    let arity_mismatch = opts
+        .arity_mismatch
+        .as_deref()
+        .map(str::parse)
+        .transpose()
+        .map_err(|e| Error::new(Status::InvalidArg, format!("{e}")))?
+        .unwrap_or_default();
+    let version_aliasing = opts
+        .version_aliasing
+        .as_deref()
+        .map(str::parse)
+        .transpose()
+        .map_err(|e| Error::new(Status::InvalidArg, format!("{e}")))?
+        .unwrap_or_default();
+    let optimize = opts
+        .optimize
+        .as_deref()
+        .map(str::parse)
+        .transpose()
+        .map_err(|e| Error::new(Status::InvalidArg, format!("{e}")))?;
+    let naming = opts
+        .naming
+        .as_deref()
+        .map(str::parse)
+        .transpose()
+        .map_err(|e| Error::new(Status::InvalidArg, format!("{e}")))?
+        .unwrap_or_default();
+    let import_aliases = opts
+        .import_aliases
+        .unwrap_or_default()
+        .iter()
+        .map(|alias| alias.parse())
+        .collect::<anyhow::Result<Vec<_>>>()
+        .map_err(|e| Error::new(Status::InvalidArg, format!("{e}")))?;
+    let renames = opts
+        .renames
+        .unwrap_or_default()
+        .iter()
+        .map(|rename| rename.parse())
+        .collect::<anyhow::Result<Vec<_>>>()
+        .map_err(|e| Error::new(Status::InvalidArg, format!("{e}")))?;
+    let init_env: Vec<(String, String)> = opts
+        .init_env
+        .clone()
+        .unwrap_or_default()
+        .into_iter()
+        .collect();
+    // Sorted by name so builds are reproducible.
+    let mut custom_sections: Vec<(String, Vec<u8>)> = opts
+        .custom_sections
+        .as_ref()
+        .map(|sections| {
+            sections
+                .iter()
+                .map(|(name, data)| (name.clone(), data.to_vec()))
+                .collect()
+        })
+        .unwrap_or_default();
+    custom_sections.sort();
+    let init_dirs: Vec<componentize_qjs::InitDir> = opts
+        .init_dirs
+        .clone()
+        .unwrap_or_default()
+        .into_iter()
+        .map(|(guest, host)| componentize_qjs::InitDir {
+            host: PathBuf::from(host),
+            guest,
+        })
+        .collect();
+    let modules: Vec<(String, String)> = opts
+        .modules
+        .clone()
+        .unwrap_or_default()
+        .into_iter()
+        .collect();
+    let preludes: Vec<(String, String)> = opts
+        .preludes
+        .clone()
+        .unwrap_or_default()
+        .into_iter()
+        .enumerate()
+        .map(|(index, source)| (format!("preludes[{index}]"), source))
+        .collect();
     let opts = componentize_qjs::ComponentizeOpts {
         wit_path: &wit_path,
-        js_source: &opts.js_source,
+        wit_source: opts.wit_source.as_deref(),
+        wit_package: opts.wit_package.as_deref(),
+        js_source: opts.js_source.as_deref().unwrap_or_default(),
         js_path: js_path.as_deref(),
         module_root: module_root.as_deref(),
+        init_env: &init_env,
+        init_args: opts.init_args.as_deref().unwrap_or_default(),
+        init_dirs: &init_dirs,
+        modules: &modules,
+        entry_module: opts.entry_module.as_deref(),
+        preludes: &preludes,
         world_name: opts.world.as_deref(),
         stub_wasi: opts.stub_wasi.unwrap_or(false),
         disable_gc: opts.disable_gc.unwrap_or(false),
+        strip_source: opts.strip_source.unwrap_or(false),
+        bytecode: opts.bytecode.unwrap_or(false),
+        lazy_eval: opts.lazy_eval.unwrap_or(false),
+        gc_interval: opts.gc_interval,
+        gc_threshold: opts.gc_threshold,
+        max_list_length: opts.max_list_length,
+        memory_limit: opts.memory_limit,
+        memory_report: opts.memory_report,
+        max_instructions: opts.max_instructions.map(|n| u64::try_from(n).unwrap_or(0)),
+        init_timeout: opts
+            .init_timeout
+            .map(|ms| std::time::Duration::from_millis(ms.into())),
+        strict_types: opts.strict_types.unwrap_or(false),
+        tagged_results: opts.tagged_results.unwrap_or(false),
+        throwing_imports: opts.throwing_imports.unwrap_or(false),
+        wrap_integers: opts.wrap_integers.unwrap_or(false),
+        finite_floats: opts.finite_floats.unwrap_or(false),
+        warn_f32_rounding: opts.warn_f32_rounding.unwrap_or(false),
+        trace_values: opts.trace_values.unwrap_or(false),
+        features: &features,
+        env_shims: componentize_qjs::EnvShims {
+            polyfills: &polyfills,
+            no_default_polyfills: env_shims.no_default_polyfills.unwrap_or(false),
+            no_wasi_globals: env_shims.no_wasi_globals.unwrap_or(false),
+        },
+        deny_warnings: opts.deny_warnings.unwrap_or(false),
+        arity_mismatch,
+        math_random,
+        prime: &prime,
+        version_aliasing,
+        import_aliases: &import_aliases,
+        naming,
+        renames: &renames,
+        harden: opts.harden.unwrap_or(false),
+        import_log,
+        optimize,
+        core_module: opts.core_module.unwrap_or(false),
+        custom_sections: &custom_sections,
+        metadata: componentize_qjs::Metadata {
+            suppress: opts.no_metadata.unwrap_or(false),
+            name: opts.metadata_name.as_deref(),
+            version: opts.metadata_version.as_deref(),
+            description: opts.metadata_description.as_deref(),
+        },
+        reproducible: opts.reproducible.unwrap_or(false),
+        cache_dir: opts.cache_dir.as_deref().map(Path::new),
         runtime,
+        debug_runtime: opts.debug_runtime.unwrap_or(false),
     };
 
-    let component = componentize_qjs::componentize(&opts)
+    let (component, report) = componentize_qjs::componentize_with_report(&opts)
         .await
         .map_err(|e| Error::new(Status::GenericFailure, format!("{e:#}")))?;
 
+    let size = |bytes: usize| u32::try_from(bytes).unwrap_or(u32::MAX);
     Ok(ComponentizeResult {
         component: component.into(),
+        warnings: report.warnings,
+        init_stdout: report.init_stdout,
+        init_stderr: report.init_stderr,
+        modules: report
+            .modules
+            .into_iter()
+            .map(|module| ModuleReport {
+                name: module.name,
+                size: size(module.size),
+                custom_sections: module
+                    .custom_sections
+                    .into_iter()
+                    .map(|(name, bytes)| CustomSection {
+                        name,
+                        size: size(bytes),
+                    })
+                    .collect(),
+            })
+            .collect(),
     })
 }
 
@@ -11,19 +11,203 @@ export declare function componentize(opts: ComponentizeOpts): Promise<Componenti
 /** Options for componentizing a JavaScript source into a WebAssembly component. */
 export interface ComponentizeOpts {
   /** Path to the WIT file or directory */
-  witPath: string
-  /** JavaScript source code */
-  jsSource: string
+  witPath?: string
+  /** WIT source, parsed instead of reading `witPath` */
+  witSource?: string
+  /** Binary WIT package, decoded instead of reading `witPath` */
+  witPackage?: Buffer
+  /** JavaScript source code of the entry module */
+  jsSource?: string
   /** Path to the JavaScript entry file, used as the base for resolving imports */
   jsPath?: string
   /** Root directory exposed during Wizer for resolving JavaScript imports */
   moduleRoot?: string
+  /** Environment variables visible during Wizer initialization */
+  initEnv?: Record<string, string>
+  /** Command-line arguments visible during Wizer initialization */
+  initArgs?: Array<string>
+  /**
+   * Host directories preopened read-only during Wizer initialization, by
+   * guest path
+   */
+  initDirs?: Record<string, string>
+  /**
+   * Additional JavaScript modules by name, importable by the entry module
+   * and each other
+   */
+  modules?: Record<string, string>
+  /**
+   * Name of the module in `modules` to evaluate as the entry module, in
+   * place of `jsSource`
+   */
+  entryModule?: string
+  /** Scripts evaluated in order in the global scope before the entry module */
+  preludes?: Array<string>
   /** World name to use from the WIT (omit for default world) */
   world?: string
   /** Stub all WASI imports with traps (default: false) */
   stubWasi?: boolean
   /** Disable automatic garbage collection (default: false) */
   disableGc?: boolean
+  /** Drop the source text of JavaScript functions from the component (default: false) */
+  stripSource?: boolean
+  /**
+   * Compile the entry module to QuickJS bytecode and evaluate that during
+   * initialization instead of its source (default: false)
+   */
+  bytecode?: boolean
+  /**
+   * Evaluate the JavaScript module on the first export call instead of during
+   * Wizer (default: false)
+   */
+  lazyEval?: boolean
+  /** Run a full garbage collection cycle after every this many export calls */
+  gcInterval?: number
+  /**
+   * Start an automatic garbage collection cycle once this many bytes were
+   * allocated since the last one
+   */
+  gcThreshold?: number
+  /**
+   * Reject lists (in elements) and strings (in UTF-8 bytes) longer than this
+   * when lowering values from JavaScript
+   */
+  maxListLength?: number
+  /** Cap the QuickJS heap at this many bytes */
+  memoryLimit?: number
+  /** Print heap usage to stderr after every this many export calls */
+  memoryReport?: number
+  /**
+   * Trap when a call into JavaScript runs more than about this many bytecode
+   * instructions
+   */
+  maxInstructions?: number
+  /**
+   * Fail the build when Wizer initialization runs longer than this many
+   * milliseconds
+   */
+  initTimeout?: number
+  /**
+   * Check values lowered from JavaScript against their WIT type, throwing a
+   * `TypeError` naming the mismatch (default: false)
+   */
+  strictTypes?: boolean
+  /**
+   * Pass top-level `result` values as `{ tag, val }` objects instead of
+   * return/throw (default: false)
+   */
+  taggedResults?: boolean
+  /**
+   * With `taggedResults`, still return the `ok` payload of imports and throw
+   * the `err` one (default: false)
+   */
+  throwingImports?: boolean
+  /**
+   * Wrap out-of-range integers lowered from JavaScript instead of throwing a
+   * `RangeError` (default: false)
+   */
+  wrapIntegers?: boolean
+  /**
+   * Reject NaN and infinite floats lowered from JavaScript with a
+   * `RangeError` (default: false)
+   */
+  finiteFloats?: boolean
+  /**
+   * Warn on stderr when a number lowered as an `f32` loses precision
+   * (default: false)
+   */
+  warnF32Rounding?: boolean
+  /**
+   * Log every value lifted into or lowered out of JavaScript to stderr
+   * (default: false)
+   */
+  traceValues?: boolean
+  /** Feature flags exposed to JavaScript through the read-only `__FEATURES__` set */
+  features?: Array<string>
+  /**
+   * Globals installed on top of plain QuickJS (default: the default
+   * polyfills and every global generated from WASI imports)
+   */
+  envShims?: EnvShims
+  /**
+   * Fail the build on initialization warnings, such as exports whose parameter
+   * count differs from their WIT signature (default: false)
+   */
+  denyWarnings?: boolean
+  /**
+   * What to do with exports declaring more or fewer parameters than their WIT
+   * signature: `"ignore"`, `"warn"` or `"error"` (default: `"warn"`)
+   */
+  arityMismatch?: string
+  /**
+   * Replace `Math.random`: `"wasi"` for wasi:random entropy or `"seed:<N>"`
+   * for a reproducible sequence
+   */
+  mathRandom?: string
+  /**
+   * Exports to call once during initialization so the snapshot starts
+   * warm, each as `"<export>=<JSON array of arguments>"`
+   */
+  prime?: Array<string>
+  /**
+   * Which shortened names import a versioned WIT interface: `"none"`,
+   * `"strip-patch"` or `"strip-all"` (default: `"strip-all"`)
+   */
+  versionAliasing?: string
+  /**
+   * Extra import specifiers for WIT interfaces, each as
+   * `"<specifier>=<interface>"`
+   */
+  importAliases?: Array<string>
+  /**
+   * Names of WIT functions in JavaScript: `"camel"`, `"kebab"` or `"both"`
+   * (default: `"camel"`)
+   */
+  naming?: string
+  /**
+   * JavaScript names for individual WIT functions, each as
+   * `"<function>=<js-name>"`
+   */
+  renames?: Array<string>
+  /**
+   * Freeze the intrinsics, polyfills and WIT import objects once the
+   * JavaScript module is evaluated (default: false)
+   */
+  harden?: boolean
+  /** Record sync import calls and their results to this guest file at runtime */
+  recordImports?: string
+  /** Satisfy sync import calls from a capture recorded with `recordImports` */
+  replayImports?: string
+  /**
+   * Run the snapshotted component's core modules through wasm-opt, for
+   * `speed` or `size`
+   */
+  optimize?: string
+  /**
+   * Return a single core module importing WASI directly instead of a
+   * component; needs `sync`
+   */
+  coreModule?: boolean
+  /** Custom sections to append to the component, by name */
+  customSections?: Record<string, Buffer>
+  /** Name to record in the component's metadata */
+  metadataName?: string
+  /** Version to record in the component's metadata */
+  metadataVersion?: string
+  /** Description to record in the component's metadata */
+  metadataDescription?: string
+  /**
+   * Leave the producers and metadata sections out of the component
+   * (default: false)
+   */
+  noMetadata?: boolean
+  /**
+   * Build byte-identical components from identical inputs, pinning the
+   * clocks and randomness initialization sees (default: false)
+   */
+  reproducible?: boolean
+  /** Cache compiled components here so JavaScript-only rebuilds skip compilation */
+  cacheDir?: string
   /** Use the built-in runtime optimized for smaller generated components */
   optSize?: boolean
   /**
@@ -35,12 +219,59 @@ export interface ComponentizeOpts {
   runtime?: string
   /** Custom QuickJS runtime Wasm bytes */
   runtimeBytes?: Buffer
+  /**
+   * Use the debug runtime, with assertions and function names, to diagnose
+   * a failing component
+   */
+  debugRuntime?: boolean
+}
+
+/** Globals installed on top of plain QuickJS. */
+export interface EnvShims {
+  /** Bundled polyfills to install in addition to the defaults */
+  polyfills?: Array<string>
+  /** Install only the polyfills listed in `polyfills` (default: false) */
+  noDefaultPolyfills?: boolean
+  /**
+   * Leave out the globals generated from the world's WASI imports, such as
+   * `fetch`, `env` and `fs` (default: false)
+   */
+  noWasiGlobals?: boolean
 }
 
 /** Result of componentizing a JavaScript source. */
 export interface ComponentizeResult {
   /** The WebAssembly component bytes */
   component: Buffer
+  /**
+   * Warnings from initialization, such as exports the JavaScript doesn't
+   * define
+   */
+  warnings: Array<string>
+  /** Output of the JavaScript to stdout during initialization */
+  initStdout: string
+  /** Output of the JavaScript to stderr during initialization */
+  initStderr: string
+  /** Core modules embedded in the component, in order */
+  modules: Array<ModuleReport>
+}
+
+/** A core module embedded in the component. */
+export interface ModuleReport {
+  /** Name from the module's `name` section, if it has one */
+  name?: string
+  /** Module size in bytes */
+  size: number
+  /** Custom sections of the module, in order */
+  customSections: Array<CustomSection>
+}
+
+/** A custom section of a core module. */
+export interface CustomSection {
+  /** Section name */
+  name: string
+  /** Section size in bytes */
+  size: number
 }
 
 /**
@@ -148,6 +148,26 @@ describe("componentize", () => {
       rmSync(dir, { recursive: true, force: true });
     }
   }, TIMEOUT);
+
+  it("accepts WIT source without a file", async () => {
+    const result = await componentize({
+      witSource: readExample("hello.wit"),
+      jsSource: readExample("hello.js"),
+    });
+
+    expect(result.component).toBeInstanceOf(Buffer);
+    expect(result.component.length).toBeGreaterThan(0);
+  }, TIMEOUT);
+
+  it("rejects combining witPath and witSource", async () => {
+    await expect(
+      componentize({
+        witPath: resolve(examplesDir, "hello.wit"),
+        witSource: readExample("hello.wit"),
+        jsSource: readExample("hello.js"),
+      }),
+    ).rejects.toThrow(/only one of/i);
+  });
 });
 
 describe("runCli", () => {
@@ -0,0 +1,147 @@
+from collections.abc import Awaitable
+from os import PathLike
+from typing import Optional, Union
+
+_Path = Union[str, PathLike[str]]
+
+class EnvShims:
+    """Globals installed on top of plain QuickJS."""
+
+    polyfills: list[str]
+    no_default_polyfills: bool
+    no_wasi_globals: bool
+
+    def __init__(
+        self,
+        *,
+        polyfills: list[str] = ...,
+        no_default_polyfills: bool = False,
+        no_wasi_globals: bool = False,
+    ) -> None: ...
+
+class ComponentizeOpts:
+    """Options for componentizing a JavaScript source into a WebAssembly component."""
+
+    wit_path: _Path
+    js_source: str
+    js_path: Optional[_Path]
+    module_root: Optional[_Path]
+    init_env: dict[str, str]
+    init_args: list[str]
+    init_dirs: dict[str, _Path]
+    modules: dict[str, str]
+    entry_module: Optional[str]
+    world: Optional[str]
+    stub_wasi: bool
+    disable_gc: bool
+    strip_source: bool
+    bytecode: bool
+    lazy_eval: bool
+    gc_interval: Optional[int]
+    gc_threshold: Optional[int]
+    max_list_length: Optional[int]
+    memory_limit: Optional[int]
+    memory_report: Optional[int]
+    max_instructions: Optional[int]
+    init_timeout: Optional[int]
+    strict_types: bool
+    tagged_results: bool
+    throwing_imports: bool
+    wrap_integers: bool
+    finite_floats: bool
+    warn_f32_rounding: bool
+    trace_values: bool
+    features: list[str]
+    env_shims: EnvShims
+    deny_warnings: bool
+    arity_mismatch: Optional[str]
+    math_random: Optional[str]
+    prime: list[str]
+    version_aliasing: Optional[str]
+    import_aliases: list[str]
+    naming: Optional[str]
+    renames: list[str]
+    harden: bool
+    record_imports: Optional[str]
+    replay_imports: Optional[str]
+    optimize: Optional[str]
+    core_module: bool
+    custom_sections: dict[str, bytes]
+    metadata_name: Optional[str]
+    metadata_version: Optional[str]
+    metadata_description: Optional[str]
+    no_metadata: bool
+    reproducible: bool
+    cache_dir: Optional[_Path]
+    opt_size: bool
+    sync: bool
+    runtime: Optional[_Path]
+    runtime_bytes: Optional[bytes]
+    debug_runtime: bool
+
+    def __init__(
+        self,
+        wit_path: _Path,
+        js_source: str = "",
+        *,
+        js_path: Optional[_Path] = None,
+        module_root: Optional[_Path] = None,
+        init_env: dict[str, str] = ...,
+        init_args: list[str] = ...,
+        init_dirs: dict[str, _Path] = ...,
+        modules: dict[str, str] = ...,
+        entry_module: Optional[str] = None,
+        world: Optional[str] = None,
+        stub_wasi: bool = False,
+        disable_gc: bool = False,
+        strip_source: bool = False,
+        bytecode: bool = False,
+        lazy_eval: bool = False,
+        gc_interval: Optional[int] = None,
+        gc_threshold: Optional[int] = None,
+        max_list_length: Optional[int] = None,
+        memory_limit: Optional[int] = None,
+        memory_report: Optional[int] = None,
+        max_instructions: Optional[int] = None,
+        init_timeout: Optional[int] = None,
+        strict_types: bool = False,
+        tagged_results: bool = False,
+        throwing_imports: bool = False,
+        wrap_integers: bool = False,
+        finite_floats: bool = False,
+        warn_f32_rounding: bool = False,
+        trace_values: bool = False,
+        features: list[str] = ...,
+        env_shims: Optional[EnvShims] = None,
+        deny_warnings: bool = False,
+        arity_mismatch: Optional[str] = None,
+        math_random: Optional[str] = None,
+        prime: list[str] = ...,
+        version_aliasing: Optional[str] = None,
+        import_aliases: list[str] = ...,
+        naming: Optional[str] = None,
+        renames: list[str] = ...,
+        harden: bool = False,
+        record_imports: Optional[str] = None,
+        replay_imports: Optional[str] = None,
+        optimize: Optional[str] = None,
+        core_module: bool = False,
+        custom_sections: dict[str, bytes] = ...,
+        metadata_name: Optional[str] = None,
+        metadata_version: Optional[str] = None,
+        metadata_description: Optional[str] = None,
+        no_metadata: bool = False,
+        reproducible: bool = False,
+        cache_dir: Optional[_Path] = None,
+        opt_size: bool = False,
+        sync: bool = False,
+        runtime: Optional[_Path] = None,
+        runtime_bytes: Optional[bytes] = None,
+        debug_runtime: bool = False,
+    ) -> None: ...
+
+def componentize(opts: ComponentizeOpts) -> bytes:
+    """Convert JavaScript source code into a WebAssembly component."""
+
+def componentize_async(opts: ComponentizeOpts) -> Awaitable[bytes]:
+    """Convert JavaScript source code into a WebAssembly component asynchronously."""
@@ -0,0 +1,628 @@
+//! Python bindings for componentize-qjs.
+//!
+//! Mirrors the Node.js API: a `ComponentizeOpts` class plus `componentize`
+//! (blocking, releases the GIL) and `componentize_async` (awaitable).
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use pyo3::exceptions::{PyRuntimeError, PyValueError};
+use pyo3::prelude::*;
+
+/// Options for componentizing a JavaScript source into a WebAssembly component.
+#[pyclass(module = "componentize_qjs", get_all, set_all)]
+#[derive(Clone)]
+pub struct ComponentizeOpts {
+    /// Path to the WIT file or directory
+    pub wit_path: PathBuf,
+    /// JavaScript source code
+    pub js_source: String,
+    /// Path to the JavaScript entry file, used as the base for resolving imports
+    pub js_path: Option<PathBuf>,
+    /// Root directory exposed during Wizer for resolving JavaScript imports
+    pub module_root: Option<PathBuf>,
+    /// Environment variables visible during Wizer initialization
+    pub init_env: HashMap<String, String>,
+    /// Command-line arguments visible during Wizer initialization
+    pub init_args: Vec<String>,
+    /// Host directories preopened read-only during Wizer initialization, by
+    /// guest path
+    pub init_dirs: HashMap<String, PathBuf>,
+    /// Additional JavaScript modules by name, importable by the entry module
+    /// and each other
+    pub modules: HashMap<String, String>,
+    /// Name of the module in `modules` to evaluate as the entry module, in
+    /// place of `js_source`
+    pub entry_module: Option<String>,
+    /// World name to use from the WIT (None for the default world)
+    pub world: Option<String>,
+    /// Stub all WASI imports with traps
+    pub stub_wasi: bool,
+    /// Disable automatic garbage collection
+    pub disable_gc: bool,
+    /// Drop the source text of JavaScript functions from the component
+    pub strip_source: bool,
+    /// Compile the entry module to QuickJS bytecode and evaluate that during
+    /// initialization instead of its source
+    pub bytecode: bool,
+    /// Evaluate the JavaScript module on the first export call instead of
+    /// during Wizer
+    pub lazy_eval: bool,
+    /// Run a full garbage collection cycle after every this many export calls
+    pub gc_interval: Option<u32>,
+    /// Start an automatic garbage collection cycle once this many bytes were
+    /// allocated since the last one
+    pub gc_threshold: Option<u32>,
+    /// Reject lists (in elements) and strings (in UTF-8 bytes) longer than this
+    /// when lowering values from JavaScript
+    pub max_list_length: Option<u32>,
+    /// Cap the QuickJS heap at this many bytes
+    pub memory_limit: Option<u32>,
+    /// Print heap usage to stderr after every this many export calls
+    pub memory_report: Option<u32>,
+    /// Trap when a call into JavaScript runs more than about this many bytecode
+    /// instructions
+    pub max_instructions: Option<u64>,
+    /// Fail the build when Wizer initialization runs longer than this many
+    /// milliseconds
+    pub init_timeout: Option<u64>,
+    /// Check values lowered from JavaScript against their WIT type, throwing a
+    /// `TypeError` naming the mismatch
+    pub strict_types: bool,
+    /// Pass top-level `result` values as `{ tag, val }` objects instead of
+    /// return/throw
+    pub tagged_results: bool,
+    /// With `tagged_results`, still return the `ok` payload of imports and
+    /// throw the `err` one
+    pub throwing_imports: bool,
+    /// Wrap out-of-range integers lowered from JavaScript instead of throwing a
+    /// `RangeError`
+    pub wrap_integers: bool,
+    /// Reject NaN and infinite floats lowered from JavaScript with a
+    /// `RangeError`
+    pub finite_floats: bool,
+    /// Warn on stderr when a number lowered as an `f32` loses precision
+    pub warn_f32_rounding: bool,
+    /// Log every value lifted into or lowered out of JavaScript to stderr
+    pub trace_values: bool,
+    /// Feature flags exposed to JavaScript through the read-only `__FEATURES__` set
+    pub features: Vec<String>,
+    /// Globals installed on top of plain QuickJS
+    pub env_shims: EnvShims,
+    /// Fail the build on initialization warnings, such as exports whose
+    /// parameter count differs from their WIT signature
+    pub deny_warnings: bool,
+    /// What to do with exports declaring more or fewer parameters than their
+    /// WIT signature: `ignore`, `warn` or `error` (None for `warn`)
+    pub arity_mismatch: Option<String>,
+    /// Replace `Math.random`: `"wasi"` for wasi:random entropy or `"seed:<N>"`
+    /// for a reproducible sequence
+    pub math_random: Option<String>,
+    /// Exports to call once during initialization so the snapshot starts
+    /// warm, each as `"<export>=<JSON array of arguments>"`
+    pub prime: Vec<String>,
+    /// Which shortened names import a versioned WIT interface: `"none"`,
+    /// `"strip-patch"` or `"strip-all"` (the default)
+    pub version_aliasing: Option<String>,
+    /// Extra import specifiers for WIT interfaces, each as
+    /// `"<specifier>=<interface>"`
+    pub import_aliases: Vec<String>,
+    /// Names of WIT functions in JavaScript: `"camel"` (the default),
+    /// `"kebab"` or `"both"`
+    pub naming: Option<String>,
+    /// JavaScript names for individual WIT functions, each as
+    /// `"<function>=<js-name>"`
+    pub renames: Vec<String>,
+    /// Freeze the intrinsics, polyfills and WIT import objects once the
+    /// JavaScript module is evaluated
+    pub harden: bool,
+    /// Record sync import calls and their results to this guest file at runtime
+    pub record_imports: Option<String>,
+    /// Satisfy sync import calls from a capture recorded with `record_imports`
+    pub replay_imports: Option<String>,
+    /// Run the snapshotted component's core modules through wasm-opt, for
+    /// `speed` or `size`
+    pub optimize: Option<String>,
+    /// Return a single core module importing WASI directly instead of a
+    /// component; needs `sync`
+    pub core_module: bool,
+    /// Custom sections to append to the component, by name
+    pub custom_sections: HashMap<String, Vec<u8>>,
+    /// Name to record in the component's metadata
+    pub metadata_name: Option<String>,
+    /// Version to record in the component's metadata
+    pub metadata_version: Option<String>,
+    /// Description to record in the component's metadata
+    pub metadata_description: Option<String>,
+    /// Leave the producers and metadata sections out of the component
+    pub no_metadata: bool,
+    /// Build byte-identical components from identical inputs, pinning the
+    /// clocks and randomness initialization sees
+    pub reproducible: bool,
+    /// Cache compiled components here so JavaScript-only rebuilds skip compilation
+    pub cache_dir: Option<PathBuf>,
+    /// Use the built-in runtime optimized for smaller generated components
+    pub opt_size: bool,
+    /// Use the built-in non-async runtime, producing components that do not use
+    /// the component-model async ABI
+    pub sync: bool,
+    /// Path to a custom QuickJS runtime Wasm module
+    pub runtime: Option<PathBuf>,
+    /// Custom QuickJS runtime Wasm bytes
+    pub runtime_bytes: Option<Vec<u8>>,
+    /// Use the debug runtime, with assertions and function names, to diagnose
+    /// a failing component
+    pub debug_runtime: bool,
+}
+
+/// Globals installed on top of plain QuickJS.
+#[pyclass(module = "componentize_qjs", get_all, set_all)]
+#[derive(Clone, Default)]
+pub struct EnvShims {
+    /// Bundled polyfills to install in addition to the defaults
+    pub polyfills: Vec<String>,
+    /// Install only the polyfills listed in `polyfills`
+    pub no_default_polyfills: bool,
+    /// Leave out the globals generated from the world's WASI imports, such as
+    /// `fetch`, `env` and `fs`
+    pub no_wasi_globals: bool,
+}
+
+#[pymethods]
+impl EnvShims {
+    #[new]
+    #[pyo3(signature = (*, polyfills = Vec::new(), no_default_polyfills = false, no_wasi_globals = false))]
+    fn new(polyfills: Vec<String>, no_default_polyfills: bool, no_wasi_globals: bool) -> Self {
+        Self {
+            polyfills,
+            no_default_polyfills,
+            no_wasi_globals,
+        }
+    }
+}
+
+#[pymethods]
+impl ComponentizeOpts {
+    #[new]
+    #[pyo3(signature = (
+        wit_path,
+        js_source = String::new(),
+        *,
+        js_path = None,
+        module_root = None,
+        init_env = HashMap::new(),
+        init_args = Vec::new(),
+        init_dirs = HashMap::new(),
+        modules = HashMap::new(),
+        entry_module = None,
+        world = None,
+        stub_wasi = false,
+        disable_gc = false,
+        strip_source = false,
+        bytecode = false,
+        lazy_eval = false,
+        gc_interval = None,
+        gc_threshold = None,
+        max_list_length = None,
+        memory_limit = None,
+        memory_report = None,
+        max_instructions = None,
+        init_timeout = None,
+        strict_types = false,
+        tagged_results = false,
+        throwing_imports = false,
+        wrap_integers = false,
+        finite_floats = false,
+        warn_f32_rounding = false,
+        trace_values = false,
+        features = Vec::new(),
+        env_shims = None,
+        deny_warnings = false,
+        arity_mismatch = None,
+        math_random = None,
+        prime = Vec::new(),
+        version_aliasing = None,
+        import_aliases = Vec::new(),
+        naming = None,
+        renames = Vec::new(),
+        harden = false,
+        record_imports = None,
+        replay_imports = None,
+        optimize = None,
+        core_module = false,
+        custom_sections = HashMap::new(),
+        metadata_name = None,
+        metadata_version = None,
+        metadata_description = None,
+        no_metadata = false,
+        reproducible = false,
+        cache_dir = None,
+        opt_size = false,
+        sync = false,
+        runtime = None,
+        runtime_bytes = None,
+        debug_runtime = false,
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        wit_path: PathBuf,
+        js_source: String,
+        js_path: Option<PathBuf>,
+        module_root: Option<PathBuf>,
+        init_env: HashMap<String, String>,
+        init_args: Vec<String>,
+        init_dirs: HashMap<String, PathBuf>,
+        modules: HashMap<String, String>,
+        entry_module: Option<String>,
+        world: Option<String>,
+        stub_wasi: bool,
+        disable_gc: bool,
+        strip_source: bool,
+        bytecode: bool,
+        lazy_eval: bool,
+        gc_interval: Option<u32>,
+        gc_threshold: Option<u32>,
+        max_list_length: Option<u32>,
+        memory_limit: Option<u32>,
+        memory_report: Option<u32>,
+        max_instructions: Option<u64>,
+        init_timeout: Option<u64>,
+        strict_types: bool,
+        tagged_results: bool,
+        throwing_imports: bool,
+        wrap_integers: bool,
+        finite_floats: bool,
+        warn_f32_rounding: bool,
+        trace_values: bool,
+        features: Vec<String>,
+        env_shims: Option<EnvShims>,
+        deny_warnings: bool,
+        arity_mismatch: Option<String>,
+        math_random: Option<String>,
+        prime: Vec<String>,
+        version_aliasing: Option<String>,
+        import_aliases: Vec<String>,
+        naming: Option<String>,
+        renames: Vec<String>,
+        harden: bool,
+        record_imports: Option<String>,
+        replay_imports: Option<String>,
+        optimize: Option<String>,
+        core_module: bool,
+        custom_sections: HashMap<String, Vec<u8>>,
+        metadata_name: Option<String>,
+        metadata_version: Option<String>,
+        metadata_description: Option<String>,
+        no_metadata: bool,
+        reproducible: bool,
+        cache_dir: Option<PathBuf>,
+        opt_size: bool,
+        sync: bool,
+        runtime: Option<PathBuf>,
+        runtime_bytes: Option<Vec<u8>>,
+        debug_runtime: bool,
+    ) -> Self {
+        Self {
+            wit_path,
+            js_source,
+            js_path,
+            module_root,
+            init_env,
+            init_args,
+            init_dirs,
+            modules,
+            entry_module,
+            world,
+            stub_wasi,
+            disable_gc,
+            strip_source,
+            bytecode,
+            lazy_eval,
+            gc_interval,
+            gc_threshold,
+            max_list_length,
+            memory_limit,
+            memory_report,
+            max_instructions,
+            init_timeout,
+            strict_types,
+            tagged_results,
+            throwing_imports,
+            wrap_integers,
+            finite_floats,
+            warn_f32_rounding,
+            trace_values,
+            features,
+            env_shims: env_shims.unwrap_or_default(),
+            deny_warnings,
+            arity_mismatch,
+            math_random,
+            prime,
+            version_aliasing,
+            import_aliases,
+            naming,
+            renames,
+            harden,
+            record_imports,
+            replay_imports,
+            optimize,
+            core_module,
+            custom_sections,
+            metadata_name,
+            metadata_version,
+            metadata_description,
+            no_metadata,
+            reproducible,
+            cache_dir,
+            opt_size,
+            sync,
+            runtime,
+            runtime_bytes,
+            debug_runtime,
+        }
+    }
+}
+
+/// A validated, owned componentize request that can outlive the GIL.
+struct Job {
+    opts: ComponentizeOpts,
+    custom_runtime: Option<Vec<u8>>,
+    math_random: Option<componentize_qjs::MathRandom>,
+    optimize: Option<componentize_qjs::OptLevel>,
+    prime: Vec<componentize_qjs::PrimeCall>,
+    arity_mismatch: componentize_qjs::ArityMismatch,
+    version_aliasing: componentize_qjs::VersionAliasing,
+    import_aliases: Vec<componentize_qjs::ImportAlias>,
+    naming: componentize_qjs::Naming,
+    renames: Vec<componentize_qjs::Rename>,
+}
+
+impl Job {
+    fn new(opts: &ComponentizeOpts) -> PyResult<Self> {
+        let invalid = |msg: String| Err(PyValueError::new_err(msg));
+
+        if !opts.wit_path.exists() {
+            return invalid(format!(
+                "WIT file/directory not found: {}",
+                opts.wit_path.display()
+            ));
+        }
+        if let Some(path) = &opts.js_path
+            && !path.exists()
+        {
+            return invalid(format!(
+                "JavaScript entry file not found: {}",
+                path.display()
+            ));
+        }
+        if let Some(path) = &opts.module_root
+            && !path.exists()
+        {
+            return invalid(format!("Module root not found: {}", path.display()));
+        }
+        if opts.record_imports.is_some() && opts.replay_imports.is_some() {
+            return invalid("record_imports and replay_imports are mutually exclusive".into());
+        }
+        if opts.runtime.is_some() && opts.runtime_bytes.is_some() {
+            return invalid("Use only one of runtime or runtime_bytes".into());
+        }
+        let custom_provided = opts.runtime.is_some() || opts.runtime_bytes.is_some();
+        if custom_provided && (opts.opt_size || opts.sync) {
+            return invalid("opt_size and sync cannot be combined with a custom runtime".into());
+        }
+
+        let custom_runtime = match &opts.runtime {
+            Some(path) => Some(std::fs::read(path).map_err(|e| {
+                PyValueError::new_err(format!(
+                    "Failed to read runtime file {}: {e}",
+                    path.display()
+                ))
+            })?),
+            None => opts.runtime_bytes.clone(),
+        };
+
+        let math_random = opts
+            .math_random
+            .as_deref()
+            .map(str::parse)
+            .transpose()
+            .map_err(|e: anyhow::Error| PyValueError::new_err(e.to_string()))?;
+        let optimize = opts
+            .optimize
+            .as_deref()
+            .map(str::parse)
+            .transpose()
+            .map_err(|e: anyhow::Error| PyValueError::new_err(e.to_string()))?;
+        let prime = opts
+            .prime
+            .iter()
+            .map(|call| call.parse())
+            .collect::<anyhow::Result<_>>()
+            .map_err(|e| PyValueError::new_err(e.to_string()))?;
+        let arity_mismatch = opts
+            .arity_mismatch
+            .as_deref()
+            .map(str::parse)
+            .transpose()
+            .map_err(|e: anyhow::Error| PyValueError::new_err(e.to_string()))?
+            .unwrap_or_default();
+        let version_aliasing = opts
+            .version_aliasing
+            .as_deref()
+            .map(str::parse)
+            .transpose()
+            .map_err(|e: anyhow::Error| PyValueError::new_err(e.to_string()))?
+            .unwrap_or_default();
+        let naming = opts
+            .naming
+            .as_deref()
+            .map(str::parse)
+            .transpose()
+            .map_err(|e: anyhow::Error| PyValueError::new_err(e.to_string()))?
+            .unwrap_or_default();
+        let import_aliases = opts
+            .import_aliases
+            .iter()
+            .map(|alias| alias.parse())
+            .collect::<anyhow::Result<_>>()
+            .map_err(|e| PyValueError::new_err(e.to_string()))?;
+        let renames = opts
+            .renames
+            .iter()
+            .map(|rename| rename.parse())
+            .collect::<anyhow::Result<_>>()
+            .map_err(|e| PyValueError::new_err(e.to_string()))?;
+
+        Ok(Self {
+            opts: opts.clone(),
+            custom_runtime,
+            math_random,
+            optimize,
+            prime,
+            arity_mismatch,
+            version_aliasing,
+            import_aliases,
+            naming,
+            renames,
+        })
+    }
+
+    async fn run(&self) -> anyhow::Result<Vec<u8>> {
+        let opts = &self.opts;
+        let import_log = match (&opts.record_imports, &opts.replay_imports) {
+            (Some(path), _) => Some(componentize_qjs::ImportLog::Record(path)),
+            (None, Some(path)) => Some(componentize_qjs::ImportLog::Replay(path)),
+            (None, None) => None,
+        };
+        let init_env: Vec<(String, String)> = opts.init_env.clone().into_iter().collect();
+        // Sorted by name so builds are reproducible.
+        let mut custom_sections: Vec<(String, Vec<u8>)> =
+            opts.custom_sections.clone().into_iter().collect();
+        custom_sections.sort();
+        let init_dirs: Vec<componentize_qjs::InitDir> = opts
+            .init_dirs
+            .iter()
+            .map(|(guest, host)| componentize_qjs::InitDir {
+                host: host.clone(),
+                guest: guest.clone(),
+            })
+            .collect();
+        let modules: Vec<(String, String)> = opts.modules.clone().into_iter().collect();
+        let runtime = match self.custom_runtime.as_deref() {
+            Some(wasm) => componentize_qjs::Runtime::Custom(wasm),
+            None => match (opts.sync, opts.opt_size) {
+                (true, true) => componentize_qjs::Runtime::OptSizeSync,
+                (true, false) => componentize_qjs::Runtime::DefaultSync,
+                (false, true) => componentize_qjs::Runtime::OptSize,
+                (false, false) => componentize_qjs::Runtime::default(),
+            },
+        };
+
+        componentize_qjs::componentize(&componentize_qjs::ComponentizeOpts {
+            wit_path: &opts.wit_path,
+            wit_source: None,
+            wit_package: None,
+            js_source: &opts.js_source,
+            js_path: opts.js_path.as_deref(),
+            module_root: opts.module_root.as_deref(),
+            init_env: &init_env,
+            init_args: &opts.init_args,
+            init_dirs: &init_dirs,
+            modules: &modules,
+            entry_module: opts.entry_module.as_deref(),
+            preludes: &[],
+            world_name: opts.world.as_deref(),
+            stub_wasi: opts.stub_wasi,
+            disable_gc: opts.disable_gc,
+            strip_source: opts.strip_source,
+            bytecode: opts.bytecode,
+            lazy_eval: opts.lazy_eval,
+            gc_interval: opts.gc_interval,
+            gc_threshold: opts.gc_threshold,
+            max_list_length: opts.max_list_length,
+            memory_limit: opts.memory_limit,
+            memory_report: opts.memory_report,
+            max_instructions: opts.max_instructions,
+            init_timeout: opts.init_timeout.map(std::time::Duration::from_millis),
+            strict_types: opts.strict_types,
+            tagged_results: opts.tagged_results,
+            throwing_imports: opts.throwing_imports,
+            wrap_integers: opts.wrap_integers,
+            finite_floats: opts.finite_floats,
+            warn_f32_rounding: opts.warn_f32_rounding,
+            trace_values: opts.trace_values,
+            features: &opts.features,
+            env_shims: componentize_qjs::EnvShims {
+                polyfills: &opts.env_shims.polyfills,
+                no_default_polyfills: opts.env_shims.no_default_polyfills,
+                no_wasi_globals: opts.env_shims.no_wasi_globals,
+            },
+            deny_warnings: opts.deny_warnings,
+            arity_mismatch: self.arity_mismatch,
+            math_random: self.math_random,
+            prime: &self.prime,
+            version_aliasing: self.version_aliasing,
+            import_aliases: &self.import_aliases,
+            naming: self.naming,
+            renames: &self.renames,
+            harden: opts.harden,
+            import_log,
+            optimize: self.optimize,
+            core_module: opts.core_module,
+            custom_sections: &custom_sections,
+            metadata: componentize_qjs::Metadata {
+                suppress: opts.no_metadata,
+                name: opts.metadata_name.as_deref(),
+                version: opts.metadata_version.as_deref(),
+                description: opts.metadata_description.as_deref(),
+            },
+            reproducible: opts.reproducible,
+            cache_dir: opts.cache_dir.as_deref(),
+            runtime,
+            debug_runtime: opts.debug_runtime,
+        })
+        .await
+    }
+}
+
+fn failed(err: anyhow::Error) -> PyErr {
+    PyRuntimeError::new_err(format!("{err:#}"))
+}
+
+/// Convert JavaScript source code into a WebAssembly component.
+///
+/// Blocks until the component is built, releasing the GIL meanwhile, and
+/// returns the component bytes.
+#[pyfunction]
+fn componentize(py: Python<'_>, opts: PyRef<'_, ComponentizeOpts>) -> PyResult<Vec<u8>> {
+    let job = Job::new(&opts)?;
+    py.detach(|| {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?
+            .block_on(job.run())
+    })
+    .map_err(failed)
+}
+
+/// Convert JavaScript source code into a WebAssembly component.
+///
+/// Returns an awaitable resolving to the component bytes.
+#[pyfunction]
+fn componentize_async<'py>(
+    py: Python<'py>,
+    opts: PyRef<'py, ComponentizeOpts>,
+) -> PyResult<Bound<'py, PyAny>> {
+    let job = Job::new(&opts)?;
+    pyo3_async_runtimes::tokio::future_into_py(py, async move { job.run().await.map_err(failed) })
+}
+
+#[pymodule]
+#[pyo3(name = "componentize_qjs")]
+fn componentize_qjs_py(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<ComponentizeOpts>()?;
+    m.add_class::<EnvShims>()?;
+    m.add_function(wrap_pyfunction!(componentize, m)?)?;
+    m.add_function(wrap_pyfunction!(componentize_async, m)?)?;
+    Ok(())
+}
@@ -0,0 +1,50 @@
+import asyncio
+from pathlib import Path
+
+import pytest
+
+from componentize_qjs import ComponentizeOpts, componentize, componentize_async
+
+EXAMPLES = Path(__file__).resolve().parents[2] / "examples"
+WASM_MAGIC = b"\0asm"
+
+
+def hello_opts(**kwargs):
+    return ComponentizeOpts(
+        EXAMPLES / "hello.wit",
+        (EXAMPLES / "hello.js").read_text(),
+        **kwargs,
+    )
+
+
+def test_componentize_produces_component():
+    component = componentize(hello_opts())
+    assert isinstance(component, bytes)
+    assert component.startswith(WASM_MAGIC)
+
+
+def test_componentize_async_produces_component():
+    async def build():
+        return await componentize_async(hello_opts(world="hello"))
+
+    component = asyncio.run(build())
+    assert component.startswith(WASM_MAGIC)
+
+
+def test_missing_wit_path_is_rejected():
+    opts = hello_opts()
+    opts.wit_path = EXAMPLES / "missing.wit"
+    with pytest.raises(ValueError, match="WIT file/directory not found"):
+        componentize(opts)
+
+
+def test_custom_runtime_conflicts_with_opt_size():
+    with pytest.raises(ValueError, match="cannot be combined"):
+        componentize(hello_opts(runtime_bytes=b"\0asm", opt_size=True))
+
+
+def test_invalid_script_raises_runtime_error():
+    opts = hello_opts()
+    opts.js_source = "export function greet( {"
+    with pytest.raises(RuntimeError):
+        componentize(opts)
@@ -1,4 +1,8 @@
-use componentize_qjs::{ComponentizeOpts, Runtime, componentize};
+use componentize_qjs::{
+    ArityMismatch, ComponentizeOpts, EnvShims, ImportAlias, ImportLog, InitDir, MathRandom,
+    Metadata, Naming, OptLevel, PrimeCall, Rename, Runtime, VersionAliasing,
+    componentize_with_report, plan,
+};
 
 use anyhow::{Context, Result};
 use clap::Parser;
@@ -12,12 +16,13 @@ use oxc_parser::Parser as OxcParser;
 use oxc_span::SourceType;
 
 use std::fs;
+use std::time::Duration;
 
 #[derive(Parser)]
 #[command(name = "componentize-qjs")]
 #[command(about = "Convert JavaScript to WebAssembly components using QuickJS")]
 pub struct CliArgs {
-    /// Path to the WIT file or directory
+    /// Path to the WIT file or directory, or a binary WIT package
     #[arg(short, long)]
     pub wit: std::path::PathBuf,
 
@@ -29,6 +34,25 @@ pub struct CliArgs {
     #[arg(long, value_name = "PATH")]
     pub module_root: Option<std::path::PathBuf>,
 
+    /// Set an environment variable for the JavaScript evaluated during
+    /// initialization, as `<name>=<value>` (repeatable)
+    #[arg(long = "init-env", value_name = "NAME=VALUE", value_parser = parse_env)]
+    pub init_env: Vec<(String, String)>,
+
+    /// Pass a command-line argument to initialization (repeatable, in order)
+    #[arg(long = "init-arg", value_name = "ARG")]
+    pub init_args: Vec<String>,
+
+    /// Preopen a host directory read-only during initialization, as
+    /// `<host>[::<guest>]` (repeatable)
+    #[arg(long = "init-dir", value_name = "HOST[::GUEST]")]
+    pub init_dirs: Vec<InitDir>,
+
+    /// Evaluate a JavaScript script in the global scope before the entry
+    /// module (repeatable, in order)
+    #[arg(long = "prelude", value_name = "PATH")]
+    pub preludes: Vec<std::path::PathBuf>,
+
     /// Output path for the component
     #[arg(short, long, default_value = "output.wasm")]
     pub output: std::path::PathBuf,
@@ -49,6 +73,194 @@ pub struct CliArgs {
     #[arg(long)]
     pub disable_gc: bool,
 
+    /// Drop the source text of JavaScript functions from the component, which
+    /// makes `Function.prototype.toString` omit function bodies
+    #[arg(long)]
+    pub strip_source: bool,
+
+    /// Compile the JavaScript entry module to QuickJS bytecode and evaluate
+    /// that during initialization instead of its source
+    #[arg(long)]
+    pub bytecode: bool,
+
+    /// Evaluate the JavaScript module on the first export call instead of
+    /// during Wizer, so its top-level code sees the runtime environment
+    #[arg(long)]
+    pub lazy_eval: bool,
+
+    /// Run a full garbage collection cycle after every this many export calls
+    #[arg(long, value_name = "CALLS")]
+    pub gc_interval: Option<u32>,
+
+    /// Start an automatic garbage collection cycle once this many bytes were
+    /// allocated since the last one
+    #[arg(long, value_name = "BYTES")]
+    pub gc_threshold: Option<u32>,
+
+    /// Reject lists and strings returned from or passed out of JavaScript that
+    /// are longer than this, with a catchable `RangeError`
+    #[arg(long, value_name = "LEN")]
+    pub max_list_length: Option<u32>,
+
+    /// Cap the QuickJS heap at this many bytes; allocating beyond it throws an
+    /// out-of-memory error, which traps unless JavaScript catches it
+    #[arg(long, value_name = "BYTES")]
+    pub memory_limit: Option<u32>,
+
+    /// Print heap usage to stderr after every this many export calls
+    #[arg(long, value_name = "CALLS")]
+    pub memory_report: Option<u32>,
+
+    /// Trap when a call into JavaScript runs more than about this many bytecode
+    /// instructions, so runaway loops can't hang the host
+    #[arg(long, value_name = "COUNT")]
+    pub max_instructions: Option<u64>,
+
+    /// Fail the build when initialization, including top-level code and
+    /// priming, runs longer than this many milliseconds
+    #[arg(long, value_name = "MS")]
+    pub init_timeout: Option<u64>,
+
+    /// Check values returned from or passed out of JavaScript against their WIT
+    /// type, with a catchable `TypeError` naming the mismatch
+    #[arg(long)]
+    pub strict_types: bool,
+
+    /// Pass top-level `result` values to and from JavaScript as `{ tag, val }`
+    /// objects instead of return/throw
+    #[arg(long)]
+    pub tagged_results: bool,
+
+    /// With `--tagged-results`, still return the `ok` payload of imports and
+    /// throw an `Error` carrying the `err` one
+    #[arg(long, requires = "tagged_results")]
+    pub throwing_imports: bool,
+
+    /// Wrap out-of-range integers passed out of JavaScript instead of throwing
+    /// a `RangeError`
+    #[arg(long)]
+    pub wrap_integers: bool,
+
+    /// Reject NaN and infinite floats passed out of JavaScript with a
+    /// `RangeError`
+    #[arg(long)]
+    pub finite_floats: bool,
+
+    /// Warn on stderr when a number passed out of JavaScript as an `f32` loses
+    /// precision
+    #[arg(long)]
+    pub warn_f32_rounding: bool,
+
+    /// Log every value passed into or out of JavaScript to stderr, with its
+    /// WIT type and the function being called
+    #[arg(long)]
+    pub trace_values: bool,
+
+    /// Enable a feature flag, visible to JavaScript via `__FEATURES__` (repeatable)
+    #[arg(long = "feature", value_name = "NAME")]
+    pub features: Vec<String>,
+
+    #[command(flatten)]
+    pub env_shims: EnvShimArgs,
+
+    /// Fail instead of printing warnings about the JavaScript, such as exports
+    /// whose parameter count differs from their WIT signature
+    #[arg(long)]
+    pub deny_warnings: bool,
+
+    /// What to do with exports declaring more or fewer parameters than their
+    /// WIT signature: `ignore`, `warn` or `error`
+    #[arg(long, value_name = "MODE", default_value = "warn")]
+    pub arity_mismatch: ArityMismatch,
+
+    /// Replace Math.random: `wasi` for wasi:random entropy or `seed:<N>` for a
+    /// reproducible sequence
+    #[arg(long, value_name = "SOURCE")]
+    pub math_random: Option<MathRandom>,
+
+    /// Call an export once during initialization so the snapshot starts warm,
+    /// as `<export>=<JSON array of arguments>` (repeatable)
+    #[arg(long = "prime", value_name = "EXPORT=ARGS")]
+    pub prime: Vec<PrimeCall>,
+
+    /// Which shortened names import a versioned WIT interface: `none`,
+    /// `strip-patch` (`wasi:io/streams@0.2`) or `strip-all` (`wasi:io/streams`)
+    #[arg(long, value_name = "MODE", default_value = "strip-all")]
+    pub version_aliasing: VersionAliasing,
+
+    /// Let JavaScript import a WIT interface under another name, as
+    /// `<specifier>=<interface>` (repeatable)
+    #[arg(long = "import-alias", value_name = "SPECIFIER=INTERFACE")]
+    pub import_alias: Vec<ImportAlias>,
+
+    /// Names of WIT functions in JavaScript: `camel` (`getRandomU64`), `kebab`
+    /// (`get-random-u64`) or `both`
+    #[arg(long, value_name = "POLICY", default_value = "camel")]
+    pub naming: Naming,
+
+    /// Give one WIT function another name in JavaScript, as
+    /// `<function>=<js-name>`; qualify the function as `<interface>#<function>`
+    /// to pick one from an interface (repeatable)
+    #[arg(long = "rename", value_name = "FUNCTION=JS_NAME")]
+    pub rename: Vec<Rename>,
+
+    /// Freeze the intrinsics, polyfills and WIT import objects once the
+    /// JavaScript module is evaluated
+    #[arg(long)]
+    pub harden: bool,
+
+    /// Record sync import calls and their results to this guest file at runtime
+    #[arg(long, value_name = "GUEST_PATH", conflicts_with = "replay_imports")]
+    pub record_imports: Option<String>,
+
+    /// Satisfy sync import calls from a capture recorded with --record-imports
+    #[arg(long, value_name = "GUEST_PATH")]
+    pub replay_imports: Option<String>,
+
+    /// Run the snapshotted component's core modules through wasm-opt, for
+    /// `speed` or `size` (wasm-opt from WASM_OPT or PATH)
+    #[arg(long, value_name = "LEVEL")]
+    pub optimize: Option<OptLevel>,
+
+    /// Write a single core module importing WASI directly instead of a
+    /// component, for engines without component-model support (needs --sync,
+    /// and wasm-merge from WASM_MERGE or PATH)
+    #[arg(long)]
+    pub core_module: bool,
+
+    /// Append a file to the component as a custom section, as
+    /// `<name>=<path>` (repeatable, in order)
+    #[arg(long = "custom-section", value_name = "NAME=PATH", value_parser = parse_env)]
+    pub custom_sections: Vec<(String, String)>,
+
+    /// Name to record in the component's metadata
+    #[arg(long, value_name = "NAME")]
+    pub metadata_name: Option<String>,
+
+    /// Version to record in the component's metadata
+    #[arg(long, value_name = "VERSION")]
+    pub metadata_version: Option<String>,
+
+    /// Description to record in the component's metadata
+    #[arg(long, value_name = "TEXT")]
+    pub metadata_description: Option<String>,
+
+    /// Leave the producers and metadata sections out of the component
+    #[arg(
+        long,
+        conflicts_with_all = ["metadata_name", "metadata_version", "metadata_description"]
+    )]
+    pub no_metadata: bool,
+
+    /// Build byte-identical components from identical inputs, pinning the
+    /// clocks and randomness initialization sees
+    #[arg(long)]
+    pub reproducible: bool,
+
+    /// Cache compiled components here so JavaScript-only rebuilds skip compilation
+    #[arg(long, value_name = "PATH")]
+    pub cache_dir: Option<std::path::PathBuf>,
+
     /// Use the built-in runtime optimized for smaller generated components
     #[arg(long, conflicts_with = "runtime")]
     pub opt_size: bool,
@@ -61,6 +273,40 @@ pub struct CliArgs {
     /// Path to a custom QuickJS runtime Wasm module
     #[arg(long, value_name = "PATH")]
     pub runtime: Option<std::path::PathBuf>,
+
+    /// Use the debug runtime, with assertions and function names, to diagnose
+    /// a failing component (needs the `debug-runtime` feature)
+    #[arg(long, conflicts_with_all = ["runtime", "opt_size", "sync"])]
+    pub debug_runtime: bool,
+
+    /// Print the build plan as JSON instead of building the component
+    #[arg(long)]
+    pub dry_run: bool,
+}
+
+/// Globals installed on top of plain QuickJS.
+#[derive(clap::Args)]
+pub struct EnvShimArgs {
+    /// Install a bundled polyfill in addition to the defaults (repeatable)
+    #[arg(long = "polyfill", value_name = "NAME")]
+    pub polyfills: Vec<String>,
+
+    /// Install only the polyfills given with --polyfill
+    #[arg(long)]
+    pub no_default_polyfills: bool,
+
+    /// Leave out the fetch, env, fs, Date and other globals generated from the
+    /// world's WASI imports
+    #[arg(long)]
+    pub no_wasi_globals: bool,
+}
+
+/// Parse `<name>=<value>` for `--init-env` and `--custom-section`.
+fn parse_env(s: &str) -> Result<(String, String), String> {
+    match s.split_once('=') {
+        Some((name, value)) if !name.is_empty() => Ok((name.to_string(), value.to_string())),
+        _ => Err(format!("expected `<name>=<value>`, got `{s}`")),
+    }
 }
 
 /// Run the componentize-qjs CLI with the given arguments.
@@ -88,6 +334,26 @@ pub async fn run(args: Vec<String>) -> Result<()> {
     let js_source = fs::read_to_string(&args.js)
         .with_context(|| format!("failed to read JS file: {}", args.js.display()))?;
 
+    let preludes = args
+        .preludes
+        .iter()
+        .map(|path| {
+            let source = fs::read_to_string(path)
+                .with_context(|| format!("failed to read prelude: {}", path.display()))?;
+            Ok((path.display().to_string(), source))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let custom_sections = args
+        .custom_sections
+        .iter()
+        .map(|(name, path)| {
+            let data =
+                fs::read(path).with_context(|| format!("failed to read custom section: {path}"))?;
+            Ok((name.clone(), data))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
     let js_source = if args.minify {
         let allocator = Allocator::default();
         let source_type = SourceType::mjs();
@@ -114,11 +380,6 @@ pub async fn run(args: Vec<String>) -> Result<()> {
         js_source
     };
 
-    println!("componentize-qjs");
-    println!("  WIT:    {}", args.wit.display());
-    println!("  JS:     {}", args.js.display());
-    println!("  Output: {}", args.output.display());
-
     let runtime = match &args.runtime {
         Some(file) => Runtime::Custom(&fs::read(file)?),
         None => match (args.sync, args.opt_size) {
@@ -129,26 +390,104 @@ pub async fn run(args: Vec<String>) -> Result<()> {
         },
     };
 
-    if args.stub_wasi {
-        println!("Stubbing WASI imports...");
-    }
+    let import_log = match (&args.record_imports, &args.replay_imports) {
+        (Some(path), _) => Some(ImportLog::Record(path)),
+        (None, Some(path)) => Some(ImportLog::Replay(path)),
+        (None, None) => None,
+    };
 
-    let component = componentize(&ComponentizeOpts {
+    let opts = ComponentizeOpts {
         wit_path: &args.wit,
+        wit_source: None,
+        wit_package: None,
         js_source: &js_source,
         js_path: Some(&args.js),
         module_root: args.module_root.as_deref(),
+        init_env: &args.init_env,
+        init_args: &args.init_args,
+        init_dirs: &args.init_dirs,
+        modules: &[],
+        entry_module: None,
+        preludes: &preludes,
         world_name: args.world.as_deref(),
         stub_wasi: args.stub_wasi,
         disable_gc: args.disable_gc,
+        strip_source: args.strip_source,
+        bytecode: args.bytecode,
+        lazy_eval: args.lazy_eval,
+        gc_interval: args.gc_interval,
+        gc_threshold: args.gc_threshold,
+        max_list_length: args.max_list_length,
+        memory_limit: args.memory_limit,
+        memory_report: args.memory_report,
+        max_instructions: args.max_instructions,
+        init_timeout: args.init_timeout.map(Duration::from_millis),
+        strict_types: args.strict_types,
+        tagged_results: args.tagged_results,
+        throwing_imports: args.throwing_imports,
+        wrap_integers: args.wrap_integers,
+        finite_floats: args.finite_floats,
+        warn_f32_rounding: args.warn_f32_rounding,
+        trace_values: args.trace_values,
+        features: &args.features,
+        env_shims: EnvShims {
+            polyfills: &args.env_shims.polyfills,
+            no_default_polyfills: args.env_shims.no_default_polyfills,
+            no_wasi_globals: args.env_shims.no_wasi_globals,
+        },
+        deny_warnings: args.deny_warnings,
+        arity_mismatch: args.arity_mismatch,
+        math_random: args.math_random,
+        prime: &args.prime,
+        harden: args.harden,
+        version_aliasing: args.version_aliasing,
+        import_aliases: &args.import_alias,
+        naming: args.naming,
+        renames: &args.rename,
+        import_log,
+        optimize: args.optimize,
+        core_module: args.core_module,
+        custom_sections: &custom_sections,
+        metadata: Metadata {
+            suppress: args.no_metadata,
+            name: args.metadata_name.as_deref(),
+            version: args.metadata_version.as_deref(),
+            description: args.metadata_description.as_deref(),
+        },
+        reproducible: args.reproducible,
+        cache_dir: args.cache_dir.as_deref(),
         runtime,
-    })
-    .await?;
+        debug_runtime: args.debug_runtime,
+    };
+
+    if args.dry_run {
+        println!("{}", plan(&opts)?.to_json());
+        return Ok(());
+    }
+
+    println!("componentize-qjs");
+    println!("  WIT:    {}", args.wit.display());
+    println!("  JS:     {}", args.js.display());
+    println!("  Output: {}", args.output.display());
+
+    if args.stub_wasi {
+        println!("Stubbing WASI imports...");
+    }
+
+    let (component, report) = componentize_with_report(&opts).await?;
+    for warning in &report.warnings {
+        eprintln!("warning: {warning}");
+    }
 
     fs::write(&args.output, &component)
         .with_context(|| format!("failed to write output to {}", args.output.display()))?;
 
-    println!("Component written to {}", args.output.display());
+    let kind = if args.core_module {
+        "Core module"
+    } else {
+        "Component"
+    };
+    println!("{kind} written to {}", args.output.display());
     println!("  Size: {} bytes", component.len());
 
     Ok(())
@@ -7,10 +7,12 @@ use std::pin::Pin;
 use std::task::{Context, Poll};
 
 use common::{TestCase, WasiCtxState};
-use wasmtime::StoreContextMut;
 use wasmtime::component::{
-    Destination, StreamProducer, StreamReader, StreamResult, Val, VecBuffer,
+    Component, Destination, Instance, Linker, ResourceTable, StreamProducer, StreamReader,
+    StreamResult, Val, VecBuffer,
 };
+use wasmtime::{Store, StoreContextMut};
+use wasmtime_wasi::WasiCtxBuilder;
 
 #[tokio::test]
 async fn test_async_echo_u32() {
@@ -1619,3 +1621,271 @@ async fn test_host_stream_to_guest() {
 
     assert_eq!(count, 5);
 }
+
+#[tokio::test]
+async fn test_host_stream_async_iterator() {
+    let mut instance = TestCase::new()
+        .wit(
+            r#"
+            package test:host-stream-iter;
+            world host-stream-iter {
+                export count-bytes: async func(input: stream<u8>) -> u32;
+            }
+            "#,
+        )
+        .script(
+            r#"
+            export async function countBytes(input) {
+                let total = 0;
+                for await (const chunk of input) {
+                    if (!(chunk instanceof Uint8Array)) throw new Error("expected Uint8Array chunk");
+                    total += chunk.length;
+                }
+                if (!input.closed) throw new Error("stream should be closed after iteration");
+                return total;
+            }
+            "#,
+        )
+        .build_async()
+        .await
+        .unwrap();
+
+    let (inst, store) = instance.parts();
+    let reader = StreamReader::new(&mut *store, ByteProducer::new(vec![1, 2, 3, 4, 5])).unwrap();
+    let func = inst
+        .get_typed_func::<(StreamReader<u8>,), (u32,)>(&mut *store, "count-bytes")
+        .unwrap();
+    let (count,) = func.call_async(&mut *store, (reader,)).await.unwrap();
+    assert_eq!(count, 5);
+}
+
+#[tokio::test]
+async fn test_stream_from_async_generator() {
+    let mut instance = TestCase::new()
+        .wit(
+            r#"
+            package test:stream-from;
+            world stream-from {
+                export collect: async func(input: stream<u8>) -> list<u8>;
+            }
+            "#,
+        )
+        .script(
+            r#"
+            async function* bytes() {
+                yield new Uint8Array([1, 2]);
+                await Promise.resolve();
+                yield 3;
+                yield new Uint8Array([4]);
+            }
+
+            export async function collect(input) {
+                input.drop();
+                const out = [];
+                for await (const chunk of wit.Stream.from(bytes())) {
+                    out.push(...chunk);
+                }
+                return out;
+            }
+            "#,
+        )
+        .build_async()
+        .await
+        .unwrap();
+
+    let (inst, store) = instance.parts();
+    let reader = StreamReader::new(&mut *store, ByteProducer::new(vec![])).unwrap();
+    let func = inst
+        .get_typed_func::<(StreamReader<u8>,), (Vec<u8>,)>(&mut *store, "collect")
+        .unwrap();
+    let (bytes,) = func.call_async(&mut *store, (reader,)).await.unwrap();
+    assert_eq!(bytes, vec![1, 2, 3, 4]);
+}
+
+#[tokio::test]
+async fn test_stream_read_abort_signal() {
+    let mut instance = TestCase::new()
+        .wit(
+            r#"
+            package test:stream-abort;
+            world stream-abort {
+                export abort-read: async func(input: stream<u8>) -> string;
+            }
+            "#,
+        )
+        .script(
+            r#"
+            export async function abortRead(input) {
+                input.drop();
+                const { readable, writable } = wit.Stream();
+
+                const controller = new AbortController();
+                const pending = readable.read(16, { signal: controller.signal });
+                controller.abort(new Error("stop"));
+                const reason = await pending.catch((e) => e.message);
+                const early = await readable
+                    .read(16, { signal: AbortSignal.abort() })
+                    .catch((e) => e.name);
+
+                // The cancelled read leaves the stream usable.
+                const write = writable.write(new Uint8Array([1, 2]));
+                const chunk = await readable.read(16);
+                await write;
+                writable.drop();
+                readable.drop();
+                return `${reason} ${early} ${chunk.length}`;
+            }
+            "#,
+        )
+        .build_async()
+        .await
+        .unwrap();
+
+    let (inst, store) = instance.parts();
+    let reader = StreamReader::new(&mut *store, ByteProducer::new(vec![])).unwrap();
+    let func = inst
+        .get_typed_func::<(StreamReader<u8>,), (String,)>(&mut *store, "abort-read")
+        .unwrap();
+    let (summary,) = func.call_async(&mut *store, (reader,)).await.unwrap();
+    assert_eq!(summary, "stop AbortError 2");
+}
+
+#[tokio::test]
+async fn test_async_resource_method_export_rejected() {
+    let err = TestCase::new()
+        .wit(
+            r#"
+            package test:async-methods;
+            interface counters {
+                resource counter {
+                    constructor();
+                    value: async func() -> u32;
+                }
+            }
+            world async-methods {
+                export counters;
+            }
+            "#,
+        )
+        .script(
+            r#"
+            class Counter {
+                async value() { return 0; }
+            }
+            export const counters = { Counter };
+            "#,
+        )
+        .componentize()
+        .await
+        .unwrap_err();
+    assert!(
+        format!("{err:#}").contains(
+            "counter.value`: async resource methods and static functions are not supported"
+        ),
+        "{err:#}"
+    );
+}
+
+#[tokio::test]
+async fn test_error_context_round_trip() {
+    async fn call(
+        instance: &Instance,
+        store: &mut Store<WasiCtxState>,
+        name: &str,
+        params: &[Val],
+    ) -> Val {
+        let func = instance.get_func(&mut *store, name).unwrap();
+        let mut results = [Val::Bool(false)];
+        func.call_async(&mut *store, params, &mut results)
+            .await
+            .unwrap_or_else(|e| panic!("`{name}` failed: {e:#}"));
+        results[0].clone()
+    }
+
+    let wasm = TestCase::new()
+        .wit(
+            r#"
+            package test:error-context;
+
+            interface host {
+                reflect: func(e: error-context) -> error-context;
+            }
+
+            world error-context {
+                import host;
+                export make: func(message: string) -> error-context;
+                export describe: func(e: error-context) -> string;
+                export reflect-error: func(message: string) -> string;
+                export reflect-value: func(value: u32) -> string;
+            }
+            "#,
+        )
+        .script(
+            r#"
+            import host from "test:error-context/host";
+
+            export function make(message) { return new Error(message); }
+            export function describe(e) { return `${e instanceof Error} ${e.message}`; }
+            export function reflectError(message) { return host.reflect(new Error(message)).message; }
+            export function reflectValue(value) { return host.reflect(value).message; }
+            "#,
+        )
+        .componentize()
+        .await
+        .unwrap();
+
+    let engine = common::async_engine();
+    let component = Component::new(engine, &wasm).unwrap();
+    let mut store = Store::new(
+        engine,
+        WasiCtxState {
+            wasi: WasiCtxBuilder::new().build(),
+            table: ResourceTable::new(),
+        },
+    );
+    let mut linker = Linker::new(engine);
+    wasmtime_wasi::p2::add_to_linker_async(&mut linker).unwrap();
+    wasmtime_wasi::p3::add_to_linker(&mut linker).unwrap();
+    linker
+        .instance("test:error-context/host")
+        .unwrap()
+        .func_new("reflect", |_, params, results| {
+            results[0] = params[0].clone();
+            Ok(())
+        })
+        .unwrap();
+    let instance = linker
+        .instantiate_async(&mut store, &component)
+        .await
+        .unwrap();
+
+    // Out of an export and back into another.
+    let error = call(
+        &instance,
+        &mut store,
+        "make",
+        &[Val::String("from export".into())],
+    )
+    .await;
+    assert!(matches!(error, Val::ErrorContext(_)), "{error:?}");
+    assert_eq!(
+        call(&instance, &mut store, "describe", &[error]).await,
+        Val::String("true from export".into())
+    );
+
+    // Out through an import and back as its result.
+    assert_eq!(
+        call(
+            &instance,
+            &mut store,
+            "reflect-error",
+            &[Val::String("through import".into())]
+        )
+        .await,
+        Val::String("through import".into())
+    );
+    assert_eq!(
+        call(&instance, &mut store, "reflect-value", &[Val::U32(7)]).await,
+        Val::String("7".into())
+    );
+}
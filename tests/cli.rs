@@ -172,6 +172,193 @@ fn test_cli_resolves_relative_import() {
     assert_eq!(inst.call1("answer", &[]), Val::U32(42));
 }
 
+#[test]
+fn test_cli_accepts_binary_wit_package() {
+    let dir = TempDir::new().unwrap();
+    let wit_path = dir.path().join("test.wasm");
+    let js_path = dir.path().join("main.js");
+    let output = dir.path().join("output.wasm");
+
+    let mut resolve = wit_parser::Resolve::default();
+    let pkg = resolve
+        .push_str(
+            "test.wit",
+            "package test:binary;\nworld binary { export answer: func() -> u32; }",
+        )
+        .unwrap();
+    fs::write(&wit_path, wit_component::encode(&resolve, pkg).unwrap()).unwrap();
+    fs::write(&js_path, "export function answer() { return 42; }").unwrap();
+
+    componentize_qjs()
+        .arg("--wit")
+        .arg(&wit_path)
+        .arg("--js")
+        .arg(&js_path)
+        .arg("--output")
+        .arg(&output)
+        .assert()
+        .success();
+
+    let wasm = fs::read(&output).unwrap();
+    let mut inst =
+        ComponentInstance::from_wasm(wasm, vec![], vec![]).expect("should instantiate component");
+
+    assert_eq!(inst.call1("answer", &[]), Val::U32(42));
+
+    // A component is not a WIT package.
+    fs::copy(&output, &wit_path).unwrap();
+    componentize_qjs()
+        .arg("--wit")
+        .arg(&wit_path)
+        .arg("--js")
+        .arg(&js_path)
+        .arg("--output")
+        .arg(&output)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "expected a binary WIT package, got a component",
+        ));
+}
+
+#[test]
+fn test_cli_appends_custom_sections() {
+    let dir = TempDir::new().unwrap();
+    let wit_path = dir.path().join("test.wit");
+    let js_path = dir.path().join("main.js");
+    let section_path = dir.path().join("provenance.txt");
+    let output = dir.path().join("output.wasm");
+
+    fs::write(
+        &wit_path,
+        "package test:sections;\nworld sections { export answer: func() -> u32; }",
+    )
+    .unwrap();
+    fs::write(&js_path, "export function answer() { return 42; }").unwrap();
+    fs::write(&section_path, "built by ci").unwrap();
+
+    componentize_qjs()
+        .arg("--wit")
+        .arg(&wit_path)
+        .arg("--js")
+        .arg(&js_path)
+        .arg("--custom-section")
+        .arg(format!("provenance={}", section_path.display()))
+        .arg("--output")
+        .arg(&output)
+        .assert()
+        .success();
+
+    let wasm = fs::read(&output).unwrap();
+    let section = [&[0, 22, 10][..], b"provenance", b"built by ci"].concat();
+    assert!(wasm.ends_with(&section));
+
+    let mut inst =
+        ComponentInstance::from_wasm(wasm, vec![], vec![]).expect("should instantiate component");
+    assert_eq!(inst.call1("answer", &[]), Val::U32(42));
+
+    componentize_qjs()
+        .arg("--wit")
+        .arg(&wit_path)
+        .arg("--js")
+        .arg(&js_path)
+        .arg("--custom-section")
+        .arg(format!("name={}", section_path.display()))
+        .arg("--output")
+        .arg(&output)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "custom section `name` is reserved",
+        ));
+}
+
+/// Top-level custom sections of `wasm`, by name.
+fn top_level_custom_sections(wasm: &[u8]) -> Vec<(String, Vec<u8>)> {
+    let mut depth = 0;
+    let mut sections = Vec::new();
+    for payload in wasmparser::Parser::new(0).parse_all(wasm) {
+        match payload.unwrap() {
+            wasmparser::Payload::Version { .. } => depth += 1,
+            wasmparser::Payload::End(_) => depth -= 1,
+            wasmparser::Payload::CustomSection(reader) if depth == 1 => {
+                sections.push((reader.name().to_string(), reader.data().to_vec()));
+            }
+            _ => {}
+        }
+    }
+    sections
+}
+
+#[test]
+fn test_cli_writes_component_metadata() {
+    let dir = TempDir::new().unwrap();
+    let wit_path = dir.path().join("test.wit");
+    let js_path = dir.path().join("main.js");
+    let output = dir.path().join("output.wasm");
+
+    fs::write(
+        &wit_path,
+        "package test:metadata;\nworld metadata { export answer: func() -> u32; }",
+    )
+    .unwrap();
+    fs::write(&js_path, "export function answer() { return 42; }").unwrap();
+
+    componentize_qjs()
+        .arg("--wit")
+        .arg(&wit_path)
+        .arg("--js")
+        .arg(&js_path)
+        .arg("--metadata-version")
+        .arg("1.2.3")
+        .arg("--metadata-description")
+        .arg("answers")
+        .arg("--output")
+        .arg(&output)
+        .assert()
+        .success();
+
+    let wasm = fs::read(&output).unwrap();
+    let sections = top_level_custom_sections(&wasm);
+    let section = |name: &str| {
+        sections
+            .iter()
+            .filter(|(section, _)| section == name)
+            .map(|(_, data)| data.clone())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(section("version"), [b"1.2.3".to_vec()]);
+    assert_eq!(section("description"), [b"answers".to_vec()]);
+
+    let producers = section("producers");
+    assert_eq!(producers.len(), 1, "{sections:?}");
+    let producers = String::from_utf8_lossy(&producers[0]);
+    assert!(producers.contains("componentize-qjs"), "{producers}");
+    assert!(producers.contains(env!("CARGO_PKG_VERSION")), "{producers}");
+    assert!(producers.contains("QuickJS"), "{producers}");
+
+    componentize_qjs()
+        .arg("--wit")
+        .arg(&wit_path)
+        .arg("--js")
+        .arg(&js_path)
+        .arg("--no-metadata")
+        .arg("--output")
+        .arg(&output)
+        .assert()
+        .success();
+
+    let wasm = fs::read(&output).unwrap();
+    let sections = top_level_custom_sections(&wasm);
+    assert!(
+        sections.iter().all(|(name, _)| name != "version"),
+        "{sections:?}"
+    );
+    let mut inst =
+        ComponentInstance::from_wasm(wasm, vec![], vec![]).expect("should instantiate component");
+    assert_eq!(inst.call1("answer", &[]), Val::U32(42));
+}
+
 #[test]
 fn test_cli_resolves_package_import_from_module_root() {
     let dir = TempDir::new().unwrap();
@@ -320,6 +507,93 @@ fn test_cli_reports_missing_import() {
         .stderr(predicate::str::contains("filesystem module not found"));
 }
 
+#[test]
+fn test_cli_warns_on_export_arity() {
+    let dir = TempDir::new().unwrap();
+    let wit_path = dir.path().join("test.wit");
+    fs::write(
+        &wit_path,
+        "package test:hello;\nworld hello { export add: func(a: u32, b: u32) -> u32; }",
+    )
+    .unwrap();
+    let js_path = dir.path().join("test.js");
+    fs::write(&js_path, "export function add(a, b, c) { return a + b; }").unwrap();
+
+    componentize_qjs()
+        .arg("--wit")
+        .arg(&wit_path)
+        .arg("--js")
+        .arg(&js_path)
+        .arg("--output")
+        .arg(dir.path().join("output.wasm"))
+        .assert()
+        .success()
+        .stderr(predicate::str::contains(
+            "warning: export `add` declares 3 parameters but its WIT signature has 2",
+        ));
+}
+
+#[test]
+fn test_cli_rejects_missing_export() {
+    let dir = TempDir::new().unwrap();
+    let wit_path = dir.path().join("test.wit");
+    fs::write(
+        &wit_path,
+        "package test:hello;\nworld hello { export add: func(a: u32, b: u32) -> u32; }",
+    )
+    .unwrap();
+    let js_path = dir.path().join("test.js");
+    fs::write(&js_path, "globalThis.add = (a, b) => a + b;").unwrap();
+
+    componentize_qjs()
+        .arg("--wit")
+        .arg(&wit_path)
+        .arg("--js")
+        .arg(&js_path)
+        .arg("--output")
+        .arg(dir.path().join("output.wasm"))
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "the JavaScript module does not implement these WIT exports",
+        ))
+        .stderr(predicate::str::contains(
+            "`add` (found a global `add`, export it from the module instead)",
+        ));
+}
+
+#[test]
+fn test_cli_warns_on_failed_prime() {
+    let dir = TempDir::new().unwrap();
+    let wit_path = dir.path().join("test.wit");
+    fs::write(
+        &wit_path,
+        "package test:hello;\nworld hello { export add: func(a: u32, b: u32) -> u32; }",
+    )
+    .unwrap();
+    let js_path = dir.path().join("test.js");
+    fs::write(
+        &js_path,
+        "export function add(a, b) { if (a === 0) throw new Error('zero'); return a + b; }",
+    )
+    .unwrap();
+
+    componentize_qjs()
+        .arg("--wit")
+        .arg(&wit_path)
+        .arg("--js")
+        .arg(&js_path)
+        .arg("--prime")
+        .arg("add=[0, 1]")
+        .arg("--output")
+        .arg(dir.path().join("output.wasm"))
+        .assert()
+        .success()
+        .stderr(predicate::str::contains(
+            "warning: priming export `add` failed: Error: zero",
+        ));
+}
+
 #[test]
 fn test_cli_stub_wasi() {
     let (output, _dir) = run_cli_build(
@@ -335,6 +609,124 @@ fn test_cli_stub_wasi() {
     assert_eq!(inst.call1("add", &[Val::U32(3), Val::U32(4)]), Val::U32(7));
 }
 
+#[test]
+fn test_cli_cache_dir_reuses_compilation() {
+    let cache = TempDir::new().unwrap();
+    let cache_dir = cache.path().to_str().unwrap();
+    let wit = "package test:cache;\nworld cache { export calc: func(a: u32, b: u32) -> u32; }";
+
+    let (first, _first_dir) = run_cli_build(
+        wit,
+        "export function calc(a, b) { return a + b; }",
+        &["--cache-dir", cache_dir],
+    );
+    let entries = || fs::read_dir(cache.path()).unwrap().count();
+    assert_eq!(entries(), 1, "first build should populate the cache");
+
+    let (second, _second_dir) = run_cli_build(
+        wit,
+        "export function calc(a, b) { return a * b; }",
+        &["--cache-dir", cache_dir],
+    );
+    assert_eq!(entries(), 1, "JS-only change should reuse the cache entry");
+
+    let mut inst = ComponentInstance::from_wasm(fs::read(&first).unwrap(), vec![], vec![])
+        .expect("should instantiate first component");
+    assert_eq!(inst.call1("calc", &[Val::U32(3), Val::U32(4)]), Val::U32(7));
+
+    let mut inst = ComponentInstance::from_wasm(fs::read(&second).unwrap(), vec![], vec![])
+        .expect("should instantiate second component");
+    assert_eq!(
+        inst.call1("calc", &[Val::U32(3), Val::U32(4)]),
+        Val::U32(12)
+    );
+}
+
+#[test]
+fn test_cli_cache_dir_recompiles_corrupted_entry() {
+    let cache = TempDir::new().unwrap();
+    let cache_dir = cache.path().to_str().unwrap();
+    let wit = "package test:cache;\nworld cache { export calc: func(a: u32, b: u32) -> u32; }";
+    let js = "export function calc(a, b) { return a + b; }";
+
+    run_cli_build(wit, js, &["--cache-dir", cache_dir]);
+    let entry = fs::read_dir(cache.path())
+        .unwrap()
+        .next()
+        .unwrap()
+        .unwrap()
+        .path();
+    let mut corrupted = fs::read(&entry).unwrap();
+    let last = corrupted.len() - 1;
+    corrupted[last] ^= 0xff;
+    fs::write(&entry, &corrupted).unwrap();
+
+    let (output, _dir) = run_cli_build(wit, js, &["--cache-dir", cache_dir]);
+    assert_ne!(
+        fs::read(&entry).unwrap(),
+        corrupted,
+        "a corrupted entry should be compiled again and replaced"
+    );
+    let mut inst = ComponentInstance::from_wasm(fs::read(&output).unwrap(), vec![], vec![])
+        .expect("should instantiate component");
+    assert_eq!(inst.call1("calc", &[Val::U32(3), Val::U32(4)]), Val::U32(7));
+}
+
+#[test]
+fn test_cli_dry_run_prints_plan() {
+    let dir = TempDir::new().unwrap();
+    let wit_path = dir.path().join("test.wit");
+    fs::write(
+        &wit_path,
+        "package test:plan;\n\
+         interface log { log: func(msg: string); }\n\
+         world plan { import log; export add: func(a: u32, b: u32) -> u32; }",
+    )
+    .unwrap();
+    let js_path = dir.path().join("test.js");
+    fs::write(&js_path, "export function add(a, b) { return a + b; }").unwrap();
+    let output = dir.path().join("output.wasm");
+    let stubbed_wasi =
+        predicate::str::is_match(r#"\{"name": "wasi:[^"]+", "action": "stub"\}"#).unwrap();
+
+    componentize_qjs()
+        .arg("--wit")
+        .arg(&wit_path)
+        .arg("--js")
+        .arg(&js_path)
+        .arg("--output")
+        .arg(&output)
+        .args(["--stub-wasi", "--polyfill", "streams", "--dry-run"])
+        .assert()
+        .success()
+        .stdout(predicate::str::starts_with("{"))
+        .stdout(predicate::str::contains(r#""world": "plan""#))
+        .stdout(predicate::str::contains(r#""run_export_added": false"#))
+        .stdout(predicate::str::contains(
+            r#"{"name": "test:plan/log", "action": "host"}"#,
+        ))
+        .stdout(stubbed_wasi)
+        .stdout(predicate::str::contains(
+            r#"{"name": "local:init/module-loader", "action": "stub"}"#,
+        ))
+        .stdout(predicate::str::contains(r#""exports": ["add"]"#))
+        .stdout(predicate::str::contains(
+            r#""polyfills": ["url", "encoding", "structured-clone", "events", "abort", "streams"]"#,
+        ))
+        .stdout(predicate::str::contains(r#""cache_entry": null"#));
+    assert!(!output.exists(), "a dry run should not write the component");
+
+    componentize_qjs()
+        .arg("--wit")
+        .arg(&wit_path)
+        .arg("--js")
+        .arg(&js_path)
+        .args(["--polyfill", "fetch", "--dry-run"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("unknown polyfill `fetch`"));
+}
+
 #[test]
 fn test_cli_opt_size_runtime() {
     let (output, _dir) = run_cli_build(
@@ -2,16 +2,20 @@
 #![allow(dead_code)]
 
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::OnceLock;
+use std::time::Duration;
 
 use tempfile::TempDir;
 use wasmtime::component::{Component, Instance, Linker, ResourceTable, Val};
 use wasmtime::{Config, Engine, Store};
 use wasmtime_wasi::p2::pipe::{MemoryInputPipe, MemoryOutputPipe};
-use wasmtime_wasi::{WasiCtx, WasiCtxBuilder, WasiCtxView, WasiView};
+use wasmtime_wasi::{DirPerms, FilePerms, WasiCtx, WasiCtxBuilder, WasiCtxView, WasiView};
 
-use componentize_qjs::{ComponentizeOpts, Runtime};
+use componentize_qjs::{
+    ArityMismatch, ComponentizeOpts, EnvShims, ImportAlias, ImportLog, InitDir, MathRandom,
+    Metadata, Naming, OptLevel, PrimeCall, Rename, Runtime, VersionAliasing,
+};
 
 pub struct WasiCtxState {
     pub wasi: WasiCtx,
@@ -32,7 +36,9 @@ pub fn engine() -> &'static Engine {
     ENGINE.get_or_init(|| {
         let mut config = Config::new();
         #[cfg(feature = "component-model-async")]
-        config.wasm_component_model_async(true);
+        config
+            .wasm_component_model_async(true)
+            .wasm_component_model_error_context(true);
         config.wasm_component_model(true);
         Engine::new(&config).expect("Failed to create engine")
     })
@@ -45,6 +51,7 @@ pub fn async_engine() -> &'static Engine {
         config.wasm_component_model(true);
         config.wasm_component_model_async(true);
         config.wasm_component_model_async_stackful(true);
+        config.wasm_component_model_error_context(true);
         Engine::new(&config).expect("Failed to create async engine")
     })
 }
@@ -58,12 +65,52 @@ pub struct Expectation {
 /// Builder for constructing and running component tests.
 pub struct TestCase {
     wit: Option<String>,
+    wit_source: Option<String>,
     wit_dir: Option<PathBuf>,
     world_name: Option<String>,
     script: Option<String>,
+    modules: Vec<(String, String)>,
+    entry_module: Option<String>,
+    preludes: Vec<(String, String)>,
     stub_wasi: bool,
+    strip_source: bool,
+    bytecode: bool,
+    lazy_eval: bool,
+    gc_interval: Option<u32>,
+    max_list_length: Option<u32>,
+    memory_limit: Option<u32>,
+    memory_report: Option<u32>,
+    max_instructions: Option<u64>,
+    init_timeout: Option<Duration>,
+    optimize: Option<OptLevel>,
+    reproducible: bool,
+    debug_runtime: bool,
+    strict_types: bool,
+    tagged_results: bool,
+    throwing_imports: bool,
+    wrap_integers: bool,
+    finite_floats: bool,
+    trace_values: bool,
+    features: Vec<String>,
+    polyfills: Vec<String>,
+    env_shims: EnvShims<'static>,
+    deny_warnings: bool,
+    arity_mismatch: ArityMismatch,
+    math_random: Option<MathRandom>,
+    prime: Vec<PrimeCall>,
+    version_aliasing: VersionAliasing,
+    import_aliases: Vec<ImportAlias>,
+    naming: Naming,
+    renames: Vec<Rename>,
+    harden: bool,
+    record_imports: Option<String>,
+    replay_imports: Option<String>,
     env_vars: Vec<(String, String)>,
+    init_env: Vec<(String, String)>,
+    init_args: Vec<String>,
+    init_dirs: Vec<InitDir>,
     stdin: Option<String>,
+    preopen: Option<PathBuf>,
     expectations: Vec<Expectation>,
 }
 
@@ -71,12 +118,52 @@ impl TestCase {
     pub fn new() -> Self {
         Self {
             wit: None,
+            wit_source: None,
             wit_dir: None,
             world_name: None,
             script: None,
+            modules: Vec::new(),
+            entry_module: None,
+            preludes: Vec::new(),
             stub_wasi: false,
+            strip_source: false,
+            bytecode: false,
+            lazy_eval: false,
+            gc_interval: None,
+            max_list_length: None,
+            memory_limit: None,
+            memory_report: None,
+            max_instructions: None,
+            init_timeout: None,
+            optimize: None,
+            reproducible: false,
+            debug_runtime: false,
+            strict_types: false,
+            tagged_results: false,
+            throwing_imports: false,
+            wrap_integers: false,
+            finite_floats: false,
+            trace_values: false,
+            features: Vec::new(),
+            polyfills: Vec::new(),
+            env_shims: EnvShims::default(),
+            deny_warnings: false,
+            arity_mismatch: ArityMismatch::default(),
+            math_random: None,
+            prime: Vec::new(),
+            version_aliasing: VersionAliasing::default(),
+            import_aliases: Vec::new(),
+            naming: Naming::default(),
+            renames: Vec::new(),
+            harden: false,
+            record_imports: None,
+            replay_imports: None,
             env_vars: Vec::new(),
+            init_env: Vec::new(),
+            init_args: Vec::new(),
+            init_dirs: Vec::new(),
             stdin: None,
+            preopen: None,
             expectations: Vec::new(),
         }
     }
@@ -87,6 +174,12 @@ impl TestCase {
         self
     }
 
+    /// Set inline WIT source, passed as `wit_source` without a file.
+    pub fn wit_source(mut self, wit: &str) -> Self {
+        self.wit_source = Some(wit.to_string());
+        self
+    }
+
     /// Set path to a WIT directory (with deps/).
     pub fn wit_dir(mut self, path: impl Into<PathBuf>) -> Self {
         self.wit_dir = Some(path.into());
@@ -104,11 +197,236 @@ impl TestCase {
         self
     }
 
+    /// Add an in-memory JavaScript module the script can import.
+    pub fn module(mut self, name: &str, source: &str) -> Self {
+        self.modules.push((name.to_string(), source.to_string()));
+        self
+    }
+
+    /// Evaluate the in-memory module `name` as the entry instead of a script.
+    pub fn entry_module(mut self, name: &str) -> Self {
+        self.entry_module = Some(name.to_string());
+        self
+    }
+
+    /// Add a script evaluated in the global scope before the module.
+    pub fn prelude(mut self, name: &str, source: &str) -> Self {
+        self.preludes.push((name.to_string(), source.to_string()));
+        self
+    }
+
     pub fn stub_wasi(mut self) -> Self {
         self.stub_wasi = true;
         self
     }
 
+    /// Drop function source text from the snapshot.
+    pub fn strip_source(mut self) -> Self {
+        self.strip_source = true;
+        self
+    }
+
+    /// Evaluate the module from QuickJS bytecode compiled before Wizer.
+    pub fn bytecode(mut self) -> Self {
+        self.bytecode = true;
+        self
+    }
+
+    /// Evaluate the script on the first export call instead of during Wizer.
+    pub fn lazy_eval(mut self) -> Self {
+        self.lazy_eval = true;
+        self
+    }
+
+    /// Run a full GC cycle after every `calls` export calls.
+    pub fn gc_interval(mut self, calls: u32) -> Self {
+        self.gc_interval = Some(calls);
+        self
+    }
+
+    /// Reject lists and strings longer than `len` when lowering JS values.
+    pub fn max_list_length(mut self, len: u32) -> Self {
+        self.max_list_length = Some(len);
+        self
+    }
+
+    /// Cap the QuickJS heap at `bytes`.
+    pub fn memory_limit(mut self, bytes: u32) -> Self {
+        self.memory_limit = Some(bytes);
+        self
+    }
+
+    /// Report heap usage to stderr after every `calls` export calls.
+    pub fn memory_report(mut self, calls: u32) -> Self {
+        self.memory_report = Some(calls);
+        self
+    }
+
+    /// Trap calls that run more than about `count` bytecode instructions.
+    pub fn max_instructions(mut self, count: u64) -> Self {
+        self.max_instructions = Some(count);
+        self
+    }
+
+    /// Fail the build if Wizer initialization runs longer than `timeout`.
+    pub fn init_timeout(mut self, timeout: Duration) -> Self {
+        self.init_timeout = Some(timeout);
+        self
+    }
+
+    /// Run the snapshotted component through wasm-opt.
+    pub fn optimize(mut self, level: OptLevel) -> Self {
+        self.optimize = Some(level);
+        self
+    }
+
+    /// Pin the clocks and randomness initialization sees.
+    pub fn reproducible(mut self) -> Self {
+        self.reproducible = true;
+        self
+    }
+
+    /// Embed the debug runtime.
+    pub fn debug_runtime(mut self) -> Self {
+        self.debug_runtime = true;
+        self
+    }
+
+    /// Check lowered values against their WIT type.
+    pub fn strict_types(mut self) -> Self {
+        self.strict_types = true;
+        self
+    }
+
+    /// Pass top-level `result` values as `{ tag, val }` objects.
+    pub fn tagged_results(mut self) -> Self {
+        self.tagged_results = true;
+        self
+    }
+
+    /// Keep return/throw for imports under tagged results.
+    pub fn throwing_imports(mut self) -> Self {
+        self.throwing_imports = true;
+        self
+    }
+
+    /// Wrap out-of-range integers instead of throwing.
+    pub fn wrap_integers(mut self) -> Self {
+        self.wrap_integers = true;
+        self
+    }
+
+    /// Reject NaN and infinite floats.
+    pub fn finite_floats(mut self) -> Self {
+        self.finite_floats = true;
+        self
+    }
+
+    /// Log lifted and lowered values to stderr.
+    pub fn trace_values(mut self) -> Self {
+        self.trace_values = true;
+        self
+    }
+
+    /// Enable a feature flag visible through `__FEATURES__`.
+    pub fn feature(mut self, name: &str) -> Self {
+        self.features.push(name.to_string());
+        self
+    }
+
+    /// Install a bundled polyfill in addition to the defaults.
+    pub fn polyfill(mut self, name: &str) -> Self {
+        self.polyfills.push(name.to_string());
+        self
+    }
+
+    /// Install only the polyfills added with [`TestCase::polyfill`].
+    pub fn no_default_polyfills(mut self) -> Self {
+        self.env_shims.no_default_polyfills = true;
+        self
+    }
+
+    /// Leave out the globals generated from WASI imports.
+    pub fn no_wasi_globals(mut self) -> Self {
+        self.env_shims.no_wasi_globals = true;
+        self
+    }
+
+    /// Fail the build on initialization warnings.
+    pub fn deny_warnings(mut self) -> Self {
+        self.deny_warnings = true;
+        self
+    }
+
+    /// Choose what to do with exports whose arity differs from their WIT signature.
+    pub fn arity_mismatch(mut self, mode: ArityMismatch) -> Self {
+        self.arity_mismatch = mode;
+        self
+    }
+
+    /// Call export `name` with the JSON array `args` during initialization.
+    pub fn prime(mut self, name: &str, args: &str) -> Self {
+        self.prime.push(PrimeCall {
+            name: name.to_string(),
+            args: args.to_string(),
+        });
+        self
+    }
+
+    /// Choose which shortened import specifiers resolve to versioned interfaces.
+    pub fn version_aliasing(mut self, aliasing: VersionAliasing) -> Self {
+        self.version_aliasing = aliasing;
+        self
+    }
+
+    /// Let the script import `interface` as `specifier`.
+    pub fn import_alias(mut self, specifier: &str, interface: &str) -> Self {
+        self.import_aliases.push(ImportAlias {
+            specifier: specifier.to_string(),
+            interface: interface.to_string(),
+        });
+        self
+    }
+
+    /// Choose how WIT names map to JavaScript names.
+    pub fn naming(mut self, naming: Naming) -> Self {
+        self.naming = naming;
+        self
+    }
+
+    /// Give the WIT function `function` the JavaScript name `js_name`.
+    pub fn rename(mut self, function: &str, js_name: &str) -> Self {
+        self.renames.push(Rename {
+            function: function.to_string(),
+            js_name: js_name.to_string(),
+        });
+        self
+    }
+
+    /// Freeze the environment once the module is evaluated.
+    pub fn harden(mut self) -> Self {
+        self.harden = true;
+        self
+    }
+
+    /// Replace `Math.random` with `source`.
+    pub fn math_random(mut self, source: MathRandom) -> Self {
+        self.math_random = Some(source);
+        self
+    }
+
+    /// Record sync import calls to `path` (a guest path) at runtime.
+    pub fn record_imports(mut self, path: &str) -> Self {
+        self.record_imports = Some(path.to_string());
+        self
+    }
+
+    /// Satisfy sync import calls from the capture at `path` (a guest path).
+    pub fn replay_imports(mut self, path: &str) -> Self {
+        self.replay_imports = Some(path.to_string());
+        self
+    }
+
     /// Add an environment variable visible to the WASI context.
     pub fn env(mut self, key: &str, value: &str) -> Self {
         self.env_vars.push((key.to_string(), value.to_string()));
@@ -121,6 +439,42 @@ impl TestCase {
         self
     }
 
+    /// Preopen a host directory as the guest's `/`.
+    pub fn preopen(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.preopen = Some(dir.into());
+        self
+    }
+
+    /// Add an environment variable visible during Wizer initialization.
+    pub fn init_env(mut self, key: &str, value: &str) -> Self {
+        self.init_env.push((key.to_string(), value.to_string()));
+        self
+    }
+
+    /// Add a command-line argument visible during Wizer initialization.
+    pub fn init_arg(mut self, arg: &str) -> Self {
+        self.init_args.push(arg.to_string());
+        self
+    }
+
+    /// Preopen a host directory at `guest` during Wizer initialization.
+    pub fn init_dir(mut self, host: impl Into<PathBuf>, guest: &str) -> Self {
+        self.init_dirs.push(InitDir {
+            host: host.into(),
+            guest: guest.to_string(),
+        });
+        self
+    }
+
+    /// Import capture mode selected by `record_imports`/`replay_imports`.
+    fn import_log(&self) -> Option<ImportLog<'_>> {
+        match (&self.record_imports, &self.replay_imports) {
+            (Some(path), _) => Some(ImportLog::Record(path)),
+            (None, Some(path)) => Some(ImportLog::Replay(path)),
+            (None, None) => None,
+        }
+    }
+
     /// Register an expected function call: name, params, and expected return value.
     pub fn expect_call(mut self, name: &str, params: Vec<Val>, expected: Val) -> Self {
         self.expectations.push(Expectation {
@@ -131,61 +485,100 @@ impl TestCase {
         self
     }
 
-    /// Build the component and return a live instance ready for calls.
-    pub fn build(self) -> anyhow::Result<ComponentInstance> {
+    /// Build the component and return its wasm bytes.
+    pub async fn componentize(&self) -> anyhow::Result<Vec<u8>> {
         let dir = TempDir::new()?;
 
         let wit_path = if let Some(ref wit_dir) = self.wit_dir {
             wit_dir.clone()
-        } else {
+        } else if let Some(ref wit) = self.wit {
             let p = dir.path().join("test.wit");
-            fs::write(&p, self.wit.as_deref().unwrap())?;
+            fs::write(&p, wit)?;
             p
+        } else {
+            PathBuf::from("test.wit")
         };
 
         let opts = ComponentizeOpts {
             wit_path: &wit_path,
-            js_source: self.script.as_deref().unwrap(),
+            wit_source: self.wit_source.as_deref(),
+            wit_package: None,
+            js_source: self.script.as_deref().unwrap_or_default(),
             js_path: None,
             module_root: None,
+            init_env: &self.init_env,
+            init_args: &self.init_args,
+            init_dirs: &self.init_dirs,
+            modules: &self.modules,
+            entry_module: self.entry_module.as_deref(),
+            preludes: &self.preludes,
             world_name: self.world_name.as_deref(),
             stub_wasi: self.stub_wasi,
             disable_gc: false,
+            strip_source: self.strip_source,
+            bytecode: self.bytecode,
+            lazy_eval: self.lazy_eval,
+            gc_interval: self.gc_interval,
+            gc_threshold: None,
+            max_list_length: self.max_list_length,
+            memory_limit: self.memory_limit,
+            memory_report: self.memory_report,
+            max_instructions: self.max_instructions,
+            init_timeout: self.init_timeout,
+            strict_types: self.strict_types,
+            tagged_results: self.tagged_results,
+            throwing_imports: self.throwing_imports,
+            wrap_integers: self.wrap_integers,
+            finite_floats: self.finite_floats,
+            warn_f32_rounding: false,
+            trace_values: self.trace_values,
+            features: &self.features,
+            env_shims: EnvShims {
+                polyfills: &self.polyfills,
+                ..self.env_shims
+            },
+            deny_warnings: self.deny_warnings,
+            arity_mismatch: self.arity_mismatch,
+            math_random: self.math_random,
+            prime: &self.prime,
+            version_aliasing: self.version_aliasing,
+            import_aliases: &self.import_aliases,
+            naming: self.naming,
+            renames: &self.renames,
+            harden: self.harden,
+            import_log: self.import_log(),
+            optimize: self.optimize,
+            core_module: false,
+            custom_sections: &[],
+            metadata: Metadata::default(),
+            reproducible: self.reproducible,
+            cache_dir: None,
             runtime: Runtime::Default,
+            debug_runtime: self.debug_runtime,
         };
 
+        componentize_qjs::componentize(&opts).await
+    }
+
+    /// Build the component and return a live instance ready for calls.
+    pub fn build(self) -> anyhow::Result<ComponentInstance> {
         let rt = tokio::runtime::Builder::new_current_thread()
             .enable_all()
             .build()?;
 
-        let wasm = rt.block_on(componentize_qjs::componentize(&opts))?;
-        ComponentInstance::from_wasm_with_stdin(wasm, self.env_vars, self.stdin, self.expectations)
+        let wasm = rt.block_on(self.componentize())?;
+        ComponentInstance::from_wasm_with_stdin(
+            wasm,
+            self.env_vars,
+            self.stdin,
+            self.preopen.as_deref(),
+            self.expectations,
+        )
     }
 
     /// Build the component and return an async-capable instance.
     pub async fn build_async(self) -> anyhow::Result<AsyncComponentInstance> {
-        let dir = TempDir::new()?;
-
-        let wit_path = if let Some(ref wit_dir) = self.wit_dir {
-            wit_dir.clone()
-        } else {
-            let p = dir.path().join("test.wit");
-            fs::write(&p, self.wit.as_deref().unwrap())?;
-            p
-        };
-
-        let opts = ComponentizeOpts {
-            wit_path: &wit_path,
-            js_source: self.script.as_deref().unwrap(),
-            js_path: None,
-            module_root: None,
-            world_name: self.world_name.as_deref(),
-            stub_wasi: self.stub_wasi,
-            disable_gc: false,
-            runtime: Runtime::Default,
-        };
-
-        let wasm = componentize_qjs::componentize(&opts).await?;
+        let wasm = self.componentize().await?;
 
         AsyncComponentInstance::from_wasm_with_stdin(wasm, self.env_vars, self.stdin).await
     }
@@ -195,6 +588,7 @@ pub struct ComponentInstance {
     store: Store<WasiCtxState>,
     inner: Instance,
     stdout: MemoryOutputPipe,
+    stderr: MemoryOutputPipe,
     expectations: Vec<Expectation>,
 }
 
@@ -205,13 +599,14 @@ impl ComponentInstance {
         env_vars: Vec<(String, String)>,
         expectations: Vec<Expectation>,
     ) -> anyhow::Result<Self> {
-        Self::from_wasm_with_stdin(wasm, env_vars, None, expectations)
+        Self::from_wasm_with_stdin(wasm, env_vars, None, None, expectations)
     }
 
     pub fn from_wasm_with_stdin(
         wasm: Vec<u8>,
         env_vars: Vec<(String, String)>,
         stdin: Option<String>,
+        preopen: Option<&Path>,
         expectations: Vec<Expectation>,
     ) -> anyhow::Result<Self> {
         let engine = engine();
@@ -225,9 +620,14 @@ impl ComponentInstance {
             }
         }
         let stdout = MemoryOutputPipe::new(10000);
+        let stderr = MemoryOutputPipe::new(10000);
         wasi_builder
             .stdin(MemoryInputPipe::new(stdin.unwrap_or_default()))
-            .stdout(stdout.clone());
+            .stdout(stdout.clone())
+            .stderr(stderr.clone());
+        if let Some(dir) = preopen {
+            wasi_builder.preopened_dir(dir, "/", DirPerms::all(), FilePerms::all())?;
+        }
         let wasi = wasi_builder.build();
         let table = ResourceTable::new();
         let mut store = Store::new(engine, WasiCtxState { wasi, table });
@@ -241,6 +641,7 @@ impl ComponentInstance {
             store,
             inner: instance,
             stdout,
+            stderr,
             expectations,
         })
     }
@@ -282,6 +683,10 @@ impl ComponentInstance {
         self.stdout.contents().to_vec()
     }
 
+    pub fn stderr_bytes(&self) -> Vec<u8> {
+        self.stderr.contents().to_vec()
+    }
+
     /// Get the wasmtime instance and store for typed/interface function access.
     pub fn parts(&mut self) -> (&Instance, &mut Store<WasiCtxState>) {
         (&self.inner, &mut self.store)
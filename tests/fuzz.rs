@@ -17,7 +17,7 @@ use std::sync::{Mutex, OnceLock};
 use quickcheck::{Arbitrary, Gen, TestResult, quickcheck};
 use wasmtime::component::Val;
 
-use componentize_qjs::{ComponentizeOpts, Runtime};
+use componentize_qjs::{ComponentizeOpts, EnvShims, Metadata, Runtime};
 
 const MAX_SAFE_INT: i64 = (1i64 << 53) - 1;
 const MEM_TOLERANCE: i64 = 1024;
@@ -253,13 +253,57 @@ mod fuzz {
 
             let opts = ComponentizeOpts {
                 wit_path: &wit,
+                wit_source: None,
+                wit_package: None,
                 js_source: &js,
                 js_path: None,
                 module_root: None,
+                init_env: &[],
+                init_args: &[],
+                init_dirs: &[],
+                modules: &[],
+                entry_module: None,
+                preludes: &[],
                 world_name: None,
                 stub_wasi: true,
                 disable_gc: false,
+                strip_source: false,
+                bytecode: false,
+                lazy_eval: false,
+                gc_interval: None,
+                gc_threshold: None,
+                max_list_length: None,
+                memory_limit: None,
+                memory_report: None,
+                max_instructions: None,
+                init_timeout: None,
+                strict_types: false,
+                tagged_results: false,
+                throwing_imports: false,
+                wrap_integers: false,
+                finite_floats: false,
+                warn_f32_rounding: false,
+                trace_values: false,
+                features: &[],
+                env_shims: EnvShims::default(),
+                deny_warnings: false,
+                arity_mismatch: Default::default(),
+                math_random: None,
+                prime: &[],
+                version_aliasing: Default::default(),
+                import_aliases: &[],
+                naming: Default::default(),
+                renames: &[],
+                harden: false,
+                import_log: None,
+                optimize: None,
+                core_module: false,
+                custom_sections: &[],
+                metadata: Metadata::default(),
+                reproducible: false,
+                cache_dir: None,
                 runtime: Runtime::Default,
+                debug_runtime: false,
             };
 
             let rt = tokio::runtime::Builder::new_current_thread()
@@ -458,13 +502,57 @@ mod fuzz_async {
 
             let opts = ComponentizeOpts {
                 wit_path: &wit,
+                wit_source: None,
+                wit_package: None,
                 js_source: &js,
                 js_path: None,
                 module_root: None,
+                init_env: &[],
+                init_args: &[],
+                init_dirs: &[],
+                modules: &[],
+                entry_module: None,
+                preludes: &[],
                 world_name: None,
                 stub_wasi: false,
                 disable_gc: false,
+                strip_source: false,
+                bytecode: false,
+                lazy_eval: false,
+                gc_interval: None,
+                gc_threshold: None,
+                max_list_length: None,
+                memory_limit: None,
+                memory_report: None,
+                max_instructions: None,
+                init_timeout: None,
+                strict_types: false,
+                tagged_results: false,
+                throwing_imports: false,
+                wrap_integers: false,
+                finite_floats: false,
+                warn_f32_rounding: false,
+                trace_values: false,
+                features: &[],
+                env_shims: EnvShims::default(),
+                deny_warnings: false,
+                arity_mismatch: Default::default(),
+                math_random: None,
+                prime: &[],
+                version_aliasing: Default::default(),
+                import_aliases: &[],
+                naming: Default::default(),
+                renames: &[],
+                harden: false,
+                import_log: None,
+                optimize: None,
+                core_module: false,
+                custom_sections: &[],
+                metadata: Metadata::default(),
+                reproducible: false,
+                cache_dir: None,
                 runtime: Runtime::Default,
+                debug_runtime: false,
             };
 
             let rt = tokio::runtime::Builder::new_current_thread()
@@ -0,0 +1,54 @@
+//! Tests for the `componentize_qjs::host` helpers.
+mod common;
+
+use componentize_qjs::host::ComponentInstance;
+use wasmtime::component::Val;
+use wasmtime_wasi::WasiCtxBuilder;
+use wasmtime_wasi::p2::pipe::MemoryOutputPipe;
+
+use common::TestCase;
+
+#[tokio::test]
+async fn test_host_calls_exports() {
+    let wasm = TestCase::new()
+        .wit_dir(common::wasi_wit_dir())
+        .world("host-api")
+        .script(
+            r#"
+            import stdout from "wasi:cli/stdout@0.2.12";
+
+            export function add(a, b) { return a + b; }
+            export function greet() {
+                stdout.getStdout().blockingWriteAndFlush(new Uint8Array([104, 105]));
+            }
+            export const shout = { shout(s) { return s.toUpperCase(); } };
+        "#,
+        )
+        .componentize()
+        .await
+        .unwrap();
+
+    let stdout = MemoryOutputPipe::new(1024);
+    let mut wasi = WasiCtxBuilder::new();
+    wasi.stdout(stdout.clone());
+    let mut inst = ComponentInstance::with_wasi(&wasm, wasi).await.unwrap();
+
+    let sum = inst
+        .call1("add", &[Val::U32(2), Val::U32(3)])
+        .await
+        .unwrap();
+    assert_eq!(sum, Val::U32(5));
+
+    let shout = inst
+        .call1("test:wasi/shout#shout", &[Val::String("hi".into())])
+        .await
+        .unwrap();
+    assert_eq!(shout, Val::String("HI".into()));
+
+    let greet = inst.call1("greet", &[]).await.unwrap();
+    assert_eq!(greet, Val::Result(Ok(None)));
+    assert_eq!(&stdout.contents()[..], b"hi");
+
+    let missing = inst.call("missing", &[]).await.unwrap_err();
+    assert!(missing.to_string().contains("export `missing` not found"));
+}
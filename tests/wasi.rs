@@ -2,10 +2,18 @@
 mod common;
 
 use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
 
-use wasmtime::component::Val;
+use componentize_qjs::{ComponentizeBuilder, Naming, VersionAliasing};
+use wasmtime::Store;
+use wasmtime::component::{
+    Component, ComponentType, Lift, Linker, LinkerInstance, Lower, Resource, ResourceTable,
+    ResourceType, Val,
+};
+use wasmtime_wasi::WasiCtxBuilder;
 
-use common::{TestCase, wasi_wit_dir};
+use common::{TestCase, WasiCtxState, wasi_wit_dir};
 
 #[test]
 fn test_wasi_random() {
@@ -65,6 +73,61 @@ fn test_wasi_named_imports() {
     }
 }
 
+#[cfg(feature = "profile")]
+#[test]
+fn test_profile() {
+    let mut inst = TestCase::new()
+        .wit_dir(wasi_wit_dir())
+        .world("wasi-profile")
+        .script(
+            r#"
+            import random from "wasi:random/random@0.2.12";
+
+            export function spin(n) {
+                let sum = 0;
+                for (let i = 0; i < n; i++) sum = (sum + i) % 1000;
+                return random.getRandomU64();
+            }
+            export function report() {
+                const { exports, imports } = __cqjs.getProfile();
+                const { calls, interruptTicks, time } = exports.spin;
+                const randomU64 = imports["wasi:random/random@0.2.12#get-random-u64"];
+                __cqjs.dumpProfile();
+                return [
+                    calls,
+                    interruptTicks > 0,
+                    time >= 0,
+                    exports.report.calls,
+                    randomU64.calls,
+                    randomU64.interruptTicks,
+                    randomU64.time >= 0,
+                ].join(",");
+            }
+        "#,
+        )
+        .build()
+        .expect("should build wasi-profile component");
+
+    inst.call1("spin", &[Val::U32(10)]);
+    inst.call1("spin", &[Val::U32(1_000_000)]);
+    assert_eq!(
+        inst.call1("report", &[]),
+        Val::String("2,true,true,1,2,0,true".into())
+    );
+    let stderr = String::from_utf8(inst.stderr_bytes()).unwrap();
+    assert!(stderr.contains("int. ticks"), "{stderr}");
+    let has_row = |kind: &str, name: &str| {
+        stderr
+            .lines()
+            .any(|line| line.starts_with(kind) && line.ends_with(&format!("  {name}")))
+    };
+    assert!(has_row("export", "spin"), "{stderr}");
+    assert!(
+        has_row("import", "wasi:random/random@0.2.12#get-random-u64"),
+        "{stderr}"
+    );
+}
+
 #[test]
 fn test_wasi_clocks() {
     let mut inst = TestCase::new()
@@ -110,6 +173,86 @@ fn test_wasi_clocks() {
     );
 }
 
+#[test]
+fn test_timers() {
+    let mut inst = TestCase::new()
+        .wit_dir(wasi_wit_dir())
+        .world("wasi-timers")
+        .script(
+            r#"
+            import clock from "wasi:clocks/monotonic-clock@0.2.12";
+
+            export function sequence() {
+                const start = clock.now();
+                const events = [];
+                return new Promise((resolve) => {
+                    setTimeout(() => events.push("b"), 100);
+                    setTimeout((name) => events.push(name), 10, "a");
+                    clearTimeout(setTimeout(() => events.push("cancelled"), 1));
+                    let ticks = 0;
+                    const interval = setInterval(() => {
+                        events.push(`tick${++ticks}`);
+                        Promise.resolve().then(() => events.push(`job${ticks}`));
+                        if (ticks === 2) clearInterval(interval);
+                    }, 30);
+                    setTimeout(() => {
+                        const elapsed = (clock.now() - start) / 1e6;
+                        resolve(`${events.join(",")} ${elapsed >= 150}`);
+                    }, 150);
+                });
+            }
+        "#,
+        )
+        .build()
+        .expect("should build wasi-timers component");
+
+    assert_eq!(
+        inst.call1("sequence", &[]),
+        Val::String("a,tick1,job1,tick2,job2,b true".into())
+    );
+}
+
+#[test]
+fn test_wasi_wall_clock_date() {
+    let mut inst = TestCase::new()
+        .wit_dir(wasi_wit_dir())
+        .world("wasi-wall-clock")
+        .script(
+            r#"
+            export function dateNow() { return Date.now(); }
+            export function newDate() {
+                const date = new Date();
+                if (!(date instanceof Date)) throw new Error("not a Date");
+                if (new Date(0).getTime() !== 0) throw new Error("explicit time ignored");
+                return date.getTime();
+            }
+            export function dateString() { return Date(); }
+        "#,
+        )
+        .build()
+        .expect("should build wasi-wall-clock component");
+
+    let host_ms = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64;
+
+    for func in ["date-now", "new-date"] {
+        match inst.call1(func, &[]) {
+            Val::U64(ms) => assert!(
+                ms.abs_diff(host_ms) < 60_000,
+                "`{func}` returned {ms}, host time is {host_ms}"
+            ),
+            other => panic!("Expected u64, got: {:?}", other),
+        }
+    }
+
+    match inst.call1("date-string", &[]) {
+        Val::String(s) => assert!(!s.starts_with("Thu Jan 01 1970"), "got {s}"),
+        other => panic!("Expected string, got: {:?}", other),
+    }
+}
+
 #[test]
 fn test_wasi_environment() {
     let mut inst = TestCase::new()
@@ -146,6 +289,298 @@ fn test_wasi_environment() {
     }
 }
 
+#[test]
+fn test_wasi_env_global() {
+    let mut inst = TestCase::new()
+        .wit_dir(wasi_wit_dir())
+        .world("wasi-environment")
+        .env("TEST_KEY", "test_value")
+        .script(
+            r#"
+            export function getEnvVars() {
+                if (!("TEST_KEY" in env)) throw new Error("TEST_KEY missing from env");
+                try {
+                    env.TEST_KEY = "changed";
+                    throw new Error("env should be read-only");
+                } catch (e) {
+                    if (!(e instanceof TypeError)) throw e;
+                }
+                return [["TEST_KEY", env.TEST_KEY], ...Object.entries(env)];
+            }
+        "#,
+        )
+        .build()
+        .expect("should build wasi-environment component");
+
+    let vars = inst.call1("get-env-vars", &[]);
+    let Val::List(items) = &vars else {
+        panic!("Expected list, got: {:?}", vars);
+    };
+    let expected = Val::Tuple(vec![
+        Val::String("TEST_KEY".into()),
+        Val::String("test_value".into()),
+    ]);
+    assert_eq!(items[0], expected);
+    assert!(
+        items[1..].contains(&expected),
+        "TEST_KEY=test_value not enumerated in env: {:?}",
+        items
+    );
+}
+
+#[test]
+fn test_no_wasi_globals() {
+    let mut inst = TestCase::new()
+        .wit_dir(wasi_wit_dir())
+        .world("wasi-environment")
+        .env("TEST_KEY", "test_value")
+        .no_wasi_globals()
+        .script(
+            r#"
+            import environment from "wasi:cli/environment@0.2.12";
+
+            export function getEnvVars() {
+                return [["env", typeof globalThis.env], ...environment.getEnvironment()];
+            }
+        "#,
+        )
+        .build()
+        .expect("should build wasi-environment component");
+
+    let vars = inst.call1("get-env-vars", &[]);
+    let Val::List(items) = &vars else {
+        panic!("Expected list, got: {:?}", vars);
+    };
+    assert_eq!(
+        items[0],
+        Val::Tuple(vec![
+            Val::String("env".into()),
+            Val::String("undefined".into()),
+        ])
+    );
+    let expected = Val::Tuple(vec![
+        Val::String("TEST_KEY".into()),
+        Val::String("test_value".into()),
+    ]);
+    assert!(
+        items[1..].contains(&expected),
+        "TEST_KEY=test_value not found in env vars: {:?}",
+        items
+    );
+}
+
+#[test]
+fn test_lazy_eval_reads_runtime_env() {
+    let case = || {
+        TestCase::new()
+            .wit_dir(wasi_wit_dir())
+            .world("wasi-environment")
+            .env("TEST_KEY", "test_value")
+            .script(
+                r#"
+                const captured = Object.entries(env);
+                export function getEnvVars() { return captured; }
+            "#,
+            )
+    };
+    let expected = Val::Tuple(vec![
+        Val::String("TEST_KEY".into()),
+        Val::String("test_value".into()),
+    ]);
+
+    // Evaluated during Wizer, the top level sees the build environment
+    let mut inst = case().build().unwrap();
+    let Val::List(items) = inst.call1("get-env-vars", &[]) else {
+        panic!("Expected list");
+    };
+    assert!(!items.contains(&expected), "{items:?}");
+
+    let mut inst = case().lazy_eval().build().unwrap();
+    let Val::List(items) = inst.call1("get-env-vars", &[]) else {
+        panic!("Expected list");
+    };
+    assert!(items.contains(&expected), "{items:?}");
+}
+
+#[test]
+fn test_import_version_aliasing() {
+    let case = |specifier: &str| {
+        TestCase::new()
+            .wit_dir(wasi_wit_dir())
+            .world("wasi-environment")
+            .env("TEST_KEY", "test_value")
+            .script(&format!(
+                r#"
+                import environment from "{specifier}";
+                export function getEnvVars() {{ return environment.getEnvironment(); }}
+            "#
+            ))
+    };
+
+    case("wasi:cli/environment").build().unwrap();
+    case("wasi:cli/environment@0.2")
+        .version_aliasing(VersionAliasing::StripPatch)
+        .build()
+        .unwrap();
+    case("wasi:cli/environment@0.2.12")
+        .version_aliasing(VersionAliasing::None)
+        .build()
+        .unwrap();
+    for (specifier, aliasing) in [
+        ("wasi:cli/environment", VersionAliasing::StripPatch),
+        ("wasi:cli/environment@0.2", VersionAliasing::None),
+    ] {
+        let err = case(specifier)
+            .version_aliasing(aliasing)
+            .build()
+            .err()
+            .unwrap_or_else(|| panic!("`{specifier}` must not resolve with {aliasing:?}"));
+        assert!(format!("{err:#}").contains(specifier), "{err:#}");
+    }
+
+    let mut inst = case("env")
+        .version_aliasing(VersionAliasing::None)
+        .import_alias("env", "wasi:cli/environment@0.2.12")
+        .build()
+        .unwrap();
+    let Val::List(items) = inst.call1("get-env-vars", &[]) else {
+        panic!("Expected list");
+    };
+    assert!(items.contains(&Val::Tuple(vec![
+        Val::String("TEST_KEY".into()),
+        Val::String("test_value".into()),
+    ])));
+
+    let err = case("env")
+        .import_alias("env", "wasi:cli/environment@0.3.0")
+        .build()
+        .err()
+        .unwrap();
+    assert!(
+        format!("{err:#}").contains("which the world doesn't import"),
+        "{err:#}"
+    );
+}
+
+#[test]
+fn test_naming_policy() {
+    let expected = Val::Tuple(vec![
+        Val::String("TEST_KEY".into()),
+        Val::String("test_value".into()),
+    ]);
+    let kebab = r#"
+        import environment, { "get-environment" as getEnvironment } from "wasi:cli/environment";
+
+        function getEnvVars() {
+            if (Object.keys(environment).includes("getEnvironment")) {
+                throw new Error("camelCase name should be hidden");
+            }
+            if (getEnvironment !== environment["get-environment"]) {
+                throw new Error("named and default exports differ");
+            }
+            return getEnvironment();
+        }
+        export { getEnvVars as "get-env-vars" };
+    "#;
+    let both = r#"
+        import environment from "wasi:cli/environment";
+
+        export function getEnvVars() {
+            if (environment.getEnvironment !== environment["get-environment"]) {
+                throw new Error("both names should be the same function");
+            }
+            return environment.getEnvironment();
+        }
+    "#;
+    let case = |script: &str| {
+        TestCase::new()
+            .wit_dir(wasi_wit_dir())
+            .world("wasi-environment")
+            .env("TEST_KEY", "test_value")
+            .script(script)
+    };
+
+    for (script, naming) in [(kebab, Naming::KebabCase), (both, Naming::Both)] {
+        let mut inst = case(script).naming(naming).build().unwrap();
+        let Val::List(items) = inst.call1("get-env-vars", &[]) else {
+            panic!("Expected list");
+        };
+        assert!(items.contains(&expected), "{naming:?}: {items:?}");
+    }
+
+    // Without the policy the module has no kebab-case names
+    let err = case(kebab)
+        .build()
+        .err()
+        .expect("kebab-case import resolved by default");
+    assert!(format!("{err:#}").contains("get-environment"), "{err:#}");
+}
+
+#[test]
+fn test_renamed_import() {
+    let mut inst = TestCase::new()
+        .wit_dir(wasi_wit_dir())
+        .world("wasi-environment")
+        .env("TEST_KEY", "test_value")
+        .rename("get-environment", "readEnv")
+        .rename("get-env-vars", "listEnv")
+        .script(
+            r#"
+            import { readEnv } from "wasi:cli/environment";
+
+            export function listEnv() { return readEnv(); }
+        "#,
+        )
+        .build()
+        .unwrap();
+    let Val::List(items) = inst.call1("get-env-vars", &[]) else {
+        panic!("Expected list");
+    };
+    assert!(items.contains(&Val::Tuple(vec![
+        Val::String("TEST_KEY".into()),
+        Val::String("test_value".into()),
+    ])));
+}
+
+#[test]
+fn test_imported_resource_classes() {
+    // `[method]` and `[static]` functions end up on the resource class, not
+    // as mangled members of the interface object.
+    let mut inst = TestCase::new()
+        .wit_dir(wasi_wit_dir())
+        .world("wasi-stdio")
+        .script(
+            r#"
+            import stdout from "wasi:cli/stdout@0.2.12";
+            import streams from "wasi:io/streams@0.2.12";
+
+            export function echoStdinToStdout() {
+                const keys = Object.keys(streams);
+                if (keys.some((key) => key.includes("[") || key.includes("."))) {
+                    throw new Error(`mangled members: ${keys}`);
+                }
+                const { InputStream, OutputStream } = streams;
+                if (typeof InputStream.prototype.blockingRead !== "function") {
+                    throw new Error("blockingRead should be an InputStream method");
+                }
+                const output = stdout.getStdout();
+                if (!(output instanceof OutputStream)) {
+                    throw new Error("getStdout should return an OutputStream");
+                }
+                output.blockingWriteAndFlush(new TextEncoder().encode("ok"));
+            }
+        "#,
+        )
+        .build()
+        .unwrap();
+
+    assert_eq!(
+        inst.call1("echo-stdin-to-stdout", &[]),
+        Val::Result(Ok(None))
+    );
+    assert_eq!(inst.stdout_bytes(), b"ok");
+}
+
 #[test]
 fn test_wasi_stdio() {
     let mut inst = TestCase::new()
@@ -187,35 +622,1888 @@ fn test_wasi_stdio() {
     assert_eq!(inst.stdout_bytes(), b"hello from stdin");
 }
 
-#[tokio::test]
-async fn test_wasi_0_3_stdio_example() {
-    let wit_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("examples/wasi-stdio");
+#[test]
+fn test_throwing_imports() {
+    // The export speaks tagged results while the imports still throw
+    let mut inst = TestCase::new()
+        .wit_dir(wasi_wit_dir())
+        .world("wasi-stdio")
+        .stdin("tagged")
+        .tagged_results()
+        .throwing_imports()
+        .script(
+            r#"
+            import stdin from "wasi:cli/stdin@0.2.12";
+            import stdout from "wasi:cli/stdout@0.2.12";
+
+            export function echoStdinToStdout() {
+                const input = stdin.getStdin();
+                const output = stdout.getStdout();
+                try {
+                    while (true) output.blockingWriteAndFlush(input.blockingRead(4096));
+                } catch (e) {
+                    return e.payload?.tag === "closed" ? { tag: "ok" } : { tag: "err" };
+                }
+            }
+        "#,
+        )
+        .build()
+        .expect("should build wasi-stdio component");
+
+    let result = inst.call1("echo-stdin-to-stdout", &[]);
+    assert_eq!(result, Val::Result(Ok(None)));
+    assert_eq!(inst.stdout_bytes(), b"tagged");
+}
 
+#[test]
+fn test_wasi_stream_helpers() {
     let mut inst = TestCase::new()
-        .wit_dir(wit_path)
+        .wit_dir(wasi_wit_dir())
         .world("wasi-stdio")
-        .stdin("hello from wasi 0.3")
-        .script(include_str!("../examples/wasi-stdio/echo.js"))
-        .build_async()
-        .await
-        .expect("should build WASI 0.3 stdio example");
+        .stdin("hello from stdin")
+        .script(
+            r#"
+            import stdin from "wasi:cli/stdin@0.2.12";
+            import stdout from "wasi:cli/stdout@0.2.12";
 
-    {
-        let (instance, store) = inst.parts();
-        let iface_idx = instance
-            .get_export_index(&mut *store, None, "wasi:cli/run@0.3.0")
-            .expect("wasi:cli/run export not found");
-        let run_idx = instance
-            .get_export_index(&mut *store, Some(&iface_idx), "run")
-            .expect("run export not found");
-        let run = instance.get_func(&mut *store, run_idx).unwrap();
+            export async function echoStdinToStdout() {
+                const output = stdout.getStdout();
+                let chunks = 0;
+                for await (const chunk of stdin.getStdin()) {
+                    output.writeAll(chunk);
+                    chunks++;
+                }
+                const rest = stdin.getStdin().readAll();
+                output.writeAll(` (${chunks} ${rest.length}) ✓`);
+                output.writeAll(" \uD800|\uDC00 ");
+                output.writeAll(new Uint8Array(5000).fill(46));
+            }
+        "#,
+        )
+        .build()
+        .expect("should build wasi-stdio component");
 
-        let mut results = [Val::Bool(false)];
-        run.call_async(&mut *store, &[], &mut results)
-            .await
-            .expect("WASI 0.3 stdio run should succeed");
-        assert_eq!(results[0], Val::Result(Ok(None)));
+    let result = inst.call1("echo-stdin-to-stdout", &[]);
+    assert_eq!(result, Val::Result(Ok(None)));
+    let mut expected = "hello from stdin (1 0) ✓ \u{FFFD}|\u{FFFD} "
+        .as_bytes()
+        .to_vec();
+    expected.extend([b'.'; 5000]);
+    assert_eq!(inst.stdout_bytes(), expected);
+}
+
+#[test]
+fn test_wasi_stream_helpers_with_tagged_results() {
+    // The helpers unwrap the tagged results their stream calls come back as
+    let mut inst = TestCase::new()
+        .wit_dir(wasi_wit_dir())
+        .world("wasi-stdio")
+        .stdin("tagged")
+        .tagged_results()
+        .script(
+            r#"
+            import stdin from "wasi:cli/stdin@0.2.12";
+            import stdout from "wasi:cli/stdout@0.2.12";
+
+            export function echoStdinToStdout() {
+                const output = stdout.getStdout();
+                for (const chunk of stdin.getStdin()) output.writeAll(chunk);
+                output.writeAll(` ${stdin.getStdin().readAll().length}`);
+                return { tag: "ok" };
+            }
+        "#,
+        )
+        .build()
+        .expect("should build wasi-stdio component");
+
+    let result = inst.call1("echo-stdin-to-stdout", &[]);
+    assert_eq!(result, Val::Result(Ok(None)));
+    assert_eq!(inst.stdout_bytes(), b"tagged 0");
+}
+
+#[test]
+fn test_max_list_length_import_argument() {
+    let mut inst = TestCase::new()
+        .wit_dir(wasi_wit_dir())
+        .world("wasi-list-limit")
+        .max_list_length(16)
+        .script(
+            r#"
+            import stdout from "wasi:cli/stdout@0.2.12";
+
+            export function writeZeros(len) {
+                try {
+                    stdout.getStdout().blockingWriteAndFlush(new Uint8Array(len));
+                    return "ok";
+                } catch (e) {
+                    return `${e.name}: ${e.message}`;
+                }
+            }
+        "#,
+        )
+        .build()
+        .expect("should build wasi-list-limit component");
+
+    assert_eq!(
+        inst.call1("write-zeros", &[Val::U32(16)]),
+        Val::String("ok".into())
+    );
+    match inst.call1("write-zeros", &[Val::U32(17)]) {
+        Val::String(s) => assert!(
+            s.starts_with("RangeError: argument to `wasi:io/streams@0.2.12#")
+                && s.ends_with("length 17 exceeds the maximum list length of 16"),
+            "got {s}"
+        ),
+        other => panic!("Expected string, got: {:?}", other),
     }
+    assert_eq!(inst.stdout_bytes(), [0; 16]);
+}
 
-    assert_eq!(inst.stdout_bytes(), b"hello from wasi 0.3");
+#[test]
+fn test_strict_types_import_argument() {
+    let mut inst = TestCase::new()
+        .wit_dir(wasi_wit_dir())
+        .world("wasi-list-limit")
+        .strict_types()
+        .script(
+            r#"
+            import stdout from "wasi:cli/stdout@0.2.12";
+
+            export function writeZeros(len) {
+                try {
+                    stdout.getStdout().blockingWriteAndFlush([0, 1, "2"].slice(0, len));
+                    return "ok";
+                } catch (e) {
+                    return `${e.name}: ${e.message}`;
+                }
+            }
+        "#,
+        )
+        .build()
+        .expect("should build wasi-list-limit component");
+
+    assert_eq!(
+        inst.call1("write-zeros", &[Val::U32(2)]),
+        Val::String("ok".into())
+    );
+    match inst.call1("write-zeros", &[Val::U32(3)]) {
+        Val::String(s) => assert!(
+            s.starts_with("TypeError: `wasi:io/streams@0.2.12#")
+                && s.ends_with(r#"argument 1[2]: expected u8, got string "2""#),
+            "got {s}"
+        ),
+        other => panic!("Expected string, got: {:?}", other),
+    }
+    assert_eq!(inst.stdout_bytes(), [0, 1]);
+}
+
+#[test]
+fn test_integer_range_import_argument() {
+    let script = r#"
+        import stdout from "wasi:cli/stdout@0.2.12";
+
+        export function writeZeros(len) {
+            const output = stdout.getStdout();
+            if (len === 0) {
+                const attempt = (n) => {
+                    try {
+                        output.blockingWriteZeroesAndFlush(n);
+                        return "ok";
+                    } catch (e) {
+                        return `${e.name}: ${e.message}`;
+                    }
+                };
+                return [attempt(-1), attempt(2n ** 64n)].join("\n");
+            }
+            output.blockingWriteAndFlush([0, 255, 256].slice(0, len));
+            return "ok";
+        }
+    "#;
+
+    let mut inst = TestCase::new()
+        .wit_dir(wasi_wit_dir())
+        .world("wasi-list-limit")
+        .script(script)
+        .build()
+        .expect("should build wasi-list-limit component");
+    let Val::String(s) = inst.call1("write-zeros", &[Val::U32(0)]) else {
+        panic!("Expected string");
+    };
+    let (number, big_int) = s.split_once('\n').unwrap();
+    for (message, got) in [(number, "-1"), (big_int, "BigInt 18446744073709551616")] {
+        assert!(
+            message.starts_with("RangeError: `wasi:io/streams@0.2.12#")
+                && message.ends_with(&format!(
+                    "argument 1: expected u64, got {got}, which is out of range (0 to 18446744073709551615)"
+                )),
+            "got {message}"
+        );
+    }
+
+    // A nested integer is checked while it is lowered, before the call
+    let (instance, store) = inst.parts();
+    let func = instance.get_func(&mut *store, "write-zeros").unwrap();
+    let mut results = [Val::Bool(false)];
+    func.call(&mut *store, &[Val::U32(3)], &mut results)
+        .expect_err("an out-of-range list element traps");
+    assert!(inst.stdout_bytes().is_empty());
+
+    // Opting out restores `as` cast wrapping
+    let mut inst = TestCase::new()
+        .wit_dir(wasi_wit_dir())
+        .world("wasi-list-limit")
+        .wrap_integers()
+        .script(script)
+        .build()
+        .expect("should build wasi-list-limit component");
+    assert_eq!(
+        inst.call1("write-zeros", &[Val::U32(3)]),
+        Val::String("ok".into())
+    );
+    assert_eq!(inst.stdout_bytes(), [0, 255, 0]);
+}
+
+#[test]
+fn test_import_only_world_gets_run_export() {
+    let mut inst = TestCase::new()
+        .wit_dir(wasi_wit_dir())
+        .world("wasi-stdout-only")
+        .script(
+            r#"
+            import stdout from "wasi:cli/stdout@0.2.12";
+
+            export function run() {
+                stdout.getStdout().blockingWriteAndFlush(new Uint8Array([104, 105]));
+            }
+        "#,
+        )
+        .build()
+        .expect("should build import-only component");
+
+    let result = inst.call1("run", &[]);
+    assert_eq!(result, Val::Result(Ok(None)));
+    assert_eq!(inst.stdout_bytes(), b"hi");
+}
+
+#[test]
+fn test_componentize_with_report() {
+    let wit_dir = wasi_wit_dir();
+    let script = r#"
+        import stdout from "wasi:cli/stdout@0.2.12";
+
+        stdout.getStdout().blockingWriteAndFlush(new TextEncoder().encode("initializing"));
+        export function run(verbose) {}
+    "#;
+    let opts = ComponentizeBuilder::new(&wit_dir, script)
+        .world_name("wasi-stdout-only")
+        .build();
+    let (wasm, report) = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap()
+        .block_on(componentize_qjs::componentize_with_report(&opts))
+        .unwrap();
+
+    assert_eq!(report.component_size, wasm.len());
+    assert_eq!(report.init_stdout, "initializing");
+    assert_eq!(report.warnings.len(), 1, "{:?}", report.warnings);
+    assert!(
+        report.warnings[0].starts_with("export `run` declares 1 parameters"),
+        "{:?}",
+        report.warnings
+    );
+    assert!(!report.modules.is_empty());
+    let modules: usize = report.modules.iter().map(|module| module.size).sum();
+    assert!(modules < wasm.len(), "{report:?}");
+}
+
+#[test]
+fn test_wasi_resource_drop() {
+    let mut inst = TestCase::new()
+        .wit_dir(wasi_wit_dir())
+        .world("wasi-stdio")
+        .stdin("ignored")
+        .script(
+            r#"
+            import stdin from "wasi:cli/stdin@0.2.12";
+            import stdout from "wasi:cli/stdout@0.2.12";
+
+            export function echoStdinToStdout() {
+                const input = stdin.getStdin();
+                input.drop();
+                input.drop();
+
+                const output = stdout.getStdout();
+                output.blockingWriteAndFlush(new Uint8Array([100, 114, 111, 112, 112, 101, 100]));
+                output[Symbol.for("dispose")]();
+            }
+        "#,
+        )
+        .build()
+        .expect("should build wasi-stdio component");
+
+    let result = inst.call1("echo-stdin-to-stdout", &[]);
+    assert_eq!(result, Val::Result(Ok(None)));
+    assert_eq!(inst.stdout_bytes(), b"dropped");
+}
+
+#[test]
+fn test_unreachable_owned_handles_are_dropped() {
+    // A pollable is a child of its stream in the host table, so dropping the
+    // stream traps unless the pollable was dropped first.
+    let script = r#"
+        import stdout from "wasi:cli/stdout@0.2.12";
+
+        let output;
+        export function subscribe(cyclic) {
+            output = stdout.getStdout();
+            const pollable = output.subscribe();
+            if (cyclic) pollable.self = pollable;
+        }
+        export function tick() {}
+        export function closeStdout() { output.drop(); }
+    "#;
+
+    let mut inst = TestCase::new()
+        .wit_dir(wasi_wit_dir())
+        .world("wasi-handle-gc")
+        .script(script)
+        .build()
+        .expect("should build wasi-handle-gc component");
+    inst.call("subscribe", &[Val::Bool(false)], 0);
+    inst.call("close-stdout", &[], 0);
+
+    let mut inst = TestCase::new()
+        .wit_dir(wasi_wit_dir())
+        .world("wasi-handle-gc")
+        .script(script)
+        .build()
+        .expect("should build wasi-handle-gc component");
+    inst.call("subscribe", &[Val::Bool(true)], 0);
+    for _ in 0..64 {
+        inst.call("tick", &[], 0);
+    }
+    inst.call("close-stdout", &[], 0);
+}
+
+#[test]
+fn test_harden_freezes_environment() {
+    let mut inst = TestCase::new()
+        .wit_dir(wasi_wit_dir())
+        .world("wasi-harden")
+        .env("TEST_KEY", "test_value")
+        .harden()
+        .script(
+            r#"
+            import stdout from "wasi:cli/stdout@0.2.12";
+
+            globalThis.counter = { calls: 0 };
+            Array.prototype.last = function () { return this[this.length - 1]; };
+
+            const attempt = (f) => {
+                try { f(); return "allowed"; } catch (e) { return e.constructor.name; }
+            };
+
+            export function probe() {
+                const output = stdout.getStdout();
+                class MyError extends Error {
+                    constructor() { super("boom"); this.name = "MyError"; }
+                }
+                counter.calls += 1;
+                return [
+                    attempt(() => { Array.prototype.evil = 1; }),
+                    attempt(() => { globalThis.JSON = null; }),
+                    attempt(() => { Object.getPrototypeOf(output).blockingFlush = null; }),
+                    attempt(() => { output.note = "instances stay extensible"; }),
+                    String(new MyError()),
+                    String(counter.calls),
+                    [1, 2, 3].last().toString(),
+                    env.TEST_KEY,
+                ];
+            }
+        "#,
+        )
+        .build()
+        .expect("should build wasi-harden component");
+
+    let expected = |calls: &str| {
+        Val::List(
+            [
+                "TypeError",
+                "TypeError",
+                "TypeError",
+                "allowed",
+                "MyError: boom",
+                calls,
+                "3",
+                "test_value",
+            ]
+            .map(|s| Val::String(s.into()))
+            .to_vec(),
+        )
+    };
+    assert_eq!(inst.call1("probe", &[]), expected("1"));
+    assert_eq!(inst.call1("probe", &[]), expected("2"));
+}
+
+#[test]
+fn test_fs_global() {
+    let dir = tempfile::TempDir::new().unwrap();
+    std::fs::write(dir.path().join("host.txt"), "from host").unwrap();
+    std::fs::create_dir(dir.path().join("sub")).unwrap();
+    std::fs::write(dir.path().join("sub/inner.txt"), "").unwrap();
+    std::fs::write(dir.path().join("sub/latin1.txt"), b"caf\xe9").unwrap();
+
+    let mut inst = TestCase::new()
+        .wit_dir(wasi_wit_dir())
+        .world("wasi-fs")
+        .preopen(dir.path())
+        .script(
+            r#"
+            export async function probe() {
+                fs.writeFileSync("/written.txt", "héllo ✓");
+                const bytes = fs.readFileSync("written.txt");
+                let missing;
+                try {
+                    fs.readFileSync("/missing.txt");
+                } catch (e) {
+                    missing = `${e.code} ${e.syscall} ${e.path}`;
+                }
+                return [
+                    fs.readFileSync("/host.txt", "utf8"),
+                    fs.readFileSync("./written.txt", { encoding: "utf-8" }),
+                    `${bytes.constructor.name} ${bytes.length}`,
+                    fs.readdirSync("/").join(","),
+                    fs.readdirSync("sub").join(","),
+                    (await fs.readdir("/sub")).join(","),
+                    fs.readFileSync("sub/latin1.txt", "utf8"),
+                    missing,
+                ];
+            }
+        "#,
+        )
+        .build()
+        .expect("should build wasi-fs component");
+
+    let strings =
+        |values: &[&str]| Val::List(values.iter().map(|s| Val::String(s.to_string())).collect());
+    assert_eq!(
+        inst.call1("probe", &[]),
+        strings(&[
+            "from host",
+            "héllo ✓",
+            "Uint8Array 10",
+            "host.txt,sub,written.txt",
+            "inner.txt,latin1.txt",
+            "inner.txt,latin1.txt",
+            "caf\u{fffd}",
+            "no-entry open /missing.txt",
+        ])
+    );
+    assert_eq!(
+        std::fs::read_to_string(dir.path().join("written.txt")).unwrap(),
+        "héllo ✓"
+    );
+}
+
+#[test]
+fn test_fs_global_with_tagged_results() {
+    let script = r#"
+        export function probe() {
+            return [fs.readFileSync("/host.txt", "utf8")];
+        }
+    "#;
+    let err = TestCase::new()
+        .wit_dir(wasi_wit_dir())
+        .world("wasi-fs")
+        .tagged_results()
+        .script(script)
+        .build()
+        .err()
+        .expect("tagged results without throwing imports should reject the fs global");
+    assert!(
+        format!("{err:#}").contains("generated fs expect them to throw"),
+        "{err:#}"
+    );
+
+    TestCase::new()
+        .wit_dir(wasi_wit_dir())
+        .world("wasi-fs")
+        .tagged_results()
+        .no_wasi_globals()
+        .script("export function probe() { return []; }")
+        .build()
+        .expect("should build without the fs global");
+
+    let dir = tempfile::TempDir::new().unwrap();
+    std::fs::write(dir.path().join("host.txt"), "from host").unwrap();
+    let mut inst = TestCase::new()
+        .wit_dir(wasi_wit_dir())
+        .world("wasi-fs")
+        .preopen(dir.path())
+        .tagged_results()
+        .throwing_imports()
+        .script(script)
+        .build()
+        .expect("should build wasi-fs component");
+    assert_eq!(
+        inst.call1("probe", &[]),
+        Val::List(vec![Val::String("from host".into())])
+    );
+}
+
+#[test]
+fn test_init_wasi_context() {
+    // Seen by top-level code during Wizer, not by the instance afterwards.
+    let mut inst = TestCase::new()
+        .wit_dir(wasi_wit_dir())
+        .world("wasi-environment")
+        .init_env("CONFIG", "init")
+        .init_arg("app")
+        .init_arg("--flag")
+        .env("CONFIG", "runtime")
+        .script(
+            r#"
+            import environment from "wasi:cli/environment@0.2.12";
+
+            const atInit = [
+                ...environment.getEnvironment(),
+                ["args", environment.getArguments().join(" ")],
+            ];
+            export function getEnvVars() { return atInit; }
+        "#,
+        )
+        .build()
+        .expect("should build wasi-environment component");
+
+    let pair = |k: &str, v: &str| Val::Tuple(vec![Val::String(k.into()), Val::String(v.into())]);
+    assert_eq!(
+        inst.call1("get-env-vars", &[]),
+        Val::List(vec![pair("CONFIG", "init"), pair("args", "app --flag")])
+    );
+
+    let dir = tempfile::TempDir::new().unwrap();
+    std::fs::write(dir.path().join("config.txt"), "snapshotted").unwrap();
+    let mut inst = TestCase::new()
+        .wit_dir(wasi_wit_dir())
+        .world("wasi-fs")
+        .init_dir(dir.path(), "/data")
+        .script(
+            r#"
+            const config = fs.readFileSync("/data/config.txt", "utf8");
+            export function probe() { return [config]; }
+        "#,
+        )
+        .build()
+        .expect("should build wasi-fs component");
+
+    assert_eq!(
+        inst.call1("probe", &[]),
+        Val::List(vec![Val::String("snapshotted".into())])
+    );
+}
+
+#[test]
+fn test_net_connect() {
+    use std::io::{Read, Write};
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let server = std::thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut received = String::new();
+        stream.read_to_string(&mut received).unwrap();
+        stream
+            .write_all(received.to_uppercase().as_bytes())
+            .unwrap();
+    });
+
+    let case = TestCase::new()
+        .wit_dir(wasi_wit_dir())
+        .world("wasi-tcp")
+        .script(
+            r#"
+            export async function echo(port, message) {
+                let refused;
+                try {
+                    await net.connect("127.0.0.1", 1);
+                } catch (e) {
+                    refused = e.code;
+                }
+                const socket = await net.connect("127.0.0.1", port);
+                await socket.write(message);
+                socket.end();
+                let reply = "";
+                for await (const chunk of socket) reply += String.fromCharCode(...chunk);
+                socket.close();
+                return `${refused} ${socket.remoteAddress}:${socket.remotePort === port} ${reply}`;
+            }
+        "#,
+        );
+    let wasm = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap()
+        .block_on(case.componentize())
+        .expect("should build wasi-tcp component");
+
+    let engine = common::engine();
+    let component = Component::new(engine, &wasm).unwrap();
+    let mut wasi = WasiCtxBuilder::new();
+    wasi.inherit_network().allow_ip_name_lookup(true);
+    let mut store = Store::new(
+        engine,
+        WasiCtxState {
+            wasi: wasi.build(),
+            table: ResourceTable::new(),
+        },
+    );
+    let mut linker = Linker::new(engine);
+    wasmtime_wasi::p2::add_to_linker_sync(&mut linker).unwrap();
+    let instance = linker.instantiate(&mut store, &component).unwrap();
+    let echo = instance.get_func(&mut store, "echo").unwrap();
+    let mut results = [Val::Bool(false)];
+    echo.call(
+        &mut store,
+        &[Val::U16(port), Val::String("hello".into())],
+        &mut results,
+    )
+    .unwrap();
+    server.join().unwrap();
+
+    assert_eq!(
+        results[0],
+        Val::String("connection-refused 127.0.0.1:true HELLO".into())
+    );
+}
+
+#[test]
+fn test_wasi_import_record_replay() {
+    let script = r#"
+        import random from "wasi:random/random@0.2.12";
+
+        export function getRandomU64() { return random.getRandomU64(); }
+        export function getRandomBytes(len) { return random.getRandomBytes(len); }
+    "#;
+    let dir = tempfile::TempDir::new().unwrap();
+
+    let mut recorder = TestCase::new()
+        .wit_dir(wasi_wit_dir())
+        .world("wasi-random")
+        .record_imports("/imports.jsonl")
+        .preopen(dir.path())
+        .script(script)
+        .build()
+        .expect("should build recording component");
+    let recorded = [
+        recorder.call1("get-random-u64", &[]),
+        recorder.call1("get-random-bytes", &[Val::U32(8)]),
+    ];
+
+    let capture = std::fs::read_to_string(dir.path().join("imports.jsonl")).unwrap();
+    assert_eq!(capture.lines().count(), 2, "unexpected capture: {capture}");
+    assert!(capture.contains("wasi:random/random@0.2.12#get-random-bytes"));
+
+    let mut replayer = TestCase::new()
+        .wit_dir(wasi_wit_dir())
+        .world("wasi-random")
+        .replay_imports("/imports.jsonl")
+        .preopen(dir.path())
+        .script(script)
+        .build()
+        .expect("should build replaying component");
+    let replayed = [
+        replayer.call1("get-random-u64", &[]),
+        replayer.call1("get-random-bytes", &[Val::U32(8)]),
+    ];
+    assert_eq!(recorded, replayed);
+
+    let (instance, store) = replayer.parts();
+    let func = instance.get_func(&mut *store, "get-random-u64").unwrap();
+    let mut results = [Val::Bool(false)];
+    assert!(
+        func.call(&mut *store, &[], &mut results).is_err(),
+        "an exhausted capture should trap"
+    );
+}
+
+#[test]
+fn test_import_record_skips_calls_made_while_encoding() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let mut inst = TestCase::new()
+        .wit_dir(wasi_wit_dir())
+        .world("wasi-random")
+        .record_imports("/imports.jsonl")
+        .preopen(dir.path())
+        .script(
+            r#"
+            import random from "wasi:random/random@0.2.12";
+
+            BigInt.prototype.toJSON = function () {
+                random.getRandomU64();
+                return this.toString();
+            };
+
+            export function getRandomU64() { return random.getRandomU64(); }
+            export function getRandomBytes(len) { return random.getRandomBytes(BigInt(len)); }
+        "#,
+        )
+        .build()
+        .expect("should build recording component");
+    inst.call1("get-random-bytes", &[Val::U32(8)]);
+
+    let capture = std::fs::read_to_string(dir.path().join("imports.jsonl")).unwrap();
+    assert_eq!(capture.lines().count(), 1, "unexpected capture: {capture}");
+    assert!(capture.contains("#get-random-bytes"), "{capture}");
+}
+
+#[test]
+fn test_import_log_rejects_resource_imports() {
+    // `get-stdout` returns an `output-stream` handle, which a replaying
+    // instance would have no resource behind.
+    let dir = tempfile::TempDir::new().unwrap();
+    for case in [
+        TestCase::new().record_imports("/imports.jsonl"),
+        TestCase::new().replay_imports("/imports.jsonl"),
+    ] {
+        let result = case
+            .wit_dir(wasi_wit_dir())
+            .world("wasi-handle-gc")
+            .preopen(dir.path())
+            .script(
+                r#"
+                export function subscribe(cyclic) {}
+                export function tick() {}
+                export function closeStdout() {}
+            "#,
+            )
+            .build();
+
+        let Err(err) = result else {
+            panic!("an import returning a resource should not be recorded or replayed");
+        };
+        assert!(
+            format!("{err:#}").contains("`wasi:cli/stdout@0.2.12#get-stdout`"),
+            "unexpected error: {err:#}"
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_wasi_async_import_returns_promise() {
+    let mut inst = TestCase::new()
+        .wit_dir(wasi_wit_dir())
+        .world("wasi-clocks-async")
+        .script(
+            r#"
+            import clock from "wasi:clocks/monotonic-clock@0.3.0";
+
+            export async function waitTwice(ns) {
+                const first = clock.waitFor(ns);
+                const second = clock.waitFor(ns);
+                if (!(first instanceof Promise) || !(second instanceof Promise)) {
+                    return false;
+                }
+                await Promise.all([first, second]);
+                return true;
+            }
+        "#,
+        )
+        .build_async()
+        .await
+        .expect("should build wasi-clocks-async component");
+
+    let result = inst
+        .call1_async("wait-twice", &[Val::U64(1_000)])
+        .await
+        .expect("wait-twice should succeed");
+    assert_eq!(result, Val::Bool(true));
+}
+
+#[tokio::test]
+async fn test_wasi_0_3_stdio_example() {
+    let wit_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("examples/wasi-stdio");
+
+    let mut inst = TestCase::new()
+        .wit_dir(wit_path)
+        .world("wasi-stdio")
+        .stdin("hello from wasi 0.3")
+        .script(include_str!("../examples/wasi-stdio/echo.js"))
+        .build_async()
+        .await
+        .expect("should build WASI 0.3 stdio example");
+
+    {
+        let (instance, store) = inst.parts();
+        let iface_idx = instance
+            .get_export_index(&mut *store, None, "wasi:cli/run@0.3.0")
+            .expect("wasi:cli/run export not found");
+        let run_idx = instance
+            .get_export_index(&mut *store, Some(&iface_idx), "run")
+            .expect("run export not found");
+        let run = instance.get_func(&mut *store, run_idx).unwrap();
+
+        let mut results = [Val::Bool(false)];
+        run.call_async(&mut *store, &[], &mut results)
+            .await
+            .expect("WASI 0.3 stdio run should succeed");
+        assert_eq!(results[0], Val::Result(Ok(None)));
+    }
+
+    assert_eq!(inst.stdout_bytes(), b"hello from wasi 0.3");
+}
+
+#[tokio::test]
+async fn test_console_logs_to_wasi_logging() {
+    #[derive(ComponentType, Lift, Debug, PartialEq)]
+    #[component(enum)]
+    #[repr(u8)]
+    enum Level {
+        #[component(name = "trace")]
+        Trace,
+        #[component(name = "debug")]
+        Debug,
+        #[component(name = "info")]
+        Info,
+        #[component(name = "warn")]
+        Warn,
+        #[component(name = "error")]
+        Error,
+        #[component(name = "critical")]
+        Critical,
+    }
+
+    let wasm = TestCase::new()
+        .wit(
+            r#"
+            package test:logging;
+
+            world logging {
+                import wasi:logging/logging@0.1.0-draft;
+                export run: func();
+            }
+
+            package wasi:logging@0.1.0-draft {
+                interface logging {
+                    enum level { trace, debug, info, warn, error, critical }
+                    log: func(level: level, context: string, message: string);
+                }
+            }
+        "#,
+        )
+        .script(
+            r#"
+            export function run() {
+                console.debug("debug", 1);
+                console.info("%s has %d items (%%)", "cart", 3.7, { a: [1] });
+                console.warn({ nested: { n: 10n } });
+                console.error(new TypeError("bad"));
+                console.log("plain", null, undefined);
+            }
+        "#,
+        )
+        .componentize()
+        .await
+        .expect("should build logging component");
+
+    let logs = Arc::new(Mutex::new(Vec::new()));
+    let engine = common::engine();
+    let component = Component::new(engine, &wasm).unwrap();
+    let mut store = Store::new(
+        engine,
+        WasiCtxState {
+            wasi: WasiCtxBuilder::new().build(),
+            table: ResourceTable::new(),
+        },
+    );
+    let mut linker = Linker::new(engine);
+    wasmtime_wasi::p2::add_to_linker_sync(&mut linker).unwrap();
+    let captured = logs.clone();
+    linker
+        .instance("wasi:logging/logging@0.1.0-draft")
+        .unwrap()
+        .func_wrap(
+            "log",
+            move |_, (level, _context, message): (Level, String, String)| {
+                captured.lock().unwrap().push((level, message));
+                Ok(())
+            },
+        )
+        .unwrap();
+
+    let instance = linker.instantiate(&mut store, &component).unwrap();
+    let run = instance.get_func(&mut store, "run").unwrap();
+    run.call(&mut store, &[], &mut []).unwrap();
+
+    let logs = logs.lock().unwrap();
+    assert_eq!(logs[0], (Level::Debug, "debug 1".to_string()));
+    assert_eq!(
+        logs[1],
+        (Level::Info, r#"cart has 3 items (%) {"a":[1]}"#.to_string())
+    );
+    assert_eq!(
+        logs[2],
+        (Level::Warn, r#"{"nested":{"n":"10n"}}"#.to_string())
+    );
+    assert_eq!(logs[3].0, Level::Error);
+    assert!(logs[3].1.starts_with("TypeError: bad"), "{}", logs[3].1);
+    assert_eq!(logs[4], (Level::Info, "plain null undefined".to_string()));
+    assert_eq!(logs.len(), 5);
+}
+
+#[tokio::test]
+async fn test_fetch_decodes_invalid_utf8() {
+    #[derive(ComponentType, Lift)]
+    #[component(variant)]
+    enum Method {
+        #[component(name = "get")]
+        Get,
+    }
+
+    #[derive(ComponentType, Lift)]
+    #[component(variant)]
+    enum Scheme {
+        #[component(name = "HTTP")]
+        Http,
+        #[component(name = "HTTPS")]
+        Https,
+    }
+
+    #[derive(ComponentType, Lower)]
+    #[component(variant)]
+    enum StreamError {
+        #[component(name = "closed")]
+        Closed,
+    }
+
+    enum Fields {}
+    enum OutgoingRequest {}
+    enum OutgoingBody {}
+    enum OutputStream {}
+    enum FutureResponse {}
+    enum Pollable {}
+    enum IncomingResponse {}
+    enum IncomingBody {}
+    enum InputStream {}
+
+    fn resource<T: 'static>(types: &mut LinkerInstance<'_, WasiCtxState>, name: &str) {
+        types
+            .resource(name, ResourceType::host::<T>(), |_, _| Ok(()))
+            .unwrap();
+    }
+
+    // Just enough of `wasi:http` for one `fetch()` of a GET request.
+    let wasm = TestCase::new()
+        .wit(
+            r#"
+            package test:fetch;
+
+            world fetch {
+                import wasi:http/outgoing-handler@0.2.12;
+                export fetch-text: func(url: string) -> string;
+            }
+
+            package wasi:http@0.2.12 {
+                interface types {
+                    variant method { get }
+                    variant scheme { HTTP, HTTPS }
+                    variant stream-error { closed }
+
+                    resource fields {
+                        from-list: static func(entries: list<tuple<string, list<u8>>>) -> fields;
+                        entries: func() -> list<tuple<string, list<u8>>>;
+                    }
+                    resource output-stream;
+                    resource input-stream {
+                        blocking-read: func(len: u64) -> result<list<u8>, stream-error>;
+                    }
+                    resource pollable {
+                        block: func();
+                    }
+                    resource outgoing-body {
+                        write: func() -> output-stream;
+                        finish: static func(this: outgoing-body, trailers: option<fields>);
+                    }
+                    resource outgoing-request {
+                        constructor(headers: fields);
+                        set-method: func(method: method);
+                        set-scheme: func(scheme: option<scheme>);
+                        set-authority: func(authority: option<string>);
+                        set-path-with-query: func(path-with-query: option<string>);
+                        body: func() -> outgoing-body;
+                    }
+                    resource incoming-body {
+                        %stream: func() -> input-stream;
+                    }
+                    resource incoming-response {
+                        status: func() -> u16;
+                        headers: func() -> fields;
+                        consume: func() -> incoming-body;
+                    }
+                    resource future-incoming-response {
+                        subscribe: func() -> pollable;
+                        get: func() -> option<result<result<incoming-response, string>>>;
+                    }
+                }
+
+                interface outgoing-handler {
+                    use types.{outgoing-request, future-incoming-response};
+                    handle: func(request: outgoing-request, options: option<u32>) -> future-incoming-response;
+                }
+            }
+        "#,
+        )
+        .script(
+            r#"
+            export async function fetchText(url) {
+                const response = await fetch(url);
+                return `${response.headers.get("x-name")}|${await response.text()}`;
+            }
+        "#,
+        )
+        .componentize()
+        .await
+        .expect("should build fetch component");
+
+    let engine = common::engine();
+    let component = Component::new(engine, &wasm).unwrap();
+    let mut store = Store::new(
+        engine,
+        WasiCtxState {
+            wasi: WasiCtxBuilder::new().build(),
+            table: ResourceTable::new(),
+        },
+    );
+    let mut linker = Linker::new(engine);
+    wasmtime_wasi::p2::add_to_linker_sync(&mut linker).unwrap();
+
+    let mut types = linker.instance("wasi:http/types@0.2.12").unwrap();
+    resource::<Fields>(&mut types, "fields");
+    resource::<OutgoingRequest>(&mut types, "outgoing-request");
+    resource::<OutgoingBody>(&mut types, "outgoing-body");
+    resource::<OutputStream>(&mut types, "output-stream");
+    resource::<FutureResponse>(&mut types, "future-incoming-response");
+    resource::<Pollable>(&mut types, "pollable");
+    resource::<IncomingResponse>(&mut types, "incoming-response");
+    resource::<IncomingBody>(&mut types, "incoming-body");
+    resource::<InputStream>(&mut types, "input-stream");
+
+    types
+        .func_wrap(
+            "[static]fields.from-list",
+            |_, (_,): (Vec<(String, Vec<u8>)>,)| Ok((Resource::<Fields>::new_own(0),)),
+        )
+        .unwrap();
+    types
+        .func_wrap("[method]fields.entries", |_, (_,): (Resource<Fields>,)| {
+            Ok((vec![("x-name".to_string(), b"caf\xe9".to_vec())],))
+        })
+        .unwrap();
+    types
+        .func_wrap(
+            "[constructor]outgoing-request",
+            |_, (_,): (Resource<Fields>,)| Ok((Resource::<OutgoingRequest>::new_own(0),)),
+        )
+        .unwrap();
+    types
+        .func_wrap(
+            "[method]outgoing-request.set-method",
+            |_, (_, _): (Resource<OutgoingRequest>, Method)| Ok(()),
+        )
+        .unwrap();
+    types
+        .func_wrap(
+            "[method]outgoing-request.set-scheme",
+            |_, (_, _): (Resource<OutgoingRequest>, Option<Scheme>)| Ok(()),
+        )
+        .unwrap();
+    for name in ["set-authority", "set-path-with-query"] {
+        types
+            .func_wrap(
+                &format!("[method]outgoing-request.{name}"),
+                |_, (_, _): (Resource<OutgoingRequest>, Option<String>)| Ok(()),
+            )
+            .unwrap();
+    }
+    types
+        .func_wrap(
+            "[method]outgoing-request.body",
+            |_, (_,): (Resource<OutgoingRequest>,)| Ok((Resource::<OutgoingBody>::new_own(0),)),
+        )
+        .unwrap();
+    types
+        .func_wrap(
+            "[method]outgoing-body.write",
+            |_, (_,): (Resource<OutgoingBody>,)| Ok((Resource::<OutputStream>::new_own(0),)),
+        )
+        .unwrap();
+    types
+        .func_wrap(
+            "[static]outgoing-body.finish",
+            |_, (_, _): (Resource<OutgoingBody>, Option<Resource<Fields>>)| Ok(()),
+        )
+        .unwrap();
+    types
+        .func_wrap(
+            "[method]future-incoming-response.subscribe",
+            |_, (_,): (Resource<FutureResponse>,)| Ok((Resource::<Pollable>::new_own(0),)),
+        )
+        .unwrap();
+    types
+        .func_wrap(
+            "[method]pollable.block",
+            |_, (_,): (Resource<Pollable>,)| Ok(()),
+        )
+        .unwrap();
+    types
+        .func_wrap(
+            "[method]future-incoming-response.get",
+            |_, (_,): (Resource<FutureResponse>,)| {
+                let response = Resource::<IncomingResponse>::new_own(0);
+                Ok((Some(Ok::<_, ()>(Ok::<_, String>(response))),))
+            },
+        )
+        .unwrap();
+    types
+        .func_wrap(
+            "[method]incoming-response.status",
+            |_, (_,): (Resource<IncomingResponse>,)| Ok((200u16,)),
+        )
+        .unwrap();
+    types
+        .func_wrap(
+            "[method]incoming-response.headers",
+            |_, (_,): (Resource<IncomingResponse>,)| Ok((Resource::<Fields>::new_own(0),)),
+        )
+        .unwrap();
+    types
+        .func_wrap(
+            "[method]incoming-response.consume",
+            |_, (_,): (Resource<IncomingResponse>,)| Ok((Resource::<IncomingBody>::new_own(0),)),
+        )
+        .unwrap();
+    types
+        .func_wrap(
+            "[method]incoming-body.stream",
+            |_, (_,): (Resource<IncomingBody>,)| Ok((Resource::<InputStream>::new_own(0),)),
+        )
+        .unwrap();
+    // A euro sign, a stray byte and a sequence cut short, then the end.
+    let read = AtomicBool::new(false);
+    types
+        .func_wrap(
+            "[method]input-stream.blocking-read",
+            move |_, (_, _): (Resource<InputStream>, u64)| {
+                Ok((if read.swap(true, Ordering::SeqCst) {
+                    Err(StreamError::Closed)
+                } else {
+                    Ok(b"\xe2\x82\xac \xff \xe2\x82".to_vec())
+                },))
+            },
+        )
+        .unwrap();
+
+    linker
+        .instance("wasi:http/outgoing-handler@0.2.12")
+        .unwrap()
+        .func_wrap(
+            "handle",
+            |_, (_, _): (Resource<OutgoingRequest>, Option<u32>)| {
+                Ok((Resource::<FutureResponse>::new_own(0),))
+            },
+        )
+        .unwrap();
+
+    let instance = linker.instantiate(&mut store, &component).unwrap();
+    let fetch_text = instance.get_func(&mut store, "fetch-text").unwrap();
+    let mut results = [Val::Bool(false)];
+    fetch_text
+        .call(
+            &mut store,
+            &[Val::String("https://example.com/".into())],
+            &mut results,
+        )
+        .expect("fetch should decode invalid UTF-8 leniently");
+    assert_eq!(
+        results[0],
+        Val::String("caf\u{fffd}|\u{20ac} \u{fffd} \u{fffd}".into())
+    );
+}
+
+#[tokio::test]
+async fn test_keyvalue_global() {
+    #[derive(ComponentType, Lower)]
+    #[component(variant)]
+    enum StoreError {
+        #[component(name = "no-such-store")]
+        NoSuchStore,
+        #[component(name = "access-denied")]
+        AccessDenied,
+        #[component(name = "other")]
+        Other(String),
+    }
+
+    #[derive(ComponentType, Lower)]
+    #[component(record)]
+    struct KeyResponse {
+        keys: Vec<String>,
+        cursor: Option<u64>,
+    }
+
+    enum Bucket {}
+
+    let wasm = TestCase::new()
+        .wit(
+            r#"
+            package test:keyvalue;
+
+            world keyvalue {
+                import wasi:keyvalue/store@0.2.0-draft;
+                export run: func() -> string;
+            }
+
+            package wasi:keyvalue@0.2.0-draft {
+                interface store {
+                    variant error { no-such-store, access-denied, other(string) }
+                    record key-response { keys: list<string>, cursor: option<u64> }
+
+                    open: func(identifier: string) -> result<bucket, error>;
+
+                    resource bucket {
+                        get: func(key: string) -> result<option<list<u8>>, error>;
+                        set: func(key: string, value: list<u8>) -> result<_, error>;
+                        delete: func(key: string) -> result<_, error>;
+                        exists: func(key: string) -> result<bool, error>;
+                        list-keys: func(cursor: option<u64>) -> result<key-response, error>;
+                    }
+                }
+            }
+        "#,
+        )
+        .script(
+            r#"
+            export function run() {
+                const out = [];
+                const bucket = keyvalue.openBucket("cache");
+                bucket.set("greeting", "héllo").set("bytes", new Uint8Array([1, 2, 3]));
+                bucket.set("count", "3").set("gone", "x");
+                out.push(bucket.get("greeting", "utf8"));
+                out.push(Array.from(bucket.get("bytes")).join(","));
+                out.push(String(bucket.get("nothing")));
+                out.push(bucket.has("gone"), bucket.delete("gone"), bucket.has("gone"), bucket.delete("gone"));
+                out.push(bucket.listKeys().join(","));
+                for (const f of [
+                    () => keyvalue.openBucket("missing"),
+                    () => bucket.set("readonly", "x"),
+                    () => bucket.get("corrupt"),
+                ]) {
+                    try {
+                        f();
+                        out.push("no error");
+                    } catch (e) {
+                        out.push(`${e.code}: ${e.message}`);
+                    }
+                }
+                bucket.close();
+                try {
+                    bucket.get("greeting");
+                } catch (e) {
+                    out.push(e.message);
+                }
+                return out.join("|");
+            }
+        "#,
+        )
+        .componentize()
+        .await
+        .expect("should build keyvalue component");
+
+    let engine = common::engine();
+    let component = Component::new(engine, &wasm).unwrap();
+    let mut store = Store::new(
+        engine,
+        WasiCtxState {
+            wasi: WasiCtxBuilder::new().build(),
+            table: ResourceTable::new(),
+        },
+    );
+    let mut linker = Linker::new(engine);
+    wasmtime_wasi::p2::add_to_linker_sync(&mut linker).unwrap();
+
+    // One in-memory bucket, listed two keys per page.
+    let entries = Arc::new(Mutex::new(
+        std::collections::BTreeMap::<String, Vec<u8>>::new(),
+    ));
+    let mut kv = linker.instance("wasi:keyvalue/store@0.2.0-draft").unwrap();
+    kv.resource("bucket", ResourceType::host::<Bucket>(), |_, _| Ok(()))
+        .unwrap();
+    kv.func_wrap("open", |_, (name,): (String,)| {
+        Ok((if name == "cache" {
+            Ok(Resource::<Bucket>::new_own(0))
+        } else {
+            Err(StoreError::NoSuchStore)
+        },))
+    })
+    .unwrap();
+    let bucket = entries.clone();
+    kv.func_wrap(
+        "[method]bucket.get",
+        move |_, (_, key): (Resource<Bucket>, String)| {
+            Ok((if key == "corrupt" {
+                Err(StoreError::Other("checksum mismatch".into()))
+            } else {
+                Ok(bucket.lock().unwrap().get(&key).cloned())
+            },))
+        },
+    )
+    .unwrap();
+    let bucket = entries.clone();
+    kv.func_wrap(
+        "[method]bucket.set",
+        move |_, (_, key, value): (Resource<Bucket>, String, Vec<u8>)| {
+            Ok((if key == "readonly" {
+                Err(StoreError::AccessDenied)
+            } else {
+                bucket.lock().unwrap().insert(key, value);
+                Ok(())
+            },))
+        },
+    )
+    .unwrap();
+    let bucket = entries.clone();
+    kv.func_wrap(
+        "[method]bucket.delete",
+        move |_, (_, key): (Resource<Bucket>, String)| {
+            bucket.lock().unwrap().remove(&key);
+            Ok((Ok::<_, StoreError>(()),))
+        },
+    )
+    .unwrap();
+    let bucket = entries.clone();
+    kv.func_wrap(
+        "[method]bucket.exists",
+        move |_, (_, key): (Resource<Bucket>, String)| {
+            Ok((Ok::<_, StoreError>(
+                bucket.lock().unwrap().contains_key(&key),
+            ),))
+        },
+    )
+    .unwrap();
+    let bucket = entries.clone();
+    kv.func_wrap(
+        "[method]bucket.list-keys",
+        move |_, (_, cursor): (Resource<Bucket>, Option<u64>)| {
+            let start = cursor.unwrap_or(0) as usize;
+            let keys: Vec<String> = bucket.lock().unwrap().keys().cloned().collect();
+            let end = keys.len().min(start + 2);
+            let cursor = (end < keys.len()).then_some(end as u64);
+            Ok((Ok::<_, StoreError>(KeyResponse {
+                keys: keys[start..end].to_vec(),
+                cursor,
+            }),))
+        },
+    )
+    .unwrap();
+
+    let instance = linker.instantiate(&mut store, &component).unwrap();
+    let run = instance.get_func(&mut store, "run").unwrap();
+    let mut results = [Val::Bool(false)];
+    run.call(&mut store, &[], &mut results).unwrap();
+    assert_eq!(
+        results[0],
+        Val::String(
+            [
+                "h\u{e9}llo",
+                "1,2,3",
+                "undefined",
+                "true",
+                "true",
+                "false",
+                "false",
+                "bytes,count,greeting",
+                "no-such-store: cannot open bucket 'missing': no-such-store",
+                "access-denied: cannot set 'readonly' in bucket 'cache': access-denied",
+                "other: cannot get 'corrupt' in bucket 'cache': checksum mismatch",
+                "keyvalue: bucket 'cache' is closed",
+            ]
+            .join("|")
+        )
+    );
+    assert_eq!(
+        entries.lock().unwrap().get("greeting").map(Vec::as_slice),
+        Some("h\u{e9}llo".as_bytes())
+    );
+}
+
+#[tokio::test]
+async fn test_config_global() {
+    #[derive(ComponentType, Lower)]
+    #[component(variant)]
+    enum ConfigError {
+        #[component(name = "upstream")]
+        Upstream(String),
+        #[component(name = "io")]
+        Io(String),
+    }
+
+    // Both the current interface name and the earlier draft's.
+    for interface in ["store", "runtime-config"] {
+        let wasm = TestCase::new()
+            .wit(&format!(
+                r#"
+                package test:config;
+
+                world config {{
+                    import wasi:config/{interface}@0.2.0-draft;
+                    export run: func() -> string;
+                }}
+
+                package wasi:config@0.2.0-draft {{
+                    interface {interface} {{
+                        variant error {{ upstream(string), io(string) }}
+                        get: func(key: string) -> result<option<string>, error>;
+                        get-all: func() -> result<list<tuple<string, string>>, error>;
+                    }}
+                }}
+            "#
+            ))
+            .script(
+                r#"
+                export function run() {
+                    const out = [config.get("db-url"), String(config.get("missing"))];
+                    out.push(JSON.stringify(config.getAll()));
+                    for (const f of [() => config.get("secret"), () => config.getAll()]) {
+                        try {
+                            f();
+                            out.push("no error");
+                        } catch (e) {
+                            out.push(`${e.code}: ${e.message}`);
+                        }
+                    }
+                    return out.join("|");
+                }
+            "#,
+            )
+            .componentize()
+            .await
+            .expect("should build config component");
+
+        let engine = common::engine();
+        let component = Component::new(engine, &wasm).unwrap();
+        let mut store = Store::new(
+            engine,
+            WasiCtxState {
+                wasi: WasiCtxBuilder::new().build(),
+                table: ResourceTable::new(),
+            },
+        );
+        let mut linker = Linker::new(engine);
+        wasmtime_wasi::p2::add_to_linker_sync(&mut linker).unwrap();
+        let mut host = linker
+            .instance(&format!("wasi:config/{interface}@0.2.0-draft"))
+            .unwrap();
+        host.func_wrap("get", |_, (key,): (String,)| {
+            Ok((match key.as_str() {
+                "db-url" => Ok(Some("postgres://db".to_string())),
+                "secret" => Err(ConfigError::Upstream("vault is sealed".into())),
+                _ => Ok(None),
+            },))
+        })
+        .unwrap();
+        // The first call succeeds and the second fails.
+        let listed = AtomicBool::new(false);
+        host.func_wrap("get-all", move |_, (): ()| {
+            Ok((if listed.swap(true, Ordering::SeqCst) {
+                Err(ConfigError::Io("config file went away".into()))
+            } else {
+                Ok(vec![
+                    ("db-url".to_string(), "postgres://db".to_string()),
+                    ("mode".to_string(), "dev".to_string()),
+                ])
+            },))
+        })
+        .unwrap();
+
+        let instance = linker.instantiate(&mut store, &component).unwrap();
+        let run = instance.get_func(&mut store, "run").unwrap();
+        let mut results = [Val::Bool(false)];
+        run.call(&mut store, &[], &mut results).unwrap();
+        assert_eq!(
+            results[0],
+            Val::String(
+                [
+                    "postgres://db",
+                    "undefined",
+                    r#"{"db-url":"postgres://db","mode":"dev"}"#,
+                    "upstream: cannot read config 'secret': vault is sealed",
+                    "io: cannot read config: config file went away",
+                ]
+                .join("|")
+            ),
+            "wasi:config/{interface}"
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_incoming_handler_adapter() {
+    #[derive(ComponentType, Lower)]
+    #[component(variant)]
+    enum Method {
+        #[component(name = "get")]
+        Get,
+        #[component(name = "post")]
+        Post,
+    }
+
+    #[derive(ComponentType, Lower)]
+    #[component(variant)]
+    enum Scheme {
+        #[component(name = "HTTP")]
+        Http,
+    }
+
+    #[derive(ComponentType, Lower)]
+    #[component(variant)]
+    enum StreamError {
+        #[component(name = "closed")]
+        Closed,
+    }
+
+    #[derive(ComponentType, Lift)]
+    #[component(variant)]
+    enum ErrorCode {
+        #[component(name = "internal-error")]
+        InternalError(Option<String>),
+    }
+
+    enum Fields {}
+    enum IncomingRequest {}
+    enum IncomingBody {}
+    enum InputStream {}
+    enum OutgoingResponse {}
+    enum OutgoingBody {}
+    enum OutputStream {}
+    enum ResponseOutparam {}
+    type Outcome = Result<Resource<OutgoingResponse>, ErrorCode>;
+
+    fn resource<T: 'static>(types: &mut LinkerInstance<'_, WasiCtxState>, name: &str) {
+        types
+            .resource(name, ResourceType::host::<T>(), |_, _| Ok(()))
+            .unwrap();
+    }
+
+    /// One request as the host sends it, and the response the guest sent.
+    #[derive(Default)]
+    struct Exchange {
+        path: String,
+        body: Vec<Vec<u8>>,
+        status: Option<u16>,
+        headers: Vec<(String, Vec<u8>)>,
+        response_body: Vec<u8>,
+        finished: bool,
+        error: Option<Option<String>>,
+    }
+
+    // Just enough of `wasi:http` to serve one request at a time.
+    let wasm = TestCase::new()
+        .wit(
+            r#"
+            package test:serve;
+
+            world server {
+                export wasi:http/incoming-handler@0.2.12;
+            }
+
+            package wasi:http@0.2.12 {
+                interface types {
+                    variant method { get, post }
+                    variant scheme { HTTP }
+                    variant stream-error { closed }
+                    variant error-code { internal-error(option<string>) }
+
+                    resource fields {
+                        from-list: static func(entries: list<tuple<string, list<u8>>>) -> fields;
+                        entries: func() -> list<tuple<string, list<u8>>>;
+                    }
+                    resource input-stream {
+                        blocking-read: func(len: u64) -> result<list<u8>, stream-error>;
+                    }
+                    resource output-stream {
+                        blocking-write-and-flush: func(contents: list<u8>) -> result<_, stream-error>;
+                    }
+                    resource incoming-body {
+                        %stream: func() -> input-stream;
+                    }
+                    resource incoming-request {
+                        method: func() -> method;
+                        path-with-query: func() -> option<string>;
+                        scheme: func() -> option<scheme>;
+                        authority: func() -> option<string>;
+                        headers: func() -> fields;
+                        consume: func() -> incoming-body;
+                    }
+                    resource outgoing-body {
+                        write: func() -> output-stream;
+                        finish: static func(this: outgoing-body, trailers: option<fields>);
+                    }
+                    resource outgoing-response {
+                        constructor(headers: fields);
+                        set-status-code: func(status-code: u16) -> result;
+                        body: func() -> outgoing-body;
+                    }
+                    resource response-outparam {
+                        set: static func(param: response-outparam, response: result<outgoing-response, error-code>);
+                    }
+                }
+
+                interface incoming-handler {
+                    use types.{incoming-request, response-outparam};
+                    handle: func(request: incoming-request, response-out: response-outparam);
+                }
+            }
+        "#,
+        )
+        .script(
+            r#"
+            export default {
+                async fetch(request) {
+                    const { pathname } = new URL(request.url);
+                    if (pathname === "/boom") throw new Error("kaboom");
+                    if (pathname === "/echo") {
+                        const type = request.headers.get("content-type");
+                        const text = await request.text();
+                        return new Response(`${request.method} ${request.url} ${type}: ${text}`);
+                    }
+                    return new Response("hello", {
+                        status: 201,
+                        headers: { "x-answer": "42", "content-type": "text/plain" },
+                    });
+                },
+            };
+        "#,
+        )
+        .componentize()
+        .await
+        .expect("should build incoming-handler component");
+
+    let engine = common::engine();
+    let component = Component::new(engine, &wasm).unwrap();
+    let mut store = Store::new(
+        engine,
+        WasiCtxState {
+            wasi: WasiCtxBuilder::new().build(),
+            table: ResourceTable::new(),
+        },
+    );
+    let mut linker = Linker::new(engine);
+    wasmtime_wasi::p2::add_to_linker_sync(&mut linker).unwrap();
+
+    let exchange = Arc::new(Mutex::new(Exchange::default()));
+    let mut types = linker.instance("wasi:http/types@0.2.12").unwrap();
+    resource::<Fields>(&mut types, "fields");
+    resource::<IncomingRequest>(&mut types, "incoming-request");
+    resource::<IncomingBody>(&mut types, "incoming-body");
+    resource::<InputStream>(&mut types, "input-stream");
+    resource::<OutgoingResponse>(&mut types, "outgoing-response");
+    resource::<OutgoingBody>(&mut types, "outgoing-body");
+    resource::<OutputStream>(&mut types, "output-stream");
+    resource::<ResponseOutparam>(&mut types, "response-outparam");
+
+    let state = exchange.clone();
+    types
+        .func_wrap(
+            "[static]fields.from-list",
+            move |_, (entries,): (Vec<(String, Vec<u8>)>,)| {
+                state.lock().unwrap().headers = entries;
+                Ok((Resource::<Fields>::new_own(0),))
+            },
+        )
+        .unwrap();
+    types
+        .func_wrap("[method]fields.entries", |_, (_,): (Resource<Fields>,)| {
+            Ok((vec![("content-type".to_string(), b"text/plain".to_vec())],))
+        })
+        .unwrap();
+    let state = exchange.clone();
+    types
+        .func_wrap(
+            "[method]incoming-request.method",
+            move |_, (_,): (Resource<IncomingRequest>,)| {
+                let post = !state.lock().unwrap().body.is_empty();
+                Ok((if post { Method::Post } else { Method::Get },))
+            },
+        )
+        .unwrap();
+    let state = exchange.clone();
+    types
+        .func_wrap(
+            "[method]incoming-request.path-with-query",
+            move |_, (_,): (Resource<IncomingRequest>,)| {
+                Ok((Some(state.lock().unwrap().path.clone()),))
+            },
+        )
+        .unwrap();
+    types
+        .func_wrap(
+            "[method]incoming-request.scheme",
+            |_, (_,): (Resource<IncomingRequest>,)| Ok((Some(Scheme::Http),)),
+        )
+        .unwrap();
+    types
+        .func_wrap(
+            "[method]incoming-request.authority",
+            |_, (_,): (Resource<IncomingRequest>,)| Ok((Some("example.com".to_string()),)),
+        )
+        .unwrap();
+    types
+        .func_wrap(
+            "[method]incoming-request.headers",
+            |_, (_,): (Resource<IncomingRequest>,)| Ok((Resource::<Fields>::new_own(0),)),
+        )
+        .unwrap();
+    types
+        .func_wrap(
+            "[method]incoming-request.consume",
+            |_, (_,): (Resource<IncomingRequest>,)| Ok((Resource::<IncomingBody>::new_own(0),)),
+        )
+        .unwrap();
+    types
+        .func_wrap(
+            "[method]incoming-body.stream",
+            |_, (_,): (Resource<IncomingBody>,)| Ok((Resource::<InputStream>::new_own(0),)),
+        )
+        .unwrap();
+    let state = exchange.clone();
+    types
+        .func_wrap(
+            "[method]input-stream.blocking-read",
+            move |_, (_, _): (Resource<InputStream>, u64)| {
+                let mut state = state.lock().unwrap();
+                Ok((if state.body.is_empty() {
+                    Err(StreamError::Closed)
+                } else {
+                    Ok(state.body.remove(0))
+                },))
+            },
+        )
+        .unwrap();
+    types
+        .func_wrap(
+            "[constructor]outgoing-response",
+            |_, (_,): (Resource<Fields>,)| Ok((Resource::<OutgoingResponse>::new_own(0),)),
+        )
+        .unwrap();
+    let state = exchange.clone();
+    types
+        .func_wrap(
+            "[method]outgoing-response.set-status-code",
+            move |_, (_, status): (Resource<OutgoingResponse>, u16)| {
+                state.lock().unwrap().status = Some(status);
+                Ok((Ok::<_, ()>(()),))
+            },
+        )
+        .unwrap();
+    types
+        .func_wrap(
+            "[method]outgoing-response.body",
+            |_, (_,): (Resource<OutgoingResponse>,)| Ok((Resource::<OutgoingBody>::new_own(0),)),
+        )
+        .unwrap();
+    types
+        .func_wrap(
+            "[method]outgoing-body.write",
+            |_, (_,): (Resource<OutgoingBody>,)| Ok((Resource::<OutputStream>::new_own(0),)),
+        )
+        .unwrap();
+    let state = exchange.clone();
+    types
+        .func_wrap(
+            "[method]output-stream.blocking-write-and-flush",
+            move |_, (_, contents): (Resource<OutputStream>, Vec<u8>)| {
+                state.lock().unwrap().response_body.extend(contents);
+                Ok((Ok::<_, StreamError>(()),))
+            },
+        )
+        .unwrap();
+    let state = exchange.clone();
+    types
+        .func_wrap(
+            "[static]outgoing-body.finish",
+            move |_, (_, _): (Resource<OutgoingBody>, Option<Resource<Fields>>)| {
+                state.lock().unwrap().finished = true;
+                Ok(())
+            },
+        )
+        .unwrap();
+    let state = exchange.clone();
+    types
+        .func_wrap(
+            "[static]response-outparam.set",
+            move |_, (_, response): (Resource<ResponseOutparam>, Outcome)| {
+                if let Err(ErrorCode::InternalError(message)) = response {
+                    state.lock().unwrap().error = Some(message);
+                }
+                Ok(())
+            },
+        )
+        .unwrap();
+
+    let instance = linker.instantiate(&mut store, &component).unwrap();
+    let iface_idx = instance
+        .get_export_index(&mut store, None, "wasi:http/incoming-handler@0.2.12")
+        .expect("wasi:http/incoming-handler export not found");
+    let handle_idx = instance
+        .get_export_index(&mut store, Some(&iface_idx), "handle")
+        .expect("handle export not found");
+    let handle = instance
+        .get_typed_func::<(Resource<IncomingRequest>, Resource<ResponseOutparam>), ()>(
+            &mut store, handle_idx,
+        )
+        .unwrap();
+    let mut serve = |path: &str, body: &[&str]| {
+        *exchange.lock().unwrap() = Exchange {
+            path: path.to_string(),
+            body: body.iter().map(|chunk| chunk.as_bytes().to_vec()).collect(),
+            ..Exchange::default()
+        };
+        let request = Resource::new_own(0);
+        let response_out = Resource::new_own(0);
+        handle
+            .call(&mut store, (request, response_out))
+            .expect("handle should not trap");
+        std::mem::take(&mut *exchange.lock().unwrap())
+    };
+
+    let hello = serve("/hello", &[]);
+    assert_eq!(hello.error, None);
+    assert_eq!(hello.status, Some(201));
+    let mut headers = hello.headers;
+    headers.sort();
+    assert_eq!(
+        headers,
+        [
+            ("content-type".to_string(), b"text/plain".to_vec()),
+            ("x-answer".to_string(), b"42".to_vec()),
+        ]
+    );
+    assert_eq!(hello.response_body, b"hello");
+    assert!(hello.finished);
+
+    let boom = serve("/boom", &[]);
+    assert_eq!(boom.status, None, "no response should be sent");
+    let message = boom
+        .error
+        .expect("a throwing handler should send an error")
+        .unwrap_or_default();
+    assert!(message.contains("kaboom"), "unexpected error: {message}");
+
+    let echo = serve("/echo?x=1", &["ping ", "p\u{f6}ng"]);
+    assert_eq!(echo.error, None);
+    assert_eq!(echo.status, Some(200));
+    assert_eq!(
+        String::from_utf8(echo.response_body).unwrap(),
+        "POST http://example.com/echo?x=1 text/plain: ping p\u{f6}ng"
+    );
+    assert!(echo.finished);
 }
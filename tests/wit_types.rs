@@ -1,9 +1,15 @@
 //! WIT type integration tests for componentize-qjs
 mod common;
 
+use std::path::Path;
+use std::time::Duration;
+
+use componentize_qjs::{
+    ArityMismatch, ComponentizeBuilder, Componentizer, MathRandom, PrimeCall, VersionAliasing,
+};
 use wasmtime::component::Val;
 
-use common::TestCase;
+use common::{ComponentInstance, TestCase};
 
 #[cfg(not(feature = "component-model-async"))]
 #[test]
@@ -49,6 +55,24 @@ fn test_hello_world() {
         .run();
 }
 
+#[test]
+fn test_inline_wit_source() {
+    TestCase::new()
+        .wit_source(
+            r#"
+            package test:inline;
+            world inline {
+                export greet: func() -> string;
+            }
+        "#,
+        )
+        .script(r#"export function greet() { return "inline"; }"#)
+        .expect_call("greet", vec![], Val::String("inline".into()))
+        .build()
+        .unwrap()
+        .run();
+}
+
 #[test]
 fn test_export_only_interface_is_not_importable() {
     let result = TestCase::new()
@@ -88,6 +112,126 @@ fn test_export_only_interface_is_not_importable() {
     );
 }
 
+#[test]
+fn test_deny_warnings_rejects_missing_parameters() {
+    let result = TestCase::new()
+        .wit(
+            r#"
+            package test:arity;
+            world arity {
+                export add: func(a: u32, b: u32) -> u32;
+            }
+        "#,
+        )
+        .script("export function add(a) { return a + arguments[1]; }")
+        .deny_warnings()
+        .build();
+
+    let Err(err) = result else {
+        panic!("an export declaring too few parameters should fail with deny_warnings");
+    };
+    assert!(
+        format!("{err:#}")
+            .contains("export `add` declares 1 parameters but its WIT signature has 2"),
+        "unexpected error: {err:#}"
+    );
+}
+
+#[test]
+fn test_arity_mismatch_modes() {
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+    let build = |mode, lazy_eval| {
+        let opts = ComponentizeBuilder::new(
+            Path::new("arity.wit"),
+            "export function add(a, b, c) { return a + b; }",
+        )
+        .wit_source("package test:arity; world arity { export add: func(a: u32, b: u32) -> u32; }")
+        .arity_mismatch(mode)
+        .lazy_eval(lazy_eval)
+        .build();
+        rt.block_on(componentize_qjs::componentize_with_report(&opts))
+    };
+    let mismatch = "export `add` declares 3 parameters but its WIT signature has 2";
+
+    let (_, report) = build(ArityMismatch::Ignore, false).unwrap();
+    assert!(report.warnings.is_empty(), "{:?}", report.warnings);
+
+    let (_, report) = build(ArityMismatch::Warn, false).unwrap();
+    assert_eq!(report.warnings.len(), 1, "{:?}", report.warnings);
+    assert!(
+        report.warnings[0].starts_with(mismatch),
+        "{:?}",
+        report.warnings
+    );
+
+    let err = build(ArityMismatch::Error, false).err().unwrap();
+    assert!(format!("{err:#}").contains(mismatch), "{err:#}");
+
+    let err = build(ArityMismatch::Error, true).err().unwrap();
+    assert!(
+        format!("{err:#}").contains("cannot be combined with lazy_eval"),
+        "{err:#}"
+    );
+}
+
+#[test]
+fn test_interface_export_qualified_lookup() {
+    let mut inst = TestCase::new()
+        .wit(
+            r#"
+            package test:ns@1.0.0;
+
+            interface api {
+                name: func() -> string;
+            }
+
+            interface legacy {
+                name: func() -> string;
+            }
+
+            world namespaced {
+                export api;
+                export legacy;
+            }
+        "#,
+        )
+        .script(
+            r#"
+            export const api = { name() { return "short"; } };
+            export const legacy = { name() { return "legacy"; } };
+            globalThis["test:ns/api"] = { name() { return "qualified"; } };
+        "#,
+        )
+        .build()
+        .unwrap();
+
+    let (instance, store) = inst.parts();
+    let mut call_name = |iface: &str| {
+        let iface_idx = instance
+            .get_export_index(&mut *store, None, iface)
+            .expect("interface export not found");
+        let func_idx = instance
+            .get_export_index(&mut *store, Some(&iface_idx), "name")
+            .expect("name export not found");
+        let func = instance.get_func(&mut *store, func_idx).unwrap();
+        let mut results = [Val::Bool(false)];
+        func.call(&mut *store, &[], &mut results).unwrap();
+        results[0].clone()
+    };
+
+    assert_eq!(
+        call_name("test:ns/api@1.0.0"),
+        Val::String("qualified".into())
+    );
+    assert_eq!(
+        call_name("test:ns/legacy@1.0.0"),
+        Val::String("legacy".into())
+    );
+}
+
 #[test]
 fn test_numeric_types() {
     TestCase::new()
@@ -181,6 +325,132 @@ fn test_list_type() {
         .run();
 }
 
+#[test]
+fn test_array_like_lists() {
+    let mut inst = TestCase::new()
+        .wit(
+            r#"
+            package test:lists;
+            world lists {
+                export squares: func(n: u32) -> list<u32>;
+                export from-floats: func() -> list<u32>;
+                export names: func() -> list<string>;
+            }
+        "#,
+        )
+        .script(
+            r#"
+            // A virtual list whose elements are computed as they are lowered
+            export function squares(n) {
+                return new Proxy({}, {
+                    get: (_, key) =>
+                        key === "length" ? n : typeof key === "string" ? Number(key) ** 2 : undefined,
+                });
+            }
+            export function fromFloats() { return new Float64Array([1, 2, 3]); }
+            export function names() { return { length: 2, 0: "a", 1: "b" }; }
+        "#,
+        )
+        .expect_call(
+            "from-floats",
+            vec![],
+            Val::List(vec![Val::U32(1), Val::U32(2), Val::U32(3)]),
+        )
+        .expect_call(
+            "names",
+            vec![],
+            Val::List(vec![Val::String("a".into()), Val::String("b".into())]),
+        )
+        .build()
+        .unwrap();
+    inst.run();
+
+    match inst.call1("squares", &[Val::U32(50_000)]) {
+        Val::List(items) => {
+            assert_eq!(items.len(), 50_000);
+            assert_eq!(items[3], Val::U32(9));
+            assert_eq!(items[49_999], Val::U32(49_999 * 49_999));
+        }
+        other => panic!("Expected list, got: {:?}", other),
+    }
+}
+
+#[test]
+fn test_set_lists() {
+    TestCase::new()
+        .wit(
+            r#"
+            package test:lists;
+            world lists {
+                record point { x: u32, y: u32 }
+                export numbers: func() -> list<u32>;
+                export words: func() -> list<string>;
+                export points: func() -> list<point>;
+                export nested: func() -> list<list<u8>>;
+                export bounded: func() -> result<list<u32>, string>;
+                export checked: func() -> result<list<u32>, string>;
+            }
+        "#,
+        )
+        .max_list_length(3)
+        .strict_types()
+        .script(
+            r#"
+            // Insertion order is kept and repeats were already dropped by the Set
+            export function numbers() { return new Set([3, 1, 3, 2, 1]); }
+            export function words() {
+                const words = new Set(["b", "a"]);
+                words.delete("b");
+                words.add("c").add("b");
+                return words;
+            }
+            // Distinct objects stay distinct, even with equal fields
+            export function points() {
+                const p = { x: 1, y: 2 };
+                return new Set([p, { x: 1, y: 2 }, p]);
+            }
+            export function nested() { return [new Set([7]), new Set()]; }
+            export function bounded() { return new Set([1, 2, 3, 4]); }
+            export function checked() { return new Set([1, "2"]); }
+        "#,
+        )
+        .expect_call(
+            "numbers",
+            vec![],
+            Val::List(vec![Val::U32(3), Val::U32(1), Val::U32(2)]),
+        )
+        .expect_call("words", vec![], {
+            let expected = ["a", "c", "b"].map(|s| Val::String(s.into()));
+            Val::List(expected.into())
+        })
+        .expect_call("points", vec![], {
+            let point = Val::Record(vec![("x".into(), Val::U32(1)), ("y".into(), Val::U32(2))]);
+            Val::List(vec![point.clone(), point])
+        })
+        .expect_call(
+            "nested",
+            vec![],
+            Val::List(vec![Val::List(vec![Val::U8(7)]), Val::List(vec![])]),
+        )
+        .expect_call(
+            "bounded",
+            vec![],
+            Val::Result(Err(Some(Box::new(Val::String(
+                "return value: length 4 exceeds the maximum list length of 3".into(),
+            ))))),
+        )
+        .expect_call(
+            "checked",
+            vec![],
+            Val::Result(Err(Some(Box::new(Val::String(
+                r#"return value[1]: expected u32, got string "2""#.into(),
+            ))))),
+        )
+        .build()
+        .unwrap()
+        .run();
+}
+
 #[test]
 fn test_option_type() {
     TestCase::new()
@@ -211,6 +481,52 @@ fn test_option_type() {
         .run();
 }
 
+#[test]
+fn test_option_primitives() {
+    TestCase::new()
+        .wit(
+            r#"
+            package test:options;
+            world option-test {
+                export echo-string: func(s: option<string>) -> option<string>;
+                export echo-float: func(n: option<f64>) -> option<f64>;
+                export echo-bool: func(b: option<bool>) -> option<bool>;
+                export nothing: func() -> option<string>;
+            }
+        "#,
+        )
+        .script(
+            r#"
+            export function echoString(s) { return s; }
+            export function echoFloat(n) { return n; }
+            export function echoBool(b) { return b; }
+            export function nothing() { return undefined; }
+        "#,
+        )
+        // Falsy payloads must stay `some`.
+        .expect_call(
+            "echo-string",
+            vec![Val::Option(Some(Box::new(Val::String("".into()))))],
+            Val::Option(Some(Box::new(Val::String("".into())))),
+        )
+        .expect_call("echo-string", vec![Val::Option(None)], Val::Option(None))
+        .expect_call(
+            "echo-float",
+            vec![Val::Option(Some(Box::new(Val::Float64(0.0))))],
+            Val::Option(Some(Box::new(Val::Float64(0.0)))),
+        )
+        .expect_call("echo-float", vec![Val::Option(None)], Val::Option(None))
+        .expect_call(
+            "echo-bool",
+            vec![Val::Option(Some(Box::new(Val::Bool(false))))],
+            Val::Option(Some(Box::new(Val::Bool(false)))),
+        )
+        .expect_call("nothing", vec![], Val::Option(None))
+        .build()
+        .unwrap()
+        .run();
+}
+
 #[test]
 fn test_result_type() {
     TestCase::new()
@@ -246,82 +562,75 @@ fn test_result_type() {
 }
 
 #[test]
-fn test_stub_wasi() {
+fn test_sync_export_drains_microtasks() {
     TestCase::new()
         .wit(
             r#"
-            package test:hello;
-            world hello {
-                export greet: func(name: string) -> string;
-                export add: func(a: u32, b: u32) -> u32;
+            package test:microtasks;
+            world microtasks {
+                export schedule: func() -> u32;
+                export log: func() -> string;
             }
         "#,
         )
         .script(
             r#"
-            export function greet(name) { return "Hello, " + name + "!"; }
-            export function add(a, b) { return a + b; }
+            const entries = [];
+            async function later() {
+                await null;
+                entries.push("async");
+            }
+            export function schedule() {
+                Promise.resolve().then(() => entries.push("then"));
+                queueMicrotask(() => entries.push("microtask"));
+                later();
+                return entries.length;
+            }
+            export function log() { return entries.join(","); }
         "#,
         )
-        .stub_wasi()
-        .expect_call(
-            "greet",
-            vec![Val::String("World".into())],
-            Val::String("Hello, World!".into()),
-        )
-        .expect_call("add", vec![Val::U32(2), Val::U32(3)], Val::U32(5))
+        .expect_call("schedule", vec![], Val::U32(0))
+        .expect_call("log", vec![], Val::String("then,microtask,async".into()))
         .build()
         .unwrap()
         .run();
 }
 
 #[test]
-fn test_all_integer_types() {
+fn test_sync_export_returning_promise() {
     TestCase::new()
         .wit(
             r#"
-            package test:integers;
-            world integers {
-                export add-u8: func(a: u8, b: u8) -> u8;
-                export add-s8: func(a: s8, b: s8) -> s8;
-                export add-u16: func(a: u16, b: u16) -> u16;
-                export add-s16: func(a: s16, b: s16) -> s16;
-                export add-u64: func(a: u64, b: u64) -> u64;
-                export add-s64: func(a: s64, b: s64) -> s64;
+            package test:sync-promise;
+            world sync-promise {
+                export add: func(a: u32, b: u32) -> u32;
+                export safe-div: func(a: u32, b: u32) -> result<u32, string>;
             }
         "#,
         )
         .script(
             r#"
-            export function addU8(a, b) { return a + b; }
-            export function addS8(a, b) { return a + b; }
-            export function addU16(a, b) { return a + b; }
-            export function addS16(a, b) { return a + b; }
-            export function addU64(a, b) { return a + b; }
-            export function addS64(a, b) { return a + b; }
+            export async function add(a, b) {
+                await null;
+                return a + b;
+            }
+            export async function safeDiv(a, b) {
+                await null;
+                if (b === 0) { throw "division by zero"; }
+                return Math.floor(a / b);
+            }
         "#,
         )
-        .expect_call("add-u8", vec![Val::U8(200), Val::U8(55)], Val::U8(255))
-        .expect_call("add-s8", vec![Val::S8(-100), Val::S8(50)], Val::S8(-50))
-        .expect_call(
-            "add-u16",
-            vec![Val::U16(60000), Val::U16(5535)],
-            Val::U16(65535),
-        )
-        .expect_call(
-            "add-s16",
-            vec![Val::S16(-30000), Val::S16(10000)],
-            Val::S16(-20000),
-        )
+        .expect_call("add", vec![Val::U32(2), Val::U32(3)], Val::U32(5))
         .expect_call(
-            "add-u64",
-            vec![Val::U64(1_000_000_000), Val::U64(2_000_000_000)],
-            Val::U64(3_000_000_000),
+            "safe-div",
+            vec![Val::U32(9), Val::U32(3)],
+            Val::Result(Ok(Some(Box::new(Val::U32(3))))),
         )
         .expect_call(
-            "add-s64",
-            vec![Val::S64(-1_000_000_000), Val::S64(500_000_000)],
-            Val::S64(-500_000_000),
+            "safe-div",
+            vec![Val::U32(1), Val::U32(0)],
+            Val::Result(Err(Some(Box::new(Val::String("division by zero".into()))))),
         )
         .build()
         .unwrap()
@@ -329,275 +638,529 @@ fn test_all_integer_types() {
 }
 
 #[test]
-fn test_float_types() {
+fn test_feature_flags() {
     TestCase::new()
         .wit(
             r#"
-            package test:floats;
-            world floats {
-                export add-f32: func(a: f32, b: f32) -> f32;
-                export add-f64: func(a: f64, b: f64) -> f64;
+            package test:features;
+            world features {
+                export enabled: func(name: string) -> bool;
+                export read-only: func() -> bool;
             }
         "#,
         )
-        .script("export function addF32(a, b) { return a + b; }\nexport function addF64(a, b) { return a + b; }")
+        .script(
+            r#"
+            export function enabled(name) { return __FEATURES__.has(name); }
+            export function readOnly() {
+                try { __FEATURES__.add("other"); } catch { return !__FEATURES__.has("other"); }
+                return false;
+            }
+        "#,
+        )
+        .feature("metrics")
+        .feature("beta")
         .expect_call(
-            "add-f32",
-            vec![Val::Float32(1.5), Val::Float32(2.5)],
-            Val::Float32(4.0),
+            "enabled",
+            vec![Val::String("metrics".into())],
+            Val::Bool(true),
         )
+        .expect_call("enabled", vec![Val::String("beta".into())], Val::Bool(true))
         .expect_call(
-            "add-f64",
-            vec![Val::Float64(1.5), Val::Float64(2.5)],
-            Val::Float64(4.0),
+            "enabled",
+            vec![Val::String("legacy".into())],
+            Val::Bool(false),
         )
+        .expect_call("read-only", vec![], Val::Bool(true))
         .build()
         .unwrap()
         .run();
 }
 
 #[test]
-fn test_string_operations() {
+fn test_url_globals() {
     TestCase::new()
         .wit(
             r#"
-            package test:strings;
-            world strings {
-                export take-string: func(s: string) -> u32;
-                export return-string: func() -> string;
-                export concat-strings: func(a: string, b: string) -> string;
+            package test:url;
+            world url {
+                export resolve: func(url: string, base: string) -> string;
+                export query: func(url: string, name: string) -> option<string>;
+                export with-param: func(url: string, name: string, value: string) -> string;
+                export can-parse: func(url: string) -> bool;
             }
         "#,
         )
         .script(
             r#"
-            export function takeString(s) { return s.length; }
-            export function returnString() { return "hello from js"; }
-            export function concatStrings(a, b) { return a + b; }
+            export function resolve(url, base) { return new URL(url, base).href; }
+            export function query(url, name) { return new URL(url).searchParams.get(name); }
+            export function withParam(url, name, value) {
+                const parsed = new URL(url);
+                parsed.searchParams.append(name, value);
+                return parsed.href;
+            }
+            export function canParse(url) { return URL.canParse(url); }
         "#,
         )
         .expect_call(
-            "take-string",
-            vec![Val::String("hello".into())],
-            Val::U32(5),
+            "resolve",
+            vec![
+                Val::String("../d/./e?x#y".into()),
+                Val::String("HTTP://Example.COM:80/a/b/c".into()),
+            ],
+            Val::String("http://example.com/a/d/e?x#y".into()),
         )
-        .expect_call("return-string", vec![], Val::String("hello from js".into()))
         .expect_call(
-            "concat-strings",
-            vec![Val::String("foo".into()), Val::String("bar".into())],
-            Val::String("foobar".into()),
+            "resolve",
+            vec![
+                Val::String("//[::ffff:192.168.0.1]:8080".into()),
+                Val::String("https://a/".into()),
+            ],
+            Val::String("https://[::ffff:c0a8:1]:8080/".into()),
         )
-        .build()
+        .expect_call(
+            "resolve",
+            vec![
+                Val::String("http://bücher.de/ä".into()),
+                Val::String("about:blank".into()),
+            ],
+            Val::String("http://xn--bcher-kva.de/%C3%A4".into()),
+        )
+        .expect_call(
+            "query",
+            vec![
+                Val::String("https://a/?q=caf%C3%A9+au+lait&q=2".into()),
+                Val::String("q".into()),
+            ],
+            Val::Option(Some(Box::new(Val::String("café au lait".into())))),
+        )
+        .expect_call(
+            "query",
+            vec![Val::String("https://a/".into()), Val::String("q".into())],
+            Val::Option(None),
+        )
+        .expect_call(
+            "with-param",
+            vec![
+                Val::String("https://a/p#frag".into()),
+                Val::String("k".into()),
+                Val::String("a b&c".into()),
+            ],
+            Val::String("https://a/p?k=a+b%26c#frag".into()),
+        )
+        .expect_call(
+            "can-parse",
+            vec![Val::String("http://[::1".into())],
+            Val::Bool(false),
+        )
+        .build()
         .unwrap()
         .run();
 }
 
 #[test]
-fn test_char_type() {
+fn test_polyfill_selection() {
+    let wit = r#"
+        package test:polyfills;
+        world polyfills {
+            export installed: func() -> list<string>;
+            export round-trip: func(s: string) -> string;
+        }
+    "#;
+    let script = r#"
+        const GLOBALS = ["URL", "TextEncoder", "structuredClone", "ReadableStream"];
+        export function installed() { return GLOBALS.filter((name) => name in globalThis); }
+        export function roundTrip(s) {
+            const bytes = structuredClone(new TextEncoder().encode(s));
+            return new TextDecoder().decode(bytes);
+        }
+    "#;
+    let installed =
+        |names: &[&str]| Val::List(names.iter().map(|n| Val::String(n.to_string())).collect());
+
+    TestCase::new()
+        .wit(wit)
+        .script(script)
+        .expect_call(
+            "installed",
+            vec![],
+            installed(&["URL", "TextEncoder", "structuredClone"]),
+        )
+        .expect_call(
+            "round-trip",
+            vec![Val::String("héllo 😀".into())],
+            Val::String("héllo 😀".into()),
+        )
+        .build()
+        .unwrap()
+        .run();
+
     TestCase::new()
+        .wit(wit)
+        .script(script)
+        .polyfill("streams")
+        .expect_call(
+            "installed",
+            vec![],
+            installed(&["URL", "TextEncoder", "structuredClone", "ReadableStream"]),
+        )
+        .build()
+        .unwrap()
+        .run();
+
+    TestCase::new()
+        .wit(wit)
+        .script(script)
+        .no_default_polyfills()
+        .polyfill("url")
+        .expect_call("installed", vec![], installed(&["URL"]))
+        .build()
+        .unwrap()
+        .run();
+
+    let err = TestCase::new()
+        .wit(wit)
+        .script(script)
+        .polyfill("fetch")
+        .build()
+        .expect_err("unknown polyfills should be rejected");
+    assert!(
+        format!("{err:#}").contains("unknown polyfill `fetch`"),
+        "unexpected error: {err:#}"
+    );
+}
+
+#[test]
+fn test_streams_polyfill() {
+    let mut inst = TestCase::new()
         .wit(
             r#"
-            package test:chars;
-            world chars {
-                export take-char: func(c: char) -> u32;
-                export return-char: func() -> char;
+            package test:streams;
+            world streams {
+                export shout: func(words: list<string>) -> string;
             }
         "#,
         )
         .script(
             r#"
-            export function takeChar(c) { return c.codePointAt(0); }
-            export function returnChar() { return "A"; }
+            export async function shout(words) {
+                const upper = new TransformStream({
+                    transform(chunk, controller) { controller.enqueue(chunk.toUpperCase()); },
+                    flush(controller) { controller.enqueue("!"); },
+                });
+                let out = "";
+                for await (const chunk of ReadableStream.from(words).pipeThrough(upper)) {
+                    out += chunk;
+                }
+                return out;
+            }
         "#,
         )
-        .expect_call("take-char", vec![Val::Char('A')], Val::U32(65))
-        .expect_call("return-char", vec![], Val::Char('A'))
+        .polyfill("streams")
         .build()
-        .unwrap()
-        .run();
+        .unwrap();
+
+    let words = Val::List(vec![Val::String("a".into()), Val::String("b".into())]);
+    assert_eq!(inst.call1("shout", &[words]), Val::String("AB!".into()));
 }
 
 #[test]
-fn test_result_throw_error_compatibility() {
-    const WIT: &str = r#"
-            package test:result-errors;
-            world result-errors {
-                type string-err = string;
-                export inline-string-error: func() -> result<_, string>;
-                export alias-string-error: func() -> result<_, string-err>;
-                export payload-error: func() -> result<_, u32>;
-                export plain-error-traps: func() -> result<_, u32>;
-            }
-        "#;
-    const SCRIPT: &str = r#"
-            export function inlineStringError() {
-                throw new Error("inline string error");
+fn test_abort_polyfill() {
+    let mut inst = TestCase::new()
+        .wit(
+            r#"
+            package test:abort;
+            world abort {
+                export events: func() -> list<string>;
             }
+        "#,
+        )
+        .script(
+            r#"
+            export function events() {
+                const log = [];
+                const controller = new AbortController();
+                const { signal } = controller;
+                signal.onabort = (event) => log.push(`onabort ${event.type}`);
+                signal.addEventListener("abort", () => log.push("once"), { once: true });
+                const removed = () => log.push("removed");
+                signal.addEventListener("abort", removed);
+                signal.removeEventListener("abort", removed);
+                const any = AbortSignal.any([new AbortController().signal, signal]);
 
-            export function aliasStringError() {
-                throw new Error("alias string error");
+                controller.abort();
+                controller.abort("again");
+                log.push(`${signal.aborted} ${signal.reason.name} ${any.reason === signal.reason}`);
+                try {
+                    signal.throwIfAborted();
+                } catch (e) {
+                    log.push(`threw ${e.name}`);
+                }
+                log.push(String(AbortSignal.abort("why").reason));
+                try {
+                    new AbortSignal();
+                } catch (e) {
+                    log.push(e.constructor.name);
+                }
+                return log;
             }
+        "#,
+        )
+        .build()
+        .unwrap();
 
-            export function payloadError() {
-                const error = new Error("ignored");
-                error.payload = 7;
-                throw error;
+    let expected = [
+        "onabort abort",
+        "once",
+        "true AbortError true",
+        "threw AbortError",
+        "why",
+        "TypeError",
+    ]
+    .map(|s| Val::String(s.into()));
+    assert_eq!(inst.call1("events", &[]), Val::List(expected.into()));
+}
+
+#[test]
+fn test_events_polyfill() {
+    let mut inst = TestCase::new()
+        .wit(
+            r#"
+            package test:events;
+            world events {
+                export dispatch: func() -> list<string>;
             }
+        "#,
+        )
+        .script(
+            r#"
+            class Emitter extends EventTarget {}
 
-            export function plainErrorTraps() {
-                throw new Error("not a u32 payload");
+            export function dispatch() {
+                const log = [];
+                const target = new Emitter();
+                const controller = new AbortController();
+                target.addEventListener("ping", (e) => log.push(`fn ${e.detail} ${e.target === target}`));
+                target.addEventListener("ping", { handleEvent: (e) => e.preventDefault() });
+                target.addEventListener("ping", () => log.push("once"), { once: true });
+                target.addEventListener("ping", () => log.push("aborted"), { signal: controller.signal });
+                controller.abort();
+
+                const event = new CustomEvent("ping", { detail: 7, cancelable: true });
+                log.push(String(target.dispatchEvent(event)));
+                log.push(`${event.defaultPrevented} ${event.eventPhase} ${event.currentTarget}`);
+                log.push(String(target.dispatchEvent(new Event("ping"))));
+
+                target.addEventListener("stop", (e) => e.stopImmediatePropagation());
+                target.addEventListener("stop", () => log.push("skipped"));
+                target.dispatchEvent(new Event("stop"));
+                log.push(String(controller.signal instanceof EventTarget));
+                return log;
             }
-        "#;
+        "#,
+        )
+        .build()
+        .unwrap();
 
-    let build = || TestCase::new().wit(WIT).script(SCRIPT).build().unwrap();
+    let expected = [
+        "fn 7 true",
+        "once",
+        "false",
+        "true 0 null",
+        "fn undefined true",
+        "true",
+        "true",
+    ]
+    .map(|s| Val::String(s.into()));
+    assert_eq!(inst.call1("dispatch", &[]), Val::List(expected.into()));
+}
+
+#[test]
+fn test_math_random_sources() {
+    let wit = "package test:random;\nworld random { export rolls: func() -> list<f64>; }";
+    let script =
+        "export function rolls() { return [Math.random(), Math.random(), Math.random()]; }";
+
+    // Roll in two fresh instances of the same component.
+    let rolls = |source| {
+        let wasm = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(
+                TestCase::new()
+                    .wit(wit)
+                    .script(script)
+                    .math_random(source)
+                    .componentize(),
+            )
+            .unwrap();
+        [wasm.clone(), wasm].map(|wasm| {
+            let mut inst = ComponentInstance::from_wasm(wasm, vec![], vec![]).unwrap();
+            let Val::List(values) = inst.call1("rolls", &[]) else {
+                panic!("expected a list");
+            };
+            values
+                .into_iter()
+                .map(|v| match v {
+                    Val::Float64(x) => {
+                        assert!((0.0..1.0).contains(&x), "{x} is out of range");
+                        x
+                    }
+                    other => panic!("expected f64, got {other:?}"),
+                })
+                .collect::<Vec<_>>()
+        })
+    };
+
+    let [first, second] = rolls(MathRandom::Seeded(42));
+    assert_eq!(first, second, "a seed should replay in every instance");
+    assert_ne!(first[0], first[1]);
+    assert_eq!(rolls(MathRandom::Seeded(42))[0], first);
+    assert_ne!(rolls(MathRandom::Seeded(7))[0], first);
+
+    let [first, second] = rolls(MathRandom::Wasi);
+    assert_ne!(first, second, "wasi:random should differ between instances");
+
+    let err = TestCase::new()
+        .wit(wit)
+        .script(script)
+        .stub_wasi()
+        .math_random(MathRandom::Wasi)
+        .build()
+        .expect_err("wasi:random needs WASI");
+    assert!(err.to_string().contains("stub_wasi"), "got {err}");
 
-    let mut inst = build();
-    assert_eq!(
-        inst.call1("inline-string-error", &[]),
-        Val::Result(Err(Some(Box::new(Val::String(
-            "inline string error".into()
-        )))))
-    );
-    assert_eq!(
-        inst.call1("alias-string-error", &[]),
-        Val::Result(Err(Some(Box::new(Val::String(
-            "alias string error".into()
-        )))))
-    );
     assert_eq!(
-        inst.call1("payload-error", &[]),
-        Val::Result(Err(Some(Box::new(Val::U32(7)))))
+        "seed:42".parse::<MathRandom>().unwrap(),
+        MathRandom::Seeded(42)
     );
-
-    let mut inst = build();
-    let (instance, store) = inst.parts();
-    let func = instance
-        .get_func(&mut *store, "plain-error-traps")
-        .expect("plain-error-traps export not found");
-    let mut results = [Val::Bool(false)];
-    func.call(&mut *store, &[], &mut results)
-        .expect_err("ordinary Error should trap for non-string result errors");
+    assert!("seed:-1".parse::<MathRandom>().is_err());
 }
 
 #[test]
-fn test_enum_type() {
-    // Enums are represented as their case-name strings in JS
-    TestCase::new()
+fn test_camel_case_collisions() {
+    let err = TestCase::new()
         .wit(
             r#"
-            package test:enums;
-            world enums {
-                enum color { red, green, blue }
-                export identify-color: func(c: color) -> string;
-                export favorite-color: func() -> color;
+            package test:collide;
+            interface api {
+                record point { id: u32, ID: u32 }
+                enum level { low, high, HIGH }
+                resource conn {
+                    get-id: func() -> u32;
+                    get-ID: func() -> u32;
+                }
+                check: func(p: point, l: level);
+            }
+            world collide {
+                import api;
+                export get-id: func() -> u32;
+                export get-ID: func() -> u32;
+                export get-other: func() -> u32;
             }
         "#,
         )
         .script(
             r#"
-            export function identifyColor(c) {
-                if (c === "red") return "is red";
-                if (c === "green") return "is green";
-                if (c === "blue") return "is blue";
-                return "unknown";
-            }
-            export function favoriteColor() { return "green"; }
+            export function getId() { return 2; }
+            export function getOther() { return 0; }
         "#,
         )
-        .expect_call(
-            "identify-color",
-            vec![Val::Enum("red".into())],
-            Val::String("is red".into()),
-        )
-        .expect_call(
-            "identify-color",
-            vec![Val::Enum("blue".into())],
-            Val::String("is blue".into()),
-        )
-        .expect_call("favorite-color", vec![], Val::Enum("green".into()))
         .build()
-        .unwrap()
-        .run();
+        .expect_err("colliding names should fail to componentize");
+
+    let msg = format!("{err:#}");
+    for expected in [
+        "world exports: `get-id`, `get-ID` all map to `getId`",
+        "methods of resource `conn` in imported interface `test:collide/api`: \
+         `get-id`, `get-ID` all map to `getId`",
+        "fields of record `point`: `id`, `ID` all map to `id`",
+        "constants of enum `level`: `high`, `HIGH` all map to `High`",
+    ] {
+        assert!(msg.contains(expected), "missing `{expected}` in:\n{msg}");
+    }
+    assert!(!msg.contains("getOther"), "unexpected collision in:\n{msg}");
 }
 
 #[test]
-fn test_variant_type() {
-    // Variants are { tag: case-name, val } objects in JS
+fn test_stub_wasi() {
     TestCase::new()
         .wit(
             r#"
-            package test:variants;
-            world variants {
-                variant shape { circle(f64), none }
-                export describe-shape: func(s: shape) -> string;
-                export make-circle: func(r: f64) -> shape;
+            package test:hello;
+            world hello {
+                export greet: func(name: string) -> string;
+                export add: func(a: u32, b: u32) -> u32;
             }
         "#,
         )
         .script(
             r#"
-            export function describeShape(s) {
-                if (s.tag === "circle") return "circle with radius " + s.val;
-                if (s.tag === "none") return "no shape";
-                return "unknown";
-            }
-            export function makeCircle(r) { return { tag: "circle", val: r }; }
+            export function greet(name) { return "Hello, " + name + "!"; }
+            export function add(a, b) { return a + b; }
         "#,
         )
+        .stub_wasi()
         .expect_call(
-            "describe-shape",
-            vec![Val::Variant(
-                "circle".into(),
-                Some(Box::new(Val::Float64(3.5))),
-            )],
-            Val::String("circle with radius 3.5".into()),
-        )
-        .expect_call(
-            "describe-shape",
-            vec![Val::Variant("none".into(), None)],
-            Val::String("no shape".into()),
-        )
-        .expect_call(
-            "make-circle",
-            vec![Val::Float64(2.0)],
-            Val::Variant("circle".into(), Some(Box::new(Val::Float64(2.0)))),
+            "greet",
+            vec![Val::String("World".into())],
+            Val::String("Hello, World!".into()),
         )
+        .expect_call("add", vec![Val::U32(2), Val::U32(3)], Val::U32(5))
         .build()
         .unwrap()
         .run();
 }
 
 #[test]
-fn test_flag_type() {
-    // Flags are represented as { name: boolean } objects in JS
+fn test_all_integer_types() {
     TestCase::new()
         .wit(
             r#"
-            package test:flagtest;
-            world flag-test {
-                flags permissions { read, write, execute }
-                export check-read: func(p: permissions) -> bool;
-                export read-write: func() -> permissions;
+            package test:integers;
+            world integers {
+                export add-u8: func(a: u8, b: u8) -> u8;
+                export add-s8: func(a: s8, b: s8) -> s8;
+                export add-u16: func(a: u16, b: u16) -> u16;
+                export add-s16: func(a: s16, b: s16) -> s16;
+                export add-u64: func(a: u64, b: u64) -> u64;
+                export add-s64: func(a: s64, b: s64) -> s64;
             }
         "#,
         )
         .script(
-            "export function checkRead(p) { return p.read === true; }\nexport function readWrite() { return { read: true, write: true }; }",
+            r#"
+            export function addU8(a, b) { return a + b; }
+            export function addS8(a, b) { return a + b; }
+            export function addU16(a, b) { return a + b; }
+            export function addS16(a, b) { return a + b; }
+            export function addU64(a, b) { return a + b; }
+            export function addS64(a, b) { return a + b; }
+        "#,
         )
+        .expect_call("add-u8", vec![Val::U8(200), Val::U8(55)], Val::U8(255))
+        .expect_call("add-s8", vec![Val::S8(-100), Val::S8(50)], Val::S8(-50))
         .expect_call(
-            "check-read",
-            vec![Val::Flags(vec!["read".into(), "write".into()])],
-            Val::Bool(true),
+            "add-u16",
+            vec![Val::U16(60000), Val::U16(5535)],
+            Val::U16(65535),
         )
         .expect_call(
-            "check-read",
-            vec![Val::Flags(vec!["execute".into()])],
-            Val::Bool(false),
+            "add-s16",
+            vec![Val::S16(-30000), Val::S16(10000)],
+            Val::S16(-20000),
         )
         .expect_call(
-            "read-write",
-            vec![],
-            Val::Flags(vec!["read".into(), "write".into()]),
+            "add-u64",
+            vec![Val::U64(1_000_000_000), Val::U64(2_000_000_000)],
+            Val::U64(3_000_000_000),
+        )
+        .expect_call(
+            "add-s64",
+            vec![Val::S64(-1_000_000_000), Val::S64(500_000_000)],
+            Val::S64(-500_000_000),
         )
         .build()
         .unwrap()
@@ -605,179 +1168,205 @@ fn test_flag_type() {
 }
 
 #[test]
-fn test_tuple_return() {
-    TestCase::new()
+fn test_integer_range_return_value() {
+    let mut inst = TestCase::new()
         .wit(
             r#"
-            package test:tuples;
-            world tuples {
-                export swap: func(a: u32, b: u32) -> tuple<u32, u32>;
+            package test:ranges;
+            world ranges {
+                export narrow: func(n: s32) -> result<u8, string>;
+                export wrapped: func() -> s8;
             }
         "#,
         )
-        .script("export function swap(a, b) { return [b, a]; }")
+        .script(
+            r#"
+            export function narrow(n) { return n; }
+            export function wrapped() { return 200; }
+        "#,
+        )
         .expect_call(
-            "swap",
-            vec![Val::U32(1), Val::U32(2)],
-            Val::Tuple(vec![Val::U32(2), Val::U32(1)]),
+            "narrow",
+            vec![Val::S32(255)],
+            Val::Result(Ok(Some(Box::new(Val::U8(255))))),
+        )
+        .expect_call(
+            "narrow",
+            vec![Val::S32(-1)],
+            Val::Result(Err(Some(Box::new(Val::String(
+                "return value: expected u8, got -1, which is out of range (0 to 255)".into(),
+            ))))),
         )
         .build()
-        .unwrap()
-        .run();
-}
+        .unwrap();
+    inst.run();
 
-#[test]
-fn test_many_arguments() {
-    let params: Vec<Val> = (1..=10).map(Val::U32).collect();
+    let (instance, store) = inst.parts();
+    let func = instance.get_func(&mut *store, "wrapped").unwrap();
+    let mut results = [Val::Bool(false)];
+    func.call(&mut *store, &[], &mut results)
+        .expect_err("an out-of-range return value traps");
 
     TestCase::new()
-        .wit(r#"
-            package test:manyargs;
-            world many-args {
-                export sum-ten: func(a1: u32, a2: u32, a3: u32, a4: u32, a5: u32, a6: u32, a7: u32, a8: u32, a9: u32, a10: u32) -> u32;
-            }
-        "#)
-        .script(r#"
-            export function sumTen(a1, a2, a3, a4, a5, a6, a7, a8, a9, a10) {
-                return a1 + a2 + a3 + a4 + a5 + a6 + a7 + a8 + a9 + a10;
-            }
-        "#)
-        .expect_call("sum-ten", params, Val::U32(55))
-        .build().unwrap()
+        .wit("package test:ranges;\nworld ranges { export wrapped: func() -> s8; }")
+        .wrap_integers()
+        .script("export function wrapped() { return 200; }")
+        .expect_call("wrapped", vec![], Val::S8(-56))
+        .build()
+        .unwrap()
         .run();
 }
 
 #[test]
-fn test_no_arg_functions() {
+fn test_big_int_integers() {
     TestCase::new()
         .wit(
             r#"
-            package test:noargs;
-            world noargs {
-                export get-answer: func() -> u32;
-                export get-message: func() -> string;
-                export get-flag: func() -> bool;
+            package test:big-ints;
+            world big-ints {
+                export max-u32: func() -> u32;
+                export max-u64: func() -> u64;
+                export min-s64: func() -> s64;
+                export add-u64: func(a: u64, b: u64) -> u64;
+                export list-u64: func() -> list<u64>;
             }
         "#,
         )
         .script(
             r#"
-            export function getAnswer() { return 42; }
-            export function getMessage() { return "hello"; }
-            export function getFlag() { return true; }
+            export function maxU32() { return 0xffffffffn; }
+            export function maxU64() { return 2n ** 64n - 1n; }
+            export function minS64() { return -(2n ** 63n); }
+            export function addU64(a, b) { return BigInt(a) + BigInt(b); }
+            export function listU64() { return [1n, 2, 2n ** 53n + 1n]; }
         "#,
         )
-        .expect_call("get-answer", vec![], Val::U32(42))
-        .expect_call("get-message", vec![], Val::String("hello".into()))
-        .expect_call("get-flag", vec![], Val::Bool(true))
+        .expect_call("max-u32", vec![], Val::U32(u32::MAX))
+        .expect_call("max-u64", vec![], Val::U64(u64::MAX))
+        .expect_call("min-s64", vec![], Val::S64(i64::MIN))
+        .expect_call(
+            "add-u64",
+            vec![Val::U64(1_000_000_000), Val::U64(2_000_000_000)],
+            Val::U64(3_000_000_000),
+        )
+        .expect_call(
+            "list-u64",
+            vec![],
+            Val::List(vec![Val::U64(1), Val::U64(2), Val::U64((1 << 53) + 1)]),
+        )
         .build()
         .unwrap()
         .run();
-}
 
-#[test]
-fn test_nested_lists() {
-    let nested = Val::List(vec![
-        Val::List(vec![Val::U32(1), Val::U32(2)]),
-        Val::List(vec![Val::U32(3), Val::U32(4)]),
-        Val::List(vec![Val::U32(5)]),
-    ]);
-    let expected = Val::List(vec![
-        Val::U32(1),
-        Val::U32(2),
-        Val::U32(3),
-        Val::U32(4),
-        Val::U32(5),
-    ]);
+    let mut inst = TestCase::new()
+        .wit("package test:big-ints;\nworld big-ints { export too-big: func() -> u32; }")
+        .script("export function tooBig() { return 2n ** 32n; }")
+        .build()
+        .unwrap();
+    let (instance, store) = inst.parts();
+    let func = instance.get_func(&mut *store, "too-big").unwrap();
+    let mut results = [Val::Bool(false)];
+    func.call(&mut *store, &[], &mut results)
+        .expect_err("an out-of-range BigInt is not a u32");
+
+    TestCase::new()
+        .wit("package test:big-ints;\nworld big-ints { export too-big: func() -> result<u64, string>; }")
+        .script("export function tooBig() { return 2n ** 64n; }")
+        .expect_call(
+            "too-big",
+            vec![],
+            Val::Result(Err(Some(Box::new(Val::String(
+                "return value: expected u64, got BigInt 18446744073709551616, which is out of range (0 to 18446744073709551615)".into(),
+            ))))),
+        )
+        .build()
+        .unwrap()
+        .run();
 
+    // Without wrapping, 64-bit BigInts are range checked by value, so ones
+    // past 2^128 aren't reduced into range first.
     TestCase::new()
         .wit(
             r#"
-            package test:nested;
-            world nested-lists {
-                export flatten: func(nested: list<list<u32>>) -> list<u32>;
+            package test:big-ints;
+            world big-ints {
+                export huge-u64: func() -> result<u64, string>;
+                export too-big-s64: func() -> result<s64, string>;
+                export too-small-s64: func() -> result<s64, string>;
             }
         "#,
         )
         .script(
-            "export function flatten(nested) { return nested.reduce((acc, arr) => acc.concat(arr), []); }",
+            r#"
+            export function hugeU64() { return 2n ** 128n; }
+            export function tooBigS64() { return 2n ** 63n; }
+            export function tooSmallS64() { return -(2n ** 63n) - 1n; }
+        "#,
+        )
+        .expect_call(
+            "huge-u64",
+            vec![],
+            Val::Result(Err(Some(Box::new(Val::String(
+                "return value: expected u64, got BigInt 340282366920938463463374607431768211456, which is out of range (0 to 18446744073709551615)".into(),
+            ))))),
+        )
+        .expect_call(
+            "too-big-s64",
+            vec![],
+            Val::Result(Err(Some(Box::new(Val::String(
+                "return value: expected s64, got BigInt 9223372036854775808, which is out of range (-9223372036854775808 to 9223372036854775807)".into(),
+            ))))),
+        )
+        .expect_call(
+            "too-small-s64",
+            vec![],
+            Val::Result(Err(Some(Box::new(Val::String(
+                "return value: expected s64, got BigInt -9223372036854775809, which is out of range (-9223372036854775808 to 9223372036854775807)".into(),
+            ))))),
         )
-        .expect_call("flatten", vec![nested], expected)
         .build()
         .unwrap()
         .run();
-}
-
-#[test]
-fn test_complex_record() {
-    let alice = Val::Record(vec![
-        ("name".into(), Val::String("Alice".into())),
-        ("age".into(), Val::U32(30)),
-        ("active".into(), Val::Bool(true)),
-    ]);
-    let bob = Val::Record(vec![
-        ("name".into(), Val::String("Bob".into())),
-        ("age".into(), Val::U32(25)),
-        ("active".into(), Val::Bool(true)),
-    ]);
 
-    TestCase::new()
-        .wit(r#"
-            package test:complex;
-            world complex-record {
-                record person { name: string, age: u32, active: bool }
-                export greet-person: func(p: person) -> string;
-                export make-person: func(name: string, age: u32) -> person;
-            }
-        "#)
-        .script(r#"
-            export function greetPerson(p) { return "Hello " + p.name + ", age " + p.age + ", active: " + p.active; }
-            export function makePerson(name, age) { return { name: name, age: age, active: true }; }
-        "#)
-        .expect_call("greet-person", vec![alice], Val::String("Hello Alice, age 30, active: true".into()))
-        .expect_call("make-person", vec![Val::String("Bob".into()), Val::U32(25)], bob)
-        .build().unwrap()
-        .run();
-}
+    let mut inst = TestCase::new()
+        .wit("package test:big-ints;\nworld big-ints { export nested: func() -> list<s64>; }")
+        .script("export function nested() { return [1n, 2n ** 128n - 1n]; }")
+        .build()
+        .unwrap();
+    let (instance, store) = inst.parts();
+    let func = instance.get_func(&mut *store, "nested").unwrap();
+    let mut results = [Val::Bool(false)];
+    func.call(&mut *store, &[], &mut results)
+        .expect_err("a nested out-of-range BigInt is not an s64");
 
-#[test]
-fn test_list_of_strings() {
     TestCase::new()
         .wit(
             r#"
-            package test:stringlists;
-            world string-lists {
-                export join-strings: func(parts: list<string>, sep: string) -> string;
-                export count-strings: func(parts: list<string>) -> u32;
+            package test:big-ints;
+            world big-ints {
+                export wrap-u32: func() -> u32;
+                export wrap-u64: func() -> u64;
+                export wrap-s64: func() -> s64;
+                export wrap-nested: func() -> list<s64>;
             }
         "#,
         )
+        .wrap_integers()
         .script(
             r#"
-            export function joinStrings(parts, sep) { return parts.join(sep); }
-            export function countStrings(parts) { return parts.length; }
+            export function wrapU32() { return 2n ** 32n + 1n; }
+            export function wrapU64() { return 2n ** 200n + 7n; }
+            export function wrapS64() { return 2n ** 63n; }
+            export function wrapNested() { return [1n, 2n ** 128n - 1n, -(2n ** 63n) - 1n]; }
         "#,
         )
+        .expect_call("wrap-u32", vec![], Val::U32(1))
+        .expect_call("wrap-u64", vec![], Val::U64(7))
+        .expect_call("wrap-s64", vec![], Val::S64(i64::MIN))
         .expect_call(
-            "join-strings",
-            vec![
-                Val::List(vec![
-                    Val::String("a".into()),
-                    Val::String("b".into()),
-                    Val::String("c".into()),
-                ]),
-                Val::String("-".into()),
-            ],
-            Val::String("a-b-c".into()),
-        )
-        .expect_call(
-            "count-strings",
-            vec![Val::List(vec![
-                Val::String("one".into()),
-                Val::String("two".into()),
-                Val::String("three".into()),
-            ])],
-            Val::U32(3),
+            "wrap-nested",
+            vec![],
+            Val::List(vec![Val::S64(1), Val::S64(-1), Val::S64(i64::MAX)]),
         )
         .build()
         .unwrap()
@@ -785,167 +1374,236 @@ fn test_list_of_strings() {
 }
 
 #[test]
-fn test_empty_world() {
+fn test_float_types() {
     TestCase::new()
         .wit(
             r#"
-            package test:empty;
-            world empty {}
+            package test:floats;
+            world floats {
+                export add-f32: func(a: f32, b: f32) -> f32;
+                export add-f64: func(a: f64, b: f64) -> f64;
+            }
         "#,
         )
-        .script("// empty module\n")
+        .script("export function addF32(a, b) { return a + b; }\nexport function addF64(a, b) { return a + b; }")
+        .expect_call(
+            "add-f32",
+            vec![Val::Float32(1.5), Val::Float32(2.5)],
+            Val::Float32(4.0),
+        )
+        .expect_call(
+            "add-f64",
+            vec![Val::Float64(1.5), Val::Float64(2.5)],
+            Val::Float64(4.0),
+        )
         .build()
-        .unwrap();
+        .unwrap()
+        .run();
 }
 
 #[test]
-fn test_naming_conventions() {
-    // WIT kebab-case becomes camelCase in JS
-    let rec = Val::Record(vec![
-        ("first-name".into(), Val::String("John".into())),
-        ("last-name".into(), Val::String("Doe".into())),
-    ]);
+fn test_float_policy() {
+    let wit = r#"
+        package test:floats;
+        world floats {
+            export nan-payload: func() -> f64;
+            export nan-list: func() -> list<f32>;
+            export ratio: func(a: f64, b: f64) -> result<f64, string>;
+            export scaled: func() -> result<list<f32>, string>;
+        }
+    "#;
+    let script = r#"
+        // A NaN with a non-canonical payload
+        const bits = new Float64Array(new BigUint64Array([0x7ff8_0000_dead_beefn]).buffer);
+        export function nanPayload() { return bits[0]; }
+        export function nanList() {
+            const list = new Float32Array(new Uint32Array([0x7fc0_beef, 0x3f80_0000]).buffer);
+            return list;
+        }
+        export function ratio(a, b) { return a / b; }
+        export function scaled() { return new Float32Array([1, Infinity]); }
+    "#;
 
-    TestCase::new()
-        .wit(
-            r#"
-            package test:conventions;
-            world conventions {
-                record my-record { first-name: string, last-name: string }
-                export get-full-name: func(r: my-record) -> string;
+    // NaNs are always canonicalized
+    let mut inst = TestCase::new().wit(wit).script(script).build().unwrap();
+    match inst.call1("nan-payload", &[]) {
+        Val::Float64(n) => assert_eq!(n.to_bits(), f64::NAN.to_bits()),
+        other => panic!("Expected f64, got: {:?}", other),
+    }
+    match inst.call1("nan-list", &[]) {
+        Val::List(items) => match items.as_slice() {
+            [Val::Float32(nan), Val::Float32(one)] => {
+                assert_eq!(nan.to_bits(), f32::NAN.to_bits());
+                assert_eq!(*one, 1.0);
             }
-        "#,
+            other => panic!("Expected two f32s, got: {:?}", other),
+        },
+        other => panic!("Expected list, got: {:?}", other),
+    }
+
+    TestCase::new()
+        .wit(wit)
+        .finite_floats()
+        .script(script)
+        .expect_call(
+            "ratio",
+            vec![Val::Float64(1.0), Val::Float64(4.0)],
+            Val::Result(Ok(Some(Box::new(Val::Float64(0.25))))),
+        )
+        .expect_call(
+            "ratio",
+            vec![Val::Float64(1.0), Val::Float64(0.0)],
+            Val::Result(Err(Some(Box::new(Val::String(
+                "return value: expected a finite float, got Infinity".into(),
+            ))))),
+        )
+        .expect_call(
+            "ratio",
+            vec![Val::Float64(0.0), Val::Float64(0.0)],
+            Val::Result(Err(Some(Box::new(Val::String(
+                "return value: expected a finite float, got NaN".into(),
+            ))))),
+        )
+        .expect_call(
+            "scaled",
+            vec![],
+            Val::Result(Err(Some(Box::new(Val::String(
+                "return value[1]: expected a finite float, got Infinity".into(),
+            ))))),
         )
-        .script(r#"export function getFullName(r) { return r.firstName + " " + r.lastName; }"#)
-        .expect_call("get-full-name", vec![rec], Val::String("John Doe".into()))
         .build()
         .unwrap()
         .run();
 }
 
 #[test]
-fn test_repeated_calls() {
-    let mut inst = TestCase::new()
+fn test_string_operations() {
+    TestCase::new()
         .wit(
             r#"
-            package test:repeated;
-            world repeated {
-                export hello: func() -> string;
-            }
+            package test:strings;
+            world strings {
+                export take-string: func(s: string) -> u32;
+                export return-string: func() -> string;
+                export concat-strings: func(a: string, b: string) -> string;
+            }
         "#,
         )
-        .script(r#"export function hello() { return "hello"; }"#)
+        .script(
+            r#"
+            export function takeString(s) { return s.length; }
+            export function returnString() { return "hello from js"; }
+            export function concatStrings(a, b) { return a + b; }
+        "#,
+        )
+        .expect_call(
+            "take-string",
+            vec![Val::String("hello".into())],
+            Val::U32(5),
+        )
+        .expect_call("return-string", vec![], Val::String("hello from js".into()))
+        .expect_call(
+            "concat-strings",
+            vec![Val::String("foo".into()), Val::String("bar".into())],
+            Val::String("foobar".into()),
+        )
         .build()
-        .unwrap();
-
-    for _ in 0..5 {
-        assert_eq!(inst.call1("hello", &[]), Val::String("hello".into()));
-    }
+        .unwrap()
+        .run();
 }
 
 #[test]
-fn test_deeply_nested_lists() {
-    // 3 levels: list<list<list<u32>>>
-    let input = Val::List(vec![
-        Val::List(vec![
-            Val::List(vec![Val::U32(1), Val::U32(2)]),
-            Val::List(vec![Val::U32(3)]),
-        ]),
-        Val::List(vec![Val::List(vec![Val::U32(4), Val::U32(5), Val::U32(6)])]),
-    ]);
-
+fn test_char_type() {
     TestCase::new()
         .wit(
             r#"
-            package test:deep-nesting;
-            world deep-nesting {
-                export deep-flatten: func(nested: list<list<list<u32>>>) -> list<u32>;
+            package test:chars;
+            world chars {
+                export take-char: func(c: char) -> u32;
+                export char-length: func(c: char) -> u32;
+                export return-char: func() -> char;
+                export return-astral: func() -> char;
+                export echo: func(c: list<char>) -> list<char>;
             }
         "#,
         )
         .script(
             r#"
-            export function deepFlatten(nested) {
-                let result = [];
-                for (const mid of nested) {
-                    for (const inner of mid) {
-                        for (const v of inner) {
-                            result.push(v);
-                        }
-                    }
-                }
-                return result;
-            }
+            export function takeChar(c) { return c.codePointAt(0); }
+            export function charLength(c) { return c.length; }
+            export function returnChar() { return "A"; }
+            export function returnAstral() { return String.fromCodePoint(0x1f600); }
+            export function echo(chars) { return chars; }
         "#,
         )
+        .expect_call("take-char", vec![Val::Char('A')], Val::U32(65))
+        .expect_call("return-char", vec![], Val::Char('A'))
+        // Characters beyond the BMP are a surrogate pair in JavaScript.
+        .expect_call("take-char", vec![Val::Char('😀')], Val::U32(0x1f600))
+        .expect_call("char-length", vec![Val::Char('😀')], Val::U32(2))
+        .expect_call("return-astral", vec![], Val::Char('😀'))
         .expect_call(
-            "deep-flatten",
-            vec![input],
-            Val::List(vec![
-                Val::U32(1),
-                Val::U32(2),
-                Val::U32(3),
-                Val::U32(4),
-                Val::U32(5),
-                Val::U32(6),
-            ]),
+            "echo",
+            vec![Val::List(vec![Val::Char('a'), Val::Char('𝄞')])],
+            Val::List(vec![Val::Char('a'), Val::Char('𝄞')]),
         )
         .build()
         .unwrap()
         .run();
+
+    let mut inst = TestCase::new()
+        .wit("package test:chars;\nworld chars { export two: func() -> char; }")
+        .script(r#"export function two() { return "ab"; }"#)
+        .build()
+        .unwrap();
+    let (instance, store) = inst.parts();
+    let func = instance.get_func(&mut *store, "two").unwrap();
+    let mut results = [Val::Bool(false)];
+    func.call(&mut *store, &[], &mut results)
+        .expect_err("a two-character string is not a char");
 }
 
 #[test]
-fn test_nested_option() {
-    // option<option<u32>> is wrapped as { tag: "some"|"none", val } so that
-    // none, some(none), and some(some(v)) stay distinct.
+fn test_char_coercion() {
+    let err = |message: &str| Val::Result(Err(Some(Box::new(Val::String(message.into())))));
+    let ok = |c: char| Val::Result(Ok(Some(Box::new(Val::Char(c)))));
     TestCase::new()
         .wit(
             r#"
-            package test:nested-option;
-            world nested-option {
-                export unwrap-nested: func(val: option<option<u32>>) -> u32;
-                export identity: func(val: option<option<u32>>) -> option<option<u32>>;
+            package test:chars;
+            world chars {
+                export from-string: func(s: string) -> result<char, string>;
+                export from-code: func(n: f64) -> result<char, string>;
             }
         "#,
         )
         .script(
             r#"
-            export function unwrapNested(val) {
-                if (val.tag === "none") return 0;
-                if (val.val === null || val.val === undefined) return 0;
-                return val.val;
-            }
-            export function identity(val) { return val; }
+            export function fromString(s) { return s; }
+            export function fromCode(n) { return n; }
         "#,
         )
-        .stub_wasi()
+        .expect_call("from-code", vec![Val::Float64(65.0)], ok('A'))
+        .expect_call("from-code", vec![Val::Float64(0x1f600 as f64)], ok('😀'))
         .expect_call(
-            "unwrap-nested",
-            vec![Val::Option(Some(Box::new(Val::Option(Some(Box::new(
-                Val::U32(42),
-            ))))))],
-            Val::U32(42),
+            "from-code",
+            vec![Val::Float64(0xd800 as f64)],
+            err("return value: expected a Unicode scalar value for char, got number 55296"),
         )
-        .expect_call("unwrap-nested", vec![Val::Option(None)], Val::U32(0))
         .expect_call(
-            "unwrap-nested",
-            vec![Val::Option(Some(Box::new(Val::Option(None))))],
-            Val::U32(0),
+            "from-code",
+            vec![Val::Float64(1.5)],
+            err("return value: expected a Unicode scalar value for char, got number 1.5"),
         )
-        // identity must round-trip all three distinct cases unchanged.
-        .expect_call("identity", vec![Val::Option(None)], Val::Option(None))
         .expect_call(
-            "identity",
-            vec![Val::Option(Some(Box::new(Val::Option(None))))],
-            Val::Option(Some(Box::new(Val::Option(None)))),
+            "from-string",
+            vec![Val::String("".into())],
+            err(r#"return value: expected a single code point for char, got string "" (0 code points)"#),
         )
         .expect_call(
-            "identity",
-            vec![Val::Option(Some(Box::new(Val::Option(Some(Box::new(
-                Val::U32(7),
-            ))))))],
-            Val::Option(Some(Box::new(Val::Option(Some(Box::new(Val::U32(7))))))),
+            "from-string",
+            vec![Val::String("ab".into())],
+            err(r#"return value: expected a single code point for char, got string "ab" (2 code points)"#),
         )
         .build()
         .unwrap()
@@ -953,195 +1611,214 @@ fn test_nested_option() {
 }
 
 #[test]
-fn test_list_of_records() {
-    let people = Val::List(vec![
-        Val::Record(vec![
-            ("name".into(), Val::String("Alice".into())),
-            ("score".into(), Val::U32(90)),
-        ]),
-        Val::Record(vec![
-            ("name".into(), Val::String("Bob".into())),
-            ("score".into(), Val::U32(85)),
-        ]),
-    ]);
+fn test_result_throw_error_compatibility() {
+    const WIT: &str = r#"
+            package test:result-errors;
+            world result-errors {
+                type string-err = string;
+                export inline-string-error: func() -> result<_, string>;
+                export alias-string-error: func() -> result<_, string-err>;
+                export payload-error: func() -> result<_, u32>;
+                export plain-error-traps: func() -> result<_, u32>;
+            }
+        "#;
+    const SCRIPT: &str = r#"
+            export function inlineStringError() {
+                throw new Error("inline string error");
+            }
 
-    TestCase::new()
-        .wit(
-            r#"
-            package test:list-records;
-            world list-records {
-                record player { name: string, score: u32 }
-                export total-score: func(players: list<player>) -> u32;
-                export top-player: func(players: list<player>) -> string;
+            export function aliasStringError() {
+                throw new Error("alias string error");
             }
-        "#,
-        )
-        .script(
-            r#"
-            export function totalScore(players) {
-                return players.reduce((sum, p) => sum + p.score, 0);
+
+            export function payloadError() {
+                const error = new Error("ignored");
+                error.payload = 7;
+                throw error;
             }
-            export function topPlayer(players) {
-                let best = players[0];
-                for (const p of players) {
-                    if (p.score > best.score) best = p;
-                }
-                return best.name;
+
+            export function plainErrorTraps() {
+                throw new Error("not a u32 payload");
             }
-        "#,
-        )
-        .expect_call("total-score", vec![people.clone()], Val::U32(175))
-        .expect_call("top-player", vec![people], Val::String("Alice".into()))
-        .build()
-        .unwrap()
-        .run();
+        "#;
+
+    let build = || TestCase::new().wit(WIT).script(SCRIPT).build().unwrap();
+
+    let mut inst = build();
+    assert_eq!(
+        inst.call1("inline-string-error", &[]),
+        Val::Result(Err(Some(Box::new(Val::String(
+            "inline string error".into()
+        )))))
+    );
+    assert_eq!(
+        inst.call1("alias-string-error", &[]),
+        Val::Result(Err(Some(Box::new(Val::String(
+            "alias string error".into()
+        )))))
+    );
+    assert_eq!(
+        inst.call1("payload-error", &[]),
+        Val::Result(Err(Some(Box::new(Val::U32(7)))))
+    );
+
+    let mut inst = build();
+    let (instance, store) = inst.parts();
+    let func = instance
+        .get_func(&mut *store, "plain-error-traps")
+        .expect("plain-error-traps export not found");
+    let mut results = [Val::Bool(false)];
+    func.call(&mut *store, &[], &mut results)
+        .expect_err("ordinary Error should trap for non-string result errors");
 }
 
 #[test]
-fn test_list_of_variants() {
-    // list<variant> round-trip
+fn test_enum_type() {
+    // Enums are represented as their case-name strings in JS
     TestCase::new()
         .wit(
             r#"
-            package test:list-variants;
-            world list-variants {
-                variant item { text(string), number(u32), empty }
-                export count-texts: func(items: list<item>) -> u32;
+            package test:enums;
+            world enums {
+                enum color { red, green, blue }
+                export identify-color: func(c: color) -> string;
+                export favorite-color: func() -> color;
             }
         "#,
         )
         .script(
             r#"
-            export function countTexts(items) {
-                let count = 0;
-                for (const item of items) {
-                    if (item.tag === "text") count++;
-                }
-                return count;
+            export function identifyColor(c) {
+                if (c === "red") return "is red";
+                if (c === "green") return "is green";
+                if (c === "blue") return "is blue";
+                return "unknown";
             }
+            export function favoriteColor() { return "green"; }
         "#,
         )
         .expect_call(
-            "count-texts",
-            vec![Val::List(vec![
-                Val::Variant("text".into(), Some(Box::new(Val::String("hello".into())))),
-                Val::Variant("number".into(), Some(Box::new(Val::U32(42)))),
-                Val::Variant("text".into(), Some(Box::new(Val::String("world".into())))),
-                Val::Variant("empty".into(), None),
-            ])],
-            Val::U32(2),
+            "identify-color",
+            vec![Val::Enum("red".into())],
+            Val::String("is red".into()),
+        )
+        .expect_call(
+            "identify-color",
+            vec![Val::Enum("blue".into())],
+            Val::String("is blue".into()),
         )
+        .expect_call("favorite-color", vec![], Val::Enum("green".into()))
         .build()
         .unwrap()
         .run();
 }
 
 #[test]
-fn test_record_with_nested_fields() {
-    // Record containing option, list, and result fields
-    TestCase::new()
+fn test_enum_and_flags_constants() {
+    // Enum and flags types get constants whichever way they flow: world-level
+    // types on `wit`, interface types under the interface name
+    let mut inst = TestCase::new()
         .wit(
             r#"
-            package test:nested-record;
-            world nested-record {
-                record config {
-                    name: string,
-                    tags: list<string>,
-                    max-retries: option<u32>,
-                }
-                export describe-config: func(c: config) -> string;
-                export make-config: func(name: string) -> config;
+            package test:constants;
+            interface palette {
+                enum shade { light-gray, dark-gray }
+                pick: func(s: shade) -> shade;
+            }
+            world constants {
+                enum color { red, green, blue }
+                flags permissions { read, write-all }
+                export favorite-color: func() -> color;
+                export grant: func() -> permissions;
+                export palette;
             }
         "#,
         )
         .script(
             r#"
-            export function describeConfig(c) {
-                let s = c.name + ": tags=" + c.tags.join(",");
-                if (c.maxRetries !== null && c.maxRetries !== undefined) {
-                    s += " retries=" + c.maxRetries;
-                }
-                return s;
-            }
-            export function makeConfig(name) {
-                return { name: name, tags: ["default"], maxRetries: 3 };
-            }
+            const { Color, Permissions } = wit;
+            export function favoriteColor() { return Color.Green; }
+            export function grant() { return { [Permissions.WriteAll]: true }; }
+            export const palette = {
+                pick(s) {
+                    const { Shade } = wit["test:constants/palette"];
+                    return s === Shade.LightGray ? Shade.DarkGray : s;
+                },
+            };
         "#,
         )
-        .expect_call(
-            "describe-config",
-            vec![Val::Record(vec![
-                ("name".into(), Val::String("test".into())),
-                (
-                    "tags".into(),
-                    Val::List(vec![Val::String("a".into()), Val::String("b".into())]),
-                ),
-                (
-                    "max-retries".into(),
-                    Val::Option(Some(Box::new(Val::U32(5)))),
-                ),
-            ])],
-            Val::String("test: tags=a,b retries=5".into()),
-        )
-        .expect_call(
-            "make-config",
-            vec![Val::String("prod".into())],
-            Val::Record(vec![
-                ("name".into(), Val::String("prod".into())),
-                (
-                    "tags".into(),
-                    Val::List(vec![Val::String("default".into())]),
-                ),
-                (
-                    "max-retries".into(),
-                    Val::Option(Some(Box::new(Val::U32(3)))),
-                ),
-            ]),
-        )
+        .expect_call("favorite-color", vec![], Val::Enum("green".into()))
+        .expect_call("grant", vec![], Val::Flags(vec!["write-all".into()]))
         .build()
-        .unwrap()
-        .run();
+        .unwrap();
+    inst.run();
+
+    let (instance, store) = inst.parts();
+    let iface_idx = instance
+        .get_export_index(&mut *store, None, "test:constants/palette")
+        .expect("interface export not found");
+    let func_idx = instance
+        .get_export_index(&mut *store, Some(&iface_idx), "pick")
+        .expect("pick export not found");
+    let func = instance.get_func(&mut *store, func_idx).unwrap();
+    let mut results = [Val::Bool(false)];
+    func.call(&mut *store, &[Val::Enum("light-gray".into())], &mut results)
+        .unwrap();
+    assert_eq!(results[0], Val::Enum("dark-gray".into()));
 }
 
 #[test]
-fn test_option_of_result() {
+fn test_constants_of_same_named_types() {
+    // Same-named types from different versions of an interface stay apart, and
+    // the ambiguous unversioned name throws instead of picking one
     TestCase::new()
         .wit(
             r#"
-            package test:option-result;
-            world option-result {
-                export process: func(val: option<result<u32, string>>) -> string;
+            package test:versions;
+
+            package test:dep@1.0.0 {
+                interface types {
+                    enum level { low, high }
+                }
+            }
+
+            package test:dep@2.0.0 {
+                interface types {
+                    enum level { off, on }
+                }
+            }
+
+            world versions {
+                import test:dep/types@1.0.0;
+                import test:dep/types@2.0.0;
+                export levels: func() -> string;
             }
         "#,
         )
         .script(
             r#"
-            export function process(val) {
-                if (val === null || val === undefined) return "none";
-                if (val.tag === "ok") return "ok:" + val.val;
-                return "err:" + val.val;
+            export function levels() {
+                const out = [
+                    wit["test:dep/types@1.0.0"].Level.High,
+                    wit["test:dep/types@2.0.0"].Level.On,
+                ];
+                try {
+                    wit["test:dep/types"];
+                } catch (e) {
+                    out.push(e.message);
+                }
+                return out.join(" | ");
             }
         "#,
         )
-        .stub_wasi()
-        .expect_call(
-            "process",
-            vec![Val::Option(Some(Box::new(Val::Result(Ok(Some(
-                Box::new(Val::U32(42)),
-            ))))))],
-            Val::String("ok:42".into()),
-        )
-        .expect_call(
-            "process",
-            vec![Val::Option(Some(Box::new(Val::Result(Err(Some(
-                Box::new(Val::String("fail".into())),
-            ))))))],
-            Val::String("err:fail".into()),
-        )
         .expect_call(
-            "process",
-            vec![Val::Option(None)],
-            Val::String("none".into()),
+            "levels",
+            vec![],
+            Val::String(
+                "high | on | `test:dep/types` is ambiguous between test:dep/types@1.0.0, \
+                 test:dep/types@2.0.0; use a versioned name"
+                    .into(),
+            ),
         )
         .build()
         .unwrap()
@@ -1149,228 +1826,300 @@ fn test_option_of_result() {
 }
 
 #[test]
-fn test_result_of_option() {
+fn test_import_of_ambiguous_version() {
+    // `test:dep/api` and `test:dep/api@1.0` both match two imported versions
+    let case = |specifier: &str| {
+        TestCase::new()
+            .wit(
+                r#"
+                package test:versions;
+
+                package test:dep@1.0.0 {
+                    interface api {
+                        ping: func();
+                    }
+                }
+
+                package test:dep@1.0.1 {
+                    interface api {
+                        ping: func();
+                    }
+                }
+
+                world versions {
+                    import test:dep/api@1.0.0;
+                    import test:dep/api@1.0.1;
+                    export ok: func() -> bool;
+                }
+            "#,
+            )
+            .script(&format!(
+                r#"
+                import api from "{specifier}";
+                export function ok() {{ return typeof api.ping === "function"; }}
+            "#
+            ))
+    };
+
+    for (specifier, aliasing) in [
+        ("test:dep/api", VersionAliasing::StripAll),
+        ("test:dep/api@1.0", VersionAliasing::StripPatch),
+    ] {
+        let err = case(specifier)
+            .version_aliasing(aliasing)
+            .build()
+            .err()
+            .unwrap_or_else(|| panic!("`{specifier}` must be ambiguous"));
+        assert!(
+            format!("{err:#}").contains(&format!(
+                "`{specifier}` is ambiguous between test:dep/api@1.0.0, test:dep/api@1.0.1"
+            )),
+            "{err:#}"
+        );
+    }
+}
+
+#[test]
+fn test_max_list_length_return_value() {
+    // Oversized return values of `result` exports become the `err` case
     TestCase::new()
         .wit(
             r#"
-            package test:result-option;
-            world result-option {
-                export maybe-lookup: func(key: string) -> result<option<u32>, string>;
+            package test:limits;
+            world limits {
+                record page { items: list<string> }
+                export numbers: func(n: u32) -> result<list<u32>, string>;
+                export page: func(n: u32, len: u32) -> result<page, string>;
             }
         "#,
         )
+        .max_list_length(4)
         .script(
             r#"
-            export function maybeLookup(key) {
-                if (key === "found") return 42;
-                if (key === "missing") return null;
-                throw "invalid key";
+            export function numbers(n) { return Array.from({ length: n }, (_, i) => i); }
+            export function page(n, len) {
+                return { items: Array.from({ length: n }, () => "x".repeat(len)) };
             }
         "#,
         )
-        .stub_wasi()
         .expect_call(
-            "maybe-lookup",
-            vec![Val::String("found".into())],
-            Val::Result(Ok(Some(Box::new(Val::Option(Some(Box::new(Val::U32(
-                42,
-            )))))))),
+            "numbers",
+            vec![Val::U32(4)],
+            Val::Result(Ok(Some(Box::new(Val::List(
+                (0..4).map(Val::U32).collect(),
+            ))))),
         )
         .expect_call(
-            "maybe-lookup",
-            vec![Val::String("missing".into())],
-            Val::Result(Ok(Some(Box::new(Val::Option(None))))),
+            "numbers",
+            vec![Val::U32(5)],
+            Val::Result(Err(Some(Box::new(Val::String(
+                "return value: length 5 exceeds the maximum list length of 4".into(),
+            ))))),
         )
         .expect_call(
-            "maybe-lookup",
-            vec![Val::String("error".into())],
-            Val::Result(Err(Some(Box::new(Val::String("invalid key".into()))))),
-        )
-        .build()
-        .unwrap()
-        .run();
-}
-
-#[test]
-fn test_empty_list() {
-    TestCase::new()
-        .wit(
-            r#"
-            package test:empty-list;
-            world empty-list {
-                export count: func(items: list<u32>) -> u32;
-                export make-empty: func() -> list<u32>;
-            }
-        "#,
+            "page",
+            vec![Val::U32(2), Val::U32(9)],
+            Val::Result(Err(Some(Box::new(Val::String(
+                "return value: length 9 exceeds the maximum list length of 4".into(),
+            ))))),
         )
-        .script(
-            r#"
-            export function count(items) { return items.length; }
-            export function makeEmpty() { return []; }
-        "#,
-        )
-        .stub_wasi()
-        .expect_call("count", vec![Val::List(vec![])], Val::U32(0))
-        .expect_call("make-empty", vec![], Val::List(vec![]))
         .build()
         .unwrap()
         .run();
 }
 
 #[test]
-fn test_import_export_chain() {
+fn test_tagged_results() {
     let mut inst = TestCase::new()
         .wit(
             r#"
-            package test:chain;
-            world chain {
-                export process: func(val: u32) -> u32;
+            package test:tagged;
+            world tagged {
+                export parse: func(input: string) -> result<f64, string>;
+                export check: func(ok: bool) -> result;
+                export throws: func() -> result<u32, string>;
             }
         "#,
         )
+        .tagged_results()
         .script(
             r#"
-            export function process(val) {
-                return val + 11;
+            export function parse(input) {
+                const n = Number(input);
+                return Number.isNaN(n) ? { tag: "err", val: "not a number" } : { tag: "ok", val: n };
             }
+            export function check(ok) { return { tag: ok ? "ok" : "err" }; }
+            export function throws() { throw new Error("boom"); }
         "#,
         )
-        .stub_wasi()
-        .expect_call("process", vec![Val::U32(5)], Val::U32(16))
+        .expect_call(
+            "parse",
+            vec![Val::String("1.5".into())],
+            Val::Result(Ok(Some(Box::new(Val::Float64(1.5))))),
+        )
+        .expect_call(
+            "parse",
+            vec![Val::String("x".into())],
+            Val::Result(Err(Some(Box::new(Val::String("not a number".into()))))),
+        )
+        .expect_call("check", vec![Val::Bool(true)], Val::Result(Ok(None)))
+        .expect_call("check", vec![Val::Bool(false)], Val::Result(Err(None)))
         .build()
         .unwrap();
-
     inst.run();
+
+    // Exceptions are not mapped to the `err` case in this mode
+    let (instance, store) = inst.parts();
+    let func = instance.get_func(&mut *store, "throws").unwrap();
+    let mut results = [Val::Bool(false)];
+    func.call(&mut *store, &[], &mut results)
+        .expect_err("a thrown exception traps with tagged results");
 }
 
 #[test]
-fn test_multiple_return_results() {
-    // Test result types with no error payload
+fn test_strict_types_return_value() {
+    // Mistyped return values of `result` exports become the `err` case
+    let err = |message: &str| Val::Result(Err(Some(Box::new(Val::String(message.into())))));
+    let ok = |val: Val| Val::Result(Ok(Some(Box::new(val))));
     TestCase::new()
         .wit(
             r#"
-            package test:result-void;
-            world result-void {
-                export try-op: func(succeed: bool) -> result<u32>;
+            package test:strict;
+            world strict {
+                record point { x: f64, y: f64 }
+                enum color { red, green }
+                export points: func() -> result<list<point>, string>;
+                export color: func(name: string) -> result<color, string>;
+                export small: func(n: s32) -> result<u8, string>;
+                export pair: func() -> result<tuple<string, u32>, string>;
+                export origin: func() -> result<point, string>;
             }
         "#,
         )
+        .strict_types()
         .script(
             r#"
-            export function tryOp(succeed) {
-                if (succeed) return 42;
-                throw undefined;
-            }
+            export function points() { return [{ x: 1, y: 2 }, { x: "1", y: 2 }]; }
+            export function color(name) { return name; }
+            export function small(n) { return n; }
+            export function pair() { return ["a"]; }
+            export function origin() { return { x: 0, y: 0, z: 0 }; }
         "#,
         )
-        .stub_wasi()
         .expect_call(
-            "try-op",
-            vec![Val::Bool(true)],
-            Val::Result(Ok(Some(Box::new(Val::U32(42))))),
+            "points",
+            vec![],
+            err(r#"return value[1].x: expected f64, got string "1""#),
+        )
+        .expect_call(
+            "color",
+            vec![Val::String("green".into())],
+            ok(Val::Enum("green".into())),
+        )
+        .expect_call(
+            "color",
+            vec![Val::String("blue".into())],
+            err(r#"return value: expected one of "red", "green", got string "blue""#),
+        )
+        .expect_call("small", vec![Val::S32(255)], ok(Val::U8(255)))
+        .expect_call(
+            "small",
+            vec![Val::S32(256)],
+            err("return value: expected u8, got 256, which is out of range (0 to 255)"),
+        )
+        .expect_call(
+            "pair",
+            vec![],
+            err("return value: expected tuple of 2, got array of 1"),
+        )
+        .expect_call(
+            "origin",
+            vec![],
+            err(r#"return value: unexpected field "z", expected one of "x", "y""#),
         )
-        .expect_call("try-op", vec![Val::Bool(false)], Val::Result(Err(None)))
         .build()
         .unwrap()
         .run();
 }
 
 #[test]
-fn test_variant_with_multiple_payload_types() {
-    // Variant with different payload types including string and none
+fn test_variant_type() {
+    // Variants are { tag: case-name, val } objects in JS
     TestCase::new()
         .wit(
             r#"
-            package test:multi-variant;
-            world multi-variant {
-                variant value { integer(s32), text(string), flag(bool), nothing }
-                export stringify: func(v: value) -> string;
-                export make-text: func(s: string) -> value;
-                export make-nothing: func() -> value;
+            package test:variants;
+            world variants {
+                variant shape { circle(f64), none }
+                export describe-shape: func(s: shape) -> string;
+                export make-circle: func(r: f64) -> shape;
             }
         "#,
         )
         .script(
             r#"
-            export function stringify(v) {
-                if (v.tag === "integer") return "int:" + v.val;
-                if (v.tag === "text") return "text:" + v.val;
-                if (v.tag === "flag") return "flag:" + v.val;
-                return "nothing";
+            export function describeShape(s) {
+                if (s.tag === "circle") return "circle with radius " + s.val;
+                if (s.tag === "none") return "no shape";
+                return "unknown";
             }
-            export function makeText(s) { return { tag: "text", val: s }; }
-            export function makeNothing() { return { tag: "nothing" }; }
+            export function makeCircle(r) { return { tag: "circle", val: r }; }
         "#,
         )
-        .stub_wasi()
-        .expect_call(
-            "stringify",
-            vec![Val::Variant(
-                "integer".into(),
-                Some(Box::new(Val::S32(-42))),
-            )],
-            Val::String("int:-42".into()),
-        )
         .expect_call(
-            "stringify",
+            "describe-shape",
             vec![Val::Variant(
-                "text".into(),
-                Some(Box::new(Val::String("hello".into()))),
+                "circle".into(),
+                Some(Box::new(Val::Float64(3.5))),
             )],
-            Val::String("text:hello".into()),
+            Val::String("circle with radius 3.5".into()),
         )
         .expect_call(
-            "stringify",
-            vec![Val::Variant("nothing".into(), None)],
-            Val::String("nothing".into()),
+            "describe-shape",
+            vec![Val::Variant("none".into(), None)],
+            Val::String("no shape".into()),
         )
         .expect_call(
-            "make-text",
-            vec![Val::String("world".into())],
-            Val::Variant("text".into(), Some(Box::new(Val::String("world".into())))),
+            "make-circle",
+            vec![Val::Float64(2.0)],
+            Val::Variant("circle".into(), Some(Box::new(Val::Float64(2.0)))),
         )
-        .expect_call("make-nothing", vec![], Val::Variant("nothing".into(), None))
         .build()
         .unwrap()
         .run();
 }
 
 #[test]
-fn test_tuple_of_mixed_types() {
+fn test_flag_type() {
+    // Flags are represented as { name: boolean } objects in JS
     TestCase::new()
         .wit(
             r#"
-            package test:mixed-tuple;
-            world mixed-tuple {
-                export first: func(t: tuple<string, u32, bool>) -> string;
-                export make-tuple: func() -> tuple<string, u32, bool>;
+            package test:flagtest;
+            world flag-test {
+                flags permissions { read, write, execute }
+                export check-read: func(p: permissions) -> bool;
+                export read-write: func() -> permissions;
             }
         "#,
         )
         .script(
-            r#"
-            export function first(t) { return t[0]; }
-            export function makeTuple() { return ["hello", 42, true]; }
-        "#,
+            "export function checkRead(p) { return p.read === true; }\nexport function readWrite() { return { read: true, write: true }; }",
         )
-        .stub_wasi()
         .expect_call(
-            "first",
-            vec![Val::Tuple(vec![
-                Val::String("test".into()),
-                Val::U32(99),
-                Val::Bool(false),
-            ])],
-            Val::String("test".into()),
+            "check-read",
+            vec![Val::Flags(vec!["read".into(), "write".into()])],
+            Val::Bool(true),
         )
         .expect_call(
-            "make-tuple",
+            "check-read",
+            vec![Val::Flags(vec!["execute".into()])],
+            Val::Bool(false),
+        )
+        .expect_call(
+            "read-write",
             vec![],
-            Val::Tuple(vec![
-                Val::String("hello".into()),
-                Val::U32(42),
-                Val::Bool(true),
-            ]),
+            Val::Flags(vec!["read".into(), "write".into()]),
         )
         .build()
         .unwrap()
@@ -1378,41 +2127,21 @@ fn test_tuple_of_mixed_types() {
 }
 
 #[test]
-fn test_many_params_echo() {
-    // Matching compjs's echo_many — 8 diverse params
+fn test_tuple_return() {
     TestCase::new()
         .wit(
             r#"
-            package test:many-echo;
-            world many-echo {
-                export echo-many: func(
-                    a: bool, b: u8, c: s16, d: u32,
-                    e: s64, f: f32, g: f64, h: string
-                ) -> string;
-            }
-        "#,
-        )
-        .script(
-            r#"
-            export function echoMany(a, b, c, d, e, f, g, h) {
-                return [a, b, c, d, e, f.toFixed(1), g.toFixed(1), h].join(",");
+            package test:tuples;
+            world tuples {
+                export swap: func(a: u32, b: u32) -> tuple<u32, u32>;
             }
         "#,
         )
-        .stub_wasi()
+        .script("export function swap(a, b) { return [b, a]; }")
         .expect_call(
-            "echo-many",
-            vec![
-                Val::Bool(true),
-                Val::U8(255),
-                Val::S16(-100),
-                Val::U32(1000),
-                Val::S64(-9999),
-                Val::Float32(std::f32::consts::PI),
-                Val::Float64(std::f64::consts::E),
-                Val::String("end".into()),
-            ],
-            Val::String("true,255,-100,1000,-9999,3.1,2.7,end".into()),
+            "swap",
+            vec![Val::U32(1), Val::U32(2)],
+            Val::Tuple(vec![Val::U32(2), Val::U32(1)]),
         )
         .build()
         .unwrap()
@@ -1420,71 +2149,95 @@ fn test_many_params_echo() {
 }
 
 #[test]
-fn test_list_of_options() {
+fn test_many_arguments() {
+    let params: Vec<Val> = (1..=10).map(Val::U32).collect();
+
+    TestCase::new()
+        .wit(r#"
+            package test:manyargs;
+            world many-args {
+                export sum-ten: func(a1: u32, a2: u32, a3: u32, a4: u32, a5: u32, a6: u32, a7: u32, a8: u32, a9: u32, a10: u32) -> u32;
+            }
+        "#)
+        .script(r#"
+            export function sumTen(a1, a2, a3, a4, a5, a6, a7, a8, a9, a10) {
+                return a1 + a2 + a3 + a4 + a5 + a6 + a7 + a8 + a9 + a10;
+            }
+        "#)
+        .expect_call("sum-ten", params, Val::U32(55))
+        .build().unwrap()
+        .run();
+}
+
+#[test]
+fn test_no_arg_functions() {
     TestCase::new()
         .wit(
             r#"
-            package test:list-options;
-            world list-options {
-                export count-some: func(items: list<option<u32>>) -> u32;
+            package test:noargs;
+            world noargs {
+                export get-answer: func() -> u32;
+                export get-message: func() -> string;
+                export get-flag: func() -> bool;
             }
         "#,
         )
         .script(
             r#"
-            export function countSome(items) {
-                let count = 0;
-                for (const item of items) {
-                    if (item !== null && item !== undefined) count++;
-                }
-                return count;
-            }
+            export function getAnswer() { return 42; }
+            export function getMessage() { return "hello"; }
+            export function getFlag() { return true; }
         "#,
         )
-        .stub_wasi()
-        .expect_call(
-            "count-some",
-            vec![Val::List(vec![
-                Val::Option(Some(Box::new(Val::U32(1)))),
-                Val::Option(None),
-                Val::Option(Some(Box::new(Val::U32(3)))),
-                Val::Option(None),
-                Val::Option(Some(Box::new(Val::U32(5)))),
-            ])],
-            Val::U32(3),
-        )
+        .expect_call("get-answer", vec![], Val::U32(42))
+        .expect_call("get-message", vec![], Val::String("hello".into()))
+        .expect_call("get-flag", vec![], Val::Bool(true))
         .build()
         .unwrap()
         .run();
 }
 
 #[test]
-fn test_list_of_tuples() {
+fn test_nested_lists() {
+    let nested = Val::List(vec![
+        Val::List(vec![Val::U32(1), Val::U32(2)]),
+        Val::List(vec![Val::U32(3), Val::U32(4)]),
+        Val::List(vec![Val::U32(5)]),
+    ]);
+    let expected = Val::List(vec![
+        Val::U32(1),
+        Val::U32(2),
+        Val::U32(3),
+        Val::U32(4),
+        Val::U32(5),
+    ]);
+
     TestCase::new()
         .wit(
             r#"
-            package test:list-tuples;
-            world list-tuples {
-                export sum-pairs: func(pairs: list<tuple<u32, u32>>) -> u32;
+            package test:nested;
+            world nested-lists {
+                export flatten: func(nested: list<list<u32>>) -> list<u32>;
+                export show: func(nested: list<list<u32>>) -> string;
             }
         "#,
         )
         .script(
             r#"
-            export function sumPairs(pairs) {
-                return pairs.reduce((sum, p) => sum + p[0] + p[1], 0);
-            }
+            export function flatten(nested) { return nested.reduce((acc, arr) => acc.concat(arr), []); }
+            export function show(nested) { return JSON.stringify(nested); }
         "#,
         )
-        .stub_wasi()
+        .expect_call("flatten", vec![nested.clone()], expected)
+        .expect_call("show", vec![nested], Val::String("[[1,2],[3,4],[5]]".into()))
         .expect_call(
-            "sum-pairs",
+            "show",
             vec![Val::List(vec![
-                Val::Tuple(vec![Val::U32(1), Val::U32(2)]),
-                Val::Tuple(vec![Val::U32(3), Val::U32(4)]),
-                Val::Tuple(vec![Val::U32(5), Val::U32(6)]),
+                Val::List(vec![]),
+                Val::List(vec![Val::U32(7)]),
+                Val::List(vec![]),
             ])],
-            Val::U32(21),
+            Val::String("[[],[7],[]]".into()),
         )
         .build()
         .unwrap()
@@ -1492,45 +2245,75 @@ fn test_list_of_tuples() {
 }
 
 #[test]
-fn test_result_of_result() {
-    // Nested result types
+fn test_complex_record() {
+    let alice = Val::Record(vec![
+        ("name".into(), Val::String("Alice".into())),
+        ("age".into(), Val::U32(30)),
+        ("active".into(), Val::Bool(true)),
+    ]);
+    let bob = Val::Record(vec![
+        ("name".into(), Val::String("Bob".into())),
+        ("age".into(), Val::U32(25)),
+        ("active".into(), Val::Bool(true)),
+    ]);
+
+    TestCase::new()
+        .wit(r#"
+            package test:complex;
+            world complex-record {
+                record person { name: string, age: u32, active: bool }
+                export greet-person: func(p: person) -> string;
+                export make-person: func(name: string, age: u32) -> person;
+            }
+        "#)
+        .script(r#"
+            export function greetPerson(p) { return "Hello " + p.name + ", age " + p.age + ", active: " + p.active; }
+            export function makePerson(name, age) { return { name: name, age: age, active: true }; }
+        "#)
+        .expect_call("greet-person", vec![alice], Val::String("Hello Alice, age 30, active: true".into()))
+        .expect_call("make-person", vec![Val::String("Bob".into()), Val::U32(25)], bob)
+        .build().unwrap()
+        .run();
+}
+
+#[test]
+fn test_list_of_strings() {
     TestCase::new()
         .wit(
             r#"
-            package test:nested-result;
-            world nested-result {
-                export try-nested: func(level: u32) -> result<result<u32, string>, string>;
+            package test:stringlists;
+            world string-lists {
+                export join-strings: func(parts: list<string>, sep: string) -> string;
+                export count-strings: func(parts: list<string>) -> u32;
             }
         "#,
         )
         .script(
             r#"
-            export function tryNested(level) {
-                if (level === 0) throw "outer error";
-                if (level === 1) return { tag: "err", val: "inner error" };
-                return { tag: "ok", val: level * 10 };
-            }
+            export function joinStrings(parts, sep) { return parts.join(sep); }
+            export function countStrings(parts) { return parts.length; }
         "#,
         )
-        .stub_wasi()
-        .expect_call(
-            "try-nested",
-            vec![Val::U32(0)],
-            Val::Result(Err(Some(Box::new(Val::String("outer error".into()))))),
-        )
         .expect_call(
-            "try-nested",
-            vec![Val::U32(1)],
-            Val::Result(Ok(Some(Box::new(Val::Result(Err(Some(Box::new(
-                Val::String("inner error".into()),
-            )))))))),
+            "join-strings",
+            vec![
+                Val::List(vec![
+                    Val::String("a".into()),
+                    Val::String("b".into()),
+                    Val::String("c".into()),
+                ]),
+                Val::String("-".into()),
+            ],
+            Val::String("a-b-c".into()),
         )
         .expect_call(
-            "try-nested",
-            vec![Val::U32(2)],
-            Val::Result(Ok(Some(Box::new(Val::Result(Ok(Some(Box::new(
-                Val::U32(20),
-            )))))))),
+            "count-strings",
+            vec![Val::List(vec![
+                Val::String("one".into()),
+                Val::String("two".into()),
+                Val::String("three".into()),
+            ])],
+            Val::U32(3),
         )
         .build()
         .unwrap()
@@ -1538,12 +2321,930 @@ fn test_result_of_result() {
 }
 
 #[test]
-fn test_signed_integer_boundaries() {
+fn test_empty_world() {
     TestCase::new()
         .wit(
             r#"
-            package test:signed-bounds;
-            world signed-bounds {
+            package test:empty;
+            world empty {}
+        "#,
+        )
+        .script("// empty module\n")
+        .build()
+        .unwrap();
+}
+
+#[test]
+fn test_naming_conventions() {
+    // WIT kebab-case becomes camelCase in JS
+    let rec = Val::Record(vec![
+        ("first-name".into(), Val::String("John".into())),
+        ("last-name".into(), Val::String("Doe".into())),
+    ]);
+
+    TestCase::new()
+        .wit(
+            r#"
+            package test:conventions;
+            world conventions {
+                record my-record { first-name: string, last-name: string }
+                export get-full-name: func(r: my-record) -> string;
+            }
+        "#,
+        )
+        .script(r#"export function getFullName(r) { return r.firstName + " " + r.lastName; }"#)
+        .expect_call("get-full-name", vec![rec], Val::String("John Doe".into()))
+        .build()
+        .unwrap()
+        .run();
+}
+
+#[test]
+fn test_repeated_calls() {
+    let mut inst = TestCase::new()
+        .wit(
+            r#"
+            package test:repeated;
+            world repeated {
+                export hello: func() -> string;
+            }
+        "#,
+        )
+        .script(r#"export function hello() { return "hello"; }"#)
+        .build()
+        .unwrap();
+
+    for _ in 0..5 {
+        assert_eq!(inst.call1("hello", &[]), Val::String("hello".into()));
+    }
+}
+
+#[test]
+fn test_deeply_nested_lists() {
+    // 3 levels: list<list<list<u32>>>
+    let input = Val::List(vec![
+        Val::List(vec![
+            Val::List(vec![Val::U32(1), Val::U32(2)]),
+            Val::List(vec![Val::U32(3)]),
+        ]),
+        Val::List(vec![Val::List(vec![Val::U32(4), Val::U32(5), Val::U32(6)])]),
+    ]);
+
+    TestCase::new()
+        .wit(
+            r#"
+            package test:deep-nesting;
+            world deep-nesting {
+                export deep-flatten: func(nested: list<list<list<u32>>>) -> list<u32>;
+            }
+        "#,
+        )
+        .script(
+            r#"
+            export function deepFlatten(nested) {
+                let result = [];
+                for (const mid of nested) {
+                    for (const inner of mid) {
+                        for (const v of inner) {
+                            result.push(v);
+                        }
+                    }
+                }
+                return result;
+            }
+        "#,
+        )
+        .expect_call(
+            "deep-flatten",
+            vec![input],
+            Val::List(vec![
+                Val::U32(1),
+                Val::U32(2),
+                Val::U32(3),
+                Val::U32(4),
+                Val::U32(5),
+                Val::U32(6),
+            ]),
+        )
+        .build()
+        .unwrap()
+        .run();
+}
+
+#[test]
+fn test_nested_option() {
+    // option<option<u32>> is wrapped as { tag: "some"|"none", val } so that
+    // none, some(none), and some(some(v)) stay distinct.
+    TestCase::new()
+        .wit(
+            r#"
+            package test:nested-option;
+            world nested-option {
+                export unwrap-nested: func(val: option<option<u32>>) -> u32;
+                export identity: func(val: option<option<u32>>) -> option<option<u32>>;
+                export nothing: func() -> option<option<u32>>;
+            }
+        "#,
+        )
+        .script(
+            r#"
+            export function unwrapNested(val) {
+                if (val.tag === "none") return 0;
+                if (val.val === null || val.val === undefined) return 0;
+                return val.val;
+            }
+            export function identity(val) { return val; }
+            export function nothing() { return null; }
+        "#,
+        )
+        .stub_wasi()
+        .expect_call(
+            "unwrap-nested",
+            vec![Val::Option(Some(Box::new(Val::Option(Some(Box::new(
+                Val::U32(42),
+            ))))))],
+            Val::U32(42),
+        )
+        .expect_call("unwrap-nested", vec![Val::Option(None)], Val::U32(0))
+        .expect_call(
+            "unwrap-nested",
+            vec![Val::Option(Some(Box::new(Val::Option(None))))],
+            Val::U32(0),
+        )
+        // identity must round-trip all three distinct cases unchanged.
+        .expect_call("identity", vec![Val::Option(None)], Val::Option(None))
+        .expect_call(
+            "identity",
+            vec![Val::Option(Some(Box::new(Val::Option(None))))],
+            Val::Option(Some(Box::new(Val::Option(None)))),
+        )
+        .expect_call(
+            "identity",
+            vec![Val::Option(Some(Box::new(Val::Option(Some(Box::new(
+                Val::U32(7),
+            ))))))],
+            Val::Option(Some(Box::new(Val::Option(Some(Box::new(Val::U32(7))))))),
+        )
+        // A bare null still lowers as the outer none.
+        .expect_call("nothing", vec![], Val::Option(None))
+        .build()
+        .unwrap()
+        .run();
+}
+
+#[test]
+fn test_list_of_records() {
+    let people = Val::List(vec![
+        Val::Record(vec![
+            ("name".into(), Val::String("Alice".into())),
+            ("score".into(), Val::U32(90)),
+        ]),
+        Val::Record(vec![
+            ("name".into(), Val::String("Bob".into())),
+            ("score".into(), Val::U32(85)),
+        ]),
+    ]);
+
+    TestCase::new()
+        .wit(
+            r#"
+            package test:list-records;
+            world list-records {
+                record player { name: string, score: u32 }
+                export total-score: func(players: list<player>) -> u32;
+                export top-player: func(players: list<player>) -> string;
+            }
+        "#,
+        )
+        .script(
+            r#"
+            export function totalScore(players) {
+                return players.reduce((sum, p) => sum + p.score, 0);
+            }
+            export function topPlayer(players) {
+                let best = players[0];
+                for (const p of players) {
+                    if (p.score > best.score) best = p;
+                }
+                return best.name;
+            }
+        "#,
+        )
+        .expect_call("total-score", vec![people.clone()], Val::U32(175))
+        .expect_call("top-player", vec![people], Val::String("Alice".into()))
+        .build()
+        .unwrap()
+        .run();
+}
+
+#[test]
+fn test_list_of_variants() {
+    // list<variant> round-trip
+    TestCase::new()
+        .wit(
+            r#"
+            package test:list-variants;
+            world list-variants {
+                variant item { text(string), number(u32), empty }
+                export count-texts: func(items: list<item>) -> u32;
+            }
+        "#,
+        )
+        .script(
+            r#"
+            export function countTexts(items) {
+                let count = 0;
+                for (const item of items) {
+                    if (item.tag === "text") count++;
+                }
+                return count;
+            }
+        "#,
+        )
+        .expect_call(
+            "count-texts",
+            vec![Val::List(vec![
+                Val::Variant("text".into(), Some(Box::new(Val::String("hello".into())))),
+                Val::Variant("number".into(), Some(Box::new(Val::U32(42)))),
+                Val::Variant("text".into(), Some(Box::new(Val::String("world".into())))),
+                Val::Variant("empty".into(), None),
+            ])],
+            Val::U32(2),
+        )
+        .build()
+        .unwrap()
+        .run();
+}
+
+#[test]
+fn test_record_with_nested_fields() {
+    // Record containing option, list, and result fields
+    TestCase::new()
+        .wit(
+            r#"
+            package test:nested-record;
+            world nested-record {
+                record config {
+                    name: string,
+                    tags: list<string>,
+                    max-retries: option<u32>,
+                }
+                export describe-config: func(c: config) -> string;
+                export make-config: func(name: string) -> config;
+            }
+        "#,
+        )
+        .script(
+            r#"
+            export function describeConfig(c) {
+                let s = c.name + ": tags=" + c.tags.join(",");
+                if (c.maxRetries !== null && c.maxRetries !== undefined) {
+                    s += " retries=" + c.maxRetries;
+                }
+                return s;
+            }
+            export function makeConfig(name) {
+                return { name: name, tags: ["default"], maxRetries: 3 };
+            }
+        "#,
+        )
+        .expect_call(
+            "describe-config",
+            vec![Val::Record(vec![
+                ("name".into(), Val::String("test".into())),
+                (
+                    "tags".into(),
+                    Val::List(vec![Val::String("a".into()), Val::String("b".into())]),
+                ),
+                (
+                    "max-retries".into(),
+                    Val::Option(Some(Box::new(Val::U32(5)))),
+                ),
+            ])],
+            Val::String("test: tags=a,b retries=5".into()),
+        )
+        .expect_call(
+            "make-config",
+            vec![Val::String("prod".into())],
+            Val::Record(vec![
+                ("name".into(), Val::String("prod".into())),
+                (
+                    "tags".into(),
+                    Val::List(vec![Val::String("default".into())]),
+                ),
+                (
+                    "max-retries".into(),
+                    Val::Option(Some(Box::new(Val::U32(3)))),
+                ),
+            ]),
+        )
+        .build()
+        .unwrap()
+        .run();
+}
+
+#[test]
+fn test_json_helpers() {
+    let mut inst = TestCase::new()
+        .wit(
+            r#"
+            package test:json;
+            interface shapes {
+                record point { x-pos: s64, y-pos: f64 }
+                variant shape { circle(f64), dot(point), empty }
+            }
+            interface api {
+                use shapes.{point, shape};
+                flags perms { read, write-all }
+                record item { shape: shape, tags: list<string>, note: option<option<u8>>, perms: perms, data: list<u8> }
+            }
+            world json {
+                use api.{item};
+                export round-trip: func(i: item) -> list<string>;
+            }
+        "#,
+        )
+        .script(
+            r#"
+            export function roundTrip(item) {
+                const json = wit.toJSON(item, "item");
+                const back = wit.fromJSON(json, "test:json/api/item");
+                const errors = [
+                    () => wit.toJSON({ tag: "square" }, "shape"),
+                    () => wit.toJSON({ xPos: 1.5, yPos: 0 }, "point"),
+                    () => wit.fromJSON("[1, 2]", "tuple<u8>"),
+                    () => wit.toJSON(1, "nope"),
+                ].map((f) => {
+                    try {
+                        f();
+                    } catch (e) {
+                        return e.message;
+                    }
+                });
+                return [
+                    json,
+                    wit.toJSON(back, "item"),
+                    String(back.data instanceof Uint8Array),
+                    wit.toJSON(
+                        [{ tag: "ok", val: 2n ** 63n - 1n }, { tag: "err" }, NaN],
+                        "tuple<result<s64>, result<s64>, f32>",
+                    ),
+                    String(wit.fromJSON('"9223372036854775807"', "s64") === 2n ** 63n - 1n),
+                    ...errors,
+                ];
+            }
+        "#,
+        )
+        .build()
+        .unwrap();
+
+    let item = Val::Record(vec![
+        (
+            "shape".into(),
+            Val::Variant(
+                "dot".into(),
+                Some(Box::new(Val::Record(vec![
+                    ("x-pos".into(), Val::S64(-3)),
+                    ("y-pos".into(), Val::Float64(0.5)),
+                ]))),
+            ),
+        ),
+        ("tags".into(), Val::List(vec![Val::String("a".into())])),
+        (
+            "note".into(),
+            Val::Option(Some(Box::new(Val::Option(None)))),
+        ),
+        ("perms".into(), Val::Flags(vec!["write-all".into()])),
+        ("data".into(), Val::List(vec![Val::U8(1), Val::U8(255)])),
+    ]);
+    let json = concat!(
+        r#"{"shape":{"tag":"dot","val":{"xPos":-3,"yPos":0.5}},"tags":["a"],"#,
+        r#""note":{"tag":"some","val":null},"perms":{"read":false,"writeAll":true},"data":[1,255]}"#,
+    );
+    let expected = [
+        json,
+        json,
+        "true",
+        r#"[{"tag":"ok","val":"9223372036854775807"},{"tag":"err"},"NaN"]"#,
+        "true",
+        "wit.toJSON: expected shape at $, got object",
+        "wit.toJSON: expected s64 at $.xPos, got number",
+        "wit.fromJSON: expected tuple<u8> at $, got array",
+        "wit: unknown type `nope`",
+    ]
+    .map(|s| Val::String(s.into()));
+    assert_eq!(
+        inst.call1("round-trip", &[item]),
+        Val::List(expected.into())
+    );
+}
+
+#[test]
+fn test_option_of_result() {
+    TestCase::new()
+        .wit(
+            r#"
+            package test:option-result;
+            world option-result {
+                export process: func(val: option<result<u32, string>>) -> string;
+            }
+        "#,
+        )
+        .script(
+            r#"
+            export function process(val) {
+                if (val === null || val === undefined) return "none";
+                if (val.tag === "ok") return "ok:" + val.val;
+                return "err:" + val.val;
+            }
+        "#,
+        )
+        .stub_wasi()
+        .expect_call(
+            "process",
+            vec![Val::Option(Some(Box::new(Val::Result(Ok(Some(
+                Box::new(Val::U32(42)),
+            ))))))],
+            Val::String("ok:42".into()),
+        )
+        .expect_call(
+            "process",
+            vec![Val::Option(Some(Box::new(Val::Result(Err(Some(
+                Box::new(Val::String("fail".into())),
+            ))))))],
+            Val::String("err:fail".into()),
+        )
+        .expect_call(
+            "process",
+            vec![Val::Option(None)],
+            Val::String("none".into()),
+        )
+        .build()
+        .unwrap()
+        .run();
+}
+
+#[test]
+fn test_result_of_option() {
+    TestCase::new()
+        .wit(
+            r#"
+            package test:result-option;
+            world result-option {
+                export maybe-lookup: func(key: string) -> result<option<u32>, string>;
+            }
+        "#,
+        )
+        .script(
+            r#"
+            export function maybeLookup(key) {
+                if (key === "found") return 42;
+                if (key === "missing") return null;
+                throw "invalid key";
+            }
+        "#,
+        )
+        .stub_wasi()
+        .expect_call(
+            "maybe-lookup",
+            vec![Val::String("found".into())],
+            Val::Result(Ok(Some(Box::new(Val::Option(Some(Box::new(Val::U32(
+                42,
+            )))))))),
+        )
+        .expect_call(
+            "maybe-lookup",
+            vec![Val::String("missing".into())],
+            Val::Result(Ok(Some(Box::new(Val::Option(None))))),
+        )
+        .expect_call(
+            "maybe-lookup",
+            vec![Val::String("error".into())],
+            Val::Result(Err(Some(Box::new(Val::String("invalid key".into()))))),
+        )
+        .build()
+        .unwrap()
+        .run();
+}
+
+#[test]
+fn test_empty_list() {
+    TestCase::new()
+        .wit(
+            r#"
+            package test:empty-list;
+            world empty-list {
+                export count: func(items: list<u32>) -> u32;
+                export make-empty: func() -> list<u32>;
+            }
+        "#,
+        )
+        .script(
+            r#"
+            export function count(items) { return items.length; }
+            export function makeEmpty() { return []; }
+        "#,
+        )
+        .stub_wasi()
+        .expect_call("count", vec![Val::List(vec![])], Val::U32(0))
+        .expect_call("make-empty", vec![], Val::List(vec![]))
+        .build()
+        .unwrap()
+        .run();
+}
+
+#[test]
+fn test_import_export_chain() {
+    let mut inst = TestCase::new()
+        .wit(
+            r#"
+            package test:chain;
+            world chain {
+                export process: func(val: u32) -> u32;
+            }
+        "#,
+        )
+        .script(
+            r#"
+            export function process(val) {
+                return val + 11;
+            }
+        "#,
+        )
+        .stub_wasi()
+        .expect_call("process", vec![Val::U32(5)], Val::U32(16))
+        .build()
+        .unwrap();
+
+    inst.run();
+}
+
+#[test]
+fn test_multiple_return_results() {
+    // Test result types with no error payload
+    TestCase::new()
+        .wit(
+            r#"
+            package test:result-void;
+            world result-void {
+                export try-op: func(succeed: bool) -> result<u32>;
+            }
+        "#,
+        )
+        .script(
+            r#"
+            export function tryOp(succeed) {
+                if (succeed) return 42;
+                throw undefined;
+            }
+        "#,
+        )
+        .stub_wasi()
+        .expect_call(
+            "try-op",
+            vec![Val::Bool(true)],
+            Val::Result(Ok(Some(Box::new(Val::U32(42))))),
+        )
+        .expect_call("try-op", vec![Val::Bool(false)], Val::Result(Err(None)))
+        .build()
+        .unwrap()
+        .run();
+}
+
+#[test]
+fn test_variant_with_multiple_payload_types() {
+    // Variant with different payload types including string and none
+    TestCase::new()
+        .wit(
+            r#"
+            package test:multi-variant;
+            world multi-variant {
+                variant value { integer(s32), text(string), flag(bool), nothing }
+                export stringify: func(v: value) -> string;
+                export make-text: func(s: string) -> value;
+                export make-nothing: func() -> value;
+            }
+        "#,
+        )
+        .script(
+            r#"
+            export function stringify(v) {
+                if (v.tag === "integer") return "int:" + v.val;
+                if (v.tag === "text") return "text:" + v.val;
+                if (v.tag === "flag") return "flag:" + v.val;
+                return "nothing";
+            }
+            export function makeText(s) { return { tag: "text", val: s }; }
+            export function makeNothing() { return { tag: "nothing" }; }
+        "#,
+        )
+        .stub_wasi()
+        .expect_call(
+            "stringify",
+            vec![Val::Variant(
+                "integer".into(),
+                Some(Box::new(Val::S32(-42))),
+            )],
+            Val::String("int:-42".into()),
+        )
+        .expect_call(
+            "stringify",
+            vec![Val::Variant(
+                "text".into(),
+                Some(Box::new(Val::String("hello".into()))),
+            )],
+            Val::String("text:hello".into()),
+        )
+        .expect_call(
+            "stringify",
+            vec![Val::Variant("nothing".into(), None)],
+            Val::String("nothing".into()),
+        )
+        .expect_call(
+            "make-text",
+            vec![Val::String("world".into())],
+            Val::Variant("text".into(), Some(Box::new(Val::String("world".into())))),
+        )
+        .expect_call("make-nothing", vec![], Val::Variant("nothing".into(), None))
+        .build()
+        .unwrap()
+        .run();
+}
+
+#[test]
+fn test_tuple_of_mixed_types() {
+    TestCase::new()
+        .wit(
+            r#"
+            package test:mixed-tuple;
+            world mixed-tuple {
+                export first: func(t: tuple<string, u32, bool>) -> string;
+                export make-tuple: func() -> tuple<string, u32, bool>;
+            }
+        "#,
+        )
+        .script(
+            r#"
+            export function first(t) { return t[0]; }
+            export function makeTuple() { return ["hello", 42, true]; }
+        "#,
+        )
+        .stub_wasi()
+        .expect_call(
+            "first",
+            vec![Val::Tuple(vec![
+                Val::String("test".into()),
+                Val::U32(99),
+                Val::Bool(false),
+            ])],
+            Val::String("test".into()),
+        )
+        .expect_call(
+            "make-tuple",
+            vec![],
+            Val::Tuple(vec![
+                Val::String("hello".into()),
+                Val::U32(42),
+                Val::Bool(true),
+            ]),
+        )
+        .build()
+        .unwrap()
+        .run();
+}
+
+#[test]
+fn test_many_params_echo() {
+    // Matching compjs's echo_many — 8 diverse params
+    TestCase::new()
+        .wit(
+            r#"
+            package test:many-echo;
+            world many-echo {
+                export echo-many: func(
+                    a: bool, b: u8, c: s16, d: u32,
+                    e: s64, f: f32, g: f64, h: string
+                ) -> string;
+            }
+        "#,
+        )
+        .script(
+            r#"
+            export function echoMany(a, b, c, d, e, f, g, h) {
+                return [a, b, c, d, e, f.toFixed(1), g.toFixed(1), h].join(",");
+            }
+        "#,
+        )
+        .stub_wasi()
+        .expect_call(
+            "echo-many",
+            vec![
+                Val::Bool(true),
+                Val::U8(255),
+                Val::S16(-100),
+                Val::U32(1000),
+                Val::S64(-9999),
+                Val::Float32(std::f32::consts::PI),
+                Val::Float64(std::f64::consts::E),
+                Val::String("end".into()),
+            ],
+            Val::String("true,255,-100,1000,-9999,3.1,2.7,end".into()),
+        )
+        .build()
+        .unwrap()
+        .run();
+}
+
+#[test]
+fn test_list_of_options() {
+    TestCase::new()
+        .wit(
+            r#"
+            package test:list-options;
+            world list-options {
+                export count-some: func(items: list<option<u32>>) -> u32;
+            }
+        "#,
+        )
+        .script(
+            r#"
+            export function countSome(items) {
+                let count = 0;
+                for (const item of items) {
+                    if (item !== null && item !== undefined) count++;
+                }
+                return count;
+            }
+        "#,
+        )
+        .stub_wasi()
+        .expect_call(
+            "count-some",
+            vec![Val::List(vec![
+                Val::Option(Some(Box::new(Val::U32(1)))),
+                Val::Option(None),
+                Val::Option(Some(Box::new(Val::U32(3)))),
+                Val::Option(None),
+                Val::Option(Some(Box::new(Val::U32(5)))),
+            ])],
+            Val::U32(3),
+        )
+        .build()
+        .unwrap()
+        .run();
+}
+
+#[tokio::test]
+async fn test_import_omitted_arguments() {
+    // Trailing options may be left out; leaving out a required parameter is a
+    // TypeError that names the import and its arity.
+    let wasm = TestCase::new()
+        .wit(
+            r#"
+            package test:arity;
+            interface greeter {
+                greet: func(name: string, punctuation: option<string>) -> string;
+            }
+            world arity {
+                import greeter;
+                export run: func() -> string;
+            }
+        "#,
+        )
+        .script(
+            r#"
+            import { greet } from "test:arity/greeter";
+            export function run() {
+                let missing;
+                try {
+                    greet();
+                } catch (e) {
+                    missing = `${e.name}: ${e.message}`;
+                }
+                return [greet("a"), greet("b", "!"), missing].join("|");
+            }
+        "#,
+        )
+        .componentize()
+        .await
+        .expect("should build arity component");
+
+    let engine = common::engine();
+    let component = wasmtime::component::Component::new(engine, &wasm).unwrap();
+    let wasi = wasmtime_wasi::WasiCtxBuilder::new().build();
+    let table = wasmtime::component::ResourceTable::new();
+    let mut store = wasmtime::Store::new(engine, common::WasiCtxState { wasi, table });
+    let mut linker = wasmtime::component::Linker::new(engine);
+    wasmtime_wasi::p2::add_to_linker_sync(&mut linker).unwrap();
+    linker
+        .instance("test:arity/greeter")
+        .unwrap()
+        .func_wrap(
+            "greet",
+            |_, (name, punctuation): (String, Option<String>)| {
+                Ok((name + punctuation.as_deref().unwrap_or("."),))
+            },
+        )
+        .unwrap();
+
+    let instance = linker.instantiate(&mut store, &component).unwrap();
+    let run = instance.get_func(&mut store, "run").unwrap();
+    let mut results = [Val::Bool(false)];
+    run.call(&mut store, &[], &mut results).unwrap();
+    assert_eq!(
+        results[0],
+        Val::String(
+            "a.|b!|TypeError: `test:arity/greeter#greet` expects 1 to 2 arguments, got 0".into()
+        )
+    );
+}
+
+#[test]
+fn test_list_of_tuples() {
+    TestCase::new()
+        .wit(
+            r#"
+            package test:list-tuples;
+            world list-tuples {
+                export sum-pairs: func(pairs: list<tuple<u32, u32>>) -> u32;
+            }
+        "#,
+        )
+        .script(
+            r#"
+            export function sumPairs(pairs) {
+                return pairs.reduce((sum, p) => sum + p[0] + p[1], 0);
+            }
+        "#,
+        )
+        .stub_wasi()
+        .expect_call(
+            "sum-pairs",
+            vec![Val::List(vec![
+                Val::Tuple(vec![Val::U32(1), Val::U32(2)]),
+                Val::Tuple(vec![Val::U32(3), Val::U32(4)]),
+                Val::Tuple(vec![Val::U32(5), Val::U32(6)]),
+            ])],
+            Val::U32(21),
+        )
+        .build()
+        .unwrap()
+        .run();
+}
+
+#[test]
+fn test_result_of_result() {
+    // Nested result types
+    TestCase::new()
+        .wit(
+            r#"
+            package test:nested-result;
+            world nested-result {
+                export try-nested: func(level: u32) -> result<result<u32, string>, string>;
+            }
+        "#,
+        )
+        .script(
+            r#"
+            export function tryNested(level) {
+                if (level === 0) throw "outer error";
+                if (level === 1) return { tag: "err", val: "inner error" };
+                return { tag: "ok", val: level * 10 };
+            }
+        "#,
+        )
+        .stub_wasi()
+        .expect_call(
+            "try-nested",
+            vec![Val::U32(0)],
+            Val::Result(Err(Some(Box::new(Val::String("outer error".into()))))),
+        )
+        .expect_call(
+            "try-nested",
+            vec![Val::U32(1)],
+            Val::Result(Ok(Some(Box::new(Val::Result(Err(Some(Box::new(
+                Val::String("inner error".into()),
+            )))))))),
+        )
+        .expect_call(
+            "try-nested",
+            vec![Val::U32(2)],
+            Val::Result(Ok(Some(Box::new(Val::Result(Ok(Some(Box::new(
+                Val::U32(20),
+            )))))))),
+        )
+        .build()
+        .unwrap()
+        .run();
+}
+
+#[test]
+fn test_signed_integer_boundaries() {
+    TestCase::new()
+        .wit(
+            r#"
+            package test:signed-bounds;
+            world signed-bounds {
                 export echo-s32: func(v: s32) -> s32;
                 export echo-s64: func(v: s64) -> s64;
             }
@@ -1551,358 +3252,1573 @@ fn test_signed_integer_boundaries() {
         )
         .script(
             r#"
-            export function echoS32(v) { return v; }
-            export function echoS64(v) { return v; }
+            export function echoS32(v) { return v; }
+            export function echoS64(v) { return v; }
+        "#,
+        )
+        .stub_wasi()
+        .expect_call("echo-s32", vec![Val::S32(-1)], Val::S32(-1))
+        .expect_call("echo-s32", vec![Val::S32(i32::MIN)], Val::S32(i32::MIN))
+        .expect_call("echo-s32", vec![Val::S32(i32::MAX)], Val::S32(i32::MAX))
+        .expect_call("echo-s64", vec![Val::S64(-1)], Val::S64(-1))
+        .build()
+        .unwrap()
+        .run();
+}
+
+#[test]
+fn test_echo_lists_of_each_primitive() {
+    // list<bool>, list<u8>, list<s32>, list<f64> — matching compjs echo_lists_* tests
+    TestCase::new()
+        .wit(
+            r#"
+            package test:prim-lists;
+            world prim-lists {
+                export echo-bools: func(v: list<bool>) -> list<bool>;
+                export echo-u8s: func(v: list<u8>) -> list<u8>;
+                export echo-f64s: func(v: list<f64>) -> list<f64>;
+            }
+        "#,
+        )
+        .script(
+            r#"
+            export function echoBools(v) { return v; }
+            export function echoU8s(v) { return v; }
+            export function echoF64s(v) { return v; }
+        "#,
+        )
+        .stub_wasi()
+        .expect_call(
+            "echo-bools",
+            vec![Val::List(vec![
+                Val::Bool(true),
+                Val::Bool(false),
+                Val::Bool(true),
+            ])],
+            Val::List(vec![Val::Bool(true), Val::Bool(false), Val::Bool(true)]),
+        )
+        .expect_call(
+            "echo-u8s",
+            vec![Val::List(vec![Val::U8(0), Val::U8(127), Val::U8(255)])],
+            Val::List(vec![Val::U8(0), Val::U8(127), Val::U8(255)]),
+        )
+        .expect_call(
+            "echo-f64s",
+            vec![Val::List(vec![
+                Val::Float64(1.0),
+                Val::Float64(-0.5),
+                Val::Float64(f64::MAX),
+            ])],
+            Val::List(vec![
+                Val::Float64(1.0),
+                Val::Float64(-0.5),
+                Val::Float64(f64::MAX),
+            ]),
+        )
+        .build()
+        .unwrap()
+        .run();
+}
+
+#[test]
+fn test_exported_resource() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let wit_path = dir.path().join("test.wit");
+    std::fs::write(
+        &wit_path,
+        r#"
+        package test:res;
+
+        interface counter-api {
+            resource counter {
+                constructor(initial: u32);
+                increment: func();
+                get-value: func() -> u32;
+            }
+        }
+
+        world resource-test {
+            export counter-api;
+        }
+    "#,
+    )
+    .unwrap();
+
+    let opts = componentize_qjs::ComponentizeOpts {
+        wit_path: &wit_path,
+        wit_source: None,
+        wit_package: None,
+        js_source: r#"
+            class Counter {
+                constructor(initial) { this.value = initial; }
+                increment() { this.value++; }
+                getValue() { return this.value; }
+            }
+            export const counterApi = { Counter };
+        "#,
+        js_path: None,
+        module_root: None,
+        init_env: &[],
+        init_args: &[],
+        init_dirs: &[],
+        modules: &[],
+        entry_module: None,
+        preludes: &[],
+        world_name: None,
+        stub_wasi: true,
+        disable_gc: false,
+        strip_source: false,
+        bytecode: false,
+        lazy_eval: false,
+        gc_interval: None,
+        gc_threshold: None,
+        max_list_length: None,
+        memory_limit: None,
+        memory_report: None,
+        max_instructions: None,
+        init_timeout: None,
+        strict_types: false,
+        tagged_results: false,
+        throwing_imports: false,
+        wrap_integers: false,
+        finite_floats: false,
+        warn_f32_rounding: false,
+        trace_values: false,
+        features: &[],
+        env_shims: componentize_qjs::EnvShims::default(),
+        deny_warnings: false,
+        arity_mismatch: Default::default(),
+        math_random: None,
+        prime: &[],
+        version_aliasing: Default::default(),
+        import_aliases: &[],
+        naming: Default::default(),
+        renames: &[],
+        harden: false,
+        import_log: None,
+        optimize: None,
+        core_module: false,
+        custom_sections: &[],
+        metadata: componentize_qjs::Metadata::default(),
+        reproducible: false,
+        cache_dir: None,
+        runtime: componentize_qjs::Runtime::Default,
+        debug_runtime: false,
+    };
+
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+
+    let wasm = rt.block_on(componentize_qjs::componentize(&opts)).unwrap();
+
+    // Component builds successfully with resource types
+    let engine = common::engine();
+    let component = wasmtime::component::Component::new(engine, &wasm).unwrap();
+
+    // Instantiate and call resource methods through the interface
+    let mut wasi_builder = wasmtime_wasi::WasiCtxBuilder::new();
+    let wasi = wasi_builder.build();
+    let table = wasmtime::component::ResourceTable::new();
+    let mut store = wasmtime::Store::new(engine, common::WasiCtxState { wasi, table });
+
+    let mut linker = wasmtime::component::Linker::new(engine);
+    wasmtime_wasi::p2::add_to_linker_sync(&mut linker).unwrap();
+    let instance = linker.instantiate(&mut store, &component).unwrap();
+
+    // Navigate into the exported interface
+    let iface_idx = instance
+        .get_export_index(&mut store, None, "test:res/counter-api")
+        .expect("interface export not found");
+
+    // Get constructor
+    let ctor_idx = instance
+        .get_export_index(&mut store, Some(&iface_idx), "[constructor]counter")
+        .expect("[constructor]counter not found");
+    let ctor = instance.get_func(&mut store, ctor_idx).unwrap();
+
+    // Call constructor(42)
+    let mut results = [Val::Bool(false)];
+    ctor.call(&mut store, &[Val::U32(42)], &mut results)
+        .unwrap();
+    let counter = results[0].clone();
+
+    // Get get-value method
+    let get_val_idx = instance
+        .get_export_index(&mut store, Some(&iface_idx), "[method]counter.get-value")
+        .expect("[method]counter.get-value not found");
+    let get_val = instance.get_func(&mut store, get_val_idx).unwrap();
+
+    // Call get-value(counter) => 42
+    let mut results = [Val::Bool(false)];
+    get_val
+        .call(&mut store, std::slice::from_ref(&counter), &mut results)
+        .unwrap();
+    assert_eq!(results[0], Val::U32(42), "initial value should be 42");
+
+    // Get increment method
+    let inc_idx = instance
+        .get_export_index(&mut store, Some(&iface_idx), "[method]counter.increment")
+        .expect("[method]counter.increment not found");
+    let inc = instance.get_func(&mut store, inc_idx).unwrap();
+
+    // Call increment(counter)
+    inc.call(&mut store, std::slice::from_ref(&counter), &mut [])
+        .unwrap();
+
+    // Verify value is now 43
+    let mut results = [Val::Bool(false)];
+    get_val
+        .call(&mut store, std::slice::from_ref(&counter), &mut results)
+        .unwrap();
+
+    assert_eq!(
+        results[0],
+        Val::U32(43),
+        "value should be 43 after increment"
+    );
+}
+
+#[test]
+fn test_static_resource_method_in_interface() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let wit_path = dir.path().join("test.wit");
+    std::fs::write(
+        &wit_path,
+        r#"
+        package test:static-bug;
+
+        interface widget-api {
+            resource widget {
+                constructor(name: string);
+                get-name: func() -> string;
+                create-default: static func() -> widget;
+            }
+        }
+
+        world static-test {
+            export widget-api;
+        }
+        "#,
+    )
+    .unwrap();
+
+    let opts = componentize_qjs::ComponentizeOpts {
+        wit_path: &wit_path,
+        wit_source: None,
+        wit_package: None,
+        js_source: r#"
+            class Widget {
+                constructor(name) { this.name = name; }
+                getName() { return this.name; }
+                static createDefault() { return new Widget("default"); }
+            }
+            export const widgetApi = { Widget };
+        "#,
+        js_path: None,
+        module_root: None,
+        init_env: &[],
+        init_args: &[],
+        init_dirs: &[],
+        modules: &[],
+        entry_module: None,
+        preludes: &[],
+        world_name: None,
+        stub_wasi: true,
+        disable_gc: false,
+        strip_source: false,
+        bytecode: false,
+        lazy_eval: false,
+        gc_interval: None,
+        gc_threshold: None,
+        max_list_length: None,
+        memory_limit: None,
+        memory_report: None,
+        max_instructions: None,
+        init_timeout: None,
+        strict_types: false,
+        tagged_results: false,
+        throwing_imports: false,
+        wrap_integers: false,
+        finite_floats: false,
+        warn_f32_rounding: false,
+        trace_values: false,
+        features: &[],
+        env_shims: componentize_qjs::EnvShims::default(),
+        deny_warnings: false,
+        arity_mismatch: Default::default(),
+        math_random: None,
+        prime: &[],
+        version_aliasing: Default::default(),
+        import_aliases: &[],
+        naming: Default::default(),
+        renames: &[],
+        harden: false,
+        import_log: None,
+        optimize: None,
+        core_module: false,
+        custom_sections: &[],
+        metadata: componentize_qjs::Metadata::default(),
+        reproducible: false,
+        cache_dir: None,
+        runtime: componentize_qjs::Runtime::Default,
+        debug_runtime: false,
+    };
+
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+
+    let wasm = rt.block_on(componentize_qjs::componentize(&opts)).unwrap();
+
+    let engine = common::engine();
+    let component = wasmtime::component::Component::new(engine, &wasm).unwrap();
+
+    let mut wasi_builder = wasmtime_wasi::WasiCtxBuilder::new();
+    let wasi = wasi_builder.build();
+    let table = wasmtime::component::ResourceTable::new();
+    let mut store = wasmtime::Store::new(engine, common::WasiCtxState { wasi, table });
+
+    let mut linker = wasmtime::component::Linker::new(engine);
+    wasmtime_wasi::p2::add_to_linker_sync(&mut linker).unwrap();
+    let instance = linker.instantiate(&mut store, &component).unwrap();
+
+    let iface_idx = instance
+        .get_export_index(&mut store, None, "test:static-bug/widget-api")
+        .expect("interface export not found");
+
+    // Call the static method — this should work but panics because
+    // the runtime looks for "createDefault" in globals instead of the
+    // interface object.
+    let static_idx = instance
+        .get_export_index(
+            &mut store,
+            Some(&iface_idx),
+            "[static]widget.create-default",
+        )
+        .expect("[static]widget.create-default not found");
+    let static_fn = instance.get_func(&mut store, static_idx).unwrap();
+
+    let mut results = [Val::Bool(false)];
+    static_fn.call(&mut store, &[], &mut results).unwrap();
+
+    // If we got here, we have a resource handle. Verify it works.
+    let get_name_idx = instance
+        .get_export_index(&mut store, Some(&iface_idx), "[method]widget.get-name")
+        .expect("[method]widget.get-name not found");
+    let get_name = instance.get_func(&mut store, get_name_idx).unwrap();
+
+    let mut name_results = [Val::Bool(false)];
+    get_name
+        .call(&mut store, &results, &mut name_results)
+        .unwrap();
+    assert_eq!(
+        name_results[0],
+        Val::String("default".into()),
+        "static factory should produce widget with name 'default'"
+    );
+}
+
+#[test]
+fn test_componentize_builder() {
+    let features = ["beta".to_string()];
+    let builder = componentize_qjs::ComponentizeBuilder::new(
+        std::path::Path::new("builder.wit"),
+        "export function flags() { return [...__FEATURES__].join(); }",
+    )
+    .wit_source(
+        r#"
+        package test:builder;
+        world builder {
+            export flags: func() -> string;
+        }
+    "#,
+    )
+    .stub_wasi(true)
+    .features(&features);
+    assert!(builder.opts().stub_wasi);
+    assert!(
+        !builder.opts().strict_types,
+        "unset options keep their defaults"
+    );
+
+    let wasm = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap()
+        .block_on(builder.componentize())
+        .unwrap();
+    let mut inst = ComponentInstance::from_wasm(wasm, vec![], vec![]).unwrap();
+    assert_eq!(inst.call1("flags", &[]), Val::String("beta".into()));
+}
+
+#[tokio::test]
+async fn test_async_export_rejection_propagates() {
+    let mut instance = TestCase::new()
+        .wit(
+            r#"
+            package test:async-reject;
+            world async-reject {
+                export will-throw: async func();
+            }
+            "#,
+        )
+        .script(
+            r#"
+            export async function willThrow() {
+                throw new Error("this should not be silently swallowed");
+            }
+            "#,
+        )
+        .build_async()
+        .await
+        .unwrap();
+
+    // The host calls a void async export that throws. The rejection should
+    // propagate as an error, not be silently swallowed.
+    let result = instance.call_async("will-throw", &[], 0).await;
+    assert!(
+        result.is_err(),
+        "async export that throws should return an error, not Ok"
+    );
+}
+
+#[test]
+fn test_root_level_flags() {
+    let result = TestCase::new()
+        .wit(
+            r#"
+            package test:root-flags;
+            world root-flags {
+                flags permissions {
+                    read,
+                    write,
+                    execute,
+                }
+                export check: func(p: permissions) -> string;
+            }
+            "#,
+        )
+        .script(
+            r#"
+            export function check(p) {
+                const parts = [];
+                if (p.read) parts.push("read");
+                if (p.write) parts.push("write");
+                if (p.execute) parts.push("execute");
+                return parts.join(",");
+            }
+            "#,
+        )
+        .stub_wasi()
+        .build();
+
+    let mut instance = result.expect(
+        "componentization should succeed for world-level flags — \
+         world-level flags must round-trip as { name: boolean } objects",
+    );
+
+    let flags_val = Val::Flags(vec!["read".into(), "execute".into()]);
+    let ret = instance.call1("check", &[flags_val]);
+    assert_eq!(
+        ret,
+        Val::String("read,execute".into()),
+        "root-level flags should round-trip through the component"
+    );
+}
+
+#[test]
+fn test_prime_exports() {
+    let wit = r#"
+        package test:prime;
+        interface calc { square: func(x: u32) -> u32; }
+        world prime {
+            export calls: func() -> list<string>;
+            export calc;
+        }
+    "#;
+    let script = r#"
+        const seen = [];
+        export function calls() { return seen; }
+        export const calc = {
+            square(x) { seen.push(`square(${x})`); return x * x; },
+        };
+    "#;
+    let build = |case: TestCase| {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(case.wit(wit).script(script).componentize())
+    };
+
+    // Heap changes made while priming are part of the snapshot.
+    let wasm = build(
+        TestCase::new()
+            .prime("test:prime/calc#square", "[3]")
+            .prime("calls", "[]"),
+    )
+    .unwrap();
+    let mut inst = ComponentInstance::from_wasm(wasm, vec![], vec![]).unwrap();
+    assert_eq!(
+        inst.call1("calls", &[]),
+        Val::List(vec![Val::String("square(3)".into())])
+    );
+
+    let err = build(TestCase::new().prime("cube", "[]")).expect_err("unknown export");
+    assert!(
+        format!("{err:#}").contains("cannot prime `cube`"),
+        "got {err:#}"
+    );
+
+    let err = build(TestCase::new().prime("test:prime/calc#square", "3"))
+        .expect_err("arguments must be an array");
+    assert!(
+        format!("{err:#}").contains("must be a JSON array"),
+        "got {err:#}"
+    );
+
+    assert_eq!(
+        "calls".parse::<PrimeCall>().unwrap(),
+        PrimeCall {
+            name: "calls".to_string(),
+            args: "[]".to_string(),
+        }
+    );
+    assert!("=[1]".parse::<PrimeCall>().is_err());
+}
+
+#[test]
+fn test_init_hook() {
+    let wit = "package test:hook; world hook { export table: func() -> list<u32>; }";
+    let mut inst = TestCase::new()
+        .wit(wit)
+        .script(
+            r#"
+            const entries = [];
+            globalThis.__componentize_init = async () => {
+                entries.push(1);
+                await null;
+                entries.push(2);
+            };
+            export function table() {
+                return [...entries, typeof globalThis.__componentize_init === "undefined" ? 1 : 0];
+            }
+        "#,
+        )
+        .build()
+        .unwrap();
+    assert_eq!(
+        inst.call1("table", &[]),
+        Val::List(vec![Val::U32(1), Val::U32(2), Val::U32(1)])
+    );
+
+    let err = TestCase::new()
+        .wit(wit)
+        .script(
+            r#"
+            globalThis.__componentize_init = () => { throw new Error("cold cache"); };
+            export function table() { return []; }
+        "#,
+        )
+        .build()
+        .err()
+        .unwrap();
+    assert!(
+        format!("{err:#}").contains("`__componentize_init` failed")
+            && format!("{err:#}").contains("cold cache"),
+        "{err:#}"
+    );
+}
+
+#[test]
+fn test_panic_context() {
+    let mut inst = TestCase::new()
+        .wit("package test:panics; world panics { export name: func() -> string; }")
+        .script("export function name() { return 42; }")
+        .build()
+        .unwrap();
+    let (instance, store) = inst.parts();
+    let func = instance.get_func(&mut *store, "name").unwrap();
+    let mut results = [Val::Bool(false)];
+    func.call(&mut *store, &[], &mut results)
+        .expect_err("a number is not a string");
+
+    let stderr = String::from_utf8(inst.stderr_bytes()).unwrap();
+    assert!(stderr.contains("expected string"), "{stderr}");
+    assert!(stderr.contains("last export called: `name`"), "{stderr}");
+    assert!(!stderr.contains("import in progress"), "{stderr}");
+}
+
+#[test]
+fn test_memory_report() {
+    let mut inst = TestCase::new()
+        .wit("package test:mem; world mem { export grow: func() -> f64; }")
+        .script(
+            r#"
+            const kept = [];
+            export function grow() {
+                kept.push(new Array(1000).fill("x"));
+                const usage = __cqjs.getMemoryUsage();
+                return usage.peakMallocSize >= usage.mallocSize ? usage.objCount : -1;
+            }
+        "#,
+        )
+        .memory_report(2)
+        .build()
+        .unwrap();
+
+    match inst.call1("grow", &[]) {
+        Val::Float64(objects) => assert!(objects > 0.0, "{objects}"),
+        other => panic!("unexpected {other:?}"),
+    }
+    assert!(inst.stderr_bytes().is_empty());
+
+    inst.call1("grow", &[]);
+    inst.call1("grow", &[]);
+    let stderr = String::from_utf8(inst.stderr_bytes()).unwrap();
+    assert_eq!(stderr.matches("[memory]").count(), 1, "{stderr}");
+    assert!(
+        stderr.starts_with("[memory] after 2 calls: heap ") && stderr.contains(" objects, "),
+        "{stderr}"
+    );
+}
+
+#[test]
+fn test_trace_values() {
+    let mut inst = TestCase::new()
+        .wit(
+            r#"
+            package test:trace;
+            world trace {
+                export scale: func(items: list<u32>, factor: u32) -> list<u32>;
+            }
+        "#,
+        )
+        .script("export function scale(items, factor) { return items.map((n) => n * factor); }")
+        .trace_values()
+        .build()
+        .unwrap();
+    assert_eq!(
+        inst.call1("scale", &[Val::List(vec![Val::U32(2)]), Val::U32(3)]),
+        Val::List(vec![Val::U32(6)])
+    );
+
+    let stderr = String::from_utf8(inst.stderr_bytes()).unwrap();
+    for line in [
+        "[trace] scale: lift u32 -> number 2",
+        "[trace] scale: lift list -> array",
+        "[trace] scale: lift u32 -> number 3",
+        "[trace] scale: lower list <- array",
+        "[trace] scale: lower u32 <- number 6",
+    ] {
+        assert!(stderr.contains(line), "missing `{line}` in:\n{stderr}");
+    }
+}
+
+#[test]
+fn test_export_function_resolved_once() {
+    // Reassigning an exported `let` after the first call doesn't affect later
+    // calls from the host
+    TestCase::new()
+        .wit(
+            r#"
+            package test:resolve;
+            world resolve {
+                export next: func() -> u32;
+            }
+        "#,
+        )
+        .script(
+            r#"
+            let calls = 0;
+            export let next = () => {
+                calls += 1;
+                next = () => 100;
+                return calls;
+            };
         "#,
         )
-        .stub_wasi()
-        .expect_call("echo-s32", vec![Val::S32(-1)], Val::S32(-1))
-        .expect_call("echo-s32", vec![Val::S32(i32::MIN)], Val::S32(i32::MIN))
-        .expect_call("echo-s32", vec![Val::S32(i32::MAX)], Val::S32(i32::MAX))
-        .expect_call("echo-s64", vec![Val::S64(-1)], Val::S64(-1))
+        .expect_call("next", vec![], Val::U32(1))
+        .expect_call("next", vec![], Val::U32(2))
         .build()
         .unwrap()
         .run();
 }
 
 #[test]
-fn test_echo_lists_of_each_primitive() {
-    // list<bool>, list<u8>, list<s32>, list<f64> — matching compjs echo_lists_* tests
-    TestCase::new()
+fn test_memory_limit() {
+    let wit = r#"
+        package test:memory;
+        world memory {
+            export grow: func() -> u32;
+            export small: func() -> u32;
+        }
+    "#;
+    let script = r#"
+        export function grow() {
+            const chunks = [];
+            for (;;) chunks.push("x".repeat(1024 * 1024));
+        }
+        export function small() {
+            try {
+                grow();
+            } catch (e) {
+                return String(e).includes("out of memory") ? 1 : 0;
+            }
+            return 2;
+        }
+    "#;
+
+    // Running out of the budget throws a catchable error instead of growing
+    // linear memory; uncaught, it traps
+    let mut inst = TestCase::new()
+        .wit(wit)
+        .script(script)
+        .memory_limit(16 * 1024 * 1024)
+        .build()
+        .unwrap();
+    assert_eq!(inst.call1("small", &[]), Val::U32(1));
+    let (instance, store) = inst.parts();
+    let func = instance.get_func(&mut *store, "grow").unwrap();
+    let mut results = [Val::U32(0)];
+    func.call(&mut *store, &[], &mut results)
+        .expect_err("exceeding the memory limit traps");
+
+    let err = TestCase::new()
+        .wit(wit)
+        .script(script)
+        .memory_limit(1024)
+        .build()
+        .expect_err("the heap already exceeds the limit");
+    assert!(
+        format!("{err:#}").contains("the memory limit of 1024 bytes is below"),
+        "got {err:#}"
+    );
+}
+
+#[test]
+fn test_max_instructions() {
+    let mut inst = TestCase::new()
         .wit(
             r#"
-            package test:prim-lists;
-            world prim-lists {
-                export echo-bools: func(v: list<bool>) -> list<bool>;
-                export echo-u8s: func(v: list<u8>) -> list<u8>;
-                export echo-f64s: func(v: list<f64>) -> list<f64>;
+            package test:budget;
+            world budget {
+                export sum: func(n: u32) -> u32;
+                export spin: func();
             }
         "#,
         )
         .script(
             r#"
-            export function echoBools(v) { return v; }
-            export function echoU8s(v) { return v; }
-            export function echoF64s(v) { return v; }
+            export function sum(n) {
+                let total = 0;
+                for (let i = 0; i < n; i++) total += i;
+                return total;
+            }
+            export function spin() { for (;;) {} }
         "#,
         )
-        .stub_wasi()
-        .expect_call(
-            "echo-bools",
-            vec![Val::List(vec![
-                Val::Bool(true),
-                Val::Bool(false),
-                Val::Bool(true),
-            ])],
-            Val::List(vec![Val::Bool(true), Val::Bool(false), Val::Bool(true)]),
-        )
-        .expect_call(
-            "echo-u8s",
-            vec![Val::List(vec![Val::U8(0), Val::U8(127), Val::U8(255)])],
-            Val::List(vec![Val::U8(0), Val::U8(127), Val::U8(255)]),
-        )
-        .expect_call(
-            "echo-f64s",
-            vec![Val::List(vec![
-                Val::Float64(1.0),
-                Val::Float64(-0.5),
-                Val::Float64(f64::MAX),
-            ])],
-            Val::List(vec![
-                Val::Float64(1.0),
-                Val::Float64(-0.5),
-                Val::Float64(f64::MAX),
-            ]),
-        )
+        .max_instructions(1_000_000)
         .build()
-        .unwrap()
-        .run();
+        .unwrap();
+
+    // Every call gets a fresh budget
+    for _ in 0..3 {
+        assert_eq!(inst.call1("sum", &[Val::U32(1000)]), Val::U32(499500));
+    }
+
+    let (instance, store) = inst.parts();
+    let func = instance.get_func(&mut *store, "spin").unwrap();
+    func.call(&mut *store, &[], &mut [])
+        .expect_err("a runaway loop exhausts the budget");
 }
 
 #[test]
-fn test_exported_resource() {
-    let dir = tempfile::TempDir::new().unwrap();
-    let wit_path = dir.path().join("test.wit");
-    std::fs::write(
-        &wit_path,
-        r#"
-        package test:res;
+fn test_init_timeout() {
+    let case = |script: &str| {
+        TestCase::new()
+            .wit(
+                r#"
+                package test:timeout;
+                world timeout {
+                    export ready: func() -> bool;
+                }
+            "#,
+            )
+            .script(script)
+            .init_timeout(Duration::from_millis(200))
+    };
 
-        interface counter-api {
-            resource counter {
-                constructor(initial: u32);
-                increment: func();
-                get-value: func() -> u32;
+    let mut inst = case("export function ready() { return true; }")
+        .build()
+        .unwrap();
+    assert_eq!(inst.call1("ready", &[]), Val::Bool(true));
+
+    let err = case("for (;;) {} export function ready() { return true; }")
+        .build()
+        .err()
+        .expect("a top-level loop exceeds the init timeout");
+    assert!(
+        format!("{err:?}").contains("exceeded its time budget of 200 ms"),
+        "got {err:?}"
+    );
+}
+
+#[test]
+fn test_debug_runtime() {
+    let case = TestCase::new()
+        .wit(
+            r#"
+            package test:debug;
+            world debug {
+                export add: func(a: u32, b: u32) -> u32;
             }
-        }
+        "#,
+        )
+        .script("export function add(a, b) { return a + b; }")
+        .debug_runtime();
 
-        world resource-test {
-            export counter-api;
-        }
-    "#,
-    )
-    .unwrap();
+    if componentize_qjs::debug_runtime_wasm().is_none() {
+        let err = case
+            .build()
+            .err()
+            .expect("the debug runtime needs the debug-runtime feature");
+        assert!(
+            format!("{err:?}").contains("`debug-runtime` feature"),
+            "got {err:?}"
+        );
+        return;
+    }
 
-    let opts = componentize_qjs::ComponentizeOpts {
-        wit_path: &wit_path,
-        js_source: r#"
-            class Counter {
-                constructor(initial) { this.value = initial; }
-                increment() { this.value++; }
-                getValue() { return this.value; }
+    let mut inst = case.build().unwrap();
+    assert_eq!(inst.call1("add", &[Val::U32(2), Val::U32(3)]), Val::U32(5));
+}
+
+#[test]
+fn test_optimize() {
+    let case = || {
+        TestCase::new()
+            .wit(
+                r#"
+                package test:optimize;
+                world optimize {
+                    export add: func(a: u32, b: u32) -> u32;
+                }
+            "#,
+            )
+            .script("export function add(a, b) { return a + b; }")
+    };
+    let optimized = case().optimize(componentize_qjs::OptLevel::Size);
+
+    let wasm_opt = std::env::var_os("WASM_OPT").unwrap_or_else(|| "wasm-opt".into());
+    if std::process::Command::new(wasm_opt)
+        .arg("--version")
+        .output()
+        .is_err()
+    {
+        let err = optimized.build().err().expect("optimizing needs wasm-opt");
+        assert!(
+            format!("{err:?}").contains("install binaryen"),
+            "got {err:?}"
+        );
+        return;
+    }
+
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+    let plain = rt.block_on(case().componentize()).unwrap();
+    let small = rt.block_on(optimized.componentize()).unwrap();
+    assert!(
+        small.len() < plain.len(),
+        "{} >= {}",
+        small.len(),
+        plain.len()
+    );
+
+    let mut inst = optimized.build().unwrap();
+    assert_eq!(inst.call1("add", &[Val::U32(2), Val::U32(3)]), Val::U32(5));
+}
+
+#[test]
+fn test_core_module() {
+    let builder = || {
+        ComponentizeBuilder::new(
+            Path::new("core.wit"),
+            "export function add(a, b) { return a + b; }",
+        )
+        .wit_source(
+            r#"
+            package test:core;
+            world core {
+                export add: func(a: u32, b: u32) -> u32;
             }
-            export const counterApi = { Counter };
         "#,
-        js_path: None,
-        module_root: None,
-        world_name: None,
-        stub_wasi: true,
-        disable_gc: false,
-        runtime: componentize_qjs::Runtime::Default,
+        )
+        .runtime(componentize_qjs::Runtime::DefaultSync)
+        .core_module(true)
     };
 
     let rt = tokio::runtime::Builder::new_current_thread()
         .enable_all()
         .build()
         .unwrap();
+    let wasm_merge = std::env::var_os("WASM_MERGE").unwrap_or_else(|| "wasm-merge".into());
+    if std::process::Command::new(wasm_merge)
+        .arg("--version")
+        .output()
+        .is_err()
+    {
+        let err = rt
+            .block_on(builder().componentize())
+            .expect_err("core modules need wasm-merge");
+        assert!(
+            format!("{err:?}").contains("install binaryen"),
+            "got {err:?}"
+        );
+        return;
+    }
 
-    let wasm = rt.block_on(componentize_qjs::componentize(&opts)).unwrap();
+    let engine = wasmtime::Engine::default();
+    let wasm = rt.block_on(builder().componentize()).unwrap();
+    assert!(wasm.starts_with(b"\0asm\x01\0\0\0"));
+    let module = wasmtime::Module::new(&engine, &wasm).unwrap();
+    assert!(module.exports().any(|export| export.name() == "memory"));
+    for import in module.imports() {
+        assert!(
+            import.module() == "wasi_snapshot_preview1" || import.module().starts_with("wasi:"),
+            "{}#{}",
+            import.module(),
+            import.name()
+        );
+    }
 
-    // Component builds successfully with resource types
-    let engine = common::engine();
-    let component = wasmtime::component::Component::new(engine, &wasm).unwrap();
+    let wasm = rt
+        .block_on(builder().stub_wasi(true).componentize())
+        .unwrap();
+    let module = wasmtime::Module::new(&engine, &wasm).unwrap();
+    assert_eq!(module.imports().len(), 0);
+    let mut store = wasmtime::Store::new(&engine, ());
+    let instance = wasmtime::Instance::new(&mut store, &module, &[]).unwrap();
+    let add = instance
+        .get_typed_func::<(i32, i32), i32>(&mut store, "add")
+        .unwrap();
+    assert_eq!(add.call(&mut store, (2, 3)).unwrap(), 5);
+}
 
-    // Instantiate and call resource methods through the interface
-    let mut wasi_builder = wasmtime_wasi::WasiCtxBuilder::new();
-    let wasi = wasi_builder.build();
-    let table = wasmtime::component::ResourceTable::new();
-    let mut store = wasmtime::Store::new(engine, common::WasiCtxState { wasi, table });
+#[cfg(feature = "component-model-async")]
+#[test]
+fn test_core_module_needs_sync_runtime() {
+    let builder = ComponentizeBuilder::new(Path::new("core.wit"), "export const run = () => {};")
+        .wit_source("package test:core; world core { export run: func(); }")
+        .core_module(true);
+    let err = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap()
+        .block_on(builder.componentize())
+        .expect_err("the async runtime can't be flattened");
+    assert!(err.to_string().contains("non-async runtime"), "got {err}");
+}
 
-    let mut linker = wasmtime::component::Linker::new(engine);
-    wasmtime_wasi::p2::add_to_linker_sync(&mut linker).unwrap();
-    let instance = linker.instantiate(&mut store, &component).unwrap();
+#[test]
+fn test_reproducible() {
+    let case = || {
+        TestCase::new()
+            .wit(
+                r#"
+                package test:reproducible;
+                world reproducible {
+                    export started: func() -> f64;
+                    export seeded: func() -> f64;
+                }
+            "#,
+            )
+            .script(
+                r#"
+                const start = Date.now();
+                const random = Math.random();
+                export function started() { return start; }
+                export function seeded() { return random; }
+            "#,
+            )
+            .reproducible()
+    };
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
 
-    // Navigate into the exported interface
-    let iface_idx = instance
-        .get_export_index(&mut store, None, "test:res/counter-api")
-        .expect("interface export not found");
+    let first = rt.block_on(case().componentize()).unwrap();
+    let second = rt.block_on(case().componentize()).unwrap();
+    assert!(first == second, "reproducible builds differ");
 
-    // Get constructor
-    let ctor_idx = instance
-        .get_export_index(&mut store, Some(&iface_idx), "[constructor]counter")
-        .expect("[constructor]counter not found");
-    let ctor = instance.get_func(&mut store, ctor_idx).unwrap();
+    let mut inst = case().build().unwrap();
+    let Val::Float64(start) = inst.call1("started", &[]) else {
+        panic!("expected an f64");
+    };
+    assert!(start < 1_000.0, "initialization started at {start}");
+    let seeded = inst.call1("seeded", &[]);
+    assert_eq!(case().build().unwrap().call1("seeded", &[]), seeded);
+}
 
-    // Call constructor(42)
-    let mut results = [Val::Bool(false)];
-    ctor.call(&mut store, &[Val::U32(42)], &mut results)
+#[test]
+fn test_strip_source() {
+    let case = || {
+        TestCase::new()
+            .wit(
+                r#"
+                package test:strip;
+                world strip {
+                    export source: func() -> string;
+                }
+            "#,
+            )
+            .script(
+                r#"
+                function answer() { return 42; }
+                export function source() { return String(answer); }
+            "#,
+            )
+    };
+
+    let mut inst = case().build().unwrap();
+    let Val::String(source) = inst.call1("source", &[]) else {
+        panic!("expected a string");
+    };
+    assert!(source.contains("return 42"), "got {source}");
+
+    let mut inst = case().strip_source().build().unwrap();
+    let Val::String(source) = inst.call1("source", &[]) else {
+        panic!("expected a string");
+    };
+    assert!(!source.contains("return 42"), "got {source}");
+}
+
+#[test]
+fn test_bytecode() {
+    let wit = "package test:bytecode; world bytecode { export source: func() -> string; }";
+    let case = || {
+        TestCase::new()
+            .wit(wit)
+            .module(
+                "src/main.js",
+                r#"
+                import { base } from "./base.js";
+                function answer() { return base * 2; }
+                export function source() { return `${answer()} ${answer}`; }
+            "#,
+            )
+            .module("src/base.js", "export const base = 21;")
+            .entry_module("src/main.js")
+            .bytecode()
+    };
+
+    let mut inst = case().build().unwrap();
+    let Val::String(source) = inst.call1("source", &[]) else {
+        panic!("expected a string");
+    };
+    assert!(source.starts_with("42 "), "got {source}");
+    assert!(source.contains("base * 2"), "got {source}");
+
+    let mut inst = case().strip_source().build().unwrap();
+    let Val::String(source) = inst.call1("source", &[]) else {
+        panic!("expected a string");
+    };
+    assert!(source.starts_with("42 "), "got {source}");
+    assert!(!source.contains("base * 2"), "got {source}");
+
+    // Modules are only loadable during Wizer, so a lazy module stands alone.
+    let mut inst = TestCase::new()
+        .wit(wit)
+        .script("export function source() { return String(21 * 2); }")
+        .bytecode()
+        .lazy_eval()
+        .build()
+        .unwrap();
+    assert_eq!(inst.call1("source", &[]), Val::String("42".into()));
+
+    let err = TestCase::new()
+        .wit(wit)
+        .script("export function source( {")
+        .bytecode()
+        .build()
+        .err()
+        .unwrap();
+    assert!(
+        format!("{err:#}").contains("failed to compile JavaScript to QuickJS bytecode"),
+        "{err:#}"
+    );
+}
+
+#[test]
+fn test_gc_interval() {
+    let case = || {
+        TestCase::new()
+            .wit(
+                r#"
+                package test:gc;
+                world gc {
+                    export leak: func();
+                    export collected: func() -> u32;
+                }
+            "#,
+            )
+            .script(
+                r#"
+                let collected = 0;
+                const registry = new FinalizationRegistry(() => { collected += 1; });
+                export function leak() {
+                    const a = {};
+                    const b = { a };
+                    a.b = b;
+                    registry.register(a, "cycle");
+                }
+                export function collected() { return collected; }
+            "#,
+            )
+    };
+
+    // Without an interval nothing collects the cycle between calls
+    let mut inst = case().build().unwrap();
+    inst.call("leak", &[], 0);
+    assert_eq!(inst.call1("collected", &[]), Val::U32(0));
+
+    let mut inst = case().gc_interval(1).build().unwrap();
+    inst.call("leak", &[], 0);
+    assert_eq!(inst.call1("collected", &[]), Val::U32(1));
+}
+
+#[test]
+fn test_in_memory_modules() {
+    let mut inst = TestCase::new()
+        .wit(
+            r#"
+            package test:modules;
+            world modules {
+                export answer: func() -> u32;
+            }
+        "#,
+        )
+        .script(
+            r#"
+            import { double } from "./lib/math.js";
+            import { base } from "config";
+            export function answer() { return double(base); }
+        "#,
+        )
+        .module(
+            "lib/math.js",
+            r#"import { one } from "../one"; export const double = (n) => n * 2 + one - 1;"#,
+        )
+        .module("one.mjs", "export const one = 1;")
+        .module("config.js", "export const base = 21;")
+        .build()
+        .unwrap();
+    assert_eq!(inst.call1("answer", &[]), Val::U32(42));
+
+    let err = TestCase::new()
+        .wit("package test:modules; world modules { export answer: func() -> u32; }")
+        .script(r#"import { x } from "./missing.js"; export function answer() { return x; }"#)
+        .module("present.js", "export const x = 1;")
+        .build()
+        .err()
+        .unwrap();
+    assert!(format!("{err:#}").contains("module"), "{err:#}");
+
+    let err = TestCase::new()
+        .wit("package test:modules; world modules { export answer: func() -> u32; }")
+        .script("export function answer() { return 0; }")
+        .module("../outside.js", "")
+        .build()
+        .err()
         .unwrap();
-    let counter = results[0].clone();
+    assert!(
+        format!("{err:#}").contains("invalid JavaScript module name"),
+        "{err:#}"
+    );
+}
 
-    // Get get-value method
-    let get_val_idx = instance
-        .get_export_index(&mut store, Some(&iface_idx), "[method]counter.get-value")
-        .expect("[method]counter.get-value not found");
-    let get_val = instance.get_func(&mut store, get_val_idx).unwrap();
+#[test]
+fn test_plan_estimated_size_counts_modules_and_preludes() {
+    let wit = "package test:modules; world modules { export answer: func() -> u32; }";
+    let script = r#"import { x } from "./lib.js"; export function answer() { return x; }"#;
+    let lib = format!("export const x = 42;\n{}", "// filler\n".repeat(100));
+    let modules = [("lib.js".to_string(), lib)];
+    let builder = ComponentizeBuilder::new(Path::new("modules.wit"), script).wit_source(wit);
+    let without = builder.plan().unwrap().estimated_size;
+    let builder = builder.modules(&modules);
+    let with = builder.plan().unwrap().estimated_size;
+    assert_eq!(with - without, modules[0].1.len());
 
-    // Call get-value(counter) => 42
-    let mut results = [Val::Bool(false)];
-    get_val
-        .call(&mut store, std::slice::from_ref(&counter), &mut results)
-        .unwrap();
-    assert_eq!(results[0], Val::U32(42), "initial value should be 42");
+    let preludes = [("setup.js".to_string(), "globalThis.y = 1;".to_string())];
+    let with_prelude = builder.preludes(&preludes).plan().unwrap().estimated_size;
+    assert_eq!(with_prelude - with, preludes[0].1.len());
+}
 
-    // Get increment method
-    let inc_idx = instance
-        .get_export_index(&mut store, Some(&iface_idx), "[method]counter.increment")
-        .expect("[method]counter.increment not found");
-    let inc = instance.get_func(&mut store, inc_idx).unwrap();
+#[test]
+fn test_in_memory_entry_module() {
+    let wit = "package test:modules; world modules { export answer: func() -> u32; }";
+    let mut inst = TestCase::new()
+        .wit(wit)
+        .module(
+            "src/main.js",
+            r#"import { base } from "./base.js"; export function answer() { return base * 2; }"#,
+        )
+        .module("src/base.js", "export const base = 21;")
+        .entry_module("src/main")
+        .build()
+        .unwrap();
+    assert_eq!(inst.call1("answer", &[]), Val::U32(42));
 
-    // Call increment(counter)
-    inc.call(&mut store, std::slice::from_ref(&counter), &mut [])
+    let err = TestCase::new()
+        .wit(wit)
+        .module("src/main.js", "export function answer() { return 0; }")
+        .entry_module("main.js")
+        .build()
+        .err()
         .unwrap();
+    assert!(
+        format!("{err:#}").contains("entry module \"main.js\" is not one of the modules"),
+        "{err:#}"
+    );
 
-    // Verify value is now 43
-    let mut results = [Val::Bool(false)];
-    get_val
-        .call(&mut store, std::slice::from_ref(&counter), &mut results)
+    let err = TestCase::new()
+        .wit(wit)
+        .script("export function answer() { return 0; }")
+        .module("main.js", "export function answer() { return 1; }")
+        .entry_module("main.js")
+        .build()
+        .err()
         .unwrap();
+    assert!(
+        format!("{err:#}").contains("js_source must be empty"),
+        "{err:#}"
+    );
+}
 
+#[test]
+fn test_preludes() {
+    let wit = "package test:prelude; world prelude { export greet: func(name: string) -> string; }";
+    let mut inst = TestCase::new()
+        .wit(wit)
+        .prelude("greeting.js", r#"var greeting = "hello";"#)
+        .prelude(
+            "format.js",
+            "globalThis.format = (name) => `${greeting} ${name} (${typeof wit})`;",
+        )
+        .script("export function greet(name) { return format(name); }")
+        .build()
+        .unwrap();
     assert_eq!(
-        results[0],
-        Val::U32(43),
-        "value should be 43 after increment"
+        inst.call1("greet", &[Val::String("world".into())]),
+        Val::String("hello world (object)".into())
+    );
+
+    let err = TestCase::new()
+        .wit(wit)
+        .prelude("broken.js", r#"throw new Error("boom");"#)
+        .script("export function greet(name) { return name; }")
+        .build()
+        .err()
+        .unwrap();
+    assert!(
+        format!("{err:#}").contains("Failed to evaluate prelude `broken.js`"),
+        "{err:#}"
     );
 }
 
 #[test]
-fn test_static_resource_method_in_interface() {
-    let dir = tempfile::TempDir::new().unwrap();
-    let wit_path = dir.path().join("test.wit");
-    std::fs::write(
-        &wit_path,
-        r#"
-        package test:static-bug;
+fn test_lazy_eval_defers_top_level_errors() {
+    let case = |script: &str| {
+        TestCase::new()
+            .wit("package test:lazy; world lazy { export answer: func() -> u32; }")
+            .script(script)
+            .lazy_eval()
+            .build()
+    };
 
-        interface widget-api {
-            resource widget {
-                constructor(name: string);
-                get-name: func() -> string;
-                create-default: static func() -> widget;
-            }
-        }
+    // Syntax errors are still reported at build time
+    let err = case("export function answer( {").err().unwrap();
+    assert!(format!("{err:#}").contains("compile"), "{err:#}");
 
-        world static-test {
-            export widget-api;
-        }
-        "#,
-    )
-    .unwrap();
+    let mut inst = case(r#"throw new Error("boom"); export function answer() { return 1; }"#)
+        .expect("top-level code must not run during the build");
+    let (instance, store) = inst.parts();
+    let func = instance.get_func(&mut *store, "answer").unwrap();
+    let mut results = [Val::U32(0)];
+    func.call(&mut *store, &[], &mut results)
+        .expect_err("the top-level throw surfaces on the first call");
+}
 
-    let opts = componentize_qjs::ComponentizeOpts {
-        wit_path: &wit_path,
-        js_source: r#"
-            class Widget {
-                constructor(name) { this.name = name; }
-                getName() { return this.name; }
-                static createDefault() { return new Widget("default"); }
+#[test]
+fn test_missing_exports_fail_the_build() {
+    let err = TestCase::new()
+        .wit(
+            r#"
+            package test:missing;
+            interface api {
+                resource counter {
+                    constructor(start: u32);
+                    zero: static func() -> counter;
+                }
+                reset: func();
+            }
+            world missing {
+                export api;
+                export add: func(a: u32, b: u32) -> u32;
+                export sub: func(a: u32, b: u32) -> u32;
             }
-            export const widgetApi = { Widget };
         "#,
-        js_path: None,
-        module_root: None,
-        world_name: None,
-        stub_wasi: true,
-        disable_gc: false,
-        runtime: componentize_qjs::Runtime::Default,
-    };
-
-    let rt = tokio::runtime::Builder::new_current_thread()
-        .enable_all()
+        )
+        .script(
+            r#"
+            export function add(a, b) { return a + b; }
+            export const api = { Counter: class {} };
+        "#,
+        )
         .build()
+        .err()
         .unwrap();
+    let msg = format!("{err:#}");
+    assert!(
+        msg.contains("does not implement these WIT exports"),
+        "{msg}"
+    );
+    for missing in ["`sub`", "`test:missing/api#reset`", "[static]counter.zero`"] {
+        assert!(msg.contains(missing), "{missing} not reported: {msg}");
+    }
+    assert!(!msg.contains("`add`"), "{msg}");
+    assert!(!msg.contains("[constructor]counter"), "{msg}");
+}
 
-    let wasm = rt.block_on(componentize_qjs::componentize(&opts)).unwrap();
-
-    let engine = common::engine();
-    let component = wasmtime::component::Component::new(engine, &wasm).unwrap();
-
-    let mut wasi_builder = wasmtime_wasi::WasiCtxBuilder::new();
-    let wasi = wasi_builder.build();
-    let table = wasmtime::component::ResourceTable::new();
-    let mut store = wasmtime::Store::new(engine, common::WasiCtxState { wasi, table });
+#[test]
+fn test_renames_of_interface_exports() {
+    let case = || {
+        TestCase::new()
+            .wit(
+                r#"
+                package test:renames;
+                interface api {
+                    handle: func(x: u32) -> u32;
+                }
+                world renames {
+                    export api;
+                    export add-one: func(x: u32) -> u32;
+                }
+            "#,
+            )
+            .script(
+                r#"
+                export function increment(x) { return x + 1; }
+                export const api = { onRequest(x) { return x * 2; } };
+            "#,
+            )
+            .rename("add-one", "increment")
+            .rename("test:renames/api#handle", "onRequest")
+    };
 
-    let mut linker = wasmtime::component::Linker::new(engine);
-    wasmtime_wasi::p2::add_to_linker_sync(&mut linker).unwrap();
-    let instance = linker.instantiate(&mut store, &component).unwrap();
+    let mut inst = case()
+        .expect_call("add-one", vec![Val::U32(1)], Val::U32(2))
+        .build()
+        .unwrap();
+    inst.run();
 
+    let (instance, store) = inst.parts();
     let iface_idx = instance
-        .get_export_index(&mut store, None, "test:static-bug/widget-api")
+        .get_export_index(&mut *store, None, "test:renames/api")
         .expect("interface export not found");
-
-    // Call the static method — this should work but panics because
-    // the runtime looks for "createDefault" in globals instead of the
-    // interface object.
-    let static_idx = instance
-        .get_export_index(
-            &mut store,
-            Some(&iface_idx),
-            "[static]widget.create-default",
-        )
-        .expect("[static]widget.create-default not found");
-    let static_fn = instance.get_func(&mut store, static_idx).unwrap();
-
+    let func_idx = instance
+        .get_export_index(&mut *store, Some(&iface_idx), "handle")
+        .expect("handle export not found");
+    let func = instance.get_func(&mut *store, func_idx).unwrap();
     let mut results = [Val::Bool(false)];
-    static_fn.call(&mut store, &[], &mut results).unwrap();
-
-    // If we got here, we have a resource handle. Verify it works.
-    let get_name_idx = instance
-        .get_export_index(&mut store, Some(&iface_idx), "[method]widget.get-name")
-        .expect("[method]widget.get-name not found");
-    let get_name = instance.get_func(&mut store, get_name_idx).unwrap();
+    func.call(&mut *store, &[Val::U32(4)], &mut results)
+        .unwrap();
+    assert_eq!(results[0], Val::U32(8));
 
-    let mut name_results = [Val::Bool(false)];
-    get_name
-        .call(&mut store, &results, &mut name_results)
+    let err = case()
+        .rename("test:other/api#handle", "other")
+        .build()
+        .err()
         .unwrap();
-    assert_eq!(
-        name_results[0],
-        Val::String("default".into()),
-        "static factory should produce widget with name 'default'"
+    assert!(
+        format!("{err:#}").contains("cannot rename `test:other/api#handle`"),
+        "{err:#}"
     );
 }
 
-#[tokio::test]
-async fn test_async_export_rejection_propagates() {
-    let mut instance = TestCase::new()
+#[test]
+fn test_world_introspection() {
+    let mut inst = TestCase::new()
         .wit(
             r#"
-            package test:async-reject;
-            world async-reject {
-                export will-throw: async func();
+            package test:intro@1.2.0;
+            interface api {
+                variant shape { circle(f64), point }
+                area: func(s: shape) -> f64;
             }
-            "#,
+            world intro {
+                enum color { red, dark-green }
+                flags perms { read, write }
+                export api;
+                export describe: func(names: list<string>, limit: option<u32>) -> result<string, color>;
+            }
+        "#,
         )
         .script(
             r#"
-            export async function willThrow() {
-                throw new Error("this should not be silently swallowed");
+            export const api = { area: () => 0 };
+            export function describe() {
+                const world = wit.world;
+                if (!Object.isFrozen(wit) || !Object.isFrozen(world.exports[0])) {
+                    throw new Error("wit.world should be read-only");
+                }
+                const { exports, interfaces, types } = world;
+                return JSON.stringify({
+                    name: world.name,
+                    exports: exports.map((f) => [f.interface, f.name, f.params.map((p) => p.type), f.result]),
+                    api: interfaces["test:intro/api@1.2.0"],
+                    types,
+                });
             }
-            "#,
+        "#,
         )
-        .build_async()
-        .await
+        .build()
         .unwrap();
 
-    // The host calls a void async export that throws. The rejection should
-    // propagate as an error, not be silently swallowed.
-    let result = instance.call_async("will-throw", &[], 0).await;
-    assert!(
-        result.is_err(),
-        "async export that throws should return an error, not Ok"
+    let Val::Result(Ok(Some(json))) =
+        inst.call1("describe", &[Val::List(vec![]), Val::Option(None)])
+    else {
+        panic!("Expected ok result");
+    };
+    let Val::String(json) = *json else {
+        panic!("Expected string");
+    };
+    let expected = concat!(
+        r#"{"name":"intro","exports":["#,
+        r#"["test:intro/api@1.2.0","area",["shape"],"f64"],"#,
+        r#"[null,"describe",["list<string>","option<u32>"],"result<string, color>"]],"#,
+        r#""api":{"name":"api","package":"test:intro","version":"1.2.0","direction":"export","#,
+        r#""types":{"shape":{"kind":"variant","cases":[{"name":"circle","type":"f64"},{"name":"point","type":null}]}}},"#,
+        r#""types":{"color":{"kind":"enum","cases":["red","dark-green"]},"perms":{"kind":"flags","flags":["read","write"]}}}"#,
     );
+    assert_eq!(json, expected);
 }
 
 #[test]
-fn test_root_level_flags() {
-    let result = TestCase::new()
+fn test_datetime_helpers() {
+    let mut inst = TestCase::new()
         .wit(
             r#"
-            package test:root-flags;
-            world root-flags {
-                flags permissions {
-                    read,
-                    write,
-                    execute,
-                }
-                export check: func(p: permissions) -> string;
+            package test:datetime;
+            world datetime {
+                record instant { seconds: u64, nanoseconds: u32 }
+                export shift: func(t: instant, ms: f64) -> instant;
             }
-            "#,
+        "#,
         )
         .script(
             r#"
-            export function check(p) {
-                const parts = [];
-                if (p.read) parts.push("read");
-                if (p.write) parts.push("write");
-                if (p.execute) parts.push("execute");
-                return parts.join(",");
+            export function shift(t, ms) {
+                return wit.fromDate(wit.toDate(t).getTime() + ms);
             }
-            "#,
+        "#,
         )
-        .stub_wasi()
-        .build();
+        .build()
+        .unwrap();
 
-    let mut instance = result.expect(
-        "componentization should succeed for world-level flags — \
-         world-level flags must round-trip as { name: boolean } objects",
+    let instant = |seconds, nanoseconds| {
+        Val::Record(vec![
+            ("seconds".into(), Val::U64(seconds)),
+            ("nanoseconds".into(), Val::U32(nanoseconds)),
+        ])
+    };
+    assert_eq!(
+        inst.call1(
+            "shift",
+            &[instant(1_700_000_000, 250_999_999), Val::Float64(1_800.0)]
+        ),
+        instant(1_700_000_002, 50_000_000)
     );
+}
 
-    let flags_val = Val::Flags(vec!["read".into(), "execute".into()]);
-    let ret = instance.call1("check", &[flags_val]);
-    assert_eq!(
-        ret,
-        Val::String("read,execute".into()),
-        "root-level flags should round-trip through the component"
+#[test]
+fn test_exported_interface_short_name_collision() {
+    let wit = r#"
+        package test:first;
+        interface api { get: func() -> u32; }
+        package test:second { interface api { get: func() -> u32; } }
+        world collide {
+            export api;
+            export test:second/api;
+        }
+    "#;
+
+    let err = TestCase::new()
+        .wit(wit)
+        .script("export const api = { get() { return 1; } };")
+        .build()
+        .err()
+        .unwrap();
+    assert!(
+        format!("{err:#}").contains(
+            "`test:first/api`, `test:second/api` all fall back to the module export `api`"
+        ),
+        "{err:#}"
     );
+
+    let mut inst = TestCase::new()
+        .wit(wit)
+        .script(
+            r#"
+            export const api = { get() { return 1; } };
+            const second = { get() { return 2; } };
+            export { second as "test:second/api" };
+        "#,
+        )
+        .build()
+        .unwrap();
+    let (instance, store) = inst.parts();
+    for (iface, expected) in [("test:first/api", 1), ("test:second/api", 2)] {
+        let iface_idx = instance
+            .get_export_index(&mut *store, None, iface)
+            .unwrap_or_else(|| panic!("{iface} not found"));
+        let get_idx = instance
+            .get_export_index(&mut *store, Some(&iface_idx), "get")
+            .unwrap();
+        let get = instance.get_func(&mut *store, get_idx).unwrap();
+        let mut results = [Val::Bool(false)];
+        get.call(&mut *store, &[], &mut results).unwrap();
+        assert_eq!(results[0], Val::U32(expected), "{iface}");
+    }
+}
+
+#[test]
+fn test_componentizer_reuses_setup_across_builds() {
+    let wit = r#"
+        package test:reuse;
+        world reuse {
+            export answer: func() -> u32;
+        }
+    "#;
+    let componentizer = Componentizer::new().unwrap();
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+
+    // The second build hits the cache and must still snapshot its own module.
+    for answer in [1, 2] {
+        let script = format!("export function answer() {{ return {answer}; }}");
+        let opts = ComponentizeBuilder::new(Path::new("reuse.wit"), &script)
+            .wit_source(wit)
+            .build();
+        let wasm = rt.block_on(componentizer.componentize(&opts)).unwrap();
+        let mut inst = ComponentInstance::from_wasm(wasm, vec![], vec![]).unwrap();
+        assert_eq!(inst.call1("answer", &[]), Val::U32(answer));
+    }
 }